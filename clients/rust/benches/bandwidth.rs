@@ -0,0 +1,168 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bytes read off the wire per `get_last` call, with and without frame
+//! compression negotiated (`with_compression(Codec::Zstd)`). Turns carry
+//! msgpack conversation items of generated prose, so they compress about
+//! as well as real transcripts.
+//!
+//! Values are response frame bytes (header and payload) as reported to
+//! [`Metrics::on_bytes`], so wall time is not measured here; the
+//! `round_trip` bench covers that.
+//!
+//! ```bash
+//! cargo bench -p cxdb --bench bandwidth
+//! ```
+
+mod support;
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cxdb::metrics::{Direction, Metrics};
+use cxdb::{
+    dial, encode_msgpack, with_compression, with_metrics, AppendRequest, Codec, ContextId,
+    GetLastOptions, RequestContext,
+};
+
+use support::MockServer;
+
+/// Turns fetched per GET_LAST request.
+const TURNS: u32 = 32;
+const PAYLOAD_SIZES: [(&str, usize); 3] = [("256B", 256), ("4KB", 4 * 1024), ("64KB", 64 * 1024)];
+
+/// Vocabulary the generated prose draws from.
+const WORDS: &str =
+    "the context turn assistant returns a summary of tool output with file paths and errors user";
+
+static RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Counts response bytes for [`WireBytes`].
+struct ReceivedBytes;
+
+impl Metrics for ReceivedBytes {
+    fn on_bytes(&self, direction: Direction, bytes: usize) {
+        if let Direction::Received = direction {
+            RECEIVED.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Criterion measurement counting bytes received instead of wall time.
+struct WireBytes;
+
+impl Measurement for WireBytes {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        RECEIVED.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: u64) -> u64 {
+        RECEIVED.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        a + b
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &ByteFormatter
+    }
+}
+
+struct ByteFormatter;
+
+impl ValueFormatter for ByteFormatter {
+    fn scale_values(&self, _typical: f64, _values: &mut [f64]) -> &'static str {
+        "bytes"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        _throughput: &Throughput,
+        _values: &mut [f64],
+    ) -> &'static str {
+        "bytes"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "bytes"
+    }
+}
+
+/// A msgpack conversation item of about `size` bytes of pseudo-random prose.
+fn conversation_item(seed: u64, size: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    let words: Vec<&str> = WORDS.split(' ').collect();
+    let mut text = String::with_capacity(size);
+    while text.len() < size {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        text.push_str(words[(state % words.len() as u64) as usize]);
+        text.push(' ');
+    }
+    let item = BTreeMap::from([("role", "assistant".to_string()), ("content", text)]);
+    encode_msgpack(&item).unwrap()
+}
+
+fn get_last_bytes(c: &mut Criterion<WireBytes>) {
+    let server = MockServer::start();
+    let ctx = RequestContext::background();
+    let metrics: Arc<dyn Metrics> = Arc::new(ReceivedBytes);
+    let plain = dial(server.addr(), [with_metrics(metrics.clone())]).unwrap();
+    let compressed = dial(
+        server.addr(),
+        [with_metrics(metrics), with_compression(Codec::Zstd)],
+    )
+    .unwrap();
+    assert_eq!(compressed.negotiated_compression(), Some(Codec::Zstd));
+
+    let mut group = c.benchmark_group("get_last_bytes");
+    for (context_id, (name, size)) in (1..).map(ContextId::new).zip(PAYLOAD_SIZES) {
+        for seed in 0..TURNS as u64 {
+            let payload = conversation_item(context_id.get() << 32 | seed, size);
+            let req = AppendRequest::new(context_id, "cxdb.ConversationItem", 1, payload);
+            plain.append_turn(&ctx, &req).unwrap();
+        }
+        let opts = GetLastOptions {
+            limit: TURNS,
+            include_payload: true,
+            ..Default::default()
+        };
+        for (label, client) in [("plain", &plain), ("zstd", &compressed)] {
+            group.bench_with_input(BenchmarkId::new(label, name), &opts, |b, opts| {
+                b.iter(|| black_box(client.get_last(&ctx, context_id, opts.clone()).unwrap()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn config() -> Criterion<WireBytes> {
+    Criterion::default()
+        .with_measurement(WireBytes)
+        .sample_size(10)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = get_last_bytes
+}
+criterion_main!(benches);
@@ -0,0 +1,125 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client-side encode/decode costs that do not touch a socket.
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cxdb::protocol::{decode_frame, encode_frame_into, MAX_FRAME_SIZE, MSG_APPEND_TURN};
+use cxdb::{decode_msgpack, decode_msgpack_into, encode_msgpack};
+use serde::{Deserialize, Serialize};
+
+const PAYLOAD_SIZES: [(&str, usize); 3] = [("256B", 256), ("4KB", 4 * 1024), ("256KB", 256 * 1024)];
+
+/// Numeric-tagged struct in the shape CXDB type registries describe.
+#[derive(Serialize, Deserialize)]
+struct Message {
+    #[serde(rename = "1")]
+    role: String,
+    #[serde(rename = "2")]
+    text: String,
+    #[serde(rename = "3")]
+    timestamp: u64,
+    #[serde(rename = "4")]
+    tags: Vec<String>,
+    #[serde(rename = "5", with = "serde_bytes")]
+    attachment: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Transcript {
+    #[serde(rename = "1")]
+    agent: String,
+    #[serde(rename = "2")]
+    messages: Vec<Message>,
+}
+
+fn message(i: u64, text_len: usize) -> Message {
+    Message {
+        role: if i.is_multiple_of(2) {
+            "user"
+        } else {
+            "assistant"
+        }
+        .into(),
+        text: "x".repeat(text_len),
+        timestamp: 1_700_000_000_000 + i,
+        tags: vec!["bench".into(), format!("turn-{i}")],
+        attachment: vec![i as u8; text_len / 4],
+    }
+}
+
+fn small_item() -> Transcript {
+    Transcript {
+        agent: "bench-agent".into(),
+        messages: vec![message(0, 32)],
+    }
+}
+
+fn large_item() -> Transcript {
+    Transcript {
+        agent: "bench-agent".into(),
+        messages: (0..256).map(|i| message(i, 512)).collect(),
+    }
+}
+
+fn msgpack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("msgpack");
+    for (name, item) in [("small", small_item()), ("large", large_item())] {
+        let encoded = encode_msgpack(&item).unwrap();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::new("encode", name), &item, |b, item| {
+            b.iter(|| encode_msgpack(black_box(item)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, data| {
+            b.iter(|| decode_msgpack(black_box(data)).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decode_into", name),
+            &encoded,
+            |b, data| b.iter(|| decode_msgpack_into::<Transcript>(black_box(data)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    for (name, size) in PAYLOAD_SIZES {
+        let payload = vec![0xA5u8; size];
+        let mut encoded = Vec::new();
+        encode_frame_into(&mut encoded, MSG_APPEND_TURN, 0, 1, &payload);
+
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::new("encode", name), &payload, |b, payload| {
+            let mut buf = Vec::with_capacity(encoded.len());
+            b.iter(|| {
+                buf.clear();
+                encode_frame_into(&mut buf, MSG_APPEND_TURN, 0, 1, black_box(payload));
+                black_box(buf.len())
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, data| {
+            b.iter(|| decode_frame(black_box(data), MAX_FRAME_SIZE).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn config() -> Criterion {
+    // Fixed sample counts and a noise floor keep run-to-run variance low
+    // enough to compare saved baselines (`--save-baseline`/`--baseline`).
+    Criterion::default()
+        .sample_size(100)
+        .measurement_time(Duration::from_secs(3))
+        .noise_threshold(0.03)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = msgpack, frames
+}
+criterion_main!(benches);
@@ -0,0 +1,218 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Allocations per `get_last` call: owned `Vec<u8>` payloads, records
+//! refilled in place by `get_last_into`, and `bytes::Bytes` slices of a
+//! (recycled) response buffer. `listing_allocations` lists 10k turns of
+//! three types without payloads, where interned type ids leave about one
+//! allocation per call plus the result Vec.
+//!
+//! Values are heap allocations made by the calling thread, so the mock
+//! server's own allocations are excluded.
+//!
+//! ```bash
+//! cargo bench -p cxdb --features bytes --bench payloads
+//! ```
+
+mod support;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::hint::black_box;
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cxdb::{dial, AppendRequest, ContextId, GetLastOptions, RequestContext};
+
+use support::MockServer;
+
+const TURNS: u32 = 64;
+const LISTING_TURNS: u32 = 10_000;
+const PAYLOAD_SIZES: [(&str, usize); 3] = [("256B", 256), ("4KB", 4 * 1024), ("256KB", 256 * 1024)];
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations() -> u64 {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// Criterion measurement counting heap allocations instead of wall time.
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        allocations()
+    }
+
+    fn end(&self, start: u64) -> u64 {
+        allocations() - start
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        a + b
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationFormatter
+    }
+}
+
+struct AllocationFormatter;
+
+impl ValueFormatter for AllocationFormatter {
+    fn scale_values(&self, _typical: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        _throughput: &Throughput,
+        _values: &mut [f64],
+    ) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+fn get_last_allocations(c: &mut Criterion<Allocations>) {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+
+    let mut group = c.benchmark_group("get_last_allocations");
+    for (context_id, (name, size)) in (1..).map(ContextId::new).zip(PAYLOAD_SIZES) {
+        for _ in 0..TURNS {
+            let req = AppendRequest::new(context_id, "bench.Turn", 1, vec![0x5Au8; size]);
+            client.append_turn(&ctx, &req).unwrap();
+        }
+        let opts = GetLastOptions {
+            limit: TURNS,
+            include_payload: true,
+            ..Default::default()
+        };
+
+        group.bench_function(BenchmarkId::new("vec", name), |b| {
+            b.iter(|| black_box(client.get_last(&ctx, context_id, opts.clone()).unwrap()))
+        });
+        let mut records = Vec::new();
+        group.bench_function(BenchmarkId::new("vec_into", name), |b| {
+            b.iter(|| {
+                client
+                    .get_last_into(&ctx, context_id, opts.clone(), &mut records)
+                    .unwrap();
+                black_box(&records);
+            })
+        });
+        group.bench_function(BenchmarkId::new("bytes", name), |b| {
+            b.iter(|| {
+                black_box(
+                    client
+                        .get_last_shared(&ctx, context_id, opts.clone())
+                        .unwrap(),
+                )
+            })
+        });
+        let reuse = opts.reuse_buffer(true);
+        group.bench_function(BenchmarkId::new("bytes_reused_buffer", name), |b| {
+            b.iter(|| {
+                black_box(
+                    client
+                        .get_last_shared(&ctx, context_id, reuse.clone())
+                        .unwrap(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn listing_allocations(c: &mut Criterion<Allocations>) {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+    let context_id = ContextId::new(1);
+    for (i, type_id) in ["bench.Msg", "bench.Tool", "bench.Summary"]
+        .iter()
+        .cycle()
+        .take(LISTING_TURNS as usize)
+        .enumerate()
+    {
+        let req = AppendRequest::new(context_id, *type_id, 1, vec![i as u8; 16]);
+        client.append_turn(&ctx, &req).unwrap();
+    }
+    let opts = GetLastOptions {
+        limit: LISTING_TURNS,
+        ..Default::default()
+    };
+
+    let listed = client
+        .get_last_meta(&ctx, context_id, opts.clone())
+        .unwrap();
+    assert_eq!(listed.len(), LISTING_TURNS as usize);
+
+    let mut group = c.benchmark_group("listing_allocations");
+    group.bench_function("get_last", |b| {
+        b.iter(|| black_box(client.get_last(&ctx, context_id, opts.clone()).unwrap()))
+    });
+    group.bench_function("get_last_meta", |b| {
+        b.iter(|| {
+            black_box(
+                client
+                    .get_last_meta(&ctx, context_id, opts.clone())
+                    .unwrap(),
+            )
+        })
+    });
+    group.finish();
+}
+
+fn config() -> Criterion<Allocations> {
+    Criterion::default()
+        .with_measurement(Allocations)
+        .sample_size(20)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = get_last_allocations, listing_allocations
+}
+criterion_main!(benches);
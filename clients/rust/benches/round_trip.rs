@@ -0,0 +1,160 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! End-to-end client round trips against the in-memory mock server.
+
+mod support;
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cxdb::{dial, dial_pool, AppendRequest, ContextId, GetLastOptions, RequestContext};
+
+use support::MockServer;
+
+const PAYLOAD_SIZES: [(&str, usize); 3] = [("256B", 256), ("4KB", 4 * 1024), ("256KB", 256 * 1024)];
+
+/// Turns fetched per GET_LAST request.
+const GET_LAST_LIMIT: u32 = 8;
+
+/// Independent contexts read per batch in `get_last_batch`.
+const BATCH_CONTEXTS: u64 = 16;
+
+/// Connections in the pool used by `append_batch`.
+const POOL_SIZE: usize = 4;
+
+/// Appends per batch in `append_batch`, spread over `BATCH_CONTEXTS` contexts.
+const APPEND_BATCH: u64 = 256;
+
+fn append_turn(c: &mut Criterion) {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+
+    let mut group = c.benchmark_group("append_turn");
+    for (context_id, (name, size)) in (1..).map(ContextId::new).zip(PAYLOAD_SIZES) {
+        let req = AppendRequest::new(context_id, "bench.Turn", 1, vec![0x5Au8; size]);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &req, |b, req| {
+            b.iter(|| client.append_turn(&ctx, black_box(req)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn get_last(c: &mut Criterion) {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+
+    let mut group = c.benchmark_group("get_last");
+    for (context_id, (name, size)) in (1..).map(ContextId::new).zip(PAYLOAD_SIZES) {
+        for _ in 0..GET_LAST_LIMIT {
+            let req = AppendRequest::new(context_id, "bench.Turn", 1, vec![0x5Au8; size]);
+            client.append_turn(&ctx, &req).unwrap();
+        }
+        let opts = GetLastOptions {
+            limit: GET_LAST_LIMIT,
+            include_payload: true,
+            ..Default::default()
+        };
+        group.throughput(Throughput::Bytes(size as u64 * GET_LAST_LIMIT as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &opts, |b, opts| {
+            b.iter(|| {
+                let turns = client.get_last(&ctx, context_id, opts.clone()).unwrap();
+                assert_eq!(turns.len(), GET_LAST_LIMIT as usize);
+                black_box(turns)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Sequential `get_last` calls versus one pipelined `get_last_many` batch.
+fn get_last_batch(c: &mut Criterion) {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+
+    for context_id in (1..=BATCH_CONTEXTS).map(ContextId::new) {
+        let req = AppendRequest::new(context_id, "bench.Turn", 1, vec![0x5Au8; 256]);
+        client.append_turn(&ctx, &req).unwrap();
+    }
+    let opts = GetLastOptions {
+        limit: 1,
+        include_payload: true,
+        ..Default::default()
+    };
+    let requests: Vec<_> = (1..=BATCH_CONTEXTS)
+        .map(|id| (ContextId::new(id), opts.clone()))
+        .collect();
+
+    let mut group = c.benchmark_group("get_last_batch");
+    group.throughput(Throughput::Elements(BATCH_CONTEXTS));
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            for (context_id, opts) in &requests {
+                black_box(client.get_last(&ctx, *context_id, opts.clone()).unwrap());
+            }
+        })
+    });
+    group.bench_function("pipelined", |b| {
+        b.iter(|| {
+            for turns in client.get_last_many(&ctx, &requests).unwrap() {
+                black_box(turns.unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Sequential appends on one connection versus `ClientPool::append_many`.
+fn append_batch(c: &mut Criterion) {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let pool = dial_pool(server.addr(), POOL_SIZE, Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+    let requests: Vec<_> = (0..APPEND_BATCH)
+        .map(|i| {
+            AppendRequest::new(
+                ContextId::new(1 + i % BATCH_CONTEXTS),
+                "bench.Turn",
+                1,
+                vec![0x5Au8; 4 * 1024],
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("append_batch");
+    group.throughput(Throughput::Elements(APPEND_BATCH));
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            for req in &requests {
+                black_box(client.append_turn(&ctx, req).unwrap());
+            }
+        })
+    });
+    group.bench_function("pooled", |b| {
+        b.iter(|| {
+            for result in pool.append_many(&ctx, requests.clone()) {
+                black_box(result.unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn config() -> Criterion {
+    Criterion::default()
+        .sample_size(50)
+        .measurement_time(Duration::from_secs(5))
+        .noise_threshold(0.05)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = append_turn, get_last, get_last_batch, append_batch
+}
+criterion_main!(benches);
@@ -0,0 +1,237 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! In-memory mock CXDB server for benchmarks.
+//!
+//! Speaks just enough of the binary protocol (HELLO, APPEND_TURN, GET_LAST)
+//! over loopback TCP to drive the real client end to end, without the disk
+//! and indexing costs of the real server skewing client-side measurements.
+//! Clients that offer frame compression get zstd, as from the real server.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use cxdb::protocol::{
+    read_frame, write_frame, Frame, FLAG_COMPRESSED, MSG_APPEND_TURN, MSG_ERROR, MSG_GET_LAST,
+    MSG_HELLO,
+};
+use cxdb::Codec;
+
+/// Turns and payload bytes kept per context; older turns are dropped so
+/// long benchmark runs do not grow memory without bound.
+const RETAINED_TURNS: usize = 10_000;
+const RETAINED_BYTES: usize = 16 * 1024 * 1024;
+
+/// Smallest frame payload compressed on compressed connections, as on the
+/// real server.
+const COMPRESS_MIN_BYTES: usize = 512;
+
+struct StoredTurn {
+    turn_id: u64,
+    parent_id: u64,
+    depth: u32,
+    type_id: String,
+    type_version: u32,
+    encoding: u32,
+    compression: u32,
+    hash: [u8; 32],
+    payload: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Store {
+    next_turn_id: u64,
+    contexts: HashMap<u64, StoredContext>,
+}
+
+#[derive(Default)]
+struct StoredContext {
+    turns: VecDeque<StoredTurn>,
+    payload_bytes: usize,
+}
+
+pub struct MockServer {
+    addr: String,
+}
+
+impl MockServer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let addr = listener.local_addr().expect("local addr").to_string();
+        let store = Arc::new(Mutex::new(Store::default()));
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let store = store.clone();
+                thread::spawn(move || serve(stream, store));
+            }
+        });
+        Self { addr }
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+}
+
+fn serve(stream: TcpStream, store: Arc<Mutex<Store>>) {
+    let _ = stream.set_nodelay(true);
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let mut reader = BufReader::new(stream);
+    let mut compressed = false;
+    while let Ok(mut frame) = read_frame(&mut reader) {
+        if compressed && frame.header.flags & FLAG_COMPRESSED != 0 {
+            frame.payload = zstd::decode_all(&frame.payload[..]).expect("compressed request");
+        }
+        let mut flags = 0;
+        let (msg_type, mut payload) = match frame.header.msg_type {
+            MSG_HELLO => {
+                let mut resp = 1u64.to_le_bytes().to_vec();
+                resp.extend_from_slice(&1u16.to_le_bytes());
+                if frame.header.flags & FLAG_COMPRESSED != 0 {
+                    let served = [MSG_HELLO, MSG_APPEND_TURN, MSG_GET_LAST];
+                    let bits = served.iter().fold(0u64, |bits, t| bits | 1 << t);
+                    resp.extend_from_slice(&bits.to_le_bytes());
+                    resp.push(Codec::Zstd.id());
+                    flags = FLAG_COMPRESSED;
+                }
+                (MSG_HELLO, resp)
+            }
+            MSG_APPEND_TURN => (MSG_APPEND_TURN, append(&frame, &store)),
+            MSG_GET_LAST => (MSG_GET_LAST, get_last(&frame, &store)),
+            other => (
+                MSG_ERROR,
+                error(400, &format!("unsupported msg_type {other}")),
+            ),
+        };
+        if compressed && payload.len() >= COMPRESS_MIN_BYTES {
+            let smaller = zstd::bulk::compress(&payload, 1).expect("zstd");
+            if smaller.len() < payload.len() {
+                payload = smaller;
+                flags = FLAG_COMPRESSED;
+            }
+        }
+        if write_frame(&mut writer, msg_type, flags, frame.header.req_id, &payload).is_err() {
+            return;
+        }
+        // Compression applies from the frame after HELLO.
+        compressed |= msg_type == MSG_HELLO && flags & FLAG_COMPRESSED != 0;
+    }
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        head
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+
+    fn len_prefixed(&mut self) -> &'a [u8] {
+        let len = self.u32() as usize;
+        self.take(len)
+    }
+}
+
+fn append(frame: &Frame, store: &Mutex<Store>) -> Vec<u8> {
+    let mut req = Cursor(&frame.payload);
+    let context_id = req.u64();
+    let parent_turn_id = req.u64();
+    let type_id = String::from_utf8_lossy(req.len_prefixed()).into_owned();
+    let type_version = req.u32();
+    let encoding = req.u32();
+    let compression = req.u32();
+    let _uncompressed_len = req.u32();
+    let hash: [u8; 32] = req.take(32).try_into().unwrap();
+    let payload = req.len_prefixed().to_vec();
+
+    let mut store = store.lock().unwrap();
+    store.next_turn_id += 1;
+    let turn_id = store.next_turn_id;
+    let context = store.contexts.entry(context_id).or_default();
+    let (parent_id, depth) = match context.turns.back() {
+        Some(head) if parent_turn_id == 0 => (head.turn_id, head.depth + 1),
+        _ => (parent_turn_id, 1),
+    };
+    context.payload_bytes += payload.len();
+    while context.turns.len() == RETAINED_TURNS || context.payload_bytes > RETAINED_BYTES {
+        let Some(dropped) = context.turns.pop_front() else {
+            break;
+        };
+        context.payload_bytes -= dropped.payload.len();
+    }
+    context.turns.push_back(StoredTurn {
+        turn_id,
+        parent_id,
+        depth,
+        type_id,
+        type_version,
+        encoding,
+        compression,
+        hash,
+        payload,
+    });
+
+    let mut resp = Vec::with_capacity(52);
+    resp.extend_from_slice(&context_id.to_le_bytes());
+    resp.extend_from_slice(&turn_id.to_le_bytes());
+    resp.extend_from_slice(&depth.to_le_bytes());
+    resp.extend_from_slice(&hash);
+    resp
+}
+
+fn get_last(frame: &Frame, store: &Mutex<Store>) -> Vec<u8> {
+    let mut req = Cursor(&frame.payload);
+    let context_id = req.u64();
+    let limit = req.u32() as usize;
+    let include_payload = req.u32() != 0;
+
+    let store = store.lock().unwrap();
+    let Some(StoredContext { turns, .. }) = store.contexts.get(&context_id) else {
+        return 0u32.to_le_bytes().to_vec();
+    };
+    let selected: Vec<&StoredTurn> = turns
+        .iter()
+        .skip(turns.len().saturating_sub(limit))
+        .collect();
+
+    let mut resp = (selected.len() as u32).to_le_bytes().to_vec();
+    for turn in selected {
+        resp.extend_from_slice(&turn.turn_id.to_le_bytes());
+        resp.extend_from_slice(&turn.parent_id.to_le_bytes());
+        resp.extend_from_slice(&turn.depth.to_le_bytes());
+        resp.extend_from_slice(&(turn.type_id.len() as u32).to_le_bytes());
+        resp.extend_from_slice(turn.type_id.as_bytes());
+        resp.extend_from_slice(&turn.type_version.to_le_bytes());
+        resp.extend_from_slice(&turn.encoding.to_le_bytes());
+        resp.extend_from_slice(&turn.compression.to_le_bytes());
+        resp.extend_from_slice(&(turn.payload.len() as u32).to_le_bytes());
+        resp.extend_from_slice(&turn.hash);
+        if include_payload {
+            resp.extend_from_slice(&(turn.payload.len() as u32).to_le_bytes());
+            resp.extend_from_slice(&turn.payload);
+        }
+    }
+    resp
+}
+
+fn error(code: u32, detail: &str) -> Vec<u8> {
+    let mut payload = code.to_le_bytes().to_vec();
+    payload.extend_from_slice(&(detail.len() as u32).to_le_bytes());
+    payload.extend_from_slice(detail.as_bytes());
+    payload
+}
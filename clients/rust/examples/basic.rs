@@ -2,14 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use cxdb::types::{new_user_input, TypeIDConversationItem, TypeVersionConversationItem};
-use cxdb::{dial, encode_msgpack, AppendRequest, GetLastOptions, RequestContext};
+use cxdb::{
+    dial, encode_msgpack, AppendRequest, CreateContextOptions, GetLastOptions, RequestContext,
+};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> cxdb::Result<()> {
     let addr = std::env::var("CXDB_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
     let client = dial(&addr, Vec::new())?;
     let ctx = RequestContext::background();
 
-    let head = client.create_context(&ctx, 0)?;
+    let head = client.create_context(&ctx, CreateContextOptions::default())?;
     let payload = encode_msgpack(&new_user_input("Hello from Rust", Vec::new()))?;
     let append = client.append_turn(
         &ctx,
@@ -28,5 +30,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         append.turn_id,
         turns.len()
     );
+    for turn in &turns {
+        println!("  {turn:#}");
+    }
     Ok(())
 }
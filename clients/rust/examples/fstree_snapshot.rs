@@ -3,15 +3,17 @@
 
 use cxdb::fstree;
 use cxdb::types::{new_user_input, TypeIDConversationItem, TypeVersionConversationItem};
-use cxdb::{dial, encode_msgpack, AppendRequest, RequestContext};
+use cxdb::{
+    dial, encode_msgpack, hash_to_hex, AppendRequest, CreateContextOptions, RequestContext,
+};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> cxdb::Result<()> {
     let addr = std::env::var("CXDB_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
     let root = std::env::var("CXDB_FS_ROOT").unwrap_or_else(|_| ".".to_string());
 
     let client = dial(&addr, Vec::new())?;
     let ctx = RequestContext::background();
-    let head = client.create_context(&ctx, 0)?;
+    let head = client.create_context(&ctx, CreateContextOptions::default())?;
 
     let snapshot = fstree::capture(&root, vec![fstree::with_exclude(vec![".git", "target"])])?;
     let upload = snapshot.upload(&ctx, &client)?;
@@ -31,13 +33,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!(
         "turn_id={} fs_root={} trees_uploaded={} files_uploaded={}",
         append.turn_id,
-        to_hex(&snapshot.root_hash),
+        hash_to_hex(&snapshot.root_hash),
         upload.trees_uploaded,
         upload.files_uploaded
     );
     Ok(())
 }
-
-fn to_hex(bytes: &[u8; 32]) -> String {
-    bytes.iter().map(|b| format!("{:02x}", b)).collect()
-}
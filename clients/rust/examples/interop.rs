@@ -1,7 +1,10 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use cxdb::{decode_msgpack, dial, encode_msgpack, AppendRequest, GetLastOptions, RequestContext};
+use cxdb::{
+    decode_msgpack, dial, encode_msgpack, AppendRequest, ContextId, CreateContextOptions,
+    GetLastOptions, RequestContext,
+};
 use std::collections::BTreeMap;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -26,7 +29,7 @@ fn write_flow(args: &mut impl Iterator<Item = String>) -> Result<(), Box<dyn std
 
     let client = dial(&addr, Vec::new())?;
     let ctx = RequestContext::background();
-    let head = client.create_context(&ctx, 0)?;
+    let head = client.create_context(&ctx, CreateContextOptions::default())?;
 
     let payload = encode_msgpack(&BTreeMap::from([(1u64, role), (2u64, text)]))?;
     let req = AppendRequest::new(head.context_id, "com.yourorg.ai.MessageTurn", 1, payload);
@@ -38,7 +41,7 @@ fn write_flow(args: &mut impl Iterator<Item = String>) -> Result<(), Box<dyn std
 
 fn read_flow(args: &mut impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
     let addr = args.next().unwrap_or_else(|| "127.0.0.1:9009".to_string());
-    let context_id: u64 = args
+    let context_id: ContextId = args
         .next()
         .ok_or("missing context_id")?
         .parse()
@@ -52,6 +55,7 @@ fn read_flow(args: &mut impl Iterator<Item = String>) -> Result<(), Box<dyn std:
         GetLastOptions {
             limit: 1,
             include_payload: true,
+            ..Default::default()
         },
     )?;
 
@@ -0,0 +1,28 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Frame reader and CRC32C verification over arbitrary server bytes.
+
+#![no_main]
+
+use cxdb::protocol::{decode_frame, read_frame_with_limit, verify_frame_checksum};
+use libfuzzer_sys::fuzz_target;
+
+/// Small enough that libFuzzer's RSS limit would flag an allocation made
+/// before the declared length is checked.
+const MAX_LEN: u32 = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    let mut cursor = std::io::Cursor::new(data);
+    while let Ok(frame) = read_frame_with_limit(&mut cursor, MAX_LEN) {
+        let _ = verify_frame_checksum(&frame.header, &frame.payload);
+    }
+
+    if let Ok((frame, used)) = decode_frame(data, MAX_LEN) {
+        assert!(used <= data.len());
+        assert_eq!(frame.payload.len(), frame.header.len as usize);
+        if let Ok(header) = verify_frame_checksum(&frame.header, &frame.payload) {
+            assert!(header.len < frame.header.len);
+        }
+    }
+});
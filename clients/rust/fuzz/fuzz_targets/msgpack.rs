@@ -0,0 +1,16 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Msgpack payload decoding, untyped and into the canonical types.
+
+#![no_main]
+
+use cxdb::types::ConversationItem;
+use cxdb::{decode_msgpack, decode_msgpack_into};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_msgpack(data);
+    let _ = decode_msgpack_into::<ConversationItem>(data);
+    let _ = decode_msgpack_into::<Vec<std::collections::BTreeMap<String, String>>>(data);
+});
@@ -0,0 +1,21 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! GET_LAST response decoding followed by lazy payload decoding.
+
+#![no_main]
+
+use cxdb::turn::parse_turn_records;
+use cxdb::types::ConversationItem;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(records) = parse_turn_records(data) else {
+        return;
+    };
+    for record in records {
+        let turn = record.into_lazy();
+        let _ = turn.get::<ConversationItem>();
+        let _ = cxdb::decode_msgpack(turn.raw());
+    }
+});
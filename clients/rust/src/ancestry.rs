@@ -0,0 +1,393 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ancestry paths through forked histories.
+//!
+//! Every turn links to its parent, so one line of a branching context is
+//! the parent chain of its newest turn. [`Client::get_path`] returns that
+//! chain, from the root or a given ancestor down to a target turn, and
+//! [`Client::get_children`] goes the other way, listing the turns that
+//! branch off one turn.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::MSG_GET_CHILDREN;
+use crate::turn::{
+    finish_listing, finish_records, parse_turn_listing, parse_turn_records, GetLastOptions,
+    GetTurnOptions, TurnRecord,
+};
+
+/// Default turns per page read by [`Client::get_path`].
+pub const DEFAULT_PATH_PAGE_SIZE: u32 = 256;
+
+/// How [`Client::get_path`] reads a path.
+#[derive(Debug, Clone, Copy)]
+pub struct GetPathOptions {
+    /// Turns per GET_LAST page.
+    pub page_size: u32,
+    /// Return payloads. Without it every turn is returned with
+    /// `payload_omitted` set.
+    pub include_payload: bool,
+}
+
+impl Default for GetPathOptions {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PATH_PAGE_SIZE,
+            include_payload: false,
+        }
+    }
+}
+
+impl GetPathOptions {
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn include_payload(mut self, include: bool) -> Self {
+        self.include_payload = include;
+        self
+    }
+
+    /// The page of `context_id`'s history below `turn_id`.
+    fn page(&self, turn_id: TurnId) -> GetLastOptions {
+        let opts = GetLastOptions {
+            limit: self.page_size.max(1),
+            include_payload: true,
+            ..Default::default()
+        }
+        .before(turn_id)
+        .include_compacted(true)
+        .include_expired(true);
+        if self.include_payload {
+            opts
+        } else {
+            opts.max_payload_bytes(0)
+        }
+    }
+}
+
+impl Client {
+    /// Returns the turns whose parent is `turn_id`, oldest first: none for
+    /// a leaf, one along a linear stretch of history and several where it
+    /// forks. Children appended to other contexts, such as forks of
+    /// `context_id`, are included, as are compacted and expired ones;
+    /// expired ones are marked [`TurnRecord::expired`].
+    ///
+    /// `opts` are read as by [`Client::get_range`], except that
+    /// `min_sequence` is not applied: GET_CHILDREN has no consistency wait.
+    /// The server is only told whether to send payloads, so
+    /// `max_payload_bytes` and `projection` are applied to the response.
+    ///
+    /// Returns [`Error::TurnNotFound`] if `turn_id` does not exist and
+    /// [`Error::Unsupported`] against servers without GET_CHILDREN.
+    pub fn get_children(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        turn_id: TurnId,
+        opts: GetTurnOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        let mut payload = Vec::with_capacity(20);
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        payload.write_u64::<LittleEndian>(turn_id.get())?;
+        payload.write_u32::<LittleEndian>(u32::from(opts.include_payload))?;
+        let frame = self
+            .send_request(ctx, MSG_GET_CHILDREN, &payload)
+            .map_err(|err| err.resolve_unsupported("GET_CHILDREN").resolve_not_found())?;
+        let finish = GetLastOptions {
+            include_payload: opts.include_payload,
+            max_payload_bytes: opts.max_payload_bytes,
+            projection: opts.projection,
+            ..Default::default()
+        };
+        if opts.include_payload {
+            let mut children = parse_turn_records(&frame.payload)?;
+            self.open_payloads(ctx, &mut children)?;
+            finish_records(&mut children, &finish)?;
+            return Ok(children);
+        }
+        let mut children = parse_turn_listing(&frame.payload)?;
+        for child in &mut children {
+            child.payload_omitted = true;
+        }
+        finish_listing(&mut children, &finish)?;
+        Ok(children)
+    }
+
+    /// Returns the turns from `from_turn_id` (the root when `None`) down to
+    /// `to_turn_id`, both included, following parent links, in ascending
+    /// depth order. Compacted and expired turns stay on the path; expired
+    /// ones are marked [`TurnRecord::expired`].
+    ///
+    /// The path is read a page at a time from `context_id`'s history, so
+    /// pass the context the target turn belongs to. Stretches of the path
+    /// outside that history, such as another branch of a fork, cost a round
+    /// trip per turn.
+    ///
+    /// Returns [`Error::NotAnAncestor`] if `from_turn_id` is not on the
+    /// path.
+    pub fn get_path(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        from_turn_id: std::option::Option<TurnId>,
+        to_turn_id: TurnId,
+        opts: GetPathOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        let mut path = vec![self.path_turn(ctx, to_turn_id, &opts)?];
+        loop {
+            let oldest = &path[path.len() - 1];
+            // Turn ids grow along a chain, so an ancestor has a smaller id.
+            let reached = match from_turn_id {
+                Some(from) => oldest.turn_id <= from,
+                None => false,
+            };
+            if reached || oldest.parent_id.is_zero() {
+                break;
+            }
+            let mut parent = oldest.parent_id;
+            let page = self.get_last(ctx, context_id, opts.page(oldest.turn_id))?;
+            let linked = path.len();
+            for record in page.into_iter().rev() {
+                if record.turn_id != parent {
+                    break;
+                }
+                parent = record.parent_id;
+                let done = Some(record.turn_id) == from_turn_id || parent.is_zero();
+                path.push(record);
+                if done {
+                    break;
+                }
+            }
+            if path.len() == linked {
+                // The parent is not in the context's history here (another
+                // branch), so follow the link directly.
+                path.push(self.path_turn(ctx, parent, &opts)?);
+            }
+        }
+        if let Some(from) = from_turn_id {
+            if path[path.len() - 1].turn_id != from {
+                return Err(Error::NotAnAncestor {
+                    ancestor: from,
+                    turn_id: to_turn_id,
+                });
+            }
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    /// Fetches one turn of a path, dropping its payload unless asked for.
+    fn path_turn(
+        &self,
+        ctx: &RequestContext,
+        turn_id: TurnId,
+        opts: &GetPathOptions,
+    ) -> Result<TurnRecord> {
+        let mut turn = self.get_turn(ctx, turn_id)?;
+        if !opts.include_payload {
+            turn.payload = Vec::new();
+            turn.payload_omitted = true;
+        }
+        Ok(turn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{MSG_ERROR, MSG_GET_LAST, MSG_GET_TURN};
+    use crate::test_util::{
+        linked_turns_listing, linked_turns_payload, not_found_payload, spawn_multi_server,
+    };
+    use crate::turn::TurnFields;
+
+    /// Parents of turns 1..=9: context 1 is the chain 1..=5; context 2
+    /// forks it at turn 3 with 6, 7 and 9; turn 8 is a sibling of 4.
+    const PARENTS: [u64; 10] = [0, 0, 1, 2, 3, 4, 3, 6, 3, 7];
+    const HEADS: [u64; 3] = [0, 5, 9];
+
+    /// Serves the history above, counting requests.
+    fn forked_history() -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let addr = spawn_multi_server(move |req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let u64_at =
+                |at: usize| u64::from_le_bytes(req.payload[at..at + 8].try_into().unwrap());
+            if req.header.msg_type == MSG_GET_CHILDREN {
+                let turn_id = u64_at(8);
+                if turn_id as usize >= PARENTS.len() {
+                    return (MSG_ERROR, not_found_payload("turn", turn_id));
+                }
+                let children: Vec<(u64, u64)> = (1..PARENTS.len() as u64)
+                    .filter(|&child| PARENTS[child as usize] == turn_id)
+                    .map(|child| (child, turn_id))
+                    .collect();
+                return match req.payload[16] {
+                    0 => (MSG_GET_CHILDREN, linked_turns_listing(&children)),
+                    _ => (MSG_GET_CHILDREN, linked_turns_payload(&children)),
+                };
+            }
+            if req.header.msg_type == MSG_GET_TURN {
+                let turn_id = u64_at(0);
+                return (
+                    MSG_GET_TURN,
+                    linked_turns_payload(&[(turn_id, PARENTS[turn_id as usize])]),
+                );
+            }
+            // GET_LAST walks from the head, skipping turns from the cursor up.
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as usize;
+            let before = u64_at(36);
+            let mut page = Vec::new();
+            let mut turn_id = HEADS[u64_at(0) as usize];
+            while turn_id != 0 && page.len() < limit {
+                if turn_id < before {
+                    page.push((turn_id, PARENTS[turn_id as usize]));
+                }
+                turn_id = PARENTS[turn_id as usize];
+            }
+            page.reverse();
+            (MSG_GET_LAST, linked_turns_payload(&page))
+        });
+        (addr, requests)
+    }
+
+    fn ids(path: &[TurnRecord]) -> Vec<u64> {
+        path.iter().map(|turn| turn.turn_id.get()).collect()
+    }
+
+    #[test]
+    fn paths_follow_parent_links_across_forks() {
+        let (addr, requests) = forked_history();
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetPathOptions::default().page_size(2);
+
+        let path = client
+            .get_path(&ctx, ContextId::new(2), None, TurnId::new(9), opts)
+            .unwrap();
+        assert_eq!(ids(&path), [1, 2, 3, 6, 7, 9]);
+        assert!(path.iter().all(|turn| turn.payload_omitted));
+        // The target, then three pages.
+        assert_eq!(requests.swap(0, Ordering::SeqCst), 4);
+
+        let path = client
+            .get_path(
+                &ctx,
+                ContextId::new(2),
+                Some(TurnId::new(3)),
+                TurnId::new(9),
+                opts.include_payload(true),
+            )
+            .unwrap();
+        assert_eq!(ids(&path), [3, 6, 7, 9]);
+        assert_eq!(path[1].payload, [6]);
+
+        // Turn 8 is on neither context's line, so its parent is fetched by id.
+        requests.store(0, Ordering::SeqCst);
+        let path = client
+            .get_path(&ctx, ContextId::new(1), None, TurnId::new(8), opts)
+            .unwrap();
+        assert_eq!(ids(&path), [1, 2, 3, 8]);
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn rejects_turns_off_the_path() {
+        let (addr, _) = forked_history();
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        for from in [4, 8, 10].map(TurnId::new) {
+            let err = client
+                .get_path(
+                    &ctx,
+                    ContextId::new(2),
+                    Some(from),
+                    TurnId::new(9),
+                    GetPathOptions::default(),
+                )
+                .unwrap_err();
+            assert!(
+                matches!(err, Error::NotAnAncestor { ancestor, turn_id } if ancestor == from && turn_id.get() == 9),
+                "{err:?}"
+            );
+        }
+        let path = client
+            .get_path(
+                &ctx,
+                ContextId::new(2),
+                Some(TurnId::new(9)),
+                TurnId::new(9),
+                GetPathOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(ids(&path), [9]);
+    }
+
+    #[test]
+    fn children_list_every_branch() {
+        let (addr, _) = forked_history();
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetTurnOptions::default().include_payload(false);
+
+        let children = client
+            .get_children(&ctx, ContextId::new(1), TurnId::new(3), opts)
+            .unwrap();
+        assert_eq!(ids(&children), [4, 6, 8]);
+        assert!(children.iter().all(|turn| turn.payload_omitted));
+        assert_eq!(
+            ids(&client
+                .get_children(&ctx, ContextId::new(1), TurnId::new(4), opts)
+                .unwrap()),
+            [5]
+        );
+        assert!(client
+            .get_children(&ctx, ContextId::new(1), TurnId::new(5), opts)
+            .unwrap()
+            .is_empty());
+
+        let children = client
+            .get_children(
+                &ctx,
+                ContextId::new(2),
+                TurnId::new(7),
+                opts.include_payload(true),
+            )
+            .unwrap();
+        assert_eq!(children[0].payload, [9]);
+
+        let children = client
+            .get_children(
+                &ctx,
+                ContextId::new(2),
+                TurnId::new(7),
+                opts.include_payload(true)
+                    .max_payload_bytes(0)
+                    .projection(TurnFields::PARENT),
+            )
+            .unwrap();
+        assert!(children[0].payload_omitted && children[0].payload.is_empty());
+        assert_eq!(children[0].parent_id.get(), 7);
+        assert_eq!(children[0].depth, 0);
+
+        let err = client
+            .get_children(&ctx, ContextId::new(1), TurnId::new(42), opts)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::TurnNotFound { turn_id } if turn_id.get() == 42),
+            "{err:?}"
+        );
+    }
+}
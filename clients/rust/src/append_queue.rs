@@ -0,0 +1,476 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fire-and-forget appends for [`Client::append_async`].
+//!
+//! `append_async` puts the append on a bounded in-memory queue and returns
+//! without waiting for the server. A background thread sends queued appends,
+//! in order, on a dedicated, lazily dialed connection and hands each result
+//! to the append's callback, if it has one. The thread exits whenever the
+//! queue runs dry and is started again by the next append.
+//!
+//! - The queue holds at most [`AppendQueueOptions::capacity`] appends,
+//!   counting the one being sent. What happens to an append that finds it
+//!   full is set by [`QueueFullPolicy`].
+//! - [`Client::flush`] waits until every queued append has been answered.
+//! - [`Client::close`] flushes for up to the request timeout, then fails the
+//!   appends still queued with [`Error::ClientClosed`] and reports how many
+//!   it gave up on as [`Error::AppendsDropped`].
+//!
+//! Appends travel on their own connection, so they are not ordered with
+//! requests made directly on the client: an `append_turn` issued after an
+//! `append_async` may reach the server first. Callbacks run on the
+//! background thread and hold up the appends behind them while they run.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use cxdb::append_queue::{with_append_queue, AppendQueueOptions, QueueFullPolicy};
+//! use cxdb::{dial, AppendRequest, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [with_append_queue(AppendQueueOptions {
+//!     capacity: 4096,
+//!     when_full: QueueFullPolicy::Error,
+//! })])?;
+//! let ctx = RequestContext::background();
+//! client.append_async(
+//!     &ctx,
+//!     AppendRequest::new(ContextId::new(1), "app.Telemetry", 1, vec![0x80]),
+//!     Some(Box::new(|result| {
+//!         if let Err(err) = result {
+//!             eprintln!("telemetry append failed: {err}");
+//!         }
+//!     })),
+//! )?;
+//! client.flush(Duration::from_secs(5))?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::client::{Client, ClientOption, RequestContext};
+use crate::error::{Error, Result};
+use crate::prefetch::PrefetchCache;
+use crate::reconnect::DialFunc;
+use crate::turn::{AppendRequest, AppendResult};
+
+/// Default number of appends [`Client::append_async`] may have outstanding.
+pub const DEFAULT_APPEND_QUEUE_CAPACITY: usize = 1024;
+
+/// Receives the server's answer to an append made with
+/// [`Client::append_async`].
+pub type AppendCallback = Box<dyn FnOnce(Result<AppendResult>) + Send>;
+
+/// What [`Client::append_async`] does when the queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// Wait for room, until the request context's deadline or the client's
+    /// request timeout, whichever is sooner.
+    #[default]
+    Block,
+    /// Fail at once with [`Error::QueueFull`].
+    Error,
+}
+
+/// Queue settings for [`Client::append_async`]; see [`with_append_queue`].
+#[derive(Debug, Clone, Copy)]
+pub struct AppendQueueOptions {
+    /// Most appends queued or in flight at once. Zero is treated as one.
+    pub capacity: usize,
+    pub when_full: QueueFullPolicy,
+}
+
+impl Default for AppendQueueOptions {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_APPEND_QUEUE_CAPACITY,
+            when_full: QueueFullPolicy::default(),
+        }
+    }
+}
+
+/// Sizes the queue behind [`Client::append_async`] and sets what happens
+/// when it is full.
+pub fn with_append_queue(options: AppendQueueOptions) -> ClientOption {
+    Arc::new(move |opts| opts.append_queue = options)
+}
+
+struct Job {
+    ctx: RequestContext,
+    req: AppendRequest,
+    callback: Option<AppendCallback>,
+}
+
+#[derive(Default)]
+struct State {
+    queued: VecDeque<Job>,
+    /// Whether the background thread is sending `queued[0]`.
+    in_flight: bool,
+    worker_running: bool,
+    /// Set by close; refuses further appends.
+    closing: bool,
+    /// Set once close has taken the background connection; no new one is
+    /// dialed after that.
+    shut_down: bool,
+}
+
+/// Per-client queue state.
+pub(crate) struct AppendQueue {
+    state: Mutex<State>,
+    changed: Condvar,
+    /// Background connection, dialed by the first queued append.
+    conn: Mutex<Option<Arc<Client>>>,
+    options: AppendQueueOptions,
+}
+
+impl AppendQueue {
+    pub(crate) fn new(options: AppendQueueOptions) -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            conn: Mutex::new(None),
+            options,
+        }
+    }
+
+    fn push(&self, job: Job, deadline: Instant) -> Result<bool> {
+        let capacity = self.options.capacity.max(1);
+        let mut state = self.state.lock().map_err(|_| Error::ClientClosed)?;
+        loop {
+            if state.closing {
+                return Err(Error::ClientClosed);
+            }
+            if state.queued.len() < capacity {
+                break;
+            }
+            if self.options.when_full == QueueFullPolicy::Error {
+                return Err(Error::QueueFull);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::DeadlineExceeded);
+            }
+            state = self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .map_err(|_| Error::ClientClosed)?
+                .0;
+        }
+        state.queued.push_back(job);
+        let start = !state.worker_running;
+        state.worker_running = true;
+        Ok(start)
+    }
+
+    /// Starts on the next job, or marks the worker stopped if there is none.
+    /// The job stays queued until [`AppendQueue::finish`], so flushes wait
+    /// for its callback as well.
+    fn next(&self) -> Option<(RequestContext, AppendRequest, Option<AppendCallback>)> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.queued.is_empty() {
+            state.worker_running = false;
+            return None;
+        }
+        state.in_flight = true;
+        let job = state.queued.front_mut()?;
+        Some((job.ctx.clone(), job.req.clone(), job.callback.take()))
+    }
+
+    fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.in_flight = false;
+        state.queued.pop_front();
+        self.changed.notify_all();
+    }
+
+    /// Waits until nothing is queued or in flight.
+    fn drain(&self, deadline: Instant) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if state.queued.is_empty() {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Refuses further appends, waits up to `timeout` for the queue to
+    /// drain, then fails what is left and closes the background connection.
+    /// Returns how many queued appends were given up on.
+    pub(crate) fn close(&self, timeout: Duration) -> usize {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.closing = true;
+            self.changed.notify_all();
+        }
+        let dropped = if self.drain(Instant::now() + timeout) {
+            Vec::new()
+        } else {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            // The append being sent stays for the worker to finish.
+            let keep = usize::from(state.in_flight);
+            state.queued.drain(keep..).collect()
+        };
+        let conn = {
+            let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+            self.state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .shut_down = true;
+            conn.take()
+        };
+        if let Some(conn) = conn {
+            let _ = conn.close();
+        }
+        let count = dropped.len();
+        for job in dropped {
+            if let Some(callback) = job.callback {
+                callback(Err(Error::ClientClosed));
+            }
+        }
+        count
+    }
+
+    fn connection(&self, dial: &DialFunc) -> Result<Arc<Client>> {
+        let mut conn = self.conn.lock().map_err(|_| Error::ClientClosed)?;
+        if self.state.lock().map_or(true, |state| state.shut_down) {
+            return Err(Error::ClientClosed);
+        }
+        if let Some(client) = conn.as_ref().filter(|client| !client.is_poisoned()) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(dial()?);
+        *conn = Some(client.clone());
+        Ok(client)
+    }
+}
+
+fn send_loop(queue: Arc<AppendQueue>, prefetch: Arc<PrefetchCache>, dial: DialFunc) {
+    while let Some((ctx, req, callback)) = queue.next() {
+        let result = queue
+            .connection(&dial)
+            .and_then(|conn| conn.append_turn(&ctx, &req));
+        // The append bypassed this client, so drop its cached tail here.
+        prefetch.invalidate(req.context_id);
+        if let Some(callback) = callback {
+            callback(result);
+        }
+        queue.finish();
+    }
+}
+
+impl Client {
+    /// Queues `req` to be appended in the background and returns without
+    /// waiting for the server. `callback`, if given, receives the result
+    /// once the server answers.
+    ///
+    /// Fails without queueing anything if the client is closed or
+    /// read-only, if the validator rejects `req`, or if the queue is full
+    /// and set to [`QueueFullPolicy::Error`]. See the
+    /// [`append_queue`](crate::append_queue) module for ordering and
+    /// shutdown.
+    pub fn append_async(
+        &self,
+        ctx: &RequestContext,
+        req: AppendRequest,
+        callback: Option<AppendCallback>,
+    ) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::ClientClosed);
+        }
+        self.validate_append(&req)?;
+        let deadline = self.compute_deadline(ctx)?;
+        let queue = self.append_queue().clone();
+        let job = Job {
+            ctx: ctx.clone(),
+            req,
+            callback,
+        };
+        if queue.push(job, deadline)? {
+            let prefetch = self.prefetch_cache().clone();
+            let dial = self.dialer();
+            thread::spawn(move || send_loop(queue, prefetch, dial));
+        }
+        Ok(())
+    }
+
+    /// Waits until every append queued with [`Client::append_async`] has
+    /// been answered and its callback has run. Fails with
+    /// [`Error::DeadlineExceeded`] if that takes longer than `timeout`;
+    /// the appends stay queued.
+    pub fn flush(&self, timeout: Duration) -> Result<()> {
+        if self.append_queue().drain(Instant::now() + timeout) {
+            Ok(())
+        } else {
+            Err(Error::DeadlineExceeded)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::client::{dial, with_request_timeout};
+    use crate::protocol::MSG_APPEND_TURN;
+    use crate::test_util::spawn_multi_server;
+
+    /// Acks every append after `delay`, with turn ids counting up from 1.
+    fn serve_appends(delay: Duration) -> (String, Arc<AtomicUsize>) {
+        let appends = Arc::new(AtomicUsize::new(0));
+        let addr = spawn_multi_server({
+            let appends = appends.clone();
+            move |req| {
+                assert_eq!(req.header.msg_type, MSG_APPEND_TURN);
+                thread::sleep(delay);
+                let turn_id = appends.fetch_add(1, Ordering::SeqCst) as u64 + 1;
+                let mut ack = 1u64.to_le_bytes().to_vec();
+                ack.extend_from_slice(&turn_id.to_le_bytes());
+                ack.extend_from_slice(&(turn_id as u32).to_le_bytes());
+                ack.extend_from_slice(&[0u8; 32]);
+                (MSG_APPEND_TURN, ack)
+            }
+        });
+        (addr, appends)
+    }
+
+    fn request() -> AppendRequest {
+        AppendRequest::new(ContextId::new(1), "test.Event", 1, vec![0x80])
+    }
+
+    #[test]
+    fn callbacks_receive_results_in_order() {
+        let (addr, appends) = serve_appends(Duration::ZERO);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..5 {
+            let tx = tx.clone();
+            client
+                .append_async(
+                    &ctx,
+                    request(),
+                    Some(Box::new(move |result| {
+                        tx.send(result.unwrap().turn_id.get()).unwrap();
+                    })),
+                )
+                .unwrap();
+        }
+        client.flush(Duration::from_secs(5)).unwrap();
+
+        assert_eq!(appends.load(Ordering::SeqCst), 5);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        client.close().unwrap();
+    }
+
+    #[test]
+    fn full_queue_errors_when_set_to() {
+        let (addr, _) = serve_appends(Duration::from_millis(200));
+        let client = dial(
+            &addr,
+            [with_append_queue(AppendQueueOptions {
+                capacity: 2,
+                when_full: QueueFullPolicy::Error,
+            })],
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+        client.append_async(&ctx, request(), None).unwrap();
+        client.append_async(&ctx, request(), None).unwrap();
+
+        let err = client.append_async(&ctx, request(), None).unwrap_err();
+        assert!(matches!(err, Error::QueueFull), "{err}");
+        client.flush(Duration::from_secs(5)).unwrap();
+        client.append_async(&ctx, request(), None).unwrap();
+        client.close().unwrap();
+    }
+
+    #[test]
+    fn full_queue_blocks_until_room() {
+        let (addr, appends) = serve_appends(Duration::from_millis(20));
+        let client = dial(
+            &addr,
+            [with_append_queue(AppendQueueOptions {
+                capacity: 1,
+                when_full: QueueFullPolicy::Block,
+            })],
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+        for _ in 0..3 {
+            client.append_async(&ctx, request(), None).unwrap();
+        }
+        client.flush(Duration::from_secs(5)).unwrap();
+        assert_eq!(appends.load(Ordering::SeqCst), 3);
+        client.close().unwrap();
+    }
+
+    #[test]
+    fn blocked_append_gives_up_at_deadline() {
+        let (addr, _) = serve_appends(Duration::from_millis(500));
+        let client = dial(
+            &addr,
+            [with_append_queue(AppendQueueOptions {
+                capacity: 1,
+                when_full: QueueFullPolicy::Block,
+            })],
+        )
+        .unwrap();
+        client
+            .append_async(&RequestContext::background(), request(), None)
+            .unwrap();
+
+        let ctx = RequestContext::with_timeout(Duration::from_millis(50));
+        let err = client.append_async(&ctx, request(), None).unwrap_err();
+        assert!(matches!(err, Error::DeadlineExceeded), "{err}");
+        client.flush(Duration::from_secs(5)).unwrap();
+        client.close().unwrap();
+    }
+
+    #[test]
+    fn close_reports_dropped_appends() {
+        let (addr, _) = serve_appends(Duration::from_secs(1));
+        let client = dial(&addr, [with_request_timeout(Duration::from_millis(100))]).unwrap();
+        let ctx = RequestContext::background();
+        let failed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let failed = failed.clone();
+            client
+                .append_async(
+                    &ctx,
+                    request(),
+                    Some(Box::new(move |result| {
+                        if matches!(result, Err(Error::ClientClosed)) {
+                            failed.fetch_add(1, Ordering::SeqCst);
+                        }
+                    })),
+                )
+                .unwrap();
+        }
+
+        // The first append is in flight when close gives up; depending on
+        // whether it has timed out yet, one or two are still queued.
+        let dropped = match client.close().unwrap_err() {
+            Error::AppendsDropped { dropped } => dropped,
+            err => panic!("unexpected error: {err}"),
+        };
+        assert!((1..=2).contains(&dropped), "{dropped}");
+        assert_eq!(failed.load(Ordering::SeqCst), dropped);
+        let err = client.append_async(&ctx, request(), None).unwrap_err();
+        assert!(matches!(err, Error::ClientClosed), "{err}");
+    }
+}
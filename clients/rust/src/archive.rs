@@ -0,0 +1,298 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Archiving finished contexts.
+//!
+//! [`Client::archive_context`] takes a context off the hot path: until
+//! [`Client::unarchive_context`] brings it back, reading its history or
+//! appending to it fails with
+//! [`Error::ContextArchived`](crate::Error::ContextArchived). Its head stays
+//! readable with [`Client::get_head`]. Appends never restore a context
+//! implicitly; reads do when asked to with
+//! [`GetLastOptions::auto_restore`](crate::GetLastOptions::auto_restore).
+//! [`Client::list_contexts`] reports which contexts are archived.
+//!
+//! Unarchiving is unrelated to [`Client::restore_context`], which replays a
+//! [`Snapshot`](crate::Snapshot) into a new context.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::context::{parse_context_head, ContextHead};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::Error;
+use crate::error::Result;
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::{MSG_CTX_ARCHIVE, MSG_CTX_RESTORE, MSG_LIST_CONTEXTS};
+
+/// [`ContextSummary`] flag bit the server sets on archived contexts.
+const CONTEXT_FLAG_ARCHIVED: u32 = 1;
+
+/// Which contexts [`Client::list_contexts`] returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchiveFilter {
+    #[default]
+    All,
+    Active,
+    Archived,
+}
+
+impl ArchiveFilter {
+    fn wire(self) -> u32 {
+        match self {
+            ArchiveFilter::All => 0,
+            ArchiveFilter::Active => 1,
+            ArchiveFilter::Archived => 2,
+        }
+    }
+}
+
+/// Options for [`Client::list_contexts`].
+#[derive(Debug, Clone, Copy)]
+pub struct ListContextsOptions {
+    /// Most contexts to return; 100 by default.
+    pub limit: u32,
+    pub filter: ArchiveFilter,
+}
+
+impl Default for ListContextsOptions {
+    fn default() -> Self {
+        Self {
+            limit: 100,
+            filter: ArchiveFilter::All,
+        }
+    }
+}
+
+impl ListContextsOptions {
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn filter(mut self, filter: ArchiveFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// A context as [`Client::list_contexts`] reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextSummary {
+    pub context_id: ContextId,
+    pub head_turn_id: TurnId,
+    pub head_depth: u32,
+    pub archived: bool,
+    /// When the head turn was appended (the context created, while it is
+    /// empty), in Unix milliseconds.
+    pub updated_at_unix_ms: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Archives `context_id` (see the [module docs](self)). Archiving an
+    /// archived context changes nothing.
+    ///
+    /// Servers without the CTX_ARCHIVE message fail with
+    /// [`Error::Unsupported`].
+    pub fn archive_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<ContextHead> {
+        let response = self.send_request(ctx, MSG_CTX_ARCHIVE, &context_request(context_id)?);
+        // A prefetched tail would otherwise keep answering reads.
+        self.prefetch_cache().invalidate(context_id);
+        let frame =
+            response.map_err(|err| err.resolve_unsupported("CTX_ARCHIVE").resolve_not_found())?;
+        parse_context_head(&frame.payload)
+    }
+
+    /// Brings an archived context back, so it can be read and appended to
+    /// again. Restoring an active context changes nothing.
+    pub fn unarchive_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<ContextHead> {
+        let frame = self
+            .send_request(ctx, MSG_CTX_RESTORE, &context_request(context_id)?)
+            .map_err(|err| err.resolve_unsupported("CTX_RESTORE").resolve_not_found())?;
+        parse_context_head(&frame.payload)
+    }
+
+    /// Lists up to `opts.limit` contexts, most recently active first, with
+    /// whether each is archived.
+    pub fn list_contexts(
+        &self,
+        ctx: &RequestContext,
+        opts: ListContextsOptions,
+    ) -> Result<Vec<ContextSummary>> {
+        let mut payload = Vec::with_capacity(8);
+        payload.write_u32::<LittleEndian>(opts.limit)?;
+        payload.write_u32::<LittleEndian>(opts.filter.wire())?;
+        let frame = self
+            .send_request(ctx, MSG_LIST_CONTEXTS, &payload)
+            .map_err(|err| err.resolve_unsupported("LIST_CONTEXTS"))?;
+        parse_context_list(&frame.payload)
+    }
+
+    /// Runs `read`; if it finds `context_id` archived, restores the context
+    /// and runs `read` once more. For reads with
+    /// [`GetLastOptions::auto_restore`](crate::GetLastOptions::auto_restore).
+    pub(crate) fn restoring<T>(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        mut read: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        match read() {
+            Err(Error::ContextArchived { .. }) => {
+                self.unarchive_context(ctx, context_id)?;
+                read()
+            }
+            result => result,
+        }
+    }
+}
+
+pub(crate) fn context_request(context_id: ContextId) -> Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(8);
+    payload.write_u64::<LittleEndian>(context_id.get())?;
+    Ok(payload)
+}
+
+/// Parses a LIST_CONTEXTS (or LIST_CHILDREN) response.
+pub(crate) fn parse_context_list(payload: &[u8]) -> Result<Vec<ContextSummary>> {
+    let mut reader = PayloadReader::new(payload, "context list");
+    let count = reader.u32("count")?;
+    let mut contexts = Vec::with_capacity((count as usize).min(reader.remaining() / 32));
+    for _ in 0..count {
+        contexts.push(read_context_summary(&mut reader)?);
+    }
+    Ok(contexts)
+}
+
+pub(crate) fn read_context_summary(reader: &mut PayloadReader<'_>) -> Result<ContextSummary> {
+    Ok(ContextSummary {
+        context_id: reader.u64("context_id")?.into(),
+        head_turn_id: reader.u64("head_turn_id")?.into(),
+        head_depth: reader.u32("head_depth")?,
+        archived: reader.u32("flags")? & CONTEXT_FLAG_ARCHIVED != 0,
+        updated_at_unix_ms: reader.u64("updated_at_unix_ms")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_ERROR, MSG_GET_LAST};
+    use crate::test_util::{
+        error_payload, not_found_payload, spawn_scripted_server, turn_page_payload,
+    };
+    use crate::{AppendRequest, GetLastOptions};
+
+    /// ERROR 423 naming the archived context in the trailer.
+    fn archived_error(context_id: u64) -> Vec<u8> {
+        let mut out = error_payload(423, &format!("context {context_id} is archived"));
+        out.write_u32::<LittleEndian>(0).unwrap();
+        out.write_u32::<LittleEndian>(1).unwrap();
+        for field in ["context_id", &context_id.to_string()] {
+            out.write_u32::<LittleEndian>(field.len() as u32).unwrap();
+            out.extend_from_slice(field.as_bytes());
+        }
+        out
+    }
+
+    fn head(context_id: u64, flags: u32) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&9u64.to_le_bytes());
+        out.extend_from_slice(&4u32.to_le_bytes());
+        out.extend_from_slice(&flags.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn archived_contexts_fail_reads_and_appends_unless_restored() {
+        let mut list = 2u32.to_le_bytes().to_vec();
+        list.extend_from_slice(&head(3, 1));
+        list.extend_from_slice(&1_000u64.to_le_bytes());
+        list.extend_from_slice(&head(5, 0));
+        list.extend_from_slice(&900u64.to_le_bytes());
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_ARCHIVE, head(3, 1)),
+            (MSG_LIST_CONTEXTS, list),
+            (MSG_ERROR, archived_error(3)),
+            (MSG_ERROR, archived_error(3)),
+            // auto_restore: the failed read, the restore, the read again.
+            (MSG_ERROR, archived_error(3)),
+            (MSG_CTX_RESTORE, head(3, 0)),
+            (MSG_GET_LAST, turn_page_payload(9, &[&b"\x90"[..]])),
+            (MSG_ERROR, not_found_payload("context", 8)),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let archived = client.archive_context(&ctx, ContextId::new(3)).unwrap();
+        assert_eq!((archived.context_id.get(), archived.head_depth), (3, 4));
+        let listed = client
+            .list_contexts(&ctx, ListContextsOptions::default().limit(10))
+            .unwrap();
+        assert_eq!(
+            listed[0],
+            ContextSummary {
+                context_id: ContextId::new(3),
+                head_turn_id: TurnId::new(9),
+                head_depth: 4,
+                archived: true,
+                updated_at_unix_ms: 1_000,
+            }
+        );
+        assert!(!listed[1].archived);
+
+        let is_archived = |err: Error| matches!(err, Error::ContextArchived { context_id } if context_id.get() == 3);
+        let opts = GetLastOptions::default().include_payload(true);
+        assert!(is_archived(
+            client
+                .get_last(&ctx, ContextId::new(3), opts.clone())
+                .unwrap_err()
+        ));
+        let req = AppendRequest::new(ContextId::new(3), "test", 1, b"\x90".to_vec());
+        assert!(is_archived(client.append_turn(&ctx, &req).unwrap_err()));
+        let turns = client
+            .get_last(&ctx, ContextId::new(3), opts.auto_restore(true))
+            .unwrap();
+        assert_eq!(turns[0].turn_id, TurnId::new(9));
+
+        let err = client
+            .unarchive_context(&ctx, ContextId::new(8))
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 8),
+            "{err:?}"
+        );
+
+        let requests = handle.join().unwrap();
+        let types: Vec<u16> = requests.iter().map(|r| r.header.msg_type).collect();
+        assert_eq!(
+            types,
+            [
+                MSG_CTX_ARCHIVE,
+                MSG_LIST_CONTEXTS,
+                MSG_GET_LAST,
+                MSG_APPEND_TURN,
+                MSG_GET_LAST,
+                MSG_CTX_RESTORE,
+                MSG_GET_LAST,
+                MSG_CTX_RESTORE,
+            ]
+        );
+        assert_eq!(requests[0].payload, 3u64.to_le_bytes());
+        assert_eq!(requests[1].payload[..4], 10u32.to_le_bytes());
+        assert_eq!(requests[1].payload[4..], 0u32.to_le_bytes());
+    }
+}
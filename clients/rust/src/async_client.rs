@@ -0,0 +1,724 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! An async client over any [`Transport`].
+//!
+//! [`AsyncClient`] covers the core context and turn operations. It is the
+//! client for wasm32, where the blocking [`Client`](crate::Client) is not
+//! built; on native targets it runs over [`TcpTransport`] by default.
+//!
+//! It holds one connection and pipelines over it: methods take `&self`, so
+//! requests awaited concurrently (say, a join of independent
+//! [`AsyncClient::get_turn`] calls) are all written before any response is
+//! read, and responses are matched back to their requests by req_id. It
+//! negotiates no optional protocol features and never retries: a transport
+//! or framing failure fails every request in flight, the one that hit it
+//! with the underlying error and the rest with [`Error::ConnectionClosed`],
+//! and after it every request fails with [`Error::ConnectionClosed`] and the
+//! client should be reconnected.
+//!
+//! ```ignore
+//! use cxdb::async_client::AsyncClient;
+//! use cxdb::websocket::WebSocketTransport;
+//! use cxdb::{encode_msgpack, AppendRequest, CreateContextOptions};
+//!
+//! let client = AsyncClient::<WebSocketTransport>::connect("wss://cxdb.example/ws", "web").await?;
+//! let head = client.create_context(CreateContextOptions::default()).await?;
+//! let payload = encode_msgpack(&"hello")?;
+//! client
+//!     .append_turn(&AppendRequest::new(head.context_id, "app.Note", 1, payload))
+//!     .await?;
+//! ```
+//!
+//! [`TcpTransport`]: crate::transport::TcpTransport
+
+use std::collections::HashMap;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::archive::context_request;
+use crate::context::{
+    encode_create_context, encode_create_with_turn, exists, parse_context_details,
+    parse_context_head, parse_create_with_turn, reject_parent, ContextDetails, ContextHead,
+    CreateContextOptions,
+};
+use crate::error::{parse_server_error, Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::{
+    encode_frame, encode_hello, Frame, FLAG_APPEND_META, FLAG_DEPTH_FILTER, FLAG_LINKS,
+    FLAG_REDACTIONS, FLAG_TIMESTAMPS, MAX_FRAME_SIZE, MSG_APPEND_TURN, MSG_CTX_ARCHIVE,
+    MSG_CTX_CREATE, MSG_CTX_CREATE_WITH_TURN, MSG_CTX_RESTORE, MSG_ERROR, MSG_GET_CONTEXT,
+    MSG_GET_HEAD, MSG_GET_LAST, MSG_GET_TURN, MSG_HELLO,
+};
+use crate::transport::{DefaultTransport, Transport};
+use crate::turn::{
+    budget_fetch, budget_fit, budget_listing, encode_append_request, encode_get_last_request,
+    finish_records, get_turn_request, parse_append_result, parse_single_turn, parse_turn_listing,
+    parse_turn_records, AppendRequest, AppendResult, FilterPager, GetLastOptions, TurnFields,
+    TurnRecord,
+};
+
+pub struct AsyncClient<T: Transport = DefaultTransport> {
+    conn: Mutex<Conn<T>>,
+    session_id: u64,
+    /// Whether the server offered GET_LAST depth filters at handshake.
+    depth_filter: bool,
+    links: bool,
+}
+
+/// The connection, shared by every request in flight.
+struct Conn<T> {
+    /// `None` while a request is writing a frame. Reads are polled in place
+    /// by whichever waiting request gets the lock.
+    transport: Option<T>,
+    req_id: u64,
+    poisoned: bool,
+    /// Requests written and not yet answered, by req_id.
+    in_flight: HashMap<u64, Slot>,
+    /// Requests to poll again when the transport is put back, a response is
+    /// read or the connection fails.
+    waiters: Vec<Waker>,
+}
+
+enum Slot {
+    Waiting,
+    /// Read by another request; waiting for its owner to be polled.
+    Answered(Frame),
+    /// Dropped before its response arrived, which is read and discarded.
+    Abandoned,
+}
+
+impl<T> Conn<T> {
+    fn wait(&mut self, cx: &Context<'_>) {
+        if !self.waiters.iter().any(|waker| waker.will_wake(cx.waker())) {
+            self.waiters.push(cx.waker().clone());
+        }
+    }
+
+    fn wake_all(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Closes the connection, failing every request in flight.
+    fn poison(&mut self) {
+        self.poisoned = true;
+        self.transport = None;
+        self.wake_all();
+    }
+}
+
+impl<T: Transport> AsyncClient<T> {
+    /// Connects to `addr` over `T` and says HELLO as `client_tag`.
+    pub async fn connect(addr: &str, client_tag: &str) -> Result<Self> {
+        let mut client = Self {
+            conn: Mutex::new(Conn {
+                transport: Some(T::connect(addr).await?),
+                req_id: 0,
+                poisoned: false,
+                in_flight: HashMap::new(),
+                waiters: Vec::new(),
+            }),
+            session_id: 0,
+            depth_filter: false,
+            links: false,
+        };
+        let frame = client
+            .send_request(
+                MSG_HELLO,
+                FLAG_APPEND_META
+                    | FLAG_TIMESTAMPS
+                    | FLAG_REDACTIONS
+                    | FLAG_DEPTH_FILTER
+                    | FLAG_LINKS,
+                &encode_hello(client_tag, None, &[]),
+            )
+            .await?;
+        if frame.header.msg_type != MSG_HELLO {
+            return Err(Error::protocol(format!(
+                "unexpected response type: {}",
+                frame.header.msg_type
+            )));
+        }
+        if let Some(session) = frame.payload.first_chunk::<8>() {
+            client.session_id = u64::from_le_bytes(*session);
+        }
+        client.depth_filter = frame.header.flags & FLAG_DEPTH_FILTER != 0;
+        client.links = frame.header.flags & FLAG_LINKS != 0;
+        Ok(client)
+    }
+
+    /// The session id the server assigned on HELLO.
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    /// Like [`Client::create_context`](crate::Client::create_context), but
+    /// a parent is sent whatever the server; one that predates parents
+    /// ignores it.
+    pub async fn create_context(&self, opts: CreateContextOptions) -> Result<ContextHead> {
+        let frame = self
+            .send_request(MSG_CTX_CREATE, 0, &encode_create_context(opts)?)
+            .await
+            .map_err(|err| err.resolve_not_found())?;
+        parse_context_head(&frame.payload)
+    }
+
+    /// Like
+    /// [`Client::create_context_with_turn`](crate::Client::create_context_with_turn).
+    pub async fn create_context_with_turn(
+        &self,
+        opts: CreateContextOptions,
+        first: &AppendRequest,
+    ) -> Result<(ContextHead, AppendResult)> {
+        reject_parent(opts, "CTX_CREATE_WITH_TURN")?;
+        if !first.links.is_empty() && !self.links {
+            return Err(Error::Unsupported("turn links".into()));
+        }
+        let mut first = first.clone();
+        first.context_id = ContextId::default();
+        first.delta_base = None;
+        let mut payload = Vec::with_capacity(136 + first.payload.len());
+        let flags = encode_create_with_turn(&mut payload, opts.base_turn_id, &first)?;
+        let frame = self
+            .send_request(MSG_CTX_CREATE_WITH_TURN, flags, &payload)
+            .await
+            .map_err(|err| {
+                err.resolve_unsupported("CTX_CREATE_WITH_TURN")
+                    .resolve_not_found()
+            })?;
+        parse_create_with_turn(&frame.payload)
+    }
+
+    pub async fn get_head(&self, context_id: ContextId) -> Result<ContextHead> {
+        let frame = self
+            .send_request(MSG_GET_HEAD, 0, &context_id.get().to_le_bytes())
+            .await
+            .map_err(|err| err.resolve_not_found())?;
+        parse_context_head(&frame.payload)
+    }
+
+    /// Like [`Client::context_exists`](crate::Client::context_exists).
+    pub async fn context_exists(&self, context_id: ContextId) -> Result<bool> {
+        exists(self.get_head(context_id).await)
+    }
+
+    /// Like [`Client::get_context`](crate::Client::get_context).
+    pub async fn get_context(&self, context_id: ContextId) -> Result<ContextDetails> {
+        let frame = self
+            .send_request(MSG_GET_CONTEXT, 0, &context_id.get().to_le_bytes())
+            .await
+            .map_err(|err| err.resolve_unsupported("GET_CONTEXT").resolve_not_found())?;
+        parse_context_details(&frame.payload)
+    }
+
+    /// Like [`Client::archive_context`](crate::Client::archive_context).
+    pub async fn archive_context(&self, context_id: ContextId) -> Result<ContextHead> {
+        let frame = self
+            .send_request(MSG_CTX_ARCHIVE, 0, &context_request(context_id)?)
+            .await
+            .map_err(|err| err.resolve_unsupported("CTX_ARCHIVE").resolve_not_found())?;
+        parse_context_head(&frame.payload)
+    }
+
+    /// Like [`Client::unarchive_context`](crate::Client::unarchive_context).
+    pub async fn unarchive_context(&self, context_id: ContextId) -> Result<ContextHead> {
+        let frame = self
+            .send_request(MSG_CTX_RESTORE, 0, &context_request(context_id)?)
+            .await
+            .map_err(|err| err.resolve_unsupported("CTX_RESTORE").resolve_not_found())?;
+        parse_context_head(&frame.payload)
+    }
+
+    pub async fn append_turn(&self, req: &AppendRequest) -> Result<AppendResult> {
+        if !req.links.is_empty() && !self.links {
+            return Err(Error::Unsupported("turn links".into()));
+        }
+        let mut payload = Vec::with_capacity(128 + req.payload.len());
+        let flags = encode_append_request(&mut payload, req, None)?;
+        let frame = self
+            .send_request(MSG_APPEND_TURN, flags, &payload)
+            .await
+            .map_err(|err| err.resolve_not_found().resolve_writer_conflict())?;
+        parse_append_result(&frame.payload)
+    }
+
+    /// Like [`Client::get_turn`](crate::Client::get_turn), without the turn
+    /// cache. Independent calls awaited together are pipelined.
+    pub async fn get_turn(&self, turn_id: TurnId) -> Result<TurnRecord> {
+        let frame = self
+            .send_request(MSG_GET_TURN, 0, &get_turn_request(turn_id)?)
+            .await
+            .map_err(|err| err.resolve_not_found())?;
+        parse_single_turn(&frame.payload)
+    }
+
+    /// Like [`Client::get_last`](crate::Client::get_last). A `min_sequence`
+    /// is sent without a wait, so the server answers at once.
+    pub async fn get_last(
+        &self,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        if opts.limit == 0 {
+            return Ok(Vec::new());
+        }
+        if !opts.auto_restore {
+            return self.get_last_budgeted(context_id, opts).await;
+        }
+        match self.get_last_budgeted(context_id, opts.clone()).await {
+            Err(Error::ContextArchived { .. }) => {
+                self.unarchive_context(context_id).await?;
+                self.get_last_budgeted(context_id, opts).await
+            }
+            result => result,
+        }
+    }
+
+    /// [`AsyncClient::get_last`] without restoring archived contexts.
+    async fn get_last_budgeted(
+        &self,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        if opts.byte_budget.is_none() {
+            return self.get_last_filtered(context_id, opts).await;
+        }
+        let listing = self
+            .get_last_filtered(context_id, budget_listing(&opts))
+            .await?;
+        let Some(newest) = listing.first().map(|turn| turn.turn_id) else {
+            return Ok(Vec::new());
+        };
+        let fit = budget_fit(&listing, &opts);
+        self.get_last_filtered(context_id, budget_fetch(opts, newest, fit))
+            .await
+    }
+
+    /// [`AsyncClient::get_last`] without a byte budget.
+    async fn get_last_filtered(
+        &self,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        let pages =
+            opts.type_filter.is_some() || opts.depth_range().is_some() && !self.depth_filter;
+        if !pages {
+            return self.get_last_page(context_id, opts).await;
+        }
+        let mut pager = FilterPager::new(&opts);
+        while let Some(page) = pager.next_page() {
+            pager.push(self.get_last_page(context_id, page).await?);
+        }
+        let mut records = pager.finish();
+        finish_records(&mut records, &opts)?;
+        Ok(records)
+    }
+
+    async fn get_last_page(
+        &self,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        // Projection is not negotiated here; finish_records clears the
+        // fields left out.
+        let wire = GetLastOptions {
+            projection: TurnFields::ALL,
+            ..opts.clone()
+        };
+        let payload = encode_get_last_request(context_id, &wire, Duration::ZERO)?;
+        let frame = self
+            .send_request(MSG_GET_LAST, 0, &payload)
+            .await
+            .map_err(|err| err.resolve_not_found())?;
+        let mut records = if opts.include_payload {
+            parse_turn_records(&frame.payload)?
+        } else {
+            parse_turn_listing(&frame.payload)?
+        };
+        finish_records(&mut records, &opts)?;
+        Ok(records)
+    }
+
+    /// Sends one request and awaits its response, poisoning the client on
+    /// any transport or framing failure.
+    async fn send_request(&self, msg_type: u16, flags: u16, payload: &[u8]) -> Result<Frame> {
+        let mut request = self.register()?;
+        self.write(&encode_frame(msg_type, flags, request.req_id, payload))
+            .await?;
+        request.written = true;
+        // Requests polled alongside this one get written before it blocks
+        // on a read.
+        yield_now().await;
+        let frame = poll_fn(|cx| self.poll_response(cx, request.req_id)).await?;
+        if frame.header.msg_type == MSG_ERROR {
+            return Err(parse_server_error(&frame.payload));
+        }
+        Ok(frame)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Conn<T>> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Assigns the next req_id and records it as in flight.
+    fn register(&self) -> Result<InFlight<'_, T>> {
+        let mut conn = self.lock();
+        if conn.poisoned {
+            return Err(Error::ConnectionClosed);
+        }
+        conn.req_id += 1;
+        let req_id = conn.req_id;
+        conn.in_flight.insert(req_id, Slot::Waiting);
+        Ok(InFlight {
+            conn: &self.conn,
+            req_id,
+            written: false,
+        })
+    }
+
+    /// Writes one frame, waiting for any write in progress to finish first.
+    async fn write(&self, frame: &[u8]) -> Result<()> {
+        let mut writing = poll_fn(|cx| {
+            let mut conn = self.lock();
+            if conn.poisoned {
+                return Poll::Ready(Err(Error::ConnectionClosed));
+            }
+            match conn.transport.take() {
+                Some(transport) => Poll::Ready(Ok(Writing {
+                    conn: &self.conn,
+                    transport: Some(transport),
+                    done: false,
+                })),
+                None => {
+                    conn.wait(cx);
+                    Poll::Pending
+                }
+            }
+        })
+        .await?;
+        let transport = writing.transport.as_mut().expect("taken for the write");
+        transport.write_frame(frame).await?;
+        writing.done = true;
+        Ok(())
+    }
+
+    /// Polls for the response to `req_id`, reading frames (and handing
+    /// them to the requests they answer) until it arrives.
+    fn poll_response(&self, cx: &mut Context<'_>, req_id: u64) -> Poll<Result<Frame>> {
+        let mut conn = self.lock();
+        if let Some(Slot::Answered(_)) = conn.in_flight.get(&req_id) {
+            let Some(Slot::Answered(frame)) = conn.in_flight.remove(&req_id) else {
+                unreachable!()
+            };
+            return Poll::Ready(Ok(frame));
+        }
+        loop {
+            if conn.poisoned {
+                conn.in_flight.remove(&req_id);
+                return Poll::Ready(Err(Error::ConnectionClosed));
+            }
+            let Some(transport) = conn.transport.as_mut() else {
+                conn.wait(cx);
+                return Poll::Pending;
+            };
+            // Transports' reads are cancel-safe, so this one may be dropped
+            // unfinished and the next poll, perhaps by another request,
+            // starts a new one.
+            let read = pin!(transport.read_frame(MAX_FRAME_SIZE)).poll(cx);
+            let frame = match read {
+                Poll::Pending => {
+                    conn.wait(cx);
+                    return Poll::Pending;
+                }
+                Poll::Ready(Ok(frame)) => frame,
+                Poll::Ready(Err(err)) => {
+                    conn.in_flight.remove(&req_id);
+                    conn.poison();
+                    return Poll::Ready(Err(err));
+                }
+            };
+            let id = frame.header.req_id;
+            match conn.in_flight.get_mut(&id) {
+                Some(_) if id == req_id => {
+                    conn.in_flight.remove(&req_id);
+                    // Another request takes over reading.
+                    conn.wake_all();
+                    return Poll::Ready(Ok(frame));
+                }
+                Some(slot @ Slot::Waiting) => {
+                    *slot = Slot::Answered(frame);
+                    conn.wake_all();
+                }
+                Some(Slot::Abandoned) => {
+                    conn.in_flight.remove(&id);
+                }
+                Some(Slot::Answered(_)) | None => {
+                    conn.in_flight.remove(&req_id);
+                    conn.poison();
+                    return Poll::Ready(Err(Error::protocol(format!(
+                        "response req_id {id} matches no outstanding request"
+                    ))));
+                }
+            }
+        }
+    }
+}
+
+/// A registered request. Dropped unanswered, its slot is cleared, or kept
+/// to discard the response if the request went out.
+struct InFlight<'a, T> {
+    conn: &'a Mutex<Conn<T>>,
+    req_id: u64,
+    written: bool,
+}
+
+impl<T> Drop for InFlight<'_, T> {
+    fn drop(&mut self) {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let discard = self.written && !conn.poisoned;
+        match conn.in_flight.get_mut(&self.req_id) {
+            Some(slot @ Slot::Waiting) if discard => {
+                *slot = Slot::Abandoned;
+            }
+            Some(_) => {
+                conn.in_flight.remove(&self.req_id);
+            }
+            None => return,
+        }
+        // This request may have been the one the transport would wake.
+        conn.wake_all();
+    }
+}
+
+/// The transport, taken out for a write. Put back when the write finishes;
+/// a write that fails or is dropped half done poisons the connection.
+struct Writing<'a, T> {
+    conn: &'a Mutex<Conn<T>>,
+    transport: Option<T>,
+    done: bool,
+}
+
+impl<T> Drop for Writing<'_, T> {
+    fn drop(&mut self) {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.done && !conn.poisoned {
+            conn.transport = self.transport.take();
+            conn.wake_all();
+        } else {
+            conn.poison();
+        }
+    }
+}
+
+/// Returns `Pending` once, after asking to be polled again.
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TurnId;
+    use crate::test_util::{block_on, not_found_payload, spawn_scripted_server, turn_page_payload};
+    use crate::transport::TcpTransport;
+
+    fn context_head(context_id: u64, head_turn_id: u64) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&head_turn_id.to_le_bytes());
+        out.extend_from_slice(&(head_turn_id as u32).to_le_bytes());
+        out
+    }
+
+    fn append_ack(context_id: u64, turn_id: u64) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&turn_id.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&[0u8; 32]);
+        out
+    }
+
+    #[test]
+    fn round_trips_over_the_default_tcp_transport() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, context_head(7, 0)),
+            (MSG_APPEND_TURN, append_ack(7, 1)),
+            (MSG_GET_LAST, turn_page_payload(1, &[&b"\x91\x01"[..]])),
+        ]);
+
+        let records = block_on(async {
+            let client = AsyncClient::<TcpTransport>::connect(&addr, "async").await?;
+            assert_eq!(client.session_id(), 1);
+            let head = client
+                .create_context(CreateContextOptions::default())
+                .await?;
+            let req = AppendRequest::new(head.context_id, "test", 1, b"\x91\x01".to_vec());
+            assert_eq!(client.append_turn(&req).await?.turn_id, TurnId::new(1));
+            let opts = GetLastOptions {
+                include_payload: true,
+                ..Default::default()
+            };
+            // A zero limit is answered locally; the script has no reply for it.
+            let none = client
+                .get_last(head.context_id, opts.clone().limit(0))
+                .await?;
+            assert!(none.is_empty());
+            client.get_last(head.context_id, opts).await
+        })
+        .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].payload, b"\x91\x01");
+
+        let requests = handle.join().unwrap();
+        let types: Vec<u16> = requests.iter().map(|f| f.header.msg_type).collect();
+        assert_eq!(types, [MSG_CTX_CREATE, MSG_APPEND_TURN, MSG_GET_LAST]);
+        assert_eq!(
+            u64::from_le_bytes(requests[2].payload[..8].try_into().unwrap()),
+            7
+        );
+    }
+
+    /// Polls `futures` together until every one completes.
+    async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+        let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+        let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+        poll_fn(|cx| {
+            for (future, output) in futures.iter_mut().zip(&mut outputs) {
+                if output.is_none() {
+                    if let Poll::Ready(value) = future.as_mut().poll(cx) {
+                        *output = Some(value);
+                    }
+                }
+            }
+            if outputs.iter().any(Option::is_none) {
+                return Poll::Pending;
+            }
+            Poll::Ready(outputs.drain(..).map(Option::unwrap).collect())
+        })
+        .await
+    }
+
+    /// Starts a server that answers HELLO, reads `count` GET_TURNs before
+    /// replying to any, then answers those for which `answer(index)` holds,
+    /// last request first, and hangs up.
+    fn spawn_batch_server(
+        count: usize,
+        answer: impl Fn(usize) -> bool + Send + 'static,
+    ) -> (String, std::thread::JoinHandle<()>) {
+        use crate::protocol::{read_frame, write_frame};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // A client that waits for each response stalls the batch.
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            write_frame(
+                &mut stream,
+                MSG_HELLO,
+                0,
+                hello.header.req_id,
+                &1u64.to_le_bytes(),
+            )
+            .unwrap();
+            let requests: Vec<_> = (0..count)
+                .map(|_| read_frame(&mut stream).unwrap())
+                .collect();
+            for (index, req) in requests.iter().enumerate().rev() {
+                assert_eq!(req.header.msg_type, MSG_GET_TURN);
+                if answer(index) {
+                    let turn_id = u64::from_le_bytes(req.payload[..8].try_into().unwrap());
+                    let payload = turn_page_payload(turn_id, &[&[0x90][..]]);
+                    write_frame(&mut stream, MSG_GET_TURN, 0, req.header.req_id, &payload).unwrap();
+                }
+            }
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn concurrent_requests_are_pipelined_and_matched_by_req_id() {
+        let (addr, handle) = spawn_batch_server(3, |_| true);
+
+        let turns = block_on(async {
+            let client = AsyncClient::<TcpTransport>::connect(&addr, "async").await?;
+            let turns = join_all(
+                [10, 20, 30]
+                    .map(|id| client.get_turn(TurnId::new(id)))
+                    .into(),
+            )
+            .await;
+            turns.into_iter().collect::<Result<Vec<_>>>()
+        })
+        .unwrap();
+        let ids: Vec<u64> = turns.iter().map(|turn| turn.turn_id.get()).collect();
+        assert_eq!(ids, [10, 20, 30]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn a_dropped_connection_fails_every_request_in_flight() {
+        let (addr, handle) = spawn_batch_server(3, |index| index == 1);
+
+        block_on(async {
+            let client = AsyncClient::<TcpTransport>::connect(&addr, "async").await?;
+            let results = join_all(
+                [10, 20, 30]
+                    .map(|id| client.get_turn(TurnId::new(id)))
+                    .into(),
+            )
+            .await;
+            handle.join().unwrap();
+            assert_eq!(results[1].as_ref().unwrap().turn_id, TurnId::new(20));
+            assert!(results[0].is_err());
+            assert!(matches!(results[2], Err(Error::ConnectionClosed)));
+            assert!(matches!(
+                client.get_turn(TurnId::new(10)).await,
+                Err(Error::ConnectionClosed)
+            ));
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn error_frames_resolve_and_transport_failures_poison() {
+        let (addr, handle) =
+            spawn_scripted_server(vec![(MSG_ERROR, not_found_payload("context", 42))]);
+
+        block_on(async {
+            let client = AsyncClient::<TcpTransport>::connect(&addr, "async").await?;
+            let err = client.get_head(ContextId::new(42)).await.unwrap_err();
+            assert!(
+                matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 42),
+                "got {err:?}"
+            );
+
+            // The script is exhausted, so the server hangs up.
+            handle.join().unwrap();
+            assert!(client.get_head(ContextId::new(42)).await.is_err());
+            assert!(matches!(
+                client.get_head(ContextId::new(42)).await,
+                Err(Error::ConnectionClosed)
+            ));
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! One place to configure and connect a client.
+//!
+//! [`ClientBuilder`] gathers an address, TLS and any number of
+//! [`ClientOption`]s, then builds whichever client shape is needed: a
+//! [`Client`], a [`ClientPool`], a [`ReconnectingClient`] or an
+//! [`AsyncClient`]. [`dial`] and [`dial_tls`] are shorthands for it.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use cxdb::metrics::InMemoryMetrics;
+//! use cxdb::ClientBuilder;
+//!
+//! let client = ClientBuilder::new("cxdb.internal:9009")
+//!     .tls()
+//!     .metrics(Arc::new(InMemoryMetrics::default()))
+//!     .connect_timeout(Duration::from_secs(2))
+//!     .client_tag("indexer")
+//!     .build()?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::async_client::AsyncClient;
+use crate::client::{
+    connect, connect_tls, with_bearer_token, with_client_tag, with_dial_timeout,
+    with_request_timeout, Client, ClientOption, ClientOptions,
+};
+#[cfg(doc)]
+use crate::client::{dial, dial_tls};
+use crate::credentials::{with_credentials, CredentialProvider};
+use crate::error::{Error, Result};
+use crate::metrics::{with_metrics, Metrics};
+use crate::namespace::with_namespace;
+use crate::pool::ClientPool;
+use crate::proxy::{with_proxy, with_proxy_from_env, Proxy};
+use crate::reconnect::{dial_reconnecting_inner, ReconnectOption, ReconnectingClient};
+use crate::response_validation::{with_response_validation, ResponseValidation};
+
+/// Configures a connection to one server; see the [module docs](self).
+#[derive(Clone)]
+pub struct ClientBuilder {
+    addr: String,
+    tls: bool,
+    opts: Vec<ClientOption>,
+}
+
+impl ClientBuilder {
+    /// A builder for `addr`: `host:port`, or with the `http-transport`
+    /// feature an `http://` or `https://` URL.
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            tls: false,
+            opts: Vec::new(),
+        }
+    }
+
+    /// Connects over TLS, verifying the server against the system roots
+    /// unless a [pinned certificate](crate::pinning::with_pinned_cert) is
+    /// given.
+    pub fn tls(mut self) -> Self {
+        self.tls = true;
+        self
+    }
+
+    /// Authenticates with tokens from `provider`; see
+    /// [`with_credentials`].
+    pub fn credentials(self, provider: Arc<dyn CredentialProvider>) -> Self {
+        self.option(with_credentials(provider))
+    }
+
+    /// Authenticates with a fixed token; see [`with_bearer_token`].
+    pub fn bearer_token(self, token: impl Into<String>) -> Self {
+        self.option(with_bearer_token(token))
+    }
+
+    /// Reports every request to `metrics`; see [`with_metrics`].
+    pub fn metrics(self, metrics: Arc<dyn Metrics>) -> Self {
+        self.option(with_metrics(metrics))
+    }
+
+    /// How long connecting may take; see [`with_dial_timeout`].
+    pub fn connect_timeout(self, timeout: Duration) -> Self {
+        self.option(with_dial_timeout(timeout))
+    }
+
+    /// Dials through `proxy`; see [`crate::proxy`].
+    pub fn proxy(self, proxy: Proxy) -> Self {
+        self.option(with_proxy(proxy))
+    }
+
+    /// Dials through the proxy in `ALL_PROXY`, honoring `NO_PROXY`; see
+    /// [`with_proxy_from_env`].
+    pub fn proxy_from_env(self, enabled: bool) -> Self {
+        self.option(with_proxy_from_env(enabled))
+    }
+
+    /// Default per-request deadline; see [`with_request_timeout`].
+    pub fn request_timeout(self, timeout: Duration) -> Self {
+        self.option(with_request_timeout(timeout))
+    }
+
+    /// What reads do with malformed turn pages; see
+    /// [`with_response_validation`].
+    pub fn response_validation(self, mode: ResponseValidation) -> Self {
+        self.option(with_response_validation(mode))
+    }
+
+    /// Scopes every request to `namespace`; see [`with_namespace`].
+    pub fn namespace(self, namespace: impl Into<String>) -> Self {
+        self.option(with_namespace(namespace))
+    }
+
+    /// Identifies the client to the server; see [`with_client_tag`].
+    pub fn client_tag(self, tag: impl Into<String>) -> Self {
+        self.option(with_client_tag(tag))
+    }
+
+    /// Adds any other option, applied after those already set.
+    pub fn option(mut self, opt: ClientOption) -> Self {
+        self.opts.push(opt);
+        self
+    }
+
+    /// Adds several options, in order.
+    pub fn options(mut self, opts: impl IntoIterator<Item = ClientOption>) -> Self {
+        self.opts.extend(opts);
+        self
+    }
+
+    /// Connects and says HELLO.
+    pub fn build(&self) -> Result<Client> {
+        if self.tls {
+            connect_tls(&self.addr, self.opts.clone())
+        } else {
+            connect(&self.addr, self.opts.clone())
+        }
+    }
+
+    /// Connects `size` clients sharing the configuration; see
+    /// [`Client::into_pool`].
+    pub fn build_pool(&self, size: usize) -> Result<ClientPool> {
+        self.build()?.into_pool(size)
+    }
+
+    /// Connects a client that redials and replays requests when the
+    /// connection drops; see [`crate::reconnect`].
+    pub fn build_reconnecting(
+        &self,
+        reconnect_opts: impl IntoIterator<Item = ReconnectOption>,
+    ) -> Result<ReconnectingClient> {
+        dial_reconnecting_inner(&self.addr, self.tls, reconnect_opts, self.opts.clone())
+    }
+
+    /// Connects an [`AsyncClient`] over plain TCP.
+    ///
+    /// The async client negotiates no optional features, so of the
+    /// options only the client tag applies. TLS, credentials and proxies
+    /// fail with [`Error::Unsupported`] rather than connecting without them.
+    pub async fn build_async(&self) -> Result<AsyncClient> {
+        let mut options = ClientOptions::default();
+        for opt in &self.opts {
+            opt(&mut options);
+        }
+        if self.tls {
+            return Err(Error::Unsupported("TLS for the async client".into()));
+        }
+        if options.bearer_token.is_some() || options.credentials.is_some() {
+            return Err(Error::Unsupported(
+                "credentials for the async client".into(),
+            ));
+        }
+        if crate::proxy::proxy_for(&self.addr, &options)?.is_some() {
+            return Err(Error::Unsupported("proxies for the async client".into()));
+        }
+        AsyncClient::connect(&self.addr, &options.client_tag).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ContextId;
+    use crate::metrics::{InMemoryMetrics, Operation};
+    use crate::protocol::MSG_GET_HEAD;
+    use crate::test_util::{block_on, spawn_scripted_server};
+    use crate::RequestContext;
+
+    fn head_payload() -> Vec<u8> {
+        let mut out = 1u64.to_le_bytes().to_vec();
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn build_applies_every_option() {
+        let (addr, server) = spawn_scripted_server(vec![(MSG_GET_HEAD, head_payload())]);
+        let metrics = Arc::new(InMemoryMetrics::default());
+        let client = ClientBuilder::new(addr)
+            .metrics(metrics.clone())
+            .connect_timeout(Duration::from_secs(2))
+            .client_tag("builder")
+            .option(crate::with_read_only(true))
+            .build()
+            .unwrap();
+        assert_eq!(client.client_tag(), "builder");
+        assert!(client.is_read_only());
+
+        client
+            .get_head(&RequestContext::background(), ContextId::new(1))
+            .unwrap();
+        client.close().unwrap();
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_eq!(metrics.stats(Operation::GetHead).requests, 1);
+    }
+
+    #[test]
+    fn build_async_refuses_what_it_cannot_honor() {
+        let builder = ClientBuilder::new("127.0.0.1:1");
+        let proxy = Proxy::Socks5 {
+            addr: "127.0.0.1:2".into(),
+            auth: None,
+        };
+        for builder in [
+            builder.clone().tls(),
+            builder.clone().bearer_token("secret"),
+            builder.proxy(proxy),
+        ] {
+            match block_on(builder.build_async()) {
+                Err(Error::Unsupported(_)) => {}
+                other => panic!("expected unsupported, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
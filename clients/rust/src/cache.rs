@@ -0,0 +1,412 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opt-in in-process cache of turns and their payloads.
+//!
+//! Turns never change once written, short of redaction, so a client that renders the same
+//! history repeatedly can keep the turns it has fetched. Install a cache
+//! with [`with_turn_cache`]; [`Client::get_turn`] then answers repeated ids
+//! without a round trip, and [`Client::get_last`] with `include_payload`
+//! lists the turns without their payloads and fetches only the payloads it
+//! has not cached, and so does [`Client::get_range`], which reads through
+//! it.
+//!
+//! Entries are keyed by turn id, which the server never reuses across
+//! contexts, so forks share the entries of their common history. Clients
+//! in different [namespaces](crate::namespace) keep apart entries even when
+//! they share a cache. The least
+//! recently used entries are evicted beyond `max_entries` or `max_bytes`.
+//! Expiry is recomputed on each hit from the cached `expires_at_unix_ms`.
+//! [`Client::redact_turn`] drops the redacted turn's entry, and so does a
+//! listing that reports a turn redacted; until then a cache can still
+//! answer [`Client::get_turn`] for a turn another client redacted.
+//! [`Client::prune_context`] and [`Client::delete_contexts`] drop the
+//! entries read through the contexts they touch, along with entries
+//! fetched by id alone, which may belong to any context.
+//! Hits and misses are reported to [`Metrics::on_turn_cache`].
+//!
+//! [`Client::get_turn`]: crate::Client::get_turn
+//! [`Client::get_last`]: crate::Client::get_last
+//! [`Client::get_range`]: crate::Client::get_range
+//! [`Client::prune_context`]: crate::Client::prune_context
+//! [`Client::delete_contexts`]: crate::Client::delete_contexts
+//! [`Client::redact_turn`]: crate::Client::redact_turn
+//! [`Metrics::on_turn_cache`]: crate::metrics::Metrics::on_turn_cache
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::ClientOption;
+use crate::ids::{ContextId, TurnId};
+use crate::turn::TurnRecord;
+
+/// Bounds of a turn cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub max_entries: usize,
+    /// Payload and type id bytes the cache may hold.
+    pub max_bytes: usize,
+    /// Re-hash cached payloads on every hit and drop entries that no longer
+    /// match their BLAKE3 `payload_hash`, e.g. to guard against memory
+    /// corruption in long-lived processes.
+    pub verify_hashes: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 4096,
+            max_bytes: 64 * 1024 * 1024,
+            verify_hashes: false,
+        }
+    }
+}
+
+/// Caches turns fetched by the client and every connection redialed from
+/// it. Clients dialed with the same option share one cache.
+pub fn with_turn_cache(config: CacheConfig) -> ClientOption {
+    let cache = Arc::new(TurnCache::new(config));
+    Arc::new(move |opts| opts.turn_cache = Some(cache.clone()))
+}
+
+/// LRU map of namespace and turn id to record. `order` maps each entry's
+/// last use tick to its key, so the oldest entry is the first key.
+pub(crate) struct TurnCache {
+    config: CacheConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<Key, Entry>,
+    order: BTreeMap<u64, Key>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl std::fmt::Debug for TurnCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TurnCache")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+/// A namespace and a turn id in it.
+type Key = (String, TurnId);
+
+struct Entry {
+    record: TurnRecord,
+    tick: u64,
+    /// The contexts the turn was listed in; empty if fetched by id alone.
+    contexts: Vec<ContextId>,
+}
+
+fn entry_bytes(record: &TurnRecord) -> usize {
+    record.payload.len() + record.type_id.len()
+}
+
+impl TurnCache {
+    pub(crate) fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// The cached turn `turn_id` of `namespace`, if any, with `expired`
+    /// brought up to date.
+    pub(crate) fn get(&self, namespace: &str, turn_id: TurnId) -> Option<TurnRecord> {
+        let key = (namespace.to_owned(), turn_id);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = state.entries.get(&key)?;
+        let old_tick = entry.tick;
+        if self.config.verify_hashes
+            && blake3::hash(&entry.record.payload).as_bytes() != &entry.record.payload_hash
+        {
+            state.remove(&key);
+            return None;
+        }
+        state.tick += 1;
+        let new_tick = state.tick;
+        state.order.remove(&old_tick);
+        state.order.insert(new_tick, key.clone());
+        let entry = state.entries.get_mut(&key)?;
+        entry.tick = new_tick;
+        let mut record = entry.record.clone();
+        if let Some(expires_at) = record.expires_at_unix_ms {
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64);
+            record.expired = expires_at <= now_ms;
+        }
+        Some(record)
+    }
+
+    /// Drops the cached turn `turn_id` of `namespace`, if any.
+    pub(crate) fn remove(&self, namespace: &str, turn_id: TurnId) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.remove(&(namespace.to_owned(), turn_id));
+    }
+
+    /// Drops every cached turn.
+    pub(crate) fn clear(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = State::default();
+    }
+
+    /// Drops the turns of `namespace` listed in `context_id`, and those
+    /// fetched by id alone.
+    pub(crate) fn invalidate(&self, namespace: &str, context_id: ContextId) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let stale: Vec<Key> = state
+            .entries
+            .iter()
+            .filter(|((ns, _), entry)| {
+                ns == namespace
+                    && (entry.contexts.is_empty() || entry.contexts.contains(&context_id))
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            state.remove(key);
+        }
+    }
+
+    /// Caches `record`, read in `namespace` and listed in `context_id` if
+    /// given, if it carries its payload and fits the bounds.
+    pub(crate) fn insert(
+        &self,
+        namespace: &str,
+        context_id: Option<ContextId>,
+        record: &TurnRecord,
+    ) {
+        let size = entry_bytes(record);
+        if record.payload_omitted
+            || record.payload.len() != record.payload_size as usize
+            || self.config.max_entries == 0
+            || size > self.config.max_bytes
+        {
+            return;
+        }
+        let key = (namespace.to_owned(), record.turn_id);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut contexts = state.remove(&key).map_or_else(Vec::new, |old| old.contexts);
+        if let Some(context_id) = context_id.filter(|id| !contexts.contains(id)) {
+            contexts.push(context_id);
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(
+            key.clone(),
+            Entry {
+                record: record.clone(),
+                tick,
+                contexts,
+            },
+        );
+        state.order.insert(tick, key);
+        state.bytes += size;
+        while state.entries.len() > self.config.max_entries || state.bytes > self.config.max_bytes {
+            let Some((_, oldest)) = state.order.pop_first() else {
+                break;
+            };
+            state.remove(&oldest);
+        }
+    }
+}
+
+impl State {
+    fn remove(&mut self, key: &Key) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.bytes -= entry_bytes(&entry.record);
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{with_metrics, InMemoryMetrics};
+    use crate::protocol::{FLAG_DEPTH_FILTER, MSG_CTX_DELETE_MANY, MSG_GET_LAST, MSG_GET_TURN};
+    use crate::test_util::{
+        spawn_scripted_server, spawn_scripted_server_with_flags, turn_listing_payload,
+        turn_page_payload, turn_records_payload,
+    };
+    use crate::turn::{parse_turn_records, GetLastOptions, GetTurnOptions};
+    use crate::{dial, DeleteFilter, RequestContext};
+
+    fn record(turn_id: u64, payload: &[u8]) -> TurnRecord {
+        let mut record = parse_turn_records(&turn_records_payload(&[payload]))
+            .unwrap()
+            .remove(0);
+        record.turn_id = TurnId::new(turn_id);
+        record
+    }
+
+    #[test]
+    fn evicts_least_recently_used_within_bounds() {
+        let cache = TurnCache::new(CacheConfig {
+            max_entries: 2,
+            max_bytes: 1024,
+            verify_hashes: true,
+        });
+        cache.insert("", None, &record(1, b"\x91\x01"));
+        cache.insert("", None, &record(2, b"\x91\x02"));
+        assert!(cache.get("", TurnId::new(1)).is_some());
+        cache.insert("", None, &record(3, b"\x91\x03"));
+        assert!(cache.get("", TurnId::new(2)).is_none());
+        assert!(cache.get("", TurnId::new(1)).is_some() && cache.get("", TurnId::new(3)).is_some());
+
+        // Entries that fail verification are dropped.
+        let mut corrupt = record(4, b"\x91\x04");
+        corrupt.payload = b"\x91\x05".to_vec();
+        cache.insert("", None, &corrupt);
+        assert!(cache.get("", TurnId::new(4)).is_none());
+
+        let small = TurnCache::new(CacheConfig {
+            max_entries: 10,
+            max_bytes: 20,
+            verify_hashes: false,
+        });
+        small.insert("", None, &record(1, &[0x90; 8]));
+        small.insert("", None, &record(2, &[0x90; 8]));
+        assert!(small.get("", TurnId::new(1)).is_none() && small.get("", TurnId::new(2)).is_some());
+        small.insert("", None, &record(3, &[0x90; 32]));
+        assert!(small.get("", TurnId::new(3)).is_none());
+        // The same turn id in another namespace is another entry.
+        small.insert("eu", None, &record(2, &[0x91; 8]));
+        assert!(small.get("us", TurnId::new(2)).is_none());
+        assert_eq!(small.get("eu", TurnId::new(2)).unwrap().payload, [0x91; 8]);
+    }
+
+    #[test]
+    fn invalidation_drops_the_context_and_id_only_entries() {
+        let cache = TurnCache::new(CacheConfig::default());
+        let (one, two) = (ContextId::new(1), ContextId::new(2));
+        cache.insert("", Some(one), &record(1, b"\x91\x01"));
+        cache.insert("", Some(two), &record(2, b"\x91\x02"));
+        // A fork point listed in both contexts.
+        cache.insert("", Some(one), &record(3, b"\x91\x03"));
+        cache.insert("", Some(two), &record(3, b"\x91\x03"));
+        cache.insert("", None, &record(4, b"\x91\x04"));
+        cache.insert("eu", Some(one), &record(1, b"\x91\x01"));
+
+        cache.invalidate("", one);
+        let kept: Vec<u64> = (1..=4)
+            .filter(|&id| cache.get("", TurnId::new(id)).is_some())
+            .collect();
+        assert_eq!(kept, [2]);
+        assert!(cache.get("eu", TurnId::new(1)).is_some());
+    }
+
+    #[test]
+    fn ranges_read_through_the_cache_until_a_delete() {
+        let payloads: [&[u8]; 2] = [b"\x91\x01", b"\x91\x02"];
+        let listing = turn_listing_payload(&payloads);
+        let turn = |i: usize| turn_page_payload(i as u64 + 1, &payloads[i..=i]);
+        let fetch = [
+            (MSG_GET_LAST, listing.clone()),
+            (MSG_GET_TURN, turn(0)),
+            (MSG_GET_TURN, turn(1)),
+        ];
+        let mut script = fetch.to_vec();
+        script.push((MSG_GET_LAST, listing));
+        script.push((MSG_CTX_DELETE_MANY, 1u64.to_le_bytes().to_vec()));
+        script.extend(fetch);
+        let (addr, handle) = spawn_scripted_server_with_flags(FLAG_DEPTH_FILTER, script);
+        let client = dial(&addr, [with_turn_cache(CacheConfig::default())]).unwrap();
+        let ctx = RequestContext::background();
+        let range = || {
+            let turns = client
+                .get_range(&ctx, ContextId::new(1), 1, 2, GetTurnOptions::default())
+                .unwrap();
+            let payloads: Vec<_> = turns.into_iter().map(|turn| turn.payload).collect();
+            assert_eq!(payloads, [b"\x91\x01", b"\x91\x02"]);
+        };
+
+        range();
+        range();
+        let filter = DeleteFilter::new().ids([ContextId::new(1)]);
+        assert_eq!(client.delete_contexts(&ctx, &filter).unwrap(), 1);
+        range();
+
+        let requests = handle.join().unwrap();
+        let types: Vec<_> = requests.iter().map(|f| f.header.msg_type).collect();
+        assert_eq!(
+            types,
+            [
+                MSG_GET_LAST,
+                MSG_GET_TURN,
+                MSG_GET_TURN,
+                MSG_GET_LAST,
+                MSG_CTX_DELETE_MANY,
+                MSG_GET_LAST,
+                MSG_GET_TURN,
+                MSG_GET_TURN
+            ]
+        );
+    }
+
+    #[test]
+    fn client_reads_payloads_through_the_cache() {
+        let payloads: [&[u8]; 2] = [b"\x91\x01", b"\x91\x02"];
+        let records = parse_turn_records(&turn_records_payload(&payloads)).unwrap();
+        // GET_TURN response for the `i`th turn of the listing.
+        let turn = |i: usize| {
+            let mut one = turn_records_payload(&payloads[i..=i]);
+            one[4..12].copy_from_slice(&records[i].turn_id.get().to_le_bytes());
+            one[12..20].copy_from_slice(&records[i].parent_id.get().to_le_bytes());
+            one[20..24].copy_from_slice(&records[i].depth.to_le_bytes());
+            one
+        };
+        let listing = turn_listing_payload(&payloads);
+
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_TURN, turn(0)),
+            (MSG_GET_LAST, listing.clone()),
+            (MSG_GET_TURN, turn(1)),
+            (MSG_GET_LAST, listing),
+        ]);
+        let metrics = Arc::new(InMemoryMetrics::default());
+        let client = dial(
+            &addr,
+            [
+                with_turn_cache(CacheConfig::default()),
+                with_metrics(metrics.clone()),
+            ],
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let first = client.get_turn(&ctx, records[0].turn_id).unwrap();
+        assert_eq!(client.get_turn(&ctx, records[0].turn_id).unwrap(), first);
+        // Only the second payload is fetched; the next read is all hits.
+        assert_eq!(
+            client
+                .get_last(&ctx, ContextId::new(1), opts.clone())
+                .unwrap(),
+            records
+        );
+        assert_eq!(
+            client.get_last(&ctx, ContextId::new(1), opts).unwrap(),
+            records
+        );
+        assert_eq!((metrics.cache_hits(), metrics.cache_misses()), (4, 2));
+
+        let requests = handle.join().unwrap();
+        let types: Vec<_> = requests.iter().map(|f| f.header.msg_type).collect();
+        assert_eq!(
+            types,
+            [MSG_GET_TURN, MSG_GET_LAST, MSG_GET_TURN, MSG_GET_LAST]
+        );
+        // Listings ask for metadata only.
+        assert_eq!(requests[1].payload[12..16], 0u32.to_le_bytes());
+    }
+}
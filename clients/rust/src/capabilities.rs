@@ -0,0 +1,170 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! What the server supports, as learned at the HELLO handshake.
+//!
+//! The HELLO response carries the protocol version and a bitset of the
+//! message types the server handles, and its frame flags echo the optional
+//! behaviours the client asked for (checksums, projection, depth filters,
+//! ...). [`Client::server_capabilities`] returns both. A request whose
+//! message type the server did not advertise fails with
+//! [`Error::Unsupported`](crate::Error::Unsupported) before anything is
+//! sent, and methods with a client-side fallback, such as
+//! [`Client::context_stats`](crate::Client::context_stats), take it
+//! straight away.
+//!
+//! Servers that predate the bitset advertise nothing; every message type is
+//! then assumed, and an unknown one is rejected by the server with the same
+//! error after a round trip.
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::Client;
+use crate::protocol::msg_type_name;
+
+/// The server's side of the handshake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Protocol version the server speaks.
+    pub protocol_version: u16,
+    /// HELLO flags the server echoed, e.g.
+    /// [`FLAG_PROJECTION`](crate::protocol::FLAG_PROJECTION).
+    pub flags: u16,
+    /// Bit `n` set: the server handles message type `n`. `None` from servers
+    /// that do not advertise their message types.
+    pub message_types: Option<u64>,
+}
+
+impl Capabilities {
+    /// Whether the server echoed `flag` at HELLO.
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.flags & flag == flag
+    }
+
+    /// Whether the server handles `msg_type`; always true when it did not
+    /// advertise its message types.
+    pub fn supports(&self, msg_type: u16) -> bool {
+        match self.message_types {
+            Some(bits) => msg_type < 64 && bits & (1 << msg_type) != 0,
+            None => true,
+        }
+    }
+
+    /// The name [`Error::Unsupported`](crate::Error::Unsupported) reports for
+    /// `msg_type`.
+    pub(crate) fn unsupported_name(msg_type: u16) -> String {
+        match msg_type_name(msg_type) {
+            Some(name) => name.to_string(),
+            None => format!("message type {msg_type}"),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// What the server advertised at the handshake (see the
+    /// [module docs](self)).
+    pub fn server_capabilities(&self) -> Capabilities {
+        self.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::client::RequestContext;
+    use crate::protocol::{
+        read_frame, write_frame, FLAG_PROJECTION, MSG_CONTEXT_STATS, MSG_GET_LAST, MSG_GET_QUOTAS,
+        MSG_HELLO,
+    };
+    use crate::test_util::{spawn_scripted_server, turn_page_listing};
+    use crate::turn::encode_get_last_request;
+    use crate::{dial, Error, GetLastOptions};
+
+    /// A server advertising HELLO and GET_LAST only, answering GET_LAST with
+    /// an empty page and counting every request after HELLO.
+    fn get_last_only_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            while let Ok(req) = read_frame(&mut stream) {
+                let (payload, flags) = if req.header.msg_type == MSG_HELLO {
+                    let mut resp = 9u64.to_le_bytes().to_vec();
+                    resp.extend_from_slice(&2u16.to_le_bytes());
+                    let bits = (1u64 << MSG_HELLO) | (1 << MSG_GET_LAST);
+                    resp.extend_from_slice(&bits.to_le_bytes());
+                    (resp, FLAG_PROJECTION)
+                } else {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    (turn_page_listing(1, &[]), 0)
+                };
+                let msg_type = req.header.msg_type;
+                if write_frame(&mut stream, msg_type, flags, req.header.req_id, &payload).is_err() {
+                    break;
+                }
+            }
+        });
+        (addr, requests)
+    }
+
+    #[test]
+    fn unadvertised_messages_fail_without_a_round_trip() {
+        let (addr, requests) = get_last_only_server();
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let caps = client.server_capabilities();
+        assert_eq!(caps.protocol_version, 2);
+        assert!(caps.has_flag(FLAG_PROJECTION));
+        assert!(caps.supports(MSG_GET_LAST));
+        assert!(!caps.supports(MSG_CONTEXT_STATS));
+        assert!(!caps.supports(300));
+
+        let err = client.get_quotas(&ctx).unwrap_err();
+        assert!(
+            matches!(&err, Error::Unsupported(op) if op == "GET_QUOTAS"),
+            "{err:?}"
+        );
+        assert!(!caps.supports(MSG_GET_QUOTAS));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+        // Pipelined batches fail only the unadvertised requests.
+        let get_last = encode_get_last_request(
+            ContextId::new(1),
+            &GetLastOptions::default(),
+            Duration::ZERO,
+        )
+        .unwrap();
+        let batch = client
+            .pipeline(
+                &ctx,
+                &[(MSG_GET_QUOTAS, Vec::new()), (MSG_GET_LAST, get_last)],
+            )
+            .unwrap();
+        assert!(matches!(&batch[0], Err(Error::Unsupported(_))));
+        assert_eq!(batch[1].as_ref().unwrap().header.msg_type, MSG_GET_LAST);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // CONTEXT_STATS falls back to paging GET_LAST straight away.
+        let stats = client.context_stats(&ctx, ContextId::new(1)).unwrap();
+        assert_eq!(stats.turns, 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn servers_without_the_bitset_are_assumed_to_support_everything() {
+        let (addr, _handle) = spawn_scripted_server(vec![]);
+        let client = dial(&addr, []).unwrap();
+        let caps = client.server_capabilities();
+        assert_eq!(caps.protocol_version, 1);
+        assert_eq!(caps.message_types, None);
+        assert!(caps.supports(MSG_CONTEXT_STATS));
+    }
+}
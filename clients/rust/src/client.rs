@@ -1,38 +1,158 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use std::net::{TcpStream, ToSocketAddrs};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use byteorder::{LittleEndian, WriteBytesExt};
 use rustls::pki_types::ServerName;
 use rustls::{ClientConfig, ClientConnection};
 
-use crate::error::{Error, Result};
+use crate::append_queue::{AppendQueue, AppendQueueOptions};
+use crate::builder::ClientBuilder;
+use crate::cache::TurnCache;
+use crate::capabilities::Capabilities;
+use crate::compression::{is_compressed, Codec, FrameCodec};
+use crate::credentials::CredentialProvider;
+use crate::encryption::KeyProvider;
+use crate::error::{parse_server_error, Error, Result};
+#[cfg(feature = "http-transport")]
+use crate::http_tunnel::HttpTunnel;
+use crate::ids::TurnId;
+use crate::interceptor::{intercept, Interceptor};
+use crate::metrics::{Direction, Metrics, Operation};
+use crate::pinning::PinnedCertVerifier;
+use crate::prefetch::PrefetchCache;
+use crate::proto::RequestIds;
+#[cfg(feature = "bytes")]
+use crate::protocol::read_frame_into;
 use crate::protocol::{
-    read_frame, write_frame, Frame, DEFAULT_DIAL_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, MSG_ERROR,
-    MSG_HELLO,
+    encode_frame_into, encode_frame_metadata, encode_frame_with_checksum_into, encode_hello,
+    msg_type_mutates, read_frame_into_vec, read_frame_with_limit, verify_frame_checksum, Frame,
+    FrameHeader, DEFAULT_DIAL_TIMEOUT, DEFAULT_HAPPY_EYEBALLS_DELAY, DEFAULT_PREFETCH_STALENESS,
+    DEFAULT_READ_BUFFER_BYTES, DEFAULT_REQUEST_TIMEOUT, DEFAULT_WRITE_BUFFER_BYTES,
+    FLAG_APPEND_META, FLAG_COMPRESSED, FLAG_CRC32C, FLAG_DEDUP, FLAG_DEPTH_FILTER, FLAG_LINKS,
+    FLAG_METADATA, FLAG_NAMESPACES, FLAG_PROJECTION, FLAG_REDACTIONS, FLAG_SEARCH, FLAG_TIMESTAMPS,
+    FRAME_CHECKSUM_LEN, FRAME_HEADER_LEN, MAX_DECODE_DEPTH, MAX_FRAME_SIZE, MSG_ERROR, MSG_HELLO,
+    PIPELINE_WINDOW,
 };
+use crate::proxy::{self, Proxy};
+use crate::ratelimit::{Limited, RateLimiter};
+use crate::reconnect::DialFunc;
+use crate::replay::{ConnectionRecorder, WireRecorder};
+use crate::response_validation::ResponseValidation;
+use crate::timing::{CallTiming, Phase, TimingScope, TimingSlot};
+use crate::turn::TurnRecord;
+use crate::validate::TurnValidator;
 
 pub type ClientOption = Arc<dyn Fn(&mut ClientOptions) + Send + Sync>;
 
 #[derive(Debug, Clone)]
 pub struct ClientOptions {
     pub dial_timeout: Duration,
+    /// How long a dial waits on one address before racing the next; see
+    /// [`with_happy_eyeballs_delay`].
+    pub happy_eyeballs_delay: Duration,
     pub request_timeout: Duration,
     pub client_tag: String,
+    /// Largest response payload accepted before the connection is dropped.
+    pub max_frame_size: u32,
+    /// Deepest msgpack nesting accepted when the client decodes payloads
+    /// (see [`Client::get_last_lazy`]).
+    pub max_decode_depth: usize,
+    /// Request per-frame CRC32C checksums in HELLO. Servers that do not echo
+    /// the request keep exchanging plain frames.
+    pub frame_checksums: bool,
+    /// Frame compression codecs offered in HELLO, most preferred first; see
+    /// [`crate::compression::with_compression`].
+    pub compression: Vec<Codec>,
+    /// How long a tail fetched by [`Client::prefetch`] may answer `get_last`
+    /// before the context head is rechecked.
+    pub prefetch_staleness: Duration,
+    /// Capacity of the buffered reader responses are read through.
+    pub read_buffer_bytes: usize,
+    /// Capacity the per-connection request scratch buffer keeps between
+    /// requests. Each request frame is assembled there and sent in one write.
+    pub write_buffer_bytes: usize,
+    /// Refuse requests that change server state; see [`with_read_only`].
+    pub read_only: bool,
+    /// What reads do with malformed turn pages; see
+    /// [`crate::response_validation`].
+    pub response_validation: ResponseValidation,
+    /// Namespace every request is scoped to, empty for none; see
+    /// [`crate::namespace`].
+    pub namespace: String,
+    /// Queue behind [`Client::append_async`]; see
+    /// [`crate::append_queue::with_append_queue`].
+    pub append_queue: AppendQueueOptions,
     pub(crate) tls_config: std::option::Option<Arc<ClientConfig>>,
+    /// SPKI hashes added with [`crate::pinning::with_pinned_cert`].
+    pub(crate) pinned_certs: Vec<Vec<u8>>,
+    /// Sent on HELLO; see [`with_bearer_token`].
+    pub(crate) bearer_token: std::option::Option<BearerToken>,
+    /// Installed with [`crate::credentials::with_credentials`]; overrides
+    /// `bearer_token`.
+    pub(crate) credentials: std::option::Option<Arc<dyn CredentialProvider>>,
+    /// Hook installed with [`crate::metrics::with_metrics`].
+    pub(crate) metrics: std::option::Option<Arc<dyn Metrics>>,
+    /// Cache installed with [`crate::cache::with_turn_cache`].
+    pub(crate) turn_cache: std::option::Option<Arc<TurnCache>>,
+    /// Installed with [`crate::replay::with_wire_recording`].
+    pub(crate) wire_recorder: std::option::Option<Arc<WireRecorder>>,
+    /// Installed with [`crate::validate::with_validator`].
+    pub(crate) validator: std::option::Option<Arc<dyn TurnValidator>>,
+    /// Installed with [`crate::interceptor::with_interceptor`], in order.
+    pub(crate) interceptors: Vec<Arc<dyn Interceptor>>,
+    /// Installed with [`crate::encryption::with_payload_encryption`].
+    pub(crate) payload_keys: std::option::Option<Arc<dyn KeyProvider>>,
+    /// Set with [`crate::proxy::with_proxy`].
+    pub(crate) proxy: std::option::Option<Proxy>,
+    /// Set with [`crate::proxy::with_proxy_from_env`].
+    pub(crate) proxy_from_env: bool,
+    /// Installed with [`crate::ratelimit::with_rate_limit`].
+    pub(crate) append_limiter: std::option::Option<Arc<RateLimiter>>,
+    /// Installed with [`crate::ratelimit::with_read_rate_limit`].
+    pub(crate) read_limiter: std::option::Option<Arc<RateLimiter>>,
 }
 
 impl Default for ClientOptions {
     fn default() -> Self {
         Self {
             dial_timeout: DEFAULT_DIAL_TIMEOUT,
+            happy_eyeballs_delay: DEFAULT_HAPPY_EYEBALLS_DELAY,
             request_timeout: DEFAULT_REQUEST_TIMEOUT,
             client_tag: String::new(),
+            max_frame_size: MAX_FRAME_SIZE,
+            max_decode_depth: MAX_DECODE_DEPTH,
+            frame_checksums: true,
+            compression: Vec::new(),
+            prefetch_staleness: DEFAULT_PREFETCH_STALENESS,
+            read_buffer_bytes: DEFAULT_READ_BUFFER_BYTES,
+            write_buffer_bytes: DEFAULT_WRITE_BUFFER_BYTES,
+            read_only: false,
+            response_validation: ResponseValidation::default(),
+            namespace: String::new(),
+            append_queue: AppendQueueOptions::default(),
             tls_config: None,
+            pinned_certs: Vec::new(),
+            bearer_token: None,
+            credentials: None,
+            metrics: None,
+            turn_cache: None,
+            wire_recorder: None,
+            validator: None,
+            interceptors: Vec::new(),
+            payload_keys: None,
+            proxy: None,
+            proxy_from_env: false,
+            append_limiter: None,
+            read_limiter: None,
         }
     }
 }
@@ -41,6 +161,13 @@ pub fn with_dial_timeout(timeout: Duration) -> ClientOption {
     Arc::new(move |opts| opts.dial_timeout = timeout)
 }
 
+/// Sets how long a dial gives one address before it starts a connection to
+/// the next as well (RFC 8305 "happy eyeballs"). The first connection to
+/// succeed wins. Zero tries every address at once.
+pub fn with_happy_eyeballs_delay(delay: Duration) -> ClientOption {
+    Arc::new(move |opts| opts.happy_eyeballs_delay = delay)
+}
+
 pub fn with_request_timeout(timeout: Duration) -> ClientOption {
     Arc::new(move |opts| opts.request_timeout = timeout)
 }
@@ -50,15 +177,114 @@ pub fn with_client_tag(tag: impl Into<String>) -> ClientOption {
     Arc::new(move |opts| opts.client_tag = tag.clone())
 }
 
+pub fn with_max_frame_size(max: u32) -> ClientOption {
+    Arc::new(move |opts| opts.max_frame_size = max)
+}
+
+pub fn with_max_decode_depth(depth: usize) -> ClientOption {
+    Arc::new(move |opts| opts.max_decode_depth = depth)
+}
+
+pub fn with_frame_checksums(enabled: bool) -> ClientOption {
+    Arc::new(move |opts| opts.frame_checksums = enabled)
+}
+
+pub fn with_prefetch_staleness(staleness: Duration) -> ClientOption {
+    Arc::new(move |opts| opts.prefetch_staleness = staleness)
+}
+
+pub fn with_read_buffer_bytes(bytes: usize) -> ClientOption {
+    Arc::new(move |opts| opts.read_buffer_bytes = bytes)
+}
+
+pub fn with_write_buffer_bytes(bytes: usize) -> ClientOption {
+    Arc::new(move |opts| opts.write_buffer_bytes = bytes)
+}
+
+/// Makes the client read-only: every request that would change server state
+/// (appends, context creation, forks, aliases, compaction, pruning,
+/// redaction, blob uploads) fails with [`Error::ReadOnlyClient`] before
+/// anything is written to the connection. Reads are unaffected. This is
+/// enforced by the client only; the server is not told.
+pub fn with_read_only(read_only: bool) -> ClientOption {
+    Arc::new(move |opts| opts.read_only = read_only)
+}
+
+/// Authenticates the session with a bearer token, sent on HELLO at dial and
+/// on every redial. A rejected token fails the dial with
+/// [`Error::Unauthenticated`].
+pub fn with_bearer_token(token: impl Into<String>) -> ClientOption {
+    let token = BearerToken(token.into());
+    Arc::new(move |opts| opts.bearer_token = Some(token.clone()))
+}
+
+/// A bearer token. Its `Debug` output is redacted so options and errors can
+/// be logged without leaking it.
+#[derive(Clone)]
+pub(crate) struct BearerToken(String);
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BearerToken(<redacted>)")
+    }
+}
+
 #[cfg(test)]
 pub(crate) fn with_tls_config(config: Arc<ClientConfig>) -> ClientOption {
     Arc::new(move |opts| opts.tls_config = Some(config.clone()))
 }
 
+/// [`RequestContext`] value key for a caller-chosen request id. Besides being
+/// sent as frame metadata, it is recorded on `tracing` spans (`tracing`
+/// feature).
+pub const REQUEST_ID_KEY: &str = "request_id";
+
+/// [`RequestContext`] value key for a per-request bearer token; see
+/// [`RequestContext::with_auth`].
+pub const AUTH_KEY: &str = "authorization";
+
+/// [`RequestContext`] value key for the namespace a request is scoped to;
+/// see [`crate::namespace`].
+pub const NAMESPACE_KEY: &str = "namespace";
+
+/// Request-scoped deadline, cancellation and values, modelled on Go's
+/// `context.Context`.
+///
+/// Values attached with [`RequestContext::with_value`] are sent to the server
+/// as frame metadata on every request made with the context (for servers
+/// that accept metadata at HELLO), so a request id or tenant hint shows up
+/// in server-side logs.
+///
+/// A token attached with [`RequestContext::with_auth`] travels the same way
+/// and authenticates just the requests made with the context.
 #[derive(Clone, Debug)]
 pub struct RequestContext {
     deadline: std::option::Option<Instant>,
     cancelled: Arc<AtomicBool>,
+    values: std::option::Option<Arc<ContextValue>>,
+    timing: std::option::Option<Arc<TimingSlot>>,
+}
+
+/// One [`RequestContext::with_value`] layer; lookups walk towards the root.
+struct ContextValue {
+    key: String,
+    value: String,
+    parent: std::option::Option<Arc<ContextValue>>,
+}
+
+impl fmt::Debug for ContextValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = if self.key == AUTH_KEY {
+            "<redacted>"
+        } else {
+            self.value.as_str()
+        };
+        f.debug_struct("ContextValue")
+            .field("key", &self.key)
+            .field("value", &value)
+            .field("parent", &self.parent)
+            .finish()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -77,6 +303,8 @@ impl RequestContext {
         Self {
             deadline: None,
             cancelled: Arc::new(AtomicBool::new(false)),
+            values: None,
+            timing: None,
         }
     }
 
@@ -84,6 +312,8 @@ impl RequestContext {
         Self {
             deadline: Some(deadline),
             cancelled: Arc::new(AtomicBool::new(false)),
+            values: None,
+            timing: None,
         }
     }
 
@@ -97,6 +327,8 @@ impl RequestContext {
             Self {
                 deadline: None,
                 cancelled: cancelled.clone(),
+                values: None,
+                timing: None,
             },
             CancelHandle { cancelled },
         )
@@ -109,6 +341,112 @@ impl RequestContext {
     pub fn deadline(&self) -> std::option::Option<Instant> {
         self.deadline
     }
+
+    /// Returns a child context that carries `key = value` in addition to
+    /// this context's values, overriding any parent value for `key`. The
+    /// child shares this context's deadline and cancellation.
+    pub fn with_value(&self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            values: Some(Arc::new(ContextValue {
+                key: key.into(),
+                value: value.into(),
+                parent: self.values.clone(),
+            })),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a child context whose requests present `token` instead of the
+    /// connection's [`with_bearer_token`], e.g. for a gateway acting for
+    /// several principals over pooled connections. The token is sent as
+    /// [`AUTH_KEY`] request metadata on each request made with the child and
+    /// is never stored on the connection, so later requests on the same
+    /// (pooled or redialed) connection fall back to its own credential.
+    ///
+    /// Requests made with the child fail with [`Error::Encode`] on
+    /// connections whose server did not accept metadata at HELLO, rather
+    /// than silently running as the connection's principal.
+    pub fn with_auth(&self, token: impl Into<String>) -> Self {
+        self.with_value(AUTH_KEY, token)
+    }
+
+    /// Returns a child context whose requests are scoped to `namespace`
+    /// instead of the client's [`with_namespace`](crate::namespace::with_namespace);
+    /// an empty `namespace` is the server's unnamespaced one. Sent as
+    /// [`NAMESPACE_KEY`] request metadata.
+    ///
+    /// Requests made with the child fail with [`Error::Unsupported`] on
+    /// connections whose server did not accept namespaces at HELLO, rather
+    /// than silently reaching the wrong namespace's contexts.
+    pub fn with_namespace(&self, namespace: impl Into<String>) -> Self {
+        self.with_value(NAMESPACE_KEY, namespace)
+    }
+
+    /// Returns a child context that records where each call made with it
+    /// spends its time (see [`crate::timing`]). The child shares this
+    /// context's deadline, cancellation and values.
+    pub fn with_timing(&self) -> Self {
+        Self {
+            timing: Some(Arc::new(TimingSlot::new())),
+            ..self.clone()
+        }
+    }
+
+    /// The timing of the last call made with this context (or a clone) that
+    /// finished, if the context was made with [`RequestContext::with_timing`].
+    pub fn last_timing(&self) -> std::option::Option<CallTiming> {
+        self.timing.as_ref().and_then(|slot| slot.last())
+    }
+
+    /// Times a call while the returned scope lives. Scopes nest: only the
+    /// outermost starts and publishes a call.
+    pub(crate) fn timing_scope(&self) -> TimingScope<'_> {
+        TimingScope::new(self.timing.as_deref())
+    }
+
+    /// Moves the current call on to `phase`.
+    #[inline]
+    pub(crate) fn timing_phase(&self, phase: Phase) {
+        if let Some(slot) = &self.timing {
+            slot.enter(phase);
+        }
+    }
+
+    /// Looks up `key`, innermost value first.
+    pub fn get_value(&self, key: &str) -> std::option::Option<&str> {
+        let mut node = self.values.as_deref();
+        while let Some(value) = node {
+            if value.key == key {
+                return Some(&value.value);
+            }
+            node = value.parent.as_deref();
+        }
+        None
+    }
+
+    /// Every visible value by key, with overridden parent values left out.
+    pub fn values(&self) -> BTreeMap<&str, &str> {
+        let mut values = BTreeMap::new();
+        let mut node = self.values.as_deref();
+        while let Some(value) = node {
+            values
+                .entry(value.key.as_str())
+                .or_insert(value.value.as_str());
+            node = value.parent.as_deref();
+        }
+        values
+    }
+
+    /// This context with its values replaced by `values`.
+    pub(crate) fn with_values(&self, values: &BTreeMap<String, String>) -> Self {
+        values.iter().fold(
+            Self {
+                values: None,
+                ..self.clone()
+            },
+            |ctx, (key, value)| ctx.with_value(key.as_str(), value.as_str()),
+        )
+    }
 }
 
 impl Default for RequestContext {
@@ -117,32 +455,125 @@ impl Default for RequestContext {
     }
 }
 
+/// One connection to a CXDB server.
+///
+/// A `Client` is `Send + Sync`; share it between threads behind an [`Arc`].
+/// Each request holds the connection from writing its frame until its
+/// response is read, so concurrent calls never interleave frames, but they
+/// take turns rather than overlapping. For reads and appends in parallel,
+/// use a [`ClientPool`](crate::ClientPool) (see [`Client::into_pool`]).
 pub struct Client {
-    conn: Mutex<Connection>,
-    req_id: AtomicU64,
+    conn: Mutex<Transport>,
+    req_id: RequestIds,
     closed: AtomicBool,
     timeout: Duration,
     session_id: AtomicU64,
+    peer_addr: SocketAddr,
     client_tag: String,
+    max_frame_size: u32,
+    max_decode_depth: usize,
+    /// Set by [`with_read_only`].
+    read_only: bool,
+    /// Set by [`crate::response_validation::with_response_validation`].
+    response_validation: ResponseValidation,
+    /// Set by [`crate::namespace::with_namespace`].
+    namespace: String,
+    /// Set once a framing or I/O error leaves the stream in an unknown state.
+    poisoned: AtomicBool,
+    /// Whether the server accepted CRC32C frame checksums at handshake.
+    checksums: AtomicBool,
+    /// Whether the server accepted request metadata blocks at handshake.
+    metadata: AtomicBool,
+    /// Whether the server offered SEARCH_TURNS at handshake.
+    search: AtomicBool,
+    /// Whether the server offered conditional appends at handshake.
+    dedup: AtomicBool,
+    /// Whether the server offered GET_LAST depth filters at handshake.
+    depth_filter: AtomicBool,
+    /// Whether the server offered GET_LAST field projection at handshake.
+    projection: AtomicBool,
+    /// Whether the server accepted namespaced requests at handshake.
+    namespaces: AtomicBool,
+    /// What the server advertised at handshake; unset until HELLO completes.
+    capabilities: OnceLock<Capabilities>,
+    /// The frame compression codec accepted at handshake, if any.
+    compression: OnceLock<Codec>,
+    redial: DialFunc,
+    credentials: std::option::Option<Arc<dyn CredentialProvider>>,
+    /// The token presented at HELLO, if it came from `credentials`.
+    hello_token: std::option::Option<String>,
+    metrics: std::option::Option<Arc<dyn Metrics>>,
+    turn_cache: std::option::Option<Arc<TurnCache>>,
+    validator: std::option::Option<Arc<dyn TurnValidator>>,
+    /// Set by [`crate::interceptor::with_interceptor`].
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    payload_keys: std::option::Option<Arc<dyn KeyProvider>>,
+    append_limiter: std::option::Option<Arc<RateLimiter>>,
+    read_limiter: std::option::Option<Arc<RateLimiter>>,
+    prefetch: Arc<PrefetchCache>,
+    append_queue: Arc<AppendQueue>,
+    /// Response buffer recycled by [`Client::get_last_into`].
+    response_buf: Mutex<Vec<u8>>,
+    /// Response buffer recycled by `GetLastOptions::reuse_buffer` reads.
+    #[cfg(feature = "bytes")]
+    read_buf: Mutex<bytes::BytesMut>,
 }
 
 impl Client {
+    /// Closes the connection. Appends still queued by
+    /// [`Client::append_async`] get up to the request timeout to be
+    /// delivered; any left after that fail the close with
+    /// [`Error::AppendsDropped`], though the client is closed regardless.
     pub fn close(&self) -> Result<()> {
         if self.closed.swap(true, Ordering::SeqCst) {
             return Ok(());
         }
+        let dropped = self.append_queue.close(self.timeout);
+        self.prefetch.close();
         let mut conn = self.conn.lock().map_err(|_| Error::ClientClosed)?;
-        conn.close()
+        conn.close()?;
+        if dropped > 0 {
+            return Err(Error::AppendsDropped { dropped });
+        }
+        Ok(())
     }
 
     pub fn session_id(&self) -> u64 {
         self.session_id.load(Ordering::SeqCst)
     }
 
+    /// The server address this connection was established to; with
+    /// [`dial_any`], the one that answered first.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
     pub fn client_tag(&self) -> &str {
         &self.client_tag
     }
 
+    /// The namespace requests are scoped to by default, empty for none; see
+    /// [`crate::namespace`].
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn max_decode_depth(&self) -> usize {
+        self.max_decode_depth
+    }
+
+    /// Reports whether the client was dialed with [`with_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Reports whether an earlier malformed frame or I/O failure made this
+    /// connection unusable. Poisoned clients fail every request with
+    /// [`Error::ConnectionClosed`] and should be redialed.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::SeqCst)
+    }
+
     pub(crate) fn send_request(
         &self,
         ctx: &RequestContext,
@@ -159,33 +590,544 @@ impl Client {
         flags: u16,
         payload: &[u8],
     ) -> Result<Frame> {
+        let max_frame_size = self.max_frame_size;
+        let (header, payload) = self.exchange(
+            ctx,
+            msg_type,
+            flags,
+            payload,
+            |conn: &mut Transport, checked| {
+                let frame = read_response(conn, max_frame_size, checked)?;
+                Ok((frame.header, frame.payload))
+            },
+        )?;
+        Ok(Frame { header, payload })
+    }
+
+    /// Writes `requests` without waiting for earlier responses and matches
+    /// responses back to them by req_id, so a batch of independent requests
+    /// costs roughly one round trip instead of one each. At most
+    /// [`PIPELINE_WINDOW`] requests are in flight at a time, and the whole
+    /// batch shares one deadline.
+    ///
+    /// Results are in request order. An ERROR frame fails only its own slot;
+    /// a transport or framing failure poisons the connection and fails every
+    /// request still outstanding, the first with the underlying error and
+    /// the rest with [`Error::ConnectionClosed`].
+    pub(crate) fn pipeline(
+        &self,
+        ctx: &RequestContext,
+        requests: &[(u16, Vec<u8>)],
+    ) -> Result<Vec<Result<Frame>>> {
+        if !self.interceptors.is_empty() {
+            // Interceptors see one request at a time, so each may rewrite
+            // its own; the batch goes out unpipelined.
+            return Ok(requests
+                .iter()
+                .map(|(msg_type, payload)| self.send_request(ctx, *msg_type, payload))
+                .collect());
+        }
+        let start = Instant::now();
+        let _timing = ctx.timing_scope();
+        ctx.timing_phase(Phase::Queue);
+        let (ctx, effective_deadline) = match self.authorize(ctx).and_then(|ctx| {
+            let deadline = self.ready(&ctx)?;
+            for (msg_type, payload) in requests {
+                self.throttle(&ctx, *msg_type, payload.len(), deadline)?;
+            }
+            Ok((ctx, deadline))
+        }) {
+            Ok(ready) => ready,
+            Err(err) => {
+                for (msg_type, _) in requests {
+                    self.record_request(*msg_type, start, Err(&err));
+                }
+                return Err(err);
+            }
+        };
+        let mut conn = self.conn.lock().map_err(|_| Error::ClientClosed)?;
+        let checked = self.checksums.load(Ordering::SeqCst);
+
+        let mut slots: Vec<Option<Result<Frame>>> = requests.iter().map(|_| None).collect();
+        // req_id -> index into `requests` for every request awaiting a response.
+        let mut in_flight: HashMap<u64, usize> = HashMap::new();
+        let mut sent = 0;
+        let outcome = (|| -> Result<()> {
+            conn.set_deadline(Some(effective_deadline))?;
+            while sent < requests.len() || !in_flight.is_empty() {
+                // Every frame that fits in the window goes out in one write.
+                ctx.timing_phase(Phase::Encode);
+                while sent < requests.len() && in_flight.len() < PIPELINE_WINDOW {
+                    let (msg_type, payload) = &requests[sent];
+                    if let Err(err) = self.require_message(*msg_type) {
+                        self.record_request(*msg_type, start, Err(&err));
+                        slots[sent] = Some(Err(err));
+                        sent += 1;
+                        continue;
+                    }
+                    let (flags, payload) = self.with_metadata(&ctx, 0, payload)?;
+                    let req_id = self.req_id.next();
+                    let sent_len = conn.queue_frame(*msg_type, flags, req_id, &payload, checked);
+                    self.record_bytes(Direction::Sent, sent_len);
+                    in_flight.insert(req_id, sent);
+                    sent += 1;
+                }
+                if in_flight.is_empty() {
+                    break;
+                }
+                ctx.timing_phase(Phase::Write);
+                conn.flush_frames()?;
+                if ctx.timing.is_some() {
+                    ctx.timing_phase(Phase::ServerWait);
+                    conn.wait_readable()?;
+                    ctx.timing_phase(Phase::Read);
+                }
+                let frame = read_response(&mut conn, self.max_frame_size, checked)?;
+                ctx.timing_phase(Phase::Decode);
+                self.record_bytes(
+                    Direction::Received,
+                    frame_len(frame.header.len as usize, checked),
+                );
+                let index = in_flight.remove(&frame.header.req_id).ok_or_else(|| {
+                    Error::protocol(format!(
+                        "response req_id {} matches no outstanding request",
+                        frame.header.req_id
+                    ))
+                })?;
+                self.charge_response(requests[index].0, frame.header.len as usize);
+                let result = if frame.header.msg_type == MSG_ERROR {
+                    Err(self.server_error(&frame.payload))
+                } else {
+                    Ok(frame)
+                };
+                self.record_request(requests[index].0, start, result.as_ref().map(|_| ()));
+                slots[index] = Some(result);
+            }
+            conn.set_deadline(None)?;
+            Ok(())
+        })();
+
+        let mut failure = None;
+        if let Err(err) = outcome {
+            self.poisoned.store(true, Ordering::SeqCst);
+            let _ = conn.close();
+            failure = Some(err);
+        }
+        Ok(slots
+            .into_iter()
+            .zip(requests)
+            .map(|(slot, (msg_type, _))| {
+                slot.unwrap_or_else(|| {
+                    let err = failure.take().unwrap_or(Error::ConnectionClosed);
+                    self.record_request(*msg_type, start, Err(&err));
+                    Err(err)
+                })
+            })
+            .collect())
+    }
+
+    /// Like [`Client::send_request`], but reads the response payload into the
+    /// client's recycled response buffer and hands it to `parse`, so repeated
+    /// calls reuse one allocation sized to the largest response seen.
+    pub(crate) fn send_request_reusing<T>(
+        &self,
+        ctx: &RequestContext,
+        msg_type: u16,
+        payload: &[u8],
+        parse: impl FnOnce(&[u8]) -> Result<T>,
+    ) -> Result<T> {
+        // Taken rather than held so the lock is never nested in the
+        // connection lock; a concurrent caller just reads into a fresh Vec.
+        let mut buf =
+            std::mem::take(&mut *self.response_buf.lock().map_err(|_| Error::ClientClosed)?);
+        let max_frame_size = self.max_frame_size;
+        let (_, response) = self.exchange(
+            ctx,
+            msg_type,
+            0,
+            payload,
+            |conn: &mut Transport, checked| {
+                let mut header = read_frame_into_vec(conn, max_frame_size, &mut buf)?;
+                if checked {
+                    header = verify_frame_checksum(&header, &buf)?;
+                    buf.truncate(header.len as usize);
+                }
+                conn.inflate(&mut header, &mut buf, max_frame_size)?;
+                Ok((header, buf))
+            },
+        )?;
+        let result = parse(&response);
+        *self.response_buf.lock().map_err(|_| Error::ClientClosed)? = response;
+        result
+    }
+
+    /// Like [`Client::send_request`], but reads the response into a
+    /// refcounted buffer. With `reuse_buffer` the client's shared read buffer
+    /// is used, so its allocation is recycled once earlier responses drop.
+    #[cfg(feature = "bytes")]
+    pub(crate) fn send_request_shared(
+        &self,
+        ctx: &RequestContext,
+        msg_type: u16,
+        payload: &[u8],
+        reuse_buffer: bool,
+    ) -> Result<bytes::Bytes> {
+        let max_frame_size = self.max_frame_size;
+        let (_, payload) = self.exchange(
+            ctx,
+            msg_type,
+            0,
+            payload,
+            |conn: &mut Transport, checked| {
+                let (mut header, mut response) = if reuse_buffer {
+                    let mut buf = self.read_buf.lock().map_err(|_| Error::ClientClosed)?;
+                    read_frame_into(conn, max_frame_size, &mut buf)?
+                } else {
+                    read_frame_into(conn, max_frame_size, &mut bytes::BytesMut::new())?
+                };
+                if checked {
+                    header = verify_frame_checksum(&header, &response)?;
+                    response.truncate(header.len as usize);
+                }
+                if is_compressed(&header) {
+                    let mut inflated = response.to_vec();
+                    conn.inflate(&mut header, &mut inflated, max_frame_size)?;
+                    response = inflated.into();
+                }
+                Ok((header, response))
+            },
+        )?;
+        Ok(payload)
+    }
+
+    /// Writes one request frame and reads its response with `read`, poisoning
+    /// the connection on any transport or framing failure.
+    fn exchange<P: AsRef<[u8]>>(
+        &self,
+        ctx: &RequestContext,
+        msg_type: u16,
+        flags: u16,
+        payload: &[u8],
+        read: impl FnOnce(&mut Transport, bool) -> Result<(FrameHeader, P)>,
+    ) -> Result<(FrameHeader, P)> {
+        // HELLO is connection setup rather than a call.
+        if self.interceptors.is_empty() || msg_type == MSG_HELLO {
+            return self.exchange_observed(ctx, msg_type, flags, payload, read);
+        }
+        intercept(
+            &self.interceptors,
+            ctx,
+            msg_type,
+            payload,
+            |ctx, payload| self.exchange_observed(ctx, msg_type, flags, payload, read),
+        )
+    }
+
+    fn exchange_observed<P: AsRef<[u8]>>(
+        &self,
+        ctx: &RequestContext,
+        msg_type: u16,
+        flags: u16,
+        payload: &[u8],
+        read: impl FnOnce(&mut Transport, bool) -> Result<(FrameHeader, P)>,
+    ) -> Result<(FrameHeader, P)> {
+        if self.metrics.is_none() {
+            return self.exchange_unobserved(ctx, msg_type, flags, payload, read);
+        }
+        let start = Instant::now();
+        let result = self.exchange_unobserved(ctx, msg_type, flags, payload, read);
+        self.record_request(msg_type, start, result.as_ref().map(|_| ()));
+        result
+    }
+
+    fn exchange_unobserved<P: AsRef<[u8]>>(
+        &self,
+        ctx: &RequestContext,
+        msg_type: u16,
+        flags: u16,
+        payload: &[u8],
+        read: impl FnOnce(&mut Transport, bool) -> Result<(FrameHeader, P)>,
+    ) -> Result<(FrameHeader, P)> {
+        self.require_message(msg_type)?;
+        let _timing = ctx.timing_scope();
+        ctx.timing_phase(Phase::Queue);
+        let ctx = &*self.authorize(ctx)?;
+        let effective_deadline = self.ready(ctx)?;
+        self.throttle(ctx, msg_type, payload.len(), effective_deadline)?;
+        ctx.timing_phase(Phase::Encode);
+        let (flags, payload) = self.with_metadata(ctx, flags, payload)?;
+        let payload = &payload[..];
+        ctx.timing_phase(Phase::Queue);
+        let mut conn = self.conn.lock().map_err(|_| Error::ClientClosed)?;
+        let request = FrameHeader {
+            len: payload.len() as u32,
+            msg_type,
+            flags,
+            req_id: self.req_id.next(),
+        };
+        let (header, response) =
+            match self.round_trip(ctx, &mut conn, effective_deadline, &request, payload, read) {
+                Ok(frame) => frame,
+                Err(err) => {
+                    // A partial write or read leaves the stream mid-frame; never
+                    // reuse it, or later responses would be misattributed.
+                    self.poisoned.store(true, Ordering::SeqCst);
+                    let _ = conn.close();
+                    return Err(err);
+                }
+            };
+        ctx.timing_phase(Phase::Decode);
+        self.charge_response(msg_type, header.len as usize);
+
+        if header.msg_type == MSG_ERROR {
+            return Err(self.server_error(response.as_ref()));
+        }
+
+        Ok((header, response))
+    }
+
+    fn round_trip<P>(
+        &self,
+        ctx: &RequestContext,
+        conn: &mut Transport,
+        deadline: Instant,
+        request: &FrameHeader,
+        payload: &[u8],
+        read: impl FnOnce(&mut Transport, bool) -> Result<(FrameHeader, P)>,
+    ) -> Result<(FrameHeader, P)> {
+        let FrameHeader {
+            msg_type,
+            flags,
+            req_id,
+            ..
+        } = *request;
+        let checked = self.checksums.load(Ordering::SeqCst);
+        conn.set_deadline(Some(deadline))?;
+        let sent_len = conn.queue_frame(msg_type, flags, req_id, payload, checked);
+        ctx.timing_phase(Phase::Write);
+        conn.flush_frames()?;
+        self.record_bytes(Direction::Sent, sent_len);
+        if ctx.timing.is_some() {
+            ctx.timing_phase(Phase::ServerWait);
+            conn.wait_readable()?;
+            ctx.timing_phase(Phase::Read);
+        }
+        let (header, response) = read(conn, checked)?;
+        self.record_bytes(Direction::Received, frame_len(header.len as usize, checked));
+        if header.req_id != req_id {
+            return Err(Error::protocol(format!(
+                "response req_id {} does not match request {}",
+                header.req_id, req_id
+            )));
+        }
+        conn.set_deadline(None)?;
+        Ok((header, response))
+    }
+
+    fn record_request(
+        &self,
+        msg_type: u16,
+        start: Instant,
+        result: std::result::Result<(), &Error>,
+    ) {
+        if let Some(metrics) = &self.metrics {
+            metrics.on_request(Operation::from_msg_type(msg_type), start.elapsed(), result);
+        }
+    }
+
+    fn record_bytes(&self, direction: Direction, bytes: usize) {
+        if let Some(metrics) = &self.metrics {
+            metrics.on_bytes(direction, bytes);
+        }
+    }
+
+    /// Prepends `ctx`'s values to a request payload as a metadata block and
+    /// sets [`FLAG_METADATA`], if the server accepted metadata at handshake
+    /// and there are values to send. The client's namespace is added unless
+    /// `ctx` names its own.
+    fn with_metadata<'a>(
+        &self,
+        ctx: &RequestContext,
+        flags: u16,
+        payload: &'a [u8],
+    ) -> Result<(u16, Cow<'a, [u8]>)> {
+        let stamp_namespace = !self.namespace.is_empty()
+            && self.server_namespaces()
+            && ctx.get_value(NAMESPACE_KEY).is_none();
+        if (ctx.values.is_none() && !stamp_namespace) || !self.metadata.load(Ordering::SeqCst) {
+            return Ok((flags, Cow::Borrowed(payload)));
+        }
+        let mut values = ctx.values();
+        if stamp_namespace {
+            values.insert(NAMESPACE_KEY, &self.namespace);
+        }
+        let mut framed = Vec::with_capacity(64 + payload.len());
+        encode_frame_metadata(&mut framed, values.into_iter())?;
+        framed.extend_from_slice(payload);
+        Ok((flags | FLAG_METADATA, Cow::Owned(framed)))
+    }
+
+    /// Adds the credential provider's current token to `ctx` as per-request
+    /// auth when it has rotated since HELLO and `ctx` carries none of its
+    /// own.
+    fn authorize<'a>(&self, ctx: &'a RequestContext) -> Result<Cow<'a, RequestContext>> {
+        let Some(credentials) = &self.credentials else {
+            return Ok(Cow::Borrowed(ctx));
+        };
+        if ctx.get_value(AUTH_KEY).is_some() {
+            return Ok(Cow::Borrowed(ctx));
+        }
+        let token = credentials.token()?;
+        if self.hello_token.as_deref() == Some(token.as_str()) {
+            return Ok(Cow::Borrowed(ctx));
+        }
+        Ok(Cow::Owned(ctx.with_auth(token)))
+    }
+
+    /// Parses an ERROR payload, dropping a rejected provider token.
+    fn server_error(&self, payload: &[u8]) -> Error {
+        let err = parse_server_error(payload);
+        if let (Error::Unauthenticated { .. }, Some(credentials)) = (&err, &self.credentials) {
+            credentials.invalidate();
+        }
+        err
+    }
+
+    /// Takes a token for `msg_type` from its rate limit, if it has one, and
+    /// waits until the request may go out (see [`crate::ratelimit`]).
+    fn throttle(
+        &self,
+        ctx: &RequestContext,
+        msg_type: u16,
+        bytes: usize,
+        deadline: Instant,
+    ) -> Result<()> {
+        let limiter = match Limited::from_msg_type(msg_type) {
+            Some(Limited::Appends) => self.append_limiter.as_deref(),
+            Some(Limited::Reads) => self.read_limiter.as_deref(),
+            None => None,
+        };
+        let Some(limiter) = limiter else {
+            return Ok(());
+        };
+        let (wait, state) = limiter.acquire(bytes, deadline);
+        if let Some(metrics) = &self.metrics {
+            metrics.on_rate_limit(Operation::from_msg_type(msg_type), &state);
+        }
+        let until = Instant::now() + wait?;
+        while let Some(remaining) = until.checked_duration_since(Instant::now()) {
+            if remaining.is_zero() {
+                break;
+            }
+            if self.closed.load(Ordering::SeqCst) {
+                return Err(Error::ClientClosed);
+            }
+            if ctx.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            std::thread::sleep(remaining.min(Duration::from_millis(50)));
+        }
+        Ok(())
+    }
+
+    /// Charges a read response's bytes to the read rate limit.
+    fn charge_response(&self, msg_type: u16, bytes: usize) {
+        if let (Some(Limited::Reads), Some(limiter)) =
+            (Limited::from_msg_type(msg_type), &self.read_limiter)
+        {
+            limiter.charge(bytes);
+        }
+    }
+
+    /// Checks that a request may be sent and returns its deadline.
+    fn ready(&self, ctx: &RequestContext) -> Result<Instant> {
         if self.closed.load(Ordering::SeqCst) {
             return Err(Error::ClientClosed);
         }
 
+        if self.is_poisoned() {
+            return Err(Error::ConnectionClosed);
+        }
+
         if ctx.is_cancelled() {
             return Err(Error::Cancelled);
         }
 
-        let effective_deadline = self.compute_deadline(ctx)?;
+        if ctx.get_value(AUTH_KEY).is_some() && !self.metadata.load(Ordering::SeqCst) {
+            return Err(Error::encode(
+                "server does not accept request metadata; per-request auth cannot be sent",
+            ));
+        }
 
-        let mut conn = self.conn.lock().map_err(|_| Error::ClientClosed)?;
-        conn.set_deadline(Some(effective_deadline))?;
+        let namespace = ctx.get_value(NAMESPACE_KEY).unwrap_or_default();
+        if !namespace.is_empty() && !self.server_namespaces() {
+            return Err(Error::Unsupported("namespaces".into()));
+        }
 
-        let req_id = self.req_id.fetch_add(1, Ordering::SeqCst) + 1;
-        write_frame(&mut *conn, msg_type, flags, req_id, payload)?;
-        let frame = read_frame(&mut *conn)?;
+        self.compute_deadline(ctx)
+    }
 
-        conn.set_deadline(None)?;
+    /// Whether the server searches turns itself (see [`Client::search_turns`]).
+    pub(crate) fn server_search(&self) -> bool {
+        self.search.load(Ordering::SeqCst)
+    }
+
+    /// Whether the server skips duplicate appends itself (see
+    /// [`Client::append_dedup`]).
+    pub(crate) fn server_dedup(&self) -> bool {
+        self.dedup.load(Ordering::SeqCst)
+    }
+
+    /// Whether the server filters GET_LAST by depth itself (see
+    /// [`GetLastOptions::max_depth`](crate::GetLastOptions::max_depth)).
+    pub(crate) fn server_depth_filter(&self) -> bool {
+        self.depth_filter.load(Ordering::SeqCst)
+    }
 
-        if frame.header.msg_type == MSG_ERROR {
-            return Err(parse_server_error(&frame.payload));
+    /// Whether the server leaves unrequested fields out of GET_LAST items
+    /// (see [`GetLastOptions::projection`](crate::GetLastOptions::projection)).
+    pub(crate) fn server_projection(&self) -> bool {
+        self.projection.load(Ordering::SeqCst)
+    }
+
+    /// Whether the server scopes requests to their namespace (see
+    /// [`crate::namespace`]).
+    pub(crate) fn server_namespaces(&self) -> bool {
+        self.namespaces.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        self.capabilities.get().copied().unwrap_or_default()
+    }
+
+    pub(crate) fn compression(&self) -> std::option::Option<Codec> {
+        self.compression.get().copied()
+    }
+
+    /// Fails with [`Error::Unsupported`] if the server advertised its
+    /// message types without `msg_type`.
+    fn require_message(&self, msg_type: u16) -> Result<()> {
+        self.require_writable(msg_type)?;
+        if self.capabilities().supports(msg_type) {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(Capabilities::unsupported_name(msg_type)))
         }
+    }
 
-        Ok(frame)
+    /// Fails with [`Error::ReadOnlyClient`] when the client is read-only and
+    /// `msg_type` would change server state. Message types the client does
+    /// not know are treated as mutations.
+    pub(crate) fn require_writable(&self, msg_type: u16) -> Result<()> {
+        if self.read_only && msg_type_mutates(msg_type) != Some(false) {
+            return Err(Error::ReadOnlyClient {
+                operation: Capabilities::unsupported_name(msg_type),
+            });
+        }
+        Ok(())
     }
 
-    fn compute_deadline(&self, ctx: &RequestContext) -> Result<Instant> {
+    pub(crate) fn compute_deadline(&self, ctx: &RequestContext) -> Result<Instant> {
         let now = Instant::now();
         let mut deadline = now + self.timeout;
         if let Some(ctx_deadline) = ctx.deadline() {
@@ -194,23 +1136,46 @@ impl Client {
             }
         }
         if deadline <= now {
-            return Err(Error::Timeout);
+            return Err(Error::DeadlineExceeded);
         }
         Ok(deadline)
     }
 
-    fn send_hello(&self, client_tag: &str) -> Result<()> {
-        let mut payload = Vec::with_capacity(2 + 2 + client_tag.len() + 4);
-        payload.write_u16::<LittleEndian>(1)?; // protocol version
-        payload.write_u16::<LittleEndian>(client_tag.len() as u16)?;
-        payload.extend_from_slice(client_tag.as_bytes());
-        payload.write_u32::<LittleEndian>(0)?; // no metadata
+    fn send_hello(
+        &self,
+        client_tag: &str,
+        request_checksums: bool,
+        bearer_token: std::option::Option<&BearerToken>,
+        codecs: &[Codec],
+    ) -> Result<()> {
+        let codec_ids: Vec<u8> = codecs.iter().map(|codec| codec.id()).collect();
+        let payload = encode_hello(
+            client_tag,
+            bearer_token.map(|BearerToken(token)| &token[..]),
+            &codec_ids,
+        );
 
         let ctx = RequestContext::with_timeout(self.timeout);
-        let frame = self.send_request_with_flags(&ctx, MSG_HELLO, 0, &payload)?;
+        let flags = FLAG_METADATA
+            | FLAG_SEARCH
+            | FLAG_APPEND_META
+            | FLAG_DEDUP
+            | FLAG_TIMESTAMPS
+            | FLAG_REDACTIONS
+            | FLAG_DEPTH_FILTER
+            | FLAG_PROJECTION
+            | FLAG_LINKS
+            | FLAG_NAMESPACES
+            | if request_checksums { FLAG_CRC32C } else { 0 }
+            | if codecs.is_empty() {
+                0
+            } else {
+                FLAG_COMPRESSED
+            };
+        let frame = self.send_request_with_flags(&ctx, MSG_HELLO, flags, &payload)?;
 
         if frame.header.msg_type != MSG_HELLO {
-            return Err(Error::invalid_response(format!(
+            return Err(Error::protocol(format!(
                 "unexpected response type: {}",
                 frame.header.msg_type
             )));
@@ -222,94 +1187,414 @@ impl Client {
             let session = u64::from_le_bytes(bytes);
             self.session_id.store(session, Ordering::SeqCst);
         }
+        let _ = self.capabilities.set(Capabilities {
+            protocol_version: frame
+                .payload
+                .get(8..10)
+                .map_or(0, |v| u16::from_le_bytes([v[0], v[1]])),
+            flags: frame.header.flags,
+            message_types: frame
+                .payload
+                .get(10..18)
+                .map(|bits| u64::from_le_bytes(bits.try_into().unwrap())),
+        });
+
+        // HELLO itself is never checksummed; the echoed flag switches every
+        // later frame over.
+        if request_checksums && frame.header.flags & FLAG_CRC32C != 0 {
+            self.checksums.store(true, Ordering::SeqCst);
+        }
+        if frame.header.flags & FLAG_METADATA != 0 {
+            self.metadata.store(true, Ordering::SeqCst);
+        }
+        if frame.header.flags & FLAG_SEARCH != 0 {
+            self.search.store(true, Ordering::SeqCst);
+        }
+        if frame.header.flags & FLAG_DEDUP != 0 {
+            self.dedup.store(true, Ordering::SeqCst);
+        }
+        if frame.header.flags & FLAG_DEPTH_FILTER != 0 {
+            self.depth_filter.store(true, Ordering::SeqCst);
+        }
+        if frame.header.flags & FLAG_PROJECTION != 0 {
+            self.projection.store(true, Ordering::SeqCst);
+        }
+        // Namespaces travel as request metadata, so they need both.
+        if frame.header.flags & FLAG_NAMESPACES != 0 && frame.header.flags & FLAG_METADATA != 0 {
+            self.namespaces.store(true, Ordering::SeqCst);
+        }
+        // Like checksums, compression starts with the next frame. A codec
+        // the client did not offer is ignored rather than trusted.
+        let codec = frame.payload.get(18).copied().and_then(Codec::from_id);
+        if let (true, Some(codec)) = (frame.header.flags & FLAG_COMPRESSED != 0, codec) {
+            if codecs.contains(&codec) {
+                let frame_codec = FrameCodec::new(codec)?;
+                self.conn.lock().map_err(|_| Error::ClientClosed)?.codec = Some(frame_codec);
+                let _ = self.compression.set(codec);
+            }
+        }
 
         Ok(())
     }
 }
 
+/// Connects to `addr`. When it resolves to several addresses they are tried
+/// happy-eyeballs style; see [`dial_any`].
+///
+/// With the `http-transport` feature, an `http://` or `https://` URL reaches
+/// the server through its HTTP API instead, e.g. behind the gateway. Every
+/// method behaves the same over either transport.
+///
+/// Shorthand for [`ClientBuilder`], which also builds pools, reconnecting
+/// and async clients.
 pub fn dial(addr: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+    ClientBuilder::new(addr).options(opts).build()
+}
+
+/// [`dial`] over TLS; shorthand for [`ClientBuilder::tls`].
+pub fn dial_tls(addr: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+    ClientBuilder::new(addr).tls().options(opts).build()
+}
+
+pub(crate) fn connect(addr: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+    if addr.starts_with("http://") || addr.starts_with("https://") {
+        return dial_http(addr, opts);
+    }
+    dial_any(&[addr], opts)
+}
+
+#[cfg(feature = "http-transport")]
+fn dial_http(url: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+    let opts: Vec<ClientOption> = opts.into_iter().collect();
     let mut options = ClientOptions::default();
-    for opt in opts {
+    for opt in &opts {
         opt(&mut options);
     }
 
-    let stream = connect_tcp(addr, options.dial_timeout)?;
-    let conn = Connection::Plain(stream);
+    let authority = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = authority.split('/').next().unwrap_or_default();
+    if proxy::proxy_for(authority, &options)?.is_some() {
+        return Err(Error::Unsupported("proxies with the HTTP transport".into()));
+    }
+    let token = options.bearer_token.as_ref().map(|token| token.0.clone());
+    let tunnel = HttpTunnel::new(url, token, options.credentials.clone())?;
+    let conn = Transport::new(Connection::Http(Box::new(tunnel)), &options)?;
 
-    let client = Client {
-        conn: Mutex::new(conn),
-        req_id: AtomicU64::new(0),
-        closed: AtomicBool::new(false),
-        timeout: options.request_timeout,
-        session_id: AtomicU64::new(0),
-        client_tag: options.client_tag.clone(),
+    let redial: DialFunc = {
+        let url = url.to_string();
+        Arc::new(move || dial_http(&url, opts.clone()))
     };
+    Client::handshake(conn, &options, redial)
+}
 
-    if let Err(err) = client.send_hello(&options.client_tag) {
-        let _ = client.close();
-        return Err(err);
+#[cfg(not(feature = "http-transport"))]
+fn dial_http(url: &str, _opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+    Err(Error::Connect {
+        addr: url.to_string(),
+        source: std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "HTTP URLs need the http-transport feature",
+        ),
+    })
+}
+
+/// Connects to the first of `addrs` to answer, for client-side failover.
+///
+/// Every address is resolved, and the results are tried in order with
+/// IPv6 and IPv4 alternating per host. Each attempt gets the
+/// [happy-eyeballs delay](with_happy_eyeballs_delay) before the next one
+/// starts alongside it, or less if it fails sooner. The first connection
+/// wins; [`Client::peer_addr`] reports which. If every attempt fails, the
+/// [`Error::Connect`] lists each address's error. Redials race the same list.
+pub fn dial_any(addrs: &[&str], opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+    let opts: Vec<ClientOption> = opts.into_iter().collect();
+    let mut options = ClientOptions::default();
+    for opt in &opts {
+        opt(&mut options);
     }
 
-    Ok(client)
+    let stream = proxy::connect(addrs, &options)?;
+    let conn = Transport::new(Connection::Plain(stream), &options)?;
+
+    let redial: DialFunc = {
+        let addrs: Vec<String> = addrs.iter().map(|addr| addr.to_string()).collect();
+        Arc::new(move || {
+            let addrs: Vec<&str> = addrs.iter().map(String::as_str).collect();
+            dial_any(&addrs, opts.clone())
+        })
+    };
+    Client::handshake(conn, &options, redial)
 }
 
-pub fn dial_tls(addr: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
+pub(crate) fn connect_tls(
+    addr: &str,
+    opts: impl IntoIterator<Item = ClientOption>,
+) -> Result<Client> {
     let _ = rustls::crypto::ring::default_provider().install_default();
 
+    let opts: Vec<ClientOption> = opts.into_iter().collect();
     let mut options = ClientOptions::default();
-    for opt in opts {
+    for opt in &opts {
         opt(&mut options);
     }
 
-    let stream = connect_tcp(addr, options.dial_timeout)?;
-    let config = match options.tls_config.take() {
+    let mut stream = proxy::connect(&[addr], &options)?;
+    let mut config = match options.tls_config.take() {
         Some(cfg) => cfg,
         None => Arc::new(default_tls_config()?),
     };
+    let verifier = if options.pinned_certs.is_empty() {
+        None
+    } else {
+        let verifier = Arc::new(PinnedCertVerifier::new(options.pinned_certs.clone()));
+        Arc::make_mut(&mut config)
+            .dangerous()
+            .set_certificate_verifier(verifier.clone());
+        Some(verifier)
+    };
 
     let server_name = server_name_from_addr(addr)?;
-    let conn =
+    let mut conn =
         ClientConnection::new(config, server_name).map_err(|err| Error::Tls(err.to_string()))?;
 
+    if let Some(verifier) = verifier {
+        // Handshake now, so a pin mismatch fails the dial as such rather
+        // than as an I/O error on HELLO.
+        stream.set_read_timeout(Some(options.dial_timeout))?;
+        stream.set_write_timeout(Some(options.dial_timeout))?;
+        while conn.is_handshaking() {
+            if let Err(err) = conn.complete_io(&mut stream) {
+                if verifier.mismatch.load(Ordering::SeqCst) {
+                    return Err(Error::CertPinMismatch);
+                }
+                return Err(Error::Tls(err.to_string()));
+            }
+        }
+    }
+
     let stream = rustls::StreamOwned::new(conn, stream);
 
-    let client = Client {
-        conn: Mutex::new(Connection::Tls(Box::new(stream))),
-        req_id: AtomicU64::new(0),
-        closed: AtomicBool::new(false),
-        timeout: options.request_timeout,
-        session_id: AtomicU64::new(0),
-        client_tag: options.client_tag.clone(),
+    let redial: DialFunc = {
+        let addr = addr.to_string();
+        Arc::new(move || dial_tls(&addr, opts.clone()))
     };
+    let conn = Transport::new(Connection::Tls(Box::new(stream)), &options)?;
+    Client::handshake(conn, &options, redial)
+}
+
+impl Client {
+    fn handshake(conn: Transport, options: &ClientOptions, redial: DialFunc) -> Result<Client> {
+        let hello_token = match &options.credentials {
+            Some(credentials) => Some(credentials.token()?),
+            None => None,
+        };
+        let peer_addr = conn.peer_addr()?;
+        let client = Client {
+            conn: Mutex::new(conn),
+            req_id: RequestIds::new(),
+            closed: AtomicBool::new(false),
+            timeout: options.request_timeout,
+            session_id: AtomicU64::new(0),
+            peer_addr,
+            client_tag: options.client_tag.clone(),
+            max_frame_size: options.max_frame_size,
+            max_decode_depth: options.max_decode_depth,
+            read_only: options.read_only,
+            response_validation: options.response_validation,
+            namespace: options.namespace.clone(),
+            poisoned: AtomicBool::new(false),
+            checksums: AtomicBool::new(false),
+            metadata: AtomicBool::new(false),
+            search: AtomicBool::new(false),
+            dedup: AtomicBool::new(false),
+            depth_filter: AtomicBool::new(false),
+            projection: AtomicBool::new(false),
+            namespaces: AtomicBool::new(false),
+            capabilities: OnceLock::new(),
+            compression: OnceLock::new(),
+            redial,
+            credentials: options.credentials.clone(),
+            hello_token: hello_token.clone(),
+            metrics: options.metrics.clone(),
+            turn_cache: options.turn_cache.clone(),
+            validator: options.validator.clone(),
+            interceptors: options.interceptors.clone(),
+            payload_keys: options.payload_keys.clone(),
+            append_limiter: options.append_limiter.clone(),
+            read_limiter: options.read_limiter.clone(),
+            prefetch: Arc::new(PrefetchCache::new(options.prefetch_staleness)),
+            append_queue: Arc::new(AppendQueue::new(options.append_queue)),
+            response_buf: Mutex::new(Vec::new()),
+            #[cfg(feature = "bytes")]
+            read_buf: Mutex::new(bytes::BytesMut::new()),
+        };
+
+        let bearer_token = hello_token.map(BearerToken);
+        if let Err(err) = client.send_hello(
+            &options.client_tag,
+            options.frame_checksums,
+            bearer_token.as_ref().or(options.bearer_token.as_ref()),
+            &options.compression,
+        ) {
+            let _ = client.close();
+            if let (Error::Unauthenticated { .. }, Some(credentials)) = (&err, &options.credentials)
+            {
+                credentials.invalidate();
+            }
+            return Err(err);
+        }
+        // A namespaced client on a server that ignores namespaces would
+        // read and write another tenant's contexts.
+        if !client.namespace.is_empty() && !client.server_namespaces() {
+            let _ = client.close();
+            return Err(Error::Unsupported("namespaces".into()));
+        }
+
+        Ok(client)
+    }
+
+    /// Opens a fresh connection to the same server with the same options.
+    pub fn redial(&self) -> Result<Client> {
+        (self.redial)()
+    }
+
+    pub(crate) fn dialer(&self) -> DialFunc {
+        self.redial.clone()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn prefetch_cache(&self) -> &Arc<PrefetchCache> {
+        &self.prefetch
+    }
+
+    pub(crate) fn append_queue(&self) -> &Arc<AppendQueue> {
+        &self.append_queue
+    }
+
+    pub(crate) fn turn_cache(&self) -> std::option::Option<&TurnCache> {
+        self.turn_cache.as_deref()
+    }
+
+    pub(crate) fn response_validation(&self) -> ResponseValidation {
+        self.response_validation
+    }
+
+    pub(crate) fn metrics(&self) -> std::option::Option<&Arc<dyn Metrics>> {
+        self.metrics.as_ref()
+    }
+
+    pub(crate) fn validator(&self) -> std::option::Option<&Arc<dyn TurnValidator>> {
+        self.validator.as_ref()
+    }
 
-    if let Err(err) = client.send_hello(&options.client_tag) {
-        let _ = client.close();
-        return Err(err);
+    pub(crate) fn payload_keys(&self) -> std::option::Option<&Arc<dyn KeyProvider>> {
+        self.payload_keys.as_ref()
     }
 
-    Ok(client)
+    /// Looks `turn_id` of `namespace` up in the turn cache, reporting the
+    /// outcome.
+    pub(crate) fn cached_turn(
+        &self,
+        cache: &TurnCache,
+        namespace: &str,
+        turn_id: TurnId,
+    ) -> std::option::Option<TurnRecord> {
+        let record = cache.get(namespace, turn_id);
+        if let Some(metrics) = &self.metrics {
+            metrics.on_turn_cache(record.is_some());
+        }
+        record
+    }
 }
 
-fn connect_tcp(addr: &str, timeout: Duration) -> Result<TcpStream> {
-    let addrs = addr
-        .to_socket_addrs()
-        .map_err(Error::Io)?
-        .collect::<Vec<_>>();
+/// Resolves every address in `addrs` and races connections to them (RFC
+/// 8305): each attempt gets `delay` before the next starts alongside it, or
+/// less if it fails first, and the first connection wins. Losing
+/// connections are closed as they complete.
+pub(crate) fn connect_tcp(addrs: &[&str], timeout: Duration, delay: Duration) -> Result<TcpStream> {
+    let mut failures = Vec::new();
+    let mut candidates = Vec::new();
+    for addr in addrs {
+        match addr.to_socket_addrs() {
+            Ok(resolved) => candidates.extend(interleave_families(resolved.collect())),
+            Err(err) => failures.push((addr.to_string(), err)),
+        }
+    }
 
-    let mut last_err = None;
-    for socket_addr in addrs {
-        match TcpStream::connect_timeout(&socket_addr, timeout) {
-            Ok(stream) => {
+    let (results, attempts) = mpsc::channel();
+    let mut candidates = candidates.into_iter();
+    let mut pending = 0;
+    let mut start_next = true;
+    loop {
+        if start_next {
+            if let Some(socket_addr) = candidates.next() {
+                let results = results.clone();
+                thread::spawn(move || {
+                    let result = TcpStream::connect_timeout(&socket_addr, timeout);
+                    // The receiver is gone once another attempt has won.
+                    let _ = results.send((socket_addr, result));
+                });
+                pending += 1;
+            }
+        }
+        if pending == 0 {
+            break;
+        }
+        let next = if candidates.len() > 0 {
+            attempts.recv_timeout(delay)
+        } else {
+            attempts
+                .recv()
+                .map_err(|_| mpsc::RecvTimeoutError::Disconnected)
+        };
+        start_next = match next {
+            Ok((_, Ok(stream))) => {
                 let _ = stream.set_nodelay(true);
                 return Ok(stream);
             }
-            Err(err) => last_err = Some(err),
-        }
+            Ok((socket_addr, Err(err))) => {
+                pending -= 1;
+                failures.push((socket_addr.to_string(), err));
+                true
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => true,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
     }
 
-    Err(last_err
-        .map(Error::Io)
-        .unwrap_or(Error::Io(std::io::Error::other("no addresses resolved"))))
+    let source = match failures.last() {
+        None => std::io::Error::other("no addresses resolved"),
+        Some((_, last)) => {
+            let detail = failures
+                .iter()
+                .map(|(addr, err)| format!("{addr}: {err}"))
+                .collect::<Vec<_>>()
+                .join("; ");
+            std::io::Error::new(last.kind(), detail)
+        }
+    };
+    Err(Error::Connect {
+        addr: addrs.join(", "),
+        source,
+    })
+}
+
+/// Orders one host's addresses for racing: alternating families, starting
+/// with the family of the first address the resolver returned.
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_v6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_v6);
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    while !first.is_empty() || !second.is_empty() {
+        ordered.extend(first.pop_front());
+        ordered.extend(second.pop_front());
+    }
+    ordered
 }
 
 fn default_tls_config() -> Result<ClientConfig> {
@@ -341,26 +1626,184 @@ fn server_name_from_addr(addr: &str) -> Result<ServerName<'static>> {
         .map_err(|_| Error::Tls(format!("invalid server name: {host}")))
 }
 
-fn parse_server_error(payload: &[u8]) -> Error {
-    if payload.len() < 8 {
-        return Error::server(0, "unknown error");
+/// Size on the wire of a frame carrying `payload_len` bytes.
+fn frame_len(payload_len: usize, checked: bool) -> usize {
+    FRAME_HEADER_LEN + payload_len + if checked { FRAME_CHECKSUM_LEN } else { 0 }
+}
+
+/// Reads one response frame, verifying and stripping its checksum trailer
+/// when checksums were negotiated.
+fn read_response(conn: &mut Transport, max_frame_size: u32, checked: bool) -> Result<Frame> {
+    let mut frame = read_frame_with_limit(conn, max_frame_size)?;
+    if checked {
+        frame.header = verify_frame_checksum(&frame.header, &frame.payload)?;
+        frame.payload.truncate(frame.header.len as usize);
+    }
+    conn.inflate(&mut frame.header, &mut frame.payload, max_frame_size)?;
+    Ok(frame)
+}
+
+/// A connection with its per-connection I/O buffers: a buffered reader so a
+/// frame's header and payload rarely cost separate reads, and a scratch
+/// buffer request frames are assembled in so each goes out in one write.
+pub(crate) struct Transport {
+    reader: BufReader<Connection>,
+    scratch: Vec<u8>,
+    write_buffer_bytes: usize,
+    /// Tees frames into a wire recording; see [`crate::replay`].
+    recorder: std::option::Option<ConnectionRecorder>,
+    /// Set once HELLO negotiates frame compression.
+    codec: std::option::Option<FrameCodec>,
+    /// Payloads are compressed into and decompressed out of this buffer,
+    /// which keeps the larger allocation of the two between frames.
+    compressed: Vec<u8>,
+}
+
+impl Transport {
+    fn new(conn: Connection, options: &ClientOptions) -> Result<Self> {
+        let recorder = match &options.wire_recorder {
+            Some(recorder) => Some(recorder.connection()?),
+            None => None,
+        };
+        Ok(Self {
+            reader: BufReader::with_capacity(options.read_buffer_bytes, conn),
+            scratch: Vec::with_capacity(options.write_buffer_bytes),
+            write_buffer_bytes: options.write_buffer_bytes,
+            recorder,
+            codec: None,
+            compressed: Vec::new(),
+        })
+    }
+
+    /// Appends a request frame to the scratch buffer, compressed if that
+    /// was negotiated and pays off, and returns its size on the wire;
+    /// nothing is sent until [`Transport::flush_frames`].
+    fn queue_frame(
+        &mut self,
+        msg_type: u16,
+        flags: u16,
+        req_id: u64,
+        payload: &[u8],
+        checked: bool,
+    ) -> usize {
+        let compressed = match &mut self.codec {
+            Some(codec) => codec.compress(payload, &mut self.compressed),
+            None => false,
+        };
+        let (flags, payload) = if compressed {
+            (flags | FLAG_COMPRESSED, &self.compressed[..])
+        } else {
+            (flags, payload)
+        };
+        if checked {
+            encode_frame_with_checksum_into(&mut self.scratch, msg_type, flags, req_id, payload);
+        } else {
+            encode_frame_into(&mut self.scratch, msg_type, flags, req_id, payload);
+        }
+        frame_len(payload.len(), checked)
+    }
+
+    /// Decompresses a response payload flagged with [`FLAG_COMPRESSED`] in
+    /// place and clears the flag. `header.len` keeps the compressed length,
+    /// so byte counts and read limits reflect the wire. Until HELLO
+    /// negotiates a codec the flag only echoes the request, as on the HELLO
+    /// response itself.
+    fn inflate(
+        &mut self,
+        header: &mut FrameHeader,
+        payload: &mut Vec<u8>,
+        max_frame_size: u32,
+    ) -> Result<()> {
+        let Some(codec) = &mut self.codec else {
+            return Ok(());
+        };
+        if !is_compressed(header) {
+            return Ok(());
+        }
+        codec.decompress(header, payload, &mut self.compressed, max_frame_size)?;
+        std::mem::swap(payload, &mut self.compressed);
+        header.flags &= !FLAG_COMPRESSED;
+        Ok(())
+    }
+
+    /// Writes every queued frame in a single `write_all`, then empties the
+    /// scratch buffer, giving back capacity beyond `write_buffer_bytes`.
+    fn flush_frames(&mut self) -> Result<()> {
+        let result = match self.reader.get_mut() {
+            // The tunnel POSTs the batch and maps HTTP failures itself.
+            #[cfg(feature = "http-transport")]
+            Connection::Http(tunnel) => tunnel.exchange(&self.scratch),
+            conn => conn.write_all(&self.scratch).map_err(Error::Io),
+        };
+        if let Some(recorder) = &mut self.recorder {
+            recorder.observe(Direction::Sent, &self.scratch);
+        }
+        self.scratch.clear();
+        self.scratch.shrink_to(self.write_buffer_bytes);
+        result
+    }
+
+    /// Blocks until response bytes are buffered, without consuming them.
+    fn wait_readable(&mut self) -> Result<()> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(_) => return Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(Error::Io(err)),
+            }
+        }
+    }
+
+    fn set_deadline(&mut self, deadline: std::option::Option<Instant>) -> Result<()> {
+        self.reader.get_mut().set_deadline(deadline)
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.reader.get_ref().peer_addr()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.reader.get_mut().close()
+    }
+}
+
+impl Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.observe(Direction::Received, &buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.reader.read_exact(buf)?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.observe(Direction::Received, buf);
+        }
+        Ok(())
     }
-    let code = u32::from_le_bytes(payload[0..4].try_into().unwrap_or_default());
-    let detail_len = u32::from_le_bytes(payload[4..8].try_into().unwrap_or_default()) as usize;
-    let detail = if payload.len() >= 8 + detail_len {
-        String::from_utf8_lossy(&payload[8..8 + detail_len]).to_string()
-    } else {
-        String::new()
-    };
-    Error::server(code, detail)
 }
 
 pub(crate) enum Connection {
     Plain(TcpStream),
     Tls(Box<rustls::StreamOwned<ClientConnection, TcpStream>>),
+    /// Frames go out through [`HttpTunnel::exchange`], not [`Write`].
+    #[cfg(feature = "http-transport")]
+    Http(Box<HttpTunnel>),
 }
 
 impl Connection {
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        let tcp = match self {
+            Connection::Plain(stream) => stream,
+            Connection::Tls(stream) => stream.get_ref(),
+            #[cfg(feature = "http-transport")]
+            Connection::Http(tunnel) => return Ok(tunnel.peer_addr()),
+        };
+        tcp.peer_addr().map_err(Error::Io)
+    }
+
     fn set_deadline(&mut self, deadline: std::option::Option<Instant>) -> Result<()> {
         let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
         match self {
@@ -373,6 +1816,8 @@ impl Connection {
                 tcp.set_read_timeout(timeout).map_err(Error::Io)?;
                 tcp.set_write_timeout(timeout).map_err(Error::Io)?;
             }
+            #[cfg(feature = "http-transport")]
+            Connection::Http(tunnel) => tunnel.set_timeout(timeout),
         }
         Ok(())
     }
@@ -386,24 +1831,30 @@ impl Connection {
                 .get_mut()
                 .shutdown(std::net::Shutdown::Both)
                 .map_err(Error::Io),
+            #[cfg(feature = "http-transport")]
+            Connection::Http(_) => Ok(()),
         }
     }
 }
 
-impl std::io::Read for Connection {
+impl Read for Connection {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             Connection::Plain(stream) => stream.read(buf),
             Connection::Tls(stream) => stream.read(buf),
+            #[cfg(feature = "http-transport")]
+            Connection::Http(tunnel) => tunnel.read(buf),
         }
     }
 }
 
-impl std::io::Write for Connection {
+impl Write for Connection {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             Connection::Plain(stream) => stream.write(buf),
             Connection::Tls(stream) => stream.write(buf),
+            #[cfg(feature = "http-transport")]
+            Connection::Http(_) => Err(std::io::ErrorKind::Unsupported.into()),
         }
     }
 
@@ -411,6 +1862,8 @@ impl std::io::Write for Connection {
         match self {
             Connection::Plain(stream) => stream.flush(),
             Connection::Tls(stream) => stream.flush(),
+            #[cfg(feature = "http-transport")]
+            Connection::Http(_) => Ok(()),
         }
     }
 }
@@ -418,8 +1871,10 @@ impl std::io::Write for Connection {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::ContextId;
     use crate::protocol::{read_frame, write_frame, FrameHeader, MSG_HELLO};
     use crate::test_util::{decode_hex, load_fixture};
+    use byteorder::{LittleEndian, WriteBytesExt};
     use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
     use rustls::ServerConfig;
     use std::net::TcpListener;
@@ -479,11 +1934,89 @@ mod tests {
     }
 
     #[test]
-    fn truncated_header_is_invalid_response() {
+    fn truncated_header_is_protocol_error() {
         let data = vec![0u8; 3];
         let mut cursor = std::io::Cursor::new(data);
         let err = read_frame(&mut cursor).unwrap_err();
-        assert!(matches!(err, Error::InvalidResponse(_)));
+        assert!(matches!(err, Error::Protocol(_)));
+    }
+
+    #[test]
+    fn eof_before_header_is_connection_closed() {
+        let mut cursor = std::io::Cursor::new(Vec::<u8>::new());
+        let err = read_frame(&mut cursor).unwrap_err();
+        assert!(matches!(err, Error::ConnectionClosed));
+    }
+
+    #[test]
+    fn dial_failure_is_connect_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        match dial(&addr, Vec::new()) {
+            Err(Error::Connect { addr: failed, .. }) => assert_eq!(failed, addr),
+            Err(other) => panic!("expected connect error, got {other:?}"),
+            Ok(_) => panic!("expected connect error"),
+        }
+    }
+
+    fn refused_addr() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn dial_any_fails_over_without_waiting_out_the_delay() {
+        let dead = refused_addr();
+        let (live, handle) = crate::test_util::spawn_scripted_server(Vec::new());
+        let start = Instant::now();
+        let client = dial_any(
+            &[&dead, &live],
+            [with_happy_eyeballs_delay(Duration::from_secs(30))],
+        )
+        .unwrap();
+        // A refused attempt starts the next one at once.
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(client.peer_addr().to_string(), live);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn dial_any_reports_every_failed_address() {
+        let (first, second) = (refused_addr(), refused_addr());
+        match dial_any(&[&first, "no-such-host.invalid:9009", &second], []) {
+            Err(Error::Connect { addr, source }) => {
+                assert_eq!(
+                    addr,
+                    format!("{first}, no-such-host.invalid:9009, {second}")
+                );
+                let detail = source.to_string();
+                for failed in [&first[..], "no-such-host.invalid:9009", &second[..]] {
+                    assert!(detail.contains(failed), "{detail}");
+                }
+            }
+            Err(other) => panic!("expected connect error, got {other:?}"),
+            Ok(_) => panic!("expected connect error"),
+        }
+    }
+
+    #[test]
+    fn racing_alternates_address_families() {
+        let addrs: Vec<SocketAddr> = [
+            "[::1]:1",
+            "[::1]:2",
+            "[::1]:3",
+            "127.0.0.1:4",
+            "127.0.0.1:5",
+        ]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+        let ports: Vec<u16> = interleave_families(addrs)
+            .iter()
+            .map(SocketAddr::port)
+            .collect();
+        assert_eq!(ports, [1, 4, 2, 5, 3]);
     }
 
     #[test]
@@ -496,11 +2029,235 @@ mod tests {
         buf.write_u64::<LittleEndian>(1).unwrap();
         let mut cursor = std::io::Cursor::new(buf);
         let err = read_frame(&mut cursor).unwrap_err();
-        assert!(matches!(err, Error::InvalidResponse(_)));
+        assert!(matches!(
+            err,
+            Error::FrameTooLarge {
+                max: crate::protocol::MAX_FRAME_SIZE,
+                ..
+            }
+        ));
     }
 
     #[test]
-    fn truncated_payload_is_invalid_response() {
+    fn malformed_response_poisons_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let frame = read_frame(&mut stream).unwrap();
+            let mut resp = Vec::new();
+            resp.write_u64::<LittleEndian>(1).unwrap();
+            resp.write_u16::<LittleEndian>(1).unwrap();
+            write_frame(&mut stream, MSG_HELLO, 0, frame.header.req_id, &resp).unwrap();
+
+            let req = read_frame(&mut stream).unwrap();
+            // Header declares a 1 MiB payload that never arrives.
+            let mut header = Vec::new();
+            header.write_u32::<LittleEndian>(1 << 20).unwrap();
+            header.write_u16::<LittleEndian>(2).unwrap();
+            header.write_u16::<LittleEndian>(0).unwrap();
+            header.write_u64::<LittleEndian>(req.header.req_id).unwrap();
+            std::io::Write::write_all(&mut stream, &header).unwrap();
+        });
+
+        let client = dial(&addr.to_string(), vec![with_max_frame_size(1024)]).unwrap();
+        let ctx = RequestContext::background();
+        let payload = 0u64.to_le_bytes();
+        let err = client
+            .send_request(&ctx, crate::protocol::MSG_CTX_CREATE, &payload)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::FrameTooLarge {
+                len: 1048576,
+                max: 1024
+            }
+        ));
+        assert!(client.is_poisoned());
+        let err = client
+            .send_request(&ctx, crate::protocol::MSG_CTX_CREATE, &payload)
+            .unwrap_err();
+        assert!(matches!(err, Error::ConnectionClosed));
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn negotiated_checksums_detect_corruption_and_poison() {
+        use crate::protocol::{verify_frame_checksum, write_frame_with_checksum, FLAG_CRC32C};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            assert_ne!(hello.header.flags & FLAG_CRC32C, 0);
+            let mut resp = Vec::new();
+            resp.write_u64::<LittleEndian>(1).unwrap();
+            resp.write_u16::<LittleEndian>(1).unwrap();
+            write_frame(
+                &mut stream,
+                MSG_HELLO,
+                FLAG_CRC32C,
+                hello.header.req_id,
+                &resp,
+            )
+            .unwrap();
+
+            // First request: checksummed both ways.
+            let req = read_frame(&mut stream).unwrap();
+            let header = verify_frame_checksum(&req.header, &req.payload).unwrap();
+            assert_eq!(&req.payload[..header.len as usize], &7u64.to_le_bytes());
+            let head = [0u8; 20];
+            write_frame_with_checksum(&mut stream, 2, 0, req.header.req_id, &head).unwrap();
+
+            // Second request: flip one payload byte in flight.
+            let req = read_frame(&mut stream).unwrap();
+            let mut corrupted = Vec::new();
+            write_frame_with_checksum(&mut corrupted, 2, 0, req.header.req_id, &head).unwrap();
+            corrupted[20] ^= 0x01;
+            std::io::Write::write_all(&mut stream, &corrupted).unwrap();
+        });
+
+        let client = dial(&addr.to_string(), Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let frame = client
+            .send_request(&ctx, crate::protocol::MSG_CTX_CREATE, &7u64.to_le_bytes())
+            .unwrap();
+        assert_eq!(frame.payload, vec![0u8; 20]);
+        assert_eq!(frame.header.flags & FLAG_CRC32C, 0);
+
+        let err = client
+            .send_request(&ctx, crate::protocol::MSG_CTX_CREATE, &7u64.to_le_bytes())
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::FrameCorrupted { msg_type: 2, .. }),
+            "{err:?}"
+        );
+        assert!(crate::reconnect::is_connection_error(&err));
+        assert!(client.is_poisoned());
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn checksums_stay_off_for_servers_that_do_not_echo() {
+        use crate::test_util::spawn_scripted_server;
+
+        let (addr, handle) = spawn_scripted_server(vec![(2, vec![0u8; 20])]);
+        let client = dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let frame = client
+            .send_request(&ctx, crate::protocol::MSG_CTX_CREATE, &7u64.to_le_bytes())
+            .unwrap();
+        assert_eq!(frame.payload.len(), 20);
+        let received = handle.join().unwrap();
+        assert_eq!(received[0].header.flags, 0);
+        assert_eq!(received[0].payload, 7u64.to_le_bytes());
+    }
+
+    #[test]
+    fn context_values_are_sent_as_frame_metadata() {
+        use crate::protocol::FLAG_METADATA;
+        use crate::test_util::spawn_scripted_server;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            assert_ne!(hello.header.flags & FLAG_METADATA, 0);
+            let mut resp = Vec::new();
+            resp.write_u64::<LittleEndian>(1).unwrap();
+            resp.write_u16::<LittleEndian>(1).unwrap();
+            write_frame(
+                &mut stream,
+                MSG_HELLO,
+                FLAG_METADATA,
+                hello.header.req_id,
+                &resp,
+            )
+            .unwrap();
+
+            let mut received = Vec::new();
+            for _ in 0..4 {
+                let req = read_frame(&mut stream).unwrap();
+                write_frame(&mut stream, 2, 0, req.header.req_id, &[0u8; 20]).unwrap();
+                received.push(req);
+            }
+            received
+        });
+
+        let client = dial(&addr.to_string(), Vec::new()).unwrap();
+        let parent = RequestContext::background()
+            .with_value("request_id", "req-1")
+            .with_value("tenant", "acme");
+        let child = parent.with_value("request_id", "req-2");
+        assert_eq!(child.get_value("request_id"), Some("req-2"));
+        assert_eq!(child.get_value("tenant"), Some("acme"));
+        assert_eq!(parent.get_value("request_id"), Some("req-1"));
+        assert_eq!(child.get_value("missing"), None);
+
+        let tenant = RequestContext::background().with_auth("tenant-token");
+        assert!(!format!("{tenant:?}").contains("tenant-token"));
+
+        let payload = 7u64.to_le_bytes();
+        for ctx in [&parent, &child, &tenant, &RequestContext::background()] {
+            client
+                .send_request(ctx, crate::protocol::MSG_CTX_CREATE, &payload)
+                .unwrap();
+        }
+
+        let metadata = |pairs: &[(&str, &str)]| {
+            let mut block = Vec::new();
+            block.write_u16::<LittleEndian>(pairs.len() as u16).unwrap();
+            for (key, value) in pairs {
+                for field in [key, value] {
+                    block.write_u16::<LittleEndian>(field.len() as u16).unwrap();
+                    block.extend_from_slice(field.as_bytes());
+                }
+            }
+            block.extend_from_slice(&payload);
+            block
+        };
+        let received = handle.join().unwrap();
+        assert_eq!(received[0].header.flags, FLAG_METADATA);
+        assert_eq!(
+            received[0].payload,
+            metadata(&[("request_id", "req-1"), ("tenant", "acme")])
+        );
+        assert_eq!(
+            received[1].payload,
+            metadata(&[("request_id", "req-2"), ("tenant", "acme")])
+        );
+        // The override rides on its own request only.
+        assert_eq!(received[2].payload, metadata(&[(AUTH_KEY, "tenant-token")]));
+        assert_eq!(received[3].header.flags, 0);
+        assert_eq!(received[3].payload, payload);
+
+        // Servers that do not echo the flag never see the block.
+        let (addr, handle) = spawn_scripted_server(vec![(2, vec![0u8; 20])]);
+        let client = dial(&addr, Vec::new()).unwrap();
+        client
+            .send_request(&child, crate::protocol::MSG_CTX_CREATE, &payload)
+            .unwrap();
+        // A per-request credential is refused rather than dropped.
+        let err = client
+            .send_request(&tenant, crate::protocol::MSG_CTX_CREATE, &payload)
+            .unwrap_err();
+        assert!(matches!(err, Error::Encode { .. }), "got {err:?}");
+        assert!(!client.is_poisoned());
+        let received = handle.join().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].header.flags, 0);
+        assert_eq!(received[0].payload, payload);
+    }
+
+    #[test]
+    fn truncated_payload_is_protocol_error() {
         let mut buf = Vec::new();
         buf.write_u32::<LittleEndian>(4).unwrap();
         buf.write_u16::<LittleEndian>(MSG_HELLO).unwrap();
@@ -509,7 +2266,120 @@ mod tests {
         buf.extend_from_slice(&[1, 2]);
         let mut cursor = std::io::Cursor::new(buf);
         let err = read_frame(&mut cursor).unwrap_err();
-        assert!(matches!(err, Error::InvalidResponse(_)));
+        assert!(matches!(err, Error::Protocol(_)));
+    }
+
+    #[test]
+    fn scratch_buffer_is_trimmed_to_write_buffer_bytes() {
+        let (addr, handle) = crate::test_util::spawn_scripted_server(vec![
+            (MSG_HELLO, Vec::new()),
+            (MSG_HELLO, Vec::new()),
+        ]);
+        let client = dial(&addr, vec![with_write_buffer_bytes(1024)]).unwrap();
+        let ctx = RequestContext::background();
+
+        let large = vec![0x42u8; 64 * 1024];
+        client.send_request(&ctx, MSG_HELLO, &large).unwrap();
+        assert!(client.conn.lock().unwrap().scratch.capacity() <= 1024);
+        client.send_request(&ctx, MSG_HELLO, b"small").unwrap();
+        assert!(client.conn.lock().unwrap().scratch.capacity() <= 1024);
+
+        let received = handle.join().unwrap();
+        assert_eq!(received[0].payload, large);
+        assert_eq!(received[1].payload, b"small");
+    }
+
+    #[test]
+    fn read_only_client_refuses_mutations_before_sending() {
+        use crate::context::CreateContextOptions;
+        use crate::fs::{AttachFsRequest, PutBlobRequest};
+        use crate::protocol::{msg_type_name, MSG_GET_HEAD};
+        use crate::redact::RedactOptions;
+        use crate::turn::{AppendRequest, CompactRequest};
+
+        let mut head = 7u64.to_le_bytes().to_vec();
+        head.extend_from_slice(&3u64.to_le_bytes());
+        head.extend_from_slice(&3u32.to_le_bytes());
+        let (addr, handle) = crate::test_util::spawn_scripted_server(vec![(MSG_GET_HEAD, head)]);
+        let client = dial(&addr, vec![with_read_only(true)]).unwrap();
+        assert!(client.is_read_only());
+        let ctx = RequestContext::background();
+        let append = AppendRequest::new(ContextId::new(7), "t", 1, vec![0x90]);
+
+        // One call per public mutating method. A message type added to the
+        // protocol is caught by `every_named_message_type_is_classified`;
+        // the raw sends below cover every type classified as a mutation.
+        let refusals: Vec<Error> = vec![
+            client
+                .create_context(&ctx, CreateContextOptions::default())
+                .unwrap_err(),
+            client.fork_context(&ctx, TurnId::new(3)).unwrap_err(),
+            client
+                .create_or_get_context_by_alias(&ctx, "a", CreateContextOptions::default())
+                .unwrap_err(),
+            client.append_turn(&ctx, &append).unwrap_err(),
+            client.append_dedup(&ctx, &append).unwrap_err(),
+            client.append_turn_with_fs(&ctx, &append, None).unwrap_err(),
+            client
+                .append_multi(&ctx, &[(ContextId::new(7), append.clone())])
+                .unwrap_err(),
+            client
+                .compact_context(
+                    &ctx,
+                    ContextId::new(7),
+                    CompactRequest::new(TurnId::new(3), "s", 1, vec![0x90]),
+                )
+                .unwrap_err(),
+            client
+                .prune_context(&ctx, ContextId::new(7), 2)
+                .unwrap_err(),
+            client
+                .redact_turn(
+                    &ctx,
+                    ContextId::new(7),
+                    TurnId::new(3),
+                    RedactOptions::default(),
+                )
+                .unwrap_err(),
+            client
+                .attach_fs(
+                    &ctx,
+                    &AttachFsRequest {
+                        turn_id: TurnId::new(3),
+                        fs_root_hash: [0; 32],
+                    },
+                )
+                .unwrap_err(),
+            client
+                .put_blob(&ctx, &PutBlobRequest { data: vec![1] })
+                .unwrap_err(),
+            client.put_blob_if_absent(&ctx, vec![1]).unwrap_err(),
+        ];
+        for err in &refusals {
+            assert!(matches!(err, Error::ReadOnlyClient { .. }), "{err}");
+        }
+        for msg_type in 0..=u16::MAX {
+            if msg_type_mutates(msg_type) == Some(true) {
+                let err = client.send_request(&ctx, msg_type, b"").unwrap_err();
+                let operation = msg_type_name(msg_type).unwrap();
+                assert!(
+                    matches!(&err, Error::ReadOnlyClient { operation: op } if op == operation),
+                    "{err}"
+                );
+            }
+        }
+
+        assert_eq!(
+            client
+                .get_head(&ctx, ContextId::new(7))
+                .unwrap()
+                .head_turn_id,
+            TurnId::new(3)
+        );
+        client.close().unwrap();
+        let received = handle.join().unwrap();
+        let types: Vec<u16> = received.iter().map(|f| f.header.msg_type).collect();
+        assert_eq!(types, [MSG_GET_HEAD]);
     }
 
     #[test]
@@ -600,9 +2470,9 @@ mod tests {
             .send_request(&ctx, crate::protocol::MSG_CTX_CREATE, &payload)
             .unwrap_err();
         match err {
-            Error::Server(server) => {
-                assert_eq!(server.code, 404);
-                assert_eq!(server.detail, "not found");
+            Error::Server { code, detail, .. } => {
+                assert_eq!(code.as_u32(), 404);
+                assert_eq!(detail, "not found");
             }
             other => panic!("expected server error, got {other:?}"),
         }
@@ -610,6 +2480,47 @@ mod tests {
         handle.join().unwrap();
     }
 
+    #[test]
+    fn bearer_token_is_sent_on_hello_and_rejection_is_typed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            for accepted in [true, false] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let hello = read_frame(&mut stream).unwrap();
+                assert_eq!(hello.header.msg_type, MSG_HELLO);
+                let mut expected = hello_payload("");
+                expected.write_u32::<LittleEndian>(6).unwrap();
+                expected.extend_from_slice(b"s3cr3t");
+                assert_eq!(hello.payload, expected);
+                if accepted {
+                    let mut resp = Vec::new();
+                    resp.write_u64::<LittleEndian>(1).unwrap();
+                    resp.write_u16::<LittleEndian>(1).unwrap();
+                    write_frame(&mut stream, MSG_HELLO, 0, hello.header.req_id, &resp).unwrap();
+                } else {
+                    let err = crate::test_util::error_payload(401, "token expired");
+                    write_frame(&mut stream, MSG_ERROR, 0, hello.header.req_id, &err).unwrap();
+                }
+            }
+        });
+
+        let options = [with_bearer_token("s3cr3t")];
+        let client = dial(&addr.to_string(), options.clone()).unwrap();
+        let err = client.redial().err().expect("redial is rejected");
+        assert!(
+            matches!(&err, Error::Unauthenticated { detail } if detail == "token expired"),
+            "got {err:?}"
+        );
+        assert!(!crate::reconnect::is_connection_error(&err));
+        handle.join().unwrap();
+
+        let mut opts = ClientOptions::default();
+        options[0](&mut opts);
+        assert!(!format!("{opts:?}").contains("s3cr3t"));
+    }
+
     fn hello_payload(tag: &str) -> Vec<u8> {
         let mut payload = Vec::new();
         payload.write_u16::<LittleEndian>(1).unwrap();
@@ -638,4 +2549,65 @@ mod tests {
             )),
         )
     }
+
+    #[test]
+    fn concurrent_appends_over_one_client_stay_framed() {
+        const THREADS: u64 = 16;
+        const APPENDS: u64 = 50;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_nodelay(true).unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            let mut resp = Vec::new();
+            resp.write_u64::<LittleEndian>(1).unwrap();
+            resp.write_u16::<LittleEndian>(1).unwrap();
+            write_frame(&mut stream, MSG_HELLO, 0, hello.header.req_id, &resp).unwrap();
+            // Acks echo the request's context id, so a response handed to
+            // the wrong caller shows up as the wrong context.
+            for turn_id in 1..=THREADS * APPENDS {
+                let req = read_frame(&mut stream).unwrap();
+                assert_eq!(req.header.msg_type, crate::protocol::MSG_APPEND_TURN);
+                let mut ack = req.payload[..8].to_vec();
+                ack.write_u64::<LittleEndian>(turn_id).unwrap();
+                ack.write_u32::<LittleEndian>(1).unwrap();
+                ack.extend_from_slice(&[0; 32]);
+                write_frame(
+                    &mut stream,
+                    crate::protocol::MSG_APPEND_TURN,
+                    0,
+                    req.header.req_id,
+                    &ack,
+                )
+                .unwrap();
+            }
+        });
+
+        let client = Arc::new(dial(&addr.to_string(), []).unwrap());
+        let writers: Vec<_> = (1..=THREADS)
+            .map(|context_id| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    let ctx = RequestContext::background();
+                    for n in 0..APPENDS {
+                        let payload = vec![n as u8; 64 * context_id as usize];
+                        let req = crate::AppendRequest::new(
+                            ContextId::new(context_id),
+                            "test",
+                            1,
+                            payload,
+                        );
+                        let result = client.append_turn(&ctx, &req).unwrap();
+                        assert_eq!(result.context_id.get(), context_id);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        handle.join().unwrap();
+        assert!(!client.is_poisoned());
+    }
 }
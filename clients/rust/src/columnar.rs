@@ -0,0 +1,211 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Columnar export of turn metadata as an Arrow [`RecordBatch`] (`arrow`
+//! feature).
+//!
+//! [`Client::export_arrow`] reads the history of each context and returns
+//! one row per turn, ready for DataFusion, Polars or any other Arrow
+//! consumer:
+//!
+//! | column         | type                 |
+//! |----------------|----------------------|
+//! | `context_id`   | `UInt64`             |
+//! | `turn_id`      | `UInt64`             |
+//! | `depth`        | `UInt32`             |
+//! | `type_id`      | `Utf8`               |
+//! | `type_version` | `UInt32`             |
+//! | `content_hash` | `Utf8` (BLAKE3, hex) |
+//! | `payload_len`  | `UInt32`             |
+//! | `payload`      | `Binary`, only with [`ArrowExportOptions::include_payloads`] |
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BinaryArray, StringArray, UInt32Array, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+use crate::turn::{GetLastOptions, Order, TurnRecord};
+
+/// Turns fetched per request while exporting.
+const EXPORT_PAGE_SIZE: u32 = 256;
+
+/// What [`Client::export_arrow`] includes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArrowExportOptions {
+    /// Add a `payload` column with each turn's uncompressed payload bytes.
+    /// Without it payloads are never fetched.
+    pub include_payloads: bool,
+}
+
+impl ArrowExportOptions {
+    pub fn include_payloads(mut self, include: bool) -> Self {
+        self.include_payloads = include;
+        self
+    }
+
+    /// The schema of batches exported with these options.
+    pub fn schema(&self) -> SchemaRef {
+        let mut fields = vec![
+            Field::new("context_id", DataType::UInt64, false),
+            Field::new("turn_id", DataType::UInt64, false),
+            Field::new("depth", DataType::UInt32, false),
+            Field::new("type_id", DataType::Utf8, false),
+            Field::new("type_version", DataType::UInt32, false),
+            Field::new("content_hash", DataType::Utf8, false),
+            Field::new("payload_len", DataType::UInt32, false),
+        ];
+        if self.include_payloads {
+            fields.push(Field::new("payload", DataType::Binary, false));
+        }
+        Arc::new(Schema::new(fields))
+    }
+}
+
+impl Client {
+    /// Exports the turns of every context in `context_ids`, each oldest
+    /// first, as one batch (see the [module docs](self) for the columns).
+    /// Compacted turns are included; expired turns are not.
+    pub fn export_arrow(
+        &self,
+        ctx: &RequestContext,
+        context_ids: &[ContextId],
+        opts: ArrowExportOptions,
+    ) -> Result<RecordBatch> {
+        let mut rows: Vec<(ContextId, TurnRecord)> = Vec::new();
+        for &context_id in context_ids {
+            let history = self.history(ctx, context_id, opts.include_payloads)?;
+            rows.extend(history.into_iter().map(|turn| (context_id, turn)));
+        }
+
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|(id, _)| id.get()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|(_, turn)| turn.turn_id.get()),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                rows.iter().map(|(_, turn)| turn.depth),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|(_, turn)| &turn.type_id),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                rows.iter().map(|(_, turn)| turn.type_version),
+            )),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(
+                |(_, turn)| blake3::Hash::from_bytes(turn.payload_hash).to_hex(),
+            ))),
+            Arc::new(UInt32Array::from_iter_values(
+                rows.iter().map(|(_, turn)| turn.payload_size),
+            )),
+        ];
+        if opts.include_payloads {
+            columns.push(Arc::new(BinaryArray::from_iter_values(
+                rows.iter().map(|(_, turn)| &turn.payload),
+            )));
+        }
+        RecordBatch::try_new(opts.schema(), columns).map_err(Error::encode_err)
+    }
+
+    /// Every live turn of `context_id`, oldest first, read page by page.
+    fn history(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        include_payloads: bool,
+    ) -> Result<Vec<TurnRecord>> {
+        let mut turns = Vec::new();
+        let mut before = None;
+        loop {
+            let mut opts = GetLastOptions {
+                limit: EXPORT_PAGE_SIZE,
+                include_payload: true,
+                before_turn_id: before,
+                ..Default::default()
+            }
+            .include_compacted(true)
+            .order(Order::NewestFirst);
+            if !include_payloads {
+                opts = opts.max_payload_bytes(0);
+            }
+            let mut page = self.get_last_stored(ctx, context_id, opts)?;
+            self.materialize_deltas(ctx, &mut page)?;
+            let full = page.len() == EXPORT_PAGE_SIZE as usize;
+            before = page.last().map(|turn| turn.turn_id);
+            turns.extend(page);
+            if !full {
+                break;
+            }
+        }
+        turns.reverse();
+        Ok(turns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_GET_LAST;
+    use crate::test_util::{spawn_multi_server, turn_page_payload};
+    use arrow::array::AsArray;
+    use arrow::datatypes::{UInt32Type, UInt64Type};
+
+    #[test]
+    fn exports_one_row_per_turn_across_contexts() {
+        // Context 1 holds turns 1..=300, context 2 turns 1..=2; payload
+        // sizes are turn_id % 4.
+        let addr = spawn_multi_server(|req| {
+            let context_id = u64::from_le_bytes(req.payload[..8].try_into().unwrap());
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let head = if context_id == 1 { 300 } else { 2 };
+            let before = match req.payload.get(36..44) {
+                Some(before) => u64::from_le_bytes(before.try_into().unwrap()),
+                None => head + 1,
+            };
+            let first = before.saturating_sub(limit).max(1);
+            let payloads: Vec<Vec<u8>> =
+                (first..before).map(|id| vec![0; id as usize % 4]).collect();
+            let payloads: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+            (MSG_GET_LAST, turn_page_payload(first, &payloads))
+        });
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let batch = client
+            .export_arrow(
+                &ctx,
+                &[ContextId::new(1), ContextId::new(2)],
+                ArrowExportOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(batch.num_rows(), 302);
+        assert_eq!(batch.schema(), ArrowExportOptions::default().schema());
+        let context_ids = batch.column(0).as_primitive::<UInt64Type>();
+        let turn_ids = batch.column(1).as_primitive::<UInt64Type>();
+        assert_eq!((context_ids.value(0), turn_ids.value(0)), (1, 1));
+        assert_eq!((context_ids.value(299), turn_ids.value(299)), (1, 300));
+        assert_eq!((context_ids.value(301), turn_ids.value(301)), (2, 2));
+        let payload_len = batch.column(6).as_primitive::<UInt32Type>();
+        assert_eq!(payload_len.value(2), 3);
+        assert_eq!(
+            batch.column(5).as_string::<i32>().value(0),
+            blake3::hash(&[0]).to_hex().as_str()
+        );
+
+        let batch = client
+            .export_arrow(
+                &ctx,
+                &[ContextId::new(2)],
+                ArrowExportOptions::default().include_payloads(true),
+            )
+            .unwrap();
+        let payloads = batch.column_by_name("payload").unwrap().as_binary::<i32>();
+        assert_eq!(payloads.value(1), [0, 0]);
+    }
+}
@@ -0,0 +1,293 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Frame compression negotiated at the HELLO handshake.
+//!
+//! [`with_compression`] lists a [`Codec`] in the client's HELLO. A server
+//! that has it echoes [`FLAG_COMPRESSED`] with the codec it chose, and from
+//! then on either side compresses the frames that gain from it (payloads of
+//! at least 512 bytes that shrink) and marks them with the flag; smaller
+//! frames go out plain. [`Client::negotiated_compression`] reports the
+//! outcome. A server without any of the listed codecs, or one that predates
+//! compression, leaves the connection uncompressed rather than failing the
+//! dial.
+//!
+//! This is separate from the per-turn `compression` field of stored
+//! payloads: compressed frames are decompressed on receipt, so every method
+//! sees the same payloads either way. The biggest savings are on
+//! [`Client::get_last`] responses with many large payloads.
+//!
+//! ```no_run
+//! use cxdb::compression::{with_compression, Codec};
+//! use cxdb::dial;
+//!
+//! let client = dial("127.0.0.1:9009", [with_compression(Codec::Zstd)])?;
+//! if client.negotiated_compression().is_none() {
+//!     eprintln!("server does not compress frames");
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`FLAG_COMPRESSED`]: crate::protocol::FLAG_COMPRESSED
+
+use std::sync::Arc;
+
+use crate::client::{Client, ClientOption};
+use crate::error::{Error, Result};
+use crate::protocol::{FrameHeader, FLAG_COMPRESSED};
+
+/// Payloads shorter than this go out plain even on compressed connections;
+/// the codec's framing would eat most of the saving.
+const COMPRESS_MIN_BYTES: usize = 512;
+
+/// zstd level frames are compressed at; favours speed, as the server does.
+const ZSTD_LEVEL: i32 = 1;
+
+/// A frame compression codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Codec {
+    Zstd,
+}
+
+impl Codec {
+    /// The codec's id on the wire, shared with the turn payload
+    /// `compression` field.
+    pub fn id(self) -> u8 {
+        match self {
+            Codec::Zstd => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Offers `codec` for frame compression at HELLO. Repeat the option to
+/// offer several, most preferred first; the server picks one it has, or
+/// none.
+pub fn with_compression(codec: Codec) -> ClientOption {
+    Arc::new(move |opts| {
+        if !opts.compression.contains(&codec) {
+            opts.compression.push(codec);
+        }
+    })
+}
+
+impl Client {
+    /// The codec frames on this connection are compressed with, or `None`
+    /// if compression was not offered or the server did not accept it.
+    pub fn negotiated_compression(&self) -> Option<Codec> {
+        self.compression()
+    }
+}
+
+/// A connection's negotiated codec with its reusable contexts.
+pub(crate) struct FrameCodec {
+    compressor: zstd::bulk::Compressor<'static>,
+    decompressor: zstd::bulk::Decompressor<'static>,
+}
+
+impl FrameCodec {
+    pub(crate) fn new(codec: Codec) -> Result<Self> {
+        match codec {
+            Codec::Zstd => Ok(Self {
+                compressor: zstd::bulk::Compressor::new(ZSTD_LEVEL)?,
+                decompressor: zstd::bulk::Decompressor::new()?,
+            }),
+        }
+    }
+
+    /// Compresses `payload` into `out` if it is large enough and shrinks;
+    /// returns whether it did.
+    pub(crate) fn compress(&mut self, payload: &[u8], out: &mut Vec<u8>) -> bool {
+        if payload.len() < COMPRESS_MIN_BYTES {
+            return false;
+        }
+        out.clear();
+        // Output that would not fit in fewer bytes than the input fails
+        // here and goes out plain.
+        out.reserve(payload.len() - 1);
+        matches!(
+            self.compressor.compress_to_buffer(payload, out),
+            Ok(len) if len < payload.len()
+        )
+    }
+
+    /// Decompresses the payload of a frame flagged with
+    /// [`FLAG_COMPRESSED`] into `out`, holding the result to
+    /// `max_frame_size` like a frame read off the wire.
+    pub(crate) fn decompress(
+        &mut self,
+        header: &FrameHeader,
+        payload: &[u8],
+        out: &mut Vec<u8>,
+        max_frame_size: u32,
+    ) -> Result<()> {
+        let corrupt = |detail: &str| {
+            Error::protocol(format!(
+                "frame decompression failed (msg_type {}, req_id {}): {detail}",
+                header.msg_type, header.req_id
+            ))
+        };
+        let len = zstd::zstd_safe::get_frame_content_size(payload)
+            .ok()
+            .flatten()
+            .ok_or_else(|| corrupt("no content size"))?;
+        if len > u64::from(max_frame_size) {
+            return Err(Error::FrameTooLarge {
+                len: u32::try_from(len).unwrap_or(u32::MAX),
+                max: max_frame_size,
+            });
+        }
+        out.clear();
+        out.reserve(len as usize);
+        let written = self
+            .decompressor
+            .decompress_to_buffer(payload, out)
+            .map_err(|err| corrupt(&err.to_string()))?;
+        if written as u64 != len {
+            return Err(corrupt("content size mismatch"));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `header` marks a compressed payload.
+pub(crate) fn is_compressed(header: &FrameHeader) -> bool {
+    header.flags & FLAG_COMPRESSED != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::client::RequestContext;
+    use crate::dial;
+    use crate::protocol::{read_frame, write_frame, MSG_GET_HEAD, MSG_GET_LAST, MSG_HELLO};
+    use crate::test_util::{spawn_scripted_server, turn_page_payload};
+    use crate::GetLastOptions;
+
+    #[test]
+    fn frame_codec_round_trips_and_skips_small_payloads() {
+        let mut codec = FrameCodec::new(Codec::Zstd).unwrap();
+        let mut compressed = Vec::new();
+        assert!(!codec.compress(b"tiny", &mut compressed));
+        assert!(!codec.compress(&random_bytes(4096), &mut compressed));
+
+        let payload = b"cxdb.ConversationItem ".repeat(200);
+        assert!(codec.compress(&payload, &mut compressed));
+        assert!(compressed.len() < payload.len() / 10);
+
+        let header = FrameHeader {
+            len: compressed.len() as u32,
+            msg_type: MSG_GET_LAST,
+            flags: FLAG_COMPRESSED,
+            req_id: 1,
+        };
+        let mut out = Vec::new();
+        codec
+            .decompress(&header, &compressed, &mut out, u32::MAX)
+            .unwrap();
+        assert_eq!(out, payload);
+
+        let err = codec
+            .decompress(&header, &compressed, &mut out, 64)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::FrameTooLarge { max: 64, .. }),
+            "{err:?}"
+        );
+        let err = codec
+            .decompress(&header, &payload[..64], &mut out, u32::MAX)
+            .unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)), "{err:?}");
+    }
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// A server that accepts zstd and answers GET_LAST with one turn of a
+    /// large, compressible payload, compressed, after checking that the
+    /// request it got was compressed too.
+    fn compressing_server(payload: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut codec = FrameCodec::new(Codec::Zstd).unwrap();
+            while let Ok(req) = read_frame(&mut stream) {
+                let (resp, flags) = if req.header.msg_type == MSG_HELLO {
+                    // Codecs follow an empty token.
+                    assert_ne!(req.header.flags & FLAG_COMPRESSED, 0);
+                    assert!(req.payload.ends_with(&[0, 0, 0, 0, 1, Codec::Zstd.id()]));
+                    let mut resp = 9u64.to_le_bytes().to_vec();
+                    resp.extend_from_slice(&1u16.to_le_bytes());
+                    resp.extend_from_slice(&u64::MAX.to_le_bytes());
+                    resp.push(Codec::Zstd.id());
+                    (resp, FLAG_COMPRESSED)
+                } else {
+                    // The GET_LAST request is too small to compress.
+                    assert_eq!(req.header.flags & FLAG_COMPRESSED, 0);
+                    let page = turn_page_payload(1, &[&payload]);
+                    let mut compressed = Vec::new();
+                    assert!(codec.compress(&page, &mut compressed));
+                    (compressed, FLAG_COMPRESSED)
+                };
+                let msg_type = req.header.msg_type;
+                if write_frame(&mut stream, msg_type, flags, req.header.req_id, &resp).is_err() {
+                    break;
+                }
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn compressed_responses_decode_like_plain_ones() {
+        let payload = b"hello compressed world ".repeat(500);
+        let addr = compressing_server(payload.clone());
+        let client = dial(&addr, [with_compression(Codec::Zstd)]).unwrap();
+        assert_eq!(client.negotiated_compression(), Some(Codec::Zstd));
+
+        let turns = client
+            .get_last(
+                &RequestContext::background(),
+                ContextId::new(1),
+                GetLastOptions {
+                    limit: 1,
+                    include_payload: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(turns[0].payload, payload);
+    }
+
+    #[test]
+    fn servers_without_the_codec_leave_frames_plain() {
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_HEAD, vec![0; 20])]);
+        let client = dial(&addr, [with_compression(Codec::Zstd)]).unwrap();
+        assert_eq!(client.negotiated_compression(), None);
+        client
+            .get_head(&RequestContext::background(), ContextId::new(1))
+            .unwrap();
+        let frames = handle.join().unwrap();
+        assert_eq!(frames[0].header.flags & FLAG_COMPRESSED, 0);
+    }
+}
@@ -1,54 +1,340 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 
+use crate::archive::read_context_summary;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::archive::{parse_context_list, ContextSummary};
+#[cfg(not(target_arch = "wasm32"))]
 use crate::client::{Client, RequestContext};
 use crate::error::{Error, Result};
-use crate::protocol::{MSG_CTX_CREATE, MSG_CTX_FORK, MSG_GET_HEAD};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::{
+    PayloadReader, MSG_CTX_CREATE, MSG_CTX_CREATE_ALIAS, MSG_CTX_FORK, MSG_GET_HEAD,
+    MSG_RESOLVE_ALIAS,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::{MSG_CTX_CREATE_WITH_TURN, MSG_GET_CONTEXT, MSG_LIST_CHILDREN};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::trace::{Op, OpSpan};
+use crate::turn::{encode_append_request, parse_append_result, AppendRequest, AppendResult};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ContextHead {
-    pub context_id: u64,
-    pub head_turn_id: u64,
+    pub context_id: ContextId,
+    pub head_turn_id: TurnId,
     pub head_depth: u32,
 }
 
+/// Options for contexts created by [`Client::create_context`],
+/// [`Client::create_or_get_context_by_alias`] and
+/// [`Client::create_context_with_turn`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CreateContextOptions {
+    /// Turn the new context starts from; the zero id for an empty context.
+    /// The new context shares the history up to this turn, as with
+    /// [`Client::fork_context`].
+    pub base_turn_id: TurnId,
+    /// Context the new one derives from, e.g. the conversation that
+    /// spawned a sub-agent. Unrelated to `base_turn_id`: a child may start
+    /// empty. Only [`Client::create_context`] takes a parent; contexts
+    /// created with their first turn name theirs in its provenance
+    /// ([`with_parent_context`](crate::types::with_parent_context)).
+    pub parent: Option<ContextId>,
+}
+
+impl CreateContextOptions {
+    pub fn base_turn_id(mut self, base_turn_id: TurnId) -> Self {
+        self.base_turn_id = base_turn_id;
+        self
+    }
+
+    pub fn parent(mut self, parent: ContextId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+}
+
+/// A context as [`Client::get_context`] reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextDetails {
+    pub context_id: ContextId,
+    pub head_turn_id: TurnId,
+    pub head_depth: u32,
+    pub archived: bool,
+    /// When the head turn was appended (the context created, while it is
+    /// empty), in Unix milliseconds.
+    pub updated_at_unix_ms: u64,
+    /// The context this one derives from: the parent given to
+    /// [`Client::create_context`], else the `parent_context_id` in its first
+    /// turn's provenance. The parent may since have been deleted.
+    pub parent: Option<ContextId>,
+}
+
+/// A context's head plus whether this call created it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextInfo {
+    pub context_id: ContextId,
+    pub head_turn_id: TurnId,
+    pub head_depth: u32,
+    /// False when the alias already named an existing context.
+    pub created: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 impl Client {
-    pub fn create_context(&self, ctx: &RequestContext, base_turn_id: u64) -> Result<ContextHead> {
-        let mut payload = Vec::with_capacity(8);
-        payload.write_u64::<LittleEndian>(base_turn_id)?;
-        let frame = self.send_request(ctx, MSG_CTX_CREATE, &payload)?;
-        parse_context_head(&frame.payload)
+    /// Creates a context. With the default options it starts empty and has
+    /// no parent; see [`CreateContextOptions`].
+    ///
+    /// A parent must exist, or this fails with [`Error::ContextNotFound`]
+    /// naming it. Servers that advertise their message types without
+    /// LIST_CHILDREN predate parents, and creating a child on them fails
+    /// with [`Error::Unsupported`] before anything is sent.
+    pub fn create_context(
+        &self,
+        ctx: &RequestContext,
+        opts: CreateContextOptions,
+    ) -> Result<ContextHead> {
+        let _timing = ctx.timing_scope();
+        let span = OpSpan::new(Op::CreateContext, ctx);
+        span.run(|| {
+            if opts.parent.is_some() && !self.capabilities().supports(MSG_LIST_CHILDREN) {
+                return Err(Error::Unsupported("CTX_CREATE parent".into()));
+            }
+            let frame = self
+                .send_request(ctx, MSG_CTX_CREATE, &encode_create_context(opts)?)
+                .map_err(|err| err.resolve_not_found())?;
+            let head = parse_context_head(&frame.payload)?;
+            span.context_id(head.context_id);
+            Ok(head)
+        })
+    }
+
+    /// Creates a context and appends `first` as its first turn in one round
+    /// trip, all or none: if the server rejects the turn, no context is left
+    /// behind, and no reader ever sees the context empty.
+    /// `opts.base_turn_id` is what [`Client::create_context`] takes.
+    /// `first.context_id` is ignored; a zero `first.parent_turn_id` appends
+    /// to the base turn. Returns the new context's head, which is the
+    /// appended turn, and the append result.
+    ///
+    /// The new context's id is not known until the server answers, so with
+    /// [payload encryption](crate::encryption) the turn is sealed with the
+    /// key the provider returns for context 0.
+    ///
+    /// Servers without the CTX_CREATE_WITH_TURN message fail with
+    /// [`Error::Unsupported`]; there is no non-atomic fallback. So does
+    /// `opts.parent`: name the parent in the turn's provenance instead.
+    pub fn create_context_with_turn(
+        &self,
+        ctx: &RequestContext,
+        opts: CreateContextOptions,
+        first: &AppendRequest,
+    ) -> Result<(ContextHead, AppendResult)> {
+        reject_parent(opts, "CTX_CREATE_WITH_TURN")?;
+        self.validate_append(first)?;
+        // The context has no history yet for a delta to refer to.
+        let mut first = first.clone();
+        first.context_id = ContextId::default();
+        first.delta_base = None;
+        let first = self.seal_append(&first)?;
+        let mut payload = Vec::with_capacity(136 + first.payload.len());
+        let flags = encode_create_with_turn(&mut payload, opts.base_turn_id, &first)?;
+        let frame = self
+            .send_request_with_flags(ctx, MSG_CTX_CREATE_WITH_TURN, flags, &payload)
+            .map_err(|err| {
+                err.resolve_unsupported("CTX_CREATE_WITH_TURN")
+                    .resolve_not_found()
+            })?;
+        parse_create_with_turn(&frame.payload)
     }
 
-    pub fn fork_context(&self, ctx: &RequestContext, base_turn_id: u64) -> Result<ContextHead> {
+    pub fn fork_context(&self, ctx: &RequestContext, base_turn_id: TurnId) -> Result<ContextHead> {
         let mut payload = Vec::with_capacity(8);
-        payload.write_u64::<LittleEndian>(base_turn_id)?;
-        let frame = self.send_request(ctx, MSG_CTX_FORK, &payload)?;
+        payload.write_u64::<LittleEndian>(base_turn_id.get())?;
+        let frame = self
+            .send_request(ctx, MSG_CTX_FORK, &payload)
+            .map_err(|err| err.resolve_not_found())?;
         parse_context_head(&frame.payload)
     }
 
-    pub fn get_head(&self, ctx: &RequestContext, context_id: u64) -> Result<ContextHead> {
+    /// Returns the context named by `alias`, creating it on first use.
+    ///
+    /// Aliases are unique server-side: concurrent callers with the same alias
+    /// all get the same context, and exactly one sees `created: true`. On an
+    /// existing alias `opts` is ignored. Aliases must be non-empty and at
+    /// most 1024 bytes. `opts.parent` fails with [`Error::Unsupported`].
+    pub fn create_or_get_context_by_alias(
+        &self,
+        ctx: &RequestContext,
+        alias: &str,
+        opts: CreateContextOptions,
+    ) -> Result<ContextInfo> {
+        reject_parent(opts, "CTX_CREATE_ALIAS")?;
+        let mut payload = Vec::with_capacity(12 + alias.len());
+        payload.write_u64::<LittleEndian>(opts.base_turn_id.get())?;
+        write_alias(&mut payload, alias)?;
+        let frame = self
+            .send_request(ctx, MSG_CTX_CREATE_ALIAS, &payload)
+            .map_err(|err| err.resolve_not_found())?;
+        parse_context_info(&frame.payload)
+    }
+
+    /// Looks up the context named by `alias` without creating it.
+    pub fn resolve_alias(&self, ctx: &RequestContext, alias: &str) -> Result<Option<ContextId>> {
+        let mut payload = Vec::with_capacity(4 + alias.len());
+        write_alias(&mut payload, alias)?;
+        let frame = self.send_request(ctx, MSG_RESOLVE_ALIAS, &payload)?;
+        let context_id = PayloadReader::new(&frame.payload, "resolve alias").u64("context_id")?;
+        Ok((context_id != 0).then_some(ContextId::new(context_id)))
+    }
+
+    pub fn get_head(&self, ctx: &RequestContext, context_id: ContextId) -> Result<ContextHead> {
         let mut payload = Vec::with_capacity(8);
-        payload.write_u64::<LittleEndian>(context_id)?;
-        let frame = self.send_request(ctx, MSG_GET_HEAD, &payload)?;
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        let frame = self
+            .send_request(ctx, MSG_GET_HEAD, &payload)
+            .map_err(|err| err.resolve_not_found())?;
         parse_context_head(&frame.payload)
     }
+
+    /// Reports whether `context_id` names a context, with one GET_HEAD.
+    /// Archived contexts exist; deleted ones do not. Other failures, such as
+    /// a dropped connection, are still errors.
+    pub fn context_exists(&self, ctx: &RequestContext, context_id: ContextId) -> Result<bool> {
+        exists(self.get_head(ctx, context_id))
+    }
+
+    /// Returns `context_id`'s head, whether it is archived, and its parent.
+    ///
+    /// Servers without the GET_CONTEXT message fail with
+    /// [`Error::Unsupported`].
+    pub fn get_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<ContextDetails> {
+        let frame = self
+            .send_request(ctx, MSG_GET_CONTEXT, &context_id.get().to_le_bytes())
+            .map_err(|err| err.resolve_unsupported("GET_CONTEXT").resolve_not_found())?;
+        parse_context_details(&frame.payload)
+    }
+
+    /// Lists the contexts whose [parent](ContextDetails::parent) is
+    /// `parent_id`, oldest first. A deleted parent still has children.
+    ///
+    /// Servers without the LIST_CHILDREN message fail with
+    /// [`Error::Unsupported`].
+    pub fn list_children(
+        &self,
+        ctx: &RequestContext,
+        parent_id: ContextId,
+    ) -> Result<Vec<ContextSummary>> {
+        let frame = self
+            .send_request(ctx, MSG_LIST_CHILDREN, &parent_id.get().to_le_bytes())
+            .map_err(|err| err.resolve_unsupported("LIST_CHILDREN"))?;
+        parse_context_list(&frame.payload)
+    }
+}
+
+/// Fails with [`Error::Unsupported`] when `opts` names a parent, which only
+/// CTX_CREATE carries.
+pub(crate) fn reject_parent(opts: CreateContextOptions, operation: &str) -> Result<()> {
+    match opts.parent {
+        Some(_) => Err(Error::Unsupported(format!("{operation} parent"))),
+        None => Ok(()),
+    }
+}
+
+/// Encodes a CTX_CREATE body: base_turn_id, then parent_context_id only when
+/// there is a parent, so parentless requests stay readable by any server.
+pub(crate) fn encode_create_context(opts: CreateContextOptions) -> Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(16);
+    payload.write_u64::<LittleEndian>(opts.base_turn_id.get())?;
+    if let Some(parent) = opts.parent {
+        payload.write_u64::<LittleEndian>(parent.get())?;
+    }
+    Ok(payload)
+}
+
+/// Maps a GET_HEAD result onto whether the context exists.
+pub(crate) fn exists(head: Result<ContextHead>) -> Result<bool> {
+    match head {
+        Ok(_) => Ok(true),
+        Err(Error::ContextNotFound { .. }) => Ok(false),
+        Err(err) => Err(err),
+    }
 }
 
-fn parse_context_head(payload: &[u8]) -> Result<ContextHead> {
+pub(crate) fn parse_context_head(payload: &[u8]) -> Result<ContextHead> {
     if payload.len() < 20 {
-        return Err(Error::invalid_response(format!(
+        return Err(Error::protocol(format!(
             "context head too short ({} bytes)",
             payload.len()
         )));
     }
-    let mut cursor = std::io::Cursor::new(payload);
+    let mut reader = PayloadReader::new(payload, "context head");
     Ok(ContextHead {
-        context_id: cursor.read_u64::<LittleEndian>()?,
-        head_turn_id: cursor.read_u64::<LittleEndian>()?,
-        head_depth: cursor.read_u32::<LittleEndian>()?,
+        context_id: reader.u64("context_id")?.into(),
+        head_turn_id: reader.u64("head_turn_id")?.into(),
+        head_depth: reader.u32("head_depth")?,
+    })
+}
+
+/// Encodes a CTX_CREATE_WITH_TURN body and returns its frame flags, which
+/// are those of the embedded APPEND_TURN request.
+pub(crate) fn encode_create_with_turn(
+    payload: &mut Vec<u8>,
+    base_turn_id: TurnId,
+    first: &AppendRequest,
+) -> Result<u16> {
+    payload.write_u64::<LittleEndian>(base_turn_id.get())?;
+    encode_append_request(payload, first, None)
+}
+
+/// Parses a CTX_CREATE_WITH_TURN response, an APPEND_TURN ack naming the
+/// new context.
+pub(crate) fn parse_create_with_turn(payload: &[u8]) -> Result<(ContextHead, AppendResult)> {
+    let result = parse_append_result(payload)?;
+    let head = ContextHead {
+        context_id: result.context_id,
+        head_turn_id: result.turn_id,
+        head_depth: result.depth,
+    };
+    Ok((head, result))
+}
+
+/// Parses a GET_CONTEXT response: a LIST_CONTEXTS entry, then the parent's
+/// id (0 for none).
+pub(crate) fn parse_context_details(payload: &[u8]) -> Result<ContextDetails> {
+    let mut reader = PayloadReader::new(payload, "context details");
+    let summary = read_context_summary(&mut reader)?;
+    let parent = reader.u64("parent_context_id")?;
+    Ok(ContextDetails {
+        context_id: summary.context_id,
+        head_turn_id: summary.head_turn_id,
+        head_depth: summary.head_depth,
+        archived: summary.archived,
+        updated_at_unix_ms: summary.updated_at_unix_ms,
+        parent: (parent != 0).then_some(ContextId::new(parent)),
+    })
+}
+
+fn write_alias(payload: &mut Vec<u8>, alias: &str) -> Result<()> {
+    payload.write_u32::<LittleEndian>(alias.len() as u32)?;
+    payload.extend_from_slice(alias.as_bytes());
+    Ok(())
+}
+
+fn parse_context_info(payload: &[u8]) -> Result<ContextInfo> {
+    let head = parse_context_head(payload)?;
+    let mut reader = PayloadReader::new(&payload[20..], "context info");
+    Ok(ContextInfo {
+        context_id: head.context_id,
+        head_turn_id: head.head_turn_id,
+        head_depth: head.head_depth,
+        created: reader.u32("created")? != 0,
     })
 }
 
@@ -77,4 +363,198 @@ mod tests {
         assert_eq!(fixture.msg_type, MSG_GET_HEAD);
         assert_eq!(decode_hex(&fixture.payload_hex), payload_u64(42));
     }
+
+    #[test]
+    fn alias_requests_round_trip() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{error_payload, spawn_scripted_server};
+
+        let mut created = Vec::new();
+        for field in [7u64, 0] {
+            created.write_u64::<LittleEndian>(field).unwrap();
+        }
+        created.write_u32::<LittleEndian>(0).unwrap();
+        created.write_u32::<LittleEndian>(1).unwrap();
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE_ALIAS, created),
+            (MSG_RESOLVE_ALIAS, payload_u64(7)),
+            (MSG_RESOLVE_ALIAS, payload_u64(0)),
+            (MSG_ERROR, error_payload(422, "alias is empty")),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let opts = CreateContextOptions::default().base_turn_id(TurnId::new(3));
+        let info = client
+            .create_or_get_context_by_alias(&ctx, "session:abc123", opts)
+            .unwrap();
+        assert_eq!(
+            info,
+            ContextInfo {
+                context_id: ContextId::new(7),
+                head_turn_id: TurnId::new(0),
+                head_depth: 0,
+                created: true,
+            }
+        );
+        assert_eq!(
+            client.resolve_alias(&ctx, "session:abc123").unwrap(),
+            Some(ContextId::new(7))
+        );
+        assert_eq!(client.resolve_alias(&ctx, "session:nope").unwrap(), None);
+        let err = client
+            .create_or_get_context_by_alias(&ctx, "", CreateContextOptions::default())
+            .unwrap_err();
+        assert!(crate::error::is_server_error(&err, 422), "got {err:?}");
+
+        let requests = handle.join().unwrap();
+        let mut expected = payload_u64(3);
+        expected.write_u32::<LittleEndian>(14).unwrap();
+        expected.extend_from_slice(b"session:abc123");
+        assert_eq!(requests[0].payload, expected);
+        assert_eq!(requests[1].payload, expected[8..]);
+    }
+
+    #[test]
+    fn create_context_with_turn_sends_base_and_first_turn() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{error_payload, spawn_scripted_server};
+
+        let mut ack = Vec::new();
+        ack.write_u64::<LittleEndian>(9).unwrap();
+        ack.write_u64::<LittleEndian>(41).unwrap();
+        ack.write_u32::<LittleEndian>(4).unwrap();
+        ack.extend_from_slice(&[0u8; 32]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE_WITH_TURN, ack),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        // The request's context id is not sent; the server assigns one.
+        let first = AppendRequest::new(ContextId::new(77), "chat.System", 1, vec![0x80]);
+        let opts = CreateContextOptions::default().base_turn_id(TurnId::new(40));
+        let (head, result) = client.create_context_with_turn(&ctx, opts, &first).unwrap();
+        assert_eq!(
+            head,
+            ContextHead {
+                context_id: ContextId::new(9),
+                head_turn_id: TurnId::new(41),
+                head_depth: 4,
+            }
+        );
+        assert_eq!((result.context_id.get(), result.turn_id.get()), (9, 41));
+        let err = client
+            .create_context_with_turn(&ctx, CreateContextOptions::default(), &first)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Unsupported(ref op) if op == "CTX_CREATE_WITH_TURN"),
+            "{err:?}"
+        );
+
+        let requests = handle.join().unwrap();
+        let mut expected = payload_u64(40);
+        let mut first = first.clone();
+        first.context_id = ContextId::default();
+        encode_append_request(&mut expected, &first, None).unwrap();
+        assert_eq!(requests[0].payload, expected);
+    }
+
+    #[test]
+    fn parents_are_sent_reported_and_listed() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{not_found_payload, spawn_scripted_server};
+
+        let entry = |context_id: u64, flags: u32| {
+            let mut out = payload_u64(context_id);
+            out.extend_from_slice(&payload_u64(0));
+            out.write_u32::<LittleEndian>(0).unwrap();
+            out.write_u32::<LittleEndian>(flags).unwrap();
+            out.extend_from_slice(&payload_u64(1_700_000_000_000));
+            out
+        };
+        let mut head = payload_u64(8);
+        head.extend_from_slice(&[0u8; 12]);
+        let mut details = entry(8, 1);
+        details.extend_from_slice(&payload_u64(5));
+        let mut children = 2u32.to_le_bytes().to_vec();
+        children.extend_from_slice(&entry(8, 0));
+        children.extend_from_slice(&entry(9, 0));
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, head),
+            (MSG_ERROR, not_found_payload("context", 6)),
+            (MSG_GET_CONTEXT, details),
+            (MSG_LIST_CHILDREN, children),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let opts = CreateContextOptions::default().parent(ContextId::new(5));
+        assert_eq!(
+            client.create_context(&ctx, opts).unwrap().context_id,
+            ContextId::new(8)
+        );
+        let err = client
+            .create_context(
+                &ctx,
+                CreateContextOptions::default().parent(ContextId::new(6)),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 6),
+            "{err:?}"
+        );
+        let got = client.get_context(&ctx, ContextId::new(8)).unwrap();
+        assert_eq!(
+            (got.context_id, got.archived, got.parent),
+            (ContextId::new(8), true, Some(ContextId::new(5)))
+        );
+        let ids: Vec<u64> = client
+            .list_children(&ctx, ContextId::new(5))
+            .unwrap()
+            .iter()
+            .map(|child| child.context_id.get())
+            .collect();
+        assert_eq!(ids, [8, 9]);
+        // Only CTX_CREATE carries a parent; nothing is sent for the others.
+        let err = client
+            .create_or_get_context_by_alias(&ctx, "a", opts)
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        let requests = handle.join().unwrap();
+        let mut expected = payload_u64(0);
+        expected.extend_from_slice(&payload_u64(5));
+        assert_eq!(requests[0].payload, expected);
+        assert_eq!(requests[2].payload, payload_u64(8));
+        assert_eq!(requests[3].payload, payload_u64(5));
+        assert_eq!(requests.len(), 4);
+    }
+
+    #[test]
+    fn context_exists_maps_not_found_to_false() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{error_payload, not_found_payload, spawn_scripted_server};
+
+        let mut head = payload_u64(7);
+        head.extend_from_slice(&payload_u64(3));
+        head.write_u32::<LittleEndian>(2).unwrap();
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_HEAD, head),
+            (MSG_ERROR, not_found_payload("context", 8)),
+            (MSG_ERROR, error_payload(500, "disk on fire")),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        assert!(client.context_exists(&ctx, ContextId::new(7)).unwrap());
+        assert!(!client.context_exists(&ctx, ContextId::new(8)).unwrap());
+        let err = client.context_exists(&ctx, ContextId::new(9)).unwrap_err();
+        assert!(crate::is_server_error(&err, 500), "{err:?}");
+
+        let requests = handle.join().unwrap();
+        assert!(requests.iter().all(|r| r.header.msg_type == MSG_GET_HEAD));
+        assert_eq!(requests[1].payload, payload_u64(8));
+    }
 }
@@ -0,0 +1,213 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rotating bearer credentials.
+//!
+//! [`with_bearer_token`] fixes one token for the life of the client. For
+//! OAuth or STS-style tokens that expire, install a [`CredentialProvider`]
+//! with [`with_credentials`] instead. The client asks it for a token on
+//! every HELLO (dial and redial) and before every request; when the token
+//! differs from the one the connection presented at HELLO, the request
+//! carries the new one as per-request auth (see
+//! [`RequestContext::with_auth`]), so a rotation needs no reconnect. A
+//! request or HELLO rejected with [`Error::Unauthenticated`] calls
+//! [`CredentialProvider::invalidate`], so the next call fetches afresh.
+//!
+//! [`CachedCredentials`] caches a fetched token for a fixed TTL.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use cxdb::credentials::{with_credentials, CachedCredentials};
+//! use cxdb::dial;
+//!
+//! # fn fetch_sts_token() -> cxdb::Result<String> { Ok(String::new()) }
+//! let provider = CachedCredentials::new(Duration::from_secs(300), fetch_sts_token);
+//! let client = dial("127.0.0.1:9009", [with_credentials(Arc::new(provider))])?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`with_bearer_token`]: crate::with_bearer_token
+//! [`RequestContext::with_auth`]: crate::RequestContext::with_auth
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client::ClientOption;
+use crate::error::{Error, Result};
+
+/// Supplies the bearer token to present. Like the client, it is
+/// synchronous; providers backed by an async token service block on it.
+///
+/// `token` is called before every request, so it should be cheap once a
+/// token is cached.
+pub trait CredentialProvider: Send + Sync {
+    /// The token to present now. An error fails the dial or request.
+    fn token(&self) -> Result<String>;
+
+    /// The server rejected the current token; the next [`token`] call
+    /// should not return it again.
+    ///
+    /// [`token`]: CredentialProvider::token
+    fn invalidate(&self) {}
+}
+
+impl fmt::Debug for dyn CredentialProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CredentialProvider")
+    }
+}
+
+/// Authenticates the client and every connection redialed from it with
+/// tokens from `provider`. Takes precedence over [`crate::with_bearer_token`].
+pub fn with_credentials(provider: Arc<dyn CredentialProvider>) -> ClientOption {
+    Arc::new(move |opts| opts.credentials = Some(provider.clone()))
+}
+
+/// A [`CredentialProvider`] that calls `fetch` for a token and reuses it for
+/// `ttl`, or until the server rejects it. Pick a `ttl` somewhat shorter
+/// than the token's real lifetime so it is replaced before it expires.
+pub struct CachedCredentials<F> {
+    fetch: F,
+    ttl: Duration,
+    cached: Mutex<Option<(String, Instant)>>,
+}
+
+impl<F> CachedCredentials<F>
+where
+    F: Fn() -> Result<String> + Send + Sync,
+{
+    pub fn new(ttl: Duration, fetch: F) -> Self {
+        Self {
+            fetch,
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+}
+
+impl<F> fmt::Debug for CachedCredentials<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedCredentials")
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl<F> CredentialProvider for CachedCredentials<F>
+where
+    F: Fn() -> Result<String> + Send + Sync,
+{
+    fn token(&self) -> Result<String> {
+        let mut cached = self.cached.lock().map_err(|_| Error::ClientClosed)?;
+        if let Some((token, fetched_at)) = cached.as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(token.clone());
+            }
+        }
+        let token = (self.fetch)()?;
+        *cached = Some((token.clone(), Instant::now()));
+        Ok(token)
+    }
+
+    fn invalidate(&self) {
+        if let Ok(mut cached) = self.cached.lock() {
+            *cached = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::AUTH_KEY;
+    use crate::protocol::{
+        encode_frame_metadata, read_frame, write_frame, FLAG_METADATA, MSG_ERROR, MSG_HELLO,
+    };
+    use crate::test_util::error_payload;
+    use crate::{dial, CreateContextOptions, RequestContext};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::thread;
+
+    #[test]
+    fn cached_credentials_refetch_after_ttl_or_invalidate() {
+        let fetches = AtomicU32::new(0);
+        let provider = CachedCredentials::new(Duration::from_millis(50), || {
+            Ok(format!("t{}", fetches.fetch_add(1, Ordering::SeqCst)))
+        });
+        assert_eq!(provider.token().unwrap(), "t0");
+        assert_eq!(provider.token().unwrap(), "t0");
+        provider.invalidate();
+        assert_eq!(provider.token().unwrap(), "t1");
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(provider.token().unwrap(), "t2");
+    }
+
+    #[test]
+    fn rotated_tokens_ride_on_requests_without_redial() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            let mut resp = Vec::new();
+            resp.write_u64::<LittleEndian>(1).unwrap();
+            resp.write_u16::<LittleEndian>(1).unwrap();
+            write_frame(
+                &mut stream,
+                MSG_HELLO,
+                FLAG_METADATA,
+                hello.header.req_id,
+                &resp,
+            )
+            .unwrap();
+
+            let mut received = vec![hello];
+            for response in [None, Some(error_payload(401, "token expired")), None] {
+                let req = read_frame(&mut stream).unwrap();
+                let (msg_type, payload) = match response {
+                    Some(payload) => (MSG_ERROR, payload),
+                    None => (2, vec![0u8; 20]),
+                };
+                write_frame(&mut stream, msg_type, 0, req.header.req_id, &payload).unwrap();
+                received.push(req);
+            }
+            received
+        });
+
+        let fetches = AtomicU32::new(0);
+        let provider = CachedCredentials::new(Duration::from_secs(60), move || {
+            Ok(format!("t{}", fetches.fetch_add(1, Ordering::SeqCst)))
+        });
+        let provider = Arc::new(provider);
+        let client = dial(&addr, [with_credentials(provider.clone())]).unwrap();
+        let ctx = RequestContext::background();
+        client
+            .create_context(&ctx, CreateContextOptions::default())
+            .unwrap();
+        // Rotate: the rejected token is dropped and the next call fetches.
+        provider.invalidate();
+        let err = client
+            .create_context(&ctx, CreateContextOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, Error::Unauthenticated { .. }), "got {err:?}");
+        client
+            .create_context(&ctx, CreateContextOptions::default())
+            .unwrap();
+
+        let received = handle.join().unwrap();
+        assert!(received[0].payload.ends_with(b"t0"));
+        assert_eq!(received[1].header.flags & FLAG_METADATA, 0);
+        let with_token = |token: &str| {
+            let mut block = Vec::new();
+            encode_frame_metadata(&mut block, [(AUTH_KEY, token)].into_iter()).unwrap();
+            block.extend_from_slice(&0u64.to_le_bytes());
+            block
+        };
+        assert_eq!(received[2].payload, with_token("t1"));
+        assert_eq!(received[3].payload, with_token("t2"));
+    }
+}
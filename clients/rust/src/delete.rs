@@ -0,0 +1,179 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Deleting contexts in bulk.
+//!
+//! [`Client::delete_contexts`] deletes every context a [`DeleteFilter`]
+//! matches. A filter's criteria are AND-ed: ids, metadata values and a
+//! creation cutoff. A filter naming none of them fails with
+//! [`Error::EmptyFilter`] before anything is sent, so a forgotten criterion
+//! can never delete every context.
+//!
+//! Deleting a context removes its head and frees its aliases. Its turns
+//! stay, as forks may share them, and its id is never reused.
+//!
+//! ```no_run
+//! # use cxdb::{dial, DeleteFilter, RequestContext};
+//! # let client = dial("127.0.0.1:9009", [])?;
+//! let filter = DeleteFilter::new()
+//!     .metadata("tag", "nightly-eval")
+//!     .created_before(1_735_689_600_000);
+//! let deleted = client.delete_contexts(&RequestContext::background(), &filter)?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::{PayloadReader, MSG_CTX_DELETE_MANY};
+
+/// Which contexts [`Client::delete_contexts`] deletes; see the
+/// [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteFilter {
+    /// Only these contexts. Ids naming no context are skipped.
+    pub context_ids: Vec<ContextId>,
+    /// Only contexts whose metadata holds every `(field, value)`, matched
+    /// exactly. Fields are named as in CQL: `tag`, `title`, `label`,
+    /// `user`, `service`, `host` and `trace_id`.
+    pub metadata: Vec<(String, String)>,
+    /// Only contexts created before this time, in Unix milliseconds. This
+    /// is when the context was created, not when it was last appended to.
+    pub created_before_unix_ms: Option<u64>,
+}
+
+impl DeleteFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `ids` to the contexts that may be deleted.
+    pub fn ids(mut self, ids: impl IntoIterator<Item = ContextId>) -> Self {
+        self.context_ids.extend(ids);
+        self
+    }
+
+    /// Requires metadata `field` to be `value`.
+    pub fn metadata(mut self, field: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((field.into(), value.into()));
+        self
+    }
+
+    /// Requires the context to have been created before `unix_ms`.
+    pub fn created_before(mut self, unix_ms: u64) -> Self {
+        self.created_before_unix_ms = Some(unix_ms);
+        self
+    }
+
+    /// Whether the filter names no criterion, and so is refused.
+    pub fn is_empty(&self) -> bool {
+        self.context_ids.is_empty()
+            && self.metadata.is_empty()
+            && self.created_before_unix_ms.is_none()
+    }
+
+    pub(crate) fn encode(&self) -> Result<Vec<u8>> {
+        if self.is_empty() {
+            return Err(Error::EmptyFilter);
+        }
+        let mut payload = Vec::with_capacity(17 + self.context_ids.len() * 8);
+        payload.write_u32::<LittleEndian>(self.context_ids.len() as u32)?;
+        for context_id in &self.context_ids {
+            payload.write_u64::<LittleEndian>(context_id.get())?;
+        }
+        payload.write_u32::<LittleEndian>(self.metadata.len() as u32)?;
+        for (field, value) in &self.metadata {
+            for text in [field, value] {
+                payload.write_u32::<LittleEndian>(text.len() as u32)?;
+                payload.extend_from_slice(text.as_bytes());
+            }
+        }
+        payload.write_u8(u8::from(self.created_before_unix_ms.is_some()))?;
+        payload.write_u64::<LittleEndian>(self.created_before_unix_ms.unwrap_or(0))?;
+        Ok(payload)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Deletes every context `filter` matches and returns how many were
+    /// deleted. An empty filter fails with [`Error::EmptyFilter`] without
+    /// contacting the server; so does an unknown metadata field, with the
+    /// server's 422.
+    ///
+    /// Drops the turn cache's entries for the contexts the filter may match
+    /// (see [`crate::cache`]).
+    ///
+    /// Servers without the CTX_DELETE_MANY message fail with
+    /// [`Error::Unsupported`].
+    pub fn delete_contexts(&self, ctx: &RequestContext, filter: &DeleteFilter) -> Result<u64> {
+        let payload = filter.encode()?;
+        let response = self.send_request(ctx, MSG_CTX_DELETE_MANY, &payload);
+        // Which contexts went is not reported, so no prefetched tail can be
+        // trusted to belong to a live context.
+        self.prefetch_cache().clear();
+        if let Some(cache) = self.turn_cache() {
+            // Only the listed ids can have gone, if the filter lists any.
+            if filter.context_ids.is_empty() {
+                cache.clear();
+            }
+            for &context_id in &filter.context_ids {
+                cache.invalidate(self.namespace_of(ctx), context_id);
+            }
+        }
+        let frame = response.map_err(|err| err.resolve_unsupported("CTX_DELETE_MANY"))?;
+        PayloadReader::new(&frame.payload, "delete contexts").u64("deleted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_ERROR;
+    use crate::test_util::{error_payload, spawn_scripted_server};
+
+    #[test]
+    fn delete_contexts_sends_the_filter_and_refuses_an_empty_one() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_DELETE_MANY, 2u64.to_le_bytes().to_vec()),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let err = client
+            .delete_contexts(&ctx, &DeleteFilter::new())
+            .unwrap_err();
+        assert!(matches!(err, Error::EmptyFilter), "{err:?}");
+
+        let filter = DeleteFilter::new()
+            .ids([4, 7].map(ContextId::new))
+            .metadata("tag", "eval")
+            .created_before(1_000);
+        assert_eq!(client.delete_contexts(&ctx, &filter).unwrap(), 2);
+        let err = client
+            .delete_contexts(&ctx, &DeleteFilter::new().ids([ContextId::new(4)]))
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported(ref op) if op == "CTX_DELETE_MANY"));
+
+        let requests = handle.join().unwrap();
+        // The empty filter never reached the server.
+        assert_eq!(requests.len(), 2);
+        let mut expected = 2u32.to_le_bytes().to_vec();
+        expected.extend_from_slice(&4u64.to_le_bytes());
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        for text in ["tag", "eval"] {
+            expected.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            expected.extend_from_slice(text.as_bytes());
+        }
+        expected.push(1);
+        expected.extend_from_slice(&1_000u64.to_le_bytes());
+        assert_eq!(requests[0].payload, expected);
+    }
+}
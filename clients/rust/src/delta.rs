@@ -0,0 +1,641 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Delta-encoded payloads.
+//!
+//! A turn appended with [`AppendRequest::delta_against`] is stored as a
+//! binary diff against an earlier turn's payload, for histories of large,
+//! mostly unchanged snapshots. The client computes the diff at append time
+//! and materializes the full payload on every read, so
+//! [`Client::get_last`], [`Client::get_turn`] and the other reads, as well
+//! as JSON Lines, snapshot and Arrow exports, return the payload, its
+//! original encoding and hash as if it had been stored whole. Set
+//! [`GetLastOptions::include_raw_delta`] to get the stored delta instead.
+//!
+//! The turn is stored with [`ENCODING_DELTA`] and an envelope:
+//!
+//! ```text
+//! magic "CXD1" | encoding u32 | base_turn_id u64 | chain u16
+//!   | base_hash [32] | payload_hash [32] | ops
+//! ```
+//!
+//! `encoding` and `payload_hash` (BLAKE3) describe the full payload,
+//! `base_hash` the base turn's full payload, and `chain` counts the deltas
+//! to read back to a whole payload (1 when the base is stored whole). Ops
+//! follow until the end: `0x00 offset len` copies `len` bytes of the base
+//! from `offset`, `0x01 len bytes` inserts `len` literal bytes, with
+//! integers as unsigned LEB128. The server stores the envelope as opaque
+//! bytes, hashed as stored.
+//!
+//! A base may itself be a delta. Chains are capped at
+//! [`MAX_DELTA_CHAIN`]: an append past it, or one whose delta would not be
+//! smaller than the payload, stores the payload whole. A read whose base is
+//! gone (pruned, redacted, or otherwise no longer holding the payload the
+//! delta was made against) fails with [`Error::DeltaBaseUnavailable`].
+//!
+//! Deltas are not combined with [payload encryption](crate::encryption):
+//! appends to a context with an encryption key are stored whole. The
+//! [`AsyncClient`](crate::AsyncClient) and server-side search and HTTP
+//! projection see the stored envelope.
+//!
+//! ```no_run
+//! use cxdb::{dial, AppendRequest, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", Vec::new())?;
+//! let ctx = RequestContext::background();
+//! let context_id = ContextId::new(1);
+//! let state = vec![0x90];
+//! let first = client.append_turn(&ctx, &AppendRequest::new(context_id, "com.example.AgentState", 1, state))?;
+//! let next = AppendRequest::new(context_id, "com.example.AgentState", 1, vec![0x91, 0x01])
+//!     .delta_against(first.turn_id);
+//! client.append_turn(&ctx, &next)?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`AppendRequest::delta_against`]: crate::AppendRequest::delta_against
+//! [`Client::get_last`]: crate::Client::get_last
+//! [`Client::get_turn`]: crate::Client::get_turn
+//! [`GetLastOptions::include_raw_delta`]: crate::GetLastOptions::include_raw_delta
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::TurnId;
+pub use crate::protocol::ENCODING_DELTA;
+use crate::protocol::{ENCODING_ENCRYPTED, ENCODING_MSGPACK};
+use crate::turn::{AppendRequest, TurnRecord};
+
+/// First bytes of every delta envelope.
+pub const DELTA_MAGIC: &[u8; 4] = b"CXD1";
+
+/// Most deltas read back to a whole payload; see the [module docs](self).
+pub const MAX_DELTA_CHAIN: u16 = 16;
+
+/// Shortest run of the base worth a copy op.
+const MIN_MATCH: usize = 16;
+
+const HEADER_LEN: usize = 4 + 4 + 8 + 2 + 32 + 32;
+
+const OP_COPY: u8 = 0;
+const OP_INSERT: u8 = 1;
+
+/// A parsed delta envelope.
+pub(crate) struct DeltaEnvelope<'a> {
+    /// Encoding of the full payload.
+    pub encoding: u32,
+    pub base_turn_id: TurnId,
+    pub chain: u16,
+    pub base_hash: [u8; 32],
+    pub payload_hash: [u8; 32],
+    pub ops: &'a [u8],
+}
+
+pub(crate) fn parse_delta_envelope(payload: &[u8]) -> Result<DeltaEnvelope<'_>> {
+    if payload.len() < HEADER_LEN || !payload.starts_with(DELTA_MAGIC) {
+        return Err(Error::decode("malformed delta envelope"));
+    }
+    let u64_at = |at: usize| u64::from_le_bytes(payload[at..at + 8].try_into().unwrap());
+    Ok(DeltaEnvelope {
+        encoding: u32::from_le_bytes(payload[4..8].try_into().unwrap()),
+        base_turn_id: TurnId::new(u64_at(8)),
+        chain: u16::from_le_bytes(payload[16..18].try_into().unwrap()),
+        base_hash: payload[18..50].try_into().unwrap(),
+        payload_hash: payload[50..82].try_into().unwrap(),
+        ops: &payload[HEADER_LEN..],
+    })
+}
+
+/// Encodes `target` as an envelope of ops against `base`.
+pub(crate) fn encode_delta(
+    base: &[u8],
+    base_turn_id: TurnId,
+    chain: u16,
+    target: &[u8],
+    encoding: u32,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + target.len() / 8);
+    out.extend_from_slice(DELTA_MAGIC);
+    out.extend_from_slice(&encoding.to_le_bytes());
+    out.extend_from_slice(&base_turn_id.get().to_le_bytes());
+    out.extend_from_slice(&chain.to_le_bytes());
+    out.extend_from_slice(blake3::hash(base).as_bytes());
+    out.extend_from_slice(blake3::hash(target).as_bytes());
+    diff(base, target, &mut out);
+    out
+}
+
+/// Appends ops rebuilding `target` from `base` to `out`.
+///
+/// Every `MIN_MATCH`-byte window of the base is indexed by its first
+/// offset. The target is scanned for windows found in the base, trying the
+/// base offset in step with the last copy first, so edits that keep the
+/// length line back up without a lookup; matches are extended both ways.
+fn diff(base: &[u8], target: &[u8], out: &mut Vec<u8>) {
+    let mut index: HashMap<&[u8], usize> = HashMap::new();
+    for offset in 0..base.len().saturating_sub(MIN_MATCH - 1) {
+        index
+            .entry(&base[offset..offset + MIN_MATCH])
+            .or_insert(offset);
+    }
+    let mut literal = 0;
+    let mut pos = 0;
+    // Where the base would continue if the pending literal replaced the
+    // same number of base bytes.
+    let mut in_step: Option<usize> = None;
+    while pos + MIN_MATCH <= target.len() {
+        let window = &target[pos..pos + MIN_MATCH];
+        let aligned = in_step
+            .map(|next| next + (pos - literal))
+            .filter(|&at| base.get(at..at + MIN_MATCH) == Some(window));
+        let Some(at) = aligned.or_else(|| index.get(window).copied()) else {
+            pos += 1;
+            continue;
+        };
+        let ahead = base[at..]
+            .iter()
+            .zip(&target[pos..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        let behind = base[..at]
+            .iter()
+            .rev()
+            .zip(target[literal..pos].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        push_insert(out, &target[literal..pos - behind]);
+        out.push(OP_COPY);
+        write_uvarint(out, (at - behind) as u64);
+        write_uvarint(out, (behind + ahead) as u64);
+        pos += ahead;
+        literal = pos;
+        in_step = Some(at + ahead);
+    }
+    push_insert(out, &target[literal..]);
+}
+
+fn push_insert(out: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    out.push(OP_INSERT);
+    write_uvarint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Applies `envelope` to `base`, the full payload of its base turn, and
+/// checks the result against the envelope's hash. `turn_id` names the delta
+/// turn in errors.
+pub(crate) fn apply_delta(
+    turn_id: TurnId,
+    envelope: &DeltaEnvelope<'_>,
+    base: &[u8],
+) -> Result<Vec<u8>> {
+    if blake3::hash(base).as_bytes() != &envelope.base_hash {
+        return Err(Error::DeltaBaseUnavailable {
+            turn_id,
+            base_turn_id: envelope.base_turn_id,
+        });
+    }
+    let malformed = || Error::decode(format!("turn {turn_id}: malformed delta ops"));
+    let mut out = Vec::new();
+    let mut ops = envelope.ops;
+    while let Some((&op, rest)) = ops.split_first() {
+        ops = rest;
+        match op {
+            OP_COPY => {
+                let offset = read_uvarint(&mut ops).ok_or_else(malformed)? as usize;
+                let len = read_uvarint(&mut ops).ok_or_else(malformed)? as usize;
+                let end = offset.checked_add(len).ok_or_else(malformed)?;
+                out.extend_from_slice(base.get(offset..end).ok_or_else(malformed)?);
+            }
+            OP_INSERT => {
+                let len = read_uvarint(&mut ops).ok_or_else(malformed)? as usize;
+                if ops.len() < len {
+                    return Err(malformed());
+                }
+                let (bytes, rest) = ops.split_at(len);
+                out.extend_from_slice(bytes);
+                ops = rest;
+            }
+            _ => return Err(malformed()),
+        }
+    }
+    if blake3::hash(&out).as_bytes() != &envelope.payload_hash {
+        return Err(Error::decode(format!(
+            "turn {turn_id}: delta does not reproduce its payload hash"
+        )));
+    }
+    Ok(out)
+}
+
+fn write_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_uvarint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Full payloads by turn id, shared across the turns of one read.
+type Materialized = HashMap<TurnId, Vec<u8>>;
+
+impl Client {
+    /// `req` with its payload replaced by a delta against its
+    /// [`AppendRequest::delta_against`] base, when that is allowed and
+    /// smaller.
+    pub(crate) fn delta_append<'a>(
+        &self,
+        ctx: &RequestContext,
+        req: &'a AppendRequest,
+    ) -> Result<Cow<'a, AppendRequest>> {
+        let Some(base_turn_id) = req.delta_base else {
+            return Ok(Cow::Borrowed(req));
+        };
+        let encrypted = self
+            .payload_keys()
+            .is_some_and(|keys| keys.encryption_key(req.context_id).is_some());
+        if encrypted || matches!(req.encoding, ENCODING_ENCRYPTED | ENCODING_DELTA) {
+            return Ok(Cow::Borrowed(req));
+        }
+        let (base, chain) = self.full_payload(ctx, base_turn_id, &mut Materialized::new())?;
+        if chain >= MAX_DELTA_CHAIN {
+            return Ok(Cow::Borrowed(req));
+        }
+        let encoding = if req.encoding == 0 {
+            ENCODING_MSGPACK
+        } else {
+            req.encoding
+        };
+        let delta = encode_delta(&base, base_turn_id, chain + 1, &req.payload, encoding);
+        if delta.len() >= req.payload.len() {
+            return Ok(Cow::Borrowed(req));
+        }
+        let mut stored = req.clone();
+        stored.payload = delta;
+        stored.encoding = ENCODING_DELTA;
+        Ok(Cow::Owned(stored))
+    }
+
+    /// Decrypts `records` and materializes their deltas.
+    pub(crate) fn open_payloads<P>(
+        &self,
+        ctx: &RequestContext,
+        records: &mut [TurnRecord<P>],
+    ) -> Result<()>
+    where
+        P: AsRef<[u8]> + From<Vec<u8>>,
+    {
+        self.decrypt_payloads(records)?;
+        self.materialize_deltas(ctx, records)
+    }
+
+    /// Like [`Client::open_payloads`] for a history read, keeping deltas as
+    /// stored when `raw_delta` is set.
+    pub(crate) fn open_last<P>(
+        &self,
+        ctx: &RequestContext,
+        records: &mut [TurnRecord<P>],
+        raw_delta: bool,
+    ) -> Result<()>
+    where
+        P: AsRef<[u8]> + From<Vec<u8>>,
+    {
+        self.decrypt_payloads(records)?;
+        if raw_delta {
+            return Ok(());
+        }
+        self.materialize_deltas(ctx, records)
+    }
+
+    /// Replaces each delta payload in `records` with the full payload, its
+    /// encoding and hash. Oldest first, so a page of consecutive deltas
+    /// fetches only the first one's base.
+    pub(crate) fn materialize_deltas<P>(
+        &self,
+        ctx: &RequestContext,
+        records: &mut [TurnRecord<P>],
+    ) -> Result<()>
+    where
+        P: AsRef<[u8]> + From<Vec<u8>>,
+    {
+        if !records
+            .iter()
+            .any(|record| record.encoding == ENCODING_DELTA)
+        {
+            return Ok(());
+        }
+        let mut order: Vec<usize> = (0..records.len()).collect();
+        order.sort_by_key(|&i| records[i].turn_id);
+        let mut materialized = Materialized::new();
+        for i in order {
+            let record = &mut records[i];
+            if record.redacted || record.payload_omitted {
+                continue;
+            }
+            if record.encoding != ENCODING_DELTA {
+                if record.encoding != ENCODING_ENCRYPTED {
+                    materialized.insert(record.turn_id, record.payload.as_ref().to_vec());
+                }
+                continue;
+            }
+            let envelope = parse_delta_envelope(record.payload.as_ref())?;
+            let (base, _) = self.delta_base(ctx, record.turn_id, &envelope, &mut materialized)?;
+            let payload = apply_delta(record.turn_id, &envelope, &base)?;
+            materialized.insert(record.turn_id, payload.clone());
+            record.encoding = envelope.encoding;
+            record.payload_hash = envelope.payload_hash;
+            record.payload_size = payload.len() as u32;
+            record.payload = P::from(payload);
+        }
+        Ok(())
+    }
+
+    /// The full payload of the base of `turn_id`'s delta, and its chain.
+    fn delta_base(
+        &self,
+        ctx: &RequestContext,
+        turn_id: TurnId,
+        envelope: &DeltaEnvelope<'_>,
+        materialized: &mut Materialized,
+    ) -> Result<(Vec<u8>, u16)> {
+        if envelope.base_turn_id >= turn_id {
+            return Err(Error::decode(format!(
+                "turn {turn_id}: delta base {} is not an earlier turn",
+                envelope.base_turn_id
+            )));
+        }
+        self.full_payload(ctx, envelope.base_turn_id, materialized)
+            .map_err(|err| match err {
+                Error::TurnNotFound { .. } | Error::Pruned { .. } | Error::Redacted { .. } => {
+                    Error::DeltaBaseUnavailable {
+                        turn_id,
+                        base_turn_id: envelope.base_turn_id,
+                    }
+                }
+                err => err,
+            })
+    }
+
+    /// The full payload of `turn_id` and, if it was read as a delta, its
+    /// chain. Walks the chain iteratively.
+    fn full_payload(
+        &self,
+        ctx: &RequestContext,
+        turn_id: TurnId,
+        materialized: &mut Materialized,
+    ) -> Result<(Vec<u8>, u16)> {
+        // Stored envelopes from `turn_id` back towards a whole payload.
+        let mut pending: Vec<(TurnId, Vec<u8>)> = Vec::new();
+        let mut next = turn_id;
+        let mut payload = loop {
+            if let Some(payload) = materialized.get(&next) {
+                break payload.clone();
+            }
+            if pending.len() > usize::from(MAX_DELTA_CHAIN) {
+                return Err(Error::decode(format!(
+                    "turn {turn_id}: delta chain longer than {MAX_DELTA_CHAIN}"
+                )));
+            }
+            let mut record = self.get_turn_stored(ctx, next)?;
+            self.decrypt_payloads(std::slice::from_mut(&mut record))?;
+            if record.redacted {
+                return Err(Error::Redacted { turn_id: next });
+            }
+            if record.encoding != ENCODING_DELTA {
+                break record.payload;
+            }
+            let base_turn_id = parse_delta_envelope(&record.payload)?.base_turn_id;
+            if base_turn_id >= next {
+                return Err(Error::decode(format!(
+                    "turn {next}: delta base {base_turn_id} is not an earlier turn"
+                )));
+            }
+            pending.push((next, record.payload));
+            next = base_turn_id;
+        };
+        let chain = match pending.first() {
+            Some((_, stored)) => parse_delta_envelope(stored)?.chain,
+            None => 0,
+        };
+        for (id, stored) in pending.into_iter().rev() {
+            let envelope = parse_delta_envelope(&stored)?;
+            payload = apply_delta(id, &envelope, &payload)?;
+            materialized.insert(id, payload.clone());
+        }
+        Ok((payload, chain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::encoding::encode_msgpack;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_ERROR, MSG_GET_LAST, MSG_GET_TURN};
+    use crate::test_util::{not_found_payload, spawn_scripted_server, turn_page_payload};
+    use crate::turn::GetLastOptions;
+
+    #[derive(Clone, Serialize)]
+    struct Memory {
+        key: String,
+        text: String,
+        score: f64,
+        hits: u32,
+    }
+
+    #[derive(Clone, Serialize)]
+    struct AgentState {
+        step: u64,
+        goal: String,
+        memories: Vec<Memory>,
+    }
+
+    /// An ~80KB agent snapshot and the next step's: one counter bumped,
+    /// scores and hits changed in a few memories, one memory dropped and
+    /// two added.
+    fn snapshots() -> (Vec<u8>, Vec<u8>) {
+        let memories: Vec<_> = (0..800)
+            .map(|i| Memory {
+                key: format!("memory-{i:04}"),
+                text: format!("observation {i}: the build for module {} passed", i * 7),
+                score: f64::from(i) / 3.0,
+                hits: i,
+            })
+            .collect();
+        let mut state = AgentState {
+            step: 41,
+            goal: "keep the release branch green".into(),
+            memories,
+        };
+        let before = encode_msgpack(&state).unwrap();
+        state.step += 1;
+        for i in [3, 150, 420, 700] {
+            state.memories[i].score += 0.5;
+            state.memories[i].hits += 1;
+        }
+        state.memories.remove(77);
+        for i in 0..2 {
+            state.memories.push(Memory {
+                key: format!("memory-new-{i}"),
+                text: "a fresh observation from this step".into(),
+                score: 1.0,
+                hits: 0,
+            });
+        }
+        (before, encode_msgpack(&state).unwrap())
+    }
+
+    fn delta_turn(turn_id: u64, envelope: &[u8]) -> Vec<u8> {
+        let mut stored = turn_page_payload(turn_id, &[envelope]);
+        // Offset 36 is the record's `encoding` field.
+        stored[36..40].copy_from_slice(&ENCODING_DELTA.to_le_bytes());
+        stored
+    }
+
+    #[test]
+    fn near_duplicate_snapshots_delta_to_a_fraction() {
+        let (base, next) = snapshots();
+        let delta = encode_delta(&base, TurnId::new(5), 1, &next, ENCODING_MSGPACK);
+        assert!(next.len() > 60_000, "snapshot is {} bytes", next.len());
+        // The header alone is 82 bytes; the ops cover a handful of edits.
+        assert!(
+            delta.len() * 100 < next.len(),
+            "{} byte delta for a {} byte payload",
+            delta.len(),
+            next.len()
+        );
+
+        let envelope = parse_delta_envelope(&delta).unwrap();
+        assert_eq!(envelope.base_turn_id, TurnId::new(5));
+        assert_eq!(envelope.encoding, ENCODING_MSGPACK);
+        assert_eq!(apply_delta(TurnId::new(6), &envelope, &base).unwrap(), next);
+
+        // Unrelated and empty payloads round trip too.
+        for (base, target) in [
+            (&b""[..], &next[..]),
+            (&next[..], &b""[..]),
+            (b"abc", b"xyz"),
+        ] {
+            let delta = encode_delta(base, TurnId::new(1), 1, target, ENCODING_MSGPACK);
+            let envelope = parse_delta_envelope(&delta).unwrap();
+            assert_eq!(
+                apply_delta(TurnId::new(2), &envelope, base).unwrap(),
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn deltas_against_another_base_are_refused() {
+        let (base, next) = snapshots();
+        let delta = encode_delta(&base, TurnId::new(5), 1, &next, ENCODING_MSGPACK);
+        let envelope = parse_delta_envelope(&delta).unwrap();
+        let err = apply_delta(TurnId::new(6), &envelope, &next).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DeltaBaseUnavailable { turn_id, base_turn_id }
+                if turn_id.get() == 6 && base_turn_id.get() == 5
+        ));
+    }
+
+    #[test]
+    fn client_appends_deltas_and_materializes_them_on_read() {
+        let (base, next) = snapshots();
+        let envelope = encode_delta(&base, TurnId::new(5), 1, &next, ENCODING_MSGPACK);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_TURN, turn_page_payload(5, &[&base])),
+            (MSG_APPEND_TURN, vec![0u8; 52]),
+            (MSG_GET_LAST, delta_turn(6, &envelope)),
+            (MSG_GET_TURN, turn_page_payload(5, &[&base])),
+            (MSG_GET_LAST, delta_turn(6, &envelope)),
+            (MSG_GET_LAST, delta_turn(6, &envelope)),
+            (MSG_ERROR, not_found_payload("turn", 5)),
+        ]);
+        let client = crate::dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let req = AppendRequest::builder(ContextId::new(1), "com.example.AgentState")
+            .payload(next.clone())
+            .delta_against(TurnId::new(5))
+            .build();
+        client.append_turn(&ctx, &req).unwrap();
+
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+        let turns = client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        assert_eq!(turns[0].encoding, ENCODING_MSGPACK);
+        assert_eq!(turns[0].payload, next);
+        assert_eq!(turns[0].payload_hash, *blake3::hash(&next).as_bytes());
+
+        let raw = client
+            .get_last(
+                &ctx,
+                ContextId::new(1),
+                opts.clone().include_raw_delta(true),
+            )
+            .unwrap();
+        assert_eq!(raw[0].encoding, ENCODING_DELTA);
+        assert_eq!(raw[0].payload, envelope);
+
+        // The base was pruned since.
+        let err = client.get_last(&ctx, ContextId::new(1), opts).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::DeltaBaseUnavailable { turn_id, base_turn_id }
+                    if turn_id.get() == 6 && base_turn_id.get() == 5
+            ),
+            "got {err:?}"
+        );
+
+        client.close().unwrap();
+        let frames = handle.join().unwrap();
+        let append = &frames[1].payload;
+        let type_id_end = 20 + "com.example.AgentState".len();
+        assert_eq!(
+            append[type_id_end + 4..type_id_end + 8],
+            ENCODING_DELTA.to_le_bytes()
+        );
+        assert!(append.len() < envelope.len() + 200);
+    }
+
+    #[test]
+    fn payloads_that_do_not_shrink_are_stored_whole() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_TURN, turn_page_payload(5, &[b"\x91\x01"])),
+            (MSG_APPEND_TURN, vec![0u8; 52]),
+        ]);
+        let client = crate::dial(&addr, []).unwrap();
+        let req = AppendRequest::new(ContextId::new(1), "t", 1, vec![0x92, 0x01, 0x02])
+            .delta_against(TurnId::new(5));
+        client
+            .append_turn(&RequestContext::background(), &req)
+            .unwrap();
+        client.close().unwrap();
+
+        let frames = handle.join().unwrap();
+        let append = &frames[1].payload;
+        assert_eq!(append[21 + 4..21 + 8], ENCODING_MSGPACK.to_le_bytes());
+    }
+}
@@ -9,36 +9,41 @@ use serde::Serialize;
 use serde_value::Value as SerdeValue;
 
 use crate::error::{Error, Result};
+use crate::protocol::MAX_DECODE_DEPTH;
 
 pub fn encode_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>> {
-    let value = serde_value::to_value(value)
-        .map_err(|err| Error::invalid_response(format!("msgpack encode error: {err}")))?;
+    let value = serde_value::to_value(value).map_err(Error::encode_err)?;
     let mut buf = Vec::new();
-    write_serde_value(&mut buf, &value)
-        .map_err(|err| Error::invalid_response(format!("msgpack encode error: {err}")))?;
+    write_serde_value(&mut buf, &value).map_err(Error::encode_err)?;
     Ok(buf)
 }
 
 pub fn decode_msgpack(data: &[u8]) -> Result<BTreeMap<u64, Value>> {
+    decode_msgpack_with_max_depth(data, MAX_DECODE_DEPTH)
+}
+
+/// Like [`decode_msgpack`], rejecting values nested deeper than `max_depth`.
+pub fn decode_msgpack_with_max_depth(
+    data: &[u8],
+    max_depth: usize,
+) -> Result<BTreeMap<u64, Value>> {
     let mut cursor = std::io::Cursor::new(data);
-    let value = rmpv::decode::read_value(&mut cursor)
-        .map_err(|err| Error::invalid_response(format!("msgpack decode error: {err}")))?;
+    let value = rmpv::decode::read_value_with_max_depth(&mut cursor, rmpv_depth(max_depth))
+        .map_err(Error::decode_err)?;
     let map = match value {
         Value::Map(entries) => entries,
-        _ => return Err(Error::invalid_response("msgpack payload is not a map")),
+        _ => return Err(Error::decode("msgpack payload is not a map")),
     };
 
     let mut out = BTreeMap::new();
     for (k, v) in map {
         let key = match k {
-            Value::Integer(i) => i
-                .as_u64()
-                .ok_or_else(|| Error::invalid_response("invalid map key"))?,
+            Value::Integer(i) => i.as_u64().ok_or_else(|| Error::decode("invalid map key"))?,
             Value::String(s) => s
                 .as_str()
                 .and_then(|s| s.parse::<u64>().ok())
-                .ok_or_else(|| Error::invalid_response("invalid map key"))?,
-            _ => return Err(Error::invalid_response("invalid map key")),
+                .ok_or_else(|| Error::decode("invalid map key"))?,
+            _ => return Err(Error::decode("invalid map key")),
         };
         out.insert(key, v);
     }
@@ -46,19 +51,58 @@ pub fn decode_msgpack(data: &[u8]) -> Result<BTreeMap<u64, Value>> {
 }
 
 pub fn decode_msgpack_into<T: DeserializeOwned>(data: &[u8]) -> Result<T> {
-    match rmp_serde::from_slice::<T>(data) {
+    decode_msgpack_into_with_max_depth(data, MAX_DECODE_DEPTH)
+}
+
+/// Like [`decode_msgpack_into`], rejecting values nested deeper than
+/// `max_depth`.
+pub fn decode_msgpack_into_with_max_depth<T: DeserializeOwned>(
+    data: &[u8],
+    max_depth: usize,
+) -> Result<T> {
+    let mut de = rmp_serde::Deserializer::from_read_ref(data);
+    de.set_max_depth(max_depth);
+    match T::deserialize(&mut de) {
         Ok(value) => Ok(value),
         Err(_) => {
             let mut cursor = std::io::Cursor::new(data);
-            let mut value = rmpv::decode::read_value(&mut cursor)
-                .map_err(|err| Error::invalid_response(format!("msgpack decode error: {err}")))?;
+            let mut value =
+                rmpv::decode::read_value_with_max_depth(&mut cursor, rmpv_depth(max_depth))
+                    .map_err(Error::decode_err)?;
             normalize_map_keys_to_string(&mut value);
-            rmpv::ext::from_value::<T>(value)
-                .map_err(|err| Error::invalid_response(format!("msgpack decode error: {err}")))
+            rmpv::ext::from_value::<T>(value).map_err(Error::decode_err)
         }
     }
 }
 
+/// Encodes `value` as CBOR (RFC 8949). Append it with
+/// [`AppendRequest::encoding`](crate::AppendRequest::encoding) set to
+/// [`ENCODING_CBOR`](crate::protocol::ENCODING_CBOR) so reads decode it as
+/// CBOR.
+#[cfg(feature = "cbor")]
+pub fn encode_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    ciborium::into_writer(value, &mut buf).map_err(Error::encode_err)?;
+    Ok(buf)
+}
+
+#[cfg(feature = "cbor")]
+pub fn decode_cbor<T: DeserializeOwned>(data: &[u8]) -> Result<T> {
+    decode_cbor_with_max_depth(data, MAX_DECODE_DEPTH)
+}
+
+/// Like [`decode_cbor`], rejecting values nested deeper than `max_depth`.
+#[cfg(feature = "cbor")]
+pub fn decode_cbor_with_max_depth<T: DeserializeOwned>(data: &[u8], max_depth: usize) -> Result<T> {
+    ciborium::de::from_reader_with_recursion_limit(data, max_depth).map_err(Error::decode_err)
+}
+
+/// rmpv charges two units per container level (one for the marker, one for
+/// its contents), whereas `max_depth` counts nesting levels.
+pub(crate) fn rmpv_depth(max_depth: usize) -> usize {
+    max_depth.saturating_mul(2).saturating_add(1)
+}
+
 #[allow(non_snake_case)]
 pub fn EncodeMsgpack<T: Serialize>(value: &T) -> Result<Vec<u8>> {
     encode_msgpack(value)
@@ -0,0 +1,471 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client-side payload encryption.
+//!
+//! Install a [`KeyProvider`] with [`with_payload_encryption`] and the client
+//! encrypts every payload it appends to a context the provider has a key
+//! for, so the server (and its operators) only ever hold ciphertext. Reads
+//! decrypt transparently: [`Client::get_last`] and the other reads return
+//! the plaintext and its original encoding, ready to
+//! [`decode`](crate::TurnRecord::decode).
+//!
+//! Payloads are sealed with XChaCha20-Poly1305 under a fresh random nonce
+//! into an envelope:
+//!
+//! ```text
+//! magic "CXE1" | encoding u32 | key_id_len u8 | key_id | nonce [24] | ciphertext | tag [16]
+//! ```
+//!
+//! where `encoding` is the plaintext's encoding and everything before the
+//! ciphertext is authenticated as associated data. The turn itself is
+//! stored with [`ENCODING_ENCRYPTED`], and its `payload_hash` is the hash
+//! of the envelope, so the server still verifies what it stores. A turn
+//! whose key the provider does not return stays sealed, and decoding it
+//! fails with [`Error::NoDecryptionKey`].
+//!
+//! JSON Lines exports, snapshots and Arrow exports keep the envelope as
+//! stored; importing or restoring them appends it unchanged, so the copy
+//! opens with the same key. Sealed payloads are never re-encrypted.
+//!
+//! Because every append uses a new nonce, equal plaintexts never share a
+//! hash: [`Client::append_dedup`] and server search cannot see through
+//! encryption. The [`AsyncClient`](crate::AsyncClient) does not encrypt or
+//! decrypt.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use cxdb::encryption::{with_payload_encryption, PayloadKey};
+//! use cxdb::dial;
+//!
+//! // One key for every context; implement KeyProvider to choose per context.
+//! let key = PayloadKey::new("2025-01", [7; 32]);
+//! let client = dial("127.0.0.1:9009", [with_payload_encryption(Arc::new(key))])?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`Client::get_last`]: crate::Client::get_last
+//! [`Client::append_dedup`]: crate::Client::append_dedup
+
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::borrow::Cow;
+
+#[cfg(not(target_arch = "wasm32"))]
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+#[cfg(not(target_arch = "wasm32"))]
+use ring::rand::{SecureRandom, SystemRandom};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, ClientOption};
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+pub use crate::protocol::ENCODING_ENCRYPTED;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::turn::{AppendRequest, TurnRecord};
+
+/// First bytes of every envelope.
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"CXE1";
+
+/// Longest key id an envelope can carry, in bytes.
+pub const MAX_KEY_ID_LEN: usize = u8::MAX as usize;
+
+const NONCE_LEN: usize = 24;
+#[cfg(not(target_arch = "wasm32"))]
+const TAG_LEN: usize = 16;
+
+/// A 256-bit payload key and the id recorded in the envelopes it seals.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PayloadKey {
+    pub id: String,
+    key: [u8; 32],
+}
+
+impl PayloadKey {
+    pub fn new(id: impl Into<String>, key: [u8; 32]) -> Self {
+        Self { id: id.into(), key }
+    }
+}
+
+impl fmt::Debug for PayloadKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PayloadKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Supplies the keys payloads are sealed and opened with.
+///
+/// Called on the appending or reading thread, once per turn; cache keys
+/// fetched from a KMS.
+pub trait KeyProvider: Send + Sync {
+    /// The key new turns of `context_id` are sealed with, or `None` to
+    /// append them in the clear.
+    fn encryption_key(&self, context_id: ContextId) -> Option<PayloadKey>;
+
+    /// The key with `key_id`, for opening turns sealed with it. `None`
+    /// leaves such turns sealed.
+    fn decryption_key(&self, key_id: &str) -> Option<PayloadKey>;
+}
+
+impl fmt::Debug for dyn KeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyProvider")
+    }
+}
+
+/// A single key used for every context.
+impl KeyProvider for PayloadKey {
+    fn encryption_key(&self, _context_id: ContextId) -> Option<PayloadKey> {
+        Some(self.clone())
+    }
+
+    fn decryption_key(&self, key_id: &str) -> Option<PayloadKey> {
+        (key_id == self.id).then(|| self.clone())
+    }
+}
+
+/// Encrypts the payloads of the client and every connection redialed from
+/// it with keys from `provider` (see the [module docs](self)).
+#[cfg(not(target_arch = "wasm32"))]
+pub fn with_payload_encryption(provider: Arc<dyn KeyProvider>) -> ClientOption {
+    Arc::new(move |opts| opts.payload_keys = Some(provider.clone()))
+}
+
+/// The parsed header of an envelope.
+pub(crate) struct Envelope<'a> {
+    /// Encoding of the plaintext.
+    pub encoding: u32,
+    pub key_id: &'a str,
+    pub nonce: [u8; NONCE_LEN],
+    /// The header bytes, authenticated as associated data.
+    pub header: &'a [u8],
+    /// Ciphertext followed by the tag.
+    pub sealed: &'a [u8],
+}
+
+pub(crate) fn parse_envelope(payload: &[u8]) -> Result<Envelope<'_>> {
+    let malformed = || Error::decode("malformed encryption envelope");
+    let rest = payload
+        .strip_prefix(ENVELOPE_MAGIC.as_slice())
+        .ok_or_else(malformed)?;
+    let (encoding, rest) = rest.split_first_chunk::<4>().ok_or_else(malformed)?;
+    let (&key_id_len, rest) = rest.split_first().ok_or_else(malformed)?;
+    if rest.len() < key_id_len as usize + NONCE_LEN {
+        return Err(malformed());
+    }
+    let (key_id, rest) = rest.split_at(key_id_len as usize);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    Ok(Envelope {
+        encoding: u32::from_le_bytes(*encoding),
+        key_id: std::str::from_utf8(key_id).map_err(|_| malformed())?,
+        nonce: nonce.try_into().expect("nonce length checked"),
+        header: &payload[..payload.len() - sealed.len()],
+        sealed,
+    })
+}
+
+/// Seals `plaintext`, of `encoding`, under `key` with `nonce`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn seal(
+    key: &PayloadKey,
+    encoding: u32,
+    plaintext: &[u8],
+    nonce: [u8; NONCE_LEN],
+) -> Result<Vec<u8>> {
+    if key.id.len() > MAX_KEY_ID_LEN {
+        return Err(Error::encode(format!(
+            "payload key id longer than {MAX_KEY_ID_LEN} bytes"
+        )));
+    }
+    let mut out = Vec::with_capacity(9 + key.id.len() + NONCE_LEN + plaintext.len() + TAG_LEN);
+    out.extend_from_slice(ENVELOPE_MAGIC);
+    out.extend_from_slice(&encoding.to_le_bytes());
+    out.push(key.id.len() as u8);
+    out.extend_from_slice(key.id.as_bytes());
+    out.extend_from_slice(&nonce);
+    let header_len = out.len();
+    out.extend_from_slice(plaintext);
+    let (header, in_out) = out.split_at_mut(header_len);
+    let tag = xchacha_key(&key.key, &nonce)
+        .seal_in_place_separate_tag(chacha_nonce(&nonce), Aad::from(&*header), in_out)
+        .map_err(|_| Error::encode("payload encryption failed"))?;
+    out.extend_from_slice(tag.as_ref());
+    Ok(out)
+}
+
+/// Opens `envelope` with `key`, returning the plaintext.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn open(key: &PayloadKey, envelope: &Envelope<'_>) -> Option<Vec<u8>> {
+    let mut in_out = envelope.sealed.to_vec();
+    let len = xchacha_key(&key.key, &envelope.nonce)
+        .open_in_place(
+            chacha_nonce(&envelope.nonce),
+            Aad::from(envelope.header),
+            &mut in_out,
+        )
+        .ok()?
+        .len();
+    in_out.truncate(len);
+    Some(in_out)
+}
+
+/// XChaCha20-Poly1305 is ChaCha20-Poly1305 under a subkey derived from the
+/// first 16 nonce bytes, with the last 8 as the nonce.
+#[cfg(not(target_arch = "wasm32"))]
+fn xchacha_key(key: &[u8; 32], nonce: &[u8; NONCE_LEN]) -> LessSafeKey {
+    let subkey = hchacha20(key, nonce[..16].try_into().expect("16-byte prefix"));
+    LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &subkey).expect("32-byte key"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn chacha_nonce(nonce: &[u8; NONCE_LEN]) -> Nonce {
+    let mut out = [0u8; 12];
+    out[4..].copy_from_slice(&nonce[16..]);
+    Nonce::assume_unique_for_key(out)
+}
+
+/// HChaCha20 (draft-irtf-cfrg-xchacha, section 2.2).
+#[cfg(not(target_arch = "wasm32"))]
+fn hchacha20(key: &[u8; 32], nonce: &[u8; 16]) -> [u8; 32] {
+    let mut s = [0u32; 16];
+    s[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for (word, bytes) in s[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().expect("4 bytes"));
+    }
+    for (word, bytes) in s[12..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().expect("4 bytes"));
+    }
+    for _ in 0..10 {
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+    let mut out = [0u8; 32];
+    for (bytes, word) in out.chunks_exact_mut(4).zip(s[..4].iter().chain(&s[12..])) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// `req` with its payload sealed, if the key provider has a key for its
+    /// context. Payloads already sealed are left alone.
+    pub(crate) fn seal_append<'a>(&self, req: &'a AppendRequest) -> Result<Cow<'a, AppendRequest>> {
+        let key = match self.payload_keys() {
+            Some(keys) if req.encoding != ENCODING_ENCRYPTED => keys.encryption_key(req.context_id),
+            _ => None,
+        };
+        let Some(key) = key else {
+            return Ok(Cow::Borrowed(req));
+        };
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| Error::encode("no randomness for payload nonce"))?;
+        let encoding = if req.encoding == 0 {
+            crate::protocol::ENCODING_MSGPACK
+        } else {
+            req.encoding
+        };
+        let mut sealed = req.clone();
+        sealed.payload = seal(&key, encoding, &req.payload, nonce)?;
+        sealed.encoding = ENCODING_ENCRYPTED;
+        Ok(Cow::Owned(sealed))
+    }
+
+    /// Opens the sealed payloads of `records` the key provider has keys
+    /// for, restoring their original encoding. A payload that fails
+    /// authentication is an [`Error::Decode`].
+    pub(crate) fn decrypt_payloads<P>(&self, records: &mut [TurnRecord<P>]) -> Result<()>
+    where
+        P: AsRef<[u8]> + From<Vec<u8>>,
+    {
+        let Some(keys) = self.payload_keys() else {
+            return Ok(());
+        };
+        for record in records {
+            if record.encoding != ENCODING_ENCRYPTED || record.redacted || record.payload_omitted {
+                continue;
+            }
+            let envelope = parse_envelope(record.payload.as_ref())?;
+            let Some(key) = keys.decryption_key(envelope.key_id) else {
+                continue;
+            };
+            let plaintext = open(&key, &envelope).ok_or_else(|| {
+                Error::decode(format!(
+                    "turn {}: payload failed authentication with key {}",
+                    record.turn_id, envelope.key_id
+                ))
+            })?;
+            record.encoding = envelope.encoding;
+            record.payload = P::from(plaintext);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn hchacha20_matches_the_draft_vector() {
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let nonce: [u8; 16] = unhex("000000090000004a0000000031415927")
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            hchacha20(&key, &nonce).to_vec(),
+            unhex("82413b4227b27bfed30e42508a877d73a0f9e4d58a74a853c12ec41326d3ecdc")
+        );
+    }
+
+    #[test]
+    fn xchacha20_poly1305_matches_the_draft_vector() {
+        let key: [u8; 32] = std::array::from_fn(|i| 0x80 + i as u8);
+        let nonce: [u8; 24] = std::array::from_fn(|i| 0x40 + i as u8);
+        let aad = unhex("50515253c0c1c2c3c4c5c6c7");
+        let mut in_out = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+only one tip for the future, sunscreen would be it."
+            .to_vec();
+        let tag = xchacha_key(&key, &nonce)
+            .seal_in_place_separate_tag(chacha_nonce(&nonce), Aad::from(&aad[..]), &mut in_out)
+            .unwrap();
+        assert_eq!(
+            in_out,
+            unhex(
+                "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb
+                 731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452
+                 2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9
+                 21f9664c97637da9768812f615c68b13b52e"
+            )
+        );
+        assert_eq!(tag.as_ref(), unhex("c0875924c1c7987947deafd8780acf49"));
+    }
+
+    #[test]
+    fn envelopes_round_trip_and_reject_tampering() {
+        let key = PayloadKey::new("k1", [9; 32]);
+        let sealed = seal(&key, 2, b"secret", [5; NONCE_LEN]).unwrap();
+        assert!(sealed.starts_with(ENVELOPE_MAGIC));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+
+        let envelope = parse_envelope(&sealed).unwrap();
+        assert_eq!((envelope.encoding, envelope.key_id), (2, "k1"));
+        assert_eq!(open(&key, &envelope).unwrap(), b"secret");
+        assert!(open(&PayloadKey::new("k1", [8; 32]), &envelope).is_none());
+
+        // The header is authenticated: a changed encoding fails to open.
+        let mut tampered = sealed.clone();
+        tampered[4] = 1;
+        assert!(open(&key, &parse_envelope(&tampered).unwrap()).is_none());
+
+        assert!(parse_envelope(b"CXE1\x01\x00").is_err());
+        assert!(parse_envelope(b"\x81\xa1a").is_err());
+    }
+
+    /// A GET_LAST response holding one turn with `payload` stored as
+    /// `encoding`.
+    fn record_as(payload: &[u8], encoding: u32) -> Vec<u8> {
+        let mut out = crate::test_util::turn_records_payload(&[payload]);
+        // Count, turn_id, parent_id, depth, "test" type_id and version
+        // precede the encoding.
+        out[36..40].copy_from_slice(&encoding.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn clients_seal_appends_and_open_reads_with_their_keys() {
+        use crate::protocol::{MSG_APPEND_TURN, MSG_GET_LAST};
+        use crate::test_util::spawn_scripted_server;
+        use crate::{dial, encode_msgpack, GetLastOptions, RequestContext};
+
+        let key = PayloadKey::new("k1", [3; 32]);
+        let plaintext = encode_msgpack(&"hello").unwrap();
+        let envelope = seal(&key, crate::protocol::ENCODING_MSGPACK, &plaintext, [1; 24]).unwrap();
+        let reply = record_as(&envelope, ENCODING_ENCRYPTED);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_APPEND_TURN, vec![0u8; 52]),
+            (MSG_GET_LAST, reply.clone()),
+        ]);
+        let client = dial(&addr, [with_payload_encryption(Arc::new(key.clone()))]).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let req = AppendRequest::new(ContextId::new(1), "test", 1, plaintext.clone());
+        client.append_turn(&ctx, &req).unwrap();
+        let turns = client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        assert_eq!(turns[0].encoding, crate::protocol::ENCODING_MSGPACK);
+        assert_eq!(turns[0].decode::<String>().unwrap(), "hello");
+        assert_eq!(turns[0].payload_hash, *blake3::hash(&envelope).as_bytes());
+
+        // The appended payload is an envelope under the client's key, hashed
+        // as sealed.
+        let requests = handle.join().unwrap();
+        let append = &requests[0].payload;
+        let encoding_at = 8 + 8 + 4 + 4 + 4;
+        assert_eq!(
+            append[encoding_at..encoding_at + 4],
+            ENCODING_ENCRYPTED.to_le_bytes()
+        );
+        let sealed = &append[encoding_at + 48..];
+        let sealed = &sealed[..sealed.len() - 4];
+        assert_eq!(
+            &append[encoding_at + 12..encoding_at + 44],
+            blake3::hash(sealed).as_bytes()
+        );
+        assert_eq!(
+            open(&key, &parse_envelope(sealed).unwrap()).unwrap(),
+            plaintext
+        );
+
+        // Without the key the turn stays sealed.
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_LAST, reply)]);
+        let other = PayloadKey::new("k2", [4; 32]);
+        let client = dial(&addr, [with_payload_encryption(Arc::new(other))]).unwrap();
+        let turns = client.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        assert_eq!(turns[0].encoding, ENCODING_ENCRYPTED);
+        let err = turns[0].decode::<String>().unwrap_err();
+        assert!(
+            matches!(&err, Error::NoDecryptionKey { key_id } if key_id == "k1"),
+            "{err:?}"
+        );
+        handle.join().unwrap();
+    }
+}
@@ -1,50 +1,445 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::BTreeMap;
 use std::fmt;
+use std::time::Duration;
+
+use crate::fstree::FstreeError;
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::{
+    ERROR_CONTEXT_ARCHIVED, ERROR_FLAG_RETRYABLE, ERROR_PRUNED, ERROR_QUOTA_EXCEEDED,
+    ERROR_REPLICA_LAGGING, ERROR_UNAUTHENTICATED,
+};
+use crate::validate::ValidationError;
 
 /// CXDB client error type.
+///
+/// Every fallible public API in this crate returns [`Result`], so callers can
+/// `match` on the variant instead of inspecting message strings.
 #[derive(Debug)]
 pub enum Error {
-    ClientClosed,
-    ContextNotFound,
-    TurnNotFound,
-    InvalidResponse(String),
-    Server(ServerError),
+    /// Dialing the server failed before a connection was established.
+    Connect {
+        addr: String,
+        source: std::io::Error,
+    },
+    /// I/O failure on an established connection.
     Io(std::io::Error),
     Tls(String),
-    Timeout,
+    /// The proxy set with [`crate::proxy::with_proxy`] answered but would not
+    /// open a tunnel to the server. `reason` classifies the refusal;
+    /// `response` is what the proxy said: the HTTP status line, or the
+    /// SOCKS5 reply and its meaning.
+    ProxyHandshake {
+        proxy: String,
+        reason: ProxyFailure,
+        response: String,
+    },
+    /// The TLS server's certificate key matched none of the pins set with
+    /// [`crate::pinning::with_pinned_cert`].
+    CertPinMismatch,
+    /// The server sent a frame or payload that does not follow the protocol.
+    Protocol(String),
+    /// A frame declared a payload longer than the configured maximum.
+    FrameTooLarge {
+        len: u32,
+        max: u32,
+    },
+    /// A frame failed its negotiated CRC32C check. The connection is dropped
+    /// rather than resynchronized.
+    FrameCorrupted {
+        msg_type: u16,
+        req_id: u64,
+    },
+    /// A value could not be encoded to msgpack (or another wire format).
+    /// `source` holds the encoder's own error when there was one.
+    Encode {
+        message: String,
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+    /// A payload could not be decoded from msgpack (or another wire
+    /// format). `source` holds the decoder's own error when there was one.
+    Decode {
+        message: String,
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+    /// Error frame returned by the server.
+    Server {
+        code: ServerErrorCode,
+        /// Whether the server considers the request safe to retry.
+        retryable: bool,
+        detail: String,
+        details: BTreeMap<String, String>,
+    },
+    /// The server has no context with this id.
+    ///
+    /// Only raised when the server names the missing resource in its error
+    /// details; older servers' 404s stay [`Error::Server`].
+    ContextNotFound {
+        context_id: ContextId,
+    },
+    /// The context is archived (see
+    /// [`Client::archive_context`](crate::Client::archive_context)): its
+    /// history cannot be read or appended to until it is restored.
+    ContextArchived {
+        context_id: ContextId,
+    },
+    /// The server has no turn with this id. As with
+    /// [`Error::ContextNotFound`], older servers' 404s stay [`Error::Server`].
+    TurnNotFound {
+        turn_id: TurnId,
+    },
+    /// `ancestor` is not on the parent chain of `turn_id` (see
+    /// [`Client::get_path`](crate::Client::get_path)).
+    NotAnAncestor {
+        ancestor: TurnId,
+        turn_id: TurnId,
+    },
+    /// [`Client::get_turn_at_depth`](crate::Client::get_turn_at_depth)
+    /// found no turn at `depth`: it is past the head (at `head_depth`), the
+    /// context is empty, or the turn was pruned.
+    DepthOutOfRange {
+        context_id: ContextId,
+        depth: u64,
+        head_depth: u64,
+    },
+    /// The turn was deleted by pruning (see
+    /// [`Client::prune_context`](crate::Client::prune_context)).
+    Pruned {
+        turn_id: TurnId,
+    },
+    /// The turn's payload was removed by redaction (see
+    /// [`Client::redact_turn`](crate::Client::redact_turn)), so there is
+    /// nothing to decode.
+    Redacted {
+        turn_id: TurnId,
+    },
+    /// Turn `turn_id` is stored as a delta (see [`crate::delta`]) against
+    /// `base_turn_id`, which no longer holds the payload the delta was made
+    /// against: it was pruned, redacted or changed.
+    DeltaBaseUnavailable {
+        turn_id: TurnId,
+        base_turn_id: TurnId,
+    },
+    /// The turn's payload is encrypted (see [`crate::encryption`]) and no
+    /// key with this id was available to open it.
+    NoDecryptionKey {
+        key_id: String,
+    },
+    /// An append's `writer_seq` was not above the last sequence its
+    /// `writer_id` appended to the context; nothing was appended.
+    WriterSequenceConflict {
+        writer_id: String,
+        last_seq: u64,
+        writer_seq: u64,
+    },
+    /// The server rejected the session's bearer token (missing, invalid or
+    /// expired). The detail never includes the token.
+    Unauthenticated {
+        detail: String,
+    },
+    /// The request would exceed a server quota (see
+    /// [`Client::get_quotas`](crate::Client::get_quotas)): `quota` names it,
+    /// `limit` is its cap and `current` the usage the server counted.
+    /// Never retried.
+    QuotaExceeded {
+        quota: String,
+        limit: u64,
+        current: u64,
+    },
+    /// A turn's payload has an encoding this build cannot decode: unknown,
+    /// or CBOR without the `cbor` feature. The raw bytes are still in the
+    /// record's `payload`.
+    UnsupportedEncoding(u32),
+    /// The server does not implement the named operation.
+    Unsupported(String),
+    /// The client was dialed read-only (see
+    /// [`with_read_only`](crate::client::with_read_only)) and `operation`
+    /// would change server state. Nothing was sent.
+    ReadOnlyClient {
+        operation: String,
+    },
+    /// A page of turns broke the protocol's invariants, e.g. by repeating a
+    /// turn id, with a client set to reject such pages (see
+    /// [`crate::response_validation`]). Not to be confused with
+    /// [`Error::invalid_response`], the Go-parity name for
+    /// [`Error::Protocol`].
+    InconsistentPage {
+        reason: String,
+    },
+    /// A [`DeleteFilter`](crate::delete::DeleteFilter) named no criterion,
+    /// so it would match every context. Nothing was sent.
+    EmptyFilter,
+    /// [`Client::close`](crate::Client::close) gave up on `dropped` appends
+    /// still queued by [`Client::append_async`](crate::Client::append_async)
+    /// once its request timeout passed; their callbacks got
+    /// [`Error::ClientClosed`]. The client is closed regardless.
+    AppendsDropped {
+        dropped: usize,
+    },
+    /// An [`Interceptor`](crate::interceptor::Interceptor) hook failed the
+    /// call with this message.
+    Interceptor(String),
+    /// The server cannot append to several contexts atomically (see
+    /// [`Client::append_multi`](crate::Client::append_multi)); nothing was
+    /// appended.
+    TransactionUnsupported,
+    /// Entry `entry` (0-based) of an
+    /// [`Client::append_multi`](crate::Client::append_multi) batch failed
+    /// with `source`. Against a transactional server nothing was appended;
+    /// a best-effort fallback keeps the entries before it.
+    TransactionAborted {
+        entry: usize,
+        source: Box<Error>,
+    },
+    /// A [`Subscription`](crate::subscribe::Subscription) with
+    /// [`OverflowPolicy::Error`](crate::subscribe::OverflowPolicy::Error)
+    /// fell `buffer_turns` turns behind and was ended.
+    SubscriptionOverflow {
+        buffer_turns: usize,
+    },
+    /// A payload being imported hashes to `actual`, not the archived
+    /// `expected` (both BLAKE3, hex encoded); `line` is 1-based.
+    HashMismatch {
+        line: usize,
+        expected: String,
+        actual: String,
+    },
+    /// A resumable export's output file does not match its checkpoint (see
+    /// [`Client::export_jsonl_resumable`](crate::Client::export_jsonl_resumable)).
+    CheckpointMismatch {
+        detail: String,
+    },
+    /// The request deadline (or client request timeout) elapsed.
+    DeadlineExceeded,
     Cancelled,
+    /// The server closed the connection.
+    ConnectionClosed,
+    /// The client was closed locally.
+    ClientClosed,
     QueueFull,
+    /// A client-side rate limit (see [`crate::ratelimit`]) is exhausted and
+    /// set not to wait; a token is due after `retry_after`.
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// The serving replica had not applied the requested sequence before the deadline.
+    ReplicaLagging,
+    Fstree(FstreeError),
+    /// The installed [`TurnValidator`](crate::validate::TurnValidator)
+    /// rejected an append's payload; nothing was sent.
+    Validation(ValidationError),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ServerError {
-    pub code: u32,
-    pub detail: String,
+/// Why a proxy refused to open a tunnel (see [`Error::ProxyHandshake`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProxyFailure {
+    /// The proxy wants credentials, or rejected the ones given.
+    AuthRejected,
+    /// The proxy could not reach the server: unresolvable name, no route,
+    /// or the connection was refused or timed out.
+    TargetUnreachable,
+    /// The proxy declined for another reason, e.g. its rules forbid the
+    /// destination.
+    Denied,
+    /// The proxy's answer was not valid SOCKS5 or HTTP.
+    Malformed,
 }
 
-impl fmt::Display for ServerError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "cxdb server error {}: {}", self.code, self.detail)
+/// Server error codes carried in ERROR frames.
+///
+/// Codes this client does not know about are preserved as `Unknown`.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerErrorCode {
+    BadRequest,
+    NotFound,
+    Conflict,
+    Unprocessable,
+    TooEarly,
+    Internal,
+    Unavailable,
+    Unknown(u32),
+}
+
+impl ServerErrorCode {
+    pub fn from_u32(code: u32) -> Self {
+        match code {
+            400 => ServerErrorCode::BadRequest,
+            404 => ServerErrorCode::NotFound,
+            409 => ServerErrorCode::Conflict,
+            422 => ServerErrorCode::Unprocessable,
+            425 => ServerErrorCode::TooEarly,
+            500 => ServerErrorCode::Internal,
+            503 => ServerErrorCode::Unavailable,
+            other => ServerErrorCode::Unknown(other),
+        }
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            ServerErrorCode::BadRequest => 400,
+            ServerErrorCode::NotFound => 404,
+            ServerErrorCode::Conflict => 409,
+            ServerErrorCode::Unprocessable => 422,
+            ServerErrorCode::TooEarly => 425,
+            ServerErrorCode::Internal => 500,
+            ServerErrorCode::Unavailable => 503,
+            ServerErrorCode::Unknown(code) => *code,
+        }
+    }
+
+    /// Retryability assumed when the server does not send an explicit flag.
+    pub fn default_retryable(&self) -> bool {
+        matches!(
+            self,
+            ServerErrorCode::TooEarly | ServerErrorCode::Unavailable
+        )
     }
 }
 
-impl std::error::Error for ServerError {}
+impl fmt::Display for ServerErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_u32())
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::ClientClosed => write!(f, "cxdb: client closed"),
-            Error::ContextNotFound => write!(f, "cxdb: context not found"),
-            Error::TurnNotFound => write!(f, "cxdb: turn not found"),
-            Error::InvalidResponse(msg) => write!(f, "cxdb: invalid response: {msg}"),
-            Error::Server(err) => write!(f, "{err}"),
+            Error::Connect { addr, source } => write!(f, "cxdb: connect {addr}: {source}"),
             Error::Io(err) => write!(f, "cxdb io: {err}"),
             Error::Tls(err) => write!(f, "cxdb tls: {err}"),
-            Error::Timeout => write!(f, "cxdb: deadline exceeded"),
+            Error::ProxyHandshake {
+                proxy, response, ..
+            } => write!(f, "cxdb: proxy {proxy} refused tunnel: {response}"),
+            Error::CertPinMismatch => {
+                write!(f, "cxdb tls: server certificate does not match pinned key")
+            }
+            Error::Protocol(msg) => write!(f, "cxdb: protocol error: {msg}"),
+            Error::FrameTooLarge { len, max } => {
+                write!(f, "cxdb: frame size {len} exceeds maximum {max}")
+            }
+            Error::FrameCorrupted { msg_type, req_id } => write!(
+                f,
+                "cxdb: frame checksum mismatch (msg_type {msg_type}, req_id {req_id})"
+            ),
+            Error::Encode { message, .. } => write!(f, "cxdb: encode error: {message}"),
+            Error::Decode { message, .. } => write!(f, "cxdb: decode error: {message}"),
+            Error::Server { code, detail, .. } => {
+                write!(f, "cxdb server error {code}: {detail}")
+            }
+            Error::ContextNotFound { context_id } => {
+                write!(f, "cxdb: context not found: {context_id}")
+            }
+            Error::ContextArchived { context_id } => {
+                write!(f, "cxdb: context {context_id} is archived")
+            }
+            Error::TurnNotFound { turn_id } => write!(f, "cxdb: turn not found: {turn_id}"),
+            Error::NotAnAncestor { ancestor, turn_id } => {
+                write!(
+                    f,
+                    "cxdb: turn {ancestor} is not an ancestor of turn {turn_id}"
+                )
+            }
+            Error::DepthOutOfRange {
+                context_id,
+                depth,
+                head_depth,
+            } => write!(
+                f,
+                "cxdb: no turn at depth {depth} in context {context_id} (head depth {head_depth})"
+            ),
+            Error::Pruned { turn_id } => write!(f, "cxdb: turn {turn_id} was pruned"),
+            Error::Redacted { turn_id } => write!(f, "cxdb: turn {turn_id} was redacted"),
+            Error::DeltaBaseUnavailable {
+                turn_id,
+                base_turn_id,
+            } => write!(
+                f,
+                "cxdb: turn {turn_id} is a delta against unavailable turn {base_turn_id}"
+            ),
+            Error::NoDecryptionKey { key_id } => {
+                write!(f, "cxdb: no decryption key {key_id:?} for payload")
+            }
+            Error::WriterSequenceConflict {
+                writer_id,
+                last_seq,
+                writer_seq,
+            } => write!(
+                f,
+                "cxdb: writer {writer_id:?} sequence {writer_seq} is not after {last_seq}"
+            ),
+            Error::Unauthenticated { detail } => write!(f, "cxdb: unauthenticated: {detail}"),
+            Error::QuotaExceeded {
+                quota,
+                limit,
+                current,
+            } => write!(
+                f,
+                "cxdb: quota {quota} exceeded (limit {limit}, current {current})"
+            ),
+            Error::UnsupportedEncoding(encoding) => {
+                write!(f, "cxdb: unsupported payload encoding {encoding}")
+            }
+            Error::Unsupported(operation) => {
+                write!(f, "cxdb: server does not support {operation}")
+            }
+            Error::ReadOnlyClient { operation } => {
+                write!(f, "cxdb: {operation} refused by read-only client")
+            }
+            Error::InconsistentPage { reason } => {
+                write!(f, "cxdb: inconsistent turn page: {reason}")
+            }
+            Error::EmptyFilter => {
+                write!(f, "cxdb: delete filter has no criteria")
+            }
+            Error::AppendsDropped { dropped } => {
+                write!(
+                    f,
+                    "cxdb: client closed with {dropped} queued appends undelivered"
+                )
+            }
+            Error::Interceptor(msg) => write!(f, "cxdb: interceptor: {msg}"),
+            Error::TransactionUnsupported => {
+                write!(
+                    f,
+                    "cxdb: server does not support multi-context transactions"
+                )
+            }
+            Error::TransactionAborted { entry, source } => {
+                write!(f, "cxdb: transaction aborted at entry {entry}: {source}")
+            }
+            Error::SubscriptionOverflow { buffer_turns } => write!(
+                f,
+                "cxdb: subscriber fell {buffer_turns} turns behind and was disconnected"
+            ),
+            Error::CheckpointMismatch { detail } => {
+                write!(f, "cxdb: export checkpoint mismatch: {detail}")
+            }
+            Error::HashMismatch {
+                line,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "cxdb: line {line}: payload hash {actual} does not match {expected}"
+            ),
+            Error::DeadlineExceeded => write!(f, "cxdb: deadline exceeded"),
             Error::Cancelled => write!(f, "cxdb: request cancelled"),
+            Error::ConnectionClosed => write!(f, "cxdb: connection closed by server"),
+            Error::ClientClosed => write!(f, "cxdb: client closed"),
             Error::QueueFull => write!(f, "cxdb: request queue full"),
+            Error::RateLimited { retry_after } => {
+                write!(
+                    f,
+                    "cxdb: client rate limit reached; retry in {retry_after:?}"
+                )
+            }
+            Error::ReplicaLagging => write!(f, "cxdb: replica lagging behind requested sequence"),
+            Error::Fstree(err) => write!(f, "cxdb: {err}"),
+            Error::Validation(err) => write!(f, "cxdb: {err}"),
         }
     }
 }
@@ -52,8 +447,19 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
+            Error::Connect { source, .. } => Some(source),
             Error::Io(err) => Some(err),
-            Error::Server(err) => Some(err),
+            Error::Encode {
+                source: Some(source),
+                ..
+            }
+            | Error::Decode {
+                source: Some(source),
+                ..
+            } => Some(source.as_ref()),
+            Error::Fstree(err) => Some(err),
+            Error::Validation(err) => Some(err),
+            Error::TransactionAborted { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
@@ -65,31 +471,473 @@ impl From<std::io::Error> for Error {
     }
 }
 
+/// Lets `?` lift msgpack encoding in user code into [`Error::Encode`], as
+/// the crate's own encoders report it. The rmp_serde error is the
+/// [`source`](std::error::Error::source).
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Error::encode_err(err)
+    }
+}
+
+/// Lets `?` lift msgpack decoding in user code into [`Error::Decode`], as
+/// the crate's own decoders report it. The rmp_serde error is the
+/// [`source`](std::error::Error::source).
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        Error::decode_err(err)
+    }
+}
+
+impl From<FstreeError> for Error {
+    fn from(err: FstreeError) -> Self {
+        Error::Fstree(err)
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        Error::Validation(err)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[allow(non_upper_case_globals)]
 pub const ErrClientClosed: Error = Error::ClientClosed;
 #[allow(non_upper_case_globals)]
-pub const ErrContextNotFound: Error = Error::ContextNotFound;
+pub const ErrContextNotFound: Error = Error::ContextNotFound {
+    context_id: ContextId::new(0),
+};
 #[allow(non_upper_case_globals)]
-pub const ErrTurnNotFound: Error = Error::TurnNotFound;
+pub const ErrTurnNotFound: Error = Error::TurnNotFound {
+    turn_id: TurnId::new(0),
+};
 #[allow(non_upper_case_globals)]
-pub const ErrInvalidResponse: Error = Error::InvalidResponse(String::new());
+pub const ErrInvalidResponse: Error = Error::Protocol(String::new());
 
 /// Checks whether an error is a server error with the specified code.
 pub fn is_server_error(err: &Error, code: u32) -> bool {
-    matches!(err, Error::Server(ServerError { code: c, .. }) if *c == code)
+    matches!(err, Error::Server { code: c, .. } if c.as_u32() == code)
 }
 
 impl Error {
+    pub fn protocol(msg: impl Into<String>) -> Self {
+        Error::Protocol(msg.into())
+    }
+
+    /// An [`Error::Encode`] with no underlying cause.
+    pub fn encode(message: impl Into<String>) -> Self {
+        Error::Encode {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// An [`Error::Decode`] with no underlying cause.
+    pub fn decode(message: impl Into<String>) -> Self {
+        Error::Decode {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// An [`Error::Encode`] whose message is `err`'s and whose source is `err`.
+    pub(crate) fn encode_err<E>(err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::encode_with(err.to_string(), err)
+    }
+
+    /// An [`Error::Decode`] whose message is `err`'s and whose source is `err`.
+    pub(crate) fn decode_err<E>(err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::decode_with(err.to_string(), err)
+    }
+
+    pub(crate) fn encode_with<E>(message: impl Into<String>, source: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::Encode {
+            message: message.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    pub(crate) fn decode_with<E>(message: impl Into<String>, source: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::Decode {
+            message: message.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// Go-parity name for [`Error::protocol`].
     pub fn invalid_response(msg: impl Into<String>) -> Self {
-        Error::InvalidResponse(msg.into())
+        Error::protocol(msg)
     }
 
     pub fn server(code: u32, detail: impl Into<String>) -> Self {
-        Error::Server(ServerError {
+        let code = ServerErrorCode::from_u32(code);
+        Error::Server {
             code,
+            retryable: code.default_retryable(),
             detail: detail.into(),
-        })
+            details: BTreeMap::new(),
+        }
+    }
+
+    /// Maps a 404 server error onto the typed not-found variant for the
+    /// resource the server named.
+    ///
+    /// The server names a missing context or turn in the `resource` detail
+    /// entry along with its `context_id` or `turn_id`. Errors without them
+    /// (older servers, missing blobs) stay [`Error::Server`].
+    pub(crate) fn resolve_not_found(self) -> Self {
+        let details = match &self {
+            Error::Server {
+                code: ServerErrorCode::NotFound,
+                details,
+                ..
+            } => details,
+            _ => return self,
+        };
+        let id = |key: &str| details.get(key).and_then(|v| v.parse::<u64>().ok());
+        match (
+            details.get("resource").map(String::as_str),
+            id("context_id"),
+            id("turn_id"),
+        ) {
+            (Some("context"), Some(context_id), _) => Error::ContextNotFound {
+                context_id: ContextId::new(context_id),
+            },
+            (Some("turn"), _, Some(turn_id)) => Error::TurnNotFound {
+                turn_id: TurnId::new(turn_id),
+            },
+            _ => self,
+        }
+    }
+
+    /// Maps a 409 server error that names a writer onto
+    /// [`Error::WriterSequenceConflict`].
+    pub(crate) fn resolve_writer_conflict(self) -> Self {
+        let details = match &self {
+            Error::Server {
+                code: ServerErrorCode::Conflict,
+                details,
+                ..
+            } => details,
+            _ => return self,
+        };
+        let Some(writer_id) = details.get("writer_id") else {
+            return self;
+        };
+        let seq = |key: &str| details.get(key).and_then(|v| v.parse().ok()).unwrap_or(0);
+        Error::WriterSequenceConflict {
+            writer_id: writer_id.clone(),
+            last_seq: seq("last_seq"),
+            writer_seq: seq("writer_seq"),
+        }
+    }
+
+    /// Reports whether retrying the failed request may succeed.
+    ///
+    /// Server errors follow the server's retryable flag; other variants are
+    /// classified by [`crate::reconnect::is_connection_error`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Server { retryable, .. } => *retryable,
+            other => crate::reconnect::is_connection_error(other),
+        }
+    }
+}
+
+pub(crate) fn parse_server_error(payload: &[u8]) -> Error {
+    if payload.len() < 8 {
+        return Error::server(0, "unknown error");
+    }
+    let code = u32::from_le_bytes(payload[0..4].try_into().unwrap_or_default());
+    if code == ERROR_REPLICA_LAGGING {
+        return Error::ReplicaLagging;
+    }
+    let detail_len = u32::from_le_bytes(payload[4..8].try_into().unwrap_or_default()) as usize;
+    let detail = if payload.len() >= 8 + detail_len {
+        String::from_utf8_lossy(&payload[8..8 + detail_len]).to_string()
+    } else {
+        String::new()
+    };
+    if code == ERROR_UNAUTHENTICATED {
+        return Error::Unauthenticated { detail };
+    }
+    match payload
+        .get(8 + detail_len..)
+        .and_then(parse_server_error_ext)
+    {
+        Some((_, details)) if code == ERROR_PRUNED && details.contains_key("turn_id") => {
+            Error::Pruned {
+                turn_id: details["turn_id"].parse().unwrap_or_default(),
+            }
+        }
+        Some((_, details))
+            if code == ERROR_CONTEXT_ARCHIVED && details.contains_key("context_id") =>
+        {
+            Error::ContextArchived {
+                context_id: details["context_id"].parse().unwrap_or_default(),
+            }
+        }
+        Some((_, details)) if code == ERROR_QUOTA_EXCEEDED && details.contains_key("quota") => {
+            let number = |key: &str| details.get(key).and_then(|v| v.parse().ok()).unwrap_or(0);
+            Error::QuotaExceeded {
+                limit: number("limit"),
+                current: number("current"),
+                quota: details["quota"].clone(),
+            }
+        }
+        Some((flags, details)) => Error::Server {
+            code: ServerErrorCode::from_u32(code),
+            retryable: flags & ERROR_FLAG_RETRYABLE != 0,
+            detail,
+            details,
+        },
+        None => Error::server(code, detail),
+    }
+}
+
+/// Parses the optional trailer newer servers append to ERROR frames:
+/// `flags: u32` followed by `count: u32` length-prefixed key/value pairs.
+fn parse_server_error_ext(mut rest: &[u8]) -> Option<(u32, BTreeMap<String, String>)> {
+    fn take_u32(rest: &mut &[u8]) -> Option<u32> {
+        let (head, tail) = rest.split_at_checked(4)?;
+        *rest = tail;
+        Some(u32::from_le_bytes(head.try_into().ok()?))
+    }
+    fn take_str(rest: &mut &[u8]) -> Option<String> {
+        let len = take_u32(rest)? as usize;
+        let (head, tail) = rest.split_at_checked(len)?;
+        *rest = tail;
+        Some(String::from_utf8_lossy(head).into_owned())
+    }
+
+    let flags = take_u32(&mut rest)?;
+    let count = take_u32(&mut rest)?;
+    let mut details = BTreeMap::new();
+    for _ in 0..count {
+        let key = take_str(&mut rest)?;
+        let value = take_str(&mut rest)?;
+        details.insert(key, value);
+    }
+    Some((flags, details))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::error::Error as _;
+
+    #[test]
+    fn connect_error_exposes_io_source() {
+        let err = Error::Connect {
+            addr: "127.0.0.1:1".into(),
+            source: std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused"),
+        };
+        let source = err.source().expect("source");
+        let io = source.downcast_ref::<std::io::Error>().expect("io source");
+        assert_eq!(io.kind(), std::io::ErrorKind::ConnectionRefused);
+        assert!(err.to_string().contains("127.0.0.1:1"));
+    }
+
+    #[test]
+    fn question_mark_keeps_msgpack_and_io_error_sources() {
+        fn decode(bytes: &[u8]) -> Result<String> {
+            Ok(rmp_serde::from_slice(bytes)?)
+        }
+        fn read(path: &str) -> Result<Vec<u8>> {
+            Ok(std::fs::read(path)?)
+        }
+
+        let err = decode(&[0xc1]).unwrap_err();
+        assert!(matches!(err, Error::Decode { .. }), "got {err:?}");
+        let source = err.source().expect("rmp_serde error kept as source");
+        assert!(source.downcast_ref::<rmp_serde::decode::Error>().is_some());
+
+        struct Unencodable;
+        impl serde::Serialize for Unencodable {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                _: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("unencodable"))
+            }
+        }
+        fn encode() -> Result<Vec<u8>> {
+            Ok(rmp_serde::to_vec(&Unencodable)?)
+        }
+        let err = encode().unwrap_err();
+        assert!(matches!(err, Error::Encode { .. }), "got {err:?}");
+        let source = err.source().expect("rmp_serde error kept as source");
+        assert!(source.downcast_ref::<rmp_serde::encode::Error>().is_some());
+        let err = read("/nonexistent/cxdb").unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+    }
+
+    #[test]
+    fn decode_errors_chain_the_decoder_error() {
+        let err = crate::encoding::decode_msgpack(&[0x81]).unwrap_err();
+        assert!(matches!(err, Error::Decode { .. }), "got {err:?}");
+        let source = err.source().expect("decoder error kept as source");
+        assert!(source.downcast_ref::<rmpv::decode::Error>().is_some());
+
+        let err = Error::decode("no cause");
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn server_error_is_matchable() {
+        let err = Error::server(422, "bad type");
+        assert!(is_server_error(&err, 422));
+        assert!(!is_server_error(&err, 404));
+        assert!(matches!(
+            err,
+            Error::Server {
+                code: ServerErrorCode::Unprocessable,
+                retryable: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn not_found_details_map_to_typed_variants() {
+        let not_found = |entries: &[(&str, &str)]| Error::Server {
+            code: ServerErrorCode::NotFound,
+            retryable: false,
+            detail: "context 7 not found".into(),
+            details: entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+
+        let err = not_found(&[("resource", "context"), ("context_id", "7")]).resolve_not_found();
+        assert!(matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 7));
+        let err = not_found(&[("resource", "turn"), ("turn_id", "99")]).resolve_not_found();
+        assert!(matches!(err, Error::TurnNotFound { turn_id } if turn_id.get() == 99));
+
+        // Without the details nothing is guessed from the detail text.
+        for err in [
+            Error::server(404, "context"),
+            Error::server(404, "parent turn"),
+            not_found(&[("resource", "context")]),
+            not_found(&[("resource", "blob"), ("turn_id", "1")]),
+        ] {
+            assert!(is_server_error(&err.resolve_not_found(), 404));
+        }
+    }
+
+    #[test]
+    fn writer_conflicts_map_to_typed_variant() {
+        let mut details = BTreeMap::new();
+        details.insert("writer_id".to_string(), "agent-a".to_string());
+        details.insert("last_seq".to_string(), "5".to_string());
+        details.insert("writer_seq".to_string(), "4".to_string());
+        let err = Error::Server {
+            code: ServerErrorCode::Conflict,
+            retryable: false,
+            detail: "writer sequence".into(),
+            details,
+        }
+        .resolve_writer_conflict();
+        match err {
+            Error::WriterSequenceConflict {
+                writer_id,
+                last_seq,
+                writer_seq,
+            } => {
+                assert_eq!(writer_id, "agent-a");
+                assert_eq!((last_seq, writer_seq), (5, 4));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        // Other conflicts stay server errors.
+        let err = Error::server(409, "hash mismatch").resolve_writer_conflict();
+        assert!(is_server_error(&err, 409));
+    }
+
+    #[test]
+    fn unknown_server_codes_round_trip() {
+        let code = ServerErrorCode::from_u32(599);
+        assert_eq!(code, ServerErrorCode::Unknown(599));
+        assert_eq!(code.as_u32(), 599);
+        assert!(ServerErrorCode::from_u32(503).default_retryable());
+        assert!(!ServerErrorCode::from_u32(404).default_retryable());
+    }
+
+    #[test]
+    fn replica_lagging_code_maps_to_typed_error() {
+        let mut payload = Vec::new();
+        payload
+            .write_u32::<LittleEndian>(ERROR_REPLICA_LAGGING)
+            .unwrap();
+        payload.write_u32::<LittleEndian>(0).unwrap();
+        assert!(matches!(
+            parse_server_error(&payload),
+            Error::ReplicaLagging
+        ));
+    }
+
+    #[test]
+    fn server_error_trailer_carries_retryable_and_details() {
+        let mut payload = Vec::new();
+        payload.write_u32::<LittleEndian>(500).unwrap();
+        payload.write_u32::<LittleEndian>(4).unwrap();
+        payload.extend_from_slice(b"busy");
+        payload
+            .write_u32::<LittleEndian>(ERROR_FLAG_RETRYABLE)
+            .unwrap();
+        payload.write_u32::<LittleEndian>(1).unwrap();
+        for part in ["reason", "storage_busy"] {
+            payload
+                .write_u32::<LittleEndian>(part.len() as u32)
+                .unwrap();
+            payload.extend_from_slice(part.as_bytes());
+        }
+
+        match parse_server_error(&payload) {
+            Error::Server {
+                code,
+                retryable,
+                detail,
+                details,
+            } => {
+                assert_eq!(code, ServerErrorCode::Internal);
+                assert!(retryable);
+                assert_eq!(detail, "busy");
+                assert_eq!(
+                    details.get("reason").map(String::as_str),
+                    Some("storage_busy")
+                );
+            }
+            other => panic!("expected server error, got {other:?}"),
+        }
+
+        // Legacy frames without the trailer fall back to the code's default.
+        let legacy = &payload[..12];
+        assert!(matches!(
+            parse_server_error(legacy),
+            Error::Server {
+                retryable: false,
+                ..
+            }
+        ));
     }
 }
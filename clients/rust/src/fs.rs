@@ -1,23 +1,24 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::io::Read;
+use byteorder::{LittleEndian, WriteBytesExt};
 
+#[cfg(not(target_arch = "wasm32"))]
 use crate::client::{Client, RequestContext};
 use crate::error::{Error, Result};
-use crate::protocol::{ENCODING_MSGPACK, MSG_APPEND_TURN, MSG_ATTACH_FS, MSG_PUT_BLOB};
-use crate::turn::{AppendRequest, AppendResult};
+use crate::ids::TurnId;
+use crate::protocol::{PayloadReader, MSG_APPEND_TURN, MSG_ATTACH_FS, MSG_GET_BLOB, MSG_PUT_BLOB};
+use crate::turn::{encode_append_request, parse_append_result, AppendRequest, AppendResult};
 
 #[derive(Debug, Clone)]
 pub struct AttachFsRequest {
-    pub turn_id: u64,
+    pub turn_id: TurnId,
     pub fs_root_hash: [u8; 32],
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AttachFsResult {
-    pub turn_id: u64,
+    pub turn_id: TurnId,
     pub fs_root_hash: [u8; 32],
 }
 
@@ -32,28 +33,27 @@ pub struct PutBlobResult {
     pub was_new: bool,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl Client {
     pub fn attach_fs(&self, ctx: &RequestContext, req: &AttachFsRequest) -> Result<AttachFsResult> {
         let mut payload = Vec::with_capacity(40);
-        payload.write_u64::<LittleEndian>(req.turn_id)?;
+        payload.write_u64::<LittleEndian>(req.turn_id.get())?;
         payload.extend_from_slice(&req.fs_root_hash);
 
-        let frame = self.send_request(ctx, MSG_ATTACH_FS, &payload)?;
+        let frame = self
+            .send_request(ctx, MSG_ATTACH_FS, &payload)
+            .map_err(|err| err.resolve_not_found())?;
         if frame.payload.len() < 40 {
-            return Err(Error::invalid_response(format!(
+            return Err(Error::protocol(format!(
                 "attach fs response too short ({} bytes)",
                 frame.payload.len()
             )));
         }
 
-        let mut cursor = std::io::Cursor::new(frame.payload);
-        let turn_id = cursor.read_u64::<LittleEndian>()?;
-        let mut hash = [0u8; 32];
-        cursor.read_exact(&mut hash)?;
-
+        let mut reader = PayloadReader::new(&frame.payload, "attach fs response");
         Ok(AttachFsResult {
-            turn_id,
-            fs_root_hash: hash,
+            turn_id: reader.u64("turn_id")?.into(),
+            fs_root_hash: reader.array("fs_root_hash")?,
         })
     }
 
@@ -66,7 +66,7 @@ impl Client {
 
         let frame = self.send_request(ctx, MSG_PUT_BLOB, &payload)?;
         if frame.payload.len() < 33 {
-            return Err(Error::invalid_response(format!(
+            return Err(Error::protocol(format!(
                 "put blob response too short ({} bytes)",
                 frame.payload.len()
             )));
@@ -89,67 +89,40 @@ impl Client {
         Ok((result.hash, result.was_new))
     }
 
+    /// Fetches a blob by its BLAKE3 content hash, uncompressed. Turn payloads
+    /// are stored as blobs, so this also retrieves a payload left out by
+    /// [`GetLastOptions::max_payload_bytes`](crate::GetLastOptions::max_payload_bytes)
+    /// via its `payload_hash`.
+    pub fn get_blob(&self, ctx: &RequestContext, hash: &[u8; 32]) -> Result<Vec<u8>> {
+        let frame = self.send_request(ctx, MSG_GET_BLOB, hash)?;
+        let mut reader = PayloadReader::new(&frame.payload, "get blob response");
+        Ok(reader.len_prefixed("data")?.to_vec())
+    }
+
     pub fn append_turn_with_fs(
         &self,
         ctx: &RequestContext,
         req: &AppendRequest,
         fs_root_hash: Option<[u8; 32]>,
     ) -> Result<AppendResult> {
-        let encoding = if req.encoding == 0 {
-            ENCODING_MSGPACK
-        } else {
-            req.encoding
-        };
-
-        let hash = blake3::hash(&req.payload);
-        let mut payload = Vec::with_capacity(128 + req.payload.len());
-        payload.write_u64::<LittleEndian>(req.context_id)?;
-        payload.write_u64::<LittleEndian>(req.parent_turn_id)?;
-        payload.write_u32::<LittleEndian>(req.type_id.len() as u32)?;
-        payload.extend_from_slice(req.type_id.as_bytes());
-        payload.write_u32::<LittleEndian>(req.type_version)?;
-        payload.write_u32::<LittleEndian>(encoding)?;
-        payload.write_u32::<LittleEndian>(req.compression)?;
-        payload.write_u32::<LittleEndian>(req.payload.len() as u32)?;
-        payload.extend_from_slice(hash.as_bytes());
-        payload.write_u32::<LittleEndian>(req.payload.len() as u32)?;
-        payload.extend_from_slice(&req.payload);
-        payload.write_u32::<LittleEndian>(req.idempotency_key.len() as u32)?;
-        if !req.idempotency_key.is_empty() {
-            payload.extend_from_slice(&req.idempotency_key);
-        }
-
-        let mut flags = 0u16;
-        if let Some(hash) = fs_root_hash {
-            flags |= 1;
-            payload.extend_from_slice(&hash);
-        }
-
-        let frame = self.send_request_with_flags(ctx, MSG_APPEND_TURN, flags, &payload)?;
-        if frame.payload.len() < 52 {
-            return Err(Error::invalid_response(format!(
-                "append response too short ({} bytes)",
-                frame.payload.len()
-            )));
-        }
-        let mut cursor = std::io::Cursor::new(frame.payload);
-        let context_id = cursor.read_u64::<LittleEndian>()?;
-        let turn_id = cursor.read_u64::<LittleEndian>()?;
-        let depth = cursor.read_u32::<LittleEndian>()?;
-        let mut hash = [0u8; 32];
-        cursor.read_exact(&mut hash)?;
-        Ok(AppendResult {
-            context_id,
-            turn_id,
-            depth,
-            payload_hash: hash,
-        })
+        self.validate_append(req)?;
+        let req = &*self.delta_append(ctx, req)?;
+        let req = &*self.seal_append(req)?;
+        let mut payload = Vec::with_capacity(160 + req.payload.len());
+        let flags = encode_append_request(&mut payload, req, fs_root_hash)?;
+
+        let response = self.send_request_with_flags(ctx, MSG_APPEND_TURN, flags, &payload);
+        self.prefetch_cache().invalidate(req.context_id);
+        let frame = response.map_err(|err| err.resolve_not_found().resolve_writer_conflict())?;
+        parse_append_result(&frame.payload)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::ContextId;
+    use crate::protocol::ENCODING_MSGPACK;
     use crate::test_util::{decode_hex, load_fixture};
 
     fn build_append_payload(req: &AppendRequest, fs_root_hash: Option<[u8; 32]>) -> Vec<u8> {
@@ -160,9 +133,11 @@ mod tests {
         };
         let hash = blake3::hash(&req.payload);
         let mut payload = Vec::new();
-        payload.write_u64::<LittleEndian>(req.context_id).unwrap();
         payload
-            .write_u64::<LittleEndian>(req.parent_turn_id)
+            .write_u64::<LittleEndian>(req.context_id.get())
+            .unwrap();
+        payload
+            .write_u64::<LittleEndian>(req.parent_turn_id.get())
             .unwrap();
         payload
             .write_u32::<LittleEndian>(req.type_id.len() as u32)
@@ -218,16 +193,46 @@ mod tests {
         assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
         assert_eq!(fixture.flags, 1);
         let req = AppendRequest {
-            context_id: 1,
-            parent_turn_id: 0,
+            context_id: ContextId::new(1),
+            parent_turn_id: TurnId::new(0),
             type_id: "cxdb.ConversationItem".into(),
             type_version: 3,
             payload: vec![0x91, 0x04],
             idempotency_key: Vec::new(),
             encoding: ENCODING_MSGPACK,
             compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
         };
         let payload = build_append_payload(&req, Some([0xBB; 32]));
         assert_eq!(decode_hex(&fixture.payload_hex), payload);
     }
+
+    #[test]
+    fn get_blob_requests_by_hash() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{error_payload, spawn_scripted_server};
+
+        let data = b"large payload".to_vec();
+        let mut resp = (data.len() as u32).to_le_bytes().to_vec();
+        resp.extend_from_slice(&data);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_BLOB, resp),
+            (MSG_ERROR, error_payload(404, "blob not found")),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let hash = *blake3::hash(&data).as_bytes();
+
+        assert_eq!(client.get_blob(&ctx, &hash).unwrap(), data);
+        let err = client.get_blob(&ctx, &[0u8; 32]).unwrap_err();
+        assert!(crate::error::is_server_error(&err, 404));
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.msg_type, MSG_GET_BLOB);
+        assert_eq!(requests[0].payload, hash);
+    }
 }
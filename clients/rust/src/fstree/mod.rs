@@ -6,6 +6,7 @@ mod options;
 mod snapshot;
 mod tracker;
 mod types;
+#[cfg(not(target_arch = "wasm32"))]
 mod upload;
 
 pub use capture::{
@@ -21,6 +22,7 @@ pub use types::{
     EntryKind, EntryKindDirectory, EntryKindFile, EntryKindSymlink, FileRef, Snapshot,
     SnapshotDiff, SnapshotStats, TreeEntry, TreeObject,
 };
+#[cfg(not(target_arch = "wasm32"))]
 pub use upload::{capture_and_upload, upload_and_attach, UploadResult};
 
 /// Go-parity alias for snapshot option type.
@@ -7,6 +7,7 @@ use crate::Client;
 
 use super::capture::{FstreeError, FstreeErrorKind, Result as FstreeResult};
 use super::types::Snapshot;
+use crate::ids::TurnId;
 
 #[derive(Debug, Clone, Default)]
 pub struct UploadResult {
@@ -78,7 +79,7 @@ pub fn upload_and_attach(
     ctx: &RequestContext,
     client: &Client,
     root: impl AsRef<std::path::Path>,
-    turn_id: u64,
+    turn_id: TurnId,
     opts: impl IntoIterator<Item = super::options::SnapshotOption>,
 ) -> FstreeResult<UploadResult> {
     let snapshot = super::capture::capture(root, opts)?;
@@ -0,0 +1,77 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hex forms of content hashes.
+//!
+//! Payload hashes, fstree roots and blob hashes are raw BLAKE3 bytes on the
+//! wire and lowercase hex everywhere people see them: logs, the HTTP API's
+//! `/v1/blobs/:hash` and the UI.
+
+use crate::error::{Error, Result};
+
+/// `hash` as lowercase hex, e.g. for a log line or a blob URL.
+pub fn hash_to_hex(hash: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(hash.len() * 2);
+    for &byte in hash {
+        out.push(DIGITS[usize::from(byte >> 4)] as char);
+        out.push(DIGITS[usize::from(byte & 0xf)] as char);
+    }
+    out
+}
+
+/// Parses the hex form of a hash, in either case. Fails with
+/// [`Error::Decode`] on an odd length or a non-hex digit.
+pub fn hash_from_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex.as_bytes();
+    if !digits.len().is_multiple_of(2) {
+        return Err(Error::decode(format!(
+            "hex hash has odd length {}",
+            digits.len()
+        )));
+    }
+    let nibble = |i: usize| {
+        (digits[i] as char)
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or_else(|| Error::decode(format!("invalid hex digit at offset {i} in hash")))
+    };
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| Ok(nibble(i)? << 4 | nibble(i + 1)?))
+        .collect()
+}
+
+/// Parses the hex form of a 32-byte BLAKE3 hash.
+pub(crate) fn hash32_from_hex(hex: &str) -> Result<[u8; 32]> {
+    let bytes = hash_from_hex(hex)?;
+    bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::decode(format!("hash is {} bytes, expected 32", bytes.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips_and_rejects_bad_input() {
+        let hash = *blake3::hash(b"cxdb").as_bytes();
+        let hex = hash_to_hex(&hash);
+        assert_eq!(hex, blake3::hash(b"cxdb").to_hex().as_str());
+        assert_eq!(hash_from_hex(&hex).unwrap(), hash);
+        assert_eq!(hash_from_hex(&hex.to_uppercase()).unwrap(), hash);
+        assert_eq!(hash32_from_hex(&hex).unwrap(), hash);
+        assert_eq!(hash_to_hex(&[]), "");
+        assert_eq!(hash_from_hex("").unwrap(), Vec::<u8>::new());
+
+        for bad in ["abc", "zz", "0g", "é0"] {
+            assert!(
+                matches!(hash_from_hex(bad), Err(Error::Decode { .. })),
+                "{bad:?} parsed"
+            );
+        }
+        assert!(matches!(hash32_from_hex("00ff"), Err(Error::Decode { .. })));
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Turn counts by declared type.
+//!
+//! [`Client::type_histogram`] asks the server how many turns of each
+//! `type_id` a context's history holds, e.g. tool calls against messages for
+//! a dashboard, without reading any payloads. The server walks the history
+//! as [`Client::get_last`](crate::Client::get_last) would and answers with
+//! one small map, so it is cheap enough to call on every page load.
+
+use std::collections::HashMap;
+
+#[cfg(not(target_arch = "wasm32"))]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::{
+    GET_LAST_BEFORE, GET_LAST_DEPTH_RANGE, GET_LAST_INCLUDE_COMPACTED, GET_LAST_INCLUDE_EXPIRED,
+    MSG_TYPE_HISTOGRAM,
+};
+
+/// Which turns [`Client::type_histogram_scoped`] counts. The fields mean
+/// what they do on [`GetLastOptions`](crate::GetLastOptions); the default
+/// counts the whole visible history.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeHistogramOptions {
+    /// Count turns hidden by compaction too.
+    pub include_compacted: bool,
+    /// Count expired turns too.
+    pub include_expired: bool,
+    /// Count only turns older than this one.
+    pub before_turn_id: Option<TurnId>,
+    /// Count only turns at this depth or deeper.
+    pub min_depth: Option<u32>,
+    /// Count only turns at this depth or shallower.
+    pub max_depth: Option<u32>,
+}
+
+impl TypeHistogramOptions {
+    pub fn include_compacted(mut self, include: bool) -> Self {
+        self.include_compacted = include;
+        self
+    }
+
+    pub fn include_expired(mut self, include: bool) -> Self {
+        self.include_expired = include;
+        self
+    }
+
+    pub fn before(mut self, turn_id: TurnId) -> Self {
+        self.before_turn_id = Some(turn_id);
+        self
+    }
+
+    pub fn min_depth(mut self, depth: u32) -> Self {
+        self.min_depth = Some(depth);
+        self
+    }
+
+    pub fn max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Counts the turns of each declared type along the history of
+    /// `context_id` (see the [module docs](self)). Types with no turns are
+    /// absent from the map.
+    ///
+    /// Servers without the TYPE_HISTOGRAM message fail with
+    /// [`Error::Unsupported`].
+    pub fn type_histogram(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<HashMap<String, u64>> {
+        self.type_histogram_scoped(ctx, context_id, &TypeHistogramOptions::default())
+    }
+
+    /// Like [`Client::type_histogram`], counting only the turns `opts`
+    /// selects.
+    pub fn type_histogram_scoped(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: &TypeHistogramOptions,
+    ) -> Result<HashMap<String, u64>> {
+        let mut flags = 0;
+        if opts.include_compacted {
+            flags |= GET_LAST_INCLUDE_COMPACTED;
+        }
+        if opts.include_expired {
+            flags |= GET_LAST_INCLUDE_EXPIRED;
+        }
+        if opts.before_turn_id.is_some() {
+            flags |= GET_LAST_BEFORE;
+        }
+        let depths = opts.min_depth.is_some() || opts.max_depth.is_some();
+        if depths {
+            flags |= GET_LAST_DEPTH_RANGE;
+        }
+        let mut payload = Vec::with_capacity(28);
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        payload.write_u32::<LittleEndian>(flags)?;
+        if let Some(before_turn_id) = opts.before_turn_id {
+            payload.write_u64::<LittleEndian>(before_turn_id.get())?;
+        }
+        if depths {
+            payload.write_u32::<LittleEndian>(opts.min_depth.unwrap_or(0))?;
+            payload.write_u32::<LittleEndian>(opts.max_depth.unwrap_or(u32::MAX))?;
+        }
+        let frame = self
+            .send_request(ctx, MSG_TYPE_HISTOGRAM, &payload)
+            .map_err(|err| {
+                err.resolve_unsupported("TYPE_HISTOGRAM")
+                    .resolve_not_found()
+            })?;
+        parse_type_histogram(&frame.payload)
+    }
+}
+
+pub(crate) fn parse_type_histogram(payload: &[u8]) -> Result<HashMap<String, u64>> {
+    let mut reader = PayloadReader::new(payload, "type histogram response");
+    let count = reader.u32("count")?;
+    let mut counts = HashMap::new();
+    for _ in 0..count {
+        let type_id = std::str::from_utf8(reader.len_prefixed("type_id")?)
+            .map_err(|_| Error::protocol("histogram type_id not utf8"))?;
+        counts.insert(type_id.to_string(), reader.u64("turns")?);
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_ERROR;
+    use crate::test_util::{error_payload, not_found_payload, spawn_scripted_server};
+
+    fn histogram_payload(counts: &[(&str, u64)]) -> Vec<u8> {
+        let mut out = (counts.len() as u32).to_le_bytes().to_vec();
+        for (type_id, count) in counts {
+            out.extend_from_slice(&(type_id.len() as u32).to_le_bytes());
+            out.extend_from_slice(type_id.as_bytes());
+            out.extend_from_slice(&count.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn histogram_sends_the_scope_and_reads_counts() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (
+                MSG_TYPE_HISTOGRAM,
+                histogram_payload(&[("msg", 4), ("tool", 7)]),
+            ),
+            (MSG_TYPE_HISTOGRAM, histogram_payload(&[])),
+            (MSG_ERROR, not_found_payload("context", 5)),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let counts = client.type_histogram(&ctx, ContextId::new(3)).unwrap();
+        assert_eq!(
+            counts,
+            HashMap::from([("msg".to_string(), 4), ("tool".to_string(), 7)])
+        );
+        let opts = TypeHistogramOptions::default()
+            .include_expired(true)
+            .before(TurnId::new(40))
+            .max_depth(9);
+        assert!(client
+            .type_histogram_scoped(&ctx, ContextId::new(3), &opts)
+            .unwrap()
+            .is_empty());
+        let err = client.type_histogram(&ctx, ContextId::new(5)).unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 5),
+            "{err:?}"
+        );
+        let err = client.type_histogram(&ctx, ContextId::new(3)).unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.msg_type, MSG_TYPE_HISTOGRAM);
+        assert_eq!(requests[0].payload, {
+            let mut expected = 3u64.to_le_bytes().to_vec();
+            expected.extend_from_slice(&0u32.to_le_bytes());
+            expected
+        });
+        assert_eq!(requests[1].payload, {
+            let mut expected = 3u64.to_le_bytes().to_vec();
+            let flags = GET_LAST_INCLUDE_EXPIRED | GET_LAST_BEFORE | GET_LAST_DEPTH_RANGE;
+            expected.extend_from_slice(&flags.to_le_bytes());
+            expected.extend_from_slice(&40u64.to_le_bytes());
+            expected.extend_from_slice(&0u32.to_le_bytes());
+            expected.extend_from_slice(&9u32.to_le_bytes());
+            expected
+        });
+    }
+}
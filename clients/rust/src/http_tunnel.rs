@@ -0,0 +1,317 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! The HTTP transport, enabled by the `http-transport` feature.
+//!
+//! [`dial`](crate::dial) with an `http://` or `https://` URL reaches the
+//! server through its HTTP API, typically behind the gateway, instead of the
+//! binary port. Frames are the same as over TCP: each batch of requests is
+//! POSTed to `/v1/rpc` and the response body carries one response frame per
+//! request. The server relays every POST over a fresh binary connection, so
+//! the HELLO frame sent at dial is replayed ahead of each batch and its
+//! response dropped.
+//!
+//! HTTP failures map to the same errors as over TCP: 401 and 403 to
+//! [`Error::Unauthenticated`], 404 (no `/v1/rpc` route) to
+//! [`Error::Unsupported`], other statuses to [`Error::Server`] with the
+//! status as its code, and connection failures to [`Error::Io`].
+
+use std::io::Read;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::credentials::CredentialProvider;
+use crate::error::{Error, Result};
+use crate::protocol::{FRAME_HEADER_LEN, MSG_HELLO};
+
+/// One server reached over HTTP. Responses are buffered whole and read back
+/// through [`Read`].
+pub(crate) struct HttpTunnel {
+    agent: ureq::Agent,
+    /// The `/v1/rpc` endpoint.
+    url: String,
+    peer_addr: SocketAddr,
+    token: Option<String>,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+    /// The HELLO frame, replayed ahead of every later batch.
+    hello: Vec<u8>,
+    response: Vec<u8>,
+    /// How much of `response` has been read.
+    read: usize,
+    timeout: Option<Duration>,
+}
+
+impl HttpTunnel {
+    /// Prepares a tunnel to the server at `url`; nothing is sent until the
+    /// first [`HttpTunnel::exchange`]. The `Authorization` header carries the
+    /// connection's bearer token, from `credentials` if set.
+    pub(crate) fn new(
+        url: &str,
+        token: Option<String>,
+        credentials: Option<Arc<dyn CredentialProvider>>,
+    ) -> Result<Self> {
+        let (authority, default_port) = match url.split_once("://") {
+            Some(("http", rest)) => (rest, 80),
+            Some(("https", rest)) => (rest, 443),
+            _ => return Err(Error::Protocol(format!("not an HTTP URL: {url}"))),
+        };
+        let authority = authority.split('/').next().unwrap_or(authority);
+        let has_port = authority
+            .rsplit_once(':')
+            .is_some_and(|(host, _)| !host.contains(':') || host.ends_with(']'));
+        let resolved = if has_port {
+            authority.to_socket_addrs()
+        } else {
+            (
+                authority.trim_start_matches('[').trim_end_matches(']'),
+                default_port,
+            )
+                .to_socket_addrs()
+        };
+        let peer_addr = resolved
+            .and_then(|mut addrs| {
+                addrs.next().ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses")
+                })
+            })
+            .map_err(|source| Error::Connect {
+                addr: url.to_string(),
+                source,
+            })?;
+        Ok(Self {
+            agent: ureq::AgentBuilder::new().build(),
+            url: format!("{}/v1/rpc", url.trim_end_matches('/')),
+            peer_addr,
+            token,
+            credentials,
+            hello: Vec::new(),
+            response: Vec::new(),
+            read: 0,
+            timeout: None,
+        })
+    }
+
+    pub(crate) fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// POSTs `frames` and queues their responses for reading.
+    pub(crate) fn exchange(&mut self, frames: &[u8]) -> Result<()> {
+        if frames.is_empty() {
+            return Ok(());
+        }
+        let starts_with_hello = frames.len() >= FRAME_HEADER_LEN
+            && u16::from_le_bytes([frames[4], frames[5]]) == MSG_HELLO;
+        if starts_with_hello {
+            self.hello.clear();
+        }
+        let mut body = Vec::with_capacity(self.hello.len() + frames.len());
+        body.extend_from_slice(&self.hello);
+        body.extend_from_slice(frames);
+
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/octet-stream");
+        let token = match &self.credentials {
+            Some(credentials) => Some(credentials.token()?),
+            None => self.token.clone(),
+        };
+        if let Some(token) = token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send_bytes(&body).map_err(|err| {
+            if let (ureq::Error::Status(401 | 403, _), Some(credentials)) =
+                (&err, &self.credentials)
+            {
+                credentials.invalidate();
+            }
+            http_error(err)
+        })?;
+        let mut received = Vec::new();
+        response.into_reader().read_to_end(&mut received)?;
+
+        let mut received = received.as_slice();
+        if starts_with_hello {
+            self.hello = frames[..FRAME_HEADER_LEN + frame_payload_len(frames)].to_vec();
+        } else if !self.hello.is_empty() {
+            if received.len() < FRAME_HEADER_LEN {
+                return Err(Error::protocol("HTTP response is missing the HELLO reply"));
+            }
+            let skip = FRAME_HEADER_LEN + frame_payload_len(received);
+            received = received.get(skip..).unwrap_or_default();
+        }
+        // Whatever was read of the last batch is gone; keep the rest.
+        self.response.drain(..self.read);
+        self.read = 0;
+        self.response.extend_from_slice(received);
+        Ok(())
+    }
+}
+
+impl Read for HttpTunnel {
+    /// Reads buffered responses; with none left this is end of stream.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut rest = &self.response[self.read..];
+        let n = rest.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+fn frame_payload_len(frame: &[u8]) -> usize {
+    u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize
+}
+
+/// Maps a failed POST to the error the same failure gets over TCP.
+fn http_error(err: ureq::Error) -> Error {
+    match err {
+        ureq::Error::Status(status, response) => {
+            let detail = response.into_string().unwrap_or_default();
+            match status {
+                401 | 403 => Error::Unauthenticated { detail },
+                404 => Error::Unsupported("the HTTP transport".into()),
+                _ => Error::server(u32::from(status), detail),
+            }
+        }
+        ureq::Error::Transport(transport) => {
+            let kind = match transport.kind() {
+                ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed => {
+                    std::io::ErrorKind::ConnectionRefused
+                }
+                _ => std::error::Error::source(&transport)
+                    .and_then(|source| source.downcast_ref::<std::io::Error>())
+                    .map(std::io::Error::kind)
+                    .unwrap_or(std::io::ErrorKind::Other),
+            };
+            Error::Io(std::io::Error::new(kind, transport.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::protocol::{encode_frame_into, read_frame};
+
+    /// The body and `Authorization` header of one POST.
+    type Posted = (Vec<u8>, Option<String>);
+
+    /// Answers each POST to `/v1/rpc` with `status` and, for 200, echoes
+    /// every frame of the body back as its own response. Returns the URL and
+    /// a channel of the request bodies and authorization headers.
+    fn spawn_http_server(status: u16) -> (String, std::sync::mpsc::Receiver<Posted>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut head = Vec::new();
+                while !head.ends_with(b"\r\n\r\n") {
+                    let mut byte = [0u8; 1];
+                    stream.read_exact(&mut byte).unwrap();
+                    head.push(byte[0]);
+                }
+                let head = String::from_utf8(head).unwrap();
+                assert!(head.starts_with("POST /v1/rpc "), "{head}");
+                let header = |name: &str| {
+                    head.lines().find_map(|line| {
+                        let (key, value) = line.split_once(": ")?;
+                        key.eq_ignore_ascii_case(name).then(|| value.to_string())
+                    })
+                };
+                let len: usize = header("content-length").unwrap().parse().unwrap();
+                let mut body = vec![0u8; len];
+                stream.read_exact(&mut body).unwrap();
+                let mut reply = Vec::new();
+                if status == 200 {
+                    let mut frames = body.as_slice();
+                    while !frames.is_empty() {
+                        let frame = read_frame(&mut frames).unwrap();
+                        encode_frame_into(
+                            &mut reply,
+                            frame.header.msg_type,
+                            0,
+                            frame.header.req_id,
+                            &frame.payload,
+                        );
+                    }
+                } else {
+                    reply.extend_from_slice(b"denied");
+                }
+                tx.send((body, header("authorization"))).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    reply.len()
+                )
+                .unwrap();
+                stream.write_all(&reply).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn frame(msg_type: u16, req_id: u64, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_frame_into(&mut out, msg_type, 0, req_id, payload);
+        out
+    }
+
+    #[test]
+    fn replays_hello_and_drops_its_reply() {
+        let (url, bodies) = spawn_http_server(200);
+        let mut tunnel = HttpTunnel::new(&url, Some("secret".into()), None).unwrap();
+
+        let hello = frame(MSG_HELLO, 1, b"hi");
+        tunnel.exchange(&hello).unwrap();
+        let (body, auth) = bodies.recv().unwrap();
+        assert_eq!(body, hello);
+        assert_eq!(auth.as_deref(), Some("Bearer secret"));
+        assert_eq!(read_frame(&mut tunnel).unwrap().header.req_id, 1);
+
+        let batch = [frame(2, 2, b"a"), frame(2, 3, b"bc")].concat();
+        tunnel.exchange(&batch).unwrap();
+        let (body, _) = bodies.recv().unwrap();
+        assert_eq!(body, [hello, batch].concat());
+        assert_eq!(read_frame(&mut tunnel).unwrap().payload, b"a");
+        assert_eq!(read_frame(&mut tunnel).unwrap().payload, b"bc");
+        let mut rest = Vec::new();
+        tunnel.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn statuses_map_to_typed_errors() {
+        let (url, _bodies) = spawn_http_server(401);
+        let mut tunnel = HttpTunnel::new(&url, None, None).unwrap();
+        let err = tunnel.exchange(&frame(MSG_HELLO, 1, b"")).unwrap_err();
+        assert!(
+            matches!(&err, Error::Unauthenticated { detail } if detail == "denied"),
+            "{err:?}"
+        );
+
+        let (url, _bodies) = spawn_http_server(404);
+        let mut tunnel = HttpTunnel::new(&url, None, None).unwrap();
+        let err = tunnel.exchange(&frame(MSG_HELLO, 1, b"")).unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        let (url, _bodies) = spawn_http_server(502);
+        let mut tunnel = HttpTunnel::new(&url, None, None).unwrap();
+        let err = tunnel.exchange(&frame(MSG_HELLO, 1, b"")).unwrap_err();
+        assert!(crate::is_server_error(&err, 502), "{err:?}");
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Context and turn identifiers.
+//!
+//! Both are `u64`s on the wire, which made it easy to pass a turn id where a
+//! context id was expected. [`ContextId`] and [`TurnId`] keep them apart:
+//! neither converts into the other, and getting the `u64` back is explicit
+//! ([`ContextId::get`], `u64::from`).
+//!
+//! Client methods take the typed ids. Code written against 0.1 that holds a
+//! bare `u64` converts at the call site with [`ContextId::new`] or `.into()`;
+//! the `From<u64>` impls are that migration path and will be removed in a
+//! later release. New code should carry the ids it gets back from the client.
+//!
+//! Both serialize as the bare number, and `Display` / `FromStr` use the
+//! decimal form, so ids in JSON, logs and command lines are unchanged.
+
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! define_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(u64);
+
+        impl $name {
+            pub const fn new(id: u64) -> Self {
+                Self(id)
+            }
+
+            /// The id as it goes on the wire.
+            pub const fn get(self) -> u64 {
+                self.0
+            }
+
+            /// Whether this is the zero id, which the protocol uses for
+            /// "none".
+            pub const fn is_zero(self) -> bool {
+                self.0 == 0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        /// Migration shim for callers still passing bare `u64`s.
+        impl From<u64> for $name {
+            fn from(id: u64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for u64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+define_id!(
+    /// A context, as returned by [`Client::create_context`](crate::Client::create_context)
+    /// and friends.
+    ContextId
+);
+
+define_id!(
+    /// A turn. Turn ids are global, not per context, and grow along a
+    /// context's history. The zero id stands for "no turn": the parent of a
+    /// context's first turn, or the base of an empty context.
+    TurnId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_print_parse_and_serialize_as_numbers() {
+        let context_id = ContextId::new(42);
+        assert_eq!(context_id.to_string(), "42");
+        assert_eq!("42".parse::<ContextId>().unwrap(), context_id);
+        assert!("forty-two".parse::<ContextId>().is_err());
+        assert_eq!(u64::from(context_id), 42);
+        assert_eq!(ContextId::from(42), context_id);
+
+        let turn_id: TurnId = serde_json::from_str("7").unwrap();
+        assert_eq!(turn_id.get(), 7);
+        assert_eq!(serde_json::to_string(&turn_id).unwrap(), "7");
+        assert_eq!(
+            rmp_serde::to_vec(&turn_id).unwrap(),
+            rmp_serde::to_vec(&7u64).unwrap()
+        );
+        assert_eq!(TurnId::default(), TurnId::new(0));
+    }
+}
@@ -0,0 +1,345 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Request interceptors.
+//!
+//! An [`Interceptor`] sees every request the client sends after HELLO and
+//! every response or error it gets back, for cross-cutting concerns such as audit logging
+//! or injecting tenant metadata. Install one or more with
+//! [`with_interceptor`]; they run in the order they were installed, on the
+//! client and every connection redialed from it. Each attempt a
+//! [reconnecting client](crate::reconnect) makes runs them again.
+//!
+//! [`Interceptor::before_request`] may rewrite the request's metadata (sent
+//! as frame metadata to servers that accept it; see
+//! [`RequestContext::with_value`](crate::RequestContext::with_value)) and
+//! its encoded body. A hook that returns an error aborts the call with
+//! [`Error::Interceptor`] before anything is sent.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use cxdb::interceptor::{with_interceptor, Interceptor, RequestEnvelope};
+//! use cxdb::{dial, CreateContextOptions, RequestContext};
+//!
+//! struct Tenant(String);
+//!
+//! impl Interceptor for Tenant {
+//!     fn before_request(&self, req: &mut RequestEnvelope) -> Result<(), String> {
+//!         req.metadata.insert("tenant".into(), self.0.clone());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let client = dial("127.0.0.1:9009", [with_interceptor(Arc::new(Tenant("acme".into())))])?;
+//! client.create_context(&RequestContext::background(), CreateContextOptions::default())?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::client::{ClientOption, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+use crate::metrics::Operation;
+use crate::protocol::{
+    FrameHeader, MSG_APPEND_TURN, MSG_CONTEXT_STATS, MSG_CTX_ARCHIVE, MSG_CTX_COMPACT,
+    MSG_CTX_PRUNE, MSG_CTX_RESTORE, MSG_GET_CHILDREN, MSG_GET_HEAD, MSG_GET_LAST, MSG_GET_LINKED,
+    MSG_SEARCH_TURNS, MSG_TEXT_SEARCH, MSG_TURN_REDACT, MSG_TYPE_HISTOGRAM,
+};
+
+/// Hooks run around every request. Every method defaults to a no-op, so
+/// implementations override only what they need.
+///
+/// Hooks run on the calling thread, outside the connection lock for
+/// single requests.
+pub trait Interceptor: Send + Sync {
+    /// Runs before `req` is sent; changes to it are what goes out. An error
+    /// aborts the call with [`Error::Interceptor`].
+    fn before_request(&self, _req: &mut RequestEnvelope) -> std::result::Result<(), String> {
+        Ok(())
+    }
+
+    /// Runs after a successful response is read. An error fails the call
+    /// with [`Error::Interceptor`], though the server has acted on it.
+    fn after_response(&self, _resp: &ResponseEnvelope<'_>) -> std::result::Result<(), String> {
+        Ok(())
+    }
+
+    /// Runs when the call fails, including by another interceptor, with the
+    /// error it returns.
+    fn on_error(&self, _req: &RequestEnvelope, _err: &Error) {}
+}
+
+impl fmt::Debug for dyn Interceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Interceptor")
+    }
+}
+
+/// Appends `interceptor` to the client's chain (see the
+/// [module docs](self)).
+pub fn with_interceptor(interceptor: Arc<dyn Interceptor>) -> ClientOption {
+    Arc::new(move |opts| opts.interceptors.push(interceptor.clone()))
+}
+
+/// A request as interceptors see it.
+#[derive(Debug, Clone)]
+pub struct RequestEnvelope {
+    pub operation: Operation,
+    pub msg_type: u16,
+    /// The context the request addresses, for requests that name exactly
+    /// one (reads, appends, pruning, redaction and so on).
+    pub context_id: Option<ContextId>,
+    /// The request's context values, sent as frame metadata.
+    pub metadata: BTreeMap<String, String>,
+    /// The encoded request body (see `docs/protocol.md`). An APPEND_TURN
+    /// body carries the turn payload's hash, so rewriting the payload here
+    /// fails the append; change turns before building the
+    /// [`AppendRequest`](crate::AppendRequest) instead.
+    pub payload: Vec<u8>,
+}
+
+/// A successful response as interceptors see it.
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseEnvelope<'a> {
+    pub request: &'a RequestEnvelope,
+    /// The encoded response body.
+    pub payload: &'a [u8],
+}
+
+impl RequestEnvelope {
+    pub(crate) fn new(ctx: &RequestContext, msg_type: u16, payload: &[u8]) -> Self {
+        Self {
+            operation: Operation::from_msg_type(msg_type),
+            msg_type,
+            context_id: request_context_id(msg_type, payload),
+            metadata: ctx
+                .values()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            payload: payload.to_vec(),
+        }
+    }
+
+    /// `ctx` with its values replaced by the envelope's metadata.
+    pub(crate) fn context(&self, ctx: &RequestContext) -> RequestContext {
+        ctx.with_values(&self.metadata)
+    }
+}
+
+/// Runs a request through `interceptors`: `before_request` in order, then
+/// `send` with the rewritten context and body, then `after_response` in
+/// order, with `on_error` on every interceptor if anything fails.
+pub(crate) fn intercept<P: AsRef<[u8]>>(
+    interceptors: &[Arc<dyn Interceptor>],
+    ctx: &RequestContext,
+    msg_type: u16,
+    payload: &[u8],
+    send: impl FnOnce(&RequestContext, &[u8]) -> Result<(FrameHeader, P)>,
+) -> Result<(FrameHeader, P)> {
+    let mut req = RequestEnvelope::new(ctx, msg_type, payload);
+    let result = (|| {
+        for interceptor in interceptors {
+            interceptor
+                .before_request(&mut req)
+                .map_err(Error::Interceptor)?;
+        }
+        let response = send(&req.context(ctx), &req.payload)?;
+        let resp = ResponseEnvelope {
+            request: &req,
+            payload: response.1.as_ref(),
+        };
+        for interceptor in interceptors {
+            interceptor
+                .after_response(&resp)
+                .map_err(Error::Interceptor)?;
+        }
+        Ok(response)
+    })();
+    if let Err(err) = &result {
+        for interceptor in interceptors {
+            interceptor.on_error(&req, err);
+        }
+    }
+    result
+}
+
+/// The context id a request body starts with, for message types whose body
+/// names a single context there. CTX_COMPACT puts it after `up_to_turn_id`.
+fn request_context_id(msg_type: u16, payload: &[u8]) -> Option<ContextId> {
+    let offset = match msg_type {
+        MSG_GET_HEAD | MSG_APPEND_TURN | MSG_GET_LAST | MSG_CTX_PRUNE | MSG_TURN_REDACT
+        | MSG_GET_CHILDREN | MSG_SEARCH_TURNS | MSG_TYPE_HISTOGRAM | MSG_CONTEXT_STATS
+        | MSG_GET_LINKED | MSG_TEXT_SEARCH | MSG_CTX_ARCHIVE | MSG_CTX_RESTORE => 0,
+        MSG_CTX_COMPACT => 8,
+        _ => return None,
+    };
+    let bytes = payload.get(offset..offset + 8)?;
+    let context_id = u64::from_le_bytes(bytes.try_into().ok()?);
+    // TEXT_SEARCH uses 0 for "every context".
+    (context_id != 0).then_some(ContextId::new(context_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::protocol::{MSG_ERROR, MSG_GET_LAST};
+    use crate::test_util::{error_payload, spawn_scripted_server, turn_records_payload};
+    use crate::turn::GetLastOptions;
+    use crate::{dial, RequestContext};
+
+    /// Logs each hook as `name:hook:detail` and applies `rewrite` before
+    /// requests.
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        rewrite: fn(&mut RequestEnvelope) -> std::result::Result<(), String>,
+    }
+
+    impl Interceptor for Recorder {
+        fn before_request(&self, req: &mut RequestEnvelope) -> std::result::Result<(), String> {
+            self.log.lock().unwrap().push(format!(
+                "{}:before:{:?}:{:?}",
+                self.name, req.context_id, req.metadata
+            ));
+            (self.rewrite)(req)
+        }
+
+        fn after_response(&self, resp: &ResponseEnvelope<'_>) -> std::result::Result<(), String> {
+            self.log.lock().unwrap().push(format!(
+                "{}:after:{}",
+                self.name,
+                resp.request.operation.as_str()
+            ));
+            Ok(())
+        }
+
+        fn on_error(&self, _req: &RequestEnvelope, err: &Error) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{}:error:{err}", self.name));
+        }
+    }
+
+    fn recorders(
+        first: fn(&mut RequestEnvelope) -> std::result::Result<(), String>,
+    ) -> (Arc<Mutex<Vec<String>>>, Vec<crate::ClientOption>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let options = [("a", first), ("b", (|_| Ok(())) as fn(&mut _) -> _)]
+            .into_iter()
+            .map(|(name, rewrite)| {
+                with_interceptor(Arc::new(Recorder {
+                    name,
+                    log: log.clone(),
+                    rewrite,
+                }))
+            })
+            .collect();
+        (log, options)
+    }
+
+    fn opts() -> GetLastOptions {
+        GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn interceptors_run_in_order_and_rewrite_requests() {
+        let (addr, handle) =
+            spawn_scripted_server(vec![(MSG_GET_LAST, turn_records_payload(&[b"\x91\x01"]))]);
+        let (log, options) = recorders(|req| {
+            req.metadata.insert("tenant".into(), "acme".into());
+            req.payload[..8].copy_from_slice(&9u64.to_le_bytes());
+            req.context_id = Some(ContextId::new(9));
+            Ok(())
+        });
+        let client = dial(&addr, options).unwrap();
+        let ctx = RequestContext::background().with_value("trace", "t1");
+
+        client.get_last(&ctx, ContextId::new(1), opts()).unwrap();
+        client.close().unwrap();
+
+        let frames = handle.join().unwrap();
+        assert_eq!(frames[0].payload[..8], 9u64.to_le_bytes());
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                r#"a:before:Some(ContextId(1)):{"trace": "t1"}"#,
+                r#"b:before:Some(ContextId(9)):{"tenant": "acme", "trace": "t1"}"#,
+                "a:after:get_last",
+                "b:after:get_last",
+            ]
+        );
+    }
+
+    #[test]
+    fn failing_interceptor_aborts_before_sending() {
+        let (addr, handle) =
+            spawn_scripted_server(vec![(MSG_GET_LAST, turn_records_payload(&[b"\x91\x01"]))]);
+        let (log, options) = recorders(|_| Err("tenant unknown".into()));
+        let client = dial(&addr, options).unwrap();
+
+        let err = client
+            .get_last(&RequestContext::background(), ContextId::new(1), opts())
+            .unwrap_err();
+        assert!(matches!(&err, Error::Interceptor(msg) if msg == "tenant unknown"));
+        client.close().unwrap();
+
+        assert!(handle.join().unwrap().is_empty());
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "a:before:Some(ContextId(1)):{}",
+                "a:error:cxdb: interceptor: tenant unknown",
+                "b:error:cxdb: interceptor: tenant unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn server_errors_and_batches_reach_interceptors() {
+        let records = turn_records_payload(&[b"\x91\x01"]);
+        let (addr, _handle) = spawn_scripted_server(vec![
+            (MSG_ERROR, error_payload(404, "context")),
+            (MSG_GET_LAST, records.clone()),
+            (MSG_GET_LAST, records),
+        ]);
+        let (log, options) = recorders(|_| Ok(()));
+        let client = dial(&addr, options).unwrap();
+        let ctx = RequestContext::background();
+
+        client
+            .get_last(&ctx, ContextId::new(1), opts())
+            .unwrap_err();
+        let batch = client
+            .get_last_many(
+                &ctx,
+                &[(ContextId::new(2), opts()), (ContextId::new(3), opts())],
+            )
+            .unwrap();
+        assert!(batch.iter().all(Result::is_ok));
+
+        let log = log.lock().unwrap();
+        assert!(log[2].starts_with("a:error:"), "{log:?}");
+        assert!(log[3].starts_with("b:error:"), "{log:?}");
+        let batch_before: Vec<_> = log[4..]
+            .iter()
+            .filter(|entry| entry.starts_with("a:before"))
+            .collect();
+        assert_eq!(
+            batch_before,
+            [
+                "a:before:Some(ContextId(2)):{}",
+                "a:before:Some(ContextId(3)):{}"
+            ]
+        );
+    }
+}
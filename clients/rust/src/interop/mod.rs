@@ -0,0 +1,6 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversions between CXDB conversation turns and other message formats.
+
+pub mod openai;
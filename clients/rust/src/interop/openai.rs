@@ -0,0 +1,352 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! OpenAI chat `messages` conversion.
+//!
+//! [`turns_to_openai`] turns a context's [`ConversationItem`] turns into the
+//! `messages` array of a chat completion request, and
+//! [`chat_messages_to_appends`] turns the reply (or a whole transcript) back
+//! into append requests. [`ChatMessage`] serializes with serde to the
+//! OpenAI JSON shape.
+//!
+//! | OpenAI message | Conversation item |
+//! |----------------|-------------------|
+//! | `system` | `system` (kind `info`) |
+//! | `user` | `user_input` |
+//! | `assistant` content | `assistant` |
+//! | `assistant` `tool_calls` | one `tool_call` per call |
+//! | `tool` | `tool_result` |
+//!
+//! Reading also accepts `assistant_turn` items, whose tool calls become the
+//! message's `tool_calls` followed by a `tool` message per finished call.
+//! Handoffs and turns of other types are skipped.
+//!
+//! ```no_run
+//! use cxdb::interop::openai::{chat_messages_to_appends, turns_to_openai};
+//! use cxdb::{dial, ContextId, GetLastOptions, RequestContext};
+//!
+//! # fn call_llm(_: &[cxdb::interop::openai::ChatMessage]) -> Vec<cxdb::interop::openai::ChatMessage> { Vec::new() }
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let ctx = RequestContext::background();
+//! let context_id = ContextId::new(1);
+//! let opts = GetLastOptions { include_payload: true, ..Default::default() };
+//! let messages = turns_to_openai(&client.get_last(&ctx, context_id, opts)?)?;
+//! for req in chat_messages_to_appends(context_id, &call_llm(&messages))? {
+//!     client.append_turn(&ctx, &req)?;
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::ids::ContextId;
+use crate::turn::{AppendRequest, TurnRecord};
+use crate::typed::{typed_append_request, CxdbType};
+use crate::types::{
+    new_assistant, new_system_info, new_tool_call, new_tool_result, new_user_input,
+    ConversationItem, ItemTypeAssistant, ItemTypeAssistantTurn, ItemTypeSystem, ItemTypeToolCall,
+    ItemTypeToolResult, ItemTypeUserInput,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+/// One entry of an OpenAI chat `messages` array.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    /// Text content; `None` for assistant messages that only call tools.
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ChatToolCall>,
+    /// The call a `tool` message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: Some(content.into()),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    /// A `tool` message answering `tool_call_id`.
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+}
+
+/// A function call requested by an assistant message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatToolCall {
+    pub id: String,
+    /// Always `"function"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: ChatFunctionCall,
+}
+
+impl ChatToolCall {
+    pub fn function(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        arguments: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            kind: "function".to_string(),
+            function: ChatFunctionCall {
+                name: name.into(),
+                arguments: arguments.into(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatFunctionCall {
+    pub name: String,
+    /// JSON-encoded arguments, as the model produced them.
+    pub arguments: String,
+}
+
+/// Converts conversation turns, oldest first, into chat messages. Turns not
+/// declared as [`ConversationItem`] are skipped; a conversation turn whose
+/// payload fails to decode (or was omitted) returns
+/// [`Error::Decode`](crate::Error::Decode).
+///
+/// Consecutive `tool_call` items join the preceding assistant message, as
+/// the chat format requires.
+#[allow(non_upper_case_globals)]
+pub fn turns_to_openai(turns: &[TurnRecord]) -> Result<Vec<ChatMessage>> {
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    for turn in turns {
+        if !ConversationItem::matches(&turn.type_id) {
+            continue;
+        }
+        let item: ConversationItem = turn.decode()?;
+        match item.item_type.as_str() {
+            ItemTypeSystem => {
+                if let Some(system) = item.system {
+                    messages.push(ChatMessage::new(Role::System, system.content));
+                }
+            }
+            ItemTypeUserInput => {
+                if let Some(input) = item.user_input {
+                    messages.push(ChatMessage::new(Role::User, input.text));
+                }
+            }
+            ItemTypeAssistant => {
+                if let Some(assistant) = item.assistant {
+                    messages.push(ChatMessage::new(Role::Assistant, assistant.text));
+                }
+            }
+            ItemTypeToolCall => {
+                let Some(call) = item.tool_call else {
+                    continue;
+                };
+                let call = ChatToolCall::function(call.call_id, call.name, call.args);
+                match messages.last_mut() {
+                    Some(last) if last.role == Role::Assistant => last.tool_calls.push(call),
+                    _ => messages.push(ChatMessage {
+                        role: Role::Assistant,
+                        content: None,
+                        tool_calls: vec![call],
+                        tool_call_id: None,
+                    }),
+                }
+            }
+            ItemTypeToolResult => {
+                if let Some(result) = item.tool_result {
+                    messages.push(ChatMessage::tool(result.call_id, result.content));
+                }
+            }
+            ItemTypeAssistantTurn => {
+                let Some(turn) = item.turn else {
+                    continue;
+                };
+                let mut results = Vec::new();
+                let tool_calls = turn
+                    .tool_calls
+                    .into_iter()
+                    .map(|call| {
+                        let output = match (call.result, call.error) {
+                            (Some(result), _) => Some(result.content),
+                            (None, Some(error)) => Some(error.message),
+                            (None, None) => None,
+                        };
+                        if let Some(output) = output {
+                            results.push(ChatMessage::tool(call.id.clone(), output));
+                        }
+                        ChatToolCall::function(call.id, call.name, call.args)
+                    })
+                    .collect();
+                messages.push(ChatMessage {
+                    role: Role::Assistant,
+                    content: (!turn.text.is_empty()).then_some(turn.text),
+                    tool_calls,
+                    tool_call_id: None,
+                });
+                messages.extend(results);
+            }
+            _ => {}
+        }
+    }
+    Ok(messages)
+}
+
+/// Converts chat messages into append requests for `context_id`, in order.
+/// An assistant message becomes an `assistant` item for its content (if
+/// any) followed by a `tool_call` item per call. The requests append at the
+/// context head, so send them one after another.
+pub fn chat_messages_to_appends(
+    context_id: ContextId,
+    messages: &[ChatMessage],
+) -> Result<Vec<AppendRequest>> {
+    let mut items = Vec::new();
+    for message in messages {
+        let content = message.content.clone().unwrap_or_default();
+        match message.role {
+            Role::System => items.push(new_system_info(content)),
+            Role::User => items.push(new_user_input(content, Vec::new())),
+            Role::Assistant => {
+                if message.content.is_some() || message.tool_calls.is_empty() {
+                    items.push(new_assistant(content));
+                }
+                items.extend(message.tool_calls.iter().map(|call| {
+                    new_tool_call(&call.id, &call.function.name, &call.function.arguments)
+                }));
+            }
+            Role::Tool => {
+                let call_id = message.tool_call_id.clone().unwrap_or_default();
+                items.push(new_tool_result(call_id, content, false));
+            }
+        }
+    }
+    items
+        .iter()
+        .map(|item| typed_append_request(context_id, item))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::encode_msgpack;
+    use crate::ids::TurnId;
+    use crate::protocol::{COMPRESSION_NONE, ENCODING_MSGPACK};
+    use crate::types::{build_assistant_turn, build_tool_call_item};
+
+    /// The records a server would return for `requests`, oldest first.
+    fn stored(requests: &[AppendRequest]) -> Vec<TurnRecord> {
+        requests
+            .iter()
+            .enumerate()
+            .map(|(i, req)| TurnRecord {
+                turn_id: TurnId::new(i as u64 + 1),
+                parent_id: TurnId::new(i as u64),
+                depth: i as u32 + 1,
+                type_id: req.type_id.as_str().into(),
+                type_version: req.type_version,
+                encoding: ENCODING_MSGPACK,
+                compression: COMPRESSION_NONE,
+                payload_hash: *blake3::hash(&req.payload).as_bytes(),
+                payload_size: req.payload.len() as u32,
+                payload_omitted: false,
+                payload: req.payload.clone(),
+                writer_id: None,
+                writer_seq: 0,
+                expires_at_unix_ms: None,
+                expired: false,
+                created_at_unix_ms: None,
+                redacted: false,
+                redacted_at_unix_ms: None,
+                redaction_reason: None,
+                links: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn messages_round_trip_through_appends() {
+        let messages = vec![
+            ChatMessage::new(Role::System, "be brief"),
+            ChatMessage::new(Role::User, "weather in Paris?"),
+            ChatMessage {
+                role: Role::Assistant,
+                content: None,
+                tool_calls: vec![
+                    ChatToolCall::function("call_1", "weather", r#"{"city":"Paris"}"#),
+                    ChatToolCall::function("call_2", "time", "{}"),
+                ],
+                tool_call_id: None,
+            },
+            ChatMessage::tool("call_1", "18C"),
+            ChatMessage::tool("call_2", "noon"),
+            ChatMessage::new(Role::Assistant, "18C at noon."),
+        ];
+        let appends = chat_messages_to_appends(ContextId::new(7), &messages).unwrap();
+        assert_eq!(appends.len(), 7);
+        assert!(appends.iter().all(|req| req.context_id == ContextId::new(7)
+            && req.type_id == ConversationItem::TYPE_ID
+            && req.type_version == ConversationItem::TYPE_VERSION));
+
+        assert_eq!(turns_to_openai(&stored(&appends)).unwrap(), messages);
+    }
+
+    #[test]
+    fn assistant_turns_expand_to_calls_and_results() {
+        let mut call = build_tool_call_item("call_1", "ls", "{}");
+        call.with_result("a.txt", Some(0));
+        let mut turn = build_assistant_turn("");
+        turn.with_tool_call(call.build());
+        // Still running: no tool message yet.
+        turn.with_tool_call(build_tool_call_item("call_2", "cat", "{}").build());
+        let mut requests = vec![
+            typed_append_request(ContextId::new(1), &turn.build()).unwrap(),
+            AppendRequest::new(
+                ContextId::new(1),
+                "com.example.Other",
+                1,
+                encode_msgpack(&1u8).unwrap(),
+            ),
+        ];
+        requests.extend(
+            chat_messages_to_appends(ContextId::new(1), &[ChatMessage::new(Role::User, "ok")])
+                .unwrap(),
+        );
+
+        let messages = turns_to_openai(&stored(&requests)).unwrap();
+        assert_eq!(
+            messages,
+            [
+                ChatMessage {
+                    role: Role::Assistant,
+                    content: None,
+                    tool_calls: vec![
+                        ChatToolCall::function("call_1", "ls", "{}"),
+                        ChatToolCall::function("call_2", "cat", "{}"),
+                    ],
+                    tool_call_id: None,
+                },
+                ChatMessage::tool("call_1", "a.txt"),
+                ChatMessage::new(Role::User, "ok"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,492 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Chronological iteration over a context's whole history.
+//!
+//! [`Client::iter_turns`] reads a context oldest first, page by page, while
+//! other clients may be appending to it. GET_LAST only pages backwards, so
+//! each pass first walks back from the head collecting page cursors (turn
+//! metadata only), then reads the pages forwards. Every page below the newest
+//! is addressed by a `before` cursor, a turn id, so turns appended meanwhile
+//! cannot shift it: no turn is returned twice and none is skipped.
+//!
+//! By default the iterator keeps going until it has caught up with the head,
+//! returning turns appended while it ran. With [`IterOptions::snapshot`] it
+//! returns exactly the turns at or below the head it saw on its first page.
+//!
+//! [`Client::for_each_turn`] is the push counterpart for ETL jobs: it hands
+//! each turn of a [`Client::get_last`] selection to a closure, a page at a
+//! time as each response arrives, and stops requesting pages as soon as the
+//! closure breaks.
+//!
+//! ```no_run
+//! use cxdb::iter::IterOptions;
+//! use cxdb::{dial, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", Vec::new())?;
+//! let ctx = RequestContext::background();
+//! for turn in client.iter_turns(&ctx, ContextId::new(1), IterOptions::default().snapshot(true)) {
+//!     println!("{}", turn?.turn_id);
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+
+use crate::client::{Client, RequestContext};
+use crate::error::Result;
+use crate::ids::{ContextId, TurnId};
+use crate::turn::{GetLastOptions, Order, TurnFields, TurnRecord};
+
+/// Default turns per page read by [`Client::iter_turns`].
+pub const DEFAULT_ITER_PAGE_SIZE: u32 = 256;
+
+/// Turns per GET_LAST page read by [`Client::for_each_turn`].
+const FOR_EACH_PAGE_SIZE: u32 = DEFAULT_ITER_PAGE_SIZE;
+
+/// How [`Client::iter_turns`] reads a context.
+#[derive(Debug, Clone, Copy)]
+pub struct IterOptions {
+    /// Turns per GET_LAST page.
+    pub page_size: u32,
+    pub include_payload: bool,
+    /// Walk past compaction summaries into the turns they cover.
+    pub include_compacted: bool,
+    /// Stop at the head seen on the first page instead of following appends
+    /// made during the iteration.
+    pub snapshot: bool,
+}
+
+impl Default for IterOptions {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_ITER_PAGE_SIZE,
+            include_payload: true,
+            include_compacted: false,
+            snapshot: false,
+        }
+    }
+}
+
+impl IterOptions {
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn include_payload(mut self, include: bool) -> Self {
+        self.include_payload = include;
+        self
+    }
+
+    pub fn include_compacted(mut self, include: bool) -> Self {
+        self.include_compacted = include;
+        self
+    }
+
+    /// Pins the iteration to the head (turn id) observed at the first page.
+    pub fn snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+}
+
+impl Client {
+    /// Iterates over the turns of `context_id`, oldest first. Expired turns
+    /// are skipped. After an error the iterator ends.
+    pub fn iter_turns(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: IterOptions,
+    ) -> TurnIter<'_> {
+        TurnIter {
+            client: self,
+            ctx: ctx.clone(),
+            context_id,
+            opts: IterOptions {
+                page_size: opts.page_size.max(1),
+                ..opts
+            },
+            returned: TurnId::default(),
+            cursors: Vec::new(),
+            newest: VecDeque::new(),
+            buffer: VecDeque::new(),
+            planned: false,
+            done: false,
+        }
+    }
+
+    /// Calls `f` with each turn [`Client::get_last`] would return for
+    /// `opts`, in `opts.order`, without collecting them: turns are read
+    /// 256 at a time and handed over as each page arrives. Pass `limit: u32::MAX` to visit the whole history. When `f`
+    /// returns [`ControlFlow::Break`] no further pages are requested.
+    ///
+    /// GET_LAST pages backwards, so an oldest-first walk longer than one
+    /// page first reads back through the selection, turn ids only, to find
+    /// its page cursors. Turns appended meanwhile are not visited.
+    pub fn for_each_turn(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+        mut f: impl FnMut(TurnRecord) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let limit = opts.limit;
+        let pages = match opts.order {
+            Order::OldestFirst if limit > FOR_EACH_PAGE_SIZE => {
+                self.page_cursors(ctx, context_id, opts.clone(), limit)?
+            }
+            Order::OldestFirst => vec![(opts.before_turn_id, limit)],
+            Order::NewestFirst => {
+                let mut remaining = limit;
+                let mut before = opts.before_turn_id;
+                while remaining > 0 {
+                    let page_limit = remaining.min(FOR_EACH_PAGE_SIZE);
+                    let page = GetLastOptions {
+                        limit: page_limit,
+                        before_turn_id: before,
+                        ..opts.clone()
+                    };
+                    let turns = self.get_last(ctx, context_id, page)?;
+                    let full = turns.len() == page_limit as usize;
+                    remaining -= turns.len() as u32;
+                    before = turns.last().map(|turn| turn.turn_id);
+                    for turn in turns {
+                        if f(turn).is_break() {
+                            return Ok(());
+                        }
+                    }
+                    if !full {
+                        break;
+                    }
+                }
+                return Ok(());
+            }
+        };
+        for (before_turn_id, limit) in pages.into_iter().rev() {
+            let page = GetLastOptions {
+                limit,
+                before_turn_id,
+                ..opts.clone()
+            };
+            for turn in self.get_last(ctx, context_id, page)? {
+                if f(turn).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The `(before_turn_id, limit)` of each page of the newest `limit`
+    /// turns `opts` selects, newest page first. The newest page is pinned
+    /// below the head as it was read.
+    fn page_cursors(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+        limit: u32,
+    ) -> Result<Vec<(Option<TurnId>, u32)>> {
+        let mut pages = Vec::new();
+        let mut remaining = limit;
+        let mut before = opts.before_turn_id;
+        while remaining > 0 {
+            let page_limit = remaining.min(FOR_EACH_PAGE_SIZE);
+            let page = GetLastOptions {
+                limit: page_limit,
+                include_payload: false,
+                before_turn_id: before,
+                order: Order::OldestFirst,
+                projection: TurnFields::NONE,
+                ..opts.clone()
+            };
+            let turns = self.get_last(ctx, context_id, page)?;
+            let (Some(oldest), Some(newest)) = (turns.first(), turns.last()) else {
+                break;
+            };
+            let cursor = before.or(newest.turn_id.get().checked_add(1).map(TurnId::new));
+            pages.push((cursor, turns.len() as u32));
+            remaining -= turns.len() as u32;
+            before = Some(oldest.turn_id);
+            if turns.len() < page_limit as usize {
+                break;
+            }
+        }
+        Ok(pages)
+    }
+}
+
+/// Iterator returned by [`Client::iter_turns`].
+pub struct TurnIter<'a> {
+    client: &'a Client,
+    ctx: RequestContext,
+    context_id: ContextId,
+    opts: IterOptions,
+    /// Id of the last turn returned; only newer turns are returned next.
+    returned: TurnId,
+    /// `before` cursors of the pages still to read, oldest last.
+    cursors: Vec<TurnId>,
+    /// The newest page of the current pass, read while planning it.
+    newest: VecDeque<TurnRecord>,
+    /// Turns of the page being returned.
+    buffer: VecDeque<TurnRecord>,
+    /// Whether a pass has been planned yet.
+    planned: bool,
+    done: bool,
+}
+
+impl TurnIter<'_> {
+    fn get_options(&self, before_turn_id: Option<TurnId>, payload: bool) -> GetLastOptions {
+        let opts = GetLastOptions {
+            limit: self.opts.page_size,
+            include_payload: true,
+            before_turn_id,
+            ..Default::default()
+        }
+        .include_compacted(self.opts.include_compacted);
+        if payload {
+            opts
+        } else {
+            opts.max_payload_bytes(0)
+        }
+    }
+
+    fn read_page(&self, before_turn_id: Option<TurnId>, payload: bool) -> Result<Vec<TurnRecord>> {
+        let opts = self.get_options(before_turn_id, payload);
+        self.client.get_last(&self.ctx, self.context_id, opts)
+    }
+
+    /// Plans the next pass: reads the newest page from the live head and
+    /// walks back, metadata only, to the last turn returned. Returns false
+    /// when there is nothing newer to read.
+    fn plan(&mut self) -> Result<bool> {
+        let newest = self.read_page(None, self.opts.include_payload)?;
+        let mut oldest = match (newest.first(), newest.last()) {
+            (Some(first), Some(last)) if last.turn_id > self.returned => first.turn_id,
+            _ => return Ok(false),
+        };
+        let mut full = newest.len() == self.opts.page_size as usize;
+        self.newest = newest.into_iter().collect();
+        while full && oldest > self.returned {
+            let page = self.read_page(Some(oldest), false)?;
+            full = page.len() == self.opts.page_size as usize;
+            self.cursors.push(oldest);
+            match page.first() {
+                Some(first) => oldest = first.turn_id,
+                None => break,
+            }
+        }
+        Ok(true)
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        let page: VecDeque<TurnRecord> = match self.cursors.pop() {
+            Some(cursor) => self
+                .read_page(Some(cursor), self.opts.include_payload)?
+                .into(),
+            None if !self.newest.is_empty() => std::mem::take(&mut self.newest),
+            None if self.planned && self.opts.snapshot => {
+                self.done = true;
+                return Ok(());
+            }
+            None => {
+                self.planned = true;
+                if !self.plan()? {
+                    self.done = true;
+                }
+                return Ok(());
+            }
+        };
+        let returned = self.returned;
+        self.buffer
+            .extend(page.into_iter().filter(|turn| turn.turn_id > returned));
+        Ok(())
+    }
+}
+
+impl Iterator for TurnIter<'_> {
+    type Item = Result<TurnRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(turn) = self.buffer.pop_front() {
+                self.returned = turn.turn_id;
+                return Some(Ok(turn));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.fill() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_GET_LAST;
+    use crate::test_util::{spawn_multi_server, turn_page_listing, turn_page_payload};
+
+    /// A context holding turns `1..=head`, where a writer appends
+    /// `appends_per_read` turns after each of the first `busy_reads` reads.
+    fn growing_context(head: u64, appends_per_read: u64, busy_reads: u64) -> String {
+        let head = Arc::new(AtomicU64::new(head));
+        let reads = AtomicU64::new(0);
+        spawn_multi_server(move |req| {
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let top = match req.payload.get(36..44) {
+                Some(before) => u64::from_le_bytes(before.try_into().unwrap()) - 1,
+                None => head.load(Ordering::SeqCst),
+            };
+            let first = top.saturating_sub(limit) + 1;
+            let payloads: Vec<Vec<u8>> = (first..=top).map(|id| vec![id as u8]).collect();
+            let payloads: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+            if reads.fetch_add(1, Ordering::SeqCst) < busy_reads {
+                head.fetch_add(appends_per_read, Ordering::SeqCst);
+            }
+            (MSG_GET_LAST, turn_page_payload(first, &payloads))
+        })
+    }
+
+    fn turn_ids(iter: TurnIter<'_>) -> Vec<u64> {
+        iter.map(|turn| turn.unwrap().turn_id.get()).collect()
+    }
+
+    #[test]
+    fn snapshot_stops_at_the_first_head_despite_appends() {
+        let client = dial(&growing_context(25, 3, u64::MAX), []).unwrap();
+        let opts = IterOptions::default().page_size(10).snapshot(true);
+        let ids =
+            turn_ids(client.iter_turns(&RequestContext::background(), ContextId::new(1), opts));
+        assert_eq!(ids, (1..=25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn default_iteration_follows_appends_until_caught_up() {
+        let client = dial(&growing_context(25, 3, 6), []).unwrap();
+        let opts = IterOptions::default().page_size(10);
+        let ids =
+            turn_ids(client.iter_turns(&RequestContext::background(), ContextId::new(1), opts));
+        assert_eq!(ids, (1..=43).collect::<Vec<_>>());
+    }
+
+    /// A context holding turns `1..=head`, counting the GET_LAST reads.
+    fn counted_context(head: u64) -> (String, Arc<AtomicU64>) {
+        let reads = Arc::new(AtomicU64::new(0));
+        let counter = reads.clone();
+        let addr = spawn_multi_server(move |req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let include_payload = req.payload[12] != 0;
+            let top = match req.payload.get(36..44) {
+                Some(before) => (u64::from_le_bytes(before.try_into().unwrap()) - 1).min(head),
+                None => head,
+            };
+            let first = top.saturating_sub(limit) + 1;
+            let payloads: Vec<Vec<u8>> = (first..=top).map(|id| vec![id as u8]).collect();
+            let payloads: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+            if include_payload {
+                (MSG_GET_LAST, turn_page_payload(first, &payloads))
+            } else {
+                (MSG_GET_LAST, turn_page_listing(first, &payloads))
+            }
+        });
+        (addr, reads)
+    }
+
+    fn visited(client: &Client, opts: GetLastOptions) -> Vec<u64> {
+        let mut ids = Vec::new();
+        client
+            .for_each_turn(
+                &RequestContext::background(),
+                ContextId::new(1),
+                opts,
+                |turn| {
+                    assert_eq!(turn.payload, [turn.turn_id.get() as u8]);
+                    ids.push(turn.turn_id.get());
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+        ids
+    }
+
+    #[test]
+    fn for_each_turn_visits_the_get_last_selection_in_order() {
+        let (addr, _) = counted_context(600);
+        let client = dial(&addr, []).unwrap();
+        let all = GetLastOptions {
+            limit: u32::MAX,
+            include_payload: true,
+            ..Default::default()
+        };
+        assert_eq!(visited(&client, all.clone()), (1..=600).collect::<Vec<_>>());
+        let newest = all.clone().order(Order::NewestFirst);
+        assert_eq!(
+            visited(&client, newest),
+            (1..=600).rev().collect::<Vec<_>>()
+        );
+        let last = GetLastOptions { limit: 300, ..all };
+        assert_eq!(
+            visited(&client, last.clone()),
+            (301..=600).collect::<Vec<_>>()
+        );
+        let older = last.before(TurnId::new(101)).order(Order::NewestFirst);
+        assert_eq!(visited(&client, older), (1..=100).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn for_each_turn_stops_requesting_pages_on_break() {
+        let (addr, reads) = counted_context(600);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            limit: u32::MAX,
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let mut seen = 0;
+        client
+            .for_each_turn(&ctx, ContextId::new(1), opts.clone(), |_| {
+                seen += 1;
+                if seen == 10 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert_eq!(seen, 10);
+        // Three cursor pages, then only the oldest page of turns.
+        assert_eq!(reads.swap(0, Ordering::SeqCst), 4);
+
+        let opts = opts.order(Order::NewestFirst);
+        client
+            .for_each_turn(&ctx, ContextId::new(1), opts, |_| ControlFlow::Break(()))
+            .unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn empty_context_yields_nothing() {
+        let addr = spawn_multi_server(|_| (MSG_GET_LAST, turn_page_payload(1, &[])));
+        let client = dial(&addr, []).unwrap();
+        let mut iter = client.iter_turns(
+            &RequestContext::background(),
+            ContextId::new(1),
+            IterOptions::default(),
+        );
+        assert!(iter.next().is_none());
+    }
+}
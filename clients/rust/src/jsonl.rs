@@ -0,0 +1,819 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Context export and import as JSON Lines.
+//!
+//! [`Client::export_jsonl`] writes a context's history, oldest turn first,
+//! one JSON object per line:
+//!
+//! ```json
+//! {"turn_id":7,"depth":0,"type_id":"cxdb.ConversationItem","type_version":3,"encoding":1,"content_hash":"af13…","created_at_unix_ms":1735689600000,"payload_base64":"gaEx…"}
+//! ```
+//!
+//! `created_at_unix_ms` is written when the server reports turn timestamps.
+//! Import ignores it: the new context's turns are stamped when replayed.
+//!
+//! A [redacted](crate::redact) turn is written with `"redacted":true`, its
+//! `redacted_at_unix_ms` and `redaction_reason`, an empty `payload_base64`
+//! and the `content_hash` of the erased payload. Import replays it as an
+//! empty turn and redacts that again with the same reason.
+//!
+//! Payloads are kept byte for byte as base64 so [`Client::import_jsonl`]
+//! can replay the file into a new context with every content hash intact.
+//! Decode them with [`decode_msgpack`](crate::decode_msgpack) or
+//! [`TurnRecord::decode`](crate::TurnRecord::decode) for analysis.
+//!
+//! For hand-written fixtures, import also accepts a line whose `payload` is
+//! plain JSON. That payload is encoded to msgpack with
+//! [`encode_msgpack`](crate::encode_msgpack).
+//! `turn_id`, `depth`, `encoding` and `content_hash` may be left out.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Client, RequestContext};
+use crate::context::{ContextHead, CreateContextOptions};
+use crate::encoding::encode_msgpack;
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::ENCODING_MSGPACK;
+use crate::redact::RedactOptions;
+use crate::turn::{AppendRequest, GetLastOptions, TurnRecord};
+
+/// Turns fetched per request while exporting.
+const EXPORT_PAGE_SIZE: u32 = 256;
+
+/// One exported turn: a line of the JSON Lines file.
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonlTurn {
+    turn_id: TurnId,
+    depth: u32,
+    type_id: String,
+    type_version: u32,
+    encoding: u32,
+    /// BLAKE3 hash of the payload, hex encoded.
+    content_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at_unix_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    redacted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redacted_at_unix_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redaction_reason: Option<String>,
+    payload_base64: String,
+}
+
+/// A line as read back by [`Client::import_jsonl`].
+#[derive(Debug, Deserialize)]
+struct ImportedTurn {
+    type_id: Option<String>,
+    type_version: Option<u32>,
+    encoding: Option<u32>,
+    content_hash: Option<String>,
+    payload_base64: Option<String>,
+    payload: Option<serde_json::Value>,
+    #[serde(default)]
+    redacted: bool,
+    redaction_reason: Option<String>,
+}
+
+/// How [`Client::import_jsonl`] replays a file.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Type every turn is appended as instead of its archived
+    /// `type_id`/`type_version`. `None` keeps each line's own.
+    pub type_override: Option<(String, u32)>,
+    /// Check each payload against its archived `content_hash`, failing with
+    /// [`Error::HashMismatch`] on the first that differs.
+    pub verify_hashes: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            type_override: None,
+            verify_hashes: true,
+        }
+    }
+}
+
+impl ImportOptions {
+    pub fn type_override(mut self, type_id: impl Into<String>, type_version: u32) -> Self {
+        self.type_override = Some((type_id.into(), type_version));
+        self
+    }
+
+    pub fn verify_hashes(mut self, verify: bool) -> Self {
+        self.verify_hashes = verify;
+        self
+    }
+}
+
+/// Where [`Client::export_jsonl_resumable`] records its progress.
+#[derive(Debug, Clone)]
+pub struct ExportCheckpoint {
+    pub path: PathBuf,
+    /// Turns written between checkpoints. Each checkpoint syncs the output
+    /// file first.
+    pub every: u64,
+}
+
+impl ExportCheckpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            every: EXPORT_PAGE_SIZE as u64,
+        }
+    }
+
+    pub fn every(mut self, turns: u64) -> Self {
+        self.every = turns.max(1);
+        self
+    }
+
+    fn load(&self) -> Result<Option<CheckpointState>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|err| checkpoint_mismatch(format!("unreadable checkpoint: {err}")))
+    }
+
+    /// Replaces the checkpoint atomically, so a crash leaves the old one.
+    fn save(&self, state: &CheckpointState) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let data = serde_json::to_vec(state).map_err(Error::encode_err)?;
+        let mut file = File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_data()?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Progress of a resumable export, as stored in its checkpoint file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheckpointState {
+    context_id: ContextId,
+    /// The head the export started from; a resumed export stops there too.
+    head_turn_id: TurnId,
+    head_depth: u32,
+    /// Last turn fully written; 0 before the first.
+    turn_id: TurnId,
+    /// Output length after that turn's line.
+    offset: u64,
+    written: u64,
+    /// BLAKE3 of the last `tail_len` bytes before `offset` (that line),
+    /// hex encoded.
+    tail_len: u64,
+    tail_hash: String,
+}
+
+fn checkpoint_mismatch(detail: String) -> Error {
+    Error::CheckpointMismatch { detail }
+}
+
+impl Client {
+    /// Writes the history of `context_id` as it stands at the call, oldest
+    /// turn first, to `writer` as JSON Lines (see the [module docs](self)),
+    /// and returns the number of turns written. Compacted turns are
+    /// included; expired turns are not.
+    ///
+    /// Only one page of payloads is held at a time, so contexts of any
+    /// length export in bounded memory.
+    pub fn export_jsonl(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        mut writer: impl Write,
+    ) -> Result<u64> {
+        let head = self.get_head(ctx, context_id)?;
+        let written = self.visit_history(ctx, &head, |turn| write_turn(&mut writer, &turn))?;
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Like [`Client::export_jsonl`], writing to the file at `path` and
+    /// recording progress in `checkpoint` so an interrupted export can pick
+    /// up where it stopped. Returns the number of turns in the finished
+    /// file.
+    ///
+    /// Without a checkpoint file the export starts over, truncating `path`.
+    /// With one, it checks that `path` still holds what the checkpoint
+    /// recorded (its length and a hash of the last line written), cuts off
+    /// anything written after the checkpoint, such as a partial line, and
+    /// continues from the next turn. A mismatch fails with
+    /// [`Error::CheckpointMismatch`]; delete both files to start over.
+    ///
+    /// A resumed export ends at the head the first attempt started from, so
+    /// the finished file is byte for byte what an uninterrupted export at
+    /// that time would have written, unless turns it had yet to write were
+    /// pruned or expired in between. The checkpoint file is removed once
+    /// the export completes.
+    pub fn export_jsonl_resumable(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        path: impl AsRef<Path>,
+        checkpoint: &ExportCheckpoint,
+    ) -> Result<u64> {
+        let (file, state) = self.open_export(ctx, context_id, path.as_ref(), checkpoint)?;
+        self.resume_export(ctx, &file, BufWriter::new(&file), state, checkpoint)
+    }
+
+    /// Opens the output of a resumable export, validated against and
+    /// truncated to `checkpoint`, or fresh with a new checkpoint.
+    fn open_export(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        path: &Path,
+        checkpoint: &ExportCheckpoint,
+    ) -> Result<(File, CheckpointState)> {
+        let Some(state) = checkpoint.load()? else {
+            let file = File::create(path)?;
+            let head = self.get_head(ctx, context_id)?;
+            let state = CheckpointState {
+                context_id,
+                head_turn_id: head.head_turn_id,
+                head_depth: head.head_depth,
+                turn_id: TurnId::default(),
+                offset: 0,
+                written: 0,
+                tail_len: 0,
+                tail_hash: blake3::hash(b"").to_hex().to_string(),
+            };
+            checkpoint.save(&state)?;
+            return Ok((file, state));
+        };
+
+        if state.context_id != context_id {
+            return Err(checkpoint_mismatch(format!(
+                "checkpoint is for context {}, not {context_id}",
+                state.context_id
+            )));
+        }
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len();
+        if len < state.offset || state.tail_len > state.offset {
+            return Err(checkpoint_mismatch(format!(
+                "output is {len} bytes, checkpoint expects {}",
+                state.offset
+            )));
+        }
+        let mut tail = vec![0; state.tail_len as usize];
+        file.seek(SeekFrom::Start(state.offset - state.tail_len))?;
+        file.read_exact(&mut tail)?;
+        if blake3::hash(&tail).to_hex().as_str() != state.tail_hash {
+            return Err(checkpoint_mismatch(format!(
+                "output before offset {} differs from the checkpoint",
+                state.offset
+            )));
+        }
+        file.set_len(state.offset)?;
+        file.seek(SeekFrom::Start(state.offset))?;
+        Ok((file, state))
+    }
+
+    /// Writes the turns after `state` through `writer` (which writes to
+    /// `file`), checkpointing every `checkpoint.every` turns.
+    fn resume_export(
+        &self,
+        ctx: &RequestContext,
+        file: &File,
+        mut writer: impl Write,
+        mut state: CheckpointState,
+        checkpoint: &ExportCheckpoint,
+    ) -> Result<u64> {
+        let head = ContextHead {
+            context_id: state.context_id,
+            head_turn_id: state.head_turn_id,
+            head_depth: state.head_depth,
+        };
+        let mut line = Vec::new();
+        let mut unsaved = 0;
+        self.visit_history_after(ctx, &head, state.turn_id, |turn| {
+            line.clear();
+            write_turn(&mut line, &turn)?;
+            writer.write_all(&line)?;
+            state.turn_id = turn.turn_id;
+            state.offset += line.len() as u64;
+            state.written += 1;
+            state.tail_len = line.len() as u64;
+            state.tail_hash = blake3::hash(&line).to_hex().to_string();
+            unsaved += 1;
+            if unsaved >= checkpoint.every {
+                writer.flush()?;
+                file.sync_data()?;
+                checkpoint.save(&state)?;
+                unsaved = 0;
+            }
+            Ok(())
+        })?;
+        writer.flush()?;
+        file.sync_data()?;
+        fs::remove_file(&checkpoint.path)?;
+        Ok(state.written)
+    }
+
+    /// Calls `visit` on each turn of `head`'s history, oldest first,
+    /// fetching payloads a page at a time, and returns the number visited.
+    /// Compacted turns are included; expired turns are not.
+    pub(crate) fn visit_history(
+        &self,
+        ctx: &RequestContext,
+        head: &ContextHead,
+        visit: impl FnMut(TurnRecord) -> Result<()>,
+    ) -> Result<u64> {
+        self.visit_history_after(ctx, head, TurnId::default(), visit)
+    }
+
+    /// Like [`Client::visit_history`], skipping turns up to `after_turn_id`
+    /// without fetching their payloads.
+    fn visit_history_after(
+        &self,
+        ctx: &RequestContext,
+        head: &ContextHead,
+        after_turn_id: TurnId,
+        mut visit: impl FnMut(TurnRecord) -> Result<()>,
+    ) -> Result<u64> {
+        if head.head_turn_id <= after_turn_id {
+            return Ok(0);
+        }
+        let context_id = head.context_id;
+
+        // Pages come newest first, so find every page's upper bound from
+        // metadata alone, then fetch payloads from the oldest page up. Page
+        // `i` holds the turns from `bounds[i + 1]` (0 for the last) up to
+        // `bounds[i]`. Turn ids grow along a history, so paging stops at the
+        // first page reaching back to `after_turn_id`.
+        let mut bounds = vec![TurnId::new(head.head_turn_id.get() + 1)];
+        loop {
+            let page =
+                self.get_last_stored(ctx, context_id, export_page(bounds[bounds.len() - 1]))?;
+            if page.len() < EXPORT_PAGE_SIZE as usize || page[0].turn_id <= after_turn_id {
+                break;
+            }
+            bounds.push(page[0].turn_id);
+        }
+
+        let mut visited = 0;
+        for (i, &upper) in bounds.iter().enumerate().rev() {
+            let lower = bounds.get(i + 1).copied().unwrap_or_default();
+            let opts = GetLastOptions {
+                max_payload_bytes: None,
+                ..export_page(upper)
+            };
+            let mut page = self.get_last_stored(ctx, context_id, opts)?;
+            self.materialize_deltas(ctx, &mut page)?;
+            for turn in page {
+                if turn.turn_id < lower || turn.turn_id <= after_turn_id {
+                    continue;
+                }
+                visit(turn)?;
+                visited += 1;
+            }
+        }
+        Ok(visited)
+    }
+
+    /// Replays a JSON Lines export into a new context, in file order, and
+    /// returns the new context's head. Blank lines are skipped.
+    ///
+    /// Turn ids are assigned afresh; encodings and payloads are kept, and
+    /// types are kept unless `opts` overrides them. A malformed line fails
+    /// with [`Error::Decode`] naming the line, a payload that does not match
+    /// its `content_hash` with [`Error::HashMismatch`]; either way the turns
+    /// before it stay appended. Redacted turns are appended empty and
+    /// redacted again.
+    ///
+    /// Replaying a redacted turn needs a server that supports redaction;
+    /// others fail with [`Error::Unsupported`].
+    pub fn import_jsonl(
+        &self,
+        ctx: &RequestContext,
+        reader: impl Read,
+        opts: ImportOptions,
+    ) -> Result<ContextHead> {
+        let mut head = self.create_context(ctx, CreateContextOptions::default())?;
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (req, redaction) = import_line(head.context_id, index + 1, &line, &opts)?;
+            let result = self.append_turn(ctx, &req)?;
+            if let Some(reason) = redaction {
+                let opts = RedactOptions::default().reason(reason);
+                self.redact_turn(ctx, head.context_id, result.turn_id, opts)?;
+            }
+            head.head_turn_id = result.turn_id;
+            head.head_depth = result.depth;
+        }
+        Ok(head)
+    }
+}
+
+/// Builds the append for line `line_number` of an import, with the reason
+/// to redact the appended turn with if the line is a redacted turn.
+fn import_line(
+    context_id: ContextId,
+    line_number: usize,
+    line: &str,
+    opts: &ImportOptions,
+) -> Result<(AppendRequest, Option<String>)> {
+    let malformed = |detail: String| Error::decode(format!("line {line_number}: {detail}"));
+    let turn: ImportedTurn =
+        serde_json::from_str(line).map_err(|err| malformed(err.to_string()))?;
+    let (payload, encoding) = match (turn.payload_base64, turn.payload) {
+        (Some(encoded), None) => {
+            let payload = BASE64
+                .decode(encoded)
+                .map_err(|err| malformed(format!("payload_base64: {err}")))?;
+            (payload, turn.encoding.unwrap_or(ENCODING_MSGPACK))
+        }
+        (None, Some(value)) => (encode_msgpack(&value)?, ENCODING_MSGPACK),
+        _ => {
+            return Err(malformed(
+                "expected exactly one of payload_base64 and payload".into(),
+            ))
+        }
+    };
+    // A redacted turn's hash names the payload that was erased.
+    let verify = opts.verify_hashes && !turn.redacted;
+    if let Some(expected) = turn.content_hash.filter(|_| verify) {
+        let actual = blake3::hash(&payload).to_hex().to_string();
+        if actual != expected {
+            return Err(Error::HashMismatch {
+                line: line_number,
+                expected,
+                actual,
+            });
+        }
+    }
+    let (type_id, type_version) = match (&opts.type_override, turn.type_id, turn.type_version) {
+        (Some((type_id, type_version)), _, _) => (type_id.clone(), *type_version),
+        (None, Some(type_id), Some(type_version)) => (type_id, type_version),
+        (None, _, _) => return Err(malformed("missing type_id or type_version".into())),
+    };
+    let redaction = turn
+        .redacted
+        .then(|| turn.redaction_reason.unwrap_or_default());
+    let req = AppendRequest::new(context_id, type_id, type_version, payload).encoding(encoding);
+    Ok((req, redaction))
+}
+
+/// The page of history below `before_turn_id`, payloads withheld.
+fn export_page(before_turn_id: TurnId) -> GetLastOptions {
+    GetLastOptions {
+        limit: EXPORT_PAGE_SIZE,
+        include_payload: true,
+        before_turn_id: Some(before_turn_id),
+        ..Default::default()
+    }
+    .max_payload_bytes(0)
+    .include_compacted(true)
+}
+
+fn write_turn(writer: &mut impl Write, turn: &TurnRecord) -> Result<()> {
+    let line = JsonlTurn {
+        turn_id: turn.turn_id,
+        depth: turn.depth,
+        type_id: turn.type_id.to_string(),
+        type_version: turn.type_version,
+        encoding: turn.encoding,
+        content_hash: blake3::Hash::from_bytes(turn.payload_hash)
+            .to_hex()
+            .to_string(),
+        created_at_unix_ms: turn.created_at_unix_ms,
+        redacted: turn.redacted,
+        redacted_at_unix_ms: turn.redacted_at_unix_ms,
+        redaction_reason: turn.redaction_reason.clone(),
+        payload_base64: BASE64.encode(&turn.payload),
+    };
+    serde_json::to_writer(&mut *writer, &line).map_err(Error::encode_err)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_CTX_CREATE, MSG_GET_HEAD, MSG_GET_LAST};
+    use crate::test_util::{spawn_multi_server, spawn_scripted_server, turn_page_payload};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn context_head(context_id: u64, head_turn_id: u64) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&head_turn_id.to_le_bytes());
+        out.extend_from_slice(&(head_turn_id as u32).to_le_bytes());
+        out
+    }
+
+    /// Serves context 4, whose history is turns 1..=300 with payload
+    /// [id % 256]; turn 301 lands after an export has read the head. Counts
+    /// GET_LAST requests in `reads`.
+    fn spawn_history_server(reads: Arc<AtomicUsize>) -> String {
+        spawn_multi_server(move |req| {
+            if req.header.msg_type == MSG_GET_HEAD {
+                return (MSG_GET_HEAD, context_head(4, 300));
+            }
+            reads.fetch_add(1, Ordering::SeqCst);
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let before = u64::from_le_bytes(req.payload[36..44].try_into().unwrap()).min(302);
+            let first = before.saturating_sub(limit).max(1);
+            let payloads: Vec<[u8; 1]> = (first..before).map(|id| [id as u8]).collect();
+            let payloads: Vec<&[u8]> = payloads.iter().map(|p| &p[..]).collect();
+            (MSG_GET_LAST, turn_page_payload(first, &payloads))
+        })
+    }
+
+    #[test]
+    fn export_writes_history_oldest_first_page_by_page() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let addr = spawn_history_server(reads.clone());
+        let client = dial(&addr, []).unwrap();
+
+        let mut out = Vec::new();
+        let written = client
+            .export_jsonl(&RequestContext::background(), ContextId::new(4), &mut out)
+            .unwrap();
+        assert_eq!(written, 300);
+        // Two metadata pages, then two payload pages.
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+
+        let lines: Vec<JsonlTurn> = std::str::from_utf8(&out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let ids: Vec<u64> = lines.iter().map(|line| line.turn_id.get()).collect();
+        assert_eq!(ids, (1..=300).collect::<Vec<_>>());
+        let last = &lines[299];
+        assert_eq!(BASE64.decode(&last.payload_base64).unwrap(), [300u64 as u8]);
+        assert_eq!(
+            last.content_hash,
+            blake3::hash(&[300u64 as u8]).to_hex().as_str()
+        );
+        assert_eq!((last.type_id.as_str(), last.depth), ("test", 300));
+        // The test server reports no timestamps, so none are written.
+        assert_eq!(last.created_at_unix_ms, None);
+        assert!(!std::str::from_utf8(&out).unwrap().contains("created_at"));
+    }
+
+    /// Writes through to `inner` until `budget` bytes have gone, then
+    /// fails, leaving a partial write behind like a dropped disk or mount.
+    struct FailAfter<W> {
+        inner: W,
+        budget: usize,
+    }
+
+    impl<W: Write> Write for FailAfter<W> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.budget == 0 {
+                return Err(std::io::Error::other("injected failure"));
+            }
+            let n = self.inner.write(&buf[..buf.len().min(self.budget)])?;
+            self.budget -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    #[test]
+    fn interrupted_export_resumes_to_an_identical_file() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let addr = spawn_history_server(reads.clone());
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let mut expected = Vec::new();
+        client
+            .export_jsonl(&ctx, ContextId::new(4), &mut expected)
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("export.jsonl");
+        let checkpoint = ExportCheckpoint::new(dir.path().join("export.ckpt")).every(50);
+
+        // Fail partway through a line around turn 280: turns 1..=250 are
+        // checkpointed and the bytes written after them are not.
+        let line_len = expected.len() / 300;
+        let (file, state) = client
+            .open_export(&ctx, ContextId::new(4), &out, &checkpoint)
+            .unwrap();
+        let writer = FailAfter {
+            inner: &file,
+            budget: line_len * 280 - 3,
+        };
+        let err = client
+            .resume_export(&ctx, &file, writer, state, &checkpoint)
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(_)), "got {err:?}");
+        drop(file);
+        assert!(fs::metadata(&out).unwrap().len() > (line_len * 250) as u64);
+        assert_eq!(
+            checkpoint.load().unwrap().unwrap().turn_id,
+            TurnId::new(250)
+        );
+
+        reads.store(0, Ordering::SeqCst);
+        let written = client
+            .export_jsonl_resumable(&ctx, ContextId::new(4), &out, &checkpoint)
+            .unwrap();
+        assert_eq!(written, 300);
+        assert_eq!(fs::read(&out).unwrap(), expected);
+        assert!(!checkpoint.path.exists());
+        // One metadata page reaches back past turn 250; one payload page.
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn resume_refuses_output_that_does_not_match_the_checkpoint() {
+        let addr = spawn_history_server(Arc::new(AtomicUsize::new(0)));
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("export.jsonl");
+        let checkpoint = ExportCheckpoint::new(dir.path().join("export.ckpt")).every(10);
+
+        let (file, state) = client
+            .open_export(&ctx, ContextId::new(4), &out, &checkpoint)
+            .unwrap();
+        let writer = FailAfter {
+            inner: &file,
+            budget: 2000,
+        };
+        client
+            .resume_export(&ctx, &file, writer, state, &checkpoint)
+            .unwrap_err();
+        drop(file);
+
+        let err = client
+            .export_jsonl_resumable(&ctx, ContextId::new(5), &out, &checkpoint)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::CheckpointMismatch { .. }),
+            "got {err:?}"
+        );
+
+        let offset = checkpoint.load().unwrap().unwrap().offset as usize;
+        let mut data = fs::read(&out).unwrap();
+        data[offset - 2] ^= 1;
+        fs::write(&out, &data).unwrap();
+        let err = client
+            .export_jsonl_resumable(&ctx, ContextId::new(4), &out, &checkpoint)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::CheckpointMismatch { .. }),
+            "got {err:?}"
+        );
+
+        fs::write(&out, &data[..offset - 1]).unwrap();
+        let err = client
+            .export_jsonl_resumable(&ctx, ContextId::new(4), &out, &checkpoint)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::CheckpointMismatch { .. }),
+            "got {err:?}"
+        );
+    }
+
+    #[test]
+    fn import_rejects_payloads_that_do_not_match_their_hash() {
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_CTX_CREATE, context_head(9, 0))]);
+        let client = dial(&addr, []).unwrap();
+        let line = serde_json::to_string(&JsonlTurn {
+            turn_id: TurnId::new(1),
+            depth: 0,
+            type_id: "test".into(),
+            type_version: 1,
+            encoding: 1,
+            content_hash: blake3::hash(b"\x90").to_hex().to_string(),
+            created_at_unix_ms: Some(1_700_000_000_000),
+            redacted: false,
+            redacted_at_unix_ms: None,
+            redaction_reason: None,
+            payload_base64: BASE64.encode(b"\x91\x01"),
+        })
+        .unwrap();
+
+        let input = format!("\n{line}\n");
+        let err = client
+            .import_jsonl(
+                &RequestContext::background(),
+                input.as_bytes(),
+                ImportOptions::default(),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::HashMismatch { line: 2, actual, .. }
+                if *actual == blake3::hash(b"\x91\x01").to_hex().as_str()),
+            "got {err:?}"
+        );
+        // Nothing was appended.
+        assert_eq!(handle.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn import_encodes_json_payloads_and_overrides_types() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, context_head(9, 0)),
+            (MSG_APPEND_TURN, append_ack(9, 1)),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let input =
+            r#"{"type_id":"old","type_version":1,"content_hash":"00","payload":{"text":"hi"}}"#;
+
+        let opts = ImportOptions::default()
+            .type_override("fixture.Note", 2)
+            .verify_hashes(false);
+        let head = client
+            .import_jsonl(&RequestContext::background(), input.as_bytes(), opts)
+            .unwrap();
+        assert_eq!((head.context_id.get(), head.head_turn_id.get()), (9, 1));
+
+        let requests = handle.join().unwrap();
+        let append = &requests[1].payload;
+        let expected = encode_msgpack(&serde_json::json!({"text": "hi"})).unwrap();
+        assert!(contains(append, &expected));
+        assert!(contains(append, b"fixture.Note"));
+    }
+
+    #[test]
+    fn import_redacts_turns_that_were_redacted() {
+        use crate::protocol::MSG_TURN_REDACT;
+
+        let mut redaction = 1u64.to_le_bytes().to_vec();
+        redaction.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        redaction.extend_from_slice(&3u32.to_le_bytes());
+        redaction.extend_from_slice(b"pii");
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, context_head(9, 0)),
+            (MSG_APPEND_TURN, append_ack(9, 1)),
+            (MSG_TURN_REDACT, redaction),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        // The hash names the erased payload, so it is not checked.
+        let line = serde_json::to_string(&JsonlTurn {
+            turn_id: TurnId::new(4),
+            depth: 0,
+            type_id: "test".into(),
+            type_version: 1,
+            encoding: 1,
+            content_hash: blake3::hash(b"\x91\x01").to_hex().to_string(),
+            created_at_unix_ms: None,
+            redacted: true,
+            redacted_at_unix_ms: Some(1_600_000_000_000),
+            redaction_reason: Some("pii".into()),
+            payload_base64: String::new(),
+        })
+        .unwrap();
+        assert!(line.contains(r#""redacted":true"#));
+
+        let head = client
+            .import_jsonl(
+                &RequestContext::background(),
+                line.as_bytes(),
+                ImportOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(head.head_turn_id, TurnId::new(1));
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[2].header.msg_type, MSG_TURN_REDACT);
+        assert_eq!(
+            requests[2].payload[..16],
+            [9u64.to_le_bytes(), 1u64.to_le_bytes()].concat()
+        );
+        assert!(requests[2].payload.ends_with(b"pii"));
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    fn append_ack(context_id: u64, turn_id: u64) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&turn_id.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&[0u8; 32]);
+        out
+    }
+}
@@ -5,39 +5,224 @@
 //!
 //! Exposes a synchronous TCP/TLS client, reconnecting wrapper, fstree snapshots,
 //! and canonical conversation types plus msgpack helpers.
+//!
+//! On wasm32 the blocking client is not built. The types, msgpack helpers and
+//! [`async_client::AsyncClient`] are, the latter over a WebSocket with the
+//! `websocket` feature.
+
+// The wire helpers the blocking client shares with the async client are
+// partly unused where only the latter is built.
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ancestry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod append_queue;
+pub mod archive;
+#[cfg(any(not(target_arch = "wasm32"), feature = "websocket"))]
+pub mod async_client;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod builder;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cache;
+pub mod capabilities;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod client;
+#[cfg(all(feature = "arrow", not(target_arch = "wasm32")))]
+pub mod columnar;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compression;
 pub mod context;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod credentials;
+pub mod delete;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod delta;
 pub mod encoding;
+pub mod encryption;
 pub mod error;
 pub mod fs;
+pub mod hash;
+pub mod histogram;
+pub mod ids;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod interceptor;
+pub mod interop;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod iter;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod jsonl;
+pub mod links;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod namespace;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod outbox;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pinning;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pool;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod prefetch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proto;
 pub mod protocol;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proxy;
+pub mod prune;
+pub mod quota;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ratelimit;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod reconnect;
+pub mod redact;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod replay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod response_validation;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod search;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod snapshot;
+pub mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod subscribe;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod telemetry;
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
+pub mod testing;
+pub mod text_search;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timing;
+pub mod transaction;
+pub mod transport;
 pub mod turn;
+pub mod type_id;
+pub mod typed;
+pub mod validate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watch;
+#[cfg(all(target_arch = "wasm32", feature = "websocket"))]
+pub mod websocket;
 
 pub mod fstree;
 pub mod types;
 
+#[cfg(all(feature = "http-transport", not(target_arch = "wasm32")))]
+mod http_tunnel;
 #[cfg(test)]
 mod test_util;
+#[cfg(not(target_arch = "wasm32"))]
+mod trace;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::ancestry::GetPathOptions;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::append_queue::{
+    with_append_queue, AppendCallback, AppendQueueOptions, QueueFullPolicy,
+};
+pub use crate::archive::{ArchiveFilter, ContextSummary, ListContextsOptions};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::builder::ClientBuilder;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::cache::{with_turn_cache, CacheConfig};
+pub use crate::capabilities::Capabilities;
+#[cfg(not(target_arch = "wasm32"))]
 pub use crate::client::{
-    dial, dial_tls, with_client_tag, with_dial_timeout, with_request_timeout, Client, ClientOption,
-    RequestContext,
+    dial, dial_any, dial_tls, with_bearer_token, with_client_tag, with_dial_timeout,
+    with_frame_checksums, with_happy_eyeballs_delay, with_max_decode_depth, with_max_frame_size,
+    with_prefetch_staleness, with_read_buffer_bytes, with_read_only, with_request_timeout,
+    with_write_buffer_bytes, Client, ClientOption, RequestContext, AUTH_KEY, NAMESPACE_KEY,
+    REQUEST_ID_KEY,
 };
-pub use crate::context::ContextHead;
-pub use crate::encoding::{decode_msgpack, decode_msgpack_into, encode_msgpack};
-pub use crate::error::{is_server_error, Error, Result, ServerError};
+#[cfg(all(feature = "arrow", not(target_arch = "wasm32")))]
+pub use crate::columnar::ArrowExportOptions;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::compression::{with_compression, Codec};
+pub use crate::context::{ContextDetails, ContextHead, ContextInfo, CreateContextOptions};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::credentials::{with_credentials, CachedCredentials, CredentialProvider};
+pub use crate::delete::DeleteFilter;
+#[cfg(feature = "cbor")]
+pub use crate::encoding::{decode_cbor, decode_cbor_with_max_depth, encode_cbor};
+pub use crate::encoding::{
+    decode_msgpack, decode_msgpack_into, decode_msgpack_into_with_max_depth,
+    decode_msgpack_with_max_depth, encode_msgpack,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::encryption::with_payload_encryption;
+pub use crate::encryption::{KeyProvider, PayloadKey};
+pub use crate::error::{is_server_error, Error, ProxyFailure, Result, ServerErrorCode};
 pub use crate::fs::{AttachFsRequest, AttachFsResult, PutBlobRequest, PutBlobResult};
+pub use crate::hash::{hash_from_hex, hash_to_hex};
+pub use crate::histogram::TypeHistogramOptions;
+pub use crate::ids::{ContextId, TurnId};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::interceptor::{with_interceptor, Interceptor, RequestEnvelope, ResponseEnvelope};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::iter::{IterOptions, TurnIter};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::jsonl::{ExportCheckpoint, ImportOptions};
+pub use crate::links::{LinkDirection, LinkKind, TurnLink};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::metrics::{with_metrics, Metrics};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::namespace::{with_namespace, ScopedContextId};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::outbox::{OutboxAck, OutboxClient, OutboxOptions, ProvisionalAppend};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::pinning::with_pinned_cert;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::pool::{dial_pool, dial_tls_pool, ClientPool};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::proxy::{with_proxy, with_proxy_from_env, Proxy, ProxyAuth};
+pub use crate::prune::PruneResult;
+pub use crate::quota::QuotaInfo;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::ratelimit::{with_rate_limit, with_read_rate_limit, RateLimit};
+#[cfg(not(target_arch = "wasm32"))]
 pub use crate::reconnect::{
-    dial_reconnecting, dial_tls_reconnecting, DialFunc, ReconnectOption, ReconnectingClient,
+    dial_reconnecting, dial_tls_reconnecting, with_on_reconnect_event, with_on_retry,
+    with_retry_policy, DialFunc, ReconnectEvent, ReconnectOption, ReconnectingClient, RetryEvent,
+    RetryPolicy,
+};
+pub use crate::redact::{RedactOptions, Redaction};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::response_validation::{with_response_validation, ResponseValidation};
+#[cfg(feature = "schema")]
+pub use crate::schema::SchemaFile;
+pub use crate::search::{SearchHit, SearchQuery};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::snapshot::{Snapshot, SnapshotTurn};
+pub use crate::stats::ContextStats;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::subscribe::{OverflowPolicy, SubscribeOptions, Subscription, SubscriptionItem};
+pub use crate::text_search::{TextHit, TextQuery};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::timing::CallTiming;
+pub use crate::transaction::AppendMultiOptions;
+#[cfg(feature = "bytes")]
+pub use crate::turn::SharedTurnRecord;
+pub use crate::turn::{
+    AppendRequest, AppendRequestBuilder, AppendResult, CompactRequest, ConsistencyToken,
+    GetLastOptions, GetTurnOptions, LazyTurn, Order, Turn, TurnFields, TurnHeader, TurnMeta,
+    TurnPage, TurnRecord,
 };
-pub use crate::turn::{AppendRequest, AppendResult, GetLastOptions, TurnRecord};
+pub use crate::type_id::TypeId;
+pub use crate::typed::{CxdbType, RegisteredType, TypeRegistry};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::validate::with_validator;
+pub use crate::validate::{MsgpackWellFormed, TurnValidator, TypeValidator, ValidationError};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::watch::{ContextChanged, WatchOptions, WatchStream};
 
 // Re-export shared constants for parity with Go names.
 #[allow(non_upper_case_globals)]
 pub const EncodingMsgpack: u32 = protocol::ENCODING_MSGPACK;
 #[allow(non_upper_case_globals)]
+pub const EncodingCbor: u32 = protocol::ENCODING_CBOR;
+#[allow(non_upper_case_globals)]
 pub const CompressionNone: u32 = protocol::COMPRESSION_NONE;
 #[allow(non_upper_case_globals)]
 pub const CompressionZstd: u32 = protocol::COMPRESSION_ZSTD;
@@ -47,16 +232,21 @@ pub const DefaultDialTimeout: std::time::Duration = protocol::DEFAULT_DIAL_TIMEO
 #[allow(non_upper_case_globals)]
 pub const DefaultRequestTimeout: std::time::Duration = protocol::DEFAULT_REQUEST_TIMEOUT;
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_upper_case_globals)]
 pub const DefaultMaxRetries: usize = reconnect::DEFAULT_MAX_RETRIES;
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_upper_case_globals)]
 pub const DefaultRetryDelay: std::time::Duration = reconnect::DEFAULT_RETRY_DELAY;
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_upper_case_globals)]
 pub const DefaultMaxRetryDelay: std::time::Duration = reconnect::DEFAULT_MAX_RETRY_DELAY;
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_upper_case_globals)]
 pub const DefaultQueueSize: usize = reconnect::DEFAULT_QUEUE_SIZE;
 
 /// Go-parity alias for client options.
+#[cfg(not(target_arch = "wasm32"))]
 pub type Option = ClientOption;
 
 #[allow(non_snake_case)]
@@ -64,31 +254,37 @@ pub fn IsServerError(err: &Error, code: u32) -> bool {
     is_server_error(err, code)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn WithDialTimeout(timeout: std::time::Duration) -> ClientOption {
     with_dial_timeout(timeout)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn WithRequestTimeout(timeout: std::time::Duration) -> ClientOption {
     with_request_timeout(timeout)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn WithClientTag(tag: impl Into<String>) -> ClientOption {
     with_client_tag(tag)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn Dial(addr: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
     dial(addr, opts)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn DialTLS(addr: &str, opts: impl IntoIterator<Item = ClientOption>) -> Result<Client> {
     dial_tls(addr, opts)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn DialReconnecting(
     addr: &str,
@@ -98,6 +294,7 @@ pub fn DialReconnecting(
     dial_reconnecting(addr, reconnect_opts, opts)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[allow(non_snake_case)]
 pub fn DialTLSReconnecting(
     addr: &str,
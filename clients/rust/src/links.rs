@@ -0,0 +1,335 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structured links between turns.
+//!
+//! A turn's parent says where it sits in history; a link says what it is
+//! about. [`AppendRequest::link_to`] records that a turn replies to, or was
+//! caused by, an earlier turn (or relates to it in an application-defined
+//! way), and reads return the links in [`TurnRecord::links`].
+//! [`Client::get_linked_turns`] follows them from a turn in either
+//! direction, so a tool result can be found from its call without scanning
+//! the context.
+//!
+//! Links point back along the new turn's own history: the server rejects
+//! an append linking to a turn that does not exist, or that exists only on
+//! another branch, with [`Error::TurnNotFound`]. A fork can link to turns it
+//! shares with its base context, but not to turns appended there after the
+//! fork point.
+//!
+//! ```no_run
+//! use cxdb::links::{LinkDirection, LinkKind};
+//! use cxdb::{dial, AppendRequest, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", Vec::new())?;
+//! let ctx = RequestContext::background();
+//! let context_id = ContextId::new(1);
+//! # let (call_payload, result_payload) = (vec![0x80], vec![0x80]);
+//! let call = client.append_turn(&ctx, &AppendRequest::new(context_id, "app.ToolCall", 1, call_payload))?;
+//! let result = AppendRequest::new(context_id, "app.ToolResult", 1, result_payload)
+//!     .link_to(call.turn_id, LinkKind::Causes);
+//! client.append_turn(&ctx, &result)?;
+//!
+//! for turn in client.get_linked_turns(&ctx, context_id, call.turn_id, LinkDirection::Incoming)? {
+//!     println!("turn {} links to the call", turn.turn_id);
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`AppendRequest::link_to`]: crate::AppendRequest::link_to
+//! [`TurnRecord::links`]: crate::TurnRecord::links
+//! [`Error::TurnNotFound`]: crate::Error::TurnNotFound
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::{FLAG_LINKS, MSG_GET_LINKED};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::turn::{parse_turn_records, AppendRequest, TurnRecord};
+
+const KIND_REPLIES_TO: u8 = 1;
+const KIND_CAUSES: u8 = 2;
+const KIND_CUSTOM: u8 = 3;
+
+/// How a turn relates to the turn it links to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// The turn answers the target.
+    RepliesTo,
+    /// The target caused the turn, as a tool call causes its result.
+    Causes,
+    /// An application-defined relation: non-empty UTF-8 of at most 256
+    /// bytes.
+    Custom(String),
+}
+
+impl LinkKind {
+    fn tag(&self) -> u8 {
+        match self {
+            LinkKind::RepliesTo => KIND_REPLIES_TO,
+            LinkKind::Causes => KIND_CAUSES,
+            LinkKind::Custom(_) => KIND_CUSTOM,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            LinkKind::Custom(name) => name,
+            _ => "",
+        }
+    }
+}
+
+/// A link from a turn to an earlier turn in its history.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TurnLink {
+    pub target_turn_id: TurnId,
+    pub kind: LinkKind,
+}
+
+/// Which way [`Client::get_linked_turns`] follows links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkDirection {
+    /// The turns the turn links to.
+    Outgoing,
+    /// The turns linking to the turn.
+    Incoming,
+}
+
+/// Appends `links` as APPEND_TURN encodes them: a count, then per link the
+/// target, the kind's tag and the custom kind's name.
+pub(crate) fn encode_links(payload: &mut Vec<u8>, links: &[TurnLink]) -> Result<()> {
+    payload.write_u32::<LittleEndian>(links.len() as u32)?;
+    for link in links {
+        payload.write_u64::<LittleEndian>(link.target_turn_id.get())?;
+        payload.push(link.kind.tag());
+        payload.write_u32::<LittleEndian>(link.kind.name().len() as u32)?;
+        payload.extend_from_slice(link.kind.name().as_bytes());
+    }
+    Ok(())
+}
+
+/// Reads links written by [`encode_links`].
+pub(crate) fn read_links(reader: &mut PayloadReader<'_>) -> Result<Vec<TurnLink>> {
+    let count = reader.u32("link_count")? as usize;
+    // Each link is at least 13 bytes.
+    let mut links = Vec::with_capacity(count.min(reader.remaining() / 13));
+    for _ in 0..count {
+        let target_turn_id = TurnId::new(reader.u64("target_turn_id")?);
+        let tag = reader.u8("link kind")?;
+        let name = std::str::from_utf8(reader.len_prefixed("link kind name")?)
+            .map_err(|_| Error::protocol("link kind not utf8"))?;
+        let kind = match tag {
+            KIND_REPLIES_TO => LinkKind::RepliesTo,
+            KIND_CAUSES => LinkKind::Causes,
+            KIND_CUSTOM => LinkKind::Custom(name.to_string()),
+            other => return Err(Error::protocol(format!("unknown link kind {other}"))),
+        };
+        links.push(TurnLink {
+            target_turn_id,
+            kind,
+        });
+    }
+    Ok(links)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Returns the turns `turn_id` links to ([`LinkDirection::Outgoing`])
+    /// or the turns in `context_id`'s history that link to it
+    /// ([`LinkDirection::Incoming`]), oldest first, with their payloads.
+    /// Links from other branches, such as forks of `context_id`, are left
+    /// out of the incoming direction. Compacted and expired turns are
+    /// included; expired ones are marked [`TurnRecord::expired`].
+    ///
+    /// Returns [`Error::TurnNotFound`] if `turn_id` does not exist and
+    /// [`Error::Unsupported`] against servers without GET_LINKED.
+    pub fn get_linked_turns(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        turn_id: TurnId,
+        direction: LinkDirection,
+    ) -> Result<Vec<TurnRecord>> {
+        let mut payload = Vec::with_capacity(24);
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        payload.write_u64::<LittleEndian>(turn_id.get())?;
+        payload.write_u32::<LittleEndian>(match direction {
+            LinkDirection::Outgoing => 0,
+            LinkDirection::Incoming => 1,
+        })?;
+        payload.write_u32::<LittleEndian>(1)?;
+        let frame = self
+            .send_request(ctx, MSG_GET_LINKED, &payload)
+            .map_err(|err| err.resolve_unsupported("GET_LINKED").resolve_not_found())?;
+        let mut turns = parse_turn_records(&frame.payload)?;
+        self.open_payloads(ctx, &mut turns)?;
+        Ok(turns)
+    }
+
+    /// Fails with [`Error::Unsupported`] if `req` carries links and the
+    /// server did not offer them at handshake; an older server would drop
+    /// them without a word.
+    pub(crate) fn check_links_supported(&self, req: &AppendRequest) -> Result<()> {
+        if req.links.is_empty() || self.capabilities().has_flag(FLAG_LINKS) {
+            return Ok(());
+        }
+        Err(Error::Unsupported("turn links".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{
+        read_frame, write_frame, Frame, APPEND_FLAG_LINKS, MSG_APPEND_TURN, MSG_ERROR, MSG_HELLO,
+    };
+    use crate::test_util::{linked_turns_payload, not_found_payload, spawn_scripted_server};
+
+    #[test]
+    fn links_round_trip_through_the_wire_encoding() {
+        let links = vec![
+            TurnLink {
+                target_turn_id: TurnId::new(7),
+                kind: LinkKind::RepliesTo,
+            },
+            TurnLink {
+                target_turn_id: TurnId::new(3),
+                kind: LinkKind::Custom("cites".into()),
+            },
+        ];
+        let mut payload = Vec::new();
+        encode_links(&mut payload, &links).unwrap();
+        let mut reader = PayloadReader::new(&payload, "links");
+        assert_eq!(read_links(&mut reader).unwrap(), links);
+        assert_eq!(reader.remaining(), 0);
+
+        payload[12] = 9;
+        let err = read_links(&mut PayloadReader::new(&payload, "links")).unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)), "{err:?}");
+    }
+
+    /// A server that echoes [`FLAG_LINKS`] and answers every request with
+    /// `reply`, handing the requests it got back on join.
+    fn linking_server(reply: (u16, Vec<u8>)) -> (String, std::thread::JoinHandle<Vec<Frame>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut requests = Vec::new();
+            while let Ok(req) = read_frame(&mut stream) {
+                let (msg_type, flags, payload) = if req.header.msg_type == MSG_HELLO {
+                    assert_ne!(req.header.flags & FLAG_LINKS, 0);
+                    (MSG_HELLO, FLAG_LINKS, 1u64.to_le_bytes().to_vec())
+                } else {
+                    requests.push(req.clone());
+                    (reply.0, 0, reply.1.clone())
+                };
+                if write_frame(&mut stream, msg_type, flags, req.header.req_id, &payload).is_err() {
+                    break;
+                }
+            }
+            requests
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn appends_carry_links_to_servers_that_offer_them() {
+        let mut ack = 1u64.to_le_bytes().to_vec();
+        ack.extend_from_slice(&9u64.to_le_bytes());
+        ack.extend_from_slice(&2u32.to_le_bytes());
+        ack.extend_from_slice(&[0; 32]);
+        let (addr, handle) = linking_server((MSG_APPEND_TURN, ack));
+        let client = dial(&addr, []).unwrap();
+        let req = AppendRequest::new(ContextId::new(1), "app.ToolResult", 1, vec![0x80])
+            .link_to(TurnId::new(7), LinkKind::Causes)
+            .link_to(TurnId::new(3), LinkKind::Custom("cites".into()));
+        client
+            .append_turn(&RequestContext::background(), &req)
+            .unwrap();
+        drop(client);
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.flags, APPEND_FLAG_LINKS);
+        let mut expected = Vec::new();
+        encode_links(&mut expected, &req.links).unwrap();
+        assert!(requests[0].payload.ends_with(&expected));
+    }
+
+    #[test]
+    fn links_fail_fast_against_servers_without_them() {
+        let (addr, handle) = spawn_scripted_server(vec![]);
+        let client = dial(&addr, []).unwrap();
+        let req = AppendRequest::new(ContextId::new(1), "app.Reply", 1, vec![0x80])
+            .link_to(TurnId::new(1), LinkKind::RepliesTo);
+        let err = client
+            .append_turn(&RequestContext::background(), &req)
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+        drop(client);
+        assert!(handle.join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn linked_turns_are_read_with_their_links() {
+        // Turn 5 replies to turn 2; the links trailer follows the
+        // writers, expiries, timestamps and redactions trailers.
+        let mut page = linked_turns_payload(&[(5, 4)]);
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.extend_from_slice(&1u32.to_le_bytes());
+        page.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.extend_from_slice(&1u32.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+        let link = TurnLink {
+            target_turn_id: TurnId::new(2),
+            kind: LinkKind::RepliesTo,
+        };
+        encode_links(&mut page, std::slice::from_ref(&link)).unwrap();
+        let (addr, handle) = linking_server((MSG_GET_LINKED, page));
+        let client = dial(&addr, []).unwrap();
+        let turns = client
+            .get_linked_turns(
+                &RequestContext::background(),
+                ContextId::new(1),
+                TurnId::new(2),
+                LinkDirection::Incoming,
+            )
+            .unwrap();
+        assert_eq!(turns[0].turn_id, TurnId::new(5));
+        assert_eq!(turns[0].links, [link]);
+        assert_eq!(turns[0].payload, [5]);
+        drop(client);
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].payload[16..20], 1u32.to_le_bytes());
+    }
+
+    #[test]
+    fn missing_turns_resolve_to_turn_not_found() {
+        let (addr, _) = linking_server((MSG_ERROR, not_found_payload("turn", 42)));
+        let client = dial(&addr, []).unwrap();
+        let err = client
+            .get_linked_turns(
+                &RequestContext::background(),
+                ContextId::new(1),
+                TurnId::new(42),
+                LinkDirection::Outgoing,
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::TurnNotFound { turn_id } if turn_id.get() == 42),
+            "{err:?}"
+        );
+    }
+}
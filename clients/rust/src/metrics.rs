@@ -0,0 +1,390 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pluggable client metrics.
+//!
+//! Implement [`Metrics`] (for example on top of the `metrics` or
+//! `prometheus` crates) and install it with [`with_metrics`]. The client
+//! reports every request it sends, including the HELLO handshake and each
+//! request of a pipelined batch, and the frame bytes it writes and reads.
+//! Without a hook the client skips the timing entirely.
+//!
+//! [`InMemoryMetrics`] keeps simple totals, which is handy in tests.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use cxdb::metrics::{with_metrics, InMemoryMetrics, Operation};
+//! use cxdb::{dial, CreateContextOptions, RequestContext};
+//!
+//! let metrics = Arc::new(InMemoryMetrics::default());
+//! let client = dial("127.0.0.1:9009", [with_metrics(metrics.clone())])?;
+//! client.create_context(&RequestContext::background(), CreateContextOptions::default())?;
+//! assert_eq!(metrics.stats(Operation::CreateContext).requests, 1);
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::client::ClientOption;
+use crate::error::Error;
+use crate::protocol::{
+    MSG_APPEND_MULTI, MSG_APPEND_TURN, MSG_ATTACH_FS, MSG_CONTEXT_STATS, MSG_CTX_ARCHIVE,
+    MSG_CTX_COMPACT, MSG_CTX_CREATE, MSG_CTX_CREATE_ALIAS, MSG_CTX_CREATE_WITH_TURN,
+    MSG_CTX_DELETE_MANY, MSG_CTX_FORK, MSG_CTX_PRUNE, MSG_CTX_RESTORE, MSG_GET_BLOB,
+    MSG_GET_CHILDREN, MSG_GET_CONTEXT, MSG_GET_HEAD, MSG_GET_LAST, MSG_GET_LINKED, MSG_GET_QUOTAS,
+    MSG_GET_TURN, MSG_HELLO, MSG_LIST_CHILDREN, MSG_LIST_CONTEXTS, MSG_PUT_BLOB, MSG_RESOLVE_ALIAS,
+    MSG_SEARCH_TURNS, MSG_TEXT_SEARCH, MSG_TURN_REDACT, MSG_TYPE_HISTOGRAM,
+};
+use crate::ratelimit::{Limited, RateLimitState};
+
+/// Receives client metrics. Every method defaults to a no-op, so
+/// implementations override only what they record.
+///
+/// Hooks run on the calling thread while the request's connection is held,
+/// so they should be cheap (update a counter, not perform I/O).
+pub trait Metrics: Send + Sync {
+    /// One request finished after `duration`, measured from the call until
+    /// its response was read. Failed requests pass the error, before it is
+    /// mapped onto typed variants such as [`Error::ContextNotFound`].
+    fn on_request(&self, _op: Operation, _duration: Duration, _result: Result<(), &Error>) {}
+
+    /// `bytes` of frames (header, payload and any checksum) were written to
+    /// or read from the connection.
+    fn on_bytes(&self, _direction: Direction, _bytes: usize) {}
+
+    /// A lookup in the turn cache (see [`crate::cache`]) hit or missed.
+    fn on_turn_cache(&self, _hit: bool) {}
+
+    /// A request drew on a client-side rate limit (see [`crate::ratelimit`]);
+    /// `state` is its bucket as the request left it.
+    fn on_rate_limit(&self, _op: Operation, _state: &RateLimitState) {}
+
+    /// A page of turns returned for `op` broke the protocol's invariants and
+    /// was passed through anyway, as [`ResponseValidation::Lenient`] clients
+    /// do; `reason` says how.
+    ///
+    /// [`ResponseValidation::Lenient`]: crate::response_validation::ResponseValidation::Lenient
+    fn on_inconsistent_page(&self, _op: Operation, _reason: &str) {}
+}
+
+impl fmt::Debug for dyn Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Metrics")
+    }
+}
+
+/// Installs `metrics` on the client and every connection redialed from it.
+pub fn with_metrics(metrics: Arc<dyn Metrics>) -> ClientOption {
+    Arc::new(move |opts| opts.metrics = Some(metrics.clone()))
+}
+
+/// A [`Metrics`] hook that records nothing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+/// The request a metric refers to, named after its message type.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Hello,
+    CreateContext,
+    ForkContext,
+    GetHead,
+    AppendTurn,
+    GetLast,
+    GetBlob,
+    AttachFs,
+    PutBlob,
+    CreateContextAlias,
+    ResolveAlias,
+    CompactContext,
+    GetTurn,
+    GetQuotas,
+    SearchTurns,
+    GetChildren,
+    PruneContext,
+    RedactTurn,
+    TextSearch,
+    TypeHistogram,
+    AppendMulti,
+    ContextStats,
+    GetLinked,
+    ArchiveContext,
+    UnarchiveContext,
+    ListContexts,
+    DeleteContexts,
+    CreateContextWithTurn,
+    GetContext,
+    ListChildren,
+    /// A message type this client does not name.
+    Other(u16),
+}
+
+impl Operation {
+    pub fn from_msg_type(msg_type: u16) -> Self {
+        match msg_type {
+            MSG_HELLO => Operation::Hello,
+            MSG_CTX_CREATE => Operation::CreateContext,
+            MSG_CTX_FORK => Operation::ForkContext,
+            MSG_GET_HEAD => Operation::GetHead,
+            MSG_APPEND_TURN => Operation::AppendTurn,
+            MSG_GET_LAST => Operation::GetLast,
+            MSG_GET_BLOB => Operation::GetBlob,
+            MSG_ATTACH_FS => Operation::AttachFs,
+            MSG_PUT_BLOB => Operation::PutBlob,
+            MSG_CTX_CREATE_ALIAS => Operation::CreateContextAlias,
+            MSG_RESOLVE_ALIAS => Operation::ResolveAlias,
+            MSG_CTX_COMPACT => Operation::CompactContext,
+            MSG_GET_TURN => Operation::GetTurn,
+            MSG_GET_QUOTAS => Operation::GetQuotas,
+            MSG_SEARCH_TURNS => Operation::SearchTurns,
+            MSG_GET_CHILDREN => Operation::GetChildren,
+            MSG_CTX_PRUNE => Operation::PruneContext,
+            MSG_TURN_REDACT => Operation::RedactTurn,
+            MSG_TEXT_SEARCH => Operation::TextSearch,
+            MSG_TYPE_HISTOGRAM => Operation::TypeHistogram,
+            MSG_APPEND_MULTI => Operation::AppendMulti,
+            MSG_CONTEXT_STATS => Operation::ContextStats,
+            MSG_GET_LINKED => Operation::GetLinked,
+            MSG_CTX_ARCHIVE => Operation::ArchiveContext,
+            MSG_CTX_RESTORE => Operation::UnarchiveContext,
+            MSG_LIST_CONTEXTS => Operation::ListContexts,
+            MSG_CTX_DELETE_MANY => Operation::DeleteContexts,
+            MSG_CTX_CREATE_WITH_TURN => Operation::CreateContextWithTurn,
+            MSG_GET_CONTEXT => Operation::GetContext,
+            MSG_LIST_CHILDREN => Operation::ListChildren,
+            other => Operation::Other(other),
+        }
+    }
+
+    /// Snake-case name suitable as a metric label, e.g. `"append_turn"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Hello => "hello",
+            Operation::CreateContext => "create_context",
+            Operation::ForkContext => "fork_context",
+            Operation::GetHead => "get_head",
+            Operation::AppendTurn => "append_turn",
+            Operation::GetLast => "get_last",
+            Operation::GetBlob => "get_blob",
+            Operation::AttachFs => "attach_fs",
+            Operation::PutBlob => "put_blob",
+            Operation::CreateContextAlias => "create_context_alias",
+            Operation::ResolveAlias => "resolve_alias",
+            Operation::CompactContext => "compact_context",
+            Operation::GetTurn => "get_turn",
+            Operation::GetQuotas => "get_quotas",
+            Operation::SearchTurns => "search_turns",
+            Operation::GetChildren => "get_children",
+            Operation::PruneContext => "prune_context",
+            Operation::RedactTurn => "redact_turn",
+            Operation::TextSearch => "text_search",
+            Operation::TypeHistogram => "type_histogram",
+            Operation::AppendMulti => "append_multi",
+            Operation::ContextStats => "context_stats",
+            Operation::GetLinked => "get_linked",
+            Operation::ArchiveContext => "archive_context",
+            Operation::UnarchiveContext => "unarchive_context",
+            Operation::ListContexts => "list_contexts",
+            Operation::DeleteContexts => "delete_contexts",
+            Operation::CreateContextWithTurn => "create_context_with_turn",
+            Operation::GetContext => "get_context",
+            Operation::ListChildren => "list_children",
+            Operation::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Totals for one [`Operation`], as kept by [`InMemoryMetrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpStats {
+    pub requests: u64,
+    pub errors: u64,
+    pub total_duration: Duration,
+    pub max_duration: Duration,
+}
+
+/// A [`Metrics`] hook that keeps per-operation totals and byte counts in
+/// memory.
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    ops: Mutex<HashMap<Operation, OpStats>>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    throttled_appends: AtomicU64,
+    throttled_reads: AtomicU64,
+    inconsistent_pages: AtomicU64,
+}
+
+impl InMemoryMetrics {
+    pub fn stats(&self, op: Operation) -> OpStats {
+        self.ops
+            .lock()
+            .map(|ops| ops.get(&op).copied().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    pub fn bytes(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::Sent => self.bytes_sent.load(Ordering::Relaxed),
+            Direction::Received => self.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses.load(Ordering::Relaxed)
+    }
+
+    /// Inconsistent turn pages passed through by a lenient client.
+    pub fn inconsistent_pages(&self) -> u64 {
+        self.inconsistent_pages.load(Ordering::Relaxed)
+    }
+
+    /// Requests a rate limit held back, by waiting or rejecting them.
+    pub fn throttled(&self, limited: Limited) -> u64 {
+        match limited {
+            Limited::Appends => self.throttled_appends.load(Ordering::Relaxed),
+            Limited::Reads => self.throttled_reads.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Metrics for InMemoryMetrics {
+    fn on_request(&self, op: Operation, duration: Duration, result: Result<(), &Error>) {
+        if let Ok(mut ops) = self.ops.lock() {
+            let stats = ops.entry(op).or_default();
+            stats.requests += 1;
+            stats.errors += u64::from(result.is_err());
+            stats.total_duration += duration;
+            stats.max_duration = stats.max_duration.max(duration);
+        }
+    }
+
+    fn on_bytes(&self, direction: Direction, bytes: usize) {
+        let counter = match direction {
+            Direction::Sent => &self.bytes_sent,
+            Direction::Received => &self.bytes_received,
+        };
+        counter.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn on_turn_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_rate_limit(&self, _op: Operation, state: &RateLimitState) {
+        if !state.throttled() {
+            return;
+        }
+        let counter = match state.limited {
+            Limited::Appends => &self.throttled_appends,
+            Limited::Reads => &self.throttled_reads,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_inconsistent_page(&self, _op: Operation, _reason: &str) {
+        self.inconsistent_pages.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ContextId;
+    use crate::protocol::{MSG_ERROR, MSG_GET_LAST};
+    use crate::test_util::{error_payload, spawn_scripted_server, turn_records_payload};
+    use crate::turn::GetLastOptions;
+    use crate::{dial, RequestContext};
+
+    #[test]
+    fn client_reports_requests_and_bytes() {
+        let records = turn_records_payload(&[b"\x91\x01"]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, records.clone()),
+            (MSG_ERROR, error_payload(404, "context")),
+            (MSG_GET_LAST, records.clone()),
+            (MSG_GET_LAST, records.clone()),
+        ]);
+        let metrics = Arc::new(InMemoryMetrics::default());
+        let client = dial(&addr, [with_metrics(metrics.clone())]).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        client
+            .get_last(&ctx, ContextId::new(2), opts.clone())
+            .unwrap_err();
+        let batch = client
+            .get_last_many(
+                &ctx,
+                &[
+                    (ContextId::new(1), opts.clone()),
+                    (ContextId::new(1), opts.clone()),
+                ],
+            )
+            .unwrap();
+        assert!(batch.iter().all(Result::is_ok));
+        let requests = handle.join().unwrap();
+
+        assert_eq!(metrics.stats(Operation::Hello).requests, 1);
+        let get_last = metrics.stats(Operation::GetLast);
+        assert_eq!(get_last.requests, 4);
+        assert_eq!(get_last.errors, 1);
+        assert!(get_last.max_duration <= get_last.total_duration);
+
+        // HELLO is 16 + 8 bytes; each response above is counted with its header.
+        let sent: usize = requests.iter().map(|f| 16 + f.payload.len()).sum();
+        assert_eq!(metrics.bytes(Direction::Sent), (sent + 16 + 8) as u64);
+        let error_len = error_payload(404, "context").len();
+        let received = 3 * (16 + records.len()) + 16 + error_len + 16 + 10;
+        assert_eq!(metrics.bytes(Direction::Received), received as u64);
+
+        // Requests refused before sending still count.
+        client.close().unwrap();
+        client.get_last(&ctx, ContextId::new(1), opts).unwrap_err();
+        assert_eq!(metrics.stats(Operation::GetLast).errors, 2);
+    }
+
+    #[test]
+    fn operations_are_named_after_message_types() {
+        assert_eq!(Operation::from_msg_type(5), Operation::AppendTurn);
+        assert_eq!(Operation::AppendTurn.to_string(), "append_turn");
+        assert_eq!(Operation::from_msg_type(200), Operation::Other(200));
+        NoopMetrics.on_request(Operation::Hello, Duration::ZERO, Ok(()));
+    }
+}
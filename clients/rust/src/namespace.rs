@@ -0,0 +1,173 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scoping a client to a namespace.
+//!
+//! One server can keep several tenants apart by namespace: each request
+//! names its namespace, and a context id only names a context within it.
+//! A client dialed [`with_namespace`] stamps its namespace on every request
+//! as [`NAMESPACE_KEY`] metadata; [`RequestContext::with_namespace`]
+//! overrides it for the requests made with one context.
+//!
+//! Namespaces are negotiated on HELLO. Dialing a namespace on a server that
+//! does not accept them fails with [`Error::Unsupported`], as does a request
+//! whose context names one, so a namespaced client never reaches another
+//! tenant's contexts by accident. The reference server does not scope by
+//! namespace yet; clients dialed without one work with it as before.
+//!
+//! Because the same id can name different contexts in two namespaces, the
+//! client keeps apart what it caches per namespace: prefetched tails only
+//! answer reads in the client's own namespace, and a
+//! [turn cache](crate::cache) shared between clients keys its entries by
+//! namespace. Code that carries context ids across namespaces can hold them
+//! as a [`ScopedContextId`], which compares unequal across namespaces and
+//! builds the request context to use them with.
+//!
+//! ```no_run
+//! use cxdb::namespace::with_namespace;
+//! use cxdb::{CreateContextOptions, RequestContext};
+//!
+//! let client = cxdb::dial("127.0.0.1:9009", [with_namespace("prod-eu")])?;
+//! let ctx = RequestContext::background();
+//! let head = client.create_context(&ctx, CreateContextOptions::default())?;
+//! let id = client.scoped(&ctx, head.context_id);
+//! assert_eq!(id.to_string(), format!("prod-eu/{}", head.context_id));
+//!
+//! // Wherever the id is carried, it reads from its own namespace.
+//! let elsewhere = ctx.with_namespace("staging");
+//! client.get_head(&id.context(&elsewhere), id.id)?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`NAMESPACE_KEY`]: crate::client::NAMESPACE_KEY
+//! [`Error::Unsupported`]: crate::Error::Unsupported
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::client::{Client, ClientOption, RequestContext, NAMESPACE_KEY};
+use crate::ids::ContextId;
+
+/// Scopes every request to `namespace`; empty, the default, for none.
+pub fn with_namespace(namespace: impl Into<String>) -> ClientOption {
+    let namespace = namespace.into();
+    Arc::new(move |opts| opts.namespace = namespace.clone())
+}
+
+/// A context id together with the namespace it names a context in; see the
+/// [module docs](self). Displays as `namespace/id`, or just `id` in the
+/// unnamespaced space.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScopedContextId {
+    /// Empty for the server's unnamespaced contexts.
+    pub namespace: String,
+    pub id: ContextId,
+}
+
+impl ScopedContextId {
+    pub fn new(namespace: impl Into<String>, id: ContextId) -> Self {
+        Self {
+            namespace: namespace.into(),
+            id,
+        }
+    }
+
+    /// A child of `ctx` whose requests reach this id's namespace.
+    pub fn context(&self, ctx: &RequestContext) -> RequestContext {
+        ctx.with_namespace(self.namespace.as_str())
+    }
+}
+
+impl fmt::Display for ScopedContextId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.namespace.is_empty() {
+            write!(f, "{}", self.id)
+        } else {
+            write!(f, "{}/{}", self.namespace, self.id)
+        }
+    }
+}
+
+impl Client {
+    /// `context_id` in the namespace requests made with `ctx` reach.
+    pub fn scoped(&self, ctx: &RequestContext, context_id: ContextId) -> ScopedContextId {
+        ScopedContextId::new(self.namespace_of(ctx), context_id)
+    }
+
+    /// The namespace requests made with `ctx` are scoped to.
+    pub(crate) fn namespace_of<'a>(&'a self, ctx: &'a RequestContext) -> &'a str {
+        ctx.get_value(NAMESPACE_KEY)
+            .unwrap_or_else(|| self.namespace())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::error::Error;
+    use crate::protocol::{encode_frame_metadata, FLAG_METADATA, FLAG_NAMESPACES, MSG_GET_HEAD};
+    use crate::test_util::{spawn_scripted_server, spawn_scripted_server_with_flags};
+
+    #[test]
+    fn namespaces_need_server_support() {
+        let (addr, handle) = spawn_scripted_server(Vec::new());
+        let err = dial(&addr, [with_namespace("prod-eu")]).err().unwrap();
+        assert!(
+            matches!(&err, Error::Unsupported(what) if what == "namespaces"),
+            "{err:?}"
+        );
+        handle.join().unwrap();
+
+        // Unnamespaced clients still work, but refuse a per-call namespace.
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_HEAD, vec![0; 20])]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let err = client
+            .get_head(&ctx.with_namespace("prod-eu"), ContextId::new(7))
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::Unsupported(what) if what == "namespaces"),
+            "{err:?}"
+        );
+        client
+            .get_head(&ctx.with_namespace(""), ContextId::new(7))
+            .unwrap();
+        client.close().unwrap();
+        assert_eq!(handle.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn requests_carry_the_namespace_they_are_scoped_to() {
+        let (addr, handle) = spawn_scripted_server_with_flags(
+            FLAG_METADATA | FLAG_NAMESPACES,
+            vec![(MSG_GET_HEAD, vec![0; 20]); 3],
+        );
+        let client = dial(&addr, [with_namespace("prod-eu")]).unwrap();
+        assert_eq!(client.namespace(), "prod-eu");
+        let ctx = RequestContext::background();
+
+        let here = client.scoped(&ctx, ContextId::new(7));
+        let there = client.scoped(&ctx.with_namespace("staging"), ContextId::new(7));
+        assert_ne!(here, there);
+        assert_eq!(
+            (here.to_string(), there.to_string()),
+            ("prod-eu/7".into(), "staging/7".into())
+        );
+        assert_eq!(ScopedContextId::new("", ContextId::new(7)).to_string(), "7");
+
+        for id in [&here, &there, &ScopedContextId::new("", ContextId::new(7))] {
+            client.get_head(&id.context(&ctx), id.id).unwrap();
+        }
+        client.close().unwrap();
+
+        let requests = handle.join().unwrap();
+        for (request, namespace) in requests.iter().zip(["prod-eu", "staging", ""]) {
+            let mut expected = Vec::new();
+            encode_frame_metadata(&mut expected, [(NAMESPACE_KEY, namespace)].into_iter()).unwrap();
+            expected.extend_from_slice(&7u64.to_le_bytes());
+            assert_eq!(request.header.flags & FLAG_METADATA, FLAG_METADATA);
+            assert_eq!(request.payload, expected);
+        }
+    }
+}
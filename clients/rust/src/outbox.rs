@@ -0,0 +1,886 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Durable local outbox for appends made while the server is unreachable.
+//!
+//! An [`OutboxClient`] forwards appends to the server when it can. When an
+//! append fails with a connection error it is written to an append-only file
+//! and replayed, in order, by a background thread once a connection can be
+//! re-established. Final server results are reported on the channel returned
+//! by [`OutboxClient::acks`].
+//!
+//! # Durability
+//!
+//! - Every queued append is assigned an idempotency key (unless the caller
+//!   supplied one) before it is persisted, so replaying an append whose ack was
+//!   lost is deduplicated by the server.
+//! - Acknowledgements are recorded in the same file. On startup only
+//!   unacknowledged entries are replayed.
+//! - A torn record at the end of the file (crash mid-write) is discarded.
+//! - The file is truncated once every entry has been acknowledged.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use cxdb::outbox::OutboxOptions;
+//! use cxdb::{dial, AppendRequest, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", Vec::new())?;
+//! let outbox = client.with_outbox("/var/lib/agent/cxdb.outbox", OutboxOptions::default())?;
+//! let acks = outbox.acks();
+//!
+//! let ctx = RequestContext::background();
+//! let queued = outbox.append_turn(&ctx, &AppendRequest::new(ContextId::new(1), "app.Event", 1, vec![0x80]))?;
+//! if queued.result.is_none() {
+//!     let ack = acks.recv().unwrap();
+//!     println!("sequence {} -> {:?}", ack.sequence, ack.result.map(|r| r.turn_id));
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::TurnId;
+use crate::links::{encode_links, read_links};
+use crate::protocol::PayloadReader;
+use crate::reconnect::{is_connection_error, DialFunc};
+use crate::turn::{ttl_millis, AppendRequest, AppendResult};
+use crate::validate::{validate_with, TurnValidator};
+
+/// Default cap on the outbox file size.
+pub const DEFAULT_OUTBOX_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default interval between replay attempts.
+pub const DEFAULT_OUTBOX_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const RECORD_ENTRY: u8 = 1;
+const RECORD_ACK: u8 = 2;
+
+/// Configuration for an [`OutboxClient`].
+#[derive(Debug, Clone, Copy)]
+pub struct OutboxOptions {
+    /// Maximum outbox file size; appends that would exceed it fail with
+    /// [`Error::QueueFull`].
+    pub max_bytes: u64,
+    /// How often the background thread retries delivery.
+    pub flush_interval: Duration,
+}
+
+impl Default for OutboxOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_OUTBOX_MAX_BYTES,
+            flush_interval: DEFAULT_OUTBOX_FLUSH_INTERVAL,
+        }
+    }
+}
+
+/// Outcome of [`OutboxClient::append_turn`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisionalAppend {
+    /// Outbox sequence number; matches [`OutboxAck::sequence`].
+    pub sequence: u64,
+    /// Idempotency key the append was (or will be) sent with.
+    pub idempotency_key: Vec<u8>,
+    /// Server result if the append was delivered immediately; `None` if it
+    /// was queued in the outbox.
+    pub result: Option<AppendResult>,
+}
+
+/// Final delivery result of a queued append.
+#[derive(Debug)]
+pub struct OutboxAck {
+    pub sequence: u64,
+    /// Server-assigned result, or the permanent error that caused the
+    /// append to be dropped from the outbox.
+    pub result: Result<AppendResult>,
+}
+
+/// Append-through client backed by a durable outbox file.
+pub struct OutboxClient {
+    inner: Arc<Inner>,
+    worker: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    wake: Condvar,
+    dial_func: DialFunc,
+    options: OutboxOptions,
+    /// The wrapped client's validator, checked before anything is queued.
+    validator: Option<Arc<dyn TurnValidator>>,
+    acks_tx: Sender<OutboxAck>,
+    acks_rx: Receiver<OutboxAck>,
+}
+
+struct State {
+    client: Option<Client>,
+    log: OutboxLog,
+    shutdown: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    sequence: u64,
+    req: AppendRequest,
+}
+
+impl Client {
+    /// Wraps this client with a durable outbox stored at `path`.
+    ///
+    /// Unacknowledged appends left in the file by a previous process are
+    /// replayed in order by the background thread.
+    pub fn with_outbox(
+        self,
+        path: impl AsRef<Path>,
+        options: OutboxOptions,
+    ) -> Result<OutboxClient> {
+        let dial_func = self.dialer();
+        OutboxClient::open(path, options, Some(self), dial_func)
+    }
+}
+
+impl OutboxClient {
+    pub(crate) fn open(
+        path: impl AsRef<Path>,
+        options: OutboxOptions,
+        client: Option<Client>,
+        dial_func: DialFunc,
+    ) -> Result<Self> {
+        let log = OutboxLog::open(path.as_ref())?;
+        let (acks_tx, acks_rx) = unbounded();
+        let validator = client
+            .as_ref()
+            .and_then(|client| client.validator().cloned());
+        let inner = Arc::new(Inner {
+            state: Mutex::new(State {
+                client,
+                log,
+                shutdown: false,
+            }),
+            wake: Condvar::new(),
+            dial_func,
+            options,
+            validator,
+            acks_tx,
+            acks_rx,
+        });
+
+        let worker_inner = inner.clone();
+        let handle = thread::Builder::new()
+            .name("cxdb-outbox".into())
+            .spawn(move || flush_loop(worker_inner))?;
+
+        Ok(Self {
+            inner,
+            worker: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// Appends a turn, queueing it in the outbox if the server is unreachable.
+    ///
+    /// Appends are delivered in call order: while earlier appends are still
+    /// queued, new ones are queued behind them. Server rejections of an
+    /// immediate attempt are returned as errors and are not queued, and
+    /// neither are payloads the client's validator rejects (see
+    /// [`crate::validate`]). Replayed appends are validated again.
+    pub fn append_turn(
+        &self,
+        ctx: &RequestContext,
+        req: &AppendRequest,
+    ) -> Result<ProvisionalAppend> {
+        validate_with(self.inner.validator.as_ref(), req)?;
+        let mut state = self.inner.state.lock().map_err(|_| Error::ClientClosed)?;
+        if state.shutdown {
+            return Err(Error::ClientClosed);
+        }
+
+        let sequence = state.log.next_sequence;
+        let mut req = req.clone();
+        if req.idempotency_key.is_empty() {
+            req.idempotency_key =
+                format!("outbox:{}:{}", state.log.instance, sequence).into_bytes();
+        }
+
+        if state.log.pending.is_empty() {
+            if let Some(client) = state.client.as_ref() {
+                match client.append_turn(ctx, &req) {
+                    Ok(result) => {
+                        state.log.next_sequence += 1;
+                        return Ok(ProvisionalAppend {
+                            sequence,
+                            idempotency_key: req.idempotency_key,
+                            result: Some(result),
+                        });
+                    }
+                    Err(err) if is_connection_error(&err) => {
+                        if let Some(client) = state.client.take() {
+                            let _ = client.close();
+                        }
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        let idempotency_key = req.idempotency_key.clone();
+        state
+            .log
+            .push(Entry { sequence, req }, self.inner.options.max_bytes)?;
+        self.inner.wake.notify_one();
+        Ok(ProvisionalAppend {
+            sequence,
+            idempotency_key,
+            result: None,
+        })
+    }
+
+    /// Channel reporting the final result of every queued append.
+    pub fn acks(&self) -> Receiver<OutboxAck> {
+        self.inner.acks_rx.clone()
+    }
+
+    /// Number of appends waiting in the outbox.
+    pub fn pending(&self) -> usize {
+        self.inner
+            .state
+            .lock()
+            .map(|state| state.log.pending.len())
+            .unwrap_or(0)
+    }
+
+    /// Wakes the background thread to attempt delivery now.
+    pub fn flush(&self) {
+        self.inner.wake.notify_one();
+    }
+
+    /// Stops the background thread. Queued appends stay in the file and are
+    /// replayed the next time the outbox is opened.
+    pub fn close(&self) -> Result<()> {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.shutdown = true;
+            if let Some(client) = state.client.take() {
+                let _ = client.close();
+            }
+        }
+        self.inner.wake.notify_one();
+        if let Some(handle) = self.worker.lock().ok().and_then(|mut h| h.take()) {
+            let _ = handle.join();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OutboxClient {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn flush_loop(inner: Arc<Inner>) {
+    let mut state = match inner.state.lock() {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+    loop {
+        if state.shutdown {
+            return;
+        }
+        if !state.log.pending.is_empty() {
+            let sent_all;
+            (state, sent_all) = match deliver_pending(&inner, state) {
+                Some(delivered) => delivered,
+                None => return,
+            };
+            // A close or appends that arrived during the batch missed the
+            // wakeup.
+            if state.shutdown || (sent_all && !state.log.pending.is_empty()) {
+                continue;
+            }
+        }
+        state = match inner.wake.wait_timeout(state, inner.options.flush_interval) {
+            Ok((state, _)) => state,
+            Err(_) => return,
+        };
+    }
+}
+
+/// Sends the pending entries in order. The lock is released while dialing
+/// and appending, so [`OutboxClient::append_turn`] keeps queueing locally
+/// while the server is slow or unreachable; it is retaken to record each ack.
+/// Returns whether the whole batch was sent, or `None` if the lock was
+/// poisoned.
+fn deliver_pending<'a>(
+    inner: &'a Inner,
+    mut state: MutexGuard<'a, State>,
+) -> Option<(MutexGuard<'a, State>, bool)> {
+    // Entries queued while this batch is in flight go in the next one, so
+    // they still follow it.
+    let batch: Vec<Entry> = state.log.pending.iter().cloned().collect();
+    let mut client = state.client.take();
+    drop(state);
+
+    let ctx = RequestContext::background();
+    let mut sent = 0;
+    for entry in &batch {
+        if client.is_none() {
+            match (inner.dial_func)() {
+                Ok(dialed) => client = Some(dialed),
+                Err(_) => break,
+            }
+        }
+        let Some(conn) = client.as_ref() else {
+            break;
+        };
+        let result = match conn.append_turn(&ctx, &entry.req) {
+            Err(err) if is_connection_error(&err) || conn.is_poisoned() => {
+                if let Some(conn) = client.take() {
+                    let _ = conn.close();
+                }
+                break;
+            }
+            other => other,
+        };
+
+        let mut state = inner.state.lock().ok()?;
+        let turn_id = result.as_ref().map(|r| r.turn_id).unwrap_or_default();
+        let acked = state.log.ack(entry.sequence, turn_id).is_ok();
+        let shutdown = state.shutdown;
+        drop(state);
+        if !acked {
+            // Without a durable ack the entry would be replayed (and deduped
+            // by its idempotency key) after a restart; stop until next tick.
+            break;
+        }
+        sent += 1;
+        let _ = inner.acks_tx.send(OutboxAck {
+            sequence: entry.sequence,
+            result,
+        });
+        if shutdown {
+            break;
+        }
+    }
+
+    let mut state = inner.state.lock().ok()?;
+    if let Some(client) = client {
+        if state.shutdown || state.client.is_some() {
+            let _ = client.close();
+        } else {
+            state.client = Some(client);
+        }
+    }
+    Some((state, sent == batch.len()))
+}
+
+/// Append-only record file: `[len: u32][checksum: u32][kind: u8][body]`.
+struct OutboxLog {
+    file: File,
+    path: PathBuf,
+    size: u64,
+    pending: VecDeque<Entry>,
+    next_sequence: u64,
+    /// Random per-open prefix for generated idempotency keys.
+    instance: String,
+}
+
+impl OutboxLog {
+    fn open(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut pending: VecDeque<Entry> = VecDeque::new();
+        let mut next_sequence = 1;
+        let mut valid_len = 0usize;
+        let mut reader = PayloadReader::new(&data, "outbox record");
+        while let Some((kind, body)) = read_record(&mut reader) {
+            valid_len = data.len() - reader.remaining();
+            match kind {
+                RECORD_ENTRY => {
+                    let entry = decode_entry(body)?;
+                    next_sequence = next_sequence.max(entry.sequence + 1);
+                    pending.push_back(entry);
+                }
+                RECORD_ACK => {
+                    let sequence = PayloadReader::new(body, "outbox ack").u64("sequence")?;
+                    pending.retain(|e| e.sequence != sequence);
+                }
+                other => {
+                    return Err(Error::protocol(format!(
+                        "unknown outbox record kind {other}"
+                    )));
+                }
+            }
+        }
+
+        if valid_len < data.len() {
+            // Discard a torn tail left by a crash mid-write.
+            file.set_len(valid_len as u64)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        let mut log = Self {
+            file,
+            path: path.to_path_buf(),
+            size: valid_len as u64,
+            pending,
+            next_sequence,
+            instance: uuid::Uuid::new_v4().simple().to_string(),
+        };
+        log.compact_if_drained()?;
+        Ok(log)
+    }
+
+    fn push(&mut self, entry: Entry, max_bytes: u64) -> Result<()> {
+        let body = encode_entry(&entry)?;
+        if self.size + 9 + body.len() as u64 > max_bytes {
+            return Err(Error::QueueFull);
+        }
+        self.write_record(RECORD_ENTRY, &body)?;
+        self.next_sequence = self.next_sequence.max(entry.sequence + 1);
+        self.pending.push_back(entry);
+        Ok(())
+    }
+
+    fn ack(&mut self, sequence: u64, turn_id: TurnId) -> Result<()> {
+        let mut body = Vec::with_capacity(16);
+        body.write_u64::<LittleEndian>(sequence)?;
+        body.write_u64::<LittleEndian>(turn_id.get())?;
+        self.write_record(RECORD_ACK, &body)?;
+        self.pending.retain(|e| e.sequence != sequence);
+        self.compact_if_drained()
+    }
+
+    fn write_record(&mut self, kind: u8, body: &[u8]) -> Result<()> {
+        let mut record = Vec::with_capacity(9 + body.len());
+        record.write_u32::<LittleEndian>(body.len() as u32 + 1)?;
+        record.write_u32::<LittleEndian>(checksum(kind, body))?;
+        record.push(kind);
+        record.extend_from_slice(body);
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.size += record.len() as u64;
+        Ok(())
+    }
+
+    fn compact_if_drained(&mut self) -> Result<()> {
+        if self.pending.is_empty() && self.size > 0 {
+            self.file.set_len(0)?;
+            self.file.sync_data()?;
+            self.size = 0;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for OutboxLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutboxLog")
+            .field("path", &self.path)
+            .field("size", &self.size)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+fn checksum(kind: u8, body: &[u8]) -> u32 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[kind]);
+    hasher.update(body);
+    let hash = hasher.finalize();
+    u32::from_le_bytes(hash.as_bytes()[0..4].try_into().unwrap_or_default())
+}
+
+/// Reads one complete, checksummed record; `None` at end of file or at a
+/// torn/corrupt tail.
+fn read_record<'a>(reader: &mut PayloadReader<'a>) -> Option<(u8, &'a [u8])> {
+    let len = reader.u32("len").ok()? as usize;
+    let sum = reader.u32("checksum").ok()?;
+    let record = reader.bytes(len, "record").ok()?;
+    let (&kind, body) = record.split_first()?;
+    if checksum(kind, body) != sum {
+        return None;
+    }
+    Some((kind, body))
+}
+
+fn encode_entry(entry: &Entry) -> Result<Vec<u8>> {
+    let req = &entry.req;
+    let mut body = Vec::with_capacity(64 + req.payload.len());
+    body.write_u64::<LittleEndian>(entry.sequence)?;
+    body.write_u64::<LittleEndian>(req.context_id.get())?;
+    body.write_u64::<LittleEndian>(req.parent_turn_id.get())?;
+    body.write_u32::<LittleEndian>(req.type_id.len() as u32)?;
+    body.extend_from_slice(req.type_id.as_bytes());
+    body.write_u32::<LittleEndian>(req.type_version)?;
+    body.write_u32::<LittleEndian>(req.encoding)?;
+    body.write_u32::<LittleEndian>(req.compression)?;
+    body.write_u32::<LittleEndian>(req.payload.len() as u32)?;
+    body.extend_from_slice(&req.payload);
+    body.write_u32::<LittleEndian>(req.idempotency_key.len() as u32)?;
+    body.extend_from_slice(&req.idempotency_key);
+    // Optional trailing writer stamp (an empty id when only later fields
+    // follow), then TTL (0 for none when links follow), then links; entries
+    // written before they existed end early.
+    let has_links = !req.links.is_empty();
+    if req.writer_id.is_some() || req.ttl.is_some() || has_links {
+        let writer_id = req.writer_id.as_deref().unwrap_or_default();
+        body.write_u32::<LittleEndian>(writer_id.len() as u32)?;
+        body.extend_from_slice(writer_id.as_bytes());
+        body.write_u64::<LittleEndian>(req.writer_seq)?;
+    }
+    if req.ttl.is_some() || has_links {
+        body.write_u64::<LittleEndian>(req.ttl.map_or(0, ttl_millis))?;
+    }
+    if has_links {
+        encode_links(&mut body, &req.links)?;
+    }
+    Ok(body)
+}
+
+fn decode_entry(body: &[u8]) -> Result<Entry> {
+    let mut reader = PayloadReader::new(body, "outbox entry");
+    let sequence = reader.u64("sequence")?;
+    let context_id = reader.u64("context_id")?.into();
+    let parent_turn_id = reader.u64("parent_turn_id")?.into();
+    let type_id = String::from_utf8(reader.len_prefixed("type_id")?.to_vec())
+        .map_err(|_| Error::protocol("outbox type_id not utf8"))?;
+    let type_version = reader.u32("type_version")?;
+    let encoding = reader.u32("encoding")?;
+    let compression = reader.u32("compression")?;
+    let payload = reader.len_prefixed("payload")?.to_vec();
+    let idempotency_key = reader.len_prefixed("idempotency_key")?.to_vec();
+    let (writer_id, writer_seq) = if reader.remaining() > 0 {
+        let writer_id = String::from_utf8(reader.len_prefixed("writer_id")?.to_vec())
+            .map_err(|_| Error::protocol("outbox writer_id not utf8"))?;
+        let writer_seq = reader.u64("writer_seq")?;
+        ((!writer_id.is_empty()).then_some(writer_id), writer_seq)
+    } else {
+        (None, 0)
+    };
+    let ttl = if reader.remaining() > 0 {
+        let ttl_ms = reader.u64("ttl_ms")?;
+        (ttl_ms > 0).then(|| Duration::from_millis(ttl_ms))
+    } else {
+        None
+    };
+    let links = if reader.remaining() > 0 {
+        read_links(&mut reader)?
+    } else {
+        Vec::new()
+    };
+    Ok(Entry {
+        sequence,
+        req: AppendRequest {
+            context_id,
+            parent_turn_id,
+            type_id,
+            type_version,
+            payload,
+            idempotency_key,
+            encoding,
+            compression,
+            writer_id,
+            writer_seq,
+            ttl,
+            links,
+            delta_base: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ContextId;
+    use crate::links::{LinkKind, TurnLink};
+    use crate::protocol::MSG_APPEND_TURN;
+    use crate::test_util::spawn_scripted_server;
+
+    fn failing_dialer() -> DialFunc {
+        Arc::new(|| {
+            Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "refused",
+            )))
+        })
+    }
+
+    fn append_ack(turn_id: u64) -> Vec<u8> {
+        let mut ack = Vec::new();
+        ack.write_u64::<LittleEndian>(1).unwrap();
+        ack.write_u64::<LittleEndian>(turn_id).unwrap();
+        ack.write_u32::<LittleEndian>(1).unwrap();
+        ack.extend_from_slice(&[0u8; 32]);
+        ack
+    }
+
+    fn quiet_options() -> OutboxOptions {
+        OutboxOptions {
+            flush_interval: Duration::from_secs(3600),
+            ..OutboxOptions::default()
+        }
+    }
+
+    #[test]
+    fn offline_appends_survive_restart_and_replay_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxdb.outbox");
+        let ctx = RequestContext::background();
+
+        let outbox = OutboxClient::open(&path, quiet_options(), None, failing_dialer()).unwrap();
+        for i in 0..3u8 {
+            let queued = outbox
+                .append_turn(
+                    &ctx,
+                    &AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90 + i]),
+                )
+                .unwrap();
+            assert_eq!(queued.sequence, i as u64 + 1);
+            assert!(queued.result.is_none());
+        }
+        assert_eq!(outbox.pending(), 3);
+        outbox.close().unwrap();
+        drop(outbox);
+
+        let replies = (0..3)
+            .map(|i| (MSG_APPEND_TURN, append_ack(10 + i)))
+            .collect();
+        let (addr, handle) = spawn_scripted_server(replies);
+        let dial_func: DialFunc = Arc::new(move || crate::client::dial(&addr, Vec::new()));
+        let outbox = OutboxClient::open(&path, quiet_options(), None, dial_func).unwrap();
+        let acks = outbox.acks();
+        outbox.flush();
+
+        let delivered: Vec<(u64, u64)> = (0..3)
+            .map(|_| {
+                let ack = acks.recv_timeout(Duration::from_secs(5)).unwrap();
+                (ack.sequence, ack.result.unwrap().turn_id.get())
+            })
+            .collect();
+        assert_eq!(delivered, vec![(1, 10), (2, 11), (3, 12)]);
+        assert_eq!(outbox.pending(), 0);
+
+        let frames = handle.join().unwrap();
+        let payloads: Vec<u8> = frames
+            .iter()
+            .map(|f| {
+                let entry = decode_entry_from_append(&f.payload);
+                entry.payload[0]
+            })
+            .collect();
+        assert_eq!(payloads, vec![0x90, 0x91, 0x92]);
+        outbox.close().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn appends_queue_while_the_flusher_is_dialing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (dialing_tx, dialing_rx) = crossbeam_channel::bounded(1);
+        let (release_tx, release_rx) = crossbeam_channel::bounded::<()>(1);
+        let dial_func: DialFunc = Arc::new(move || {
+            let _ = dialing_tx.try_send(());
+            let _ = release_rx.recv_timeout(Duration::from_secs(10));
+            Err(Error::Io(std::io::ErrorKind::TimedOut.into()))
+        });
+        let outbox = OutboxClient::open(
+            dir.path().join("cxdb.outbox"),
+            quiet_options(),
+            None,
+            dial_func,
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+        let req = AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]);
+
+        outbox.append_turn(&ctx, &req).unwrap();
+        outbox.flush();
+        dialing_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        let started = std::time::Instant::now();
+        let queued = outbox.append_turn(&ctx, &req).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!((queued.sequence, queued.result), (2, None));
+        assert_eq!(outbox.pending(), 2);
+
+        release_tx.send(()).unwrap();
+        outbox.close().unwrap();
+    }
+
+    #[test]
+    fn acknowledged_entries_are_not_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxdb.outbox");
+        let mut log = OutboxLog::open(&path).unwrap();
+        for sequence in 1..=2 {
+            let mut req = AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]);
+            req.idempotency_key = format!("k{sequence}").into_bytes();
+            log.push(Entry { sequence, req }, u64::MAX).unwrap();
+        }
+        log.ack(1, TurnId::new(42)).unwrap();
+        drop(log);
+
+        let log = OutboxLog::open(&path).unwrap();
+        let pending: Vec<u64> = log.pending.iter().map(|e| e.sequence).collect();
+        assert_eq!(pending, vec![2]);
+        assert_eq!(log.pending[0].req.idempotency_key, b"k2");
+        assert_eq!(log.next_sequence, 3);
+    }
+
+    #[test]
+    fn torn_tail_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxdb.outbox");
+        let mut log = OutboxLog::open(&path).unwrap();
+        log.push(
+            Entry {
+                sequence: 1,
+                req: AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]),
+            },
+            u64::MAX,
+        )
+        .unwrap();
+        let good_len = log.size;
+        drop(log);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xFF, 0x00, 0x00]).unwrap();
+        drop(file);
+
+        let log = OutboxLog::open(&path).unwrap();
+        assert_eq!(log.pending.len(), 1);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), good_len);
+    }
+
+    #[test]
+    fn writer_stamps_ttls_and_links_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxdb.outbox");
+        let mut log = OutboxLog::open(&path).unwrap();
+        for (sequence, req) in [
+            AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]),
+            AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90])
+                .writer_id("agent-a")
+                .writer_seq(4),
+            AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90])
+                .ttl(Duration::from_secs(60)),
+            AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90])
+                .link_to(TurnId::new(2), LinkKind::Causes),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let entry = Entry {
+                sequence: sequence as u64 + 1,
+                req,
+            };
+            log.push(entry, u64::MAX).unwrap();
+        }
+        drop(log);
+
+        let log = OutboxLog::open(&path).unwrap();
+        assert_eq!(log.pending[0].req.writer_id, None);
+        assert_eq!(log.pending[1].req.writer_id.as_deref(), Some("agent-a"));
+        assert_eq!(log.pending[1].req.writer_seq, 4);
+        assert_eq!(log.pending[1].req.ttl, None);
+        assert_eq!(log.pending[2].req.writer_id, None);
+        assert_eq!(log.pending[2].req.ttl, Some(Duration::from_secs(60)));
+        assert!(log.pending[2].req.links.is_empty());
+        assert_eq!(log.pending[3].req.ttl, None);
+        assert_eq!(
+            log.pending[3].req.links,
+            [TurnLink {
+                target_turn_id: TurnId::new(2),
+                kind: LinkKind::Causes,
+            }]
+        );
+    }
+
+    #[test]
+    fn max_bytes_rejects_overflow() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cxdb.outbox");
+        let options = OutboxOptions {
+            max_bytes: 128,
+            ..quiet_options()
+        };
+        let outbox = OutboxClient::open(&path, options, None, failing_dialer()).unwrap();
+        let ctx = RequestContext::background();
+        let err = outbox
+            .append_turn(
+                &ctx,
+                &AppendRequest::new(ContextId::new(1), "test", 1, vec![0u8; 256]),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::QueueFull));
+    }
+
+    #[test]
+    fn invalid_payloads_are_not_queued() {
+        use crate::validate::{with_validator, MsgpackWellFormed};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, _handle) = spawn_scripted_server(Vec::new());
+        let client = crate::dial(&addr, [with_validator(Arc::new(MsgpackWellFormed))]).unwrap();
+        let outbox = client
+            .with_outbox(dir.path().join("cxdb.outbox"), quiet_options())
+            .unwrap();
+
+        // A two-element array holding one element.
+        let err = outbox
+            .append_turn(
+                &RequestContext::background(),
+                &AppendRequest::new(ContextId::new(1), "test", 1, vec![0x92, 0x01]),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::Validation(_)));
+        assert_eq!(outbox.pending(), 0);
+    }
+
+    fn decode_entry_from_append(payload: &[u8]) -> AppendRequest {
+        let mut reader = PayloadReader::new(payload, "append");
+        let context_id = ContextId::new(reader.u64("context_id").unwrap());
+        let parent_turn_id = TurnId::new(reader.u64("parent_turn_id").unwrap());
+        let type_id = String::from_utf8(reader.len_prefixed("type_id").unwrap().to_vec()).unwrap();
+        let type_version = reader.u32("type_version").unwrap();
+        let encoding = reader.u32("encoding").unwrap();
+        let compression = reader.u32("compression").unwrap();
+        reader.u32("uncompressed_len").unwrap();
+        reader.array::<32>("hash").unwrap();
+        let payload = reader.len_prefixed("payload").unwrap().to_vec();
+        let idempotency_key = reader.len_prefixed("idempotency_key").unwrap().to_vec();
+        assert!(!idempotency_key.is_empty());
+        AppendRequest {
+            context_id,
+            parent_turn_id,
+            type_id,
+            type_version,
+            payload,
+            idempotency_key,
+            encoding,
+            compression,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
+        }
+    }
+}
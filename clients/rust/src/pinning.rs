@@ -0,0 +1,209 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! TLS certificate pinning.
+//!
+//! [`with_pinned_cert`] makes [`dial_tls`] accept only a server whose leaf
+//! certificate carries a public key with the given SHA-256 SPKI hash (the
+//! hash of the DER `SubjectPublicKeyInfo`, as used by HPKP). The pin stands
+//! in for CA chain validation, so servers with self-signed or private-CA
+//! certificates can be dialed without installing a root; the server must
+//! still prove possession of the pinned key in the handshake. Any of
+//! several pins may match, which allows a key to be rotated.
+//!
+//! A handshake with a server presenting any other key fails the dial with
+//! [`Error::CertPinMismatch`]. Compute a pin with [`spki_sha256`], or with
+//! `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl
+//! dgst -sha256`.
+//!
+//! ```no_run
+//! use cxdb::pinning::with_pinned_cert;
+//! use cxdb::dial_tls;
+//!
+//! # let pin = vec![0u8; 32];
+//! let client = dial_tls("cxdb.internal:9009", [with_pinned_cert(pin)])?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`dial_tls`]: crate::dial_tls
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+
+use crate::client::ClientOption;
+use crate::error::{Error, Result};
+
+/// Accepts only servers whose leaf certificate's SPKI hashes (SHA-256) to
+/// `spki_sha256`. Repeat the option to allow several keys.
+pub fn with_pinned_cert(spki_sha256: impl Into<Vec<u8>>) -> ClientOption {
+    let pin = spki_sha256.into();
+    Arc::new(move |opts| opts.pinned_certs.push(pin.clone()))
+}
+
+/// The SHA-256 hash of the `SubjectPublicKeyInfo` of a DER certificate:
+/// the value [`with_pinned_cert`] expects.
+pub fn spki_sha256(cert_der: &[u8]) -> Result<Vec<u8>> {
+    let cert = CertificateDer::from(cert_der);
+    let cert = webpki::EndEntityCert::try_from(&cert)
+        .map_err(|err| Error::Tls(format!("invalid certificate: {err:?}")))?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, &cert.subject_public_key_info());
+    Ok(digest.as_ref().to_vec())
+}
+
+/// Verifies the server by pin instead of by chain. Records a mismatch in
+/// `mismatch` so the dial can report [`Error::CertPinMismatch`] rather than
+/// the I/O error rustls surfaces it as.
+#[derive(Debug)]
+pub(crate) struct PinnedCertVerifier {
+    pins: Vec<Vec<u8>>,
+    algorithms: WebPkiSupportedAlgorithms,
+    pub(crate) mismatch: AtomicBool,
+}
+
+impl PinnedCertVerifier {
+    pub(crate) fn new(pins: Vec<Vec<u8>>) -> Self {
+        Self {
+            pins,
+            algorithms: rustls::crypto::ring::default_provider().signature_verification_algorithms,
+            mismatch: AtomicBool::new(false),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let pinned = spki_sha256(end_entity).is_ok_and(|hash| self.pins.contains(&hash));
+        if !pinned {
+            self.mismatch.store(true, Ordering::SeqCst);
+            return Err(rustls::Error::General(
+                "server certificate does not match pinned key".into(),
+            ));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::with_tls_config;
+    use crate::dial_tls;
+    use crate::protocol::{read_frame, write_frame, MSG_HELLO};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use rcgen::{CertificateParams, KeyPair};
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+    use rustls::{ClientConfig, ServerConfig};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A self-signed `localhost` certificate and the pin of its key.
+    fn fixture() -> (CertificateDer<'static>, PrivateKeyDer<'static>, Vec<u8>) {
+        let key_pair = KeyPair::generate().unwrap();
+        let params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        let cert = params.self_signed(&key_pair).unwrap();
+        let pin = ring::digest::digest(&ring::digest::SHA256, &key_pair.public_key_der());
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+        (cert.der().clone(), key, pin.as_ref().to_vec())
+    }
+
+    /// Serves one TLS connection, answering HELLO if the handshake succeeds.
+    fn spawn_tls_server(
+        cert: CertificateDer<'static>,
+        key: PrivateKeyDer<'static>,
+    ) -> (String, thread::JoinHandle<()>) {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("localhost:{}", listener.local_addr().unwrap().port());
+        let handle = thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let conn = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            let mut stream = rustls::StreamOwned::new(conn, tcp);
+            let Ok(hello) = read_frame(&mut stream) else {
+                return;
+            };
+            let mut resp = Vec::new();
+            resp.write_u64::<LittleEndian>(7).unwrap();
+            resp.write_u16::<LittleEndian>(1).unwrap();
+            write_frame(&mut stream, MSG_HELLO, 0, hello.header.req_id, &resp).unwrap();
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn pinned_key_is_accepted_without_a_trusted_root() {
+        let (cert, key, pin) = fixture();
+        assert_eq!(spki_sha256(&cert).unwrap(), pin);
+        let (addr, handle) = spawn_tls_server(cert, key);
+
+        let client = dial_tls(
+            &addr,
+            [with_pinned_cert(vec![0u8; 32]), with_pinned_cert(pin)],
+        )
+        .unwrap();
+        assert_eq!(client.session_id(), 7);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn other_keys_fail_with_pin_mismatch() {
+        let (cert, key, _) = fixture();
+        let (_, _, other_pin) = fixture();
+        let (addr, handle) = spawn_tls_server(cert.clone(), key);
+
+        // The pin is checked even when the certificate chains to a trusted root.
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let err = dial_tls(
+            &addr,
+            [
+                with_tls_config(Arc::new(config)),
+                with_pinned_cert(other_pin),
+            ],
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, Error::CertPinMismatch), "got {err:?}");
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! A fixed-size pool of client connections for parallel work.
+//!
+//! [`ClientPool::append_many`] fans a batch of appends out across the pooled
+//! connections. Appends to the same context are sent in input order on one
+//! connection, so turns that append to the head keep their relative order;
+//! different contexts proceed in parallel. A pooled connection that fails is
+//! redialed before it is handed out again.
+//!
+//! ```no_run
+//! use cxdb::{dial_pool, AppendRequest, ContextId, RequestContext};
+//!
+//! let pool = dial_pool("127.0.0.1:9009", 8, Vec::new())?;
+//! let requests = (1..=100)
+//!     .map(ContextId::new)
+//!     .map(|context_id| AppendRequest::new(context_id, "app.Event", 1, vec![0x80]))
+//!     .collect();
+//! for result in pool.append_many(&RequestContext::background(), requests) {
+//!     println!("{:?}", result.map(|r| r.turn_id));
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::client::{dial, dial_tls, Client, ClientOption, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+use crate::turn::{AppendRequest, AppendResult};
+
+pub struct ClientPool {
+    clients: Vec<Mutex<Arc<Client>>>,
+    next: AtomicUsize,
+}
+
+impl Client {
+    /// Turns this client into a pool of `size` connections, dialing the
+    /// extra ones with the same address and options.
+    pub fn into_pool(self, size: usize) -> Result<ClientPool> {
+        let mut clients = Vec::with_capacity(size.max(1));
+        for _ in 1..size {
+            clients.push(Mutex::new(Arc::new(self.redial()?)));
+        }
+        clients.insert(0, Mutex::new(Arc::new(self)));
+        Ok(ClientPool {
+            clients,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl ClientPool {
+    /// Number of pooled connections.
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Returns the next pooled client in round-robin order.
+    pub fn get(&self) -> Result<Arc<Client>> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        self.checkout(index)
+    }
+
+    /// Appends every request, spreading contexts across the pooled
+    /// connections, and returns one result per request in input order.
+    ///
+    /// Requests for the same context are sent sequentially in input order.
+    /// A failed append only fails its own slot; a connection that breaks is
+    /// redialed and its worker carries on with the next request.
+    pub fn append_many(
+        &self,
+        ctx: &RequestContext,
+        requests: Vec<AppendRequest>,
+    ) -> Vec<Result<AppendResult>> {
+        // One queue of request indexes per context, in first-seen order.
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut by_context: HashMap<ContextId, usize> = HashMap::new();
+        for (index, req) in requests.iter().enumerate() {
+            let group = *by_context.entry(req.context_id).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(index);
+        }
+
+        let results: Vec<Mutex<Option<Result<AppendResult>>>> =
+            requests.iter().map(|_| Mutex::new(None)).collect();
+        let next_group = AtomicUsize::new(0);
+        let workers = self.clients.len().min(groups.len());
+        thread::scope(|scope| {
+            for worker in 0..workers {
+                let (groups, requests, results, next_group) =
+                    (&groups, &requests, &results, &next_group);
+                scope.spawn(move || loop {
+                    let Some(group) = groups.get(next_group.fetch_add(1, Ordering::SeqCst)) else {
+                        return;
+                    };
+                    for &index in group {
+                        let result = self
+                            .checkout(worker)
+                            .and_then(|client| client.append_turn(ctx, &requests[index]));
+                        *results[index].lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+                    }
+                });
+            }
+        });
+
+        results
+            .into_iter()
+            .map(|slot| {
+                slot.into_inner()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .unwrap_or(Err(Error::ConnectionClosed))
+            })
+            .collect()
+    }
+
+    pub fn close(&self) -> Result<()> {
+        for slot in &self.clients {
+            let client = slot.lock().map_err(|_| Error::ClientClosed)?;
+            client.close()?;
+        }
+        Ok(())
+    }
+
+    /// Returns pooled client `index`, redialing it first if its connection
+    /// was poisoned.
+    fn checkout(&self, index: usize) -> Result<Arc<Client>> {
+        let mut slot = self.clients[index]
+            .lock()
+            .map_err(|_| Error::ClientClosed)?;
+        if slot.is_poisoned() {
+            *slot = Arc::new(slot.redial()?);
+        }
+        Ok(slot.clone())
+    }
+}
+
+/// Dials `size` plain TCP connections to `addr`.
+pub fn dial_pool(
+    addr: &str,
+    size: usize,
+    opts: impl IntoIterator<Item = ClientOption>,
+) -> Result<ClientPool> {
+    dial(addr, opts)?.into_pool(size)
+}
+
+/// Dials `size` TLS connections to `addr`.
+pub fn dial_tls_pool(
+    addr: &str,
+    size: usize,
+    opts: impl IntoIterator<Item = ClientOption>,
+) -> Result<ClientPool> {
+    dial_tls(addr, opts)?.into_pool(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::TurnId;
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_ERROR};
+    use crate::test_util::{error_payload, spawn_multi_server};
+
+    /// Acks each append with a per-context turn counter; context 13 is
+    /// rejected and the first append to context 99 kills its connection.
+    fn spawn_append_server() -> String {
+        let turns: Mutex<HashMap<u64, u64>> = Mutex::new(HashMap::new());
+        let crashed = AtomicBool::new(false);
+        spawn_multi_server(move |req| {
+            assert_eq!(req.header.msg_type, MSG_APPEND_TURN);
+            let context_id = u64::from_le_bytes(req.payload[..8].try_into().unwrap());
+            if context_id == 13 {
+                return (MSG_ERROR, error_payload(422, "rejected"));
+            }
+            if context_id == 99 && !crashed.swap(true, Ordering::SeqCst) {
+                panic!("simulated connection failure");
+            }
+            let mut turns = turns.lock().unwrap();
+            let turn_id = turns.entry(context_id).or_insert(0);
+            *turn_id += 1;
+            let mut ack = context_id.to_le_bytes().to_vec();
+            ack.extend_from_slice(&turn_id.to_le_bytes());
+            ack.extend_from_slice(&1u32.to_le_bytes());
+            ack.extend_from_slice(&[0u8; 32]);
+            (MSG_APPEND_TURN, ack)
+        })
+    }
+
+    #[test]
+    fn append_many_preserves_input_and_per_context_order() {
+        let pool = dial_pool(&spawn_append_server(), 3, Vec::new()).unwrap();
+        assert_eq!(pool.len(), 3);
+        let requests: Vec<_> = (0..40u64)
+            .map(|i| AppendRequest::new(ContextId::new(1 + i % 5), "test", 1, vec![0x90]))
+            .collect();
+
+        let results = pool.append_many(&RequestContext::background(), requests.clone());
+        assert_eq!(results.len(), requests.len());
+        let mut last_turn: HashMap<ContextId, TurnId> = HashMap::new();
+        for (req, result) in requests.iter().zip(&results) {
+            let result = result.as_ref().unwrap();
+            assert_eq!(result.context_id, req.context_id);
+            let last = last_turn
+                .insert(req.context_id, result.turn_id)
+                .unwrap_or_default();
+            assert_eq!(
+                result.turn_id.get(),
+                last.get() + 1,
+                "context {}",
+                req.context_id
+            );
+        }
+        pool.close().unwrap();
+    }
+
+    #[test]
+    fn append_many_isolates_failures() {
+        let pool = dial_pool(&spawn_append_server(), 2, Vec::new()).unwrap();
+        let requests = vec![
+            AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]),
+            AppendRequest::new(ContextId::new(13), "test", 1, vec![0x90]),
+            AppendRequest::new(ContextId::new(99), "test", 1, vec![0x90]),
+            AppendRequest::new(ContextId::new(2), "test", 1, vec![0x90]),
+            AppendRequest::new(ContextId::new(99), "test", 1, vec![0x90]),
+            AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]),
+        ];
+
+        let results = pool.append_many(&RequestContext::background(), requests);
+        assert_eq!(results[0].as_ref().unwrap().turn_id, TurnId::new(1));
+        assert!(crate::error::is_server_error(
+            results[1].as_ref().unwrap_err(),
+            422
+        ));
+        assert!(crate::reconnect::is_connection_error(
+            results[2].as_ref().unwrap_err()
+        ));
+        assert_eq!(results[3].as_ref().unwrap().turn_id, TurnId::new(1));
+        // The broken connection was redialed for the next append.
+        assert_eq!(results[4].as_ref().unwrap().turn_id, TurnId::new(1));
+        assert_eq!(results[5].as_ref().unwrap().turn_id, TurnId::new(2));
+        assert!((0..pool.len()).all(|_| !pool.get().unwrap().is_poisoned()));
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opt-in background prefetch of context tails for [`Client::get_last`].
+//!
+//! [`Client::prefetch`] fetches a context's most recent turns on a dedicated,
+//! lazily dialed connection and caches them on the client. A later
+//! `get_last` for that context is answered from the cache when:
+//!
+//! - the cached tail covers the requested `limit` and no `min_sequence` is
+//!   set, and
+//! - the tail is younger than the client's prefetch staleness window
+//!   ([`with_prefetch_staleness`](crate::client::with_prefetch_staleness)),
+//!   or a GET_HEAD round trip shows the context head is still the newest
+//!   cached turn.
+//!
+//! Within the staleness window the cache is trusted without asking the
+//! server, so turns appended by *other* clients may be missed for up to that
+//! long. Appends made through the same client always invalidate the entry.
+//! A `get_last` issued while a prefetch is still in flight waits for it
+//! instead of sending a duplicate request. A tail holding a turn whose TTL
+//! has since passed is refetched, so expired turns are not served from it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::reconnect::DialFunc;
+use crate::turn::{GetLastOptions, TurnRecord};
+
+/// Per-client prefetch state.
+pub(crate) struct PrefetchCache {
+    entries: Mutex<Entries>,
+    ready: Condvar,
+    /// Background connection, dialed by the first prefetch.
+    conn: Mutex<Option<Arc<Client>>>,
+    staleness: Duration,
+}
+
+#[derive(Default)]
+struct Entries {
+    next_generation: u64,
+    by_context: HashMap<ContextId, Entry>,
+}
+
+enum Entry {
+    /// A prefetch is in flight. Completions are only stored if the entry
+    /// still carries their generation, so an invalidation wins the race.
+    Pending(u64),
+    Ready(Tail),
+}
+
+#[derive(Clone)]
+struct Tail {
+    limit: u32,
+    records: Vec<TurnRecord>,
+    fetched_at: Instant,
+}
+
+impl Tail {
+    fn head_turn_id(&self) -> TurnId {
+        self.records
+            .last()
+            .map_or(TurnId::default(), |record| record.turn_id)
+    }
+
+    /// Whether a cached turn has expired since the tail was fetched.
+    fn has_expired(&self) -> bool {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.records
+            .iter()
+            .any(|record| record.expires_at_unix_ms.is_some_and(|at| at <= now_ms))
+    }
+
+    fn covers(&self, limit: u32) -> bool {
+        // A short tail is the whole context, so it covers any limit.
+        limit <= self.limit || self.records.len() < self.limit as usize
+    }
+
+    fn last(&self, limit: u32) -> Vec<TurnRecord> {
+        let skip = self.records.len().saturating_sub(limit as usize);
+        self.records[skip..].to_vec()
+    }
+}
+
+impl PrefetchCache {
+    pub(crate) fn new(staleness: Duration) -> Self {
+        Self {
+            entries: Mutex::new(Entries::default()),
+            ready: Condvar::new(),
+            conn: Mutex::new(None),
+            staleness,
+        }
+    }
+
+    /// Drops any cached or in-flight tail for `context_id`.
+    pub(crate) fn invalidate(&self, context_id: ContextId) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.by_context.remove(&context_id).is_some() {
+            self.ready.notify_all();
+        }
+    }
+
+    /// Drops every cached or in-flight tail.
+    pub(crate) fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if !entries.by_context.is_empty() {
+            entries.by_context.clear();
+            self.ready.notify_all();
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(conn) = conn {
+            let _ = conn.close();
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.by_context.clear();
+        self.ready.notify_all();
+    }
+
+    fn start(&self, context_id: ContextId) -> u64 {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.next_generation += 1;
+        let generation = entries.next_generation;
+        entries
+            .by_context
+            .insert(context_id, Entry::Pending(generation));
+        generation
+    }
+
+    fn finish(&self, context_id: ContextId, generation: u64, tail: Option<Tail>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if !matches!(entries.by_context.get(&context_id), Some(Entry::Pending(g)) if *g == generation)
+        {
+            return;
+        }
+        match tail {
+            Some(tail) => entries.by_context.insert(context_id, Entry::Ready(tail)),
+            None => entries.by_context.remove(&context_id),
+        };
+        self.ready.notify_all();
+    }
+
+    /// Waits out an in-flight prefetch and returns the cached tail, if any.
+    fn wait(&self, context_id: ContextId, deadline: Instant) -> Option<Tail> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match entries.by_context.get(&context_id)? {
+                Entry::Ready(tail) => return Some(tail.clone()),
+                Entry::Pending(_) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    entries = self
+                        .ready
+                        .wait_timeout(entries, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            }
+        }
+    }
+
+    fn touch(&self, context_id: ContextId, head_turn_id: TurnId) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(Entry::Ready(tail)) = entries.by_context.get_mut(&context_id) {
+            if tail.head_turn_id() == head_turn_id {
+                tail.fetched_at = Instant::now();
+            }
+        }
+    }
+
+    fn connection(&self, dial: &DialFunc) -> Result<Arc<Client>> {
+        let mut conn = self.conn.lock().map_err(|_| Error::ClientClosed)?;
+        if let Some(client) = conn.as_ref().filter(|client| !client.is_poisoned()) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(dial()?);
+        *conn = Some(client.clone());
+        Ok(client)
+    }
+}
+
+impl Client {
+    /// Starts fetching the last `limit` turns of `context_id` (with payloads)
+    /// in the background so a following [`Client::get_last`] can be answered
+    /// from memory. Returns immediately; fetch errors are discarded and the
+    /// next `get_last` simply goes to the server.
+    ///
+    /// See the [`prefetch`](crate::prefetch) module for when cached tails are
+    /// served and how long they may be stale.
+    pub fn prefetch(&self, context_id: ContextId, limit: u32) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::ClientClosed);
+        }
+        let cache = self.prefetch_cache().clone();
+        let dial = self.dialer();
+        let generation = cache.start(context_id);
+        let opts = GetLastOptions {
+            limit: if limit == 0 { 10 } else { limit },
+            include_payload: true,
+            ..Default::default()
+        };
+        thread::spawn(move || {
+            let limit = opts.limit;
+            let tail = cache
+                .connection(&dial)
+                .and_then(|conn| conn.get_last(&RequestContext::background(), context_id, opts))
+                .ok()
+                .map(|records| Tail {
+                    limit,
+                    records,
+                    fetched_at: Instant::now(),
+                });
+            cache.finish(context_id, generation, tail);
+        });
+        Ok(())
+    }
+
+    /// Answers a `get_last` from a prefetched tail when that is safe.
+    pub(crate) fn prefetched_last(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: &GetLastOptions,
+    ) -> Result<Option<Vec<TurnRecord>>> {
+        // The cache holds default (compacted, unexpired) head reads in the
+        // client's own namespace only.
+        if self.namespace_of(ctx) != self.namespace()
+            || !opts.min_sequence.is_none()
+            || opts.include_compacted
+            || opts.include_expired
+            || opts.before_turn_id.is_some()
+        {
+            return Ok(None);
+        }
+        let cache = self.prefetch_cache();
+        let tail = match cache.wait(context_id, self.compute_deadline(ctx)?) {
+            Some(tail) if tail.covers(opts.limit) => tail,
+            _ => return Ok(None),
+        };
+        if tail.has_expired() {
+            cache.invalidate(context_id);
+            return Ok(None);
+        }
+        if tail.fetched_at.elapsed() >= cache.staleness {
+            let head = self.get_head(ctx, context_id)?;
+            if head.head_turn_id != tail.head_turn_id() {
+                cache.invalidate(context_id);
+                return Ok(None);
+            }
+            cache.touch(context_id, head.head_turn_id);
+        }
+        Ok(Some(tail.last(opts.limit)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::client::{dial, with_prefetch_staleness};
+    use crate::protocol::{MSG_APPEND_TURN, MSG_GET_HEAD, MSG_GET_LAST};
+    use crate::test_util::{spawn_multi_server, turn_records_payload};
+    use crate::turn::AppendRequest;
+
+    /// Serves a single context whose head is `head`, counting requests by type.
+    struct FakeContext {
+        head: AtomicU64,
+        get_last: AtomicUsize,
+        get_head: AtomicUsize,
+    }
+
+    fn serve_context(head: u64) -> (String, Arc<FakeContext>) {
+        let state = Arc::new(FakeContext {
+            head: AtomicU64::new(head),
+            get_last: AtomicUsize::new(0),
+            get_head: AtomicUsize::new(0),
+        });
+        let addr = spawn_multi_server({
+            let state = state.clone();
+            move |req| {
+                let head = state.head.load(Ordering::SeqCst);
+                match req.header.msg_type {
+                    MSG_GET_LAST => {
+                        state.get_last.fetch_add(1, Ordering::SeqCst);
+                        let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap());
+                        let turns = vec![&[0x90u8][..]; head.min(limit as u64) as usize];
+                        (MSG_GET_LAST, turn_records_payload(&turns))
+                    }
+                    MSG_GET_HEAD => {
+                        state.get_head.fetch_add(1, Ordering::SeqCst);
+                        let mut resp = 1u64.to_le_bytes().to_vec();
+                        resp.extend_from_slice(&head.to_le_bytes());
+                        resp.extend_from_slice(&(head as u32).to_le_bytes());
+                        (MSG_GET_HEAD, resp)
+                    }
+                    MSG_APPEND_TURN => {
+                        let head = state.head.fetch_add(1, Ordering::SeqCst) + 1;
+                        let mut ack = 1u64.to_le_bytes().to_vec();
+                        ack.extend_from_slice(&head.to_le_bytes());
+                        ack.extend_from_slice(&(head as u32).to_le_bytes());
+                        ack.extend_from_slice(&[0u8; 32]);
+                        (MSG_APPEND_TURN, ack)
+                    }
+                    other => panic!("unexpected request type {other}"),
+                }
+            }
+        });
+        (addr, state)
+    }
+
+    fn opts(limit: u32) -> GetLastOptions {
+        GetLastOptions {
+            limit,
+            include_payload: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prefetched_tail_answers_get_last_from_memory() {
+        let (addr, state) = serve_context(3);
+        let client = dial(
+            &addr,
+            vec![with_prefetch_staleness(Duration::from_secs(60))],
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+
+        client.prefetch(ContextId::new(1), 8).unwrap();
+        let turns = client.get_last(&ctx, ContextId::new(1), opts(2)).unwrap();
+        assert_eq!(
+            turns.iter().map(|t| t.turn_id.get()).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            client
+                .get_last(&ctx, ContextId::new(1), opts(10))
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(state.get_last.load(Ordering::SeqCst), 1);
+        assert_eq!(state.get_head.load(Ordering::SeqCst), 0);
+
+        // Tails are never used to satisfy a read-your-writes request.
+        let token = crate::turn::ConsistencyToken::from_sequence(1);
+        client
+            .get_last(&ctx, ContextId::new(1), opts(2).min_sequence(token))
+            .unwrap();
+        assert_eq!(state.get_last.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn append_through_client_invalidates_prefetched_tail() {
+        let (addr, state) = serve_context(3);
+        let client = dial(
+            &addr,
+            vec![with_prefetch_staleness(Duration::from_secs(60))],
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+
+        client.prefetch(ContextId::new(1), 8).unwrap();
+        client.get_last(&ctx, ContextId::new(1), opts(8)).unwrap();
+        client
+            .append_turn(
+                &ctx,
+                &AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]),
+            )
+            .unwrap();
+        let turns = client.get_last(&ctx, ContextId::new(1), opts(8)).unwrap();
+        assert_eq!(turns.last().unwrap().turn_id, TurnId::new(4));
+        assert_eq!(state.get_last.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stale_tail_is_served_only_while_head_is_unchanged() {
+        let (addr, state) = serve_context(3);
+        let client = dial(&addr, vec![with_prefetch_staleness(Duration::ZERO)]).unwrap();
+        let ctx = RequestContext::background();
+
+        client.prefetch(ContextId::new(1), 8).unwrap();
+        assert_eq!(
+            client
+                .get_last(&ctx, ContextId::new(1), opts(8))
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(state.get_last.load(Ordering::SeqCst), 1);
+        assert_eq!(state.get_head.load(Ordering::SeqCst), 1);
+
+        // Another writer moves the head.
+        state.head.store(5, Ordering::SeqCst);
+        assert_eq!(
+            client
+                .get_last(&ctx, ContextId::new(1), opts(8))
+                .unwrap()
+                .len(),
+            5
+        );
+        assert_eq!(state.get_last.load(Ordering::SeqCst), 2);
+        assert_eq!(state.get_head.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn get_last_without_prefetch_never_consults_cache() {
+        let (addr, state) = serve_context(2);
+        let client = dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        client.get_last(&ctx, ContextId::new(1), opts(8)).unwrap();
+        client.get_last(&ctx, ContextId::new(1), opts(8)).unwrap();
+        assert_eq!(state.get_last.load(Ordering::SeqCst), 2);
+        assert_eq!(state.get_head.load(Ordering::SeqCst), 0);
+    }
+}
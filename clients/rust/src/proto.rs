@@ -0,0 +1,123 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Frame-level API for protocol extensions.
+//!
+//! Servers that add their own message types can be spoken to without
+//! vendoring the client: [`Client::call_raw`] sends an arbitrary opcode over
+//! the client's connection, with the same handshake, timeouts, checksums,
+//! request metadata and metrics as built-in calls, and returns the response
+//! payload whatever its opcode. [`ReconnectingClient::call_raw`] adds
+//! redial and retry.
+//!
+//! The frame codec and request id allocator are re-exported here for tools
+//! that drive their own connection.
+//!
+//! [`ReconnectingClient::call_raw`]: crate::ReconnectingClient::call_raw
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+pub use crate::protocol::{
+    decode_frame, encode_frame, frame_checksum, read_frame, read_frame_with_limit,
+    verify_frame_checksum, write_frame, write_frame_with_checksum, Frame, FrameHeader, FLAG_CRC32C,
+    FLAG_METADATA, FRAME_CHECKSUM_LEN, FRAME_HEADER_LEN, MAX_FRAME_SIZE, MSG_ERROR, MSG_HELLO,
+};
+
+/// Allocates request ids for one connection: 1, 2, 3, ... Responses echo
+/// the id of the request they answer.
+#[derive(Debug, Default)]
+pub struct RequestIds(AtomicU64);
+
+impl RequestIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+impl Client {
+    /// Sends `body` as a request with message type `opcode` and returns the
+    /// response payload. Any response opcode is accepted except
+    /// [`MSG_ERROR`], which fails like other calls with [`Error::Server`].
+    ///
+    /// HELLO and ERROR cannot be sent; the session is negotiated at dial.
+    pub fn call_raw(&self, ctx: &RequestContext, opcode: u16, body: &[u8]) -> Result<Vec<u8>> {
+        check_opcode(opcode)?;
+        Ok(self.send_request(ctx, opcode, body)?.payload)
+    }
+
+    /// Like [`Client::call_raw`], but reads the response into a refcounted
+    /// buffer.
+    #[cfg(feature = "bytes")]
+    pub fn call_raw_shared(
+        &self,
+        ctx: &RequestContext,
+        opcode: u16,
+        body: &[u8],
+    ) -> Result<bytes::Bytes> {
+        check_opcode(opcode)?;
+        self.send_request_shared(ctx, opcode, body, false)
+    }
+}
+
+pub(crate) fn check_opcode(opcode: u16) -> Result<()> {
+    if opcode == MSG_HELLO || opcode == MSG_ERROR {
+        return Err(Error::encode(format!("opcode {opcode} is reserved")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{error_payload, spawn_scripted_server};
+    use crate::{dial, ReconnectingClient};
+
+    #[test]
+    fn raw_calls_accept_unknown_response_opcodes() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (201, b"pong".to_vec()),
+            (MSG_ERROR, error_payload(400, "bad ping")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        assert_eq!(client.call_raw(&ctx, 200, b"ping").unwrap(), b"pong");
+        let err = client.call_raw(&ctx, 200, b"").unwrap_err();
+        assert!(crate::is_server_error(&err, 400), "got {err:?}");
+        for opcode in [MSG_HELLO, MSG_ERROR] {
+            let err = client.call_raw(&ctx, opcode, b"").unwrap_err();
+            assert!(matches!(err, Error::Encode { .. }), "got {err:?}");
+        }
+        assert!(!client.is_poisoned());
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header.msg_type, 200);
+        assert_eq!(requests[0].payload, b"ping");
+        assert!(requests[1].header.req_id > requests[0].header.req_id);
+    }
+
+    #[test]
+    fn reconnecting_client_forwards_raw_calls() {
+        let (addr, handle) = spawn_scripted_server(vec![(77, vec![1, 2, 3])]);
+        let client: ReconnectingClient = crate::dial_reconnecting(&addr, [], []).unwrap();
+        let response = client
+            .call_raw(&RequestContext::background(), 300, &[9])
+            .unwrap();
+        assert_eq!(response, [1, 2, 3]);
+        client.close().unwrap();
+        assert_eq!(handle.join().unwrap()[0].header.msg_type, 300);
+    }
+
+    #[test]
+    fn request_ids_start_at_one() {
+        let ids = RequestIds::new();
+        assert_eq!([ids.next(), ids.next(), ids.next()], [1, 2, 3]);
+    }
+}
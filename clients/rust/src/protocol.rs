@@ -4,8 +4,6 @@
 use std::io::{Read, Write};
 use std::time::Duration;
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-
 use crate::error::{Error, Result};
 
 pub const MSG_HELLO: u16 = 1;
@@ -17,17 +15,283 @@ pub const MSG_GET_LAST: u16 = 6;
 pub const MSG_GET_BLOB: u16 = 9;
 pub const MSG_ATTACH_FS: u16 = 10;
 pub const MSG_PUT_BLOB: u16 = 11;
+pub const MSG_CTX_CREATE_ALIAS: u16 = 12;
+pub const MSG_RESOLVE_ALIAS: u16 = 13;
+pub const MSG_CTX_COMPACT: u16 = 14;
+pub const MSG_GET_TURN: u16 = 15;
+pub const MSG_GET_QUOTAS: u16 = 16;
+pub const MSG_SEARCH_TURNS: u16 = 17;
+pub const MSG_GET_CHILDREN: u16 = 18;
+pub const MSG_CTX_PRUNE: u16 = 19;
+pub const MSG_TURN_REDACT: u16 = 20;
+pub const MSG_TEXT_SEARCH: u16 = 21;
+pub const MSG_TYPE_HISTOGRAM: u16 = 22;
+pub const MSG_APPEND_MULTI: u16 = 23;
+pub const MSG_CONTEXT_STATS: u16 = 24;
+pub const MSG_GET_LINKED: u16 = 25;
+pub const MSG_CTX_ARCHIVE: u16 = 26;
+pub const MSG_CTX_RESTORE: u16 = 27;
+pub const MSG_LIST_CONTEXTS: u16 = 28;
+pub const MSG_CTX_DELETE_MANY: u16 = 29;
+pub const MSG_CTX_CREATE_WITH_TURN: u16 = 30;
+pub const MSG_GET_CONTEXT: u16 = 31;
+pub const MSG_LIST_CHILDREN: u16 = 32;
 pub const MSG_ERROR: u16 = 255;
 
+/// The protocol name of `msg_type`, e.g. `"GET_LAST"`, as used in
+/// [`Error::Unsupported`].
+pub fn msg_type_name(msg_type: u16) -> Option<&'static str> {
+    Some(match msg_type {
+        MSG_HELLO => "HELLO",
+        MSG_CTX_CREATE => "CTX_CREATE",
+        MSG_CTX_FORK => "CTX_FORK",
+        MSG_GET_HEAD => "GET_HEAD",
+        MSG_APPEND_TURN => "APPEND_TURN",
+        MSG_GET_LAST => "GET_LAST",
+        MSG_GET_BLOB => "GET_BLOB",
+        MSG_ATTACH_FS => "ATTACH_FS",
+        MSG_PUT_BLOB => "PUT_BLOB",
+        MSG_CTX_CREATE_ALIAS => "CTX_CREATE_ALIAS",
+        MSG_RESOLVE_ALIAS => "RESOLVE_ALIAS",
+        MSG_CTX_COMPACT => "CTX_COMPACT",
+        MSG_GET_TURN => "GET_TURN",
+        MSG_GET_QUOTAS => "GET_QUOTAS",
+        MSG_SEARCH_TURNS => "SEARCH_TURNS",
+        MSG_GET_CHILDREN => "GET_CHILDREN",
+        MSG_CTX_PRUNE => "CTX_PRUNE",
+        MSG_TURN_REDACT => "TURN_REDACT",
+        MSG_TEXT_SEARCH => "TEXT_SEARCH",
+        MSG_TYPE_HISTOGRAM => "TYPE_HISTOGRAM",
+        MSG_APPEND_MULTI => "APPEND_MULTI",
+        MSG_CONTEXT_STATS => "CONTEXT_STATS",
+        MSG_GET_LINKED => "GET_LINKED",
+        MSG_CTX_ARCHIVE => "CTX_ARCHIVE",
+        MSG_CTX_RESTORE => "CTX_RESTORE",
+        MSG_LIST_CONTEXTS => "LIST_CONTEXTS",
+        MSG_CTX_DELETE_MANY => "CTX_DELETE_MANY",
+        MSG_CTX_CREATE_WITH_TURN => "CTX_CREATE_WITH_TURN",
+        MSG_GET_CONTEXT => "GET_CONTEXT",
+        MSG_LIST_CHILDREN => "LIST_CHILDREN",
+        MSG_ERROR => "ERROR",
+        _ => return None,
+    })
+}
+
+/// Whether a `msg_type` request changes server state, and so is refused by a
+/// read-only client (see [`crate::client::with_read_only`]). `None` for
+/// message types this client does not know; every type named by
+/// [`msg_type_name`] must be classified here.
+pub fn msg_type_mutates(msg_type: u16) -> Option<bool> {
+    Some(match msg_type {
+        MSG_CTX_CREATE
+        | MSG_CTX_FORK
+        | MSG_APPEND_TURN
+        | MSG_ATTACH_FS
+        | MSG_PUT_BLOB
+        | MSG_CTX_CREATE_ALIAS
+        | MSG_CTX_COMPACT
+        | MSG_CTX_PRUNE
+        | MSG_TURN_REDACT
+        | MSG_APPEND_MULTI
+        | MSG_CTX_ARCHIVE
+        | MSG_CTX_RESTORE
+        | MSG_CTX_DELETE_MANY
+        | MSG_CTX_CREATE_WITH_TURN => true,
+        MSG_HELLO | MSG_GET_HEAD | MSG_GET_LAST | MSG_GET_BLOB | MSG_RESOLVE_ALIAS
+        | MSG_GET_TURN | MSG_GET_QUOTAS | MSG_SEARCH_TURNS | MSG_GET_CHILDREN | MSG_TEXT_SEARCH
+        | MSG_TYPE_HISTOGRAM | MSG_CONTEXT_STATS | MSG_GET_LINKED | MSG_LIST_CONTEXTS
+        | MSG_GET_CONTEXT | MSG_LIST_CHILDREN | MSG_ERROR => false,
+        _ => return None,
+    })
+}
+
+/// Error code returned when the HELLO bearer token is missing or rejected.
+pub const ERROR_UNAUTHENTICATED: u32 = 401;
+
+/// Error code returned for a turn deleted by pruning.
+pub const ERROR_PRUNED: u32 = 410;
+
+/// Error code returned for reads of, and appends to, an archived context.
+pub const ERROR_CONTEXT_ARCHIVED: u32 = 423;
+
+/// Error code returned when a read's `min_sequence` was not reached in time.
+pub const ERROR_REPLICA_LAGGING: u32 = 425;
+
+/// Error code returned when a request would exceed a tenant quota.
+pub const ERROR_QUOTA_EXCEEDED: u32 = 429;
+
+/// ERROR frame trailer flag: the request may be retried.
+pub const ERROR_FLAG_RETRYABLE: u32 = 1;
+
 pub const ENCODING_MSGPACK: u32 = 1;
+/// Turn payload encoding for CBOR (RFC 8949); decoded with the `cbor` feature.
+pub const ENCODING_CBOR: u32 = 2;
+/// Turn payload encoding for a client-side encryption envelope; the
+/// plaintext's encoding is inside (see [`crate::encryption`]).
+pub const ENCODING_ENCRYPTED: u32 = 3;
+/// Turn payload encoding for a client-side delta against an earlier turn;
+/// the full payload's encoding is inside (see [`crate::delta`]).
+pub const ENCODING_DELTA: u32 = 4;
 pub const COMPRESSION_NONE: u32 = 0;
 pub const COMPRESSION_ZSTD: u32 = 1;
 
 pub const DEFAULT_DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a dial waits on one address before also trying the next.
+pub const DEFAULT_HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
 pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// How long a prefetched `get_last` tail is trusted without a head check.
+pub const DEFAULT_PREFETCH_STALENESS: Duration = Duration::from_secs(1);
+
 pub const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024; // 64 MiB
 
+/// Default nesting limit for decoded msgpack payloads. Deeper values are
+/// rejected before they can exhaust the stack.
+pub const MAX_DECODE_DEPTH: usize = 128;
+
+/// Default capacity of a connection's buffered reader.
+pub const DEFAULT_READ_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Default capacity a connection's request scratch buffer keeps between
+/// requests. Larger frames still go out in one write, but the extra capacity
+/// is released afterwards.
+pub const DEFAULT_WRITE_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Most requests a pipelined batch keeps in flight before reading responses.
+pub const PIPELINE_WINDOW: usize = 64;
+
+/// APPEND_TURN request flag: a 32-byte fs_root_hash follows the body.
+pub const APPEND_FLAG_FS_ROOT: u16 = 1 << 0;
+
+/// APPEND_TURN request flag: a writer stamp (`writer_id`, `writer_seq`)
+/// follows the body and any fs_root_hash.
+pub const APPEND_FLAG_WRITER: u16 = 1 << 1;
+
+/// APPEND_TURN request flag: a time to live (`ttl_ms`) follows the body and
+/// any writer stamp.
+pub const APPEND_FLAG_TTL: u16 = 1 << 2;
+
+/// APPEND_TURN request flag: skip the append when the context already holds
+/// the content. The ack ends with `appended u8`. Only sent to servers that
+/// echoed [`FLAG_DEDUP`].
+pub const APPEND_FLAG_DEDUP: u16 = 1 << 3;
+
+/// APPEND_TURN request flag: the turn's links (see [`crate::links`]) follow
+/// the body and any ttl. Only sent to servers that echoed [`FLAG_LINKS`].
+pub const APPEND_FLAG_LINKS: u16 = 1 << 4;
+
+/// GET_LAST request flag: walk past compaction boundaries.
+pub const GET_LAST_INCLUDE_COMPACTED: u32 = 1 << 0;
+
+/// GET_LAST request flag: return expired turns instead of skipping them.
+pub const GET_LAST_INCLUDE_EXPIRED: u32 = 1 << 1;
+
+/// GET_LAST request flag: `before_turn_id` follows the flags; the server
+/// returns only older turns.
+pub const GET_LAST_BEFORE: u32 = 1 << 2;
+
+/// GET_LAST request flag: `min_depth u32` and `max_depth u32` follow any
+/// `before_turn_id`; the server returns only turns at those depths. Only sent
+/// to servers that echoed [`FLAG_DEPTH_FILTER`].
+pub const GET_LAST_DEPTH_RANGE: u32 = 1 << 3;
+
+/// GET_LAST request flag: `fields u32` follows any depth range; response
+/// items carry `turn_id` and only those `TURN_FIELD_*` fields. Only sent to
+/// servers that echoed [`FLAG_PROJECTION`].
+pub const GET_LAST_PROJECTION: u32 = 1 << 4;
+
+/// Projected GET_LAST field: `parent_turn_id`.
+pub const TURN_FIELD_PARENT: u32 = 1 << 0;
+/// Projected GET_LAST field: `depth`.
+pub const TURN_FIELD_DEPTH: u32 = 1 << 1;
+/// Projected GET_LAST fields: `declared_type_id` and `declared_type_version`.
+pub const TURN_FIELD_TYPE: u32 = 1 << 2;
+/// Projected GET_LAST fields: `encoding` and `compression`.
+pub const TURN_FIELD_ENCODING: u32 = 1 << 3;
+/// Projected GET_LAST field: `uncompressed_len`.
+pub const TURN_FIELD_SIZE: u32 = 1 << 4;
+/// Projected GET_LAST field: `content_hash_b3_256`.
+pub const TURN_FIELD_CONTENT_HASH: u32 = 1 << 5;
+
+/// GET_LAST `payload_len` sentinel: the server withheld a payload larger than
+/// the request's `max_payload_bytes`; `uncompressed_len` still gives its size.
+pub const PAYLOAD_OMITTED: u32 = u32::MAX;
+
+/// Size of the fixed frame header on the wire.
+pub const FRAME_HEADER_LEN: usize = 16;
+
+/// Frame flag: the payload is followed by a CRC32C trailer.
+///
+/// Requested by the client on HELLO and echoed by servers that support it;
+/// once echoed, every later frame in both directions carries the trailer.
+pub const FLAG_CRC32C: u16 = 1 << 15;
+
+/// Frame flag: the payload starts with a request metadata block (see
+/// [`encode_frame_metadata`]).
+///
+/// Negotiated like [`FLAG_CRC32C`]: requested on HELLO and echoed by servers
+/// that support it. Only request frames ever carry the block.
+pub const FLAG_METADATA: u16 = 1 << 14;
+
+/// Frame flag, HELLO only: the server answers SEARCH_TURNS. Negotiated like
+/// [`FLAG_CRC32C`].
+pub const FLAG_SEARCH: u16 = 1 << 13;
+
+/// Frame flag, HELLO only: append acks carry the turn's timestamp, stored
+/// size and the new context head. Negotiated like [`FLAG_CRC32C`]; acks
+/// without them still decode.
+pub const FLAG_APPEND_META: u16 = 1 << 12;
+
+/// Frame flag, HELLO only: the server honours [`APPEND_FLAG_DEDUP`].
+/// Negotiated like [`FLAG_CRC32C`].
+pub const FLAG_DEDUP: u16 = 1 << 11;
+
+/// Frame flag, HELLO only: turn records read on the connection carry their
+/// creation time. Negotiated like [`FLAG_CRC32C`]; responses without it
+/// still decode.
+pub const FLAG_TIMESTAMPS: u16 = 1 << 10;
+
+/// Frame flag, HELLO only: turn records read on the connection carry their
+/// redaction, if any. Negotiated like [`FLAG_CRC32C`]; responses without it
+/// still decode, but redacted turns are then unmarked.
+pub const FLAG_REDACTIONS: u16 = 1 << 9;
+
+/// Frame flag, HELLO only: the server honours [`GET_LAST_DEPTH_RANGE`].
+/// Negotiated like [`FLAG_CRC32C`].
+pub const FLAG_DEPTH_FILTER: u16 = 1 << 8;
+
+/// Frame flag, HELLO only: the server honours [`GET_LAST_PROJECTION`].
+/// Negotiated like [`FLAG_CRC32C`].
+pub const FLAG_PROJECTION: u16 = 1 << 7;
+
+/// Frame flag: the payload is compressed with the codec negotiated on
+/// HELLO (see [`crate::compression`]).
+///
+/// Requested on HELLO, whose payload then lists the client's codecs, and
+/// echoed by servers that share one; once echoed, either side may set it on
+/// any later frame. Frames without it are plain.
+pub const FLAG_COMPRESSED: u16 = 1 << 6;
+
+/// Frame flag, HELLO only: the server stores turn links, and turn records
+/// read on the connection carry them. Negotiated like [`FLAG_CRC32C`];
+/// implies [`FLAG_REDACTIONS`], whose trailer the links trailer follows.
+pub const FLAG_LINKS: u16 = 1 << 5;
+
+/// Frame flag, HELLO only: the server scopes each request to the namespace
+/// in its [`NAMESPACE_KEY`](crate::client::NAMESPACE_KEY) metadata entry,
+/// so context ids only name contexts within their namespace. Negotiated
+/// like [`FLAG_CRC32C`]; only meaningful alongside [`FLAG_METADATA`].
+pub const FLAG_NAMESPACES: u16 = 1 << 4;
+
+/// SEARCH_TURNS match mode: the field equals a msgpack operand.
+pub const SEARCH_MATCH_EQUALS: u32 = 0;
+
+/// SEARCH_TURNS match mode: the field is a string containing a UTF-8 operand.
+pub const SEARCH_MATCH_CONTAINS: u32 = 1;
+
+/// Size of the CRC32C trailer counted in `len` of checksummed frames.
+pub const FRAME_CHECKSUM_LEN: usize = 4;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FrameHeader {
     pub len: u32,
@@ -36,12 +300,166 @@ pub struct FrameHeader {
     pub req_id: u64,
 }
 
+impl FrameHeader {
+    pub fn encode(&self) -> [u8; FRAME_HEADER_LEN] {
+        let mut out = [0u8; FRAME_HEADER_LEN];
+        out[0..4].copy_from_slice(&self.len.to_le_bytes());
+        out[4..6].copy_from_slice(&self.msg_type.to_le_bytes());
+        out[6..8].copy_from_slice(&self.flags.to_le_bytes());
+        out[8..16].copy_from_slice(&self.req_id.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8; FRAME_HEADER_LEN]) -> Self {
+        let [l0, l1, l2, l3, m0, m1, f0, f1, req_id @ ..] = *bytes;
+        Self {
+            len: u32::from_le_bytes([l0, l1, l2, l3]),
+            msg_type: u16::from_le_bytes([m0, m1]),
+            flags: u16::from_le_bytes([f0, f1]),
+            req_id: u64::from_le_bytes(req_id),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Frame {
     pub header: FrameHeader,
     pub payload: Vec<u8>,
 }
 
+/// Encodes a HELLO request: protocol version 1, `client_tag`, an empty
+/// metadata block, when given the session's bearer token and, when any,
+/// the frame compression codec ids the client accepts, most preferred first
+/// (after an empty token if there is none).
+pub(crate) fn encode_hello(client_tag: &str, bearer_token: Option<&str>, codecs: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(2 + 2 + client_tag.len() + 4);
+    payload.extend_from_slice(&1u16.to_le_bytes()); // protocol version
+    payload.extend_from_slice(&(client_tag.len() as u16).to_le_bytes());
+    payload.extend_from_slice(client_tag.as_bytes());
+    payload.extend_from_slice(&0u32.to_le_bytes()); // no metadata
+    if bearer_token.is_some() || !codecs.is_empty() {
+        let token = bearer_token.unwrap_or_default();
+        payload.extend_from_slice(&(token.len() as u32).to_le_bytes());
+        payload.extend_from_slice(token.as_bytes());
+    }
+    if !codecs.is_empty() {
+        payload.push(codecs.len() as u8);
+        payload.extend_from_slice(codecs);
+    }
+    payload
+}
+
+/// Appends one encoded frame (header and payload) to `buf`.
+pub fn encode_frame_into(
+    buf: &mut Vec<u8>,
+    msg_type: u16,
+    flags: u16,
+    req_id: u64,
+    payload: &[u8],
+) {
+    let header = FrameHeader {
+        len: payload.len() as u32,
+        msg_type,
+        flags,
+        req_id,
+    };
+    buf.reserve(FRAME_HEADER_LEN + payload.len());
+    buf.extend_from_slice(&header.encode());
+    buf.extend_from_slice(payload);
+}
+
+/// Appends one checksummed frame (header, payload and CRC32C trailer) to
+/// `buf`.
+pub fn encode_frame_with_checksum_into(
+    buf: &mut Vec<u8>,
+    msg_type: u16,
+    flags: u16,
+    req_id: u64,
+    payload: &[u8],
+) {
+    let header = FrameHeader {
+        len: (payload.len() + FRAME_CHECKSUM_LEN) as u32,
+        msg_type,
+        flags: flags | FLAG_CRC32C,
+        req_id,
+    };
+    let checksum = frame_checksum(&header, payload);
+    buf.reserve(FRAME_HEADER_LEN + payload.len() + FRAME_CHECKSUM_LEN);
+    buf.extend_from_slice(&header.encode());
+    buf.extend_from_slice(payload);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+}
+
+/// Appends a request metadata block to `buf`: count (u16), then per entry
+/// key_len (u16), key, value_len (u16), value, all UTF-8.
+///
+/// Fails with [`Error::Encode`] if there are more than `u16::MAX` entries or
+/// a key or value is longer than `u16::MAX` bytes.
+pub fn encode_frame_metadata<'a>(
+    buf: &mut Vec<u8>,
+    entries: impl ExactSizeIterator<Item = (&'a str, &'a str)>,
+) -> Result<()> {
+    fn put(buf: &mut Vec<u8>, field: &str) -> Result<()> {
+        let len = u16::try_from(field.len())
+            .map_err(|_| Error::encode(format!("metadata field longer than {} bytes", u16::MAX)))?;
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(field.as_bytes());
+        Ok(())
+    }
+
+    let count = u16::try_from(entries.len())
+        .map_err(|_| Error::encode(format!("more than {} metadata entries", u16::MAX)))?;
+    buf.extend_from_slice(&count.to_le_bytes());
+    for (key, value) in entries {
+        put(buf, key)?;
+        put(buf, value)?;
+    }
+    Ok(())
+}
+
+pub fn encode_frame(msg_type: u16, flags: u16, req_id: u64, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    encode_frame_into(&mut buf, msg_type, flags, req_id, payload);
+    buf
+}
+
+/// Decodes one frame from the start of `buf`, returning it together with the
+/// number of bytes consumed.
+///
+/// Applies the same validation as [`read_frame_with_limit`] without needing a
+/// stream, so the codec can be exercised on in-memory buffers.
+pub fn decode_frame(buf: &[u8], max_len: u32) -> Result<(Frame, usize)> {
+    if buf.is_empty() {
+        return Err(Error::ConnectionClosed);
+    }
+    let Some((header, rest)) = buf.split_first_chunk::<FRAME_HEADER_LEN>() else {
+        return Err(Error::protocol(format!(
+            "frame header truncated after {} of {FRAME_HEADER_LEN} bytes",
+            buf.len()
+        )));
+    };
+    let header = FrameHeader::decode(header);
+    if header.len > max_len {
+        return Err(Error::FrameTooLarge {
+            len: header.len,
+            max: max_len,
+        });
+    }
+    let Some(payload) = rest.get(..header.len as usize) else {
+        return Err(Error::protocol(format!(
+            "frame payload truncated (declared {} bytes, msg_type {})",
+            header.len, header.msg_type
+        )));
+    };
+    Ok((
+        Frame {
+            header,
+            payload: payload.to_vec(),
+        },
+        FRAME_HEADER_LEN + payload.len(),
+    ))
+}
+
 pub fn write_frame<W: Write>(
     writer: &mut W,
     msg_type: u16,
@@ -49,65 +467,359 @@ pub fn write_frame<W: Write>(
     req_id: u64,
     payload: &[u8],
 ) -> Result<()> {
-    writer.write_u32::<LittleEndian>(payload.len() as u32)?;
-    writer.write_u16::<LittleEndian>(msg_type)?;
-    writer.write_u16::<LittleEndian>(flags)?;
-    writer.write_u64::<LittleEndian>(req_id)?;
+    let header = FrameHeader {
+        len: payload.len() as u32,
+        msg_type,
+        flags,
+        req_id,
+    };
+    writer.write_all(&header.encode())?;
+    writer.write_all(payload)?;
+    Ok(())
+}
+
+/// Writes one frame followed by a CRC32C trailer over the header and payload.
+pub fn write_frame_with_checksum<W: Write>(
+    writer: &mut W,
+    msg_type: u16,
+    flags: u16,
+    req_id: u64,
+    payload: &[u8],
+) -> Result<()> {
+    let header = FrameHeader {
+        len: (payload.len() + FRAME_CHECKSUM_LEN) as u32,
+        msg_type,
+        flags: flags | FLAG_CRC32C,
+        req_id,
+    };
+    let checksum = frame_checksum(&header, payload);
+    writer.write_all(&header.encode())?;
     writer.write_all(payload)?;
+    writer.write_all(&checksum.to_le_bytes())?;
     Ok(())
 }
 
+/// CRC32C over the encoded header (as sent, trailer included in `len`) and
+/// the payload.
+pub fn frame_checksum(header: &FrameHeader, payload: &[u8]) -> u32 {
+    crc32c::crc32c_append(crc32c::crc32c(&header.encode()), payload)
+}
+
+/// Verifies the CRC32C trailer of a frame read on a checksummed connection.
+///
+/// Returns the header describing the frame without its trailer; the caller
+/// truncates the payload to the returned `len`. Frames missing the flag or
+/// trailer, or whose checksum does not match, fail with
+/// [`Error::FrameCorrupted`].
+pub fn verify_frame_checksum(header: &FrameHeader, payload: &[u8]) -> Result<FrameHeader> {
+    let body_len = payload.len().checked_sub(FRAME_CHECKSUM_LEN);
+    let (Some(body_len), true) = (body_len, header.flags & FLAG_CRC32C != 0) else {
+        return Err(Error::FrameCorrupted {
+            msg_type: header.msg_type,
+            req_id: header.req_id,
+        });
+    };
+    let (body, trailer) = payload.split_at(body_len);
+    let expected = u32::from_le_bytes(trailer.try_into().unwrap_or_default());
+    if frame_checksum(header, body) != expected {
+        return Err(Error::FrameCorrupted {
+            msg_type: header.msg_type,
+            req_id: header.req_id,
+        });
+    }
+    Ok(FrameHeader {
+        len: body_len as u32,
+        flags: header.flags & !FLAG_CRC32C,
+        ..*header
+    })
+}
+
 pub fn read_frame<R: Read>(reader: &mut R) -> Result<Frame> {
-    let len = match reader.read_u32::<LittleEndian>() {
-        Ok(v) => v,
-        Err(err) => {
-            if err.kind() == std::io::ErrorKind::UnexpectedEof {
-                return Err(Error::invalid_response("frame header truncated"));
+    read_frame_with_limit(reader, MAX_FRAME_SIZE)
+}
+
+/// Reads one frame, rejecting declared payload lengths above `max_len`
+/// before allocating for them.
+pub fn read_frame_with_limit<R: Read>(reader: &mut R, max_len: u32) -> Result<Frame> {
+    let header = read_frame_header(reader, max_len)?;
+    let mut payload = vec![0u8; header.len as usize];
+    read_payload(reader, &header, &mut payload)?;
+    Ok(Frame { header, payload })
+}
+
+/// Reads one frame's payload into `buf`, reusing its allocation, and returns
+/// the frame header.
+pub fn read_frame_into_vec<R: Read>(
+    reader: &mut R,
+    max_len: u32,
+    buf: &mut Vec<u8>,
+) -> Result<FrameHeader> {
+    let header = read_frame_header(reader, max_len)?;
+    buf.clear();
+    buf.resize(header.len as usize, 0);
+    read_payload(reader, &header, buf)?;
+    Ok(header)
+}
+
+/// Reads one frame into `buf`, returning the payload as a refcounted
+/// [`bytes::Bytes`].
+///
+/// `buf` is split off rather than copied, so once every `Bytes` handed out
+/// from a previous frame has been dropped its allocation is reused.
+#[cfg(feature = "bytes")]
+pub fn read_frame_into<R: Read>(
+    reader: &mut R,
+    max_len: u32,
+    buf: &mut bytes::BytesMut,
+) -> Result<(FrameHeader, bytes::Bytes)> {
+    let header = read_frame_header(reader, max_len)?;
+    buf.clear();
+    buf.resize(header.len as usize, 0);
+    read_payload(reader, &header, &mut buf[..])?;
+    Ok((header, buf.split().freeze()))
+}
+
+fn read_frame_header<R: Read>(reader: &mut R, max_len: u32) -> Result<FrameHeader> {
+    let mut header = [0u8; FRAME_HEADER_LEN];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Err(Error::ConnectionClosed),
+            Ok(0) => {
+                return Err(Error::protocol(format!(
+                    "frame header truncated after {filled} of {FRAME_HEADER_LEN} bytes"
+                )))
             }
-            return Err(Error::Io(err));
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(Error::Io(err)),
         }
-    };
-
-    if len > MAX_FRAME_SIZE {
-        return Err(Error::invalid_response(format!(
-            "frame size {} exceeds maximum {}",
-            len, MAX_FRAME_SIZE
-        )));
     }
 
-    let msg_type = reader
-        .read_u16::<LittleEndian>()
-        .map_err(map_header_error)?;
-    let flags = reader
-        .read_u16::<LittleEndian>()
-        .map_err(map_header_error)?;
-    let req_id = reader
-        .read_u64::<LittleEndian>()
-        .map_err(map_header_error)?;
+    let header = FrameHeader::decode(&header);
+    if header.len > max_len {
+        return Err(Error::FrameTooLarge {
+            len: header.len,
+            max: max_len,
+        });
+    }
+    Ok(header)
+}
 
-    let mut payload = vec![0u8; len as usize];
-    if let Err(err) = reader.read_exact(&mut payload) {
+fn read_payload<R: Read>(reader: &mut R, header: &FrameHeader, payload: &mut [u8]) -> Result<()> {
+    if let Err(err) = reader.read_exact(payload) {
         if err.kind() == std::io::ErrorKind::UnexpectedEof {
-            return Err(Error::invalid_response("frame payload truncated"));
+            return Err(Error::protocol(format!(
+                "frame payload truncated (declared {} bytes, msg_type {})",
+                header.len, header.msg_type
+            )));
         }
         return Err(Error::Io(err));
     }
+    Ok(())
+}
 
-    Ok(Frame {
-        header: FrameHeader {
-            len,
-            msg_type,
-            flags,
-            req_id,
-        },
-        payload,
-    })
+/// Bounds-checked little-endian reader over a response payload.
+///
+/// Every read validates the remaining length first, so malformed payloads
+/// surface as [`Error::Protocol`] naming the field instead of panicking or
+/// allocating based on an untrusted length.
+pub(crate) struct PayloadReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    what: &'static str,
 }
 
-fn map_header_error(err: std::io::Error) -> Error {
-    if err.kind() == std::io::ErrorKind::UnexpectedEof {
-        Error::invalid_response("frame header truncated")
-    } else {
-        Error::Io(err)
+impl<'a> PayloadReader<'a> {
+    pub(crate) fn new(buf: &'a [u8], what: &'static str) -> Self {
+        Self { buf, pos: 0, what }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize, field: &str) -> Result<&'a [u8]> {
+        if len > self.remaining() {
+            return Err(Error::protocol(format!(
+                "{} truncated reading {field}: need {len} bytes at offset {}, have {}",
+                self.what,
+                self.pos,
+                self.remaining()
+            )));
+        }
+        let out = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(out)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self, field: &str) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.bytes(N, field)?);
+        Ok(out)
+    }
+
+    pub(crate) fn u8(&mut self, field: &str) -> Result<u8> {
+        Ok(self.array::<1>(field)?[0])
+    }
+
+    pub(crate) fn u32(&mut self, field: &str) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array(field)?))
+    }
+
+    pub(crate) fn u64(&mut self, field: &str) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array(field)?))
+    }
+
+    /// Reads a u32 length prefix followed by that many bytes.
+    pub(crate) fn len_prefixed(&mut self, field: &str) -> Result<&'a [u8]> {
+        let len = self.u32(field)? as usize;
+        self.bytes(len, field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64*: deterministic so failures reproduce from the seed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn bytes(&mut self, max_len: usize) -> Vec<u8> {
+            let len = (self.next() as usize) % (max_len + 1);
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    #[test]
+    fn random_server_bytes_never_panic() {
+        let mut rng = Rng(0x5eed_cafe_f00d_d00d);
+        for _ in 0..5_000 {
+            let data = rng.bytes(256);
+            let _ = read_frame_with_limit(&mut std::io::Cursor::new(&data), 1024);
+            let _ = crate::turn::parse_turn_records(&data);
+            let _ = crate::turn::parse_append_result(&data);
+            let _ = crate::context::parse_context_head(&data);
+            let _ = crate::error::parse_server_error(&data);
+        }
+    }
+
+    #[test]
+    fn every_named_message_type_is_classified() {
+        // A new message type has to be named for errors and metrics; this
+        // makes it be classified for read-only clients too.
+        for msg_type in 0..=u16::MAX {
+            if let Some(name) = msg_type_name(msg_type) {
+                assert!(
+                    msg_type_mutates(msg_type).is_some(),
+                    "{name} is not classified in msg_type_mutates"
+                );
+            }
+            if msg_type_mutates(msg_type).is_some() {
+                assert!(msg_type_name(msg_type).is_some(), "{msg_type} is unnamed");
+            }
+        }
+    }
+
+    #[test]
+    fn frame_codec_matches_stream_codec() {
+        let payload = b"hello frame".to_vec();
+        let encoded = encode_frame(MSG_APPEND_TURN, 3, 42, &payload);
+
+        let mut written = Vec::new();
+        write_frame(&mut written, MSG_APPEND_TURN, 3, 42, &payload).unwrap();
+        assert_eq!(encoded, written);
+
+        let (frame, used) = decode_frame(&encoded, MAX_FRAME_SIZE).unwrap();
+        assert_eq!(used, encoded.len());
+        assert_eq!(
+            frame,
+            read_frame(&mut std::io::Cursor::new(&encoded)).unwrap()
+        );
+
+        assert!(matches!(
+            decode_frame(&encoded[..10], MAX_FRAME_SIZE),
+            Err(Error::Protocol(_))
+        ));
+        assert!(matches!(
+            decode_frame(&encoded[..20], MAX_FRAME_SIZE),
+            Err(Error::Protocol(_))
+        ));
+        assert!(matches!(
+            decode_frame(&encoded, 4),
+            Err(Error::FrameTooLarge { len: 11, max: 4 })
+        ));
+    }
+
+    #[test]
+    fn checksummed_frames_round_trip() {
+        let mut buf = Vec::new();
+        write_frame_with_checksum(&mut buf, MSG_GET_LAST, 1, 9, b"payload").unwrap();
+        let mut encoded = Vec::new();
+        encode_frame_with_checksum_into(&mut encoded, MSG_GET_LAST, 1, 9, b"payload");
+        assert_eq!(encoded, buf);
+        let (frame, used) = decode_frame(&buf, MAX_FRAME_SIZE).unwrap();
+        assert_eq!(used, buf.len());
+        let header = verify_frame_checksum(&frame.header, &frame.payload).unwrap();
+        assert_eq!(header.len, 7);
+        assert_eq!(header.flags, 1);
+        assert_eq!(&frame.payload[..7], b"payload");
+
+        // A plain frame on a checksummed connection is corruption too.
+        let plain = encode_frame(MSG_GET_LAST, 0, 9, b"payload");
+        let (frame, _) = decode_frame(&plain, MAX_FRAME_SIZE).unwrap();
+        assert!(matches!(
+            verify_frame_checksum(&frame.header, &frame.payload),
+            Err(Error::FrameCorrupted { .. })
+        ));
+    }
+
+    #[test]
+    fn corrupted_checksummed_frames_never_panic_or_verify() {
+        let mut rng = Rng(0xc0ff_ee00_dead_beef);
+        for _ in 0..5_000 {
+            let payload = rng.bytes(96);
+            let mut buf = Vec::new();
+            write_frame_with_checksum(&mut buf, MSG_APPEND_TURN, 0, rng.next(), &payload).unwrap();
+
+            let flips = 1 + (rng.next() % 3) as usize;
+            for _ in 0..flips {
+                let pos = (rng.next() as usize) % buf.len();
+                buf[pos] ^= 1 << (rng.next() % 8);
+            }
+            if rng.next().is_multiple_of(4) {
+                buf.truncate((rng.next() as usize) % buf.len());
+            }
+
+            let Ok(frame) = read_frame_with_limit(&mut std::io::Cursor::new(&buf), 1024) else {
+                continue;
+            };
+            // CRC32C detects every burst error of up to 32 bits, so flips
+            // within a 3-byte window must fail; sparse flips almost surely do.
+            if let Ok(header) = verify_frame_checksum(&frame.header, &frame.payload) {
+                assert_eq!(&frame.payload[..header.len as usize], &payload[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn huge_declared_lengths_are_rejected_without_allocating() {
+        // count = u32::MAX, then a type_id length far beyond the payload.
+        let mut data = u32::MAX.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 20]);
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        let err = crate::turn::parse_turn_records(&data).unwrap_err();
+        match err {
+            Error::Protocol(msg) => assert!(msg.contains("type_id"), "{msg}"),
+            other => panic!("expected protocol error, got {other:?}"),
+        }
     }
 }
@@ -0,0 +1,140 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pruning old history.
+//!
+//! Long-running contexts accumulate history nothing reads any more.
+//! [`Client::prune_context`] deletes a context's turns below a depth. The
+//! oldest turn kept becomes a root: reads end there, its `parent_id` still
+//! names the pruned parent, and fetching a pruned turn (for example by
+//! following that link with [`Client::get_path`]) fails with
+//! [`Error::Pruned`](crate::Error::Pruned).
+
+#[cfg(not(target_arch = "wasm32"))]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::Result;
+use crate::ids::ContextId;
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::MSG_CTX_PRUNE;
+
+/// What [`Client::prune_context`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneResult {
+    pub turns_pruned: u64,
+    /// Bytes the server freed from its turn records. Payload blobs are
+    /// deduplicated across turns and stay stored.
+    pub bytes_reclaimed: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Deletes the turns of `context_id` below depth `keep_from_depth`, so
+    /// the turn at that depth becomes the oldest of the context.
+    ///
+    /// Turns another context still reaches, such as a fork point and the
+    /// history below it, are kept. Pruning at or below the oldest turn left
+    /// removes nothing; a depth past the head fails. Drops the turns the
+    /// turn cache (see [`crate::cache`]) holds for the context, which may
+    /// have been pruned.
+    ///
+    /// Servers without the CTX_PRUNE message fail with
+    /// [`Error::Unsupported`](crate::Error::Unsupported).
+    pub fn prune_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        keep_from_depth: u64,
+    ) -> Result<PruneResult> {
+        let mut payload = Vec::with_capacity(16);
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        payload.write_u64::<LittleEndian>(keep_from_depth)?;
+        let response = self.send_request(ctx, MSG_CTX_PRUNE, &payload);
+        self.prefetch_cache().invalidate(context_id);
+        if let Some(cache) = self.turn_cache() {
+            cache.invalidate(self.namespace_of(ctx), context_id);
+        }
+        let frame =
+            response.map_err(|err| err.resolve_unsupported("CTX_PRUNE").resolve_not_found())?;
+        parse_prune_result(&frame.payload)
+    }
+}
+
+pub(crate) fn parse_prune_result(payload: &[u8]) -> Result<PruneResult> {
+    let mut reader = PayloadReader::new(payload, "prune response");
+    Ok(PruneResult {
+        turns_pruned: reader.u64("turns_pruned")?,
+        bytes_reclaimed: reader.u64("bytes_reclaimed")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::ids::TurnId;
+    use crate::protocol::{MSG_ERROR, MSG_GET_TURN};
+    use crate::test_util::{error_payload, not_found_payload, spawn_scripted_server};
+    use crate::Error;
+
+    /// ERROR 410 naming the pruned turn in the trailer.
+    fn pruned_error(turn_id: u64) -> Vec<u8> {
+        let mut out = error_payload(410, &format!("turn {turn_id} was pruned"));
+        out.write_u32::<LittleEndian>(0).unwrap();
+        out.write_u32::<LittleEndian>(1).unwrap();
+        for field in ["turn_id", &turn_id.to_string()] {
+            out.write_u32::<LittleEndian>(field.len() as u32).unwrap();
+            out.extend_from_slice(field.as_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn prune_reports_what_was_reclaimed() {
+        let mut reclaimed = Vec::new();
+        reclaimed.write_u64::<LittleEndian>(40).unwrap();
+        reclaimed.write_u64::<LittleEndian>(4096).unwrap();
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_PRUNE, reclaimed),
+            (MSG_ERROR, pruned_error(7)),
+            (MSG_ERROR, not_found_payload("context", 9)),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let result = client.prune_context(&ctx, ContextId::new(3), 40).unwrap();
+        assert_eq!(
+            result,
+            PruneResult {
+                turns_pruned: 40,
+                bytes_reclaimed: 4096,
+            }
+        );
+        let err = client.get_turn(&ctx, TurnId::new(7)).unwrap_err();
+        assert!(
+            matches!(err, Error::Pruned { turn_id } if turn_id.get() == 7),
+            "{err:?}"
+        );
+        let err = client
+            .prune_context(&ctx, ContextId::new(9), 1)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 9),
+            "{err:?}"
+        );
+        let err = client
+            .prune_context(&ctx, ContextId::new(3), 1)
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.msg_type, MSG_CTX_PRUNE);
+        assert_eq!(requests[0].payload[..8], 3u64.to_le_bytes());
+        assert_eq!(requests[0].payload[8..], 40u64.to_le_bytes());
+        assert_eq!(requests[1].header.msg_type, MSG_GET_TURN);
+    }
+}
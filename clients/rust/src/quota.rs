@@ -0,0 +1,184 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Server quotas.
+//!
+//! Multi-tenant deployments cap what a tenant may store. [`Client::get_quotas`]
+//! reports the caps that apply to the session, so a client can stay under
+//! them instead of discovering them by failing. A request that would exceed
+//! one fails with [`Error::QuotaExceeded`], which is never retried.
+
+use std::collections::BTreeMap;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result, ServerErrorCode};
+use crate::protocol::{PayloadReader, MSG_GET_QUOTAS};
+
+/// The quotas a server enforces on the session. `None` means the server
+/// sets no such limit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuotaInfo {
+    /// Largest total payload size a context may hold.
+    pub max_context_bytes: Option<u64>,
+    pub max_turns_per_context: Option<u64>,
+    /// Appends allowed per UTC day.
+    pub max_daily_turns: Option<u64>,
+    /// Appends left today.
+    pub remaining_daily_turns: Option<u64>,
+    /// Quotas this client does not name, by the server's key.
+    pub other: BTreeMap<String, u64>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Fetches the quotas the server enforces on this session.
+    ///
+    /// Servers without the GET_QUOTAS message fail with
+    /// [`Error::Unsupported`].
+    pub fn get_quotas(&self, ctx: &RequestContext) -> Result<QuotaInfo> {
+        let frame = self
+            .send_request(ctx, MSG_GET_QUOTAS, &[])
+            .map_err(|err| err.resolve_unsupported("GET_QUOTAS"))?;
+        parse_quota_info(&frame.payload)
+    }
+}
+
+impl Error {
+    /// Maps the server's rejection of an unknown message type onto
+    /// [`Error::Unsupported`].
+    pub(crate) fn resolve_unsupported(self, operation: &str) -> Self {
+        match &self {
+            Error::Server {
+                code: ServerErrorCode::BadRequest | ServerErrorCode::Unprocessable,
+                detail,
+                ..
+            } if detail.contains("unknown msg_type") => Error::Unsupported(operation.to_string()),
+            _ => self,
+        }
+    }
+}
+
+pub(crate) fn parse_quota_info(payload: &[u8]) -> Result<QuotaInfo> {
+    let mut reader = PayloadReader::new(payload, "quota response");
+    let count = reader.u32("count")?;
+    let mut info = QuotaInfo::default();
+    for _ in 0..count {
+        let key = std::str::from_utf8(reader.len_prefixed("key")?)
+            .map_err(|_| Error::protocol("quota key not utf8"))?
+            .to_string();
+        let value = reader.u64("value")?;
+        match key.as_str() {
+            "max_context_bytes" => info.max_context_bytes = Some(value),
+            "max_turns_per_context" => info.max_turns_per_context = Some(value),
+            "max_daily_turns" => info.max_daily_turns = Some(value),
+            "remaining_daily_turns" => info.remaining_daily_turns = Some(value),
+            _ => {
+                info.other.insert(key, value);
+            }
+        }
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::ids::ContextId;
+    use crate::protocol::{ERROR_FLAG_RETRYABLE, MSG_APPEND_TURN, MSG_ERROR};
+    use crate::reconnect::is_connection_error;
+    use crate::test_util::{error_payload, spawn_scripted_server};
+    use crate::turn::AppendRequest;
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    fn quota_payload(entries: &[(&str, u64)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.write_u32::<LittleEndian>(entries.len() as u32).unwrap();
+        for (key, value) in entries {
+            out.write_u32::<LittleEndian>(key.len() as u32).unwrap();
+            out.extend_from_slice(key.as_bytes());
+            out.write_u64::<LittleEndian>(*value).unwrap();
+        }
+        out
+    }
+
+    /// ERROR 429 with the retryable flag set and the quota trailer.
+    fn quota_error(quota: &str, limit: u64, current: u64) -> Vec<u8> {
+        let mut out = error_payload(429, "quota exceeded");
+        out.write_u32::<LittleEndian>(ERROR_FLAG_RETRYABLE).unwrap();
+        let details = [
+            ("quota", quota.to_string()),
+            ("limit", limit.to_string()),
+            ("current", current.to_string()),
+        ];
+        out.write_u32::<LittleEndian>(details.len() as u32).unwrap();
+        for (key, value) in details {
+            for field in [key, value.as_str()] {
+                out.write_u32::<LittleEndian>(field.len() as u32).unwrap();
+                out.extend_from_slice(field.as_bytes());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn get_quotas_reports_server_limits() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (
+                MSG_GET_QUOTAS,
+                quota_payload(&[
+                    ("max_context_bytes", 1 << 20),
+                    ("remaining_daily_turns", 12),
+                    ("max_forks", 3),
+                ]),
+            ),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let info = client.get_quotas(&ctx).unwrap();
+        assert_eq!(info.max_context_bytes, Some(1 << 20));
+        assert_eq!(info.remaining_daily_turns, Some(12));
+        assert_eq!(info.max_turns_per_context, None);
+        assert_eq!(info.other.get("max_forks"), Some(&3));
+
+        let err = client.get_quotas(&ctx).unwrap_err();
+        assert!(
+            matches!(&err, Error::Unsupported(op) if op == "GET_QUOTAS"),
+            "got {err:?}"
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn quota_errors_are_typed_and_not_retried() {
+        let (addr, handle) = spawn_scripted_server(vec![(
+            MSG_ERROR,
+            quota_error("max_turns_per_context", 100, 100),
+        )]);
+        let client = dial(&addr, []).unwrap();
+        let req = AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]);
+
+        let err = client
+            .append_turn(&RequestContext::background(), &req)
+            .unwrap_err();
+        match &err {
+            Error::QuotaExceeded {
+                quota,
+                limit,
+                current,
+            } => {
+                assert_eq!(quota, "max_turns_per_context");
+                assert_eq!((*limit, *current), (100, 100));
+            }
+            other => panic!("got {other:?}"),
+        }
+        // Even though the server flagged it retryable.
+        assert!(!err.is_retryable());
+        assert!(!is_connection_error(&err));
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.msg_type, MSG_APPEND_TURN);
+    }
+}
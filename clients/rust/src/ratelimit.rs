@@ -0,0 +1,338 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional client-side rate limiting.
+//!
+//! [`with_rate_limit`] caps appends (APPEND_TURN, compaction summaries and
+//! the first turns of [`Client::create_context_with_turn`]) and
+//! [`with_read_rate_limit`] caps reads (GET_HEAD, GET_CONTEXT, GET_LAST,
+//! GET_TURN, GET_BLOB, SEARCH_TURNS and GET_CHILDREN), each with its own
+//! token bucket: one token per request, refilled at [`RateLimit::per_sec`]
+//! up to [`RateLimit::burst`]. An optional byte budget meters append payloads as
+//! they are sent and read responses as they arrive; a request may overdraw
+//! it, and the requests after it wait until the debt is repaid.
+//!
+//! A bucket is created with the option and shared by every connection
+//! dialed with it, so redials, pools and reconnecting clients all draw from
+//! the same budget. A request that finds its bucket empty waits for a token
+//! (as queue time, within its deadline), or with [`RateLimit::wait`] off
+//! fails at once with [`Error::RateLimited`]. Every limited request reports
+//! the bucket to [`Metrics::on_rate_limit`](crate::Metrics::on_rate_limit).
+//!
+//! ```no_run
+//! use cxdb::ratelimit::{with_rate_limit, RateLimit};
+//! use cxdb::dial;
+//!
+//! let client = dial(
+//!     "127.0.0.1:9009",
+//!     [with_rate_limit(RateLimit::new(100.0, 20).bytes_per_sec(1 << 20))],
+//! )?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client::ClientOption;
+use crate::error::{Error, Result};
+use crate::protocol::{
+    MSG_APPEND_TURN, MSG_CTX_COMPACT, MSG_CTX_CREATE_WITH_TURN, MSG_GET_BLOB, MSG_GET_CHILDREN,
+    MSG_GET_CONTEXT, MSG_GET_HEAD, MSG_GET_LAST, MSG_GET_LINKED, MSG_GET_TURN, MSG_SEARCH_TURNS,
+};
+
+/// A token bucket: sustained rate, burst and optional byte budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Requests per second, sustained.
+    pub per_sec: f64,
+    /// Requests that may go out back to back after a quiet spell.
+    pub burst: u32,
+    /// Bytes per second, sustained, with a one-second burst.
+    pub bytes_per_sec: Option<u64>,
+    /// Wait for the bucket to refill; otherwise fail with
+    /// [`Error::RateLimited`].
+    pub wait: bool,
+}
+
+impl RateLimit {
+    pub fn new(per_sec: f64, burst: u32) -> Self {
+        Self {
+            per_sec,
+            burst,
+            bytes_per_sec: None,
+            wait: true,
+        }
+    }
+
+    pub fn bytes_per_sec(mut self, bytes_per_sec: u64) -> Self {
+        self.bytes_per_sec = Some(bytes_per_sec);
+        self
+    }
+
+    pub fn wait(mut self, wait: bool) -> Self {
+        self.wait = wait;
+        self
+    }
+}
+
+/// Limits appends with `limit`, shared by every connection dialed with this
+/// option.
+pub fn with_rate_limit(limit: RateLimit) -> ClientOption {
+    let limiter = Arc::new(RateLimiter::new(Limited::Appends, limit));
+    Arc::new(move |opts| opts.append_limiter = Some(limiter.clone()))
+}
+
+/// Limits reads with `limit`, shared by every connection dialed with this
+/// option.
+pub fn with_read_rate_limit(limit: RateLimit) -> ClientOption {
+    let limiter = Arc::new(RateLimiter::new(Limited::Reads, limit));
+    Arc::new(move |opts| opts.read_limiter = Some(limiter.clone()))
+}
+
+/// Which requests a bucket limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limited {
+    Appends,
+    Reads,
+}
+
+impl Limited {
+    /// The bucket a message type draws from, if any.
+    pub(crate) fn from_msg_type(msg_type: u16) -> Option<Self> {
+        match msg_type {
+            MSG_APPEND_TURN | MSG_CTX_COMPACT | MSG_CTX_CREATE_WITH_TURN => Some(Limited::Appends),
+            MSG_GET_HEAD | MSG_GET_CONTEXT | MSG_GET_LAST | MSG_GET_TURN | MSG_GET_BLOB
+            | MSG_SEARCH_TURNS | MSG_GET_CHILDREN | MSG_GET_LINKED => Some(Limited::Reads),
+            _ => None,
+        }
+    }
+}
+
+/// A bucket as one request found it, reported to
+/// [`Metrics::on_rate_limit`](crate::Metrics::on_rate_limit).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitState {
+    pub limited: Limited,
+    /// Request tokens left after this request; negative while requests are
+    /// queued for tokens.
+    pub tokens: f64,
+    /// Bytes left in the byte budget, if there is one; negative while in
+    /// debt.
+    pub bytes: Option<f64>,
+    /// How long the request waits (or, rejected, would have waited).
+    pub wait: Duration,
+    /// The request failed with [`Error::RateLimited`] instead of waiting.
+    pub rejected: bool,
+}
+
+impl RateLimitState {
+    /// Whether the request was held back at all.
+    pub fn throttled(&self) -> bool {
+        self.rejected || !self.wait.is_zero()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limited: Limited,
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    bytes: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    fn new(limited: Limited, limit: RateLimit) -> Self {
+        Self {
+            limited,
+            limit,
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(limit.burst.max(1)),
+                bytes: limit.bytes_per_sec.unwrap_or(0) as f64,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token and charges `bytes` to the byte budget, returning how
+    /// long the caller must wait before sending. Fails without taking
+    /// anything if it must not wait, or could not before `deadline`.
+    pub(crate) fn acquire(
+        &self,
+        bytes: usize,
+        deadline: Instant,
+    ) -> (Result<Duration>, RateLimitState) {
+        let now = Instant::now();
+        let Ok(mut bucket) = self.bucket.lock() else {
+            return (
+                Err(Error::ClientClosed),
+                self.state(0.0, None, Duration::ZERO),
+            );
+        };
+        bucket.refill(&self.limit, now);
+
+        let mut wait = Duration::ZERO;
+        if bucket.tokens < 1.0 && self.limit.per_sec > 0.0 {
+            wait = Duration::from_secs_f64((1.0 - bucket.tokens) / self.limit.per_sec);
+        }
+        if let Some(rate) = self.limit.bytes_per_sec.filter(|rate| *rate > 0) {
+            if bucket.bytes < 0.0 {
+                wait = wait.max(Duration::from_secs_f64(-bucket.bytes / rate as f64));
+            }
+        }
+
+        let bytes_left = self.limit.bytes_per_sec.map(|_| bucket.bytes);
+        if !wait.is_zero() && !self.limit.wait {
+            let mut state = self.state(bucket.tokens, bytes_left, wait);
+            state.rejected = true;
+            return (Err(Error::RateLimited { retry_after: wait }), state);
+        }
+        if now + wait > deadline {
+            return (
+                Err(Error::DeadlineExceeded),
+                self.state(bucket.tokens, bytes_left, wait),
+            );
+        }
+        bucket.tokens -= 1.0;
+        if self.limit.bytes_per_sec.is_some() {
+            bucket.bytes -= bytes as f64;
+        }
+        let bytes_left = self.limit.bytes_per_sec.map(|_| bucket.bytes);
+        (Ok(wait), self.state(bucket.tokens, bytes_left, wait))
+    }
+
+    /// Charges `bytes` received to the byte budget.
+    pub(crate) fn charge(&self, bytes: usize) {
+        if self.limit.bytes_per_sec.is_none() {
+            return;
+        }
+        if let Ok(mut bucket) = self.bucket.lock() {
+            bucket.refill(&self.limit, Instant::now());
+            bucket.bytes -= bytes as f64;
+        }
+    }
+
+    fn state(&self, tokens: f64, bytes: Option<f64>, wait: Duration) -> RateLimitState {
+        RateLimitState {
+            limited: self.limited,
+            tokens,
+            bytes,
+            wait,
+            rejected: false,
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        let burst = f64::from(limit.burst.max(1));
+        self.tokens = (self.tokens + elapsed * limit.per_sec).min(burst);
+        if let Some(rate) = limit.bytes_per_sec {
+            self.bytes = (self.bytes + elapsed * rate as f64).min(rate as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::metrics::{with_metrics, InMemoryMetrics};
+    use crate::test_util::{spawn_multi_server, turn_listing_payload};
+    use crate::{dial, AppendRequest, GetLastOptions, RequestContext};
+
+    fn far() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    #[test]
+    fn bursts_then_refills_at_the_rate() {
+        let limiter = RateLimiter::new(Limited::Appends, RateLimit::new(10.0, 3));
+        for _ in 0..3 {
+            assert_eq!(limiter.acquire(0, far()).0.unwrap(), Duration::ZERO);
+        }
+        // The fourth waits a tenth of a second, the fifth queues behind it.
+        let wait = limiter.acquire(0, far()).0.unwrap();
+        assert!(wait > Duration::from_millis(80), "{wait:?}");
+        let (result, state) = limiter.acquire(0, far());
+        assert!(result.unwrap() > Duration::from_millis(180));
+        assert!(state.tokens < -1.0 && state.throttled());
+
+        let (result, _) = limiter.acquire(0, Instant::now() + Duration::from_millis(50));
+        assert!(matches!(result, Err(Error::DeadlineExceeded)));
+    }
+
+    #[test]
+    fn fail_fast_rejects_without_taking_tokens() {
+        let limiter = RateLimiter::new(Limited::Reads, RateLimit::new(1.0, 1).wait(false));
+        limiter.acquire(0, far()).0.unwrap();
+        for _ in 0..2 {
+            let (result, state) = limiter.acquire(0, far());
+            let Err(Error::RateLimited { retry_after }) = result else {
+                panic!("{result:?}");
+            };
+            assert!(retry_after > Duration::from_millis(900));
+            assert!(state.rejected);
+        }
+    }
+
+    #[test]
+    fn byte_debt_holds_back_the_next_request() {
+        let limit = RateLimit::new(1000.0, 100).bytes_per_sec(1000);
+        let limiter = RateLimiter::new(Limited::Appends, limit);
+        // One request may overdraw the budget ...
+        assert!(limiter.acquire(3000, far()).0.unwrap().is_zero());
+        // ... and the next waits for the two seconds of debt.
+        let (result, state) = limiter.acquire(1, far());
+        assert!(result.unwrap() > Duration::from_millis(1900));
+        assert!(state.bytes.unwrap() < -2000.0);
+
+        limiter.charge(500);
+        let (result, _) = limiter.acquire(0, far());
+        assert!(result.unwrap() > Duration::from_millis(2400));
+    }
+
+    #[test]
+    fn clients_share_a_bucket_and_report_it() {
+        let requests = Arc::new(AtomicU64::new(0));
+        let counter = requests.clone();
+        let addr = spawn_multi_server(move |_req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            (MSG_GET_LAST, turn_listing_payload(&[b"\x90"]))
+        });
+        let metrics = Arc::new(InMemoryMetrics::default());
+        let opts = [
+            with_read_rate_limit(RateLimit::new(0.5, 2).wait(false)),
+            with_metrics(metrics.clone()),
+        ];
+        let first = dial(&addr, opts.clone()).unwrap();
+        let second = first.redial().unwrap();
+        let ctx = RequestContext::background();
+        let read = GetLastOptions::default();
+
+        first
+            .get_last(&ctx, ContextId::new(1), read.clone())
+            .unwrap();
+        second
+            .get_last(&ctx, ContextId::new(1), read.clone())
+            .unwrap();
+        let err = first.get_last(&ctx, ContextId::new(1), read).unwrap_err();
+        assert!(matches!(err, Error::RateLimited { .. }), "{err:?}");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(metrics.throttled(Limited::Reads), 1);
+
+        // Appends have no limit here.
+        let append = AppendRequest::new(ContextId::new(1), "test", 1, vec![0x90]);
+        let _ = first.append_turn(&ctx, &append);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -10,17 +10,96 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::{bounded, select, Receiver, Sender};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use crate::client::{dial, dial_tls, Client, ClientOption, RequestContext};
+use crate::client::{
+    dial, dial_tls, with_dial_timeout, Client, ClientOption, ClientOptions, RequestContext,
+};
 use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::trace;
+use crate::typed::{decode_typed, typed_append_request, CxdbType};
 
 pub const DEFAULT_MAX_RETRIES: usize = 5;
 pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
 pub const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 pub const DEFAULT_QUEUE_SIZE: usize = 10_000;
 
+pub const DEFAULT_MAX_SERVER_RETRIES: usize = 3;
+
 pub type DialFunc = Arc<dyn Fn() -> Result<Client> + Send + Sync>;
 
+/// Controls how the reconnecting client retries requests that failed with a
+/// server error (connection errors always trigger a reconnect).
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retry server errors the server flagged as retryable.
+    pub honor_retryable: bool,
+    /// Maximum retries of a single request after retryable server errors.
+    pub max_attempts: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            honor_retryable: true,
+            max_attempts: DEFAULT_MAX_SERVER_RETRIES,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry server errors.
+    pub fn none() -> Self {
+        Self {
+            honor_retryable: false,
+            max_attempts: 0,
+        }
+    }
+
+    pub fn should_retry(&self, err: &Error, attempt: usize) -> bool {
+        self.honor_retryable
+            && attempt < self.max_attempts
+            && matches!(
+                err,
+                Error::Server {
+                    retryable: true,
+                    ..
+                }
+            )
+    }
+}
+
+/// A redial the reconnecting client is about to attempt, passed to the
+/// [`with_on_reconnect_event`] callback.
+#[derive(Debug)]
+pub struct ReconnectEvent<'a> {
+    /// 1-based dial attempt within this reconnect.
+    pub attempt: usize,
+    /// How long the client waits before dialing; zero on the first attempt.
+    pub delay: Duration,
+    /// The connection error that started the reconnect, or the previous
+    /// attempt's dial failure.
+    pub error: &'a Error,
+}
+
+/// A request the reconnecting client is about to retry after a retryable
+/// server error, passed to the [`with_on_retry`] callback.
+#[derive(Debug)]
+pub struct RetryEvent<'a> {
+    /// Operation name, e.g. `"AppendTurn"`.
+    pub op: &'static str,
+    /// 1-based retry of this request.
+    pub attempt: usize,
+    /// How long the client waits before retrying.
+    pub delay: Duration,
+    pub error: &'a Error,
+}
+
+pub type ReconnectEventCallback = Arc<dyn Fn(&ReconnectEvent<'_>) + Send + Sync>;
+pub type RetryEventCallback = Arc<dyn Fn(&RetryEvent<'_>) + Send + Sync>;
+
 pub type ReconnectOption = Arc<dyn Fn(&mut ReconnectConfig) + Send + Sync>;
 
 #[derive(Clone)]
@@ -29,7 +108,10 @@ pub struct ReconnectConfig {
     pub retry_delay: Duration,
     pub max_retry_delay: Duration,
     pub queue_size: usize,
+    pub retry_policy: RetryPolicy,
     pub on_reconnect: Option<Arc<dyn Fn(u64) + Send + Sync>>,
+    pub on_reconnect_event: Option<ReconnectEventCallback>,
+    pub on_retry: Option<RetryEventCallback>,
     pub dial_func: Option<DialFunc>,
 }
 
@@ -40,7 +122,10 @@ impl Default for ReconnectConfig {
             retry_delay: DEFAULT_RETRY_DELAY,
             max_retry_delay: DEFAULT_MAX_RETRY_DELAY,
             queue_size: DEFAULT_QUEUE_SIZE,
+            retry_policy: RetryPolicy::default(),
             on_reconnect: None,
+            on_reconnect_event: None,
+            on_retry: None,
             dial_func: None,
         }
     }
@@ -62,6 +147,10 @@ pub fn with_queue_size(size: usize) -> ReconnectOption {
     Arc::new(move |cfg| cfg.queue_size = size)
 }
 
+pub fn with_retry_policy(policy: RetryPolicy) -> ReconnectOption {
+    Arc::new(move |cfg| cfg.retry_policy = policy)
+}
+
 pub fn with_on_reconnect<F>(f: F) -> ReconnectOption
 where
     F: Fn(u64) + Send + Sync + 'static,
@@ -70,6 +159,27 @@ where
     Arc::new(move |cfg| cfg.on_reconnect = Some(f.clone()))
 }
 
+/// Calls `f` before every redial attempt. Callbacks run on the client's
+/// worker thread without any client lock held, so they may log or call back
+/// into the client, but requests queue behind them.
+pub fn with_on_reconnect_event<F>(f: F) -> ReconnectOption
+where
+    F: Fn(&ReconnectEvent<'_>) + Send + Sync + 'static,
+{
+    let f: ReconnectEventCallback = Arc::new(f);
+    Arc::new(move |cfg| cfg.on_reconnect_event = Some(f.clone()))
+}
+
+/// Calls `f` before every retry of a request after a retryable server error.
+/// Runs like the [`with_on_reconnect_event`] callback.
+pub fn with_on_retry<F>(f: F) -> ReconnectOption
+where
+    F: Fn(&RetryEvent<'_>) + Send + Sync + 'static,
+{
+    let f: RetryEventCallback = Arc::new(f);
+    Arc::new(move |cfg| cfg.on_retry = Some(f.clone()))
+}
+
 #[cfg(test)]
 pub(crate) fn with_dial_func(func: DialFunc) -> ReconnectOption {
     Arc::new(move |cfg| cfg.dial_func = Some(func.clone()))
@@ -80,14 +190,20 @@ pub struct ReconnectingClient {
     worker: Mutex<Option<thread::JoinHandle<()>>>,
 }
 
+/// Dials a new client, taking at most the given time if there is one.
+type BoundedDial = Arc<dyn Fn(Option<Duration>) -> Result<Client> + Send + Sync>;
+
 struct Inner {
     client: Mutex<Option<Arc<Client>>>,
-    dial_func: DialFunc,
+    dial_func: BoundedDial,
 
     max_retries: usize,
     retry_delay: Duration,
     max_retry_delay: Duration,
+    retry_policy: RetryPolicy,
     on_reconnect: Option<Arc<dyn Fn(u64) + Send + Sync>>,
+    on_reconnect_event: Option<ReconnectEventCallback>,
+    on_retry: Option<RetryEventCallback>,
 
     queue_tx: Sender<QueuedRequest>,
     queue_rx: Receiver<QueuedRequest>,
@@ -98,6 +214,8 @@ struct Inner {
 
 struct QueuedRequest {
     ctx: RequestContext,
+    /// Operation name, for retry events.
+    desc: &'static str,
     op: Arc<dyn Fn(&Client) -> Result<()> + Send + Sync>,
     result_tx: Sender<Result<()>>,
 }
@@ -118,7 +236,7 @@ pub fn dial_tls_reconnecting(
     dial_reconnecting_inner(addr, true, reconnect_opts, opts)
 }
 
-fn dial_reconnecting_inner(
+pub(crate) fn dial_reconnecting_inner(
     addr: &str,
     use_tls: bool,
     reconnect_opts: impl IntoIterator<Item = ReconnectOption>,
@@ -131,22 +249,35 @@ fn dial_reconnecting_inner(
 
     let options: Vec<ClientOption> = opts.into_iter().collect();
 
-    let dial_func: DialFunc = cfg.dial_func.clone().unwrap_or_else(|| {
-        let addr = addr.to_string();
-        let opts = options.clone();
-        Arc::new(move || {
-            if use_tls {
-                dial_tls(&addr, opts.clone())
-            } else {
-                dial(&addr, opts.clone())
+    // A custom dial function cannot be given a timeout; the default dial
+    // caps its dial timeout at the time left before the request deadline.
+    let dial_func: BoundedDial = match cfg.dial_func.clone() {
+        Some(func) => Arc::new(move |_| func()),
+        None => {
+            let addr = addr.to_string();
+            let mut client_opts = ClientOptions::default();
+            for opt in &options {
+                opt(&mut client_opts);
             }
-        })
-    });
+            let dial_timeout = client_opts.dial_timeout;
+            Arc::new(move |budget: Option<Duration>| {
+                let mut opts = options.clone();
+                if let Some(budget) = budget {
+                    opts.push(with_dial_timeout(dial_timeout.min(budget)));
+                }
+                if use_tls {
+                    dial_tls(&addr, opts)
+                } else {
+                    dial(&addr, opts)
+                }
+            })
+        }
+    };
 
     let (queue_tx, queue_rx) = bounded(cfg.queue_size);
     let (shutdown_tx, shutdown_rx) = bounded(1);
 
-    let client = Arc::new(dial_func()?);
+    let client = Arc::new(dial_func(None)?);
 
     let inner = Arc::new(Inner {
         client: Mutex::new(Some(client)),
@@ -154,7 +285,10 @@ fn dial_reconnecting_inner(
         max_retries: cfg.max_retries,
         retry_delay: cfg.retry_delay,
         max_retry_delay: cfg.max_retry_delay,
+        retry_policy: cfg.retry_policy,
         on_reconnect: cfg.on_reconnect.clone(),
+        on_reconnect_event: cfg.on_reconnect_event.clone(),
+        on_retry: cfg.on_retry.clone(),
         queue_tx,
         queue_rx: queue_rx.clone(),
         shutdown_tx: shutdown_tx.clone(),
@@ -195,6 +329,35 @@ impl ReconnectingClient {
             .unwrap_or(0)
     }
 
+    /// The current connection's [`Client::server_capabilities`]; the
+    /// default, which assumes every message type, while disconnected.
+    pub fn server_capabilities(&self) -> crate::capabilities::Capabilities {
+        self.inner
+            .client
+            .lock()
+            .ok()
+            .and_then(|c| c.as_ref().map(|client| client.server_capabilities()))
+            .unwrap_or_default()
+    }
+
+    /// The current connection's [`Client::negotiated_compression`]; `None`
+    /// while disconnected.
+    pub fn negotiated_compression(&self) -> Option<crate::compression::Codec> {
+        self.inner.client.lock().ok().and_then(|c| {
+            c.as_ref()
+                .and_then(|client| client.negotiated_compression())
+        })
+    }
+
+    /// The current connection's server address, if connected.
+    pub fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        self.inner
+            .client
+            .lock()
+            .ok()
+            .and_then(|c| c.as_ref().map(|client| client.peer_addr()))
+    }
+
     pub fn client_tag(&self) -> String {
         self.inner
             .client
@@ -204,6 +367,16 @@ impl ReconnectingClient {
             .unwrap_or_default()
     }
 
+    /// Starts a background [`Client::prefetch`] on the current connection.
+    /// Prefetched tails live on that connection and are dropped on reconnect.
+    pub fn prefetch(&self, context_id: ContextId, limit: u32) -> Result<()> {
+        let client = self.inner.client.lock().map_err(|_| Error::ClientClosed)?;
+        match client.as_ref() {
+            Some(client) => client.prefetch(context_id, limit),
+            None => Err(Error::ConnectionClosed),
+        }
+    }
+
     pub fn queue_length(&self) -> usize {
         self.inner.queue_rx.len()
     }
@@ -211,13 +384,13 @@ impl ReconnectingClient {
     pub fn create_context(
         &self,
         ctx: &RequestContext,
-        base_turn_id: u64,
+        opts: crate::context::CreateContextOptions,
     ) -> Result<crate::context::ContextHead> {
         let result = Arc::new(Mutex::new(None));
         let ctx_clone = ctx.clone();
         let result_clone = result.clone();
         self.enqueue(ctx, "CreateContext", move |client| {
-            let head = client.create_context(&ctx_clone, base_turn_id)?;
+            let head = client.create_context(&ctx_clone, opts)?;
             *result_clone.lock().unwrap() = Some(head);
             Ok(())
         })?;
@@ -225,10 +398,31 @@ impl ReconnectingClient {
         Ok(value)
     }
 
+    /// Reconnecting wrapper around [`Client::create_context_with_turn`]. A
+    /// replay after a lost response creates a second context.
+    pub fn create_context_with_turn(
+        &self,
+        ctx: &RequestContext,
+        opts: crate::context::CreateContextOptions,
+        first: &crate::turn::AppendRequest,
+    ) -> Result<(crate::context::ContextHead, crate::turn::AppendResult)> {
+        let result = Arc::new(Mutex::new(None));
+        let first = first.clone();
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "CreateContextWithTurn", move |client| {
+            let created = client.create_context_with_turn(&ctx_clone, opts, &first)?;
+            *result_clone.lock().unwrap() = Some(created);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
     pub fn fork_context(
         &self,
         ctx: &RequestContext,
-        base_turn_id: u64,
+        base_turn_id: TurnId,
     ) -> Result<crate::context::ContextHead> {
         let result = Arc::new(Mutex::new(None));
         let ctx_clone = ctx.clone();
@@ -242,10 +436,46 @@ impl ReconnectingClient {
         Ok(value)
     }
 
+    /// Reconnecting wrapper around [`Client::create_or_get_context_by_alias`].
+    /// Safe to retry: a replay after a lost response finds the alias bound
+    /// and returns the same context.
+    pub fn create_or_get_context_by_alias(
+        &self,
+        ctx: &RequestContext,
+        alias: &str,
+        opts: crate::context::CreateContextOptions,
+    ) -> Result<crate::context::ContextInfo> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        let alias = alias.to_string();
+        self.enqueue(ctx, "CreateOrGetContextByAlias", move |client| {
+            let info = client.create_or_get_context_by_alias(&ctx_clone, &alias, opts)?;
+            *result_clone.lock().unwrap() = Some(info);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn resolve_alias(&self, ctx: &RequestContext, alias: &str) -> Result<Option<ContextId>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        let alias = alias.to_string();
+        self.enqueue(ctx, "ResolveAlias", move |client| {
+            let context_id = client.resolve_alias(&ctx_clone, &alias)?;
+            *result_clone.lock().unwrap() = Some(context_id);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
     pub fn get_head(
         &self,
         ctx: &RequestContext,
-        context_id: u64,
+        context_id: ContextId,
     ) -> Result<crate::context::ContextHead> {
         let result = Arc::new(Mutex::new(None));
         let ctx_clone = ctx.clone();
@@ -259,6 +489,19 @@ impl ReconnectingClient {
         Ok(value)
     }
 
+    pub fn context_exists(&self, ctx: &RequestContext, context_id: ContextId) -> Result<bool> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "ContextExists", move |client| {
+            let exists = client.context_exists(&ctx_clone, context_id)?;
+            *result_clone.lock().unwrap() = Some(exists);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
     pub fn append_turn(
         &self,
         ctx: &RequestContext,
@@ -277,17 +520,487 @@ impl ReconnectingClient {
         Ok(value)
     }
 
+    /// See [`Client::append_dedup`].
+    pub fn append_dedup(
+        &self,
+        ctx: &RequestContext,
+        req: &crate::turn::AppendRequest,
+    ) -> Result<(crate::turn::AppendResult, bool)> {
+        let result = Arc::new(Mutex::new(None));
+        let req = req.clone();
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "AppendDedup", move |client| {
+            let res = client.append_dedup(&ctx_clone, &req)?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    /// See [`Client::append_multi`].
+    pub fn append_multi(
+        &self,
+        ctx: &RequestContext,
+        entries: &[(ContextId, crate::turn::AppendRequest)],
+    ) -> Result<Vec<crate::turn::AppendResult>> {
+        self.append_multi_with(ctx, entries, &Default::default())
+    }
+
+    /// See [`Client::append_multi_with`].
+    pub fn append_multi_with(
+        &self,
+        ctx: &RequestContext,
+        entries: &[(ContextId, crate::turn::AppendRequest)],
+        opts: &crate::transaction::AppendMultiOptions,
+    ) -> Result<Vec<crate::turn::AppendResult>> {
+        let result = Arc::new(Mutex::new(None));
+        let entries = entries.to_vec();
+        let opts = *opts;
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "AppendMulti", move |client| {
+            let res = client.append_multi_with(&ctx_clone, &entries, &opts)?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn compact_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        req: crate::turn::CompactRequest,
+    ) -> Result<crate::turn::AppendResult> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "CompactContext", move |client| {
+            let res = client.compact_context(&ctx_clone, context_id, req.clone())?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    /// See [`Client::get_turn_at_depth`].
+    pub fn get_turn_at_depth(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        depth: u64,
+        opts: crate::turn::GetTurnOptions,
+    ) -> Result<crate::turn::TurnRecord> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetTurnAtDepth", move |client| {
+            let record = client.get_turn_at_depth(&ctx_clone, context_id, depth, opts)?;
+            *result_clone.lock().unwrap() = Some(record);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn get_turn(
+        &self,
+        ctx: &RequestContext,
+        turn_id: TurnId,
+    ) -> Result<crate::turn::TurnRecord> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetTurn", move |client| {
+            let record = client.get_turn(&ctx_clone, turn_id)?;
+            *result_clone.lock().unwrap() = Some(record);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn prune_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        keep_from_depth: u64,
+    ) -> Result<crate::prune::PruneResult> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "PruneContext", move |client| {
+            let res = client.prune_context(&ctx_clone, context_id, keep_from_depth)?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn archive_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<crate::context::ContextHead> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "ArchiveContext", move |client| {
+            let head = client.archive_context(&ctx_clone, context_id)?;
+            *result_clone.lock().unwrap() = Some(head);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn unarchive_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<crate::context::ContextHead> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "UnarchiveContext", move |client| {
+            let head = client.unarchive_context(&ctx_clone, context_id)?;
+            *result_clone.lock().unwrap() = Some(head);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn get_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<crate::context::ContextDetails> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetContext", move |client| {
+            let details = client.get_context(&ctx_clone, context_id)?;
+            *result_clone.lock().unwrap() = Some(details);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn list_children(
+        &self,
+        ctx: &RequestContext,
+        parent_id: ContextId,
+    ) -> Result<Vec<crate::archive::ContextSummary>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "ListChildren", move |client| {
+            let children = client.list_children(&ctx_clone, parent_id)?;
+            *result_clone.lock().unwrap() = Some(children);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn list_contexts(
+        &self,
+        ctx: &RequestContext,
+        opts: crate::archive::ListContextsOptions,
+    ) -> Result<Vec<crate::archive::ContextSummary>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "ListContexts", move |client| {
+            let contexts = client.list_contexts(&ctx_clone, opts)?;
+            *result_clone.lock().unwrap() = Some(contexts);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn delete_contexts(
+        &self,
+        ctx: &RequestContext,
+        filter: &crate::delete::DeleteFilter,
+    ) -> Result<u64> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        let filter = filter.clone();
+        self.enqueue(ctx, "DeleteContexts", move |client| {
+            let deleted = client.delete_contexts(&ctx_clone, &filter)?;
+            *result_clone.lock().unwrap() = Some(deleted);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn redact_turn(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        turn_id: TurnId,
+        opts: crate::redact::RedactOptions,
+    ) -> Result<crate::redact::Redaction> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "RedactTurn", move |client| {
+            let res = client.redact_turn(&ctx_clone, context_id, turn_id, opts.clone())?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn search(
+        &self,
+        ctx: &RequestContext,
+        query: &crate::text_search::TextQuery,
+    ) -> Result<Vec<crate::text_search::TextHit>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let query = query.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "TextSearch", move |client| {
+            let hits = client.search(&ctx_clone, &query)?;
+            *result_clone.lock().unwrap() = Some(hits);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn type_histogram(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<std::collections::HashMap<String, u64>> {
+        self.type_histogram_scoped(ctx, context_id, &Default::default())
+    }
+
+    pub fn type_histogram_scoped(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: &crate::histogram::TypeHistogramOptions,
+    ) -> Result<std::collections::HashMap<String, u64>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let opts = opts.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "TypeHistogram", move |client| {
+            let counts = client.type_histogram_scoped(&ctx_clone, context_id, &opts)?;
+            *result_clone.lock().unwrap() = Some(counts);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn context_stats(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<crate::stats::ContextStats> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "ContextStats", move |client| {
+            let stats = client.context_stats(&ctx_clone, context_id)?;
+            *result_clone.lock().unwrap() = Some(stats);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn get_linked_turns(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        turn_id: TurnId,
+        direction: crate::links::LinkDirection,
+    ) -> Result<Vec<crate::turn::TurnRecord>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetLinkedTurns", move |client| {
+            let turns = client.get_linked_turns(&ctx_clone, context_id, turn_id, direction)?;
+            *result_clone.lock().unwrap() = Some(turns);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn get_quotas(&self, ctx: &RequestContext) -> Result<crate::quota::QuotaInfo> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetQuotas", move |client| {
+            let info = client.get_quotas(&ctx_clone)?;
+            *result_clone.lock().unwrap() = Some(info);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn search_turns(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        query: &crate::search::SearchQuery,
+    ) -> Result<Vec<crate::search::SearchHit>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let query = query.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "SearchTurns", move |client| {
+            let hits = client.search_turns(&ctx_clone, context_id, &query)?;
+            *result_clone.lock().unwrap() = Some(hits);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    /// See [`Client::append_typed`].
+    pub fn append_typed<T: CxdbType + Serialize>(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        value: &T,
+    ) -> Result<crate::turn::AppendResult> {
+        self.append_turn(ctx, &typed_append_request(context_id, value)?)
+    }
+
+    /// See [`Client::get_last_typed`].
+    pub fn get_last_typed<T: CxdbType + DeserializeOwned>(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: crate::turn::GetLastOptions,
+    ) -> Result<Vec<(crate::turn::TurnHeader, T)>> {
+        let opts = crate::turn::GetLastOptions {
+            include_payload: true,
+            ..opts
+        };
+        let records = self.get_last(ctx, context_id, opts)?;
+        let max_decode_depth = self
+            .inner
+            .client
+            .lock()
+            .ok()
+            .and_then(|c| c.as_ref().map(|client| client.max_decode_depth()))
+            .unwrap_or(crate::protocol::MAX_DECODE_DEPTH);
+        decode_typed(records, max_decode_depth)
+    }
+
     pub fn get_last(
         &self,
         ctx: &RequestContext,
-        context_id: u64,
+        context_id: ContextId,
         opts: crate::turn::GetLastOptions,
     ) -> Result<Vec<crate::turn::TurnRecord>> {
         let result = Arc::new(Mutex::new(None));
         let ctx_clone = ctx.clone();
         let result_clone = result.clone();
         self.enqueue(ctx, "GetLast", move |client| {
-            let res = client.get_last(&ctx_clone, context_id, opts)?;
+            let res = client.get_last(&ctx_clone, context_id, opts.clone())?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn get_last_meta(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: crate::turn::GetLastOptions,
+    ) -> Result<Vec<crate::turn::TurnMeta>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetLastMeta", move |client| {
+            let res = client.get_last_meta(&ctx_clone, context_id, opts.clone())?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    pub fn get_last_full(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: crate::turn::GetLastOptions,
+    ) -> Result<Vec<crate::turn::Turn>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetLastFull", move |client| {
+            let res = client.get_last_full(&ctx_clone, context_id, opts.clone())?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    /// Pipelined [`Client::get_last_many`]. If the connection drops mid-batch
+    /// the whole batch is retried on the new connection; reads are idempotent.
+    pub fn get_last_many(
+        &self,
+        ctx: &RequestContext,
+        requests: &[(ContextId, crate::turn::GetLastOptions)],
+    ) -> Result<Vec<Result<Vec<crate::turn::TurnRecord>>>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        let requests = requests.to_vec();
+        self.enqueue(ctx, "GetLastMany", move |client| {
+            let mut res = client.get_last_many(&ctx_clone, &requests)?;
+            if let Some(pos) = res
+                .iter()
+                .position(|r| matches!(r, Err(err) if is_connection_error(err)))
+            {
+                return res.swap_remove(pos).map(|_| ());
+            }
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    #[cfg(feature = "bytes")]
+    pub fn get_last_shared(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: crate::turn::GetLastOptions,
+    ) -> Result<Vec<crate::turn::SharedTurnRecord>> {
+        let result = Arc::new(Mutex::new(None));
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "GetLast", move |client| {
+            let res = client.get_last_shared(&ctx_clone, context_id, opts.clone())?;
             *result_clone.lock().unwrap() = Some(res);
             Ok(())
         })?;
@@ -368,7 +1081,25 @@ impl ReconnectingClient {
         Ok(value)
     }
 
-    fn enqueue<F>(&self, ctx: &RequestContext, _desc: &str, op: F) -> Result<()>
+    /// See [`Client::call_raw`]. Like every queued request, the call is
+    /// retried on a new connection after a connection error, so the server
+    /// may see it more than once.
+    pub fn call_raw(&self, ctx: &RequestContext, opcode: u16, body: &[u8]) -> Result<Vec<u8>> {
+        crate::proto::check_opcode(opcode)?;
+        let result = Arc::new(Mutex::new(None));
+        let body = body.to_vec();
+        let ctx_clone = ctx.clone();
+        let result_clone = result.clone();
+        self.enqueue(ctx, "CallRaw", move |client| {
+            let res = client.call_raw(&ctx_clone, opcode, &body)?;
+            *result_clone.lock().unwrap() = Some(res);
+            Ok(())
+        })?;
+        let value = result.lock().unwrap().take().unwrap();
+        Ok(value)
+    }
+
+    fn enqueue<F>(&self, ctx: &RequestContext, desc: &'static str, op: F) -> Result<()>
     where
         F: Fn(&Client) -> Result<()> + Send + Sync + 'static,
     {
@@ -380,13 +1111,14 @@ impl ReconnectingClient {
         }
         if let Some(deadline) = ctx.deadline() {
             if deadline <= Instant::now() {
-                return Err(Error::Timeout);
+                return Err(Error::DeadlineExceeded);
             }
         }
 
         let (result_tx, result_rx) = bounded(1);
         let req = QueuedRequest {
             ctx: ctx.clone(),
+            desc,
             op: Arc::new(op),
             result_tx,
         };
@@ -425,7 +1157,7 @@ fn process_request(inner: &Arc<Inner>, req: QueuedRequest) {
     }
     if let Some(deadline) = req.ctx.deadline() {
         if deadline <= Instant::now() {
-            let _ = req.result_tx.send(Err(Error::Timeout));
+            let _ = req.result_tx.send(Err(Error::DeadlineExceeded));
             return;
         }
     }
@@ -443,10 +1175,14 @@ fn process_request(inner: &Arc<Inner>, req: QueuedRequest) {
     };
 
     let op = req.op.clone();
-    let mut err = (op)(&client);
+    let mut err = if client.is_poisoned() {
+        Err(Error::ConnectionClosed)
+    } else {
+        (op)(&client)
+    };
     if let Err(ref e) = err {
         if is_connection_error(e) {
-            if let Err(reconn_err) = reconnect(inner, &req.ctx) {
+            if let Err(reconn_err) = reconnect(inner, &req.ctx, e) {
                 err = Err(reconn_err);
             } else {
                 let client = inner.client.lock().ok().and_then(|c| c.as_ref().cloned());
@@ -457,14 +1193,53 @@ fn process_request(inner: &Arc<Inner>, req: QueuedRequest) {
         }
     }
 
+    let mut attempt = 0;
+    let mut delay = inner.retry_delay;
+    while let Err(ref e) = err {
+        if !inner.retry_policy.should_retry(e, attempt) {
+            break;
+        }
+        attempt += 1;
+        trace::retrying(&req.ctx, req.desc, attempt, delay, e);
+        if let Some(cb) = &inner.on_retry {
+            cb(&RetryEvent {
+                op: req.desc,
+                attempt,
+                delay,
+                error: e,
+            });
+        }
+        if let Err(sleep_err) = sleep_with_cancel(delay, &req.ctx, inner) {
+            err = Err(sleep_err);
+            break;
+        }
+        delay = cmp::min(delay * 2, inner.max_retry_delay);
+        let client = inner.client.lock().ok().and_then(|c| c.as_ref().cloned());
+        match client {
+            Some(client) => err = (op)(&client),
+            None => break,
+        }
+    }
+
     let _ = req.result_tx.send(err);
 }
 
-fn reconnect(inner: &Arc<Inner>, ctx: &RequestContext) -> Result<()> {
+/// Redials until a dial succeeds, `max_retries` dials fail, or `ctx` is
+/// done. The backoff and each dial count against the request deadline, and
+/// a failed reconnect returns [`Error::DeadlineExceeded`] rather than the
+/// last dial error once the deadline has passed.
+fn reconnect(inner: &Arc<Inner>, ctx: &RequestContext, cause: &Error) -> Result<()> {
     let mut delay = inner.retry_delay;
     let mut last_err: Option<Error> = None;
 
     for attempt in 1..=inner.max_retries {
+        if let Some(cb) = &inner.on_reconnect_event {
+            cb(&ReconnectEvent {
+                attempt,
+                delay: if attempt > 1 { delay } else { Duration::ZERO },
+                error: last_err.as_ref().unwrap_or(cause),
+            });
+        }
         if attempt > 1 {
             sleep_with_cancel(delay, ctx, inner)?;
             delay = cmp::min(delay * 2, inner.max_retry_delay);
@@ -473,6 +1248,13 @@ fn reconnect(inner: &Arc<Inner>, ctx: &RequestContext) -> Result<()> {
         if inner.closed.load(Ordering::SeqCst) {
             return Err(Error::ClientClosed);
         }
+        let budget = match ctx.deadline() {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) if !left.is_zero() => Some(left),
+                _ => return Err(Error::DeadlineExceeded),
+            },
+            None => None,
+        };
 
         if let Ok(mut guard) = inner.client.lock() {
             if let Some(client) = guard.take() {
@@ -480,29 +1262,46 @@ fn reconnect(inner: &Arc<Inner>, ctx: &RequestContext) -> Result<()> {
             }
         }
 
-        match (inner.dial_func)() {
+        match (inner.dial_func)(budget) {
             Ok(client) => {
                 let client = Arc::new(client);
                 let session_id = client.session_id();
                 if let Ok(mut guard) = inner.client.lock() {
                     *guard = Some(client);
                 }
+                trace::reconnected(ctx, attempt, session_id);
                 if let Some(cb) = &inner.on_reconnect {
                     cb(session_id);
                 }
                 return Ok(());
             }
             Err(err) => {
+                trace::reconnect_failed(ctx, attempt, &err);
                 last_err = Some(err);
             }
         }
     }
 
+    if ctx
+        .deadline()
+        .is_some_and(|deadline| deadline <= Instant::now())
+    {
+        return Err(Error::DeadlineExceeded);
+    }
     Err(last_err.unwrap_or(Error::ClientClosed))
 }
 
+/// Sleeps for a backoff `duration`, failing at once with
+/// [`Error::DeadlineExceeded`] if it would end past the request deadline,
+/// so the worker is not held by a request that cannot finish in time.
 fn sleep_with_cancel(duration: Duration, ctx: &RequestContext, inner: &Arc<Inner>) -> Result<()> {
     let start = Instant::now();
+    if ctx
+        .deadline()
+        .is_some_and(|deadline| start + duration >= deadline)
+    {
+        return Err(Error::DeadlineExceeded);
+    }
     let step = Duration::from_millis(50);
     while start.elapsed() < duration {
         if inner.closed.load(Ordering::SeqCst) {
@@ -513,7 +1312,7 @@ fn sleep_with_cancel(duration: Duration, ctx: &RequestContext, inner: &Arc<Inner
         }
         if let Some(deadline) = ctx.deadline() {
             if deadline <= Instant::now() {
-                return Err(Error::Timeout);
+                return Err(Error::DeadlineExceeded);
             }
         }
         let remaining = duration.saturating_sub(start.elapsed());
@@ -538,7 +1337,7 @@ fn wait_for_result(result_rx: &Receiver<Result<()>>, ctx: &RequestContext) -> Re
         let timeout = match deadline {
             Some(deadline) => {
                 if deadline <= Instant::now() {
-                    return Err(Error::Timeout);
+                    return Err(Error::DeadlineExceeded);
                 }
                 Some(deadline.saturating_duration_since(Instant::now()))
             }
@@ -561,7 +1360,7 @@ fn wait_for_result(result_rx: &Receiver<Result<()>>, ctx: &RequestContext) -> Re
                 }
                 if let Some(deadline) = deadline {
                     if deadline <= Instant::now() {
-                        return Err(Error::Timeout);
+                        return Err(Error::DeadlineExceeded);
                     }
                 }
                 continue;
@@ -576,10 +1375,21 @@ fn wait_for_result(result_rx: &Receiver<Result<()>>, ctx: &RequestContext) -> Re
 pub fn is_connection_error(err: &Error) -> bool {
     match err {
         Error::ClientClosed => false,
-        Error::Server(_) => false,
-        Error::Timeout => false,
+        Error::Server { .. } => false,
+        Error::Unauthenticated { .. } => false,
+        Error::CertPinMismatch => false,
+        Error::QuotaExceeded { .. } | Error::Unsupported(_) => false,
+        Error::HashMismatch { .. } | Error::Validation(_) => false,
+        Error::NotAnAncestor { .. } | Error::Pruned { .. } => false,
+        Error::ContextArchived { .. } => false,
+        Error::DeadlineExceeded => false,
         Error::Cancelled => false,
-        Error::QueueFull => false,
+        Error::QueueFull | Error::AppendsDropped { .. } => false,
+        Error::RateLimited { .. } => false,
+        // A best-effort batch may have appended the entries before the one
+        // that failed, so a retry would append them twice.
+        Error::TransactionAborted { .. } => false,
+        Error::Connect { .. } | Error::ConnectionClosed | Error::FrameCorrupted { .. } => true,
         Error::Io(io_err) => match io_err.kind() {
             std::io::ErrorKind::ConnectionReset
             | std::io::ErrorKind::ConnectionAborted
@@ -591,7 +1401,7 @@ pub fn is_connection_error(err: &Error) -> bool {
             _ => contains_connection_pattern(&io_err.to_string()),
         },
         Error::Tls(msg) => contains_connection_pattern(msg),
-        Error::InvalidResponse(msg) => contains_connection_pattern(msg),
+        Error::Protocol(msg) => contains_connection_pattern(msg),
         _ => contains_connection_pattern(&err.to_string()),
     }
 }
@@ -649,12 +1459,7 @@ mod tests {
     #[test]
     fn is_connection_error_matches_basic_cases() {
         assert!(!is_connection_error(&Error::ClientClosed));
-        assert!(!is_connection_error(&Error::Server(
-            crate::error::ServerError {
-                code: 404,
-                detail: "not found".into()
-            }
-        )));
+        assert!(!is_connection_error(&Error::server(404, "not found")));
         assert!(is_connection_error(&Error::Io(std::io::Error::new(
             std::io::ErrorKind::ConnectionReset,
             "reset"
@@ -669,6 +1474,10 @@ mod tests {
         assert!(is_connection_error(&Error::Io(std::io::Error::other(
             "use of closed network connection"
         ))));
+        assert!(!is_connection_error(&Error::TransactionAborted {
+            entry: 1,
+            source: Box::new(Error::ConnectionClosed),
+        }));
     }
 
     #[test]
@@ -710,6 +1519,7 @@ mod tests {
         let (queued_tx, queued_rx) = bounded(1);
         let queued_req = QueuedRequest {
             ctx: RequestContext::background(),
+            desc: "queued",
             op: Arc::new(|_| Ok(())),
             result_tx: queued_tx,
         };
@@ -767,6 +1577,7 @@ mod tests {
         let (queued_tx, queued_rx) = bounded(1);
         let queued_req = QueuedRequest {
             ctx: RequestContext::background(),
+            desc: "queued",
             op: Arc::new(|_| Ok(())),
             result_tx: queued_tx,
         };
@@ -902,6 +1713,208 @@ mod tests {
         handle.join().unwrap();
     }
 
+    #[test]
+    fn deadline_bounds_reconnect_against_a_bouncing_server() {
+        let (addr, stop_tx, handle) = start_hello_server();
+        let dial_count = Arc::new(AtomicUsize::new(0));
+        let dial_func: DialFunc = Arc::new({
+            let addr = addr.clone();
+            let dial_count = dial_count.clone();
+            move || {
+                if dial_count.fetch_add(1, AtomicOrdering::SeqCst) == 0 {
+                    return dial(&addr, Vec::<ClientOption>::new());
+                }
+                // The server accepts and drops each connection a while later.
+                thread::sleep(Duration::from_millis(40));
+                Err(Error::ConnectionClosed)
+            }
+        });
+        let client = dial_reconnecting_inner(
+            &addr,
+            false,
+            vec![
+                with_dial_func(dial_func),
+                with_max_retries(50),
+                with_retry_delay(Duration::from_millis(60)),
+            ],
+            Vec::<ClientOption>::new(),
+        )
+        .unwrap();
+
+        // The worker gives up at the deadline instead of backing off for
+        // seconds, so the next request is taken at once.
+        let ctx = RequestContext::with_timeout(Duration::from_millis(300));
+        let err = client
+            .enqueue(&ctx, "force-reconnect", |_| Err(Error::ConnectionClosed))
+            .unwrap_err();
+        assert!(matches!(err, Error::DeadlineExceeded), "{err:?}");
+        let started = Instant::now();
+        let _ = client.enqueue(&RequestContext::background(), "next", |_| Ok(()));
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        // The dial loop itself stops at the deadline too.
+        let dials_before = dial_count.load(AtomicOrdering::SeqCst);
+        let ctx = RequestContext::with_timeout(Duration::from_millis(300));
+        let started = Instant::now();
+        let err = reconnect(&client.inner, &ctx, &Error::ConnectionClosed).unwrap_err();
+        assert!(matches!(err, Error::DeadlineExceeded), "{err:?}");
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(dial_count.load(AtomicOrdering::SeqCst) - dials_before <= 5);
+
+        client.close().unwrap();
+        let _ = stop_tx.send(());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn retry_policy_honors_retryable_flag() {
+        let policy = RetryPolicy::default();
+        let busy = Error::server(503, "storage busy");
+        let invalid = Error::server(422, "invalid type_id");
+        assert!(policy.should_retry(&busy, 0));
+        assert!(!policy.should_retry(&busy, policy.max_attempts));
+        assert!(!policy.should_retry(&invalid, 0));
+        assert!(!RetryPolicy::none().should_retry(&busy, 0));
+    }
+
+    #[test]
+    fn retryable_server_errors_are_retried() {
+        let (addr, stop_tx, handle) = start_hello_server();
+        let dial_func: DialFunc = Arc::new({
+            let addr = addr.clone();
+            move || dial(&addr, Vec::<ClientOption>::new())
+        });
+        let client = dial_reconnecting_inner(
+            &addr,
+            false,
+            vec![
+                with_dial_func(dial_func),
+                with_retry_delay(Duration::from_millis(1)),
+            ],
+            Vec::<ClientOption>::new(),
+        )
+        .unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        client
+            .enqueue(&RequestContext::background(), "flaky", move |_| {
+                if calls_clone.fetch_add(1, AtomicOrdering::SeqCst) < 2 {
+                    Err(Error::server(503, "storage busy"))
+                } else {
+                    Ok(())
+                }
+            })
+            .unwrap();
+        assert_eq!(calls.load(AtomicOrdering::SeqCst), 3);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        let err = client
+            .enqueue(&RequestContext::background(), "permanent", move |_| {
+                calls_clone.fetch_add(1, AtomicOrdering::SeqCst);
+                Err(Error::server(422, "invalid type_id"))
+            })
+            .unwrap_err();
+        assert!(crate::error::is_server_error(&err, 422));
+        assert_eq!(calls.load(AtomicOrdering::SeqCst), 1);
+
+        client.close().unwrap();
+        let _ = stop_tx.send(());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn reconnect_and_retry_callbacks_fire_without_locks_held() {
+        let (addr, stop_tx, handle) = start_hello_server();
+        let (addr2, stop_tx2, handle2) = start_hello_server();
+        let dial_count = Arc::new(AtomicUsize::new(0));
+        let dial_func: DialFunc = Arc::new({
+            let dial_count = dial_count.clone();
+            move || match dial_count.fetch_add(1, AtomicOrdering::SeqCst) {
+                0 => dial(&addr, Vec::<ClientOption>::new()),
+                1 => Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "refused",
+                ))),
+                _ => dial(&addr2, Vec::<ClientOption>::new()),
+            }
+        });
+
+        // Callbacks read the client back, which would deadlock if they ran
+        // under the connection lock.
+        let this: Arc<std::sync::OnceLock<std::sync::Weak<ReconnectingClient>>> = Arc::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let on_reconnect = {
+            let (this, events) = (this.clone(), events.clone());
+            move |event: &ReconnectEvent<'_>| {
+                let session = this.get().and_then(|c| c.upgrade()).map(|c| c.session_id());
+                events.lock().unwrap().push(format!(
+                    "reconnect {} {:?} {} {session:?}",
+                    event.attempt, event.delay, event.error
+                ));
+            }
+        };
+        let on_retry = {
+            let (this, events) = (this.clone(), events.clone());
+            move |event: &RetryEvent<'_>| {
+                let session = this.get().and_then(|c| c.upgrade()).map(|c| c.session_id());
+                events.lock().unwrap().push(format!(
+                    "retry {} {} {:?} {} {session:?}",
+                    event.op, event.attempt, event.delay, event.error
+                ));
+            }
+        };
+        let client = Arc::new(
+            dial_reconnecting_inner(
+                "unused",
+                false,
+                vec![
+                    with_dial_func(dial_func),
+                    with_retry_delay(Duration::from_millis(1)),
+                    with_on_reconnect_event(on_reconnect),
+                    with_on_retry(on_retry),
+                ],
+                Vec::<ClientOption>::new(),
+            )
+            .unwrap(),
+        );
+        this.set(Arc::downgrade(&client)).unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        client
+            .enqueue(
+                &RequestContext::background(),
+                "Flaky",
+                move |_| match calls_clone.fetch_add(1, AtomicOrdering::SeqCst) {
+                    0 => Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::ConnectionReset,
+                        "reset",
+                    ))),
+                    1 => Err(Error::server(503, "storage busy")),
+                    _ => Ok(()),
+                },
+            )
+            .unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3, "{events:?}");
+        assert!(events[0].starts_with("reconnect 1 0ns "), "{}", events[0]);
+        assert!(events[0].contains("reset"), "{}", events[0]);
+        assert!(events[1].starts_with("reconnect 2 1ms "), "{}", events[1]);
+        assert!(events[1].contains("refused"), "{}", events[1]);
+        assert!(events[2].starts_with("retry Flaky 1 1ms "), "{}", events[2]);
+        assert!(events[2].contains("storage busy"), "{}", events[2]);
+        assert!(events[2].ends_with("Some(1)"), "{}", events[2]);
+
+        client.close().unwrap();
+        for (stop_tx, handle) in [(stop_tx, handle), (stop_tx2, handle2)] {
+            let _ = stop_tx.send(());
+            handle.join().unwrap();
+        }
+    }
+
     #[test]
     fn queue_full_returns_error_legacy() {
         let dial_func: DialFunc = Arc::new(|| Err(Error::ClientClosed));
@@ -0,0 +1,159 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Redacting turns.
+//!
+//! Compliance sometimes requires removing what one turn says, such as PII
+//! leaked into a prompt, without destroying the conversation around it.
+//! [`Client::redact_turn`] erases the turn's payload on the server. The turn
+//! keeps its id, place in history, type and `payload_hash`, so the history
+//! keeps its shape and the hash stays for audit. Reads return it with
+//! [`redacted`](crate::TurnRecord::redacted) set, an empty payload and the
+//! reason and time of the redaction, and decoding it fails with
+//! [`Error::Redacted`](crate::Error::Redacted).
+
+#[cfg(not(target_arch = "wasm32"))]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::MSG_TURN_REDACT;
+
+/// How [`Client::redact_turn`] records a redaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedactOptions {
+    /// Why the turn is redacted, reported with it on every read. May be
+    /// empty; servers reject reasons over 1024 bytes.
+    pub reason: String,
+}
+
+impl RedactOptions {
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = reason.into();
+        self
+    }
+}
+
+/// A redaction as recorded by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    pub turn_id: TurnId,
+    pub redacted_at_unix_ms: u64,
+    pub reason: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Erases the payload of `turn_id`, which must be on the history of
+    /// `context_id`, keeping the turn itself (see the
+    /// [module docs](self)). Redacting a turn again returns the first
+    /// redaction unchanged. Drops the turn from the turn cache (see
+    /// [`crate::cache`]).
+    ///
+    /// The server erases the payload blob unless another unredacted turn
+    /// holds the same content. A turn off the context's history fails with
+    /// a 422 server error. Servers without the TURN_REDACT message fail with
+    /// [`Error::Unsupported`].
+    pub fn redact_turn(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        turn_id: TurnId,
+        opts: RedactOptions,
+    ) -> Result<Redaction> {
+        let mut payload = Vec::with_capacity(20 + opts.reason.len());
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        payload.write_u64::<LittleEndian>(turn_id.get())?;
+        payload.write_u32::<LittleEndian>(opts.reason.len() as u32)?;
+        payload.extend_from_slice(opts.reason.as_bytes());
+        let response = self.send_request(ctx, MSG_TURN_REDACT, &payload);
+        self.prefetch_cache().invalidate(context_id);
+        if let Some(cache) = self.turn_cache() {
+            cache.remove(self.namespace_of(ctx), turn_id);
+        }
+        let frame =
+            response.map_err(|err| err.resolve_unsupported("TURN_REDACT").resolve_not_found())?;
+        parse_redaction(&frame.payload)
+    }
+}
+
+pub(crate) fn parse_redaction(payload: &[u8]) -> Result<Redaction> {
+    let mut reader = PayloadReader::new(payload, "redact response");
+    let turn_id = TurnId::new(reader.u64("turn_id")?);
+    let redacted_at_unix_ms = reader.u64("redacted_at_unix_ms")?;
+    let reason = std::str::from_utf8(reader.len_prefixed("reason")?)
+        .map_err(|_| Error::protocol("redaction reason not utf8"))?;
+    Ok(Redaction {
+        turn_id,
+        redacted_at_unix_ms,
+        reason: reason.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_ERROR;
+    use crate::test_util::{error_payload, not_found_payload, spawn_scripted_server};
+
+    fn redaction_payload(turn_id: u64, redacted_at: u64, reason: &str) -> Vec<u8> {
+        let mut out = turn_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&redacted_at.to_le_bytes());
+        out.extend_from_slice(&(reason.len() as u32).to_le_bytes());
+        out.extend_from_slice(reason.as_bytes());
+        out
+    }
+
+    #[test]
+    fn redact_sends_reason_and_reads_back_the_marker() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (
+                MSG_TURN_REDACT,
+                redaction_payload(7, 1_700_000_000_000, "pii"),
+            ),
+            (MSG_ERROR, not_found_payload("turn", 8)),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let opts = RedactOptions::default().reason("pii");
+        let redaction = client
+            .redact_turn(&ctx, ContextId::new(3), TurnId::new(7), opts.clone())
+            .unwrap();
+        assert_eq!(
+            redaction,
+            Redaction {
+                turn_id: TurnId::new(7),
+                redacted_at_unix_ms: 1_700_000_000_000,
+                reason: "pii".into(),
+            }
+        );
+        let err = client
+            .redact_turn(&ctx, ContextId::new(3), TurnId::new(8), opts.clone())
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::TurnNotFound { turn_id } if turn_id.get() == 8),
+            "{err:?}"
+        );
+        let err = client
+            .redact_turn(&ctx, ContextId::new(3), TurnId::new(7), opts)
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.msg_type, MSG_TURN_REDACT);
+        assert_eq!(requests[0].payload, {
+            let mut expected = 3u64.to_le_bytes().to_vec();
+            expected.extend_from_slice(&7u64.to_le_bytes());
+            expected.extend_from_slice(&3u32.to_le_bytes());
+            expected.extend_from_slice(b"pii");
+            expected
+        });
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wire recordings for debugging.
+//!
+//! [`with_wire_recording`] tees every frame the client and every connection
+//! redialed from it send and receive into a file, byte for byte, with a
+//! timestamp and direction. [`ReplayServer`] serves such a recording back,
+//! so a misbehaving exchange can be reproduced in a unit test without the
+//! server that produced it:
+//!
+//! ```no_run
+//! use cxdb::replay::{with_wire_recording, ReplayServer};
+//! use cxdb::{dial, ContextId, GetLastOptions, RequestContext};
+//!
+//! let context_id = ContextId::new(1);
+//!
+//! // Capture, against the real server.
+//! let client = dial("127.0.0.1:9009", [with_wire_recording("session.cxwire")])?;
+//! let turns = client.get_last(&RequestContext::background(), context_id, GetLastOptions::default());
+//! drop(client);
+//!
+//! // Reproduce, anywhere.
+//! let server = ReplayServer::start("session.cxwire")?;
+//! let client = dial(&server.addr(), [])?;
+//! let replayed = client.get_last(&RequestContext::background(), context_id, GetLastOptions::default());
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! Recordings hold everything on the wire, including bearer tokens and
+//! payloads. [`with_redacted_wire_recording`] keeps only frame headers, which
+//! is safe to share but cannot be replayed.
+//!
+//! # File format
+//!
+//! The magic `CXDBWIRE` and a version (`u16`, 1), then one record per frame,
+//! all integers little-endian:
+//!
+//! ```text
+//! connection:     u32   // 0 for the first connection, counting up per dial
+//! direction:      u8    // 0 = client to server, 1 = server to client
+//! redacted:       u8    // 1 = payload (and checksum) bytes withheld
+//! at_unix_micros: u64
+//! frame_len:      u32
+//! frame:          [frame_len]u8  // the frame as on the wire: header,
+//!                                // payload and any CRC32C trailer
+//! ```
+//!
+//! A redacted record's frame is the 16-byte header alone; its `len` field
+//! still gives the payload size.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::client::ClientOption;
+use crate::error::{Error, Result};
+use crate::metrics::Direction;
+use crate::protocol::{FrameHeader, FRAME_HEADER_LEN};
+
+const MAGIC: &[u8; 8] = b"CXDBWIRE";
+const VERSION: u16 = 1;
+
+/// Records every frame of the client's connections to `path`, replacing
+/// any existing file. The file is created at dial.
+pub fn with_wire_recording(path: impl Into<PathBuf>) -> ClientOption {
+    let recorder = Arc::new(WireRecorder::new(path.into(), false));
+    Arc::new(move |opts| opts.wire_recorder = Some(recorder.clone()))
+}
+
+/// Like [`with_wire_recording`], but records frame headers only.
+pub fn with_redacted_wire_recording(path: impl Into<PathBuf>) -> ClientOption {
+    let recorder = Arc::new(WireRecorder::new(path.into(), true));
+    Arc::new(move |opts| opts.wire_recorder = Some(recorder.clone()))
+}
+
+/// One recorded frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireRecord {
+    pub connection: u32,
+    pub direction: Direction,
+    pub redacted: bool,
+    pub at_unix_micros: u64,
+    /// The frame as on the wire, or its header alone when `redacted`.
+    pub frame: Vec<u8>,
+}
+
+impl WireRecord {
+    pub fn header(&self) -> Option<FrameHeader> {
+        let header = self.frame.first_chunk::<FRAME_HEADER_LEN>()?;
+        Some(FrameHeader::decode(header))
+    }
+}
+
+/// Reads every record of the recording at `path`.
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<WireRecord>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    let version = reader.read_u16::<LittleEndian>()?;
+    if &magic != MAGIC || version != VERSION {
+        return Err(Error::protocol("not a cxdb wire recording"));
+    }
+    let mut records = Vec::new();
+    loop {
+        let connection = match reader.read_u32::<LittleEndian>() {
+            Ok(connection) => connection,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        let direction = match reader.read_u8()? {
+            0 => Direction::Sent,
+            1 => Direction::Received,
+            other => return Err(Error::protocol(format!("bad record direction {other}"))),
+        };
+        let redacted = reader.read_u8()? != 0;
+        let at_unix_micros = reader.read_u64::<LittleEndian>()?;
+        let frame_len = reader.read_u32::<LittleEndian>()? as usize;
+        let mut frame = vec![0u8; frame_len];
+        reader.read_exact(&mut frame)?;
+        records.push(WireRecord {
+            connection,
+            direction,
+            redacted,
+            at_unix_micros,
+            frame,
+        });
+    }
+    Ok(records)
+}
+
+/// The recording file shared by a client's connections.
+pub(crate) struct WireRecorder {
+    path: PathBuf,
+    redacted: bool,
+    file: Mutex<Option<File>>,
+    connections: AtomicU32,
+}
+
+impl std::fmt::Debug for WireRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WireRecorder")
+            .field("path", &self.path)
+            .field("redacted", &self.redacted)
+            .finish()
+    }
+}
+
+impl WireRecorder {
+    fn new(path: PathBuf, redacted: bool) -> Self {
+        Self {
+            path,
+            redacted,
+            file: Mutex::new(None),
+            connections: AtomicU32::new(0),
+        }
+    }
+
+    /// Starts recording a new connection, creating the file on first use.
+    pub(crate) fn connection(self: &Arc<Self>) -> Result<ConnectionRecorder> {
+        let mut file = self.file.lock().map_err(|_| Error::ClientClosed)?;
+        if file.is_none() {
+            let mut created = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)?;
+            created.write_all(MAGIC)?;
+            created.write_u16::<LittleEndian>(VERSION)?;
+            *file = Some(created);
+        }
+        Ok(ConnectionRecorder {
+            recorder: self.clone(),
+            connection: self.connections.fetch_add(1, Ordering::SeqCst),
+            pending: [Vec::new(), Vec::new()],
+        })
+    }
+
+    fn write(&self, connection: u32, direction: Direction, frame: &[u8]) {
+        let frame = if self.redacted {
+            &frame[..FRAME_HEADER_LEN]
+        } else {
+            frame
+        };
+        let at_unix_micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+        let mut record = Vec::with_capacity(18 + frame.len());
+        record.extend_from_slice(&connection.to_le_bytes());
+        record.push(match direction {
+            Direction::Sent => 0,
+            Direction::Received => 1,
+        });
+        record.push(u8::from(self.redacted));
+        record.extend_from_slice(&at_unix_micros.to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(frame);
+        // Recording is best effort; a full disk must not fail requests.
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.write_all(&record);
+            }
+        }
+    }
+}
+
+/// Splits one connection's byte streams into frames as they pass.
+pub(crate) struct ConnectionRecorder {
+    recorder: Arc<WireRecorder>,
+    connection: u32,
+    /// Bytes of a partial frame, per direction.
+    pending: [Vec<u8>; 2],
+}
+
+impl ConnectionRecorder {
+    pub(crate) fn observe(&mut self, direction: Direction, bytes: &[u8]) {
+        let pending = &mut self.pending[direction as usize];
+        pending.extend_from_slice(bytes);
+        loop {
+            let Some(header) = pending.first_chunk::<FRAME_HEADER_LEN>() else {
+                return;
+            };
+            let frame_len = FRAME_HEADER_LEN + FrameHeader::decode(header).len as usize;
+            if pending.len() < frame_len {
+                return;
+            }
+            self.recorder
+                .write(self.connection, direction, &pending[..frame_len]);
+            pending.drain(..frame_len);
+        }
+    }
+}
+
+/// Serves a wire recording back to clients, for reproducing a session in a
+/// test. Each accepted connection replays the next recorded connection:
+/// every recorded request is read from the client and checked against the
+/// recording by message type, and the recorded responses are written back
+/// verbatim (request ids included, which a client replaying the same calls
+/// allocates identically).
+pub struct ReplayServer {
+    addr: String,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl ReplayServer {
+    /// Listens on an ephemeral localhost port and replays the recording at
+    /// `path`. Redacted recordings cannot be replayed.
+    pub fn start(path: impl AsRef<Path>) -> Result<Self> {
+        let records = read_recording(path)?;
+        if records.iter().any(|record| record.redacted) {
+            return Err(Error::protocol("redacted recordings cannot be replayed"));
+        }
+        let mut connections: Vec<Vec<WireRecord>> = Vec::new();
+        for record in records {
+            let index = record.connection as usize;
+            if connections.len() <= index {
+                connections.resize(index + 1, Vec::new());
+            }
+            connections[index].push(record);
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?.to_string();
+        let handle = thread::spawn(move || {
+            for (index, records) in connections.into_iter().enumerate() {
+                let (stream, _) = listener.accept()?;
+                replay_connection(stream, &records)
+                    .map_err(|err| Error::protocol(format!("replay connection {index}: {err}")))?;
+            }
+            Ok(())
+        });
+        Ok(Self { addr, handle })
+    }
+
+    pub fn addr(&self) -> String {
+        self.addr.clone()
+    }
+
+    /// Waits until every recorded connection has been replayed, returning
+    /// the first divergence from the recording.
+    pub fn join(self) -> Result<()> {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(Error::protocol("replay server panicked")))
+    }
+}
+
+fn replay_connection(mut stream: TcpStream, records: &[WireRecord]) -> Result<()> {
+    for record in records {
+        match record.direction {
+            Direction::Sent => {
+                let mut header = [0u8; FRAME_HEADER_LEN];
+                stream.read_exact(&mut header)?;
+                let header = FrameHeader::decode(&header);
+                let mut payload = vec![0u8; header.len as usize];
+                stream.read_exact(&mut payload)?;
+                let expected = record.header().map(|h| h.msg_type);
+                if expected != Some(header.msg_type) {
+                    return Err(Error::protocol(format!(
+                        "client sent msg_type {}, recording has {expected:?}",
+                        header.msg_type
+                    )));
+                }
+            }
+            Direction::Received => stream.write_all(&record.frame)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ContextId;
+    use crate::metrics::Direction;
+    use crate::protocol::{MSG_CTX_CREATE, MSG_GET_LAST, MSG_HELLO};
+    use crate::test_util::{spawn_scripted_server, turn_listing_payload, turn_records_payload};
+    use crate::{dial, CreateContextOptions, GetLastOptions, RequestContext};
+
+    #[test]
+    fn recorded_sessions_replay_deterministically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cxwire");
+        let mut head = 7u64.to_le_bytes().to_vec();
+        head.extend_from_slice(&[0u8; 12]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, head),
+            (
+                MSG_GET_LAST,
+                turn_records_payload(&[b"\x91\x01", b"\x91\x02"]),
+            ),
+        ]);
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+        let session = |client: &crate::Client| {
+            let head = client
+                .create_context(&ctx, CreateContextOptions::default())
+                .unwrap();
+            let turns = client
+                .get_last(&ctx, head.context_id, opts.clone())
+                .unwrap();
+            (head, turns)
+        };
+
+        let client = dial(&addr, [with_wire_recording(&path)]).unwrap();
+        let recorded = session(&client);
+        drop(client);
+        handle.join().unwrap();
+
+        let records = read_recording(&path).unwrap();
+        let shape: Vec<_> = records
+            .iter()
+            .map(|r| (r.connection, r.direction, r.header().unwrap().msg_type))
+            .collect();
+        assert_eq!(
+            shape,
+            [
+                (0, Direction::Sent, MSG_HELLO),
+                (0, Direction::Received, MSG_HELLO),
+                (0, Direction::Sent, MSG_CTX_CREATE),
+                (0, Direction::Received, MSG_CTX_CREATE),
+                (0, Direction::Sent, MSG_GET_LAST),
+                (0, Direction::Received, MSG_GET_LAST),
+            ]
+        );
+        assert!(records
+            .windows(2)
+            .all(|w| w[0].at_unix_micros <= w[1].at_unix_micros));
+
+        let server = ReplayServer::start(&path).unwrap();
+        let client = dial(&server.addr(), []).unwrap();
+        assert_eq!(session(&client), recorded);
+        drop(client);
+        server.join().unwrap();
+
+        // A client that strays from the recording is reported.
+        let server = ReplayServer::start(&path).unwrap();
+        let client = dial(&server.addr(), []).unwrap();
+        client.get_last(&ctx, ContextId::new(7), opts).unwrap_err();
+        drop(client);
+        assert!(server.join().is_err());
+    }
+
+    #[test]
+    fn redacted_recordings_keep_headers_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redacted.cxwire");
+        let (addr, handle) =
+            spawn_scripted_server(vec![(MSG_GET_LAST, turn_listing_payload(&[b"\x91\x01"]))]);
+        let client = dial(
+            &addr,
+            [
+                with_redacted_wire_recording(&path),
+                crate::with_bearer_token("s3cr3t"),
+            ],
+        )
+        .unwrap();
+        client
+            .get_last(
+                &RequestContext::background(),
+                ContextId::new(1),
+                GetLastOptions::default(),
+            )
+            .unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        let records = read_recording(&path).unwrap();
+        assert_eq!(records.len(), 4);
+        for record in &records {
+            assert!(record.redacted);
+            assert_eq!(record.frame.len(), FRAME_HEADER_LEN);
+            assert!(record.header().unwrap().len > 0);
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.windows(6).any(|w| w == b"s3cr3t"));
+        assert!(ReplayServer::start(&path).is_err());
+    }
+}
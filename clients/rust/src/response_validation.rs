@@ -0,0 +1,212 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checking the turn pages a server returns.
+//!
+//! A GET_LAST page walks one context's history oldest first, so its turn
+//! ids strictly increase, its depths do too, and a turn following its
+//! parent is exactly one deeper. A server that breaks this, sending a turn
+//! twice, turns out of order, or depths that disagree with the parent
+//! links, would otherwise go unnoticed: reads sort pages by turn id, and
+//! maps keyed by turn id silently drop the duplicate.
+//!
+//! Every read of the blocking client's `get_last` family checks its page.
+//! What happens to a bad one is set with [`with_response_validation`]:
+//!
+//! - [`ResponseValidation::Lenient`], the default, passes the page through
+//!   unchanged and reports it to the client's
+//!   [`Metrics::on_inconsistent_page`] hook and, with the `tracing` feature,
+//!   as a warning.
+//! - [`ResponseValidation::Strict`] fails the read with
+//!   [`Error::InconsistentPage`].
+//!
+//! Fields left out by a [projection](crate::GetLastOptions::projection) are
+//! not checked.
+//!
+//! ```no_run
+//! use cxdb::response_validation::{with_response_validation, ResponseValidation};
+//!
+//! let client = cxdb::dial(
+//!     "127.0.0.1:9009",
+//!     [with_response_validation(ResponseValidation::Strict)],
+//! )?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`Metrics::on_inconsistent_page`]: crate::metrics::Metrics::on_inconsistent_page
+//! [`Error::InconsistentPage`]: crate::Error::InconsistentPage
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::client::{Client, ClientOption, RequestContext};
+use crate::error::{Error, Result};
+use crate::metrics::Operation;
+use crate::turn::{TurnFields, TurnRecord};
+
+/// What a client does with a malformed page; see the
+/// [module docs](self).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseValidation {
+    /// Report the page and return it as received.
+    #[default]
+    Lenient,
+    /// Fail the read with [`Error::InconsistentPage`].
+    Strict,
+}
+
+/// Sets how the client treats malformed turn pages; lenient by default.
+pub fn with_response_validation(mode: ResponseValidation) -> ClientOption {
+    Arc::new(move |opts| opts.response_validation = mode)
+}
+
+/// What is wrong with `records`, a page in the order the server sent it,
+/// looking only at `fields`; `None` if nothing is.
+pub(crate) fn page_defect<P>(records: &[TurnRecord<P>], fields: TurnFields) -> Option<String> {
+    for pair in records.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        if next.turn_id <= prev.turn_id {
+            // A repeated id is the likelier bug; name it if there is one.
+            let mut seen = HashSet::with_capacity(records.len());
+            if let Some(dup) = records.iter().find(|r| !seen.insert(r.turn_id)) {
+                return Some(format!("turn {} appears twice", dup.turn_id));
+            }
+            return Some(format!(
+                "turn {} follows turn {}",
+                next.turn_id, prev.turn_id
+            ));
+        }
+        if !fields.contains(TurnFields::DEPTH) {
+            continue;
+        }
+        if next.depth <= prev.depth {
+            return Some(format!(
+                "turn {} at depth {} follows turn {} at depth {}",
+                next.turn_id, next.depth, prev.turn_id, prev.depth
+            ));
+        }
+        if fields.contains(TurnFields::PARENT)
+            && next.parent_id == prev.turn_id
+            && next.depth != prev.depth + 1
+        {
+            return Some(format!(
+                "turn {} at depth {} has parent {} at depth {}",
+                next.turn_id, next.depth, prev.turn_id, prev.depth
+            ));
+        }
+    }
+    None
+}
+
+impl Client {
+    /// Checks a page of `op` holding `fields`, as the client's
+    /// [`ResponseValidation`] says.
+    pub(crate) fn check_page<P>(
+        &self,
+        ctx: &RequestContext,
+        op: Operation,
+        records: &[TurnRecord<P>],
+        fields: TurnFields,
+    ) -> Result<()> {
+        let Some(reason) = page_defect(records, fields) else {
+            return Ok(());
+        };
+        match self.response_validation() {
+            ResponseValidation::Strict => Err(Error::InconsistentPage { reason }),
+            ResponseValidation::Lenient => {
+                if let Some(metrics) = self.metrics() {
+                    metrics.on_inconsistent_page(op, &reason);
+                }
+                crate::trace::inconsistent_page(ctx, op.as_str(), &reason);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::ids::ContextId;
+    use crate::metrics::{with_metrics, InMemoryMetrics};
+    use crate::protocol::MSG_GET_LAST;
+    use crate::test_util::{raw_turns_payload, spawn_scripted_server};
+    use crate::turn::GetLastOptions;
+
+    fn records(turns: &[(u64, u64, u32)]) -> Vec<TurnRecord> {
+        crate::turn::parse_turn_records(&raw_turns_payload(turns)).unwrap()
+    }
+
+    #[test]
+    fn page_defect_names_the_first_problem() {
+        let defect = |turns: &[(u64, u64, u32)]| page_defect(&records(turns), TurnFields::ALL);
+        assert_eq!(defect(&[]), None);
+        assert_eq!(defect(&[(4, 2, 1), (5, 4, 2), (9, 5, 3)]), None);
+        // A pruned root keeps its parent and depth.
+        assert_eq!(defect(&[(7, 3, 5), (8, 7, 6)]), None);
+
+        assert_eq!(
+            defect(&[(4, 2, 1), (5, 4, 2), (4, 2, 1)]).unwrap(),
+            "turn 4 appears twice"
+        );
+        assert_eq!(
+            defect(&[(5, 4, 2), (4, 2, 1)]).unwrap(),
+            "turn 4 follows turn 5"
+        );
+        assert_eq!(
+            defect(&[(4, 2, 3), (5, 4, 3)]).unwrap(),
+            "turn 5 at depth 3 follows turn 4 at depth 3"
+        );
+        assert_eq!(
+            defect(&[(4, 2, 1), (5, 4, 3)]).unwrap(),
+            "turn 5 at depth 3 has parent 4 at depth 1"
+        );
+        // Fields left out of a projection are not checked.
+        let skipped_depth = records(&[(4, 2, 1), (5, 4, 3)]);
+        assert_eq!(page_defect(&skipped_depth, TurnFields::PARENT), None);
+    }
+
+    #[test]
+    fn strict_clients_fail_and_lenient_ones_report_bad_pages() {
+        let duplicated = raw_turns_payload(&[(4, 0, 0), (5, 4, 1), (5, 4, 1)]);
+        let (addr, server) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, duplicated.clone()),
+            (MSG_GET_LAST, raw_turns_payload(&[(5, 4, 1), (4, 0, 0)])),
+        ]);
+        let strict = dial(
+            &addr,
+            [with_response_validation(ResponseValidation::Strict)],
+        )
+        .unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions::default().include_payload(true);
+        let err = strict
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::InconsistentPage { reason } if reason == "turn 5 appears twice"),
+            "{err:?}"
+        );
+        // Reads sort pages, so the order is checked as sent.
+        let newest_first = opts.clone().order(crate::Order::NewestFirst);
+        let err = strict
+            .get_last(&ctx, ContextId::new(1), newest_first)
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::InconsistentPage { reason } if reason == "turn 4 follows turn 5"),
+            "{err:?}"
+        );
+        strict.close().unwrap();
+        server.join().unwrap();
+
+        let (addr, server) = spawn_scripted_server(vec![(MSG_GET_LAST, duplicated)]);
+        let metrics = Arc::new(InMemoryMetrics::default());
+        let lenient = dial(&addr, [with_metrics(metrics.clone())]).unwrap();
+        let turns = lenient.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        assert_eq!(turns.len(), 3);
+        assert_eq!(metrics.inconsistent_pages(), 1);
+        lenient.close().unwrap();
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,673 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Schema files for tag-numbered msgpack payloads (`schema` feature).
+//!
+//! Payloads key their fields by numeric tag, so a turn appended by another
+//! language decodes to `{1: "user", 2: "hi"}` unless there is a Rust type for
+//! it. A [`SchemaFile`] supplies the names instead, from the same YAML the
+//! other teams define their types in. Its layout is the registry bundle's
+//! (see `docs/type-registry.md`), so a JSON bundle loads as is:
+//!
+//! ```yaml
+//! types:
+//!   com.example.Message:
+//!     versions:
+//!       1:
+//!         fields:
+//!           1: { name: role, type: string }
+//!           2: { name: text, type: string }
+//!           3: { name: attachments, type: array, items: bytes }
+//!           4: { name: sender, type: ref, ref: com.example.User }
+//! ```
+//!
+//! [`SchemaFile::decode_to_json`] renders a turn as an object keyed by field
+//! name, and [`SchemaFile::encode_from_json`] goes back. Tags the schema does
+//! not name are kept as `"_<tag>"` keys, so an older schema does not drop
+//! newer fields. `bytes` fields are base64 strings; other values map to JSON
+//! by their msgpack type. Nested objects (`ref` fields and `ref` array items)
+//! use the newest version of the referenced type.
+//!
+//! ```no_run
+//! # use cxdb::{dial, ContextId, GetLastOptions, RequestContext, SchemaFile};
+//! # let client = dial("127.0.0.1:9009", Vec::new())?;
+//! # let ctx = RequestContext::background();
+//! let schema = SchemaFile::load("types.yaml")?;
+//! let opts = GetLastOptions { include_payload: true, ..Default::default() };
+//! for turn in client.get_last(&ctx, ContextId::new(1), opts)? {
+//!     println!("{}", schema.decode_to_json(&turn)?);
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rmpv::Value;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value as JsonValue};
+
+use crate::error::{Error, Result};
+use crate::turn::TurnRecord;
+
+/// Field names, types and tags per type id and version, read from a schema
+/// file.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaFile {
+    types: HashMap<String, BTreeMap<u32, TypeSchema>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TypeSchema {
+    fields: BTreeMap<u64, FieldSchema>,
+    tags_by_name: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone)]
+struct FieldSchema {
+    name: String,
+    kind: FieldKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FieldKind {
+    Bytes,
+    Int,
+    Float,
+    /// An object of the named type.
+    Ref(String),
+    Array(Box<FieldKind>),
+    /// Anything the schema does not constrain: strings, bools, maps, enums.
+    Other,
+}
+
+impl SchemaFile {
+    /// Reads a schema file. JSON registry bundles load too, JSON being YAML.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::from_yaml(&text)
+            .map_err(|err| Error::decode_with(format!("schema {}: {err}", path.display()), err))
+    }
+
+    /// Parses a schema from YAML text.
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        let file: FileDef = serde_yaml::from_str(text).map_err(Error::decode_err)?;
+        let mut types = HashMap::with_capacity(file.types.len());
+        for (type_id, def) in file.types {
+            let mut versions = BTreeMap::new();
+            for (version, version_def) in def.versions {
+                let version = u32::try_from(version.parse()?).map_err(|_| {
+                    Error::decode(format!("{type_id}: version {version} out of range"))
+                })?;
+                versions.insert(version, TypeSchema::new(&type_id, version, version_def)?);
+            }
+            types.insert(type_id, versions);
+        }
+        Ok(Self { types })
+    }
+
+    /// Whether the schema describes any version of `type_id`.
+    pub fn contains(&self, type_id: &str) -> bool {
+        self.types.contains_key(type_id)
+    }
+
+    /// Renders a turn's payload as a JSON object keyed by field name.
+    ///
+    /// The turn's `type_version` is looked up exactly, falling back to the
+    /// newest older version in the schema. Returns [`Error::Decode`] if the
+    /// schema has no such version or the payload is not a map, and whatever
+    /// [`TurnRecord::decode`] returns for payloads it cannot read.
+    pub fn decode_to_json<P: AsRef<[u8]>>(&self, turn: &TurnRecord<P>) -> Result<JsonValue> {
+        let schema = self.version(turn.type_id.as_str(), turn.type_version)?;
+        let value: Value = turn.decode()?;
+        match value {
+            Value::Map(entries) => Ok(self.object_to_json(schema, &entries)),
+            _ => Err(Error::decode(format!(
+                "turn {} payload is not a map",
+                turn.turn_id
+            ))),
+        }
+    }
+
+    /// Encodes a JSON object as a msgpack payload of `type_id` at
+    /// `type_version`, the reverse of [`decode_to_json`](Self::decode_to_json).
+    ///
+    /// Field names become their tags and `"_<tag>"` keys become `<tag>`;
+    /// fields are written in tag order.
+    /// Returns [`Error::Encode`] for names the schema does not know and for
+    /// values that do not fit their field's type.
+    pub fn encode_from_json(
+        &self,
+        type_id: &str,
+        type_version: u32,
+        value: &JsonValue,
+    ) -> Result<Vec<u8>> {
+        let schema = self.version(type_id, type_version)?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| Error::encode(format!("{type_id}: expected a JSON object")))?;
+        let value = self.object_from_json(type_id, schema, object)?;
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &value).map_err(Error::encode_err)?;
+        Ok(buf)
+    }
+
+    fn version(&self, type_id: &str, type_version: u32) -> Result<&TypeSchema> {
+        self.types
+            .get(type_id)
+            .and_then(|versions| versions.range(..=type_version).next_back())
+            .map(|(_, schema)| schema)
+            .ok_or_else(|| Error::decode(format!("no schema for {type_id} v{type_version}")))
+    }
+
+    fn latest(&self, type_id: &str) -> Option<&TypeSchema> {
+        self.types
+            .get(type_id)
+            .and_then(|versions| versions.values().next_back())
+    }
+
+    fn object_to_json(&self, schema: &TypeSchema, entries: &[(Value, Value)]) -> JsonValue {
+        let mut out = Map::with_capacity(entries.len());
+        for (key, value) in entries {
+            let (name, rendered) = match key_to_tag(key) {
+                Some(tag) => match schema.fields.get(&tag) {
+                    Some(field) => (field.name.clone(), self.field_to_json(&field.kind, value)),
+                    None => (format!("_{tag}"), plain_to_json(value)),
+                },
+                None => (key_to_string(key), plain_to_json(value)),
+            };
+            out.insert(name, rendered);
+        }
+        JsonValue::Object(out)
+    }
+
+    fn field_to_json(&self, kind: &FieldKind, value: &Value) -> JsonValue {
+        match (kind, value) {
+            (FieldKind::Bytes, Value::Binary(bytes)) => JsonValue::String(BASE64.encode(bytes)),
+            (FieldKind::Ref(type_id), Value::Map(entries)) => match self.latest(type_id) {
+                Some(schema) => self.object_to_json(schema, entries),
+                None => plain_to_json(value),
+            },
+            (FieldKind::Array(items), Value::Array(values)) => JsonValue::Array(
+                values
+                    .iter()
+                    .map(|v| self.field_to_json(items, v))
+                    .collect(),
+            ),
+            _ => plain_to_json(value),
+        }
+    }
+
+    fn object_from_json(
+        &self,
+        type_id: &str,
+        schema: &TypeSchema,
+        object: &Map<String, JsonValue>,
+    ) -> Result<Value> {
+        let mut entries = Vec::with_capacity(object.len());
+        for (name, value) in object {
+            let (tag, value) = match schema.tags_by_name.get(name) {
+                Some(&tag) => (tag, self.field_from_json(&schema.fields[&tag].kind, value)?),
+                None => match unknown_tag(name) {
+                    Some(tag) => (tag, plain_from_json(value)),
+                    None => {
+                        return Err(Error::encode(format!("{type_id}: unknown field {name:?}")))
+                    }
+                },
+            };
+            entries.push((tag, value));
+        }
+        entries.sort_by_key(|(tag, _)| *tag);
+        Ok(Value::Map(
+            entries
+                .into_iter()
+                .map(|(tag, value)| (Value::from(tag), value))
+                .collect(),
+        ))
+    }
+
+    fn field_from_json(&self, kind: &FieldKind, value: &JsonValue) -> Result<Value> {
+        Ok(match (kind, value) {
+            (FieldKind::Bytes, JsonValue::String(text)) => Value::Binary(
+                BASE64
+                    .decode(text)
+                    .map_err(|err| Error::encode_with(format!("bytes field: {err}"), err))?,
+            ),
+            (FieldKind::Float, JsonValue::Number(number)) => {
+                Value::F64(number.as_f64().unwrap_or_default())
+            }
+            (FieldKind::Int, JsonValue::Number(number)) => {
+                if let Some(u) = number.as_u64() {
+                    Value::from(u)
+                } else if let Some(i) = number.as_i64() {
+                    Value::from(i)
+                } else {
+                    return Err(Error::encode(format!("expected an integer, got {number}")));
+                }
+            }
+            (FieldKind::Ref(type_id), JsonValue::Object(object)) => match self.latest(type_id) {
+                Some(schema) => self.object_from_json(type_id, schema, object)?,
+                None => plain_from_json(value),
+            },
+            (FieldKind::Array(items), JsonValue::Array(values)) => Value::Array(
+                values
+                    .iter()
+                    .map(|v| self.field_from_json(items, v))
+                    .collect::<Result<_>>()?,
+            ),
+            _ => plain_from_json(value),
+        })
+    }
+}
+
+impl TypeSchema {
+    fn new(type_id: &str, version: u32, def: VersionDef) -> Result<Self> {
+        let mut schema = TypeSchema::default();
+        for (tag, field) in def.fields {
+            let tag = tag.parse()?;
+            let kind = field
+                .kind()
+                .map_err(|msg| Error::decode(format!("{type_id} v{version} tag {tag}: {msg}")))?;
+            if let Some(other) = schema.tags_by_name.insert(field.name.clone(), tag) {
+                return Err(Error::decode(format!(
+                    "{type_id} v{version}: tags {other} and {tag} are both named {:?}",
+                    field.name
+                )));
+            }
+            schema.fields.insert(
+                tag,
+                FieldSchema {
+                    name: field.name,
+                    kind,
+                },
+            );
+        }
+        Ok(schema)
+    }
+}
+
+/// The tag of a `"_<tag>"` key.
+fn unknown_tag(name: &str) -> Option<u64> {
+    let digits = name.strip_prefix('_')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+fn key_to_tag(key: &Value) -> Option<u64> {
+    match key {
+        Value::Integer(int) => int.as_u64(),
+        Value::String(s) => s.as_str()?.parse().ok(),
+        _ => None,
+    }
+}
+
+fn key_to_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s
+            .as_str()
+            .map(str::to_owned)
+            .unwrap_or_else(|| s.to_string()),
+        other => other.to_string(),
+    }
+}
+
+/// Maps a value the schema says nothing about. Integer map keys become
+/// `"_<tag>"` so that [`plain_from_json`] restores them.
+fn plain_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Nil => JsonValue::Null,
+        Value::Boolean(b) => JsonValue::Bool(*b),
+        Value::Integer(int) => match (int.as_u64(), int.as_i64()) {
+            (Some(u), _) => JsonValue::from(u),
+            (None, Some(i)) => JsonValue::from(i),
+            (None, None) => JsonValue::Null,
+        },
+        Value::F32(f) => float_to_json(f64::from(*f)),
+        Value::F64(f) => float_to_json(*f),
+        Value::String(s) => JsonValue::String(
+            s.as_str()
+                .map(str::to_owned)
+                .unwrap_or_else(|| String::from_utf8_lossy(s.as_bytes()).into_owned()),
+        ),
+        Value::Binary(bytes) => JsonValue::String(BASE64.encode(bytes)),
+        Value::Array(values) => JsonValue::Array(values.iter().map(plain_to_json).collect()),
+        Value::Map(entries) => JsonValue::Object(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Value::Integer(int) if int.as_u64().is_some() => format!("_{int}"),
+                        other => key_to_string(other),
+                    };
+                    (key, plain_to_json(value))
+                })
+                .collect(),
+        ),
+        Value::Ext(_, bytes) => JsonValue::String(BASE64.encode(bytes)),
+    }
+}
+
+fn float_to_json(f: f64) -> JsonValue {
+    Number::from_f64(f).map_or(JsonValue::Null, JsonValue::Number)
+}
+
+fn plain_from_json(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Nil,
+        JsonValue::Bool(b) => Value::Boolean(*b),
+        JsonValue::Number(number) => {
+            if let Some(u) = number.as_u64() {
+                Value::from(u)
+            } else if let Some(i) = number.as_i64() {
+                Value::from(i)
+            } else {
+                Value::F64(number.as_f64().unwrap_or_default())
+            }
+        }
+        JsonValue::String(s) => Value::from(s.as_str()),
+        JsonValue::Array(values) => Value::Array(values.iter().map(plain_from_json).collect()),
+        JsonValue::Object(object) => Value::Map(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let key = match unknown_tag(key) {
+                        Some(tag) => Value::from(tag),
+                        None => Value::from(key.as_str()),
+                    };
+                    (key, plain_from_json(value))
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct FileDef {
+    #[serde(default)]
+    types: HashMap<String, TypeDef>,
+}
+
+#[derive(Deserialize)]
+struct TypeDef {
+    #[serde(default)]
+    versions: BTreeMap<NumberKey, VersionDef>,
+}
+
+#[derive(Deserialize)]
+struct VersionDef {
+    #[serde(default)]
+    fields: BTreeMap<NumberKey, FieldDef>,
+}
+
+#[derive(Deserialize)]
+struct FieldDef {
+    name: String,
+    #[serde(rename = "type")]
+    field_type: String,
+    #[serde(default, rename = "ref")]
+    type_ref: Option<String>,
+    #[serde(default)]
+    nested: Option<String>,
+    #[serde(default)]
+    items: Option<ItemsDef>,
+}
+
+/// `items: string` or `items: { type: ref, ref: com.example.Part }`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ItemsDef {
+    Type(String),
+    Spec {
+        #[serde(rename = "type")]
+        item_type: String,
+        #[serde(default, rename = "ref")]
+        type_ref: Option<String>,
+    },
+}
+
+impl FieldDef {
+    fn kind(&self) -> std::result::Result<FieldKind, String> {
+        let type_ref = self.type_ref.as_ref().or(self.nested.as_ref());
+        match self.field_type.as_str() {
+            "array" => {
+                let items = match &self.items {
+                    Some(ItemsDef::Type(item_type)) => scalar_kind(item_type),
+                    Some(ItemsDef::Spec {
+                        item_type,
+                        type_ref,
+                    }) => match (item_type.as_str(), type_ref) {
+                        ("ref", Some(type_ref)) => FieldKind::Ref(type_ref.clone()),
+                        ("ref", None) => return Err("ref items without `ref`".into()),
+                        (item_type, _) => scalar_kind(item_type),
+                    },
+                    None => FieldKind::Other,
+                };
+                Ok(FieldKind::Array(Box::new(items)))
+            }
+            "ref" => type_ref
+                .map(|type_ref| FieldKind::Ref(type_ref.clone()))
+                .ok_or_else(|| "ref field without `ref`".to_owned()),
+            other => Ok(match type_ref {
+                Some(type_ref) => FieldKind::Ref(type_ref.clone()),
+                None => scalar_kind(other),
+            }),
+        }
+    }
+}
+
+fn scalar_kind(field_type: &str) -> FieldKind {
+    match field_type {
+        "bytes" | "typed_blob" => FieldKind::Bytes,
+        "f32" | "f64" | "float32" | "float64" => FieldKind::Float,
+        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "int8" | "int16"
+        | "int32" | "int64" | "uint8" | "uint16" | "uint32" | "uint64" | "unix_ms" | "time_ms"
+        | "timestamp_ms" => FieldKind::Int,
+        _ => FieldKind::Other,
+    }
+}
+
+/// A version or tag map key, written as a YAML integer or a JSON string.
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(untagged)]
+enum NumberKey {
+    Number(u64),
+    Text(String),
+}
+
+impl NumberKey {
+    fn parse(&self) -> Result<u64> {
+        match self {
+            NumberKey::Number(n) => Ok(*n),
+            NumberKey::Text(text) => text
+                .parse()
+                .map_err(|err| Error::decode_with(format!("expected a number, got {text:?}"), err)),
+        }
+    }
+}
+
+impl fmt::Display for NumberKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberKey::Number(n) => write!(f, "{n}"),
+            NumberKey::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::typed_turn_records_payload;
+    use crate::turn::parse_turn_records;
+    use serde_json::json;
+
+    const SCHEMA: &str = r#"
+types:
+  com.example.Message:
+    versions:
+      1:
+        fields:
+          1: { name: role, type: string }
+          2: { name: text, type: string }
+      2:
+        fields:
+          1: { name: role, type: string }
+          2: { name: text, type: string }
+          3: { name: attachments, type: array, items: bytes, optional: true }
+          4: { name: sender, type: ref, ref: com.example.User }
+          5: { name: replies, type: array, items: { type: ref, ref: com.example.User } }
+  com.example.User:
+    versions:
+      1:
+        fields:
+          1: { name: id, type: u64 }
+          2: { name: score, type: f64 }
+"#;
+
+    fn turn(type_version: u32, payload: Vec<u8>) -> TurnRecord {
+        let records = typed_turn_records_payload(&[("com.example.Message", &payload)]);
+        let mut turn = parse_turn_records(&records).unwrap().remove(0);
+        turn.type_version = type_version;
+        turn
+    }
+
+    fn msgpack(value: Value) -> Vec<u8> {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &value).unwrap();
+        buf
+    }
+
+    #[test]
+    fn decodes_tags_to_names_and_back() {
+        let schema = SchemaFile::from_yaml(SCHEMA).unwrap();
+        let user = |id: u64, score: f64| {
+            Value::Map(vec![(1.into(), id.into()), (2.into(), Value::F64(score))])
+        };
+        let payload = msgpack(Value::Map(vec![
+            (1.into(), "user".into()),
+            (2.into(), "hi".into()),
+            (3.into(), Value::Array(vec![Value::Binary(vec![1, 2, 3])])),
+            (4.into(), user(7, 0.5)),
+            (5.into(), Value::Array(vec![user(8, 1.0)])),
+        ]));
+
+        let json = schema.decode_to_json(&turn(2, payload.clone())).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "role": "user",
+                "text": "hi",
+                "attachments": ["AQID"],
+                "sender": {"id": 7, "score": 0.5},
+                "replies": [{"id": 8, "score": 1.0}],
+            })
+        );
+
+        let encoded = schema
+            .encode_from_json("com.example.Message", 2, &json)
+            .unwrap();
+        assert_eq!(encoded, payload);
+    }
+
+    #[test]
+    fn unknown_tags_round_trip_as_underscored_keys() {
+        let schema = SchemaFile::from_yaml(SCHEMA).unwrap();
+        let payload = msgpack(Value::Map(vec![
+            (1.into(), "user".into()),
+            (2.into(), "hi".into()),
+            (3.into(), Value::Map(vec![(1.into(), true.into())])),
+        ]));
+
+        // v1 has no tag 3.
+        let json = schema.decode_to_json(&turn(1, payload.clone())).unwrap();
+        assert_eq!(
+            json,
+            json!({"role": "user", "_3": {"_1": true}, "text": "hi"})
+        );
+        let encoded = schema
+            .encode_from_json("com.example.Message", 1, &json)
+            .unwrap();
+        assert_eq!(encoded, payload);
+    }
+
+    #[test]
+    fn falls_back_to_the_newest_older_version() {
+        let schema = SchemaFile::from_yaml(SCHEMA).unwrap();
+        let payload = msgpack(Value::Map(vec![(1.into(), "user".into())]));
+        let json = schema.decode_to_json(&turn(5, payload)).unwrap();
+        assert_eq!(json, json!({"role": "user"}));
+
+        let mut other = turn(1, Vec::new());
+        other.type_id = "com.example.Other".into();
+        assert!(matches!(
+            schema.decode_to_json(&other),
+            Err(Error::Decode { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_names_when_encoding() {
+        let schema = SchemaFile::from_yaml(SCHEMA).unwrap();
+        let err = schema
+            .encode_from_json(
+                "com.example.Message",
+                1,
+                &json!({"role": "user", "mood": 1}),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::Encode { message: msg, .. } if msg.contains("mood")));
+    }
+
+    #[test]
+    fn loads_json_registry_bundles() {
+        let bundle = r#"{"registry_version": 1, "bundle_id": "b", "types": {
+            "com.example.Message": {"versions": {"1": {"fields": {
+                "1": {"name": "role", "type": "string"},
+                "2": {"name": "text", "type": "string", "optional": true}
+            }}}}
+        }}"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.json");
+        std::fs::write(&path, bundle).unwrap();
+
+        let schema = SchemaFile::load(&path).unwrap();
+        assert!(schema.contains("com.example.Message"));
+        let payload = msgpack(Value::Map(vec![(2.into(), "hi".into())]));
+        assert_eq!(
+            schema.decode_to_json(&turn(1, payload)).unwrap(),
+            json!({"text": "hi"})
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_and_malformed_schemas() {
+        let duplicate = r#"
+types:
+  t:
+    versions:
+      1:
+        fields:
+          1: { name: a, type: string }
+          2: { name: a, type: string }
+"#;
+        assert!(matches!(
+            SchemaFile::from_yaml(duplicate),
+            Err(Error::Decode { message: msg, .. }) if msg.contains("both named")
+        ));
+        let dangling = "types: {t: {versions: {1: {fields: {1: {name: a, type: ref}}}}}}";
+        assert!(matches!(
+            SchemaFile::from_yaml(dangling),
+            Err(Error::Decode { message: msg, .. }) if msg.contains("without `ref`")
+        ));
+        assert!(matches!(
+            SchemaFile::load("/nonexistent/schema.yaml"),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,569 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Turn search by payload field.
+//!
+//! [`Client::search_turns`] finds the turns of a context whose msgpack
+//! payload holds a matching value at a field path. Each path segment selects
+//! a map entry whose key is that string (or that integer, for a decimal
+//! segment), or an array element by index, so `["3", "0"]` reaches
+//! `{"3": [<here>, ...]}`.
+//!
+//! Servers that offer SEARCH_TURNS at handshake run the search themselves.
+//! Otherwise the client pages back through the context and reads each
+//! payload only as far as the path: values off the path are skipped by
+//! their msgpack headers without being decoded, and only a matching value
+//! is decoded in full.
+//!
+//! ```no_run
+//! use cxdb::search::SearchQuery;
+//! use cxdb::{dial, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let query = SearchQuery::contains(["3", "1"], "refund")
+//!     .type_id("cxdb.ConversationItem")
+//!     .limit(20);
+//! for hit in client.search_turns(&RequestContext::background(), ContextId::new(1), &query)? {
+//!     println!("turn {}: {}", hit.turn.turn_id, hit.value);
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use rmp::Marker;
+use rmpv::Value;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::encoding::rmpv_depth;
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+use crate::protocol::{
+    PayloadReader, ENCODING_MSGPACK, MSG_SEARCH_TURNS, SEARCH_MATCH_CONTAINS, SEARCH_MATCH_EQUALS,
+};
+use crate::turn::{parse_turn_listing, GetLastOptions, TurnHeader, TurnRecord};
+
+/// Turns fetched per page when searching client-side.
+const SEARCH_PAGE_SIZE: u32 = 64;
+
+/// How a field's value is compared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Match {
+    /// The field equals this value. Integers compare by value whatever
+    /// their encoded width.
+    Equals(Value),
+    /// The field is a string containing this text.
+    Contains(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    /// Only turns declared with this type; `None` searches every type.
+    pub type_id: Option<String>,
+    /// Path to the field within each payload.
+    pub msgpack_field_path: Vec<String>,
+    pub matcher: Match,
+    /// Most hits to return; the newest matches win.
+    pub limit: u32,
+}
+
+impl SearchQuery {
+    /// Turns whose field at `path` equals `value`.
+    pub fn equals<S: Into<String>>(
+        path: impl IntoIterator<Item = S>,
+        value: impl Into<Value>,
+    ) -> Self {
+        Self::new(path, Match::Equals(value.into()))
+    }
+
+    /// Turns whose field at `path` is a string containing `text`.
+    pub fn contains<S: Into<String>>(
+        path: impl IntoIterator<Item = S>,
+        text: impl Into<String>,
+    ) -> Self {
+        Self::new(path, Match::Contains(text.into()))
+    }
+
+    fn new<S: Into<String>>(path: impl IntoIterator<Item = S>, matcher: Match) -> Self {
+        Self {
+            type_id: None,
+            msgpack_field_path: path.into_iter().map(Into::into).collect(),
+            matcher,
+            limit: 10,
+        }
+    }
+
+    /// Searches only turns declared with `type_id`.
+    pub fn type_id(mut self, type_id: impl Into<String>) -> Self {
+        self.type_id = Some(type_id.into());
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+/// A matching turn and the value found at the query's path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub turn: TurnHeader,
+    pub value: Value,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Finds the newest `query.limit` turns of `context_id` whose payload
+    /// matches `query`, oldest first. The whole history is searched,
+    /// compacted turns included; expired turns and payloads that are not
+    /// msgpack never match.
+    pub fn search_turns(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchHit>> {
+        if self.server_search() {
+            let payload = search_request(context_id, query)?;
+            let frame = self
+                .send_request(ctx, MSG_SEARCH_TURNS, &payload)
+                .map_err(|err| err.resolve_not_found())?;
+            return parse_search_hits(&frame.payload, self.max_decode_depth());
+        }
+
+        let mut hits = Vec::new();
+        let mut before_turn_id = None;
+        while hits.len() < query.limit as usize {
+            let opts = GetLastOptions {
+                limit: SEARCH_PAGE_SIZE,
+                include_payload: true,
+                before_turn_id,
+                ..Default::default()
+            }
+            .include_compacted(true);
+            let page = self.get_last(ctx, context_id, opts)?;
+            for turn in page.iter().rev() {
+                if hits.len() >= query.limit as usize {
+                    break;
+                }
+                if let Some(value) = match_turn(turn, query, self.max_decode_depth())? {
+                    hits.push(SearchHit {
+                        turn: turn.clone().split_payload().0,
+                        value,
+                    });
+                }
+            }
+            if page.len() < SEARCH_PAGE_SIZE as usize {
+                break;
+            }
+            before_turn_id = page.first().map(|turn| turn.turn_id);
+        }
+        hits.reverse();
+        Ok(hits)
+    }
+}
+
+/// The value at the query's path in `turn`, if the turn matches.
+fn match_turn(turn: &TurnRecord, query: &SearchQuery, max_depth: usize) -> Result<Option<Value>> {
+    let type_matches = query
+        .type_id
+        .as_ref()
+        .is_none_or(|type_id| *type_id == turn.type_id);
+    if !type_matches || turn.encoding != ENCODING_MSGPACK || turn.payload_omitted {
+        return Ok(None);
+    }
+    // A payload that does not parse cannot match; it is not an error.
+    let Ok(Some(field)) = find_path(&turn.payload, &query.msgpack_field_path) else {
+        return Ok(None);
+    };
+    if let Match::Contains(needle) = &query.matcher {
+        match rmp::decode::read_str_from_slice(field) {
+            Ok((text, _)) if text.contains(needle.as_str()) => {}
+            _ => return Ok(None),
+        }
+    }
+    let value = rmpv::decode::read_value_with_max_depth(&mut &field[..], rmpv_depth(max_depth))
+        .map_err(Error::decode_err)?;
+    match &query.matcher {
+        Match::Equals(expected) if value != *expected => Ok(None),
+        _ => Ok(Some(value)),
+    }
+}
+
+/// Returns the encoding of the value at `path` in the msgpack `data`,
+/// stepping over everything off the path without decoding it.
+fn find_path<'a>(data: &'a [u8], path: &[String]) -> Result<Option<&'a [u8]>> {
+    let mut rest = data;
+    'segments: for (i, segment) in path.iter().enumerate() {
+        let marker = read_marker(&mut rest)?;
+        let entries = match marker {
+            Marker::FixMap(n) => n as u64,
+            Marker::Map16 => take_len(&mut rest, 2)?,
+            Marker::Map32 => take_len(&mut rest, 4)?,
+            Marker::FixArray(n) => {
+                return index_array(rest, n as u64, segment, &path[i + 1..]);
+            }
+            Marker::Array16 => {
+                let len = take_len(&mut rest, 2)?;
+                return index_array(rest, len, segment, &path[i + 1..]);
+            }
+            Marker::Array32 => {
+                let len = take_len(&mut rest, 4)?;
+                return index_array(rest, len, segment, &path[i + 1..]);
+            }
+            _ => return Ok(None),
+        };
+        for _ in 0..entries {
+            let key = take_value(&mut rest)?;
+            if key_matches(key, segment) {
+                continue 'segments;
+            }
+            skip_value(&mut rest)?;
+        }
+        return Ok(None);
+    }
+    take_value(&mut rest).map(Some)
+}
+
+/// Continues [`find_path`] at element `segment` of an array of `len`.
+fn index_array<'a>(
+    mut rest: &'a [u8],
+    len: u64,
+    segment: &str,
+    path: &[String],
+) -> Result<Option<&'a [u8]>> {
+    let Ok(index) = segment.parse::<u64>() else {
+        return Ok(None);
+    };
+    if index >= len {
+        return Ok(None);
+    }
+    for _ in 0..index {
+        skip_value(&mut rest)?;
+    }
+    find_path(rest, path)
+}
+
+fn key_matches(key: &[u8], segment: &str) -> bool {
+    if let Ok((key, _)) = rmp::decode::read_str_from_slice(key) {
+        return key == segment;
+    }
+    rmp::decode::read_int::<u64, _>(&mut &key[..]).is_ok_and(|key| segment.parse() == Ok(key))
+}
+
+/// Consumes one value and returns its encoding.
+fn take_value<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let start = *rest;
+    skip_value(rest)?;
+    Ok(&start[..start.len() - rest.len()])
+}
+
+/// Consumes one value, nested values included, from its headers alone.
+/// Iterative, so hostile nesting cannot exhaust the stack.
+fn skip_value(rest: &mut &[u8]) -> Result<()> {
+    let mut pending: u64 = 1;
+    while pending > 0 {
+        pending -= 1;
+        let skip = match read_marker(rest)? {
+            Marker::FixPos(_) | Marker::FixNeg(_) | Marker::Null | Marker::True | Marker::False => {
+                0
+            }
+            Marker::U8 | Marker::I8 => 1,
+            Marker::U16 | Marker::I16 => 2,
+            Marker::U32 | Marker::I32 | Marker::F32 => 4,
+            Marker::U64 | Marker::I64 | Marker::F64 => 8,
+            Marker::FixStr(n) => n as u64,
+            Marker::Str8 | Marker::Bin8 => take_len(rest, 1)?,
+            Marker::Str16 | Marker::Bin16 => take_len(rest, 2)?,
+            Marker::Str32 | Marker::Bin32 => take_len(rest, 4)?,
+            Marker::FixArray(n) => {
+                pending += n as u64;
+                0
+            }
+            Marker::Array16 => {
+                pending += take_len(rest, 2)?;
+                0
+            }
+            Marker::Array32 => {
+                pending += take_len(rest, 4)?;
+                0
+            }
+            Marker::FixMap(n) => {
+                pending += 2 * n as u64;
+                0
+            }
+            Marker::Map16 => {
+                pending += 2 * take_len(rest, 2)?;
+                0
+            }
+            Marker::Map32 => {
+                pending += 2 * take_len(rest, 4)?;
+                0
+            }
+            Marker::FixExt1 => 2,
+            Marker::FixExt2 => 3,
+            Marker::FixExt4 => 5,
+            Marker::FixExt8 => 9,
+            Marker::FixExt16 => 17,
+            Marker::Ext8 => take_len(rest, 1)? + 1,
+            Marker::Ext16 => take_len(rest, 2)? + 1,
+            Marker::Ext32 => take_len(rest, 4)? + 1,
+            Marker::Reserved => return Err(Error::decode("reserved msgpack marker")),
+        };
+        take(rest, skip)?;
+    }
+    Ok(())
+}
+
+fn read_marker(rest: &mut &[u8]) -> Result<Marker> {
+    Ok(Marker::from_u8(take(rest, 1)?[0]))
+}
+
+/// Reads a big-endian length of `width` bytes.
+fn take_len(rest: &mut &[u8], width: u64) -> Result<u64> {
+    Ok(take(rest, width)?
+        .iter()
+        .fold(0, |len, byte| len << 8 | *byte as u64))
+}
+
+fn take<'a>(rest: &mut &'a [u8], n: u64) -> Result<&'a [u8]> {
+    if n > rest.len() as u64 {
+        return Err(Error::decode("truncated msgpack value"));
+    }
+    let (head, tail) = rest.split_at(n as usize);
+    *rest = tail;
+    Ok(head)
+}
+
+/// Encodes a SEARCH_TURNS request: context_id, limit and match mode, then
+/// length-prefixed type id (empty for any), path segments and operand.
+fn search_request(context_id: ContextId, query: &SearchQuery) -> Result<Vec<u8>> {
+    let (mode, operand) = match &query.matcher {
+        Match::Equals(value) => {
+            let mut encoded = Vec::new();
+            rmpv::encode::write_value(&mut encoded, value).map_err(Error::encode_err)?;
+            (SEARCH_MATCH_EQUALS, encoded)
+        }
+        Match::Contains(text) => (SEARCH_MATCH_CONTAINS, text.as_bytes().to_vec()),
+    };
+    let type_id = query.type_id.as_deref().unwrap_or_default();
+    let mut out = Vec::new();
+    out.write_u64::<LittleEndian>(context_id.get())?;
+    out.write_u32::<LittleEndian>(query.limit)?;
+    out.write_u32::<LittleEndian>(mode)?;
+    out.write_u32::<LittleEndian>(type_id.len() as u32)?;
+    out.extend_from_slice(type_id.as_bytes());
+    out.write_u32::<LittleEndian>(query.msgpack_field_path.len() as u32)?;
+    for segment in &query.msgpack_field_path {
+        out.write_u32::<LittleEndian>(segment.len() as u32)?;
+        out.extend_from_slice(segment.as_bytes());
+    }
+    out.write_u32::<LittleEndian>(operand.len() as u32)?;
+    out.extend_from_slice(&operand);
+    Ok(out)
+}
+
+/// Decodes a SEARCH_TURNS response: the matched values, then the hits as a
+/// GET_LAST response without payloads.
+fn parse_search_hits(payload: &[u8], max_depth: usize) -> Result<Vec<SearchHit>> {
+    let mut reader = PayloadReader::new(payload, "search response");
+    let count = reader.u32("count")?;
+    let mut values = Vec::new();
+    for _ in 0..count {
+        let encoded = reader.len_prefixed("value")?;
+        let value =
+            rmpv::decode::read_value_with_max_depth(&mut &encoded[..], rmpv_depth(max_depth))
+                .map_err(Error::decode_err)?;
+        values.push(value);
+    }
+    let turns = parse_turn_listing(reader.bytes(reader.remaining(), "turns")?)?;
+    if turns.len() != values.len() {
+        return Err(Error::protocol(format!(
+            "search response has {} values for {} turns",
+            values.len(),
+            turns.len()
+        )));
+    }
+    Ok(turns
+        .into_iter()
+        .zip(values)
+        .map(|(turn, value)| SearchHit {
+            turn: turn.split_payload().0,
+            value,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::ids::TurnId;
+    use crate::protocol::{read_frame, write_frame, FLAG_SEARCH, MSG_GET_LAST, MSG_HELLO};
+    use crate::test_util::{
+        spawn_scripted_server, turn_listing_payload, turn_page_payload, typed_turn_records_payload,
+    };
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn msgpack(value: Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        rmpv::encode::write_value(&mut out, &value).unwrap();
+        out
+    }
+
+    /// `{"1": kind, 3: [text, <bin>]}`, the second key an integer.
+    fn item(kind: &str, text: &str) -> Vec<u8> {
+        msgpack(Value::Map(vec![
+            (Value::from("1"), Value::from(kind)),
+            (
+                Value::from(3),
+                Value::Array(vec![Value::from(text), Value::Binary(vec![7; 300])]),
+            ),
+        ]))
+    }
+
+    fn path(segments: &[&str]) -> Vec<String> {
+        segments.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn find_path_steps_over_other_values() {
+        let payload = msgpack(Value::Map(vec![
+            (Value::from("skip"), Value::Ext(3, vec![1, 2, 3])),
+            (
+                Value::from("nested"),
+                Value::Map(vec![(Value::from(70_000), Value::from(-5))]),
+            ),
+            (
+                Value::from("list"),
+                Value::Array(vec![Value::F64(1.5), Value::from("x".repeat(40_000))]),
+            ),
+        ]));
+        let value = |segments: &[&str]| {
+            find_path(&payload, &path(segments))
+                .unwrap()
+                .map(|field| rmpv::decode::read_value(&mut &field[..]).unwrap())
+        };
+        assert_eq!(value(&["nested", "70000"]), Some(Value::from(-5)));
+        assert_eq!(value(&["list", "1"]), Some(Value::from("x".repeat(40_000))));
+        assert_eq!(value(&["list", "2"]), None);
+        assert_eq!(value(&["list", "x"]), None);
+        assert_eq!(value(&["skip", "0"]), None);
+        assert_eq!(value(&["missing"]), None);
+        assert_eq!(value(&[]).map(|v| v.is_map()), Some(true));
+
+        // A header that promises more than the payload holds.
+        assert!(find_path(&payload[..payload.len() - 1], &path(&["missing"])).is_err());
+        // Deep nesting off the path is skipped without recursion.
+        let mut deep = vec![0x82, 0xa1, b'a'];
+        deep.extend(std::iter::repeat_n(0x91, 1_000_000));
+        deep.extend([0x00, 0xa1, b'b', 0x2a]);
+        let field = find_path(&deep, &path(&["b"])).unwrap().unwrap();
+        assert_eq!(field, [0x2a]);
+    }
+
+    #[test]
+    fn search_falls_back_to_paging_client_side() {
+        let other = msgpack(Value::from("not a map"));
+        let mut newer: Vec<Vec<u8>> = (0..64).map(|_| item("assistant", "hi")).collect();
+        newer[59] = item("user", "refund please");
+        let older = [
+            item("user", "hello"),
+            other.clone(),
+            item("user", "refund status?"),
+        ];
+        let newer: Vec<&[u8]> = newer.iter().map(Vec::as_slice).collect();
+        let older: Vec<&[u8]> = older.iter().map(Vec::as_slice).collect();
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, turn_page_payload(4, &newer)),
+            (MSG_GET_LAST, turn_page_payload(1, &older)),
+            (
+                MSG_GET_LAST,
+                typed_turn_records_payload(&[
+                    ("com.example.Other", &item("user", "refund")),
+                    ("test", &item("user", "refund me")),
+                ]),
+            ),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let query = SearchQuery::contains(["3", "0"], "refund");
+        let hits = client
+            .search_turns(&ctx, ContextId::new(9), &query)
+            .unwrap();
+        let ids: Vec<u64> = hits.iter().map(|hit| hit.turn.turn_id.get()).collect();
+        assert_eq!(ids, [3, 63]);
+        assert_eq!(hits[1].value, Value::from("refund please"));
+        assert_eq!(hits[1].turn.type_id, "test");
+
+        let query = SearchQuery::equals(["1"], "user").type_id("test").limit(1);
+        let hits = client
+            .search_turns(&ctx, ContextId::new(9), &query)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            (hits[0].turn.turn_id.get(), &hits[0].value),
+            (2, &Value::from("user"))
+        );
+
+        let requests = handle.join().unwrap();
+        assert!(!requests[0].payload.ends_with(&4u64.to_le_bytes()));
+        // The next page starts below the oldest turn of the last.
+        assert!(requests[1].payload.ends_with(&4u64.to_le_bytes()));
+    }
+
+    #[test]
+    fn negotiated_search_runs_on_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            assert_ne!(hello.header.flags & FLAG_SEARCH, 0);
+            let mut resp = 1u64.to_le_bytes().to_vec();
+            resp.extend_from_slice(&1u16.to_le_bytes());
+            write_frame(
+                &mut stream,
+                MSG_HELLO,
+                FLAG_SEARCH,
+                hello.header.req_id,
+                &resp,
+            )
+            .unwrap();
+
+            let req = read_frame(&mut stream).unwrap();
+            let value = msgpack(Value::from(42));
+            let mut resp = 1u32.to_le_bytes().to_vec();
+            resp.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            resp.extend_from_slice(&value);
+            resp.extend_from_slice(&turn_listing_payload(&[b"payload"]));
+            write_frame(&mut stream, MSG_SEARCH_TURNS, 0, req.header.req_id, &resp).unwrap();
+            req
+        });
+
+        let client = dial(&addr, []).unwrap();
+        let query = SearchQuery::equals(["score"], 42).type_id("t").limit(5);
+        let hits = client
+            .search_turns(&RequestContext::background(), ContextId::new(3), &query)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].turn.turn_id, TurnId::new(1));
+        assert_eq!(hits[0].turn.payload_size, 7);
+        assert_eq!(hits[0].value, Value::from(42));
+
+        let req = handle.join().unwrap();
+        assert_eq!(req.header.msg_type, MSG_SEARCH_TURNS);
+        assert_eq!(
+            req.payload,
+            search_request(ContextId::new(3), &query).unwrap()
+        );
+        let mut expected = 3u64.to_le_bytes().to_vec();
+        for word in [5u32, SEARCH_MATCH_EQUALS, 1] {
+            expected.extend_from_slice(&word.to_le_bytes());
+        }
+        assert!(req.payload.starts_with(&expected));
+    }
+}
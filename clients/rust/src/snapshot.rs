@@ -0,0 +1,390 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Context snapshots.
+//!
+//! [`Client::snapshot_context`] captures a context's history, oldest turn
+//! first, as a [`Snapshot`], and [`Client::restore_context`] replays one
+//! into a new context. Unlike the [JSON Lines export](crate::jsonl), which
+//! is meant to be read and analysed, a snapshot is meant for round-tripping:
+//! [`Snapshot::to_bytes`] packs it into one opaque blob that
+//! [`Snapshot::from_bytes`] checks before trusting any of it.
+//!
+//! # Blob Format
+//!
+//! - magic: `b"CXDBSNAP"`
+//! - format_version: u32 (little-endian)
+//! - body: the [`Snapshot`] as msgpack with named fields
+//! - checksum: BLAKE3 of everything before it (32 bytes)
+//!
+//! Fields are named, so a reader ignores fields added after it was built
+//! and defaults the optional ones an older writer left out. The format
+//! version is only bumped for changes old readers cannot skip, and
+//! `from_bytes` rejects versions newer than its own.
+//!
+//! A [redacted](crate::redact) turn is captured without its payload and
+//! restored as an empty turn that is redacted again with the same reason.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Client, RequestContext};
+use crate::context::{ContextHead, CreateContextOptions};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::redact::RedactOptions;
+use crate::turn::{AppendRequest, TurnRecord};
+
+const MAGIC: &[u8; 8] = b"CXDBSNAP";
+
+/// Format version written by [`Snapshot::to_bytes`].
+const FORMAT_VERSION: u32 = 1;
+
+const CHECKSUM_LEN: usize = 32;
+
+/// A context's history as captured by [`Client::snapshot_context`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Context the snapshot was taken from.
+    pub context_id: ContextId,
+    pub head_turn_id: TurnId,
+    pub head_depth: u32,
+    /// When the snapshot was taken, in Unix milliseconds.
+    pub taken_at_unix_ms: u64,
+    /// The history, oldest turn first.
+    pub turns: Vec<SnapshotTurn>,
+}
+
+/// One turn of a [`Snapshot`], with its payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotTurn {
+    /// Id in the source context; restored turns get new ids.
+    pub turn_id: TurnId,
+    pub depth: u32,
+    pub type_id: String,
+    pub type_version: u32,
+    pub encoding: u32,
+    /// BLAKE3 hash of `payload`, or of the erased payload of a redacted
+    /// turn.
+    pub payload_hash: [u8; 32],
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+    #[serde(default)]
+    pub writer_id: Option<String>,
+    #[serde(default)]
+    pub writer_seq: u64,
+    #[serde(default)]
+    pub expires_at_unix_ms: Option<u64>,
+    #[serde(default)]
+    pub created_at_unix_ms: Option<u64>,
+    #[serde(default)]
+    pub redacted: bool,
+    #[serde(default)]
+    pub redacted_at_unix_ms: Option<u64>,
+    #[serde(default)]
+    pub redaction_reason: Option<String>,
+}
+
+impl From<TurnRecord> for SnapshotTurn {
+    fn from(turn: TurnRecord) -> Self {
+        Self {
+            turn_id: turn.turn_id,
+            depth: turn.depth,
+            type_id: turn.type_id.into(),
+            type_version: turn.type_version,
+            encoding: turn.encoding,
+            payload_hash: turn.payload_hash,
+            payload: turn.payload,
+            writer_id: turn.writer_id,
+            writer_seq: turn.writer_seq,
+            expires_at_unix_ms: turn.expires_at_unix_ms,
+            created_at_unix_ms: turn.created_at_unix_ms,
+            redacted: turn.redacted,
+            redacted_at_unix_ms: turn.redacted_at_unix_ms,
+            redaction_reason: turn.redaction_reason,
+        }
+    }
+}
+
+impl Snapshot {
+    /// Packs the snapshot into a checksummed blob (see the
+    /// [module docs](self)).
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        let body = rmp_serde::to_vec_named(self).map_err(Error::encode_err)?;
+        out.extend_from_slice(&body);
+        let checksum = blake3::hash(&out);
+        out.extend_from_slice(checksum.as_bytes());
+        Ok(out)
+    }
+
+    /// Unpacks a blob written by [`Snapshot::to_bytes`]. Fails with
+    /// [`Error::Decode`] if the blob is truncated or corrupt, was written
+    /// by a newer format version, or holds a payload that does not match
+    /// its hash.
+    pub fn from_bytes(blob: &[u8]) -> Result<Self> {
+        let header_len = MAGIC.len() + 4;
+        if blob.len() < header_len + CHECKSUM_LEN || !blob.starts_with(MAGIC) {
+            return Err(Error::decode("not a cxdb snapshot"));
+        }
+        let (content, checksum) = blob.split_at(blob.len() - CHECKSUM_LEN);
+        if blake3::hash(content).as_bytes() != checksum {
+            return Err(Error::decode("snapshot checksum mismatch"));
+        }
+        let version = u32::from_le_bytes(content[MAGIC.len()..header_len].try_into().unwrap());
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(Error::decode(format!(
+                "snapshot format version {version} is not supported (up to {FORMAT_VERSION})"
+            )));
+        }
+        let snapshot: Snapshot = rmp_serde::from_slice(&content[header_len..])
+            .map_err(|err| Error::decode_with(format!("snapshot body: {err}"), err))?;
+        snapshot.verify()?;
+        Ok(snapshot)
+    }
+
+    /// Checks every payload against its `payload_hash`.
+    fn verify(&self) -> Result<()> {
+        for turn in self.turns.iter().filter(|turn| !turn.redacted) {
+            let actual = blake3::hash(&turn.payload);
+            if actual.as_bytes() != &turn.payload_hash {
+                return Err(Error::decode(format!(
+                    "snapshot turn {}: payload hash {} does not match {}",
+                    turn.turn_id,
+                    actual.to_hex(),
+                    blake3::Hash::from_bytes(turn.payload_hash).to_hex()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Captures the history of `context_id` as it stands at the call,
+    /// payloads included. Compacted turns are included; expired turns are
+    /// not.
+    ///
+    /// The whole history is held in memory; for contexts too long for that,
+    /// use [`Client::export_jsonl`].
+    pub fn snapshot_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<Snapshot> {
+        let head = self.get_head(ctx, context_id)?;
+        let mut turns = Vec::with_capacity(head.head_depth as usize + 1);
+        self.visit_history(ctx, &head, |turn| {
+            turns.push(SnapshotTurn::from(turn));
+            Ok(())
+        })?;
+        Ok(Snapshot {
+            context_id,
+            head_turn_id: head.head_turn_id,
+            head_depth: head.head_depth,
+            taken_at_unix_ms: unix_ms(SystemTime::now()),
+            turns,
+        })
+    }
+
+    /// Replays `snapshot` into a new context, oldest turn first, and
+    /// returns the new context's head.
+    ///
+    /// Turn ids and timestamps are assigned afresh; types, encodings,
+    /// payloads and writer stamps are kept, and a turn with an expiry keeps
+    /// the time it had left. Payloads are checked against their hashes
+    /// before anything is created, failing with [`Error::Decode`]. Redacted
+    /// turns are appended empty and redacted again. If an append fails, the
+    /// turns before it stay appended.
+    pub fn restore_context(
+        &self,
+        ctx: &RequestContext,
+        snapshot: &Snapshot,
+    ) -> Result<ContextHead> {
+        snapshot.verify()?;
+        let mut head = self.create_context(ctx, CreateContextOptions::default())?;
+        for turn in &snapshot.turns {
+            let mut req = AppendRequest::new(
+                head.context_id,
+                turn.type_id.clone(),
+                turn.type_version,
+                turn.payload.clone(),
+            )
+            .encoding(turn.encoding);
+            if let Some(writer_id) = &turn.writer_id {
+                req = req.writer_id(writer_id).writer_seq(turn.writer_seq);
+            }
+            if let Some(expires_at) = turn.expires_at_unix_ms {
+                let left = expires_at.saturating_sub(unix_ms(SystemTime::now()));
+                req = req.ttl(Duration::from_millis(left.max(1)));
+            }
+            let result = self.append_turn(ctx, &req)?;
+            if turn.redacted {
+                let reason = turn.redaction_reason.clone().unwrap_or_default();
+                let opts = RedactOptions::default().reason(reason);
+                self.redact_turn(ctx, head.context_id, result.turn_id, opts)?;
+            }
+            head.head_turn_id = result.turn_id;
+            head.head_depth = result.depth;
+        }
+        Ok(head)
+    }
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_CTX_CREATE, MSG_GET_HEAD, MSG_GET_LAST};
+    use crate::test_util::{spawn_multi_server, spawn_scripted_server, turn_page_payload};
+
+    fn context_head(context_id: u64, head_turn_id: u64) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&head_turn_id.to_le_bytes());
+        out.extend_from_slice(&(head_turn_id as u32).to_le_bytes());
+        out
+    }
+
+    fn append_ack(context_id: u64, turn_id: u64) -> Vec<u8> {
+        let mut out = context_id.to_le_bytes().to_vec();
+        out.extend_from_slice(&turn_id.to_le_bytes());
+        out.extend_from_slice(&(turn_id as u32).to_le_bytes());
+        out.extend_from_slice(&[0u8; 32]);
+        out
+    }
+
+    fn snapshot_of(payloads: &[&[u8]]) -> Snapshot {
+        let turns = payloads
+            .iter()
+            .enumerate()
+            .map(|(i, payload)| SnapshotTurn {
+                turn_id: TurnId::new(i as u64 + 1),
+                depth: i as u32,
+                type_id: "test".into(),
+                type_version: 1,
+                encoding: 1,
+                payload_hash: *blake3::hash(payload).as_bytes(),
+                payload: payload.to_vec(),
+                writer_id: None,
+                writer_seq: 0,
+                expires_at_unix_ms: None,
+                created_at_unix_ms: None,
+                redacted: false,
+                redacted_at_unix_ms: None,
+                redaction_reason: None,
+            })
+            .collect();
+        Snapshot {
+            context_id: ContextId::new(4),
+            head_turn_id: TurnId::new(payloads.len() as u64),
+            head_depth: payloads.len().saturating_sub(1) as u32,
+            taken_at_unix_ms: 1_700_000_000_000,
+            turns,
+        }
+    }
+
+    #[test]
+    fn snapshot_captures_history_oldest_first() {
+        let addr = spawn_multi_server(|req| {
+            if req.header.msg_type == MSG_GET_HEAD {
+                return (MSG_GET_HEAD, context_head(4, 3));
+            }
+            (
+                MSG_GET_LAST,
+                turn_page_payload(1, &[b"\x01", b"\x02", b"\x03"]),
+            )
+        });
+        let client = dial(&addr, []).unwrap();
+
+        let snapshot = client
+            .snapshot_context(&RequestContext::background(), ContextId::new(4))
+            .unwrap();
+        assert_eq!(
+            (snapshot.context_id.get(), snapshot.head_turn_id.get()),
+            (4, 3)
+        );
+        let ids: Vec<u64> = snapshot
+            .turns
+            .iter()
+            .map(|turn| turn.turn_id.get())
+            .collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(snapshot.turns[2].payload, b"\x03");
+        assert_eq!(
+            Snapshot::from_bytes(&snapshot.to_bytes().unwrap()).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn blobs_are_checked_before_they_are_trusted() {
+        let blob = snapshot_of(&[b"\x91\x01", b"\x91\x02"]).to_bytes().unwrap();
+        let decode_error = |blob: &[u8]| match Snapshot::from_bytes(blob) {
+            Err(Error::Decode {
+                message: detail, ..
+            }) => detail,
+            other => panic!("expected a decode error, got {other:?}"),
+        };
+
+        let mut corrupt = blob.clone();
+        corrupt[20] ^= 0xff;
+        assert!(decode_error(&corrupt).contains("checksum"));
+        assert!(decode_error(&blob[..blob.len() - 1]).contains("checksum"));
+        assert!(decode_error(b"CXDB").contains("not a cxdb snapshot"));
+
+        let mut newer = blob[..blob.len() - CHECKSUM_LEN].to_vec();
+        newer[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let checksum = blake3::hash(&newer);
+        newer.extend_from_slice(checksum.as_bytes());
+        assert!(decode_error(&newer).contains("version 2"));
+
+        // A payload that no longer matches its hash is caught even when the
+        // blob itself is intact.
+        let mut tampered = snapshot_of(&[b"\x91\x01"]);
+        tampered.turns[0].payload = b"\x91\x02".to_vec();
+        assert!(decode_error(&tampered.to_bytes().unwrap()).contains("turn 1"));
+    }
+
+    #[test]
+    fn restore_replays_turns_into_a_new_context() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, context_head(9, 0)),
+            (MSG_APPEND_TURN, append_ack(9, 20)),
+            (MSG_APPEND_TURN, append_ack(9, 21)),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let mut snapshot = snapshot_of(&[b"\x91\x01", b"\x91\x02"]);
+        snapshot.turns[1].writer_id = Some("agent-7".into());
+        snapshot.turns[1].writer_seq = 12;
+
+        let head = client
+            .restore_context(&RequestContext::background(), &snapshot)
+            .unwrap();
+        assert_eq!((head.context_id.get(), head.head_turn_id.get()), (9, 21));
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        let last = &requests[2].payload;
+        assert!(last.windows(2).any(|window| window == b"\x91\x02"));
+        assert!(last.windows(7).any(|window| window == b"agent-7"));
+
+        // Nothing is created for a snapshot whose payloads were altered.
+        snapshot.turns[0].payload.push(0);
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_CTX_CREATE, context_head(10, 0))]);
+        let client = dial(&addr, []).unwrap();
+        let err = client
+            .restore_context(&RequestContext::background(), &snapshot)
+            .unwrap_err();
+        assert!(matches!(err, Error::Decode { .. }), "{err:?}");
+        drop(client);
+        assert!(handle.join().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Size figures for a context.
+//!
+//! [`Client::context_stats`] reports how many turns a context's history
+//! holds, how many payload bytes they add up to, in total and per declared
+//! type, and when the oldest and newest were appended. Compacted and expired
+//! turns count; redacted turns count with no bytes. Servers answer with one
+//! CONTEXT_STATS message. Against servers without it the client pages
+//! through metadata-only [`Client::get_last`] reads instead
+//! ([`ContextStats::compute`]), which gives the same figures at the cost of
+//! one round trip per thousand turns.
+
+use std::collections::BTreeMap;
+
+#[cfg(not(target_arch = "wasm32"))]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::MSG_CONTEXT_STATS;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::turn::{GetLastOptions, TurnFields};
+
+/// Turns read per GET_LAST by [`ContextStats::compute`].
+#[cfg(not(target_arch = "wasm32"))]
+const STATS_PAGE_SIZE: u32 = 1000;
+
+/// What [`Client::context_stats`] reports about a context.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContextStats {
+    pub turns: u64,
+    /// Uncompressed payload bytes of all turns.
+    pub payload_bytes: u64,
+    /// `payload_bytes` per declared `type_id`.
+    pub bytes_by_type: BTreeMap<String, u64>,
+    /// When the oldest turn was appended, in Unix milliseconds. `None` for an
+    /// empty context and from servers that do not report turn timestamps.
+    pub first_created_at_unix_ms: Option<u64>,
+    /// When the newest turn was appended, like `first_created_at_unix_ms`.
+    pub last_created_at_unix_ms: Option<u64>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ContextStats {
+    /// Computes the stats of `context_id` on the client by paging back
+    /// through its history without payloads. [`Client::context_stats`]
+    /// falls back to this; call it directly to skip the server round trip
+    /// that finds CONTEXT_STATS unsupported.
+    pub fn compute(client: &Client, ctx: &RequestContext, context_id: ContextId) -> Result<Self> {
+        let mut stats = ContextStats::default();
+        let mut before = None;
+        loop {
+            let mut opts = GetLastOptions {
+                limit: STATS_PAGE_SIZE,
+                include_payload: false,
+                ..GetLastOptions::default()
+            }
+            .include_compacted(true)
+            .include_expired(true)
+            .projection(TurnFields::TYPE | TurnFields::SIZE);
+            if let Some(turn_id) = before {
+                opts = opts.before(turn_id);
+            }
+            let page = client.get_last(ctx, context_id, opts)?;
+            // Pages arrive newest page first, each oldest turn first.
+            for record in page.iter().rev() {
+                let bytes = u64::from(record.payload_size);
+                stats.turns += 1;
+                stats.payload_bytes += bytes;
+                *stats
+                    .bytes_by_type
+                    .entry(record.type_id.to_string())
+                    .or_insert(0) += bytes;
+                if stats.last_created_at_unix_ms.is_none() {
+                    stats.last_created_at_unix_ms = record.created_at_unix_ms;
+                }
+                stats.first_created_at_unix_ms = record.created_at_unix_ms;
+            }
+            match page.first() {
+                Some(oldest) if page.len() as u32 == STATS_PAGE_SIZE => {
+                    before = Some(oldest.turn_id)
+                }
+                _ => return Ok(stats),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Reports the size of the history of `context_id` (see the
+    /// [module docs](self)), from the server when it supports
+    /// CONTEXT_STATS and computed with [`ContextStats::compute`] otherwise.
+    pub fn context_stats(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+    ) -> Result<ContextStats> {
+        let mut payload = Vec::with_capacity(8);
+        payload.write_u64::<LittleEndian>(context_id.get())?;
+        match self
+            .send_request(ctx, MSG_CONTEXT_STATS, &payload)
+            .map_err(|err| err.resolve_unsupported("CONTEXT_STATS").resolve_not_found())
+        {
+            Ok(frame) => parse_context_stats(&frame.payload),
+            Err(Error::Unsupported(_)) => ContextStats::compute(self, ctx, context_id),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+pub(crate) fn parse_context_stats(payload: &[u8]) -> Result<ContextStats> {
+    let mut reader = PayloadReader::new(payload, "context stats response");
+    let turns = reader.u64("turns")?;
+    let payload_bytes = reader.u64("payload_bytes")?;
+    let first = reader.u64("first_created_at_unix_ms")?;
+    let last = reader.u64("last_created_at_unix_ms")?;
+    let count = reader.u32("count")?;
+    let mut bytes_by_type = BTreeMap::new();
+    for _ in 0..count {
+        let type_id = std::str::from_utf8(reader.len_prefixed("type_id")?)
+            .map_err(|_| Error::protocol("stats type_id not utf8"))?;
+        bytes_by_type.insert(type_id.to_string(), reader.u64("bytes")?);
+    }
+    Ok(ContextStats {
+        turns,
+        payload_bytes,
+        bytes_by_type,
+        first_created_at_unix_ms: (first != 0).then_some(first),
+        last_created_at_unix_ms: (last != 0).then_some(last),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{GET_LAST_BEFORE, MSG_ERROR, MSG_GET_LAST};
+    use crate::test_util::{
+        error_payload, not_found_payload, spawn_multi_server, typed_turn_page_listing,
+    };
+
+    /// Turn `i + 1` of the mock history, with `i + 1` payload bytes.
+    fn history(len: usize) -> Vec<(&'static str, Vec<u8>)> {
+        (0..len)
+            .map(|i| {
+                let type_id = ["msg", "tool", "summary"][i % 3];
+                (type_id, vec![0u8; i + 1])
+            })
+            .collect()
+    }
+
+    fn stats_payload(history: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut by_type = BTreeMap::<&str, u64>::new();
+        for (type_id, payload) in history {
+            *by_type.entry(type_id).or_insert(0) += payload.len() as u64;
+        }
+        let total: u64 = by_type.values().sum();
+        let mut out = (history.len() as u64).to_le_bytes().to_vec();
+        out.extend_from_slice(&total.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&(by_type.len() as u32).to_le_bytes());
+        for (type_id, bytes) in by_type {
+            out.extend_from_slice(&(type_id.len() as u32).to_le_bytes());
+            out.extend_from_slice(type_id.as_bytes());
+            out.extend_from_slice(&bytes.to_le_bytes());
+        }
+        out
+    }
+
+    /// Serves GET_LAST listings of `history`, and CONTEXT_STATS for it only
+    /// when `stats` is set.
+    fn mock_server(len: usize, stats: bool) -> String {
+        let history = history(len);
+        spawn_multi_server(move |req| match req.header.msg_type {
+            MSG_CONTEXT_STATS if stats => (MSG_CONTEXT_STATS, stats_payload(&history)),
+            MSG_GET_LAST => {
+                // compute() always sends flags, so every trailing field
+                // up to before_turn_id is present.
+                let p = &req.payload;
+                let limit = u32::from_le_bytes(p[8..12].try_into().unwrap()) as usize;
+                let flags = u32::from_le_bytes(p[32..36].try_into().unwrap());
+                let end = if flags & GET_LAST_BEFORE != 0 {
+                    u64::from_le_bytes(p[36..44].try_into().unwrap()) as usize - 1
+                } else {
+                    history.len()
+                };
+                let start = end.saturating_sub(limit);
+                let turns: Vec<_> = history[start..end]
+                    .iter()
+                    .map(|(type_id, payload)| (*type_id, payload.as_slice()))
+                    .collect();
+                (
+                    MSG_GET_LAST,
+                    typed_turn_page_listing(start as u64 + 1, &turns),
+                )
+            }
+            _ => (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        })
+    }
+
+    #[test]
+    fn server_and_computed_stats_agree() {
+        let ctx = RequestContext::background();
+        for len in [0, 7, STATS_PAGE_SIZE as usize, 2500] {
+            let served = dial(&mock_server(len, true), []).unwrap();
+            let fallback = dial(&mock_server(len, false), []).unwrap();
+
+            let stats = served.context_stats(&ctx, ContextId::new(1)).unwrap();
+            assert_eq!(stats.turns, len as u64);
+            assert_eq!(stats.payload_bytes, (len * (len + 1) / 2) as u64);
+            assert_eq!(
+                stats,
+                ContextStats::compute(&served, &ctx, ContextId::new(1)).unwrap()
+            );
+            assert_eq!(
+                stats,
+                fallback.context_stats(&ctx, ContextId::new(1)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn context_stats_reports_a_missing_context() {
+        let addr = spawn_multi_server(|_| (MSG_ERROR, not_found_payload("context", 9)));
+        let client = dial(&addr, []).unwrap();
+        let err = client
+            .context_stats(&RequestContext::background(), ContextId::new(9))
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 9),
+            "{err:?}"
+        );
+    }
+}
@@ -0,0 +1,565 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Following a context as turns are appended.
+//!
+//! [`Client::subscribe`] polls a context's head on a dedicated connection
+//! and yields each new turn of the head's line, oldest first. Turns wait for
+//! the consumer in a queue of [`SubscribeOptions::buffer_turns`]; what
+//! happens when a stalled consumer lets it fill is the
+//! [`OverflowPolicy`]:
+//!
+//! - [`Block`](OverflowPolicy::Block) stops polling until the consumer
+//!   catches up. Nothing is lost; the context simply runs ahead.
+//! - [`DropOldest`](OverflowPolicy::DropOldest) discards the oldest queued
+//!   turns and yields a [`SubscriptionItem::Gap`] in their place, naming
+//!   what was skipped so it can be read back with [`Client::get_range`].
+//! - [`Error`](OverflowPolicy::Error) ends the subscription with
+//!   [`Error::SubscriptionOverflow`] once the queued turns are consumed.
+//!
+//! Either way memory stays bounded, and [`Subscription::lag`] reports how
+//! many turns the consumer is behind the head, for alerting.
+//!
+//! ```no_run
+//! use cxdb::subscribe::{OverflowPolicy, SubscribeOptions, SubscriptionItem};
+//! use cxdb::{dial, ContextId, GetTurnOptions, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let ctx = RequestContext::background();
+//! let context_id = ContextId::new(1);
+//! let opts = SubscribeOptions::default()
+//!     .buffer_turns(512)
+//!     .overflow(OverflowPolicy::DropOldest);
+//! let subscription = client.subscribe(context_id, opts)?;
+//! for item in &subscription {
+//!     match item? {
+//!         SubscriptionItem::Turn(turn) => println!("turn {}", turn.turn_id),
+//!         SubscriptionItem::Gap { from_depth, to_depth, .. } => {
+//!             let missed = client.get_range(&ctx, context_id, from_depth, to_depth, GetTurnOptions::default())?;
+//!             println!("caught up on {} skipped turns", missed.len());
+//!         }
+//!     }
+//!     if subscription.lag() > 10_000 {
+//!         eprintln!("subscriber is falling behind");
+//!     }
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::reconnect::{is_connection_error, DialFunc};
+use crate::turn::{GetTurnOptions, TurnRecord, RANGE_PAGE_SIZE};
+
+/// Default [`SubscribeOptions::buffer_turns`].
+pub const DEFAULT_SUBSCRIBE_BUFFER_TURNS: usize = 1024;
+
+/// Default [`SubscribeOptions::poll_interval`].
+pub const DEFAULT_SUBSCRIBE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a [`Subscription`] does with a new turn when its queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop reading until the consumer makes room.
+    #[default]
+    Block,
+    /// Drop the oldest queued turn and report it in a
+    /// [`SubscriptionItem::Gap`].
+    DropOldest,
+    /// End the subscription with [`Error::SubscriptionOverflow`].
+    Error,
+}
+
+/// How [`Client::subscribe`] follows a context.
+#[derive(Debug, Clone, Copy)]
+pub struct SubscribeOptions {
+    /// Most turns queued for the consumer.
+    pub buffer_turns: usize,
+    pub overflow: OverflowPolicy,
+    /// How often the head is checked once the subscription has caught up.
+    pub poll_interval: Duration,
+    /// Start at this depth instead of after the current head, e.g. `0` to
+    /// replay the whole line first or the depth after the last turn seen
+    /// to resume.
+    pub from_depth: Option<u32>,
+    pub include_payload: bool,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self {
+            buffer_turns: DEFAULT_SUBSCRIBE_BUFFER_TURNS,
+            overflow: OverflowPolicy::Block,
+            poll_interval: DEFAULT_SUBSCRIBE_POLL_INTERVAL,
+            from_depth: None,
+            include_payload: true,
+        }
+    }
+}
+
+impl SubscribeOptions {
+    pub fn buffer_turns(mut self, n: usize) -> Self {
+        self.buffer_turns = n;
+        self
+    }
+
+    pub fn overflow(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
+
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn from_depth(mut self, depth: u32) -> Self {
+        self.from_depth = Some(depth);
+        self
+    }
+
+    pub fn include_payload(mut self, include: bool) -> Self {
+        self.include_payload = include;
+        self
+    }
+}
+
+/// One item yielded by a [`Subscription`].
+#[derive(Debug, Clone)]
+// Turns are nearly every item; boxing them would allocate per turn to
+// shrink the rare gap.
+#[allow(clippy::large_enum_variant)]
+pub enum SubscriptionItem {
+    Turn(TurnRecord),
+    /// Turns dropped under [`OverflowPolicy::DropOldest`]: every turn from
+    /// `from_turn_id` (at `from_depth`) to `to_turn_id` (at `to_depth`).
+    /// Read them back with [`Client::get_range`].
+    Gap {
+        from_turn_id: TurnId,
+        to_turn_id: TurnId,
+        from_depth: u32,
+        to_depth: u32,
+    },
+}
+
+impl Client {
+    /// Follows `context_id`, yielding its new turns as they are appended
+    /// (see the [module docs](self)). The subscription reads on its own
+    /// connection and stops when dropped.
+    ///
+    /// Fails only if the client is closed; a context that does not exist
+    /// ends the subscription with [`Error::ContextNotFound`].
+    pub fn subscribe(&self, context_id: ContextId, opts: SubscribeOptions) -> Result<Subscription> {
+        if self.is_closed() {
+            return Err(Error::ClientClosed);
+        }
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            head_end: AtomicU64::new(0),
+            consumed_end: AtomicU64::new(0),
+        });
+        let poller = Poller {
+            shared: shared.clone(),
+            dial: self.dialer(),
+            context_id,
+            opts: SubscribeOptions {
+                buffer_turns: opts.buffer_turns.max(1),
+                ..opts
+            },
+        };
+        thread::Builder::new()
+            .name("cxdb-subscribe".into())
+            .spawn(move || poller.run())?;
+        Ok(Subscription { shared })
+    }
+}
+
+/// A stream of a context's new turns, returned by [`Client::subscribe`].
+/// Iterate it (by value or by reference) to receive them; iteration blocks
+/// until the next turn and ends after an error.
+pub struct Subscription {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when the queue or `closed` changes.
+    changed: Condvar,
+    /// One past the head's depth as last polled; 0 before the first poll
+    /// and for an empty context.
+    head_end: AtomicU64,
+    /// One past the depth of the last turn the consumer received (or
+    /// skipped over in a gap).
+    consumed_end: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<TurnRecord>,
+    /// Turns dropped just before the front of `queue`.
+    gap: Option<(TurnRecord, TurnRecord)>,
+    /// Error to yield once `queue` is drained, ending the subscription.
+    error: Option<Error>,
+    /// The poller stopped; nothing more will be queued.
+    finished: bool,
+    /// The subscription was dropped or closed.
+    closed: bool,
+}
+
+impl Subscription {
+    /// Turns appended to the head's line that the consumer has not received
+    /// yet, queued or not, as of the last poll.
+    pub fn lag(&self) -> u64 {
+        let head_end = self.shared.head_end.load(Ordering::SeqCst);
+        head_end.saturating_sub(self.shared.consumed_end.load(Ordering::SeqCst))
+    }
+
+    /// Turns waiting in the queue.
+    pub fn buffered(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Stops polling. Turns already queued are still yielded.
+    pub fn close(&self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        self.shared.changed.notify_all();
+    }
+
+    fn recv(&self) -> Option<Result<SubscriptionItem>> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some((from, to)) = state.gap.take() {
+                self.shared.consumed(to.depth);
+                return Some(Ok(SubscriptionItem::Gap {
+                    from_turn_id: from.turn_id,
+                    to_turn_id: to.turn_id,
+                    from_depth: from.depth,
+                    to_depth: to.depth,
+                }));
+            }
+            if let Some(turn) = state.queue.pop_front() {
+                self.shared.consumed(turn.depth);
+                self.shared.changed.notify_all();
+                return Some(Ok(SubscriptionItem::Turn(turn)));
+            }
+            if let Some(err) = state.error.take() {
+                return Some(Err(err));
+            }
+            if state.finished || state.closed {
+                return None;
+            }
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = Result<SubscriptionItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Iterator for &Subscription {
+    type Item = Result<SubscriptionItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn consumed(&self, depth: u32) {
+        self.consumed_end
+            .fetch_max(u64::from(depth) + 1, Ordering::SeqCst);
+    }
+}
+
+/// The background half of a [`Subscription`].
+struct Poller {
+    shared: Arc<Shared>,
+    dial: DialFunc,
+    context_id: ContextId,
+    opts: SubscribeOptions,
+}
+
+impl Poller {
+    fn run(self) {
+        let result = self.poll_loop();
+        let mut state = self.shared.lock();
+        if let Err(err) = result {
+            state.error.get_or_insert(err);
+        }
+        state.finished = true;
+        self.shared.changed.notify_all();
+    }
+
+    fn poll_loop(&self) -> Result<()> {
+        let ctx = RequestContext::background();
+        let mut conn: Option<Client> = None;
+        let mut next_depth = self.opts.from_depth.map(u64::from);
+        if let Some(depth) = next_depth {
+            self.shared.consumed_end.store(depth, Ordering::SeqCst);
+        }
+        loop {
+            if self.shared.lock().closed {
+                return Ok(());
+            }
+            let client = match conn.as_ref().filter(|client| !client.is_poisoned()) {
+                Some(client) => client,
+                None => match (self.dial)() {
+                    Ok(client) => conn.insert(client),
+                    Err(err) if is_connection_error(&err) => {
+                        self.pause();
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                },
+            };
+            match self.catch_up(client, &ctx, &mut next_depth) {
+                Ok(true) => self.pause(),
+                Ok(false) => return Ok(()),
+                Err(err) if is_connection_error(&err) => {
+                    conn = None;
+                    self.pause();
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Queues every turn from `next_depth` up to the current head. Returns
+    /// false when the subscription should stop.
+    fn catch_up(
+        &self,
+        client: &Client,
+        ctx: &RequestContext,
+        next_depth: &mut Option<u64>,
+    ) -> Result<bool> {
+        let head = client.get_head(ctx, self.context_id)?;
+        let head_end = if head.head_turn_id.is_zero() {
+            0
+        } else {
+            u64::from(head.head_depth) + 1
+        };
+        self.shared.head_end.store(head_end, Ordering::SeqCst);
+        let next = *next_depth.get_or_insert_with(|| {
+            self.shared.consumed_end.store(head_end, Ordering::SeqCst);
+            head_end
+        });
+        let opts = GetTurnOptions::default().include_payload(self.opts.include_payload);
+        let mut from = next;
+        while from < head_end {
+            let to = (from + u64::from(RANGE_PAGE_SIZE)).min(head_end) - 1;
+            // Depths fit in u32: they come from the head's u32 depth.
+            let turns = client.get_range(ctx, self.context_id, from as u32, to as u32, opts)?;
+            for turn in turns {
+                if !self.push(turn) {
+                    return Ok(false);
+                }
+            }
+            from = to + 1;
+            *next_depth = Some(from);
+        }
+        Ok(true)
+    }
+
+    /// Queues `turn` under the overflow policy. Returns false when the
+    /// subscription should stop.
+    fn push(&self, turn: TurnRecord) -> bool {
+        let mut state = self.shared.lock();
+        while state.queue.len() >= self.opts.buffer_turns && !state.closed {
+            match self.opts.overflow {
+                OverflowPolicy::Block => {
+                    state = self
+                        .shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                }
+                OverflowPolicy::DropOldest => {
+                    let dropped = state.queue.pop_front().expect("queue is full");
+                    state.gap = match state.gap.take() {
+                        Some((from, _)) => Some((from, dropped)),
+                        None => Some((dropped.clone(), dropped)),
+                    };
+                }
+                OverflowPolicy::Error => {
+                    state.error = Some(Error::SubscriptionOverflow {
+                        buffer_turns: self.opts.buffer_turns,
+                    });
+                    return false;
+                }
+            }
+        }
+        if state.closed {
+            return false;
+        }
+        state.queue.push_back(turn);
+        self.shared.changed.notify_all();
+        true
+    }
+
+    /// Waits out the poll interval, returning early if the subscription is
+    /// closed.
+    fn pause(&self) {
+        let state = self.shared.lock();
+        let _ = self
+            .shared
+            .changed
+            .wait_timeout_while(state, self.opts.poll_interval, |state| !state.closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{MSG_GET_HEAD, MSG_GET_LAST};
+    use crate::test_util::{spawn_multi_server, turn_page_payload};
+
+    /// A context holding turns `1..=head`, each at depth == turn id.
+    fn context(head: Arc<AtomicU64>) -> String {
+        spawn_multi_server(move |req| {
+            let head = head.load(Ordering::SeqCst);
+            if req.header.msg_type == MSG_GET_HEAD {
+                let mut out = 1u64.to_le_bytes().to_vec();
+                out.extend_from_slice(&head.to_le_bytes());
+                out.extend_from_slice(&(head as u32).to_le_bytes());
+                return (MSG_GET_HEAD, out);
+            }
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let top = match req.payload.get(36..44) {
+                Some(before) => u64::from_le_bytes(before.try_into().unwrap()) - 1,
+                None => head,
+            };
+            let first = top.saturating_sub(limit) + 1;
+            let payloads: Vec<Vec<u8>> = (first..=top).map(|id| vec![id as u8]).collect();
+            let payloads: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+            (MSG_GET_LAST, turn_page_payload(first, &payloads))
+        })
+    }
+
+    fn opts(buffer_turns: usize, overflow: OverflowPolicy) -> SubscribeOptions {
+        SubscribeOptions::default()
+            .buffer_turns(buffer_turns)
+            .overflow(overflow)
+            .poll_interval(Duration::from_millis(10))
+            .from_depth(1)
+    }
+
+    fn turn_id(item: Option<Result<SubscriptionItem>>) -> u64 {
+        match item {
+            Some(Ok(SubscriptionItem::Turn(turn))) => turn.turn_id.get(),
+            other => panic!("expected a turn, got {other:?}"),
+        }
+    }
+
+    /// Waits until the poller has filled the queue.
+    fn wait_full(subscription: &Subscription, n: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while subscription.buffered() < n || subscription.lag() == 0 {
+            assert!(Instant::now() < deadline, "queue never filled");
+            thread::sleep(Duration::from_millis(5));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    #[test]
+    fn block_yields_every_turn_and_follows_appends() {
+        let head = Arc::new(AtomicU64::new(5));
+        let client = dial(&context(head.clone()), []).unwrap();
+        let mut subscription = client
+            .subscribe(ContextId::new(1), opts(2, OverflowPolicy::Block))
+            .unwrap();
+
+        let ids: Vec<u64> = (0..5).map(|_| turn_id(subscription.next())).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+        head.store(7, Ordering::SeqCst);
+        assert_eq!(turn_id(subscription.next()), 6);
+        assert_eq!(turn_id(subscription.next()), 7);
+        assert_eq!(subscription.lag(), 0);
+    }
+
+    #[test]
+    fn drop_oldest_reports_a_gap_and_lag() {
+        let head = Arc::new(AtomicU64::new(10));
+        let client = dial(&context(head), []).unwrap();
+        let mut subscription = client
+            .subscribe(ContextId::new(1), opts(3, OverflowPolicy::DropOldest))
+            .unwrap();
+        wait_full(&subscription, 3);
+        assert_eq!(subscription.lag(), 10);
+
+        match subscription.next() {
+            Some(Ok(SubscriptionItem::Gap {
+                from_turn_id,
+                to_turn_id,
+                from_depth: 1,
+                to_depth: 7,
+            })) if (from_turn_id.get(), to_turn_id.get()) == (1, 7) => {}
+            other => panic!("expected a gap, got {other:?}"),
+        }
+        assert_eq!(subscription.lag(), 3);
+        let ids: Vec<u64> = (0..3).map(|_| turn_id(subscription.next())).collect();
+        assert_eq!(ids, [8, 9, 10]);
+        assert_eq!(subscription.lag(), 0);
+
+        let missed = client
+            .get_range(
+                &RequestContext::background(),
+                ContextId::new(1),
+                1,
+                7,
+                GetTurnOptions::default(),
+            )
+            .unwrap();
+        let ids: Vec<u64> = missed.iter().map(|turn| turn.turn_id.get()).collect();
+        assert_eq!(ids, (1..=7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn error_policy_ends_after_the_queued_turns() {
+        let head = Arc::new(AtomicU64::new(10));
+        let client = dial(&context(head), []).unwrap();
+        let mut subscription = client
+            .subscribe(ContextId::new(1), opts(2, OverflowPolicy::Error))
+            .unwrap();
+        wait_full(&subscription, 2);
+
+        assert_eq!(turn_id(subscription.next()), 1);
+        assert_eq!(turn_id(subscription.next()), 2);
+        match subscription.next() {
+            Some(Err(Error::SubscriptionOverflow { buffer_turns: 2 })) => {}
+            other => panic!("expected overflow, got {other:?}"),
+        }
+        assert!(subscription.next().is_none());
+    }
+}
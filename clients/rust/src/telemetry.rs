@@ -20,7 +20,7 @@
 //!
 //! ```no_run
 //! use cxdb::telemetry::{TelemetrySender, TelemetryConfig};
-//! use cxdb::{AppendRequest, EncodingMsgpack};
+//! use cxdb::{AppendRequest, ContextId, EncodingMsgpack};
 //!
 //! // Start the telemetry sender
 //! let sender = TelemetrySender::start(TelemetryConfig {
@@ -30,7 +30,7 @@
 //! });
 //!
 //! // Send telemetry (no I/O, drops oldest if queue full)
-//! let req = AppendRequest::new(1, "telemetry.Event", 1, vec![0x91, 0x01]);
+//! let req = AppendRequest::new(ContextId::new(1), "telemetry.Event", 1, vec![0x91, 0x01]);
 //! sender.send(req);
 //! ```
 
@@ -337,6 +337,7 @@ impl TelemetrySenderBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::{ContextId, TurnId};
     use crate::protocol::ENCODING_MSGPACK;
 
     #[test]
@@ -356,28 +357,38 @@ mod tests {
         // Fill the queue
         for i in 0..3 {
             let req = AppendRequest {
-                context_id: i,
-                parent_turn_id: 0,
+                context_id: ContextId::new(i),
+                parent_turn_id: TurnId::new(0),
                 type_id: "test".into(),
                 type_version: 1,
                 payload: vec![],
                 idempotency_key: vec![],
                 encoding: ENCODING_MSGPACK,
                 compression: 0,
+                writer_id: None,
+                writer_seq: 0,
+                ttl: None,
+                links: Vec::new(),
+                delta_base: None,
             };
             assert!(sender.send(req), "should not overflow for item {i}");
         }
 
         // Queue is now full, next send should overflow
         let req = AppendRequest {
-            context_id: 100,
-            parent_turn_id: 0,
+            context_id: ContextId::new(100),
+            parent_turn_id: TurnId::new(0),
             type_id: "test".into(),
             type_version: 1,
             payload: vec![],
             idempotency_key: vec![],
             encoding: ENCODING_MSGPACK,
             compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
         };
         assert!(!sender.send(req), "should overflow");
 
@@ -388,7 +399,7 @@ mod tests {
         // Verify oldest was dropped (context_id 0) and newest is present (context_id 100)
         let (lock, _) = &*sender.state;
         let guard = lock.lock().unwrap();
-        let ids: Vec<u64> = guard.queue.iter().map(|r| r.context_id).collect();
+        let ids: Vec<u64> = guard.queue.iter().map(|r| r.context_id.get()).collect();
         assert_eq!(ids, vec![1, 2, 100]);
     }
 
@@ -408,14 +419,19 @@ mod tests {
         sender.shutdown();
 
         let req = AppendRequest {
-            context_id: 1,
-            parent_turn_id: 0,
+            context_id: ContextId::new(1),
+            parent_turn_id: TurnId::new(0),
             type_id: "test".into(),
             type_version: 1,
             payload: vec![],
             idempotency_key: vec![],
             encoding: ENCODING_MSGPACK,
             compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
         };
         assert!(!sender.send(req));
     }
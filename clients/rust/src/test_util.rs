@@ -32,3 +32,292 @@ pub fn load_fixture(name: &str) -> Fixture {
 pub fn decode_hex(hex_str: &str) -> Vec<u8> {
     hex::decode(hex_str).unwrap_or_else(|err| panic!("hex decode failed: {err}"))
 }
+
+/// Starts a single-connection server that answers HELLO and then replies to
+/// each following request with the next `(msg_type, payload)` in `replies`.
+/// The join handle yields the request frames the server received.
+#[cfg(test)]
+pub fn spawn_scripted_server(
+    replies: Vec<(u16, Vec<u8>)>,
+) -> (String, std::thread::JoinHandle<Vec<crate::protocol::Frame>>) {
+    spawn_scripted_server_with_flags(0, replies)
+}
+
+/// Like [`spawn_scripted_server`], with `hello_flags` set on the HELLO
+/// response.
+#[cfg(test)]
+pub fn spawn_scripted_server_with_flags(
+    hello_flags: u16,
+    replies: Vec<(u16, Vec<u8>)>,
+) -> (String, std::thread::JoinHandle<Vec<crate::protocol::Frame>>) {
+    use crate::protocol::{read_frame, write_frame, MSG_HELLO};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let hello = read_frame(&mut stream).unwrap();
+        assert_eq!(hello.header.msg_type, MSG_HELLO);
+        let mut resp = 1u64.to_le_bytes().to_vec();
+        resp.extend_from_slice(&1u16.to_le_bytes());
+        write_frame(
+            &mut stream,
+            MSG_HELLO,
+            hello_flags,
+            hello.header.req_id,
+            &resp,
+        )
+        .unwrap();
+
+        let mut received = Vec::new();
+        for (msg_type, payload) in replies {
+            let req = match read_frame(&mut stream) {
+                Ok(req) => req,
+                Err(_) => break,
+            };
+            write_frame(&mut stream, msg_type, 0, req.header.req_id, &payload).unwrap();
+            received.push(req);
+        }
+        received
+    });
+    (addr, handle)
+}
+
+/// Encodes a legacy ERROR frame payload (code + detail, no trailer).
+#[cfg(test)]
+pub fn error_payload(code: u32, detail: &str) -> Vec<u8> {
+    let mut payload = code.to_le_bytes().to_vec();
+    payload.extend_from_slice(&(detail.len() as u32).to_le_bytes());
+    payload.extend_from_slice(detail.as_bytes());
+    payload
+}
+
+/// Encodes an ERROR frame payload with the trailer: no flags, then `details`.
+#[cfg(test)]
+pub fn error_payload_with_details(code: u32, detail: &str, details: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = error_payload(code, detail);
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(details.len() as u32).to_le_bytes());
+    for (key, value) in details {
+        out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        out.extend_from_slice(key.as_bytes());
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value.as_bytes());
+    }
+    out
+}
+
+/// Encodes the 404 a server sends for a missing `resource` (`"context"` or
+/// `"turn"`) with the given id.
+#[cfg(test)]
+pub fn not_found_payload(resource: &str, id: u64) -> Vec<u8> {
+    error_payload_with_details(
+        404,
+        &format!("{resource} {id} not found"),
+        &[
+            ("resource", resource),
+            (&format!("{resource}_id"), &id.to_string()),
+        ],
+    )
+}
+
+/// Starts a server that accepts any number of connections, answers HELLO on
+/// each, and replies to every other request with `handler(request)`.
+#[cfg(test)]
+pub fn spawn_multi_server(
+    handler: impl Fn(&crate::protocol::Frame) -> (u16, Vec<u8>) + Send + Sync + 'static,
+) -> String {
+    use crate::protocol::{read_frame, write_frame, MSG_HELLO};
+    use std::sync::Arc;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let handler = Arc::new(handler);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let handler = handler.clone();
+            std::thread::spawn(move || {
+                while let Ok(req) = read_frame(&mut stream) {
+                    let (msg_type, payload) = if req.header.msg_type == MSG_HELLO {
+                        let mut resp = 1u64.to_le_bytes().to_vec();
+                        resp.extend_from_slice(&1u16.to_le_bytes());
+                        (MSG_HELLO, resp)
+                    } else {
+                        handler(&req)
+                    };
+                    if write_frame(&mut stream, msg_type, 0, req.header.req_id, &payload).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Encodes a GET_LAST response holding one turn per payload, with turn ids
+/// counting up from 1.
+#[cfg(test)]
+pub fn turn_records_payload(payloads: &[&[u8]]) -> Vec<u8> {
+    turn_records_payload_omitting(payloads, u32::MAX)
+}
+
+/// Like [`turn_records_payload`], but withholds payloads over `max` bytes the
+/// way a server honoring `max_payload_bytes` does.
+#[cfg(test)]
+pub fn turn_records_payload_omitting(payloads: &[&[u8]], max: u32) -> Vec<u8> {
+    let turns: Vec<_> = payloads.iter().map(|payload| ("test", *payload)).collect();
+    encode_turn_records(1, &turns, Some(max))
+}
+
+/// Like [`turn_records_payload`], for a request with `include_payload` 0:
+/// no payload fields at all.
+#[cfg(test)]
+pub fn turn_listing_payload(payloads: &[&[u8]]) -> Vec<u8> {
+    let turns: Vec<_> = payloads.iter().map(|payload| ("test", *payload)).collect();
+    encode_turn_records(1, &turns, None)
+}
+
+/// Like [`turn_records_payload`], with a declared type id per turn.
+#[cfg(test)]
+pub fn typed_turn_records_payload(turns: &[(&str, &[u8])]) -> Vec<u8> {
+    encode_turn_records(1, turns, Some(u32::MAX))
+}
+
+/// Encodes the chronological page of `payloads` whose first turn has id
+/// `first_turn_id`, each turn the child of the one before.
+#[cfg(test)]
+pub fn turn_page_payload(first_turn_id: u64, payloads: &[&[u8]]) -> Vec<u8> {
+    let turns: Vec<_> = payloads.iter().map(|payload| ("test", *payload)).collect();
+    encode_turn_records(first_turn_id, &turns, Some(u32::MAX))
+}
+
+/// Like [`turn_page_payload`], for a request with `include_payload` 0.
+#[cfg(test)]
+pub fn turn_page_listing(first_turn_id: u64, payloads: &[&[u8]]) -> Vec<u8> {
+    let turns: Vec<_> = payloads.iter().map(|payload| ("test", *payload)).collect();
+    encode_turn_records(first_turn_id, &turns, None)
+}
+
+/// Like [`turn_page_listing`], with a declared type id per turn.
+#[cfg(test)]
+pub fn typed_turn_page_listing(first_turn_id: u64, turns: &[(&str, &[u8])]) -> Vec<u8> {
+    encode_turn_records(first_turn_id, turns, None)
+}
+
+/// Encodes a GET_LAST response of `(turn_id, parent_id)` turns, each with
+/// the one-byte payload `turn_id as u8`, for histories that fork.
+#[cfg(test)]
+pub fn linked_turns_payload(turns: &[(u64, u64)]) -> Vec<u8> {
+    encode_linked_turns(turns, Some(u32::MAX))
+}
+
+/// Like [`linked_turns_payload`], for a request with `include_payload` 0.
+#[cfg(test)]
+pub fn linked_turns_listing(turns: &[(u64, u64)]) -> Vec<u8> {
+    encode_linked_turns(turns, None)
+}
+
+/// Encodes a GET_LAST response of `(turn_id, parent_id, depth)` turns in
+/// the order given, each with an empty msgpack array payload, for pages a
+/// misbehaving server might send.
+#[cfg(test)]
+pub fn raw_turns_payload(turns: &[(u64, u64, u32)]) -> Vec<u8> {
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    let mut out = Vec::new();
+    out.write_u32::<LittleEndian>(turns.len() as u32).unwrap();
+    for &turn in turns {
+        encode_turn_record(&mut out, turn, "test", b"\x90", Some(u32::MAX));
+    }
+    out
+}
+
+#[cfg(test)]
+fn encode_linked_turns(turns: &[(u64, u64)], max: Option<u32>) -> Vec<u8> {
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    let mut out = Vec::new();
+    out.write_u32::<LittleEndian>(turns.len() as u32).unwrap();
+    for &(turn_id, parent_id) in turns {
+        let depth = turn_id as u32;
+        encode_turn_record(
+            &mut out,
+            (turn_id, parent_id, depth),
+            "test",
+            &[turn_id as u8],
+            max,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+fn encode_turn_records(first_turn_id: u64, turns: &[(&str, &[u8])], max: Option<u32>) -> Vec<u8> {
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    let mut out = Vec::new();
+    out.write_u32::<LittleEndian>(turns.len() as u32).unwrap();
+    for (i, (type_id, payload)) in turns.iter().enumerate() {
+        let turn_id = first_turn_id + i as u64;
+        let depth = turn_id as u32;
+        encode_turn_record(
+            &mut out,
+            (turn_id, turn_id - 1, depth),
+            type_id,
+            payload,
+            max,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+fn encode_turn_record(
+    out: &mut Vec<u8>,
+    (turn_id, parent_id, depth): (u64, u64, u32),
+    type_id: &str,
+    payload: &[u8],
+    max: Option<u32>,
+) {
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    out.write_u64::<LittleEndian>(turn_id).unwrap();
+    out.write_u64::<LittleEndian>(parent_id).unwrap();
+    out.write_u32::<LittleEndian>(depth).unwrap();
+    out.write_u32::<LittleEndian>(type_id.len() as u32).unwrap();
+    out.extend_from_slice(type_id.as_bytes());
+    out.write_u32::<LittleEndian>(1).unwrap();
+    out.write_u32::<LittleEndian>(crate::protocol::ENCODING_MSGPACK)
+        .unwrap();
+    out.write_u32::<LittleEndian>(0).unwrap();
+    out.write_u32::<LittleEndian>(payload.len() as u32).unwrap();
+    out.extend_from_slice(blake3::hash(payload).as_bytes());
+    let Some(max) = max else {
+        return;
+    };
+    if payload.len() > max as usize {
+        out.write_u32::<LittleEndian>(crate::protocol::PAYLOAD_OMITTED)
+            .unwrap();
+        return;
+    }
+    out.write_u32::<LittleEndian>(payload.len() as u32).unwrap();
+    out.extend_from_slice(payload);
+}
+
+/// Drives `future` to completion on the current thread. Enough for
+/// [`crate::transport::TcpTransport`], whose futures never stay pending.
+#[cfg(test)]
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::yield_now();
+    }
+}
@@ -0,0 +1,401 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Seeded fixture data.
+//!
+//! [`FixtureBuilder`] fills a server (or anything implementing
+//! [`FixtureTarget`], such as a test double) with contexts of generated
+//! turns and returns a [`FixtureManifest`] of what it created, for
+//! assertions.
+//!
+//! Generation is a pure function of the builder's settings: the same seed
+//! yields the same turn types and payload bytes on every platform, so
+//! [`FixtureManifest::content_digest`] can be pinned in golden tests. Turn
+//! and context ids are whatever the target assigns.
+//!
+//! ```no_run
+//! use cxdb::testing::fixtures::FixtureBuilder;
+//! use cxdb::{dial, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let manifest = FixtureBuilder::new(42)
+//!     .contexts(3)
+//!     .turns_per_context(50..200)
+//!     .type_mix(&[("com.example.Message", 0.7), ("com.example.ToolCall", 0.3)])
+//!     .build(&client)?;
+//! for context in &manifest.contexts {
+//!     let head = client.get_head(&RequestContext::background(), context.context_id)?;
+//!     assert_eq!(head.head_turn_id, context.turns.last().unwrap().turn_id);
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::client::{Client, RequestContext};
+use crate::context::CreateContextOptions;
+use crate::encoding::encode_msgpack;
+use crate::error::Result;
+use crate::hash::hash_to_hex;
+use crate::ids::{ContextId, TurnId};
+use crate::reconnect::ReconnectingClient;
+use crate::turn::{AppendRequest, AppendResult};
+
+/// Type id of generated turns when no [`FixtureBuilder::type_mix`] is given.
+pub const DEFAULT_FIXTURE_TYPE_ID: &str = "cxdb.test.Fixture";
+
+/// Words generated text is drawn from.
+const WORDS: &[&str] = &[
+    "agent", "branch", "cache", "context", "delta", "depth", "fork", "head", "index", "key",
+    "ledger", "message", "node", "payload", "query", "record", "snapshot", "tool", "turn", "value",
+];
+
+/// Where [`FixtureBuilder::build`] creates its contexts and turns.
+/// Implemented for [`Client`] and [`ReconnectingClient`]; implement it on a
+/// test double to generate fixtures without a server.
+pub trait FixtureTarget {
+    /// Creates an empty context and returns its id.
+    fn create_context(&self, ctx: &RequestContext) -> Result<ContextId>;
+
+    fn append_turn(&self, ctx: &RequestContext, req: &AppendRequest) -> Result<AppendResult>;
+}
+
+impl FixtureTarget for Client {
+    fn create_context(&self, ctx: &RequestContext) -> Result<ContextId> {
+        Ok(Client::create_context(self, ctx, CreateContextOptions::default())?.context_id)
+    }
+
+    fn append_turn(&self, ctx: &RequestContext, req: &AppendRequest) -> Result<AppendResult> {
+        Client::append_turn(self, ctx, req)
+    }
+}
+
+impl FixtureTarget for ReconnectingClient {
+    fn create_context(&self, ctx: &RequestContext) -> Result<ContextId> {
+        Ok(
+            ReconnectingClient::create_context(self, ctx, CreateContextOptions::default())?
+                .context_id,
+        )
+    }
+
+    fn append_turn(&self, ctx: &RequestContext, req: &AppendRequest) -> Result<AppendResult> {
+        ReconnectingClient::append_turn(self, ctx, req)
+    }
+}
+
+/// Generates contexts of seeded turns; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct FixtureBuilder {
+    seed: u64,
+    contexts: usize,
+    turns_per_context: Range<usize>,
+    type_mix: Vec<(String, f64)>,
+}
+
+impl FixtureBuilder {
+    /// One context of 10 turns of [`DEFAULT_FIXTURE_TYPE_ID`], seeded with
+    /// `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            contexts: 1,
+            turns_per_context: 10..11,
+            type_mix: vec![(DEFAULT_FIXTURE_TYPE_ID.to_string(), 1.0)],
+        }
+    }
+
+    pub fn contexts(mut self, n: usize) -> Self {
+        self.contexts = n;
+        self
+    }
+
+    /// How many turns each context gets, drawn uniformly from `range`
+    /// (end exclusive) per context.
+    ///
+    /// # Panics
+    ///
+    /// If `range` is empty.
+    pub fn turns_per_context(mut self, range: Range<usize>) -> Self {
+        assert!(
+            !range.is_empty(),
+            "turns per context range {range:?} is empty"
+        );
+        self.turns_per_context = range;
+        self
+    }
+
+    /// The turn types to generate and their relative weights. Weights need
+    /// not sum to 1.
+    ///
+    /// # Panics
+    ///
+    /// If a weight is negative or NaN, or none is positive.
+    pub fn type_mix(mut self, mix: &[(&str, f64)]) -> Self {
+        assert!(
+            mix.iter().all(|&(_, weight)| weight >= 0.0)
+                && mix.iter().any(|&(_, weight)| weight > 0.0),
+            "type mix needs non-negative weights with a positive total"
+        );
+        self.type_mix = mix
+            .iter()
+            .map(|&(type_id, weight)| (type_id.to_string(), weight))
+            .collect();
+        self
+    }
+
+    /// Creates the contexts in `target` and appends their turns, each onto
+    /// the context's head. Fails with the first failed call; what was
+    /// created until then stays.
+    pub fn build(&self, target: &impl FixtureTarget) -> Result<FixtureManifest> {
+        let plan = self.generate()?;
+        let ctx = RequestContext::background();
+        let mut contexts = Vec::with_capacity(plan.len());
+        for turns in plan {
+            let context_id = target.create_context(&ctx)?;
+            let mut created = Vec::with_capacity(turns.len());
+            for (type_id, payload) in turns {
+                let req = AppendRequest::new(context_id, type_id.as_str(), 1, payload);
+                let result = target.append_turn(&ctx, &req)?;
+                created.push(FixtureTurn {
+                    turn_id: result.turn_id,
+                    depth: result.depth,
+                    type_id,
+                    payload_hash: result.payload_hash,
+                });
+            }
+            contexts.push(FixtureContext {
+                context_id,
+                turns: created,
+            });
+        }
+        Ok(FixtureManifest {
+            seed: self.seed,
+            contexts,
+        })
+    }
+
+    /// Every context's turns, in append order.
+    fn generate(&self) -> Result<Vec<Vec<PlannedTurn>>> {
+        let total: f64 = self.type_mix.iter().map(|(_, weight)| weight).sum();
+        let mut rng = SplitMix64(self.seed);
+        let span = (self.turns_per_context.end - self.turns_per_context.start) as u64;
+        let mut contexts = Vec::with_capacity(self.contexts);
+        for _ in 0..self.contexts {
+            let n = self.turns_per_context.start + (rng.next() % span) as usize;
+            let mut turns = Vec::with_capacity(n);
+            for seq in 0..n as u64 {
+                let type_id = self.pick_type(&mut rng, total);
+                let words = 3 + rng.next() % 18;
+                let text = (0..words)
+                    .map(|_| WORDS[(rng.next() % WORDS.len() as u64) as usize])
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let payload = encode_msgpack(&FixturePayload {
+                    type_id,
+                    seq,
+                    text,
+                    nonce: rng.next(),
+                })?;
+                turns.push((type_id.to_string(), payload));
+            }
+            contexts.push(turns);
+        }
+        Ok(contexts)
+    }
+
+    fn pick_type(&self, rng: &mut SplitMix64, total: f64) -> &str {
+        let mut target = rng.unit() * total;
+        for (type_id, weight) in &self.type_mix {
+            if target < *weight {
+                return type_id;
+            }
+            target -= weight;
+        }
+        // Rounding can leave `target` just past the last weight.
+        let (type_id, _) = self
+            .type_mix
+            .iter()
+            .rev()
+            .find(|(_, weight)| *weight > 0.0)
+            .expect("positive total weight");
+        type_id
+    }
+}
+
+/// A generated turn's `(type_id, payload)`.
+type PlannedTurn = (String, Vec<u8>);
+
+/// The msgpack body of a generated turn.
+#[derive(Serialize)]
+struct FixturePayload<'a> {
+    #[serde(rename = "1")]
+    type_id: &'a str,
+    /// Position in the context, so no two turns of a context are equal.
+    #[serde(rename = "2")]
+    seq: u64,
+    #[serde(rename = "3")]
+    text: String,
+    #[serde(rename = "4")]
+    nonce: u64,
+}
+
+/// The SplitMix64 generator: tiny, seedable and defined purely in terms of
+/// wrapping u64 arithmetic, so its output is the same everywhere.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`, from the top 53 bits.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// What [`FixtureBuilder::build`] created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureManifest {
+    pub seed: u64,
+    /// In creation order.
+    pub contexts: Vec<FixtureContext>,
+}
+
+/// One generated context and its turns, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureContext {
+    pub context_id: ContextId,
+    pub turns: Vec<FixtureTurn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureTurn {
+    pub turn_id: TurnId,
+    pub depth: u32,
+    pub type_id: String,
+    pub payload_hash: [u8; 32],
+}
+
+impl FixtureManifest {
+    /// Turns created across every context.
+    pub fn turn_count(&self) -> usize {
+        self.contexts
+            .iter()
+            .map(|context| context.turns.len())
+            .sum()
+    }
+
+    /// A hex BLAKE3 digest of the generated content (each context's turn
+    /// types and payload hashes, in order) leaving out the ids the target
+    /// assigned. Stable for a given seed and settings, for golden tests.
+    pub fn content_digest(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for context in &self.contexts {
+            hasher.update(&(context.turns.len() as u64).to_le_bytes());
+            for turn in &context.turns {
+                hasher.update(&(turn.type_id.len() as u64).to_le_bytes());
+                hasher.update(turn.type_id.as_bytes());
+                hasher.update(&turn.payload_hash);
+            }
+        }
+        hash_to_hex(hasher.finalize().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::turn::ConsistencyToken;
+
+    /// Records appends in memory, hashing payloads as the server does.
+    #[derive(Default)]
+    struct MemoryTarget {
+        contexts: RefCell<BTreeMap<ContextId, Vec<AppendRequest>>>,
+    }
+
+    impl FixtureTarget for MemoryTarget {
+        fn create_context(&self, _ctx: &RequestContext) -> Result<ContextId> {
+            let mut contexts = self.contexts.borrow_mut();
+            let context_id = ContextId::new(contexts.len() as u64 + 1);
+            contexts.insert(context_id, Vec::new());
+            Ok(context_id)
+        }
+
+        fn append_turn(&self, _ctx: &RequestContext, req: &AppendRequest) -> Result<AppendResult> {
+            let mut contexts = self.contexts.borrow_mut();
+            let turns = contexts.get_mut(&req.context_id).unwrap();
+            turns.push(req.clone());
+            Ok(AppendResult {
+                context_id: req.context_id,
+                turn_id: TurnId::new(req.context_id.get() * 1000 + turns.len() as u64),
+                depth: turns.len() as u32,
+                payload_hash: *blake3::hash(&req.payload).as_bytes(),
+                consistency_token: ConsistencyToken::default(),
+                created_at_unix_ms: None,
+                stored_len: None,
+                head: None,
+            })
+        }
+    }
+
+    fn builder(seed: u64) -> FixtureBuilder {
+        FixtureBuilder::new(seed)
+            .contexts(3)
+            .turns_per_context(50..200)
+            .type_mix(&[("com.example.Message", 0.7), ("com.example.ToolCall", 0.3)])
+    }
+
+    #[test]
+    fn same_seed_same_content() {
+        let target = MemoryTarget::default();
+        let manifest = builder(42).build(&target).unwrap();
+        assert_eq!(manifest.contexts.len(), 3);
+        for context in &manifest.contexts {
+            assert!((50..200).contains(&context.turns.len()));
+            let appended = &target.contexts.borrow()[&context.context_id];
+            assert_eq!(appended.len(), context.turns.len());
+        }
+        let messages = manifest
+            .contexts
+            .iter()
+            .flat_map(|context| &context.turns)
+            .filter(|turn| turn.type_id == "com.example.Message")
+            .count() as f64;
+        let share = messages / manifest.turn_count() as f64;
+        assert!((0.6..0.8).contains(&share), "message share {share}");
+
+        let again = builder(42).build(&MemoryTarget::default()).unwrap();
+        assert_eq!(again, manifest);
+        let other = builder(43).build(&MemoryTarget::default()).unwrap();
+        assert_ne!(other.content_digest(), manifest.content_digest());
+    }
+
+    #[test]
+    fn content_digest_is_golden() {
+        // Pinned: a change here breaks every golden test built on fixtures.
+        let manifest = FixtureBuilder::new(7)
+            .build(&MemoryTarget::default())
+            .unwrap();
+        assert_eq!(manifest.turn_count(), 10);
+        assert_eq!(
+            manifest.content_digest(),
+            "ca4a1e7c6a35e4a5e474d3c22640079abb1b827ea0baffad7b0f43486d59a9e5"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "positive total")]
+    fn rejects_a_type_mix_without_weight() {
+        let _ = FixtureBuilder::new(1).type_mix(&[("a", 0.0), ("b", f64::NAN)]);
+    }
+}
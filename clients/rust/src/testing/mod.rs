@@ -0,0 +1,6 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers for testing code that uses CXDB (`testing` feature).
+
+pub mod fixtures;
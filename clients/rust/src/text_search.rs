@@ -0,0 +1,244 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Full-text search over turn payloads.
+//!
+//! [`Client::search`] asks the server for the turns whose payload text holds
+//! every word of a query, across all contexts or within one context's
+//! history. Words are runs of letters and digits compared without regard to
+//! case, taken from the string values of msgpack payloads (map keys are not
+//! searched). Each hit carries a short snippet of the matching text.
+//!
+//! Hits come newest first. To page, repeat the query with
+//! [`TextQuery::before`] set to the last hit's turn id.
+//!
+//! ```no_run
+//! use cxdb::text_search::TextQuery;
+//! use cxdb::{dial, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let ctx = RequestContext::background();
+//! let mut query = TextQuery::new("refund policy").limit(20);
+//! loop {
+//!     let hits = client.search(&ctx, &query)?;
+//!     for hit in &hits {
+//!         println!("context {} turn {}: {}", hit.context_id, hit.turn.turn_id, hit.snippet);
+//!     }
+//!     match hits.last() {
+//!         Some(last) if hits.len() == query.limit as usize => {
+//!             query = query.before(last.turn.turn_id);
+//!         }
+//!         _ => break,
+//!     }
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+#[cfg(not(target_arch = "wasm32"))]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::MSG_TEXT_SEARCH;
+use crate::turn::{parse_turn_listing, TurnHeader};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextQuery {
+    /// The words to find; a turn matches when it holds all of them.
+    pub text: String,
+    /// Search only this context's history; `None` searches every context.
+    pub context_id: Option<ContextId>,
+    /// Only turns declared with this type; `None` searches every type.
+    pub type_id: Option<String>,
+    /// Most hits to return.
+    pub limit: u32,
+    /// Return only turns older than this one; `None` starts from the newest.
+    pub before_turn_id: Option<TurnId>,
+}
+
+impl TextQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            context_id: None,
+            type_id: None,
+            limit: 10,
+            before_turn_id: None,
+        }
+    }
+
+    /// Searches only the history of `context_id`.
+    pub fn context(mut self, context_id: ContextId) -> Self {
+        self.context_id = Some(context_id);
+        self
+    }
+
+    /// Searches only turns declared with `type_id`.
+    pub fn type_id(mut self, type_id: impl Into<String>) -> Self {
+        self.type_id = Some(type_id.into());
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Continues a search after the hit with `turn_id`.
+    pub fn before(mut self, turn_id: TurnId) -> Self {
+        self.before_turn_id = Some(turn_id);
+        self
+    }
+}
+
+/// A turn matching a [`TextQuery`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextHit {
+    /// The searched context or, for searches across contexts, the oldest
+    /// context whose history reaches the turn.
+    pub context_id: ContextId,
+    pub turn: TurnHeader,
+    /// The text around the first matching word, with `…` where it was cut.
+    pub snippet: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Finds up to `query.limit` turns whose payload text holds every word
+    /// of `query.text`, newest first (see the [module docs](self)). Expired
+    /// and redacted turns never match.
+    ///
+    /// A query without words fails with a 422 server error. Servers without
+    /// the TEXT_SEARCH message fail with [`Error::Unsupported`].
+    pub fn search(&self, ctx: &RequestContext, query: &TextQuery) -> Result<Vec<TextHit>> {
+        let type_id = query.type_id.as_deref().unwrap_or_default();
+        let mut payload = Vec::with_capacity(28 + type_id.len() + query.text.len());
+        payload.write_u64::<LittleEndian>(query.context_id.unwrap_or_default().get())?;
+        payload.write_u32::<LittleEndian>(query.limit)?;
+        payload.write_u64::<LittleEndian>(query.before_turn_id.unwrap_or_default().get())?;
+        payload.write_u32::<LittleEndian>(type_id.len() as u32)?;
+        payload.extend_from_slice(type_id.as_bytes());
+        payload.write_u32::<LittleEndian>(query.text.len() as u32)?;
+        payload.extend_from_slice(query.text.as_bytes());
+        let frame = self
+            .send_request(ctx, MSG_TEXT_SEARCH, &payload)
+            .map_err(|err| err.resolve_unsupported("TEXT_SEARCH").resolve_not_found())?;
+        parse_text_hits(&frame.payload)
+    }
+}
+
+/// Decodes a TEXT_SEARCH response: each hit's context and snippet, then the
+/// hits as a GET_LAST response without payloads.
+pub(crate) fn parse_text_hits(payload: &[u8]) -> Result<Vec<TextHit>> {
+    let mut reader = PayloadReader::new(payload, "text search response");
+    let count = reader.u32("count")?;
+    let mut heads = Vec::new();
+    for _ in 0..count {
+        let context_id = reader.u64("context_id")?.into();
+        let snippet = std::str::from_utf8(reader.len_prefixed("snippet")?)
+            .map_err(|_| Error::protocol("search snippet not utf8"))?;
+        heads.push((context_id, snippet.to_string()));
+    }
+    let turns = parse_turn_listing(reader.bytes(reader.remaining(), "turns")?)?;
+    if turns.len() != heads.len() {
+        return Err(Error::protocol(format!(
+            "text search response has {} hits for {} turns",
+            heads.len(),
+            turns.len()
+        )));
+    }
+    Ok(turns
+        .into_iter()
+        .zip(heads)
+        .map(|(turn, (context_id, snippet))| TextHit {
+            context_id,
+            turn: turn.split_payload().0,
+            snippet,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_ERROR;
+    use crate::test_util::{
+        error_payload, not_found_payload, spawn_scripted_server, turn_listing_payload,
+    };
+
+    fn hits_payload(hits: &[(u64, &str)]) -> Vec<u8> {
+        let mut out = (hits.len() as u32).to_le_bytes().to_vec();
+        for (context_id, snippet) in hits {
+            out.extend_from_slice(&context_id.to_le_bytes());
+            out.extend_from_slice(&(snippet.len() as u32).to_le_bytes());
+            out.extend_from_slice(snippet.as_bytes());
+        }
+        let turns: Vec<&[u8]> = hits.iter().map(|_| &b""[..]).collect();
+        out.extend_from_slice(&turn_listing_payload(&turns));
+        out
+    }
+
+    #[test]
+    fn search_sends_the_query_and_pairs_snippets_with_turns() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (
+                MSG_TEXT_SEARCH,
+                hits_payload(&[(4, "…to San Francisco"), (2, "San Francisco…")]),
+            ),
+            (MSG_ERROR, not_found_payload("context", 9)),
+            (MSG_ERROR, error_payload(422, "unknown msg_type")),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let hits = client.search(&ctx, &TextQuery::new("francisco")).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(
+            (hits[0].context_id.get(), hits[0].turn.turn_id.get()),
+            (4, 1)
+        );
+        assert_eq!(hits[0].snippet, "…to San Francisco");
+        assert_eq!(
+            (hits[1].context_id.get(), hits[1].turn.turn_id.get()),
+            (2, 2)
+        );
+
+        let query = TextQuery::new("san jose")
+            .context(ContextId::new(9))
+            .type_id("com.example.Msg")
+            .limit(5)
+            .before(TurnId::new(30));
+        let err = client.search(&ctx, &query).unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id } if context_id.get() == 9),
+            "{err:?}"
+        );
+        let err = client.search(&ctx, &query).unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[1].header.msg_type, MSG_TEXT_SEARCH);
+        assert_eq!(requests[1].payload, {
+            let mut expected = 9u64.to_le_bytes().to_vec();
+            expected.extend_from_slice(&5u32.to_le_bytes());
+            expected.extend_from_slice(&30u64.to_le_bytes());
+            expected.extend_from_slice(&15u32.to_le_bytes());
+            expected.extend_from_slice(b"com.example.Msg");
+            expected.extend_from_slice(&8u32.to_le_bytes());
+            expected.extend_from_slice(b"san jose");
+            expected
+        });
+    }
+
+    #[test]
+    fn mismatched_hit_counts_are_protocol_errors() {
+        let mut payload = hits_payload(&[(1, "a")]);
+        payload[0] = 2;
+        assert!(parse_text_hits(&payload).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opt-in per-call timing.
+//!
+//! A context made with [`RequestContext::with_timing`] records where each
+//! call made with it spent its time; read the breakdown back with
+//! [`RequestContext::last_timing`] once the call returns, whether it
+//! succeeded or failed. A call that times out shows the wait in
+//! [`CallTiming::server_wait`]. Contexts without timing pay one branch per
+//! phase.
+//!
+//! [`Client::append_turn`], [`Client::append_dedup`], [`Client::get_last`]
+//! and [`Client::create_context`] time the whole call, request encoding and
+//! response decoding included. Other calls time their round trips: `encode`
+//! and `decode` then only cover framing and error decoding. A call that
+//! makes several round trips adds them up.
+//!
+//! ```no_run
+//! use cxdb::{dial, ContextId, GetLastOptions, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", Vec::new())?;
+//! let ctx = RequestContext::background().with_timing();
+//! let result = client.get_last(&ctx, ContextId::new(1), GetLastOptions::default());
+//! if let Some(timing) = ctx.last_timing() {
+//!     println!("{:?} waiting on the server", timing.server_wait);
+//! }
+//! result?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`RequestContext::with_timing`]: crate::RequestContext::with_timing
+//! [`RequestContext::last_timing`]: crate::RequestContext::last_timing
+//! [`Client::append_turn`]: crate::Client::append_turn
+//! [`Client::append_dedup`]: crate::Client::append_dedup
+//! [`Client::get_last`]: crate::Client::get_last
+//! [`Client::create_context`]: crate::Client::create_context
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Where one call spent its time. The phases add up to `total`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallTiming {
+    /// Waiting for the connection: readiness checks, credentials and the
+    /// connection lock held by concurrent calls.
+    pub queue: Duration,
+    /// Encoding the request, its metadata and its frame.
+    pub encode: Duration,
+    /// Writing the request to the socket.
+    pub write: Duration,
+    /// From the request being written until the first response byte.
+    pub server_wait: Duration,
+    /// Reading the rest of the response frame.
+    pub read: Duration,
+    /// Decoding the response.
+    pub decode: Duration,
+    pub total: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    Queue,
+    Encode,
+    Write,
+    ServerWait,
+    Read,
+    Decode,
+}
+
+/// The timing state shared by a context and its clones.
+#[derive(Debug)]
+pub(crate) struct TimingSlot {
+    state: Mutex<SlotState>,
+}
+
+#[derive(Debug)]
+struct SlotState {
+    /// Open scopes; only the outermost one starts and ends a call.
+    depth: u32,
+    start: Instant,
+    /// When the current phase began.
+    mark: Instant,
+    phase: Phase,
+    current: CallTiming,
+    last: Option<CallTiming>,
+}
+
+impl TimingSlot {
+    pub(crate) fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(SlotState {
+                depth: 0,
+                start: now,
+                mark: now,
+                phase: Phase::Encode,
+                current: CallTiming::default(),
+                last: None,
+            }),
+        }
+    }
+
+    pub(crate) fn last(&self) -> Option<CallTiming> {
+        self.state.lock().ok().and_then(|state| state.last)
+    }
+
+    /// Opens a scope, starting a call in the encode phase if none is open.
+    pub(crate) fn begin(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.depth += 1;
+        if state.depth == 1 {
+            let now = Instant::now();
+            state.start = now;
+            state.mark = now;
+            state.phase = Phase::Encode;
+            state.current = CallTiming::default();
+        }
+    }
+
+    /// Closes a scope; closing the outermost one publishes the call.
+    pub(crate) fn end(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.depth = state.depth.saturating_sub(1);
+        if state.depth == 0 {
+            let now = Instant::now();
+            state.lap(now);
+            state.current.total = now - state.start;
+            state.last = Some(state.current);
+        }
+    }
+
+    /// Charges the time since the last switch to the current phase and
+    /// moves on to `phase`.
+    pub(crate) fn enter(&self, phase: Phase) {
+        if let Ok(mut state) = self.state.lock() {
+            state.lap(Instant::now());
+            state.phase = phase;
+        }
+    }
+}
+
+impl SlotState {
+    fn lap(&mut self, now: Instant) {
+        let elapsed = now - self.mark;
+        self.mark = now;
+        let field = match self.phase {
+            Phase::Queue => &mut self.current.queue,
+            Phase::Encode => &mut self.current.encode,
+            Phase::Write => &mut self.current.write,
+            Phase::ServerWait => &mut self.current.server_wait,
+            Phase::Read => &mut self.current.read,
+            Phase::Decode => &mut self.current.decode,
+        };
+        *field += elapsed;
+    }
+}
+
+/// Times one call while alive, on contexts made with timing.
+pub(crate) struct TimingScope<'a> {
+    slot: Option<&'a TimingSlot>,
+}
+
+impl<'a> TimingScope<'a> {
+    pub(crate) fn new(slot: Option<&'a TimingSlot>) -> Self {
+        if let Some(slot) = slot {
+            slot.begin();
+        }
+        Self { slot }
+    }
+}
+
+impl Drop for TimingScope<'_> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            slot.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use std::thread::sleep;
+
+    use super::*;
+    use crate::test_util::{spawn_multi_server, turn_page_payload};
+    use crate::{dial, GetLastOptions, RequestContext};
+
+    #[test]
+    fn phases_add_up_to_the_total() {
+        let addr = spawn_multi_server(|req| {
+            sleep(Duration::from_millis(50));
+            (req.header.msg_type, turn_page_payload(1, &[b"\x90"]))
+        });
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+        assert!(ctx.last_timing().is_none());
+        client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        assert!(ctx.last_timing().is_none());
+
+        let ctx = ctx.with_timing();
+        client.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        let timing = ctx.last_timing().unwrap();
+        assert!(timing.server_wait >= Duration::from_millis(50));
+        let phases = timing.queue
+            + timing.encode
+            + timing.write
+            + timing.server_wait
+            + timing.read
+            + timing.decode;
+        assert_eq!(phases, timing.total);
+    }
+
+    #[test]
+    fn timeouts_are_charged_to_the_server_wait() {
+        let addr = spawn_multi_server(|req| {
+            sleep(Duration::from_millis(500));
+            (req.header.msg_type, Vec::new())
+        });
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::with_timeout(Duration::from_millis(100)).with_timing();
+        assert!(client.get_head(&ctx, ContextId::new(1)).is_err());
+
+        let timing = ctx.last_timing().unwrap();
+        assert!(
+            timing.server_wait >= Duration::from_millis(90),
+            "{timing:?}"
+        );
+        assert!(timing.server_wait > timing.total / 2);
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional [`tracing`](https://docs.rs/tracing) instrumentation, enabled by
+//! the `tracing` feature.
+//!
+//! Operations run inside a `DEBUG` span named after the call
+//! (`cxdb.append_turn`, `cxdb.get_last`, `cxdb.create_context`) with
+//! `context_id`, `type_id`, `payload_bytes`, `request_id` and `status`
+//! fields. The reconnecting client emits events when it retries a request or
+//! redials. `request_id` is the [`REQUEST_ID_KEY`] value of the request's
+//! [`RequestContext`], if any. Without the feature every hook is a no-op.
+
+#[cfg(not(feature = "tracing"))]
+use std::time::Duration;
+
+use crate::client::RequestContext;
+#[cfg(feature = "tracing")]
+use crate::client::REQUEST_ID_KEY;
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Op {
+    AppendTurn,
+    GetLast,
+    CreateContext,
+}
+
+/// The span of one client operation.
+pub(crate) struct OpSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! op_span {
+    ($name:literal) => {
+        tracing::debug_span!(
+            $name,
+            context_id = tracing::field::Empty,
+            type_id = tracing::field::Empty,
+            payload_bytes = tracing::field::Empty,
+            request_id = tracing::field::Empty,
+            status = tracing::field::Empty,
+            error = tracing::field::Empty,
+        )
+    };
+}
+
+impl OpSpan {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(op: Op, ctx: &RequestContext) -> Self {
+        let span = match op {
+            Op::AppendTurn => op_span!("cxdb.append_turn"),
+            Op::GetLast => op_span!("cxdb.get_last"),
+            Op::CreateContext => op_span!("cxdb.create_context"),
+        };
+        if let Some(request_id) = ctx.get_value(REQUEST_ID_KEY) {
+            span.record("request_id", request_id);
+        }
+        Self { span }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline]
+    pub(crate) fn new(_op: Op, _ctx: &RequestContext) -> Self {
+        Self {}
+    }
+
+    #[inline]
+    pub(crate) fn context_id(&self, _context_id: ContextId) {
+        #[cfg(feature = "tracing")]
+        self.span.record("context_id", _context_id.get());
+    }
+
+    #[inline]
+    pub(crate) fn type_id(&self, _type_id: &str) {
+        #[cfg(feature = "tracing")]
+        self.span.record("type_id", _type_id);
+    }
+
+    #[inline]
+    pub(crate) fn payload_bytes(&self, _bytes: usize) {
+        #[cfg(feature = "tracing")]
+        self.span.record("payload_bytes", _bytes as u64);
+    }
+
+    /// Runs `op` inside the span and records its outcome as `status`.
+    #[inline]
+    pub(crate) fn run<T>(&self, op: impl FnOnce() -> Result<T>) -> Result<T> {
+        #[cfg(feature = "tracing")]
+        {
+            let result = self.span.in_scope(op);
+            match &result {
+                Ok(_) => {
+                    self.span.record("status", "ok");
+                }
+                Err(err) => {
+                    self.span.record("status", "error");
+                    self.span.record("error", tracing::field::display(err));
+                }
+            }
+            result
+        }
+        #[cfg(not(feature = "tracing"))]
+        op()
+    }
+}
+
+/// A reconnecting client is about to retry `op` after `err`.
+#[cfg(feature = "tracing")]
+pub(crate) fn retrying(
+    ctx: &RequestContext,
+    op: &str,
+    attempt: usize,
+    delay: std::time::Duration,
+    err: &Error,
+) {
+    tracing::debug!(
+        op,
+        attempt,
+        delay_ms = delay.as_millis() as u64,
+        request_id = ctx.get_value(REQUEST_ID_KEY),
+        error = %err,
+        "cxdb retrying request"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn retrying(_: &RequestContext, _: &str, _: usize, _: Duration, _: &Error) {}
+
+/// A reconnecting client failed to redial on attempt `attempt`.
+#[cfg(feature = "tracing")]
+pub(crate) fn reconnect_failed(ctx: &RequestContext, attempt: usize, err: &Error) {
+    tracing::warn!(
+        attempt,
+        request_id = ctx.get_value(REQUEST_ID_KEY),
+        error = %err,
+        "cxdb reconnect attempt failed"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn reconnect_failed(_: &RequestContext, _: usize, _: &Error) {}
+
+/// A reconnecting client redialed and got a new session.
+#[cfg(feature = "tracing")]
+pub(crate) fn reconnected(ctx: &RequestContext, attempt: usize, session_id: u64) {
+    tracing::info!(
+        attempt,
+        session_id,
+        request_id = ctx.get_value(REQUEST_ID_KEY),
+        "cxdb reconnected"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn reconnected(_: &RequestContext, _: usize, _: u64) {}
+
+/// A lenient client passed through an inconsistent `op` page.
+#[cfg(feature = "tracing")]
+pub(crate) fn inconsistent_page(ctx: &RequestContext, op: &str, reason: &str) {
+    tracing::warn!(
+        op,
+        reason,
+        request_id = ctx.get_value(REQUEST_ID_KEY),
+        "cxdb inconsistent page"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn inconsistent_page(_: &RequestContext, _: &str, _: &str) {}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use super::*;
+    use crate::context::CreateContextOptions;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_CTX_CREATE, MSG_GET_LAST};
+    use crate::test_util::{spawn_scripted_server, turn_records_payload};
+    use crate::turn::{AppendRequest, GetLastOptions};
+
+    type Fields = HashMap<String, String>;
+
+    /// Records span names and fields, and event fields.
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<(&'static str, Fields)>>,
+        events: Mutex<Vec<Fields>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingSubscriber(Arc<Recorder>);
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut fields = Fields::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            let mut spans = self.0.spans.lock().unwrap();
+            spans.push((attrs.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.spans.lock().unwrap();
+            let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.0.events.lock().unwrap().push(fields);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn append_ack() -> Vec<u8> {
+        let mut ack = 1u64.to_le_bytes().to_vec();
+        ack.extend_from_slice(&7u64.to_le_bytes());
+        ack.extend_from_slice(&1u32.to_le_bytes());
+        ack.extend_from_slice(&[0u8; 32]);
+        ack
+    }
+
+    #[test]
+    fn operations_record_spans_with_request_id() {
+        let mut head = 1u64.to_le_bytes().to_vec();
+        head.extend_from_slice(&[0u8; 12]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_CREATE, head),
+            (MSG_APPEND_TURN, append_ack()),
+            (
+                MSG_GET_LAST,
+                turn_records_payload(&[b"\x91\x01", b"\x91\x02"]),
+            ),
+            (
+                crate::protocol::MSG_ERROR,
+                crate::test_util::error_payload(404, "context not found"),
+            ),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background().with_value(REQUEST_ID_KEY, "req-7");
+
+        let subscriber = RecordingSubscriber::default();
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            client
+                .create_context(&ctx, CreateContextOptions::default())
+                .unwrap();
+            let req = AppendRequest::new(
+                ContextId::new(1),
+                "com.example.Message",
+                1,
+                vec![0x91, 0x03, 0x04],
+            );
+            client.append_turn(&ctx, &req).unwrap();
+            let opts = GetLastOptions {
+                include_payload: true,
+                ..Default::default()
+            };
+            client
+                .get_last(&ctx, ContextId::new(1), opts.clone())
+                .unwrap();
+            client.get_last(&ctx, ContextId::new(2), opts).unwrap_err();
+            retrying(
+                &ctx,
+                "AppendTurn",
+                1,
+                std::time::Duration::from_millis(5),
+                &Error::server(503, "busy"),
+            );
+        });
+        handle.join().unwrap();
+
+        let spans = subscriber.0.spans.lock().unwrap();
+        let names: Vec<_> = spans.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "cxdb.create_context",
+                "cxdb.append_turn",
+                "cxdb.get_last",
+                "cxdb.get_last"
+            ]
+        );
+        let field = |i: usize, name: &str| spans[i].1.get(name).map(String::as_str);
+        for i in 0..4 {
+            assert_eq!(field(i, "request_id"), Some("req-7"));
+        }
+        assert_eq!(field(0, "context_id"), Some("1"));
+        assert_eq!(field(0, "status"), Some("ok"));
+        assert_eq!(field(1, "type_id"), Some("com.example.Message"));
+        assert_eq!(field(1, "payload_bytes"), Some("3"));
+        assert_eq!(field(2, "payload_bytes"), Some("4"));
+        assert_eq!(field(3, "context_id"), Some("2"));
+        assert_eq!(field(3, "status"), Some("error"));
+        assert!(field(3, "error").is_some());
+
+        let events = subscriber.0.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].get("op").map(String::as_str), Some("AppendTurn"));
+        assert_eq!(
+            events[0].get("request_id").map(String::as_str),
+            Some("req-7")
+        );
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Appending to several contexts at once.
+//!
+//! [`Client::append_multi`] sends a batch of appends, each to its own
+//! context, that the server applies in order and all or none: when one
+//! user message fans out to a main context and an audit context, a crash
+//! or a conflict cannot leave one written without the other. If any entry
+//! fails (a stale writer sequence, a missing context or parent, a bad
+//! payload) nothing is appended and the error is
+//! [`Error::TransactionAborted`], naming the entry.
+//!
+//! Servers without the APPEND_MULTI message fail with
+//! [`Error::TransactionUnsupported`]. Callers that can live without
+//! atomicity may opt into [`AppendMultiOptions::best_effort`], which falls
+//! back to one [`Client::append_turn`] per entry. A failure then still
+//! stops the batch and names the entry, but the entries before it stay
+//! appended.
+//!
+//! ```no_run
+//! use cxdb::{dial, AppendRequest, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let ctx = RequestContext::background();
+//! let (chat, audit) = (ContextId::new(1), ContextId::new(2));
+//! let message = b"\x81\xa4text\xa2hi".to_vec();
+//! let results = client.append_multi(
+//!     &ctx,
+//!     &[
+//!         (chat, AppendRequest::new(chat, "chat.Message", 1, message.clone())),
+//!         (audit, AppendRequest::new(audit, "audit.Message", 1, message)),
+//!     ],
+//! )?;
+//! assert_eq!(results.len(), 2);
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::borrow::Cow;
+
+#[cfg(not(target_arch = "wasm32"))]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::error::{Error, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ids::ContextId;
+use crate::protocol::PayloadReader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::MSG_APPEND_MULTI;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::turn::{encode_append_request, AppendRequest};
+use crate::turn::{parse_append_result, AppendResult};
+
+/// How [`Client::append_multi_with`] treats servers without transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AppendMultiOptions {
+    /// Against servers without APPEND_MULTI, append the entries one by one
+    /// instead of failing with [`Error::TransactionUnsupported`]. Not
+    /// atomic: an entry that fails leaves the ones before it appended.
+    pub best_effort: bool,
+}
+
+impl AppendMultiOptions {
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        self.best_effort = best_effort;
+        self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Appends each request to the context paired with it, in order and
+    /// all or none (see the [module docs](self)). The pair's context id
+    /// wins over the request's `context_id`. Results come in entry order.
+    ///
+    /// An entry with `parent_turn_id` 0 appends to the head its context has
+    /// after the entries before it, so one batch can add several turns to
+    /// the same context.
+    pub fn append_multi(
+        &self,
+        ctx: &RequestContext,
+        entries: &[(ContextId, AppendRequest)],
+    ) -> Result<Vec<AppendResult>> {
+        self.append_multi_with(ctx, entries, &AppendMultiOptions::default())
+    }
+
+    /// Like [`Client::append_multi`], with `opts` deciding what happens
+    /// against servers that cannot append atomically.
+    pub fn append_multi_with(
+        &self,
+        ctx: &RequestContext,
+        entries: &[(ContextId, AppendRequest)],
+        opts: &AppendMultiOptions,
+    ) -> Result<Vec<AppendResult>> {
+        self.require_writable(MSG_APPEND_MULTI)?;
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut requests = Vec::with_capacity(entries.len());
+        for (entry, (context_id, req)) in entries.iter().enumerate() {
+            let mut req = Cow::Borrowed(req);
+            if req.context_id != *context_id {
+                req.to_mut().context_id = *context_id;
+            }
+            requests.push(req);
+            self.validate_append(&requests[entry])
+                .map_err(|err| aborted(entry, err))?;
+        }
+
+        let mut payload = Vec::with_capacity(
+            128 * entries.len() + requests.iter().map(|r| r.payload.len()).sum::<usize>(),
+        );
+        payload.write_u32::<LittleEndian>(entries.len() as u32)?;
+        for (entry, req) in requests.iter().enumerate() {
+            let req = self
+                .delta_append(ctx, req)
+                .map_err(|err| aborted(entry, err))?;
+            let req = self.seal_append(&req).map_err(|err| aborted(entry, err))?;
+            let mut body = Vec::with_capacity(128 + req.payload.len());
+            let flags = encode_append_request(&mut body, &req, None)?;
+            payload.write_u32::<LittleEndian>(u32::from(flags))?;
+            payload.write_u32::<LittleEndian>(body.len() as u32)?;
+            payload.extend_from_slice(&body);
+        }
+
+        let response = self.send_request(ctx, MSG_APPEND_MULTI, &payload);
+        for req in &requests {
+            self.prefetch_cache().invalidate(req.context_id);
+        }
+        match response {
+            Ok(frame) => parse_append_multi(&frame.payload, entries.len()),
+            Err(err) => match err.resolve_unsupported("APPEND_MULTI") {
+                Error::Unsupported(_) if opts.best_effort => requests
+                    .iter()
+                    .enumerate()
+                    .map(|(entry, req)| {
+                        self.append_turn(ctx, req)
+                            .map_err(|err| aborted(entry, err))
+                    })
+                    .collect(),
+                Error::Unsupported(_) => Err(Error::TransactionUnsupported),
+                err => Err(resolve_aborted(err, &requests)),
+            },
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn aborted(entry: usize, err: Error) -> Error {
+    Error::TransactionAborted {
+        entry,
+        source: Box::new(err),
+    }
+}
+
+/// Maps a server error naming the failed entry onto
+/// [`Error::TransactionAborted`], resolving it as that entry's append
+/// would have been.
+#[cfg(not(target_arch = "wasm32"))]
+fn resolve_aborted(err: Error, requests: &[Cow<'_, AppendRequest>]) -> Error {
+    let entry = match &err {
+        Error::Server { details, .. } => details.get("entry").and_then(|v| v.parse().ok()),
+        _ => None,
+    };
+    match entry.filter(|&entry: &usize| entry < requests.len()) {
+        Some(entry) => aborted(entry, err.resolve_not_found().resolve_writer_conflict()),
+        None => err,
+    }
+}
+
+pub(crate) fn parse_append_multi(payload: &[u8], expected: usize) -> Result<Vec<AppendResult>> {
+    let mut reader = PayloadReader::new(payload, "append_multi response");
+    let count = reader.u32("count")? as usize;
+    if count != expected {
+        return Err(Error::protocol(format!(
+            "append_multi acknowledged {count} of {expected} entries"
+        )));
+    }
+    let mut results = Vec::with_capacity(count);
+    for _ in 0..count {
+        results.push(parse_append_result(reader.len_prefixed("ack")?)?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dial;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_ERROR};
+    use crate::test_util::{
+        error_payload, error_payload_with_details, not_found_payload, spawn_scripted_server,
+    };
+
+    fn ack(context_id: u64, turn_id: u64) -> Vec<u8> {
+        let mut out = vec![0u8; 52];
+        out[0..8].copy_from_slice(&context_id.to_le_bytes());
+        out[8..16].copy_from_slice(&turn_id.to_le_bytes());
+        out
+    }
+
+    fn multi_payload(acks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = (acks.len() as u32).to_le_bytes().to_vec();
+        for ack in acks {
+            out.extend_from_slice(&(ack.len() as u32).to_le_bytes());
+            out.extend_from_slice(ack);
+        }
+        out
+    }
+
+    fn entries() -> Vec<(ContextId, AppendRequest)> {
+        vec![
+            // The pair's context id wins over the request's.
+            (
+                ContextId::new(1),
+                AppendRequest::new(ContextId::new(0), "chat.Message", 1, b"hi".to_vec()),
+            ),
+            (
+                ContextId::new(2),
+                AppendRequest::new(ContextId::new(2), "audit.Message", 1, b"hi".to_vec())
+                    .writer_id("audit")
+                    .writer_seq(7),
+            ),
+        ]
+    }
+
+    #[test]
+    fn append_multi_sends_one_frame_and_names_the_failed_entry() {
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_APPEND_MULTI, multi_payload(&[ack(1, 10), ack(2, 11)])),
+            (
+                MSG_ERROR,
+                error_payload_with_details(
+                    409,
+                    "writer \"audit\" sequence 7 is not after 7",
+                    &[
+                        ("entry", "1"),
+                        ("writer_id", "audit"),
+                        ("last_seq", "7"),
+                        ("writer_seq", "7"),
+                    ],
+                ),
+            ),
+            (
+                MSG_ERROR,
+                error_payload_with_details(
+                    404,
+                    "context 1 not found",
+                    &[("resource", "context"), ("context_id", "1"), ("entry", "0")],
+                ),
+            ),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let results = client.append_multi(&ctx, &entries()).unwrap();
+        let ids: Vec<_> = results
+            .iter()
+            .map(|r| (r.context_id.get(), r.turn_id.get()))
+            .collect();
+        assert_eq!(ids, [(1, 10), (2, 11)]);
+
+        match client.append_multi(&ctx, &entries()).unwrap_err() {
+            Error::TransactionAborted { entry: 1, source } => assert!(
+                matches!(*source, Error::WriterSequenceConflict { last_seq: 7, .. }),
+                "{source:?}"
+            ),
+            other => panic!("expected abort, got {other:?}"),
+        }
+        match client.append_multi(&ctx, &entries()).unwrap_err() {
+            Error::TransactionAborted { entry: 0, source } => assert!(
+                matches!(*source, Error::ContextNotFound { context_id } if context_id.get() == 1),
+                "{source:?}"
+            ),
+            other => panic!("expected abort, got {other:?}"),
+        }
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.msg_type, MSG_APPEND_MULTI);
+        let mut reader = PayloadReader::new(&requests[0].payload, "request");
+        assert_eq!(reader.u32("count").unwrap(), 2);
+        for (context_id, mut req) in entries() {
+            req.context_id = context_id;
+            let flags = reader.u32("flags").unwrap();
+            let body = reader.len_prefixed("body").unwrap();
+            let mut expected = Vec::new();
+            let expected_flags = encode_append_request(&mut expected, &req, None).unwrap();
+            assert_eq!(flags, u32::from(expected_flags));
+            assert_eq!(body, expected.as_slice());
+            assert_eq!(body[0..8], context_id.get().to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn append_multi_falls_back_only_when_asked() {
+        let unknown = || (MSG_ERROR, error_payload(422, "unknown msg_type"));
+        let (addr, handle) = spawn_scripted_server(vec![
+            unknown(),
+            unknown(),
+            (MSG_APPEND_TURN, ack(1, 10)),
+            (MSG_ERROR, not_found_payload("context", 2)),
+        ]);
+        let client = dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let err = client.append_multi(&ctx, &entries()).unwrap_err();
+        assert!(matches!(err, Error::TransactionUnsupported), "{err:?}");
+
+        let opts = AppendMultiOptions::default().best_effort(true);
+        match client
+            .append_multi_with(&ctx, &entries(), &opts)
+            .unwrap_err()
+        {
+            Error::TransactionAborted { entry: 1, source } => assert!(
+                matches!(*source, Error::ContextNotFound { context_id } if context_id.get() == 2),
+                "{source:?}"
+            ),
+            other => panic!("expected abort, got {other:?}"),
+        }
+
+        let requests = handle.join().unwrap();
+        let types: Vec<_> = requests.iter().map(|r| r.header.msg_type).collect();
+        assert_eq!(
+            types,
+            [
+                MSG_APPEND_MULTI,
+                MSG_APPEND_MULTI,
+                MSG_APPEND_TURN,
+                MSG_APPEND_TURN
+            ]
+        );
+    }
+}
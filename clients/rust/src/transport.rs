@@ -0,0 +1,88 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Frame transports for [`AsyncClient`](crate::async_client::AsyncClient).
+//!
+//! A [`Transport`] opens a connection and moves whole frames over it. The
+//! default on native targets is [`TcpTransport`]. On wasm32 the `websocket`
+//! feature adds `websocket::WebSocketTransport`, which carries the same frame
+//! stream in binary WebSocket messages, so the server is reached through a
+//! WebSocket-to-TCP bridge such as websockify.
+
+use std::future::Future;
+
+use crate::error::Result;
+use crate::protocol::Frame;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{BufReader, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::TcpStream;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::connect_tcp;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::{
+    read_frame_with_limit, DEFAULT_DIAL_TIMEOUT, DEFAULT_HAPPY_EYEBALLS_DELAY,
+    DEFAULT_READ_BUFFER_BYTES, DEFAULT_REQUEST_TIMEOUT,
+};
+
+/// A connection that carries CXDB frames.
+pub trait Transport: Sized {
+    /// Opens a connection to `addr`: `host:port` for TCP, a `ws://` or
+    /// `wss://` URL for WebSockets.
+    fn connect(addr: &str) -> impl Future<Output = Result<Self>>;
+
+    /// Sends one encoded frame: header, payload and any checksum trailer.
+    fn write_frame(&mut self, frame: &[u8]) -> impl Future<Output = Result<()>>;
+
+    /// Receives the next frame, failing with [`crate::Error::FrameTooLarge`]
+    /// when its payload exceeds `max_frame_size`.
+    ///
+    /// The future must be cancel-safe: dropped before it completes, it must
+    /// leave any bytes it has received for the next call. The client polls
+    /// reads on behalf of several pipelined requests and drops them freely.
+    fn read_frame(&mut self, max_frame_size: u32) -> impl Future<Output = Result<Frame>>;
+}
+
+/// The transport [`AsyncClient`](crate::async_client::AsyncClient) uses
+/// unless told otherwise.
+#[cfg(not(target_arch = "wasm32"))]
+pub type DefaultTransport = TcpTransport;
+
+/// The transport [`AsyncClient`](crate::async_client::AsyncClient) uses
+/// unless told otherwise.
+#[cfg(all(target_arch = "wasm32", feature = "websocket"))]
+pub type DefaultTransport = crate::websocket::WebSocketTransport;
+
+/// The binary protocol over plain TCP.
+///
+/// I/O is blocking, so every future completes on its first poll: it runs
+/// under any executor but holds the polling thread until the frame is
+/// through. Reads and writes give up after [`DEFAULT_REQUEST_TIMEOUT`].
+#[cfg(not(target_arch = "wasm32"))]
+pub struct TcpTransport {
+    reader: BufReader<TcpStream>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Transport for TcpTransport {
+    async fn connect(addr: &str) -> Result<Self> {
+        let stream = connect_tcp(&[addr], DEFAULT_DIAL_TIMEOUT, DEFAULT_HAPPY_EYEBALLS_DELAY)?;
+        stream.set_read_timeout(Some(DEFAULT_REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(DEFAULT_REQUEST_TIMEOUT))?;
+        Ok(Self {
+            reader: BufReader::with_capacity(DEFAULT_READ_BUFFER_BYTES, stream),
+        })
+    }
+
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.reader.get_mut().write_all(frame).map_err(Error::Io)
+    }
+
+    async fn read_frame(&mut self, max_frame_size: u32) -> Result<Frame> {
+        read_frame_with_limit(&mut self.reader, max_frame_size)
+    }
+}
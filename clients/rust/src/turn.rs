@@ -1,187 +1,484 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::io::Read;
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use std::any::Any;
+#[cfg(not(target_arch = "wasm32"))]
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cache::TurnCache;
+#[cfg(not(target_arch = "wasm32"))]
 use crate::client::{Client, RequestContext};
+use crate::context::ContextHead;
+use crate::encoding::decode_msgpack_into_with_max_depth;
+use crate::encryption::parse_envelope;
 use crate::error::{Error, Result};
-use crate::protocol::{ENCODING_MSGPACK, MSG_APPEND_TURN, MSG_GET_LAST};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::hash::hash32_from_hex;
+use crate::hash::hash_to_hex;
+use crate::ids::{ContextId, TurnId};
+use crate::links::{encode_links, read_links, LinkKind, TurnLink};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::metrics::Operation;
+use crate::protocol::{
+    PayloadReader, APPEND_FLAG_DEDUP, APPEND_FLAG_FS_ROOT, APPEND_FLAG_LINKS, APPEND_FLAG_TTL,
+    APPEND_FLAG_WRITER, COMPRESSION_NONE, ENCODING_ENCRYPTED, ENCODING_MSGPACK, GET_LAST_BEFORE,
+    GET_LAST_DEPTH_RANGE, GET_LAST_INCLUDE_COMPACTED, GET_LAST_INCLUDE_EXPIRED,
+    GET_LAST_PROJECTION, MAX_DECODE_DEPTH, MSG_APPEND_TURN, MSG_CTX_COMPACT, MSG_GET_LAST,
+    MSG_GET_TURN, PAYLOAD_OMITTED, TURN_FIELD_CONTENT_HASH, TURN_FIELD_DEPTH, TURN_FIELD_ENCODING,
+    TURN_FIELD_PARENT, TURN_FIELD_SIZE, TURN_FIELD_TYPE,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::trace::{Op, OpSpan};
+use crate::type_id::TypeId;
 
 #[derive(Debug, Clone)]
 pub struct AppendRequest {
-    pub context_id: u64,
-    pub parent_turn_id: u64,
+    pub context_id: ContextId,
+    pub parent_turn_id: TurnId,
     pub type_id: String,
     pub type_version: u32,
     pub payload: Vec<u8>,
     pub idempotency_key: Vec<u8>,
     pub encoding: u32,
     pub compression: u32,
+    /// Producer identity stamped on the turn (see [`AppendRequest::writer_id`]).
+    pub writer_id: Option<String>,
+    /// Position in `writer_id`'s sequence; ignored without a `writer_id`.
+    pub writer_seq: u64,
+    /// Expire the turn this long after the append (see [`AppendRequest::ttl`]).
+    pub ttl: Option<Duration>,
+    /// Earlier turns this one refers to (see [`AppendRequest::link_to`]).
+    pub links: Vec<TurnLink>,
+    /// Turn to store the payload as a delta against (see
+    /// [`AppendRequest::delta_against`]).
+    pub delta_base: Option<TurnId>,
 }
 
 impl AppendRequest {
     pub fn new(
-        context_id: u64,
+        context_id: ContextId,
         type_id: impl Into<String>,
         type_version: u32,
         payload: Vec<u8>,
     ) -> Self {
         Self {
             context_id,
-            parent_turn_id: 0,
+            parent_turn_id: TurnId::default(),
             type_id: type_id.into(),
             type_version,
             payload,
             idempotency_key: Vec::new(),
             encoding: ENCODING_MSGPACK,
             compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
+        }
+    }
+
+    /// Starts an append of a `type_id` turn to `context_id`, for requests
+    /// that set more than [`AppendRequest::new`] takes. The type version
+    /// defaults to 1 and the payload to empty.
+    ///
+    /// ```
+    /// # use cxdb::{AppendRequest, ContextId};
+    /// let req = AppendRequest::builder(ContextId::new(7), "chat.Message")
+    ///     .version(2)
+    ///     .payload(vec![0x80])
+    ///     .idempotency_key("msg-42")
+    ///     .build();
+    /// assert_eq!(req.type_version, 2);
+    /// ```
+    pub fn builder(context_id: ContextId, type_id: impl Into<String>) -> AppendRequestBuilder {
+        AppendRequestBuilder {
+            req: AppendRequest::new(context_id, type_id, 1, Vec::new()),
+        }
+    }
+
+    /// Stamps the turn with the producer that wrote it. Reads return the
+    /// stamp in [`TurnRecord::writer_id`].
+    pub fn writer_id(mut self, writer_id: &str) -> Self {
+        self.writer_id = Some(writer_id.to_string());
+        self
+    }
+
+    /// Sets the stamp's sequence number. It must be above the last sequence
+    /// the same writer appended to the context, or the append fails with
+    /// [`Error::WriterSequenceConflict`].
+    pub fn writer_seq(mut self, writer_seq: u64) -> Self {
+        self.writer_seq = writer_seq;
+        self
+    }
+
+    /// Records the codec `payload` is serialized with, e.g.
+    /// [`ENCODING_CBOR`](crate::protocol::ENCODING_CBOR) for a payload from
+    /// `encode_cbor` (`cbor` feature). Reads decode with it; the type id
+    /// still names the schema. Defaults to msgpack.
+    pub fn encoding(mut self, encoding: u32) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Expires the turn `ttl` after the server accepts it, rounded up to a
+    /// whole millisecond. Expired turns are skipped by history reads unless
+    /// [`GetLastOptions::include_expired`] is set; they keep their id and
+    /// depth, so later reads see gaps.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Links the turn to `turn_id`, which must be in the history the turn
+    /// is appended to, or the append fails with [`Error::TurnNotFound`].
+    /// Reads return the links in [`TurnRecord::links`]; see
+    /// [`crate::links`].
+    pub fn link_to(mut self, turn_id: TurnId, kind: LinkKind) -> Self {
+        self.links.push(TurnLink {
+            target_turn_id: turn_id,
+            kind,
+        });
+        self
+    }
+
+    /// Stores the payload as a delta against `turn_id`'s payload when that
+    /// is smaller, for turns that mostly repeat an earlier one. Reads return
+    /// the full payload; see [`crate::delta`]. [`Client::append_dedup`]
+    /// ignores it and stores the payload whole.
+    pub fn delta_against(mut self, turn_id: TurnId) -> Self {
+        self.delta_base = Some(turn_id);
+        self
+    }
+}
+
+/// Builds an [`AppendRequest`]; see [`AppendRequest::builder`].
+#[derive(Debug, Clone)]
+pub struct AppendRequestBuilder {
+    req: AppendRequest,
+}
+
+impl AppendRequestBuilder {
+    /// Sets the type version the payload is written with.
+    pub fn version(mut self, type_version: u32) -> Self {
+        self.req.type_version = type_version;
+        self
+    }
+
+    /// Sets the serialized payload.
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.req.payload = payload;
+        self
+    }
+
+    /// Appends after `parent_turn_id` instead of the context head.
+    pub fn parent_turn_id(mut self, parent_turn_id: TurnId) -> Self {
+        self.req.parent_turn_id = parent_turn_id;
+        self
+    }
+
+    /// Sets the key the server deduplicates retried appends by.
+    pub fn idempotency_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.req.idempotency_key = key.into();
+        self
+    }
+
+    /// See [`AppendRequest::encoding`].
+    pub fn encoding(mut self, encoding: u32) -> Self {
+        self.req = self.req.encoding(encoding);
+        self
+    }
+
+    /// See [`AppendRequest::writer_id`].
+    pub fn writer_id(mut self, writer_id: &str) -> Self {
+        self.req = self.req.writer_id(writer_id);
+        self
+    }
+
+    /// See [`AppendRequest::writer_seq`].
+    pub fn writer_seq(mut self, writer_seq: u64) -> Self {
+        self.req = self.req.writer_seq(writer_seq);
+        self
+    }
+
+    /// See [`AppendRequest::ttl`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.req = self.req.ttl(ttl);
+        self
+    }
+
+    /// See [`AppendRequest::link_to`].
+    pub fn link_to(mut self, turn_id: TurnId, kind: LinkKind) -> Self {
+        self.req = self.req.link_to(turn_id, kind);
+        self
+    }
+
+    /// See [`AppendRequest::delta_against`].
+    pub fn delta_against(mut self, turn_id: TurnId) -> Self {
+        self.req = self.req.delta_against(turn_id);
+        self
+    }
+
+    pub fn build(self) -> AppendRequest {
+        self.req
+    }
+}
+
+/// A caller-written summary for [`Client::compact_context`].
+#[derive(Debug, Clone)]
+pub struct CompactRequest {
+    /// Newest turn the summary covers. It and every older turn drop out of
+    /// default reads.
+    pub up_to_turn_id: TurnId,
+    pub summary_type_id: String,
+    pub summary_type_version: u32,
+    pub summary_payload: Vec<u8>,
+}
+
+impl CompactRequest {
+    pub fn new(
+        up_to_turn_id: TurnId,
+        summary_type_id: impl Into<String>,
+        summary_type_version: u32,
+        summary_payload: Vec<u8>,
+    ) -> Self {
+        Self {
+            up_to_turn_id,
+            summary_type_id: summary_type_id.into(),
+            summary_type_version,
+            summary_payload,
         }
     }
 }
 
+/// A turn as returned by [`Client::get_last`].
+///
+/// `P` is the payload container: `Vec<u8>` by default, or refcounted
+/// [`bytes::Bytes`] slices of the response from [`Client::get_last_shared`]
+/// (`bytes` feature).
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct TurnRecord {
-    pub turn_id: u64,
-    pub parent_id: u64,
+pub struct TurnRecord<P = Vec<u8>> {
+    pub turn_id: TurnId,
+    /// The turn this one was appended after; the zero id for a context's
+    /// first turn.
+    pub parent_id: TurnId,
     pub depth: u32,
-    pub type_id: String,
+    /// Interned: turns of one type share the string (see [`TypeId`]).
+    pub type_id: TypeId,
     pub type_version: u32,
     pub encoding: u32,
     pub compression: u32,
     pub payload_hash: [u8; 32],
-    pub payload: Vec<u8>,
+    /// Uncompressed payload size in bytes, reported even when the payload
+    /// itself was not returned.
+    pub payload_size: u32,
+    /// The payload exceeded [`GetLastOptions::max_payload_bytes`] and was left
+    /// out; `payload` is empty. Fetch it with [`Client::get_blob`] using
+    /// `payload_hash`.
+    pub payload_omitted: bool,
+    pub payload: P,
+    /// Producer that appended the turn, if it was stamped
+    /// (see [`AppendRequest::writer_id`]).
+    pub writer_id: Option<String>,
+    /// The producer's sequence number; 0 when `writer_id` is `None`.
+    pub writer_seq: u64,
+    /// When the turn expires, in Unix milliseconds, if it was appended with
+    /// a TTL (see [`AppendRequest::ttl`]).
+    pub expires_at_unix_ms: Option<u64>,
+    /// The turn had expired when it was read. Only reads with
+    /// [`GetLastOptions::include_expired`] and reads by id return such turns.
+    pub expired: bool,
+    /// When the server accepted the append, in Unix milliseconds. `None`
+    /// from servers that do not report turn timestamps.
+    pub created_at_unix_ms: Option<u64>,
+    /// The payload was removed by [`Client::redact_turn`]; `payload` is
+    /// empty and `payload_hash` is still the original content's hash.
+    pub redacted: bool,
+    /// When the turn was redacted, in Unix milliseconds.
+    pub redacted_at_unix_ms: Option<u64>,
+    /// Why the turn was redacted, as given to [`Client::redact_turn`].
+    pub redaction_reason: Option<String>,
+    /// Turns this one links to (see [`AppendRequest::link_to`]). Empty
+    /// from servers without turn links.
+    pub links: Vec<TurnLink>,
 }
 
+/// Turn record whose payload borrows the shared response buffer.
+#[cfg(feature = "bytes")]
+pub type SharedTurnRecord = TurnRecord<bytes::Bytes>;
+
+/// Every field of a turn but its payload, as in search hits and
+/// [`Client::get_last_typed`](crate::Client::get_last_typed).
+/// `payload_size` still reports the payload's length.
+pub type TurnHeader = TurnRecord<()>;
+
+/// The metadata a listing shows, as returned by [`Client::get_last_meta`].
+/// Build one from a [`TurnRecord`] with `From`.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct AppendResult {
-    pub context_id: u64,
-    pub turn_id: u64,
+pub struct TurnMeta {
+    pub turn_id: TurnId,
     pub depth: u32,
-    pub payload_hash: [u8; 32],
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct GetLastOptions {
-    pub limit: u32,
-    pub include_payload: bool,
+    pub type_id: TypeId,
+    pub type_version: u32,
+    /// BLAKE3 hash of the uncompressed payload.
+    pub content_hash: [u8; 32],
+    /// When the server accepted the append, in Unix milliseconds. `None`
+    /// from servers that do not report turn timestamps.
+    pub created_at: Option<u64>,
+    /// Uncompressed payload size in bytes.
+    pub payload_len: u32,
 }
 
-impl Default for GetLastOptions {
-    fn default() -> Self {
+impl<P> From<&TurnRecord<P>> for TurnMeta {
+    fn from(record: &TurnRecord<P>) -> Self {
         Self {
-            limit: 10,
-            include_payload: false,
+            turn_id: record.turn_id,
+            depth: record.depth,
+            type_id: record.type_id.clone(),
+            type_version: record.type_version,
+            content_hash: record.payload_hash,
+            created_at: record.created_at_unix_ms,
+            payload_len: record.payload_size,
         }
     }
 }
 
-impl Client {
-    pub fn append_turn(&self, ctx: &RequestContext, req: &AppendRequest) -> Result<AppendResult> {
-        let encoding = if req.encoding == 0 {
-            ENCODING_MSGPACK
-        } else {
-            req.encoding
-        };
-
-        let hash = blake3::hash(&req.payload);
-
-        let mut payload = Vec::with_capacity(128 + req.payload.len());
-        payload.write_u64::<LittleEndian>(req.context_id)?;
-        payload.write_u64::<LittleEndian>(req.parent_turn_id)?;
-
-        payload.write_u32::<LittleEndian>(req.type_id.len() as u32)?;
-        payload.extend_from_slice(req.type_id.as_bytes());
-        payload.write_u32::<LittleEndian>(req.type_version)?;
-
-        payload.write_u32::<LittleEndian>(encoding)?;
-        payload.write_u32::<LittleEndian>(req.compression)?;
-        payload.write_u32::<LittleEndian>(req.payload.len() as u32)?; // uncompressed len
-        payload.extend_from_slice(hash.as_bytes());
+impl<P> From<TurnRecord<P>> for TurnMeta {
+    fn from(record: TurnRecord<P>) -> Self {
+        Self::from(&record)
+    }
+}
 
-        payload.write_u32::<LittleEndian>(req.payload.len() as u32)?;
-        payload.extend_from_slice(&req.payload);
+/// A turn's [`TurnMeta`] and payload, as returned by
+/// [`Client::get_last_full`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn<P = Vec<u8>> {
+    pub meta: TurnMeta,
+    pub payload: P,
+}
 
-        payload.write_u32::<LittleEndian>(req.idempotency_key.len() as u32)?;
-        if !req.idempotency_key.is_empty() {
-            payload.extend_from_slice(&req.idempotency_key);
+impl<P> From<TurnRecord<P>> for Turn<P> {
+    fn from(record: TurnRecord<P>) -> Self {
+        let meta = TurnMeta::from(&record);
+        Self {
+            meta,
+            payload: record.payload,
         }
-
-        let frame = self.send_request(ctx, MSG_APPEND_TURN, &payload)?;
-        parse_append_result(&frame.payload)
     }
+}
 
-    pub fn get_last(
-        &self,
-        ctx: &RequestContext,
-        context_id: u64,
-        opts: GetLastOptions,
-    ) -> Result<Vec<TurnRecord>> {
-        let limit = if opts.limit == 0 { 10 } else { opts.limit };
-        let mut payload = Vec::with_capacity(16);
-        payload.write_u64::<LittleEndian>(context_id)?;
-        payload.write_u32::<LittleEndian>(limit)?;
-        payload.write_u32::<LittleEndian>(if opts.include_payload { 1 } else { 0 })?;
-
-        let frame = self.send_request(ctx, MSG_GET_LAST, &payload)?;
-        parse_turn_records(&frame.payload)
+impl<P> From<Turn<P>> for (TurnMeta, P) {
+    fn from(turn: Turn<P>) -> Self {
+        (turn.meta, turn.payload)
     }
 }
 
-fn parse_append_result(payload: &[u8]) -> Result<AppendResult> {
-    if payload.len() < 52 {
-        return Err(Error::invalid_response(format!(
-            "append response too short ({} bytes)",
-            payload.len()
-        )));
+impl TurnHeader {
+    /// Joins the header with a payload, undoing
+    /// [`TurnRecord::into_parts`].
+    pub fn with_payload<P>(self, payload: P) -> TurnRecord<P> {
+        let TurnRecord {
+            turn_id,
+            parent_id,
+            depth,
+            type_id,
+            type_version,
+            encoding,
+            compression,
+            payload_hash,
+            payload_size,
+            payload_omitted,
+            payload: (),
+            writer_id,
+            writer_seq,
+            expires_at_unix_ms,
+            expired,
+            created_at_unix_ms,
+            redacted,
+            redacted_at_unix_ms,
+            redaction_reason,
+            links,
+        } = self;
+        TurnRecord {
+            turn_id,
+            parent_id,
+            depth,
+            type_id,
+            type_version,
+            encoding,
+            compression,
+            payload_hash,
+            payload_size,
+            payload_omitted,
+            payload,
+            writer_id,
+            writer_seq,
+            expires_at_unix_ms,
+            expired,
+            created_at_unix_ms,
+            redacted,
+            redacted_at_unix_ms,
+            redaction_reason,
+            links,
+        }
     }
-    let mut cursor = std::io::Cursor::new(payload);
-    let context_id = cursor.read_u64::<LittleEndian>()?;
-    let turn_id = cursor.read_u64::<LittleEndian>()?;
-    let depth = cursor.read_u32::<LittleEndian>()?;
-    let mut hash = [0u8; 32];
-    cursor.read_exact(&mut hash)?;
-    Ok(AppendResult {
-        context_id,
-        turn_id,
-        depth,
-        payload_hash: hash,
-    })
 }
 
-fn parse_turn_records(payload: &[u8]) -> Result<Vec<TurnRecord>> {
-    if payload.len() < 4 {
-        return Err(Error::invalid_response("turn records too short"));
+impl<P> From<(TurnHeader, P)> for TurnRecord<P> {
+    fn from((header, payload): (TurnHeader, P)) -> Self {
+        header.with_payload(payload)
     }
+}
 
-    let mut cursor = std::io::Cursor::new(payload);
-    let count = cursor.read_u32::<LittleEndian>()?;
-    let mut records = Vec::with_capacity(count as usize);
-
-    for _ in 0..count {
-        let turn_id = cursor.read_u64::<LittleEndian>()?;
-        let parent_id = cursor.read_u64::<LittleEndian>()?;
-        let depth = cursor.read_u32::<LittleEndian>()?;
-
-        let type_len = cursor.read_u32::<LittleEndian>()? as usize;
-        let mut type_bytes = vec![0u8; type_len];
-        cursor.read_exact(&mut type_bytes)?;
-        let type_id = String::from_utf8(type_bytes)
-            .map_err(|_| Error::invalid_response("type_id not utf8"))?;
-
-        let type_version = cursor.read_u32::<LittleEndian>()?;
-        let encoding = cursor.read_u32::<LittleEndian>()?;
-        let compression = cursor.read_u32::<LittleEndian>()?;
+impl<P> TurnRecord<P> {
+    /// Resets the fields outside `fields` to their defaults.
+    pub(crate) fn project(&mut self, fields: TurnFields) {
+        if !fields.contains(TurnFields::PARENT) {
+            self.parent_id = TurnId::default();
+        }
+        if !fields.contains(TurnFields::DEPTH) {
+            self.depth = 0;
+        }
+        if !fields.contains(TurnFields::TYPE) {
+            self.type_id = TypeId::default();
+            self.type_version = 0;
+        }
+        if !fields.contains(TurnFields::ENCODING) {
+            self.encoding = 0;
+            self.compression = 0;
+        }
+        if !fields.contains(TurnFields::SIZE) {
+            self.payload_size = 0;
+        }
+        if !fields.contains(TurnFields::CONTENT_HASH) {
+            self.payload_hash = [0; 32];
+        }
+    }
 
-        let _uncompressed_len = cursor.read_u32::<LittleEndian>()?;
-        let mut payload_hash = [0u8; 32];
-        cursor.read_exact(&mut payload_hash)?;
+    /// Wraps the record so its payload is decoded on first access.
+    pub fn into_lazy(self) -> LazyTurn<P> {
+        LazyTurn::new(self)
+    }
 
-        let payload_len = cursor.read_u32::<LittleEndian>()? as usize;
-        let mut payload_bytes = vec![0u8; payload_len];
-        cursor.read_exact(&mut payload_bytes)?;
+    /// Splits the record into its header and payload.
+    #[deprecated(
+        since = "0.2.0",
+        note = "use `Turn::from`, or `TurnMeta::from` for the listing fields"
+    )]
+    pub fn into_parts(self) -> (TurnHeader, P) {
+        self.split_payload()
+    }
 
-        records.push(TurnRecord {
+    /// Splits the record into its header and payload.
+    pub(crate) fn split_payload(self) -> (TurnHeader, P) {
+        let TurnRecord {
             turn_id,
             parent_id,
             depth,
@@ -190,118 +487,3658 @@ fn parse_turn_records(payload: &[u8]) -> Result<Vec<TurnRecord>> {
             encoding,
             compression,
             payload_hash,
-            payload: payload_bytes,
-        });
+            payload_size,
+            payload_omitted,
+            payload,
+            writer_id,
+            writer_seq,
+            expires_at_unix_ms,
+            expired,
+            created_at_unix_ms,
+            redacted,
+            redacted_at_unix_ms,
+            redaction_reason,
+            links,
+        } = self;
+        let meta = TurnRecord {
+            turn_id,
+            parent_id,
+            depth,
+            type_id,
+            type_version,
+            encoding,
+            compression,
+            payload_hash,
+            payload_size,
+            payload_omitted,
+            payload: (),
+            writer_id,
+            writer_seq,
+            expires_at_unix_ms,
+            expired,
+            created_at_unix_ms,
+            redacted,
+            redacted_at_unix_ms,
+            redaction_reason,
+            links,
+        };
+        (meta, payload)
     }
+}
 
-    Ok(records)
+impl<P> TurnRecord<P> {
+    /// The payload's BLAKE3 hash, hex encoded.
+    pub fn hash_hex(&self) -> String {
+        hash_to_hex(&self.payload_hash)
+    }
+
+    /// The turn's one-line [`Display`](fmt::Display) form, e.g.
+    /// `turn 42 depth 7 chat.Message v2 af1349b9`.
+    pub fn summary(&self) -> String {
+        self.to_string()
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::test_util::{decode_hex, load_fixture};
+/// Renders `turn_id`, `depth`, `type_id v<type_version>` and the first four
+/// bytes of the payload hash on one line. The alternate form (`{:#}`) adds
+/// the payload size and any redacted or expired marker:
+/// `turn 42 depth 7 chat.Message v2 af1349b9 (512 bytes, redacted)`.
+impl<P> fmt::Display for TurnRecord<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "turn {} depth {} {} v{} ",
+            self.turn_id, self.depth, self.type_id, self.type_version
+        )?;
+        for byte in &self.payload_hash[..4] {
+            write!(f, "{byte:02x}")?;
+        }
+        if f.alternate() {
+            write!(f, " ({} bytes", self.payload_size)?;
+            if self.redacted {
+                f.write_str(", redacted")?;
+            }
+            if self.expired {
+                f.write_str(", expired")?;
+            }
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
 
-    fn build_append_payload(req: &AppendRequest) -> Vec<u8> {
-        let encoding = if req.encoding == 0 {
-            ENCODING_MSGPACK
-        } else {
-            req.encoding
-        };
-        let hash = blake3::hash(&req.payload);
-        let mut payload = Vec::new();
-        payload.write_u64::<LittleEndian>(req.context_id).unwrap();
-        payload
-            .write_u64::<LittleEndian>(req.parent_turn_id)
-            .unwrap();
-        payload
-            .write_u32::<LittleEndian>(req.type_id.len() as u32)
-            .unwrap();
-        payload.extend_from_slice(req.type_id.as_bytes());
-        payload.write_u32::<LittleEndian>(req.type_version).unwrap();
-        payload.write_u32::<LittleEndian>(encoding).unwrap();
-        payload.write_u32::<LittleEndian>(req.compression).unwrap();
-        payload
-            .write_u32::<LittleEndian>(req.payload.len() as u32)
-            .unwrap();
-        payload.extend_from_slice(hash.as_bytes());
-        payload
-            .write_u32::<LittleEndian>(req.payload.len() as u32)
-            .unwrap();
-        payload.extend_from_slice(&req.payload);
-        payload
-            .write_u32::<LittleEndian>(req.idempotency_key.len() as u32)
-            .unwrap();
-        if !req.idempotency_key.is_empty() {
-            payload.extend_from_slice(&req.idempotency_key);
+impl<P: AsRef<[u8]>> TurnRecord<P> {
+    /// Decodes the payload into `T` with the codec recorded in `encoding`:
+    /// msgpack, or CBOR with the `cbor` feature.
+    ///
+    /// Returns [`Error::Decode`] if the payload was omitted, is compressed,
+    /// or does not match `T`, [`Error::UnsupportedEncoding`] if this build
+    /// cannot decode its encoding, and [`Error::NoDecryptionKey`] if it is
+    /// still encrypted (see [`crate::encryption`]).
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        self.decode_with_max_depth(MAX_DECODE_DEPTH)
+    }
+
+    pub(crate) fn decode_with_max_depth<T: DeserializeOwned>(&self, max_depth: usize) -> Result<T> {
+        if self.redacted {
+            return Err(Error::Redacted {
+                turn_id: self.turn_id,
+            });
+        }
+        if self.payload_omitted {
+            return Err(Error::decode(format!(
+                "payload omitted ({} bytes); fetch it with get_blob",
+                self.payload_size
+            )));
+        }
+        if self.encoding == ENCODING_ENCRYPTED {
+            let envelope = parse_envelope(self.payload.as_ref())?;
+            return Err(Error::NoDecryptionKey {
+                key_id: envelope.key_id.to_string(),
+            });
+        }
+        if self.compression != COMPRESSION_NONE {
+            return Err(Error::decode(format!(
+                "unsupported payload compression {}",
+                self.compression
+            )));
+        }
+        match self.encoding {
+            ENCODING_MSGPACK => {
+                decode_msgpack_into_with_max_depth(self.payload.as_ref(), max_depth)
+            }
+            #[cfg(feature = "cbor")]
+            crate::protocol::ENCODING_CBOR => {
+                crate::encoding::decode_cbor_with_max_depth(self.payload.as_ref(), max_depth)
+            }
+            other => Err(Error::UnsupportedEncoding(other)),
         }
-        payload
     }
+}
 
-    #[test]
-    fn append_payloads_match_fixtures() {
-        let fixture = load_fixture("append_parent0");
-        assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
-        assert_eq!(fixture.flags, 0);
-        let req = AppendRequest {
-            context_id: 1,
-            parent_turn_id: 0,
-            type_id: "cxdb.ConversationItem".into(),
-            type_version: 3,
-            payload: vec![0x91, 0x01],
-            idempotency_key: Vec::new(),
-            encoding: ENCODING_MSGPACK,
-            compression: 0,
-        };
-        assert_eq!(decode_hex(&fixture.payload_hex), build_append_payload(&req));
+/// A fetched turn whose payload stays raw until [`LazyTurn::get`] is called.
+///
+/// Fetching never decodes, so a malformed payload surfaces as an error from
+/// `get`, not from the read that returned it. Successful decodes are cached
+/// per target type; failed ones are retried on the next call. Record fields
+/// are reachable through `Deref`.
+///
+/// ```no_run
+/// # use cxdb::{dial, ContextId, GetLastOptions, RequestContext};
+/// # use cxdb::types::ConversationItem;
+/// # let client = dial("127.0.0.1:9009", Vec::new())?;
+/// # let ctx = RequestContext::background();
+/// let opts = GetLastOptions { include_payload: true, ..Default::default() };
+/// let turns: Vec<_> = client
+///     .get_last(&ctx, ContextId::new(1), opts)?
+///     .into_iter()
+///     .map(|turn| turn.into_lazy())
+///     .collect();
+/// let item = turns[0].get::<ConversationItem>()?; // decoded here, once
+/// # Ok::<(), cxdb::Error>(())
+/// ```
+pub struct LazyTurn<P = Vec<u8>> {
+    record: TurnRecord<P>,
+    max_decode_depth: usize,
+    decoded: Mutex<Vec<Arc<dyn Any + Send + Sync>>>,
+}
 
-        let fixture = load_fixture("append_parent7");
-        assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
-        assert_eq!(fixture.flags, 0);
-        let req = AppendRequest {
-            context_id: 1,
-            parent_turn_id: 7,
-            type_id: "cxdb.ConversationItem".into(),
-            type_version: 3,
-            payload: vec![0x91, 0x02],
-            idempotency_key: Vec::new(),
-            encoding: ENCODING_MSGPACK,
-            compression: 0,
-        };
-        assert_eq!(decode_hex(&fixture.payload_hex), build_append_payload(&req));
+impl<P> LazyTurn<P> {
+    pub fn new(record: TurnRecord<P>) -> Self {
+        Self {
+            record,
+            max_decode_depth: MAX_DECODE_DEPTH,
+            decoded: Mutex::new(Vec::new()),
+        }
+    }
 
-        let fixture = load_fixture("append_idempotent");
-        assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
-        assert_eq!(fixture.flags, 0);
-        let req = AppendRequest {
-            context_id: 1,
-            parent_turn_id: 0,
-            type_id: "cxdb.ConversationItem".into(),
-            type_version: 3,
-            payload: vec![0x91, 0x03],
-            idempotency_key: b"idem-1".to_vec(),
-            encoding: ENCODING_MSGPACK,
-            compression: 0,
-        };
-        assert_eq!(decode_hex(&fixture.payload_hex), build_append_payload(&req));
+    /// Overrides the msgpack nesting limit applied by [`LazyTurn::get`].
+    pub fn with_max_decode_depth(mut self, depth: usize) -> Self {
+        self.max_decode_depth = depth;
+        self
     }
 
-    #[test]
-    fn get_last_payloads_match_fixtures() {
-        let fixture = load_fixture("get_last_default");
-        assert_eq!(fixture.msg_type, MSG_GET_LAST);
-        let mut payload = Vec::new();
-        payload.write_u64::<LittleEndian>(1).unwrap();
-        payload.write_u32::<LittleEndian>(10).unwrap();
-        payload.write_u32::<LittleEndian>(0).unwrap();
-        assert_eq!(decode_hex(&fixture.payload_hex), payload);
+    pub fn record(&self) -> &TurnRecord<P> {
+        &self.record
+    }
 
-        let fixture = load_fixture("get_last_payload");
-        assert_eq!(fixture.msg_type, MSG_GET_LAST);
-        let mut payload = Vec::new();
-        payload.write_u64::<LittleEndian>(1).unwrap();
+    pub fn into_record(self) -> TurnRecord<P> {
+        self.record
+    }
+
+    /// Reports whether the payload has already been decoded as `T`.
+    pub fn is_decoded<T: Any>(&self) -> bool {
+        self.decoded
+            .lock()
+            .map(|cache| cache.iter().any(|value| value.is::<T>()))
+            .unwrap_or(false)
+    }
+}
+
+impl<P: AsRef<[u8]>> LazyTurn<P> {
+    /// Raw payload bytes as fetched.
+    pub fn raw(&self) -> &[u8] {
+        self.record.payload.as_ref()
+    }
+
+    /// Decodes the payload as `T`, or returns the cached value from an
+    /// earlier successful call.
+    ///
+    /// Returns [`Error::Decode`] if the payload is not msgpack, is
+    /// compressed, or does not match `T`.
+    pub fn get<T>(&self) -> Result<Arc<T>>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        if let Some(value) = self.cached::<T>() {
+            return Ok(value);
+        }
+        let value: Arc<T> = Arc::new(self.record.decode_with_max_depth(self.max_decode_depth)?);
+        if let Ok(mut cache) = self.decoded.lock() {
+            cache.push(value.clone());
+        }
+        Ok(value)
+    }
+
+    fn cached<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        let cache = self.decoded.lock().ok()?;
+        cache
+            .iter()
+            .find_map(|value| value.clone().downcast::<T>().ok())
+    }
+}
+
+impl<P> std::ops::Deref for LazyTurn<P> {
+    type Target = TurnRecord<P>;
+
+    fn deref(&self) -> &TurnRecord<P> {
+        &self.record
+    }
+}
+
+impl<P> From<TurnRecord<P>> for LazyTurn<P> {
+    fn from(record: TurnRecord<P>) -> Self {
+        Self::new(record)
+    }
+}
+
+impl<P: std::fmt::Debug> std::fmt::Debug for LazyTurn<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let decoded = self.decoded.lock().map(|cache| cache.len()).unwrap_or(0);
+        f.debug_struct("LazyTurn")
+            .field("record", &self.record)
+            .field("decoded", &decoded)
+            .finish()
+    }
+}
+
+/// Opaque read-your-writes token returned by an append.
+///
+/// Wraps the commit sequence the server assigned to the write. Passing it to
+/// [`GetLastOptions::min_sequence`] makes the read wait until the serving
+/// node has applied at least that sequence. Servers that do not report a
+/// sequence (single-node deployments) yield the zero token, which is a no-op.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConsistencyToken(u64);
+
+impl ConsistencyToken {
+    pub const fn from_sequence(sequence: u64) -> Self {
+        Self(sequence)
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.0
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// The turns [`Client::get_last_page`] read.
+#[derive(Debug, Clone, Default)]
+pub struct TurnPage {
+    pub turns: Vec<TurnRecord>,
+    /// [`GetLastOptions::byte_budget`] left out older turns that `limit`
+    /// would have allowed.
+    pub truncated: bool,
+    /// When `truncated`, the [`GetLastOptions::before_turn_id`] that
+    /// continues with the turns left out.
+    pub next_before_turn_id: Option<TurnId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppendResult {
+    pub context_id: ContextId,
+    pub turn_id: TurnId,
+    pub depth: u32,
+    pub payload_hash: [u8; 32],
+    pub consistency_token: ConsistencyToken,
+    /// When the server accepted the turn. The fields from here on are
+    /// `None` from servers that do not send append metadata.
+    pub created_at_unix_ms: std::option::Option<u64>,
+    /// Payload bytes as the server stored them, after compression.
+    pub stored_len: std::option::Option<u32>,
+    /// The context head after the append, for updating a cached header
+    /// without reading it back.
+    pub head: std::option::Option<ContextHead>,
+}
+
+/// The order [`Client::get_last`] returns turns in. Either way turns are
+/// sorted by `turn_id`, which grows along a context's history, so ties
+/// cannot occur and pages line up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Chronological, as the server sends them. The default.
+    #[default]
+    OldestFirst,
+    NewestFirst,
+}
+
+/// The fields of a [`TurnRecord`] a read returns, for
+/// [`GetLastOptions::projection`]. Combine with `|`, e.g.
+/// `TurnFields::CONTENT_HASH | TurnFields::DEPTH`.
+///
+/// `turn_id` and the read-time markers (writer stamp, expiry, timestamps,
+/// redaction) always come back, and the payload follows `include_payload`.
+/// A field left out is not an error to read: it holds its zero or empty
+/// default (`0`, `""`, an all-zero hash). Reads with payloads always return
+/// `encoding` and `compression`, which the payload cannot be read without.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TurnFields(u32);
+
+impl TurnFields {
+    /// Only `turn_id` (and the payload, if requested).
+    pub const NONE: TurnFields = TurnFields(0);
+    /// `parent_id`.
+    pub const PARENT: TurnFields = TurnFields(TURN_FIELD_PARENT);
+    /// `depth`.
+    pub const DEPTH: TurnFields = TurnFields(TURN_FIELD_DEPTH);
+    /// `type_id` and `type_version`.
+    pub const TYPE: TurnFields = TurnFields(TURN_FIELD_TYPE);
+    /// `encoding` and `compression`.
+    pub const ENCODING: TurnFields = TurnFields(TURN_FIELD_ENCODING);
+    /// `payload_size`.
+    pub const SIZE: TurnFields = TurnFields(TURN_FIELD_SIZE);
+    /// `payload_hash`.
+    pub const CONTENT_HASH: TurnFields = TurnFields(TURN_FIELD_CONTENT_HASH);
+    /// Every field; the default.
+    pub const ALL: TurnFields = TurnFields(
+        TURN_FIELD_PARENT
+            | TURN_FIELD_DEPTH
+            | TURN_FIELD_TYPE
+            | TURN_FIELD_ENCODING
+            | TURN_FIELD_SIZE
+            | TURN_FIELD_CONTENT_HASH,
+    );
+
+    /// Whether every field of `other` is in `self`.
+    pub fn contains(self, other: TurnFields) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The `TURN_FIELD_*` bits sent on the wire.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for TurnFields {
+    fn default() -> Self {
+        TurnFields::ALL
+    }
+}
+
+impl std::ops::BitOr for TurnFields {
+    type Output = TurnFields;
+
+    fn bitor(self, rhs: TurnFields) -> TurnFields {
+        TurnFields(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for TurnFields {
+    fn bitor_assign(&mut self, rhs: TurnFields) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Options for [`Client::get_last`]. Start from [`GetLastOptions::default`]
+/// (the newest 10 turns, without payloads) and override what differs. Every
+/// field has a chained setter, so this is also the type's builder:
+///
+/// ```
+/// # use cxdb::{GetLastOptions, Order};
+/// let opts = GetLastOptions::default()
+///     .limit(50)
+///     .include_payload(true)
+///     .type_filter("com.example.Message")
+///     .order(Order::NewestFirst);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GetLastOptions {
+    /// Most turns to return; 10 by default. 0 returns no turns and sends
+    /// no request.
+    pub limit: u32,
+    pub include_payload: bool,
+    /// Minimum commit sequence the serving node must have applied.
+    pub min_sequence: ConsistencyToken,
+    /// Read the response into the client's recycled buffer instead of a
+    /// fresh allocation. Only used by [`Client::get_last_shared`].
+    #[cfg(feature = "bytes")]
+    pub reuse_buffer: bool,
+    /// Return payloads only up to this many bytes; larger turns come back
+    /// with `payload_omitted` set. Sent to the server as a hint so the bytes
+    /// stay off the wire; servers that ignore it return everything and the
+    /// client drops oversized payloads itself.
+    pub max_payload_bytes: Option<u32>,
+    /// Read past compaction summaries into the raw history (see
+    /// [`Client::compact_context`]).
+    pub include_compacted: bool,
+    /// Return expired turns, with [`TurnRecord::expired`] set, instead of
+    /// skipping them.
+    pub include_expired: bool,
+    /// Order of the returned turns; oldest first by default.
+    pub order: Order,
+    /// Page back through history: return the newest `limit` turns older
+    /// than this one instead of the newest overall. Pass the smallest
+    /// `turn_id` of the previous page to get the next one.
+    pub before_turn_id: Option<TurnId>,
+    /// Return only turns at this depth or deeper.
+    pub min_depth: Option<u32>,
+    /// Return only turns at this depth or shallower, e.g. `Some(0)` for the
+    /// root alone. Like `min_depth`, `limit` counts only the turns in range.
+    /// Servers that cannot filter by depth are paged back through until
+    /// `limit` turns in range are found.
+    pub max_depth: Option<u32>,
+    /// Fields to return; the rest are left at their defaults (see
+    /// [`TurnFields`]). Servers that cannot project send every field and
+    /// the client clears the others.
+    pub projection: TurnFields,
+    /// Return only turns of this declared type; `limit` counts only those.
+    /// No server filters by type, so the client pages back through history
+    /// until `limit` turns match.
+    pub type_filter: Option<TypeId>,
+    /// Return delta-encoded turns as stored, with
+    /// [`ENCODING_DELTA`](crate::protocol::ENCODING_DELTA), instead of
+    /// materializing their payloads (see [`crate::delta`]).
+    pub include_raw_delta: bool,
+    /// Stop adding older turns once their payloads would exceed this many
+    /// bytes in total, so `limit` need not be guessed from turn sizes.
+    /// Each turn counts its stored payload size (`payload_size`), or 0 if
+    /// `max_payload_bytes` leaves it out. The newest turn is returned even
+    /// if it alone is over budget, so paging always makes progress. See
+    /// [`Client::get_last_page`] for whether the budget cut a read short.
+    pub byte_budget: Option<u64>,
+    /// If the context is archived, restore it (see
+    /// [`Client::unarchive_context`]) and read again instead of failing with
+    /// [`Error::ContextArchived`]. Not applied by [`Client::get_last_many`].
+    pub auto_restore: bool,
+}
+
+impl Default for GetLastOptions {
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            include_payload: false,
+            min_sequence: ConsistencyToken::default(),
+            #[cfg(feature = "bytes")]
+            reuse_buffer: false,
+            max_payload_bytes: None,
+            include_compacted: false,
+            include_expired: false,
+            order: Order::OldestFirst,
+            before_turn_id: None,
+            min_depth: None,
+            max_depth: None,
+            projection: TurnFields::ALL,
+            type_filter: None,
+            include_raw_delta: false,
+            byte_budget: None,
+            auto_restore: false,
+        }
+    }
+}
+
+impl GetLastOptions {
+    /// Returns at most `limit` turns. 0 returns none, without a request,
+    /// so a computed limit that comes out 0 never reads a whole context.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Fetches payloads along with the metadata.
+    pub fn include_payload(mut self, include: bool) -> Self {
+        self.include_payload = include;
+        self
+    }
+
+    /// Returns only turns declared as `type_id` (see
+    /// [`GetLastOptions::type_filter`]).
+    pub fn type_filter(mut self, type_id: impl Into<TypeId>) -> Self {
+        self.type_filter = Some(type_id.into());
+        self
+    }
+
+    /// Waits (up to the request deadline) for the write behind `token` to be visible.
+    pub fn min_sequence(mut self, token: ConsistencyToken) -> Self {
+        self.min_sequence = token;
+        self
+    }
+
+    /// Omits payloads larger than `n` bytes (see [`TurnRecord::payload_omitted`]).
+    pub fn max_payload_bytes(mut self, n: u32) -> Self {
+        self.max_payload_bytes = Some(n);
+        self
+    }
+
+    /// Returns turns hidden by compaction too, e.g. for audits.
+    pub fn include_compacted(mut self, include: bool) -> Self {
+        self.include_compacted = include;
+        self
+    }
+
+    /// Returns expired turns too, marked with [`TurnRecord::expired`].
+    pub fn include_expired(mut self, include: bool) -> Self {
+        self.include_expired = include;
+        self
+    }
+
+    /// Returns delta-encoded turns as stored (see
+    /// [`GetLastOptions::include_raw_delta`]).
+    pub fn include_raw_delta(mut self, include: bool) -> Self {
+        self.include_raw_delta = include;
+        self
+    }
+
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Caps the payload bytes returned (see
+    /// [`GetLastOptions::byte_budget`]).
+    pub fn byte_budget(mut self, bytes: u64) -> Self {
+        self.byte_budget = Some(bytes);
+        self
+    }
+
+    /// Restores an archived context on read (see
+    /// [`GetLastOptions::auto_restore`]).
+    pub fn auto_restore(mut self, restore: bool) -> Self {
+        self.auto_restore = restore;
+        self
+    }
+
+    /// Returns the page of turns older than `turn_id` (see
+    /// [`GetLastOptions::before_turn_id`]).
+    pub fn before(mut self, turn_id: TurnId) -> Self {
+        self.before_turn_id = Some(turn_id);
+        self
+    }
+
+    /// Returns only turns at `depth` or deeper.
+    pub fn min_depth(mut self, depth: u32) -> Self {
+        self.min_depth = Some(depth);
+        self
+    }
+
+    /// Returns only turns at `depth` or shallower.
+    pub fn max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Returns only `fields` of each turn, e.g.
+    /// `TurnFields::CONTENT_HASH` for a hash-reconciliation job.
+    pub fn projection(mut self, fields: TurnFields) -> Self {
+        self.projection = fields;
+        self
+    }
+
+    /// The fields the records must carry: the projection, plus the
+    /// encoding when payloads are read.
+    pub(crate) fn fields(&self) -> TurnFields {
+        if self.include_payload {
+            self.projection | TurnFields::ENCODING
+        } else {
+            self.projection
+        }
+    }
+
+    /// The depths requested, if either bound is set.
+    pub(crate) fn depth_range(&self) -> Option<RangeInclusive<u32>> {
+        if self.min_depth.is_none() && self.max_depth.is_none() {
+            return None;
+        }
+        Some(self.min_depth.unwrap_or(0)..=self.max_depth.unwrap_or(u32::MAX))
+    }
+
+    /// Recycles the client's read buffer across [`Client::get_last_shared`] calls.
+    #[cfg(feature = "bytes")]
+    pub fn reuse_buffer(mut self, reuse: bool) -> Self {
+        self.reuse_buffer = reuse;
+        self
+    }
+}
+
+/// Options for [`Client::get_turn_at_depth`] and [`Client::get_range`].
+#[derive(Debug, Clone, Copy)]
+pub struct GetTurnOptions {
+    /// Fetch the payload; on by default.
+    pub include_payload: bool,
+    /// Return the payload only up to this many bytes, as with
+    /// [`GetLastOptions::max_payload_bytes`].
+    pub max_payload_bytes: Option<u32>,
+    /// Minimum commit sequence the serving node must have applied.
+    pub min_sequence: ConsistencyToken,
+    /// Fields to return, as with [`GetLastOptions::projection`].
+    pub projection: TurnFields,
+}
+
+impl Default for GetTurnOptions {
+    fn default() -> Self {
+        Self {
+            include_payload: true,
+            max_payload_bytes: None,
+            min_sequence: ConsistencyToken::default(),
+            projection: TurnFields::ALL,
+        }
+    }
+}
+
+impl GetTurnOptions {
+    pub fn include_payload(mut self, include: bool) -> Self {
+        self.include_payload = include;
+        self
+    }
+
+    /// Omits a payload larger than `n` bytes (see [`TurnRecord::payload_omitted`]).
+    pub fn max_payload_bytes(mut self, n: u32) -> Self {
+        self.max_payload_bytes = Some(n);
+        self
+    }
+
+    /// Waits (up to the request deadline) for the write behind `token` to be visible.
+    pub fn min_sequence(mut self, token: ConsistencyToken) -> Self {
+        self.min_sequence = token;
+        self
+    }
+
+    /// Returns only `fields` (see [`GetLastOptions::projection`]).
+    pub fn projection(mut self, fields: TurnFields) -> Self {
+        self.projection = fields;
+        self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    pub fn append_turn(&self, ctx: &RequestContext, req: &AppendRequest) -> Result<AppendResult> {
+        let _timing = ctx.timing_scope();
+        let span = OpSpan::new(Op::AppendTurn, ctx);
+        span.context_id(req.context_id);
+        span.type_id(&req.type_id);
+        span.payload_bytes(req.payload.len());
+        span.run(|| {
+            self.validate_append(req)?;
+            let req = &*self.delta_append(ctx, req)?;
+            let req = &*self.seal_append(req)?;
+            let mut payload = Vec::with_capacity(128 + req.payload.len());
+            let flags = encode_append_request(&mut payload, req, None)?;
+
+            let response = self.send_request_with_flags(ctx, MSG_APPEND_TURN, flags, &payload);
+            self.prefetch_cache().invalidate(req.context_id);
+            let frame =
+                response.map_err(|err| err.resolve_not_found().resolve_writer_conflict())?;
+            parse_append_result(&frame.payload)
+        })
+    }
+
+    /// Appends `req` unless its context already holds a live turn with the
+    /// same payload, and says whether it appended.
+    ///
+    /// When the content is already there nothing changes: the head stays
+    /// where it is, `req.parent_turn_id`, writer stamp and TTL are ignored,
+    /// and the result describes the newest matching turn, which need not be
+    /// the head. Only the payload hash is compared (see
+    /// [`Client::get_turn_by_hash`] for which turns count).
+    ///
+    /// Servers that offer conditional appends at handshake check and append
+    /// in one round trip, atomically. Against other servers the history is
+    /// read first, so a concurrent writer can slip the same content in
+    /// between the check and the append; and the result of a match carries
+    /// no [`AppendResult::created_at_unix_ms`], `stored_len` or `head`.
+    pub fn append_dedup(
+        &self,
+        ctx: &RequestContext,
+        req: &AppendRequest,
+    ) -> Result<(AppendResult, bool)> {
+        self.validate_append(req)?;
+        let mut req = Cow::Borrowed(req);
+        if req.delta_base.is_some() {
+            req.to_mut().delta_base = None;
+        }
+        let req = &*req;
+        if !self.server_dedup() {
+            let hash = blake3::hash(&req.payload);
+            if let Some(turn) = self.get_turn_by_hash(ctx, req.context_id, hash.as_bytes())? {
+                let existing = AppendResult {
+                    context_id: req.context_id,
+                    turn_id: turn.turn_id,
+                    depth: turn.depth,
+                    payload_hash: turn.payload_hash,
+                    consistency_token: ConsistencyToken::default(),
+                    created_at_unix_ms: None,
+                    stored_len: None,
+                    head: None,
+                };
+                return Ok((existing, false));
+            }
+            return Ok((self.append_turn(ctx, req)?, true));
+        }
+
+        let _timing = ctx.timing_scope();
+
+        let span = OpSpan::new(Op::AppendTurn, ctx);
+        span.context_id(req.context_id);
+        span.type_id(&req.type_id);
+        span.payload_bytes(req.payload.len());
+        span.run(|| {
+            let req = &*self.seal_append(req)?;
+            let mut payload = Vec::with_capacity(128 + req.payload.len());
+            let flags = encode_append_request(&mut payload, req, None)? | APPEND_FLAG_DEDUP;
+
+            let response = self.send_request_with_flags(ctx, MSG_APPEND_TURN, flags, &payload);
+            self.prefetch_cache().invalidate(req.context_id);
+            let frame =
+                response.map_err(|err| err.resolve_not_found().resolve_writer_conflict())?;
+            let Some((&appended, ack)) = frame.payload.split_last() else {
+                return Err(Error::protocol("empty dedup append response"));
+            };
+            Ok((parse_append_result(ack)?, appended != 0))
+        })
+    }
+
+    /// The newest turn of `context_id` whose payload hashes to `hash`, read
+    /// through the whole history with compacted turns included. Expired
+    /// turns never match. Payloads are not fetched.
+    pub fn get_turn_by_hash(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        hash: &[u8; 32],
+    ) -> Result<Option<TurnRecord>> {
+        let mut before_turn_id = None;
+        loop {
+            let opts = GetLastOptions {
+                limit: HASH_LOOKUP_PAGE_SIZE,
+                include_payload: true,
+                before_turn_id,
+                ..Default::default()
+            }
+            .include_compacted(true)
+            .max_payload_bytes(0);
+            let page = self.get_last(ctx, context_id, opts)?;
+            if let Some(turn) = page.iter().rev().find(|turn| turn.payload_hash == *hash) {
+                return Ok(Some(turn.clone()));
+            }
+            if page.len() < HASH_LOOKUP_PAGE_SIZE as usize {
+                return Ok(None);
+            }
+            before_turn_id = page.first().map(|turn| turn.turn_id);
+        }
+    }
+
+    /// [`Client::get_turn_by_hash`] with the hash in hex, as logged or shown
+    /// in the UI. Fails with [`Error::Decode`] unless `hash` is 64 hex
+    /// digits.
+    pub fn get_turn_by_hash_hex(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        hash: &str,
+    ) -> Result<Option<TurnRecord>> {
+        self.get_turn_by_hash(ctx, context_id, &hash32_from_hex(hash)?)
+    }
+
+    /// Appends `req`'s summary to `context_id` and marks history up to
+    /// `req.up_to_turn_id` as compacted: default reads end at the summary
+    /// instead of walking the whole context. Compacted turns are kept;
+    /// [`GetLastOptions::include_compacted`] reads through the summary and
+    /// [`Client::get_turn`] fetches any of them by id.
+    pub fn compact_context(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        req: CompactRequest,
+    ) -> Result<AppendResult> {
+        let summary = AppendRequest::new(
+            context_id,
+            req.summary_type_id,
+            req.summary_type_version,
+            req.summary_payload,
+        );
+        self.validate_append(&summary)?;
+        let summary = self.seal_append(&summary)?;
+        let mut payload = Vec::with_capacity(136 + summary.payload.len());
+        payload.write_u64::<LittleEndian>(req.up_to_turn_id.get())?;
+        encode_append_request(&mut payload, &summary, None)?;
+
+        let response = self.send_request(ctx, MSG_CTX_COMPACT, &payload);
+        self.prefetch_cache().invalidate(context_id);
+        let frame = response.map_err(|err| err.resolve_not_found())?;
+        parse_append_result(&frame.payload)
+    }
+
+    /// Fetches one turn by id, payload included, whether or not it has been
+    /// compacted. Answered from the turn cache when one is installed (see
+    /// [`crate::cache`]).
+    pub fn get_turn(&self, ctx: &RequestContext, turn_id: TurnId) -> Result<TurnRecord> {
+        let mut record = self.get_turn_stored(ctx, turn_id)?;
+        self.open_payloads(ctx, std::slice::from_mut(&mut record))?;
+        Ok(record)
+    }
+
+    /// Like [`Client::get_turn`], with the payload as stored.
+    pub(crate) fn get_turn_stored(
+        &self,
+        ctx: &RequestContext,
+        turn_id: TurnId,
+    ) -> Result<TurnRecord> {
+        let cache = self.turn_cache();
+        let namespace = self.namespace_of(ctx);
+        if let Some(record) = cache.and_then(|cache| self.cached_turn(cache, namespace, turn_id)) {
+            return Ok(record);
+        }
+        let frame = self
+            .send_request(ctx, MSG_GET_TURN, &get_turn_request(turn_id)?)
+            .map_err(|err| err.resolve_not_found())?;
+        let record = parse_single_turn(&frame.payload)?;
+        if let Some(cache) = cache {
+            cache.insert(namespace, None, &record);
+        }
+        Ok(record)
+    }
+
+    /// Fetches the turn at `depth` on the current head's line of
+    /// `context_id`, whether or not it has been compacted or has expired,
+    /// without reading the turns around it.
+    ///
+    /// Depths follow the head's ancestry: after a fork, depths up to the
+    /// fork point resolve to the turns the context shares with its source,
+    /// and turns on other branches are never returned. A head moved by a
+    /// later append only adds depths, so a depth once resolved keeps
+    /// resolving to the same turn.
+    ///
+    /// Fails with [`Error::DepthOutOfRange`] when `depth` is past the head,
+    /// the context has no turns, or the turn at `depth` was pruned.
+    pub fn get_turn_at_depth(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        depth: u64,
+        opts: GetTurnOptions,
+    ) -> Result<TurnRecord> {
+        let found = match u32::try_from(depth) {
+            Ok(depth) => {
+                let last = GetLastOptions {
+                    limit: 1,
+                    include_payload: opts.include_payload,
+                    min_sequence: opts.min_sequence,
+                    max_payload_bytes: opts.max_payload_bytes,
+                    include_compacted: true,
+                    include_expired: true,
+                    projection: opts.projection,
+                    ..Default::default()
+                };
+                let last = last.min_depth(depth).max_depth(depth);
+                self.get_last(ctx, context_id, last)?.pop()
+            }
+            Err(_) => None,
+        };
+        match found {
+            Some(record) => Ok(record),
+            None => Err(Error::DepthOutOfRange {
+                context_id,
+                depth,
+                head_depth: self.get_head(ctx, context_id)?.head_depth.into(),
+            }),
+        }
+    }
+
+    /// Fetches the turns at depths `from_depth..=to_depth` of the current
+    /// head's line of `context_id`, oldest first, reading
+    /// [`RANGE_PAGE_SIZE`] depths per request. Compacted turns are included
+    /// and expired turns skipped, so the result can be shorter than the
+    /// range. Depths past the head are simply absent.
+    ///
+    /// Payloads are read through the turn cache when one is installed (see
+    /// [`crate::cache`]). Resumes a
+    /// [`Subscription`](crate::subscribe::Subscription) after a
+    /// [`Gap`](crate::subscribe::SubscriptionItem::Gap).
+    pub fn get_range(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        from_depth: u32,
+        to_depth: u32,
+        opts: GetTurnOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        let mut records = Vec::new();
+        let mut next = from_depth;
+        while next <= to_depth {
+            let last = next.saturating_add(RANGE_PAGE_SIZE - 1).min(to_depth);
+            let page = GetLastOptions {
+                limit: RANGE_PAGE_SIZE,
+                include_payload: opts.include_payload,
+                min_sequence: opts.min_sequence,
+                max_payload_bytes: opts.max_payload_bytes,
+                include_compacted: true,
+                projection: opts.projection,
+                ..Default::default()
+            };
+            records.extend(self.get_last(ctx, context_id, page.min_depth(next).max_depth(last))?);
+            match last.checked_add(1) {
+                Some(after) => next = after,
+                None => break,
+            }
+        }
+        Ok(records)
+    }
+
+    pub fn get_last(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        if opts.limit == 0 {
+            return Ok(Vec::new());
+        }
+        if opts.auto_restore {
+            let opts = GetLastOptions {
+                auto_restore: false,
+                ..opts
+            };
+            return self.restoring(ctx, context_id, || {
+                self.get_last(ctx, context_id, opts.clone())
+            });
+        }
+        if opts.byte_budget.is_some() {
+            return Ok(self.get_last_page(ctx, context_id, opts)?.turns);
+        }
+        let raw_delta = opts.include_raw_delta;
+        let mut records = self.get_last_stored(ctx, context_id, opts)?;
+        self.open_last(ctx, &mut records, raw_delta)?;
+        Ok(records)
+    }
+
+    /// Like [`Client::get_last`], also reporting whether
+    /// [`GetLastOptions::byte_budget`] stopped the read before `limit` turns,
+    /// and where to continue from if so.
+    ///
+    /// No server applies the budget itself, so the client lists the newest
+    /// `limit` turns without payloads first. It then fetches the ones that
+    /// fit in a second request, pinned to the listed turns so that appends
+    /// in between do not shift the page. Without a budget this is one
+    /// [`Client::get_last`].
+    ///
+    /// The budget always trims the oldest turns, whatever `opts.order`.
+    pub fn get_last_page(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<TurnPage> {
+        if opts.auto_restore && opts.limit > 0 {
+            let opts = GetLastOptions {
+                auto_restore: false,
+                ..opts
+            };
+            return self.restoring(ctx, context_id, || {
+                self.get_last_page(ctx, context_id, opts.clone())
+            });
+        }
+        if opts.limit == 0 || opts.byte_budget.is_none() {
+            let turns = self.get_last(ctx, context_id, opts)?;
+            return Ok(TurnPage {
+                turns,
+                truncated: false,
+                next_before_turn_id: None,
+            });
+        };
+        let listing = self.get_last_stored(ctx, context_id, budget_listing(&opts))?;
+        let Some(newest) = listing.first().map(|turn| turn.turn_id) else {
+            return Ok(TurnPage::default());
+        };
+        let fit = budget_fit(&listing, &opts);
+        let truncated = fit < listing.len();
+        let turns = self.get_last(ctx, context_id, budget_fetch(opts, newest, fit))?;
+        let next_before_turn_id = truncated
+            .then(|| turns.iter().map(|turn| turn.turn_id).min())
+            .flatten();
+        Ok(TurnPage {
+            turns,
+            truncated,
+            next_before_turn_id,
+        })
+    }
+
+    /// Like [`Client::get_last`] with `include_payload` off, for listings:
+    /// returns [`TurnMeta`] records, which have no payload to allocate or
+    /// forget to check. `opts.include_payload` and `max_payload_bytes` are
+    /// ignored.
+    ///
+    /// This used to return [`TurnHeader`]s; use [`Client::get_last`] for
+    /// the fields [`TurnMeta`] leaves out.
+    pub fn get_last_meta(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnMeta>> {
+        if opts.limit == 0 {
+            return Ok(Vec::new());
+        }
+        if opts.auto_restore {
+            let opts = GetLastOptions {
+                auto_restore: false,
+                ..opts
+            };
+            return self.restoring(ctx, context_id, || {
+                self.get_last_meta(ctx, context_id, opts.clone())
+            });
+        }
+        let opts = GetLastOptions {
+            include_payload: false,
+            max_payload_bytes: None,
+            ..opts
+        };
+        if self.pages_filters(&opts) {
+            let records = self.get_last_stored(ctx, context_id, opts)?;
+            return Ok(records.into_iter().map(TurnMeta::from).collect());
+        }
+        let _timing = ctx.timing_scope();
+        let span = OpSpan::new(Op::GetLast, ctx);
+        span.context_id(context_id);
+        span.run(|| {
+            let wire = self.wire_options(&opts);
+            let payload = self.get_last_request(ctx, context_id, &wire)?;
+            let frame = self
+                .send_request(ctx, MSG_GET_LAST, &payload)
+                .map_err(|err| err.resolve_not_found())?;
+            let mut records = parse_turn_records_with(&frame.payload, &wire, |_| ())?;
+            self.check_page(ctx, Operation::GetLast, &records, opts.fields())?;
+            finish_listing(&mut records, &opts)?;
+            Ok(records.into_iter().map(TurnMeta::from).collect())
+        })
+    }
+
+    /// Like [`Client::get_last`] with `include_payload` on, returning each
+    /// turn as its [`TurnMeta`] and payload.
+    pub fn get_last_full(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<Turn>> {
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..opts
+        };
+        let records = self.get_last(ctx, context_id, opts)?;
+        Ok(records.into_iter().map(Turn::from).collect())
+    }
+
+    /// Like [`Client::get_last`], but encrypted payloads are returned as
+    /// stored, for exports that keep the envelope (see
+    /// [`crate::encryption`]).
+    pub(crate) fn get_last_stored(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<TurnRecord>> {
+        if opts.limit == 0 {
+            return Ok(Vec::new());
+        }
+        if self.pages_filters(&opts) {
+            return page_filtered(&opts, |page| self.get_last_stored(ctx, context_id, page));
+        }
+        let _timing = ctx.timing_scope();
+        let span = OpSpan::new(Op::GetLast, ctx);
+        span.context_id(context_id);
+        span.run(|| {
+            let mut records = match self.prefetched_last(ctx, context_id, &opts)? {
+                Some(records) => records,
+                None => match self.turn_cache() {
+                    Some(cache) if opts.include_payload => {
+                        self.get_last_cached(ctx, context_id, &opts, cache)?
+                    }
+                    _ => {
+                        let wire = self.wire_options(&opts);
+                        let payload = self.get_last_request(ctx, context_id, &wire)?;
+                        let frame = self
+                            .send_request(ctx, MSG_GET_LAST, &payload)
+                            .map_err(|err| err.resolve_not_found())?;
+                        parse_get_last(&frame.payload, &wire)?
+                    }
+                },
+            };
+            self.check_page(ctx, Operation::GetLast, &records, opts.fields())?;
+            finish_records(&mut records, &opts)?;
+            span.payload_bytes(records.iter().map(|r| r.payload.len()).sum());
+            Ok(records)
+        })
+    }
+
+    /// Like [`Client::get_last`], but fills `records` in place instead of
+    /// returning a new Vec, reusing the type id and payload allocations of
+    /// the records already there, and reads the response into a buffer the
+    /// client recycles across calls. For tight read loops that keep one
+    /// `records` Vec around this avoids nearly all per-call allocation.
+    ///
+    /// On error `records` may hold a mix of old and new entries.
+    pub fn get_last_into(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+        records: &mut Vec<TurnRecord>,
+    ) -> Result<()> {
+        if opts.limit == 0 {
+            records.clear();
+            return Ok(());
+        }
+        if opts.auto_restore {
+            let opts = GetLastOptions {
+                auto_restore: false,
+                ..opts
+            };
+            return self.restoring(ctx, context_id, || {
+                self.get_last_into(ctx, context_id, opts.clone(), records)
+            });
+        }
+        if self.pages_filters(&opts) {
+            *records = self.get_last(ctx, context_id, opts)?;
+            return Ok(());
+        }
+        match self.prefetched_last(ctx, context_id, &opts)? {
+            Some(prefetched) => *records = prefetched,
+            None => {
+                let wire = self.wire_options(&opts);
+                let payload = self.get_last_request(ctx, context_id, &wire)?;
+                self.send_request_reusing(ctx, MSG_GET_LAST, &payload, |response| {
+                    parse_turn_records_into(response, &wire, records)
+                })
+                .map_err(|err| err.resolve_not_found())?;
+            }
+        }
+        self.check_page(ctx, Operation::GetLast, records, opts.fields())?;
+        finish_records(records, &opts)?;
+        self.open_last(ctx, records, opts.include_raw_delta)
+    }
+
+    /// Runs [`Client::get_last`] for each `(context_id, opts)` pair as one
+    /// pipelined batch: every request is written before any response is
+    /// awaited, so N independent reads cost roughly one round trip.
+    ///
+    /// Results are returned in request order and fail independently. The
+    /// outer error is reserved for failures before anything is sent (closed
+    /// client, cancelled context, elapsed deadline). Requests with a depth
+    /// range the server cannot filter are read after the batch, one
+    /// [`Client::get_last`] each.
+    pub fn get_last_many(
+        &self,
+        ctx: &RequestContext,
+        requests: &[(ContextId, GetLastOptions)],
+    ) -> Result<Vec<Result<Vec<TurnRecord>>>> {
+        let batched: Vec<_> = requests
+            .iter()
+            .filter(|(_, opts)| opts.limit > 0 && !self.pages_filters(opts))
+            .collect();
+        let payloads = batched
+            .iter()
+            .map(|(context_id, opts)| {
+                let wire = self.wire_options(opts);
+                Ok((
+                    MSG_GET_LAST,
+                    self.get_last_request(ctx, *context_id, &wire)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut responses = self.pipeline(ctx, &payloads)?.into_iter();
+        Ok(requests
+            .iter()
+            .map(|(context_id, opts)| {
+                if opts.limit == 0 {
+                    return Ok(Vec::new());
+                }
+                if self.pages_filters(opts) {
+                    return self.get_last(ctx, *context_id, opts.clone());
+                }
+                let response = responses.next().expect("one response per batched request");
+                let frame = response.map_err(|err| err.resolve_not_found())?;
+                let mut records = parse_get_last(&frame.payload, &self.wire_options(opts))?;
+                self.check_page(ctx, Operation::GetLast, &records, opts.fields())?;
+                finish_records(&mut records, opts)?;
+                self.open_last(ctx, &mut records, opts.include_raw_delta)?;
+                Ok(records)
+            })
+            .collect())
+    }
+
+    /// Like [`Client::get_last`], but returns [`LazyTurn`]s that decode on
+    /// first access using this client's `max_decode_depth`.
+    pub fn get_last_lazy(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<LazyTurn>> {
+        let depth = self.max_decode_depth();
+        Ok(self
+            .get_last(ctx, context_id, opts)?
+            .into_iter()
+            .map(|record| record.into_lazy().with_max_decode_depth(depth))
+            .collect())
+    }
+
+    /// Like [`Client::get_last`], but payloads are zero-copy slices of one
+    /// refcounted response buffer instead of one allocation per turn.
+    #[cfg(feature = "bytes")]
+    pub fn get_last_shared(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<SharedTurnRecord>> {
+        if opts.limit == 0 {
+            return Ok(Vec::new());
+        }
+        if opts.auto_restore {
+            let opts = GetLastOptions {
+                auto_restore: false,
+                ..opts
+            };
+            return self.restoring(ctx, context_id, || {
+                self.get_last_shared(ctx, context_id, opts.clone())
+            });
+        }
+        if self.pages_filters(&opts) {
+            return page_filtered(&opts, |page| self.get_last_shared(ctx, context_id, page));
+        }
+        let wire = self.wire_options(&opts);
+        let payload = self.get_last_request(ctx, context_id, &wire)?;
+        let response = self
+            .send_request_shared(ctx, MSG_GET_LAST, &payload, opts.reuse_buffer)
+            .map_err(|err| err.resolve_not_found())?;
+        let mut records =
+            parse_turn_records_with(&response, &wire, |slice| response.slice_ref(slice))?;
+        self.check_page(ctx, Operation::GetLast, &records, opts.fields())?;
+        finish_records(&mut records, &opts)?;
+        self.open_last(ctx, &mut records, opts.include_raw_delta)?;
+        Ok(records)
+    }
+
+    /// Lists the turns without payloads, fills payloads from `cache` and
+    /// fetches the rest with one pipelined batch of GET_TURN requests.
+    fn get_last_cached(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: &GetLastOptions,
+        cache: &TurnCache,
+    ) -> Result<Vec<TurnRecord>> {
+        let namespace = self.namespace_of(ctx);
+        let listing = GetLastOptions {
+            include_payload: false,
+            projection: TurnFields::ALL,
+            ..opts.clone()
+        };
+        let payload = self.get_last_request(ctx, context_id, &listing)?;
+        let frame = self
+            .send_request(ctx, MSG_GET_LAST, &payload)
+            .map_err(|err| err.resolve_not_found())?;
+        let mut records = parse_turn_listing(&frame.payload)?;
+
+        let mut missing = Vec::new();
+        for (index, record) in records.iter_mut().enumerate() {
+            // Listings describe the stored (possibly compressed) form.
+            record.compression = COMPRESSION_NONE;
+            if opts
+                .max_payload_bytes
+                .is_some_and(|max| record.payload_size > max)
+            {
+                record.payload_omitted = true;
+                continue;
+            }
+            if record.redacted {
+                // Another client redacted it; drop any payload cached before.
+                cache.remove(namespace, record.turn_id);
+                continue;
+            }
+            match self.cached_turn(cache, namespace, record.turn_id) {
+                Some(cached) => record.payload = cached.payload,
+                None => missing.push(index),
+            }
+        }
+        if missing.is_empty() {
+            return Ok(records);
+        }
+
+        let requests = missing
+            .iter()
+            .map(|&index| Ok((MSG_GET_TURN, get_turn_request(records[index].turn_id)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let responses = self.pipeline(ctx, &requests)?;
+        for (index, response) in missing.into_iter().zip(responses) {
+            let frame = response.map_err(|err| err.resolve_not_found())?;
+            let fetched = parse_single_turn(&frame.payload)?;
+            cache.insert(namespace, Some(context_id), &fetched);
+            records[index].payload = fetched.payload;
+        }
+        Ok(records)
+    }
+
+    /// Whether `opts` asks for a depth range the server cannot filter, or a
+    /// type, so the client pages for it (see [`FilterPager`]).
+    fn pages_filters(&self, opts: &GetLastOptions) -> bool {
+        opts.type_filter.is_some() || (opts.depth_range().is_some() && !self.server_depth_filter())
+    }
+
+    /// `opts` as sent to this server: without the projection if the server
+    /// cannot project, so that the client clears the fields itself.
+    fn wire_options(&self, opts: &GetLastOptions) -> GetLastOptions {
+        if self.server_projection() {
+            opts.clone()
+        } else {
+            GetLastOptions {
+                projection: TurnFields::ALL,
+                ..opts.clone()
+            }
+        }
+    }
+
+    /// Encodes the GET_LAST request for `wire` (see
+    /// [`Client::wire_options`]).
+    fn get_last_request(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        wire: &GetLastOptions,
+    ) -> Result<Vec<u8>> {
+        let wait = if wire.min_sequence.is_none() {
+            Duration::ZERO
+        } else {
+            let deadline = self.compute_deadline(ctx)?;
+            deadline.saturating_duration_since(Instant::now())
+        };
+        encode_get_last_request(context_id, wire, wait)
+    }
+}
+
+/// Encodes a GET_LAST request for `opts`. `wait` is how long the server may
+/// hold the request for `opts.min_sequence` to be applied.
+pub(crate) fn encode_get_last_request(
+    context_id: ContextId,
+    opts: &GetLastOptions,
+    wait: Duration,
+) -> Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(16);
+    payload.write_u64::<LittleEndian>(context_id.get())?;
+    payload.write_u32::<LittleEndian>(opts.limit)?;
+    payload.write_u32::<LittleEndian>(if opts.include_payload { 1 } else { 0 })?;
+    let mut flags = 0;
+    if opts.include_compacted {
+        flags |= GET_LAST_INCLUDE_COMPACTED;
+    }
+    if opts.include_expired {
+        flags |= GET_LAST_INCLUDE_EXPIRED;
+    }
+    if opts.before_turn_id.is_some() {
+        flags |= GET_LAST_BEFORE;
+    }
+    let depths = opts.depth_range();
+    if depths.is_some() {
+        flags |= GET_LAST_DEPTH_RANGE;
+    }
+    let fields = opts.fields();
+    if fields != TurnFields::ALL {
+        flags |= GET_LAST_PROJECTION;
+    }
+    if !opts.min_sequence.is_none() || opts.max_payload_bytes.is_some() || flags != 0 {
+        // Trailing read-your-writes fields; older servers ignore them.
+        payload.write_u64::<LittleEndian>(opts.min_sequence.sequence())?;
+        payload.write_u32::<LittleEndian>(wait.as_millis().min(u32::MAX as u128) as u32)?;
+    }
+    if opts.max_payload_bytes.is_some() || flags != 0 {
+        // Fields are positional, so flags need a (no-op) size limit.
+        payload.write_u32::<LittleEndian>(opts.max_payload_bytes.unwrap_or(u32::MAX))?;
+    }
+    if flags != 0 {
+        payload.write_u32::<LittleEndian>(flags)?;
+    }
+    if let Some(before_turn_id) = opts.before_turn_id {
+        payload.write_u64::<LittleEndian>(before_turn_id.get())?;
+    }
+    if let Some(depths) = depths {
+        payload.write_u32::<LittleEndian>(*depths.start())?;
+        payload.write_u32::<LittleEndian>(*depths.end())?;
+    }
+    if fields != TurnFields::ALL {
+        payload.write_u32::<LittleEndian>(fields.bits())?;
+    }
+    Ok(payload)
+}
+
+/// Appends the APPEND_TURN request body for `req` to `payload`, followed by
+/// the optional fields, and returns the frame flags announcing them.
+pub(crate) fn encode_append_request(
+    payload: &mut Vec<u8>,
+    req: &AppendRequest,
+    fs_root_hash: Option<[u8; 32]>,
+) -> Result<u16> {
+    let encoding = if req.encoding == 0 {
+        ENCODING_MSGPACK
+    } else {
+        req.encoding
+    };
+
+    let hash = blake3::hash(&req.payload);
+
+    payload.write_u64::<LittleEndian>(req.context_id.get())?;
+    payload.write_u64::<LittleEndian>(req.parent_turn_id.get())?;
+
+    payload.write_u32::<LittleEndian>(req.type_id.len() as u32)?;
+    payload.extend_from_slice(req.type_id.as_bytes());
+    payload.write_u32::<LittleEndian>(req.type_version)?;
+
+    payload.write_u32::<LittleEndian>(encoding)?;
+    payload.write_u32::<LittleEndian>(req.compression)?;
+    payload.write_u32::<LittleEndian>(req.payload.len() as u32)?; // uncompressed len
+    payload.extend_from_slice(hash.as_bytes());
+
+    payload.write_u32::<LittleEndian>(req.payload.len() as u32)?;
+    payload.extend_from_slice(&req.payload);
+
+    payload.write_u32::<LittleEndian>(req.idempotency_key.len() as u32)?;
+    if !req.idempotency_key.is_empty() {
+        payload.extend_from_slice(&req.idempotency_key);
+    }
+
+    let mut flags = 0u16;
+    if let Some(hash) = fs_root_hash {
+        flags |= APPEND_FLAG_FS_ROOT;
+        payload.extend_from_slice(&hash);
+    }
+    if let Some(writer_id) = &req.writer_id {
+        flags |= APPEND_FLAG_WRITER;
+        payload.write_u32::<LittleEndian>(writer_id.len() as u32)?;
+        payload.extend_from_slice(writer_id.as_bytes());
+        payload.write_u64::<LittleEndian>(req.writer_seq)?;
+    }
+    if let Some(ttl) = req.ttl {
+        flags |= APPEND_FLAG_TTL;
+        payload.write_u64::<LittleEndian>(ttl_millis(ttl))?;
+    }
+    if !req.links.is_empty() {
+        flags |= APPEND_FLAG_LINKS;
+        encode_links(payload, &req.links)?;
+    }
+    Ok(flags)
+}
+
+pub(crate) fn get_turn_request(turn_id: TurnId) -> Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(12);
+    payload.write_u64::<LittleEndian>(turn_id.get())?;
+    payload.write_u32::<LittleEndian>(1)?;
+    Ok(payload)
+}
+
+/// `ttl` in whole milliseconds, rounded up; the protocol rejects 0.
+pub(crate) fn ttl_millis(ttl: Duration) -> u64 {
+    ttl.as_nanos()
+        .div_ceil(1_000_000)
+        .clamp(1, u64::MAX as u128) as u64
+}
+
+/// Applies `opts` to a GET_LAST response: enforces `max_payload_bytes`
+/// locally for servers that ignored the hint, then finishes the records as
+/// [`finish_listing`] does.
+pub(crate) fn finish_records<P: AsRef<[u8]> + Default>(
+    records: &mut [TurnRecord<P>],
+    opts: &GetLastOptions,
+) -> Result<()> {
+    if let Some(max) = opts.max_payload_bytes {
+        for record in records.iter_mut() {
+            if record.payload.as_ref().len() > max as usize {
+                record.payload = P::default();
+                record.payload_omitted = true;
+            }
+        }
+    }
+    finish_listing(records, opts)
+}
+
+/// The payload-less, newest-first listing a
+/// [`GetLastOptions::byte_budget`] is measured against.
+pub(crate) fn budget_listing(opts: &GetLastOptions) -> GetLastOptions {
+    GetLastOptions {
+        include_payload: false,
+        order: Order::NewestFirst,
+        projection: opts.projection | TurnFields::SIZE,
+        byte_budget: None,
+        ..opts.clone()
+    }
+}
+
+/// How many turns of `listing` (from [`budget_listing`]) fit in
+/// `opts.byte_budget`; at least one unless `listing` is empty.
+pub(crate) fn budget_fit<P>(listing: &[TurnRecord<P>], opts: &GetLastOptions) -> usize {
+    let budget = opts.byte_budget.unwrap_or(u64::MAX);
+    let mut used = 0u64;
+    let mut fit = 0;
+    for turn in listing {
+        let size = match opts.max_payload_bytes {
+            Some(max) if turn.payload_size > max => 0,
+            _ => u64::from(turn.payload_size),
+        };
+        if fit > 0 && used + size > budget {
+            break;
+        }
+        used += size;
+        fit += 1;
+    }
+    fit
+}
+
+/// Options reading the `fit` turns of a budgeted listing whose newest turn
+/// is `newest`, and no others.
+pub(crate) fn budget_fetch(opts: GetLastOptions, newest: TurnId, fit: usize) -> GetLastOptions {
+    GetLastOptions {
+        limit: fit as u32,
+        before_turn_id: Some(TurnId::new(newest.get() + 1)),
+        byte_budget: None,
+        ..opts
+    }
+}
+
+/// Rejects pages from servers that ignored `before_turn_id` or the depth
+/// range, clears the fields outside the projection and puts the turns in
+/// `order`. Payloads are not looked at.
+pub(crate) fn finish_listing<P>(
+    records: &mut [TurnRecord<P>],
+    opts: &GetLastOptions,
+) -> Result<()> {
+    if let Some(before) = opts.before_turn_id {
+        if records.iter().any(|record| record.turn_id >= before) {
+            return Err(Error::protocol(
+                "server does not support get_last before_turn_id",
+            ));
+        }
+    }
+    let fields = opts.fields();
+    // Without depths in the records, the server's filter is taken on trust.
+    if let Some(depths) = opts
+        .depth_range()
+        .filter(|_| fields.contains(TurnFields::DEPTH))
+    {
+        if let Some(record) = records.iter().find(|r| !depths.contains(&r.depth)) {
+            return Err(Error::protocol(format!(
+                "server returned turn {} at depth {} outside {}..={}",
+                record.turn_id,
+                record.depth,
+                depths.start(),
+                depths.end()
+            )));
+        }
+    }
+    if fields != TurnFields::ALL {
+        for record in records.iter_mut() {
+            record.project(fields);
+        }
+    }
+    match opts.order {
+        Order::OldestFirst => records.sort_by_key(|record| record.turn_id),
+        Order::NewestFirst => records.sort_by_key(|record| std::cmp::Reverse(record.turn_id)),
+    }
+    Ok(())
+}
+
+/// Pages back through history for filters the server cannot apply: a
+/// depth range (when it did not echo
+/// [`FLAG_DEPTH_FILTER`](crate::protocol::FLAG_DEPTH_FILTER)) or a type, so
+/// that `limit` still counts only matching turns. Fetch each page from
+/// [`FilterPager::next_page`] without the filters and hand it to
+/// [`FilterPager::push`] until there are no more pages.
+pub(crate) struct FilterPager<P> {
+    depths: RangeInclusive<u32>,
+    type_filter: Option<TypeId>,
+    limit: usize,
+    page: GetLastOptions,
+    turns: Vec<TurnRecord<P>>,
+    done: bool,
+}
+
+impl<P> FilterPager<P> {
+    pub(crate) fn new(opts: &GetLastOptions) -> Self {
+        Self {
+            depths: opts.depth_range().unwrap_or(0..=u32::MAX),
+            type_filter: opts.type_filter.clone(),
+            limit: opts.limit as usize,
+            page: GetLastOptions {
+                min_depth: None,
+                max_depth: None,
+                order: Order::NewestFirst,
+                projection: opts.projection | TurnFields::DEPTH | TurnFields::TYPE,
+                type_filter: None,
+                ..opts.clone()
+            },
+            turns: Vec::new(),
+            done: opts.limit == 0,
+        }
+    }
+
+    /// Options for the next page, or `None` once the range is filled or
+    /// history runs out.
+    pub(crate) fn next_page(&self) -> Option<GetLastOptions> {
+        (!self.done).then(|| self.page.clone())
+    }
+
+    /// Keeps the turns of `page`, newest first, that match.
+    pub(crate) fn push(&mut self, page: Vec<TurnRecord<P>>) {
+        let full = page.len() >= self.page.limit as usize;
+        let oldest = page.iter().map(|turn| turn.turn_id).min();
+        for turn in page {
+            // Depths fall by one per parent, so nothing older is in range.
+            if turn.depth < *self.depths.start() {
+                self.done = true;
+                return;
+            }
+            let type_matches = self
+                .type_filter
+                .as_ref()
+                .is_none_or(|type_id| turn.type_id == *type_id);
+            if turn.depth <= *self.depths.end() && type_matches {
+                self.turns.push(turn);
+                if self.turns.len() == self.limit {
+                    self.done = true;
+                    return;
+                }
+            }
+        }
+        match oldest {
+            Some(oldest) if full => self.page.before_turn_id = Some(oldest),
+            _ => self.done = true,
+        }
+    }
+
+    /// The turns found, newest first.
+    pub(crate) fn finish(self) -> Vec<TurnRecord<P>> {
+        self.turns
+    }
+}
+
+/// Reads the turns `opts` asks for with a [`FilterPager`], fetching each page
+/// with `fetch`.
+#[cfg(not(target_arch = "wasm32"))]
+fn page_filtered<P: AsRef<[u8]> + Default>(
+    opts: &GetLastOptions,
+    mut fetch: impl FnMut(GetLastOptions) -> Result<Vec<TurnRecord<P>>>,
+) -> Result<Vec<TurnRecord<P>>> {
+    let mut pager = FilterPager::new(opts);
+    while let Some(page) = pager.next_page() {
+        pager.push(fetch(page)?);
+    }
+    let mut records = pager.finish();
+    finish_records(&mut records, opts)?;
+    Ok(records)
+}
+
+/// Depths read per GET_LAST page by [`Client::get_range`].
+pub const RANGE_PAGE_SIZE: u32 = 256;
+
+/// Turns read per GET_LAST page by [`Client::get_turn_by_hash`].
+#[cfg(not(target_arch = "wasm32"))]
+const HASH_LOOKUP_PAGE_SIZE: u32 = 256;
+
+/// Bytes of append metadata after the commit sequence: timestamp, stored
+/// length and head.
+const APPEND_META_LEN: usize = 8 + 4 + 8 + 4;
+
+pub(crate) fn parse_append_result(payload: &[u8]) -> Result<AppendResult> {
+    if payload.len() < 52 {
+        return Err(Error::protocol(format!(
+            "append response too short ({} bytes)",
+            payload.len()
+        )));
+    }
+    let mut reader = PayloadReader::new(payload, "append response");
+    let context_id = ContextId::new(reader.u64("context_id")?);
+    let turn_id = TurnId::new(reader.u64("turn_id")?);
+    let depth = reader.u32("depth")?;
+    let hash = reader.array("payload_hash")?;
+    // Replicated deployments append the commit sequence to the ack, and
+    // servers that echoed FLAG_APPEND_META send it (possibly 0) followed by
+    // the append metadata.
+    let sequence = if reader.remaining() >= 8 {
+        reader.u64("commit_sequence")?
+    } else {
+        0
+    };
+    let mut result = AppendResult {
+        context_id,
+        turn_id,
+        depth,
+        payload_hash: hash,
+        consistency_token: ConsistencyToken::from_sequence(sequence),
+        created_at_unix_ms: None,
+        stored_len: None,
+        head: None,
+    };
+    if reader.remaining() >= APPEND_META_LEN {
+        result.created_at_unix_ms = Some(reader.u64("created_at_unix_ms")?);
+        result.stored_len = Some(reader.u32("stored_len")?);
+        result.head = Some(ContextHead {
+            context_id,
+            head_turn_id: reader.u64("head_turn_id")?.into(),
+            head_depth: reader.u32("head_depth")?,
+        });
+    }
+    Ok(result)
+}
+
+/// Decodes a GET_LAST response payload.
+///
+/// Every length prefix is checked against the remaining input before use, so
+/// untrusted bytes yield [`Error::Protocol`] rather than a panic or an
+/// oversized allocation.
+pub fn parse_turn_records(payload: &[u8]) -> Result<Vec<TurnRecord>> {
+    let mut raw = RawTurnRecords::new(payload)?;
+    let mut records = Vec::with_capacity(raw.capacity_hint());
+    for record in raw.by_ref() {
+        records.push(record?.into_record(<[u8]>::to_vec));
+    }
+    raw.read_trailers(&mut records)?;
+    Ok(records)
+}
+
+/// Decodes a GET_TURN response, which holds exactly one record.
+pub(crate) fn parse_single_turn(payload: &[u8]) -> Result<TurnRecord> {
+    let mut records = parse_turn_records(payload)?;
+    if records.len() != 1 {
+        return Err(Error::protocol(format!(
+            "get turn response has {} records",
+            records.len()
+        )));
+    }
+    Ok(records.remove(0))
+}
+
+/// Decodes a GET_LAST response to a request without payloads, whose
+/// records carry no payload fields.
+pub(crate) fn parse_turn_listing(payload: &[u8]) -> Result<Vec<TurnRecord>> {
+    let mut raw = RawTurnRecords::new(payload)?;
+    raw.payloads = false;
+    let mut records = Vec::with_capacity(raw.capacity_hint());
+    for record in raw.by_ref() {
+        records.push(record?.into_record(<[u8]>::to_vec));
+    }
+    raw.read_trailers(&mut records)?;
+    Ok(records)
+}
+
+/// Decodes the response to a GET_LAST request sent with `wire`, whose
+/// records carry the fields and payloads it asked for.
+pub(crate) fn parse_get_last(payload: &[u8], wire: &GetLastOptions) -> Result<Vec<TurnRecord>> {
+    parse_turn_records_with(payload, wire, <[u8]>::to_vec)
+}
+
+/// Parses the response to a GET_LAST request sent with `wire`, building
+/// each payload from its slice of `payload`.
+pub(crate) fn parse_turn_records_with<P>(
+    payload: &[u8],
+    wire: &GetLastOptions,
+    mut make_payload: impl FnMut(&[u8]) -> P,
+) -> Result<Vec<TurnRecord<P>>> {
+    let mut raw = RawTurnRecords::for_request(payload, wire)?;
+    let mut records = Vec::with_capacity(raw.capacity_hint());
+    for record in raw.by_ref() {
+        records.push(record?.into_record(&mut make_payload));
+    }
+    raw.read_trailers(&mut records)?;
+    Ok(records)
+}
+
+/// Parses the response to a GET_LAST request sent with `wire` into
+/// `records`, overwriting existing entries in place so their `type_id` and
+/// `payload` allocations are reused.
+pub(crate) fn parse_turn_records_into(
+    payload: &[u8],
+    wire: &GetLastOptions,
+    records: &mut Vec<TurnRecord>,
+) -> Result<()> {
+    let mut count = 0;
+    let mut raw = RawTurnRecords::for_request(payload, wire)?;
+    for record in raw.by_ref() {
+        let record = record?;
+        match records.get_mut(count) {
+            Some(existing) => record.overwrite(existing),
+            None => records.push(record.into_record(<[u8]>::to_vec)),
+        }
+        count += 1;
+    }
+    records.truncate(count);
+    raw.read_trailers(records)
+}
+
+/// One GET_LAST record, borrowing its type id and payload from the response.
+struct RawTurnRecord<'a> {
+    turn_id: TurnId,
+    parent_id: TurnId,
+    depth: u32,
+    type_id: &'a str,
+    type_version: u32,
+    encoding: u32,
+    compression: u32,
+    payload_hash: [u8; 32],
+    payload_size: u32,
+    payload_omitted: bool,
+    payload: &'a [u8],
+}
+
+impl RawTurnRecord<'_> {
+    fn into_record<P>(self, make_payload: impl FnOnce(&[u8]) -> P) -> TurnRecord<P> {
+        TurnRecord {
+            turn_id: self.turn_id,
+            parent_id: self.parent_id,
+            depth: self.depth,
+            type_id: TypeId::intern(self.type_id),
+            type_version: self.type_version,
+            encoding: self.encoding,
+            compression: self.compression,
+            payload_hash: self.payload_hash,
+            payload_size: self.payload_size,
+            payload_omitted: self.payload_omitted,
+            payload: make_payload(self.payload),
+            writer_id: None,
+            writer_seq: 0,
+            expires_at_unix_ms: None,
+            expired: false,
+            created_at_unix_ms: None,
+            redacted: false,
+            redacted_at_unix_ms: None,
+            redaction_reason: None,
+            links: Vec::new(),
+        }
+    }
+
+    fn overwrite(self, record: &mut TurnRecord) {
+        record.turn_id = self.turn_id;
+        record.parent_id = self.parent_id;
+        record.depth = self.depth;
+        if record.type_id != self.type_id {
+            record.type_id = TypeId::intern(self.type_id);
+        }
+        record.type_version = self.type_version;
+        record.encoding = self.encoding;
+        record.compression = self.compression;
+        record.payload_hash = self.payload_hash;
+        record.payload_size = self.payload_size;
+        record.payload_omitted = self.payload_omitted;
+        record.payload.clear();
+        record.payload.extend_from_slice(self.payload);
+        record.writer_id = None;
+        record.writer_seq = 0;
+        record.expires_at_unix_ms = None;
+        record.expired = false;
+        record.created_at_unix_ms = None;
+        record.redacted = false;
+        record.redacted_at_unix_ms = None;
+        record.redaction_reason = None;
+        record.links.clear();
+    }
+}
+
+/// Iterates the records of a GET_LAST response payload. Stops after the
+/// first error.
+struct RawTurnRecords<'a> {
+    reader: PayloadReader<'a>,
+    remaining: u32,
+    /// Whether records carry payload fields (`include_payload` was set).
+    payloads: bool,
+    /// The metadata fields records carry.
+    fields: TurnFields,
+}
+
+impl<'a> RawTurnRecords<'a> {
+    fn new(payload: &'a [u8]) -> Result<Self> {
+        if payload.len() < 4 {
+            return Err(Error::protocol("turn records too short"));
+        }
+        let mut reader = PayloadReader::new(payload, "turn records");
+        let remaining = reader.u32("count")?;
+        Ok(Self {
+            reader,
+            remaining,
+            payloads: true,
+            fields: TurnFields::ALL,
+        })
+    }
+
+    /// Reads the response to a GET_LAST request sent with `wire`.
+    fn for_request(payload: &'a [u8], wire: &GetLastOptions) -> Result<Self> {
+        let mut raw = Self::new(payload)?;
+        raw.payloads = wire.include_payload;
+        raw.fields = wire.fields();
+        Ok(raw)
+    }
+
+    /// Applies the writer stamps, the expiries, the creation times and then
+    /// the redactions trailing the records, if the server sent any. Call
+    /// once every record has been read.
+    fn read_trailers<P>(&mut self, records: &mut [TurnRecord<P>]) -> Result<()> {
+        if self.reader.remaining() == 0 {
+            return Ok(());
+        }
+        let reader = &mut self.reader;
+        let count = reader.u32("writers_count")?;
+        for _ in 0..count {
+            let index = reader.u32("writer item_index")? as usize;
+            let writer_id = std::str::from_utf8(reader.len_prefixed("writer_id")?)
+                .map_err(|_| Error::protocol("writer_id not utf8"))?;
+            let writer_seq = reader.u64("writer_seq")?;
+            let record = records
+                .get_mut(index)
+                .ok_or_else(|| Error::protocol(format!("writer for missing item {index}")))?;
+            record.writer_id = Some(writer_id.to_string());
+            record.writer_seq = writer_seq;
+        }
+        if reader.remaining() == 0 {
+            return Ok(());
+        }
+        let count = reader.u32("expiries_count")?;
+        for _ in 0..count {
+            let index = reader.u32("expiry item_index")? as usize;
+            let expires_at = reader.u64("expires_at_unix_ms")?;
+            let expired = reader.u8("expired")? != 0;
+            let record = records
+                .get_mut(index)
+                .ok_or_else(|| Error::protocol(format!("expiry for missing item {index}")))?;
+            record.expires_at_unix_ms = Some(expires_at);
+            record.expired = expired;
+        }
+        if reader.remaining() == 0 {
+            return Ok(());
+        }
+        let count = reader.u32("timestamps_count")? as usize;
+        if count != records.len() {
+            return Err(Error::protocol(format!(
+                "{count} timestamps for {} records",
+                records.len()
+            )));
+        }
+        for record in records.iter_mut() {
+            record.created_at_unix_ms = Some(reader.u64("created_at_unix_ms")?);
+        }
+        if reader.remaining() == 0 {
+            return Ok(());
+        }
+        let count = reader.u32("redactions_count")?;
+        for _ in 0..count {
+            let index = reader.u32("redaction item_index")? as usize;
+            let redacted_at = reader.u64("redacted_at_unix_ms")?;
+            let reason = std::str::from_utf8(reader.len_prefixed("reason")?)
+                .map_err(|_| Error::protocol("redaction reason not utf8"))?;
+            let record = records
+                .get_mut(index)
+                .ok_or_else(|| Error::protocol(format!("redaction for missing item {index}")))?;
+            record.redacted = true;
+            record.redacted_at_unix_ms = Some(redacted_at);
+            record.redaction_reason = Some(reason.to_string());
+        }
+        if reader.remaining() == 0 {
+            return Ok(());
+        }
+        let count = reader.u32("links_count")?;
+        for _ in 0..count {
+            let index = reader.u32("links item_index")? as usize;
+            let links = read_links(reader)?;
+            let record = records
+                .get_mut(index)
+                .ok_or_else(|| Error::protocol(format!("links for missing item {index}")))?;
+            record.links = links;
+        }
+        Ok(())
+    }
+
+    /// Each record is at least 64 bytes (8 when projected), so cap
+    /// preallocation by what the payload could actually hold rather than
+    /// trusting `count`.
+    fn capacity_hint(&self) -> usize {
+        let min_len = if self.fields == TurnFields::ALL {
+            64
+        } else {
+            8
+        };
+        (self.remaining as usize).min(self.reader.remaining() / min_len)
+    }
+
+    fn read_record(&mut self) -> Result<RawTurnRecord<'a>> {
+        let reader = &mut self.reader;
+        let fields = self.fields;
+        let turn_id = TurnId::new(reader.u64("turn_id")?);
+        let parent_id = if fields.contains(TurnFields::PARENT) {
+            TurnId::new(reader.u64("parent_id")?)
+        } else {
+            TurnId::default()
+        };
+        let depth = if fields.contains(TurnFields::DEPTH) {
+            reader.u32("depth")?
+        } else {
+            0
+        };
+
+        let (type_id, type_version) = if fields.contains(TurnFields::TYPE) {
+            let type_id = std::str::from_utf8(reader.len_prefixed("type_id")?)
+                .map_err(|_| Error::protocol("type_id not utf8"))?;
+            (type_id, reader.u32("type_version")?)
+        } else {
+            ("", 0)
+        };
+
+        let (encoding, compression) = if fields.contains(TurnFields::ENCODING) {
+            (reader.u32("encoding")?, reader.u32("compression")?)
+        } else {
+            (0, 0)
+        };
+
+        let payload_size = if fields.contains(TurnFields::SIZE) {
+            reader.u32("uncompressed_len")?
+        } else {
+            0
+        };
+        let payload_hash = if fields.contains(TurnFields::CONTENT_HASH) {
+            reader.array("payload_hash")?
+        } else {
+            [0; 32]
+        };
+
+        let payload_len = if self.payloads {
+            reader.u32("payload")?
+        } else {
+            0
+        };
+        let payload_omitted = payload_len == PAYLOAD_OMITTED;
+        let payload = if payload_omitted {
+            &[]
+        } else {
+            reader.bytes(payload_len as usize, "payload")?
+        };
+
+        Ok(RawTurnRecord {
+            turn_id,
+            parent_id,
+            depth,
+            type_id,
+            type_version,
+            encoding,
+            compression,
+            payload_hash,
+            payload_size,
+            payload_omitted,
+            payload,
+        })
+    }
+}
+
+impl<'a> Iterator for RawTurnRecords<'a> {
+    type Item = Result<RawTurnRecord<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let record = self.read_record();
+        if record.is_err() {
+            self.remaining = 0;
+        }
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{decode_hex, load_fixture, turn_records_payload};
+
+    fn build_append_payload(req: &AppendRequest) -> Vec<u8> {
+        let encoding = if req.encoding == 0 {
+            ENCODING_MSGPACK
+        } else {
+            req.encoding
+        };
+        let hash = blake3::hash(&req.payload);
+        let mut payload = Vec::new();
+        payload
+            .write_u64::<LittleEndian>(req.context_id.get())
+            .unwrap();
+        payload
+            .write_u64::<LittleEndian>(req.parent_turn_id.get())
+            .unwrap();
+        payload
+            .write_u32::<LittleEndian>(req.type_id.len() as u32)
+            .unwrap();
+        payload.extend_from_slice(req.type_id.as_bytes());
+        payload.write_u32::<LittleEndian>(req.type_version).unwrap();
+        payload.write_u32::<LittleEndian>(encoding).unwrap();
+        payload.write_u32::<LittleEndian>(req.compression).unwrap();
+        payload
+            .write_u32::<LittleEndian>(req.payload.len() as u32)
+            .unwrap();
+        payload.extend_from_slice(hash.as_bytes());
+        payload
+            .write_u32::<LittleEndian>(req.payload.len() as u32)
+            .unwrap();
+        payload.extend_from_slice(&req.payload);
+        payload
+            .write_u32::<LittleEndian>(req.idempotency_key.len() as u32)
+            .unwrap();
+        if !req.idempotency_key.is_empty() {
+            payload.extend_from_slice(&req.idempotency_key);
+        }
+        payload
+    }
+
+    #[test]
+    fn append_payloads_match_fixtures() {
+        let fixture = load_fixture("append_parent0");
+        assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
+        assert_eq!(fixture.flags, 0);
+        let req = AppendRequest {
+            context_id: ContextId::new(1),
+            parent_turn_id: TurnId::new(0),
+            type_id: "cxdb.ConversationItem".into(),
+            type_version: 3,
+            payload: vec![0x91, 0x01],
+            idempotency_key: Vec::new(),
+            encoding: ENCODING_MSGPACK,
+            compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
+        };
+        assert_eq!(decode_hex(&fixture.payload_hex), build_append_payload(&req));
+
+        let fixture = load_fixture("append_parent7");
+        assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
+        assert_eq!(fixture.flags, 0);
+        let req = AppendRequest {
+            context_id: ContextId::new(1),
+            parent_turn_id: TurnId::new(7),
+            type_id: "cxdb.ConversationItem".into(),
+            type_version: 3,
+            payload: vec![0x91, 0x02],
+            idempotency_key: Vec::new(),
+            encoding: ENCODING_MSGPACK,
+            compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
+        };
+        assert_eq!(decode_hex(&fixture.payload_hex), build_append_payload(&req));
+
+        let fixture = load_fixture("append_idempotent");
+        assert_eq!(fixture.msg_type, MSG_APPEND_TURN);
+        assert_eq!(fixture.flags, 0);
+        let req = AppendRequest {
+            context_id: ContextId::new(1),
+            parent_turn_id: TurnId::new(0),
+            type_id: "cxdb.ConversationItem".into(),
+            type_version: 3,
+            payload: vec![0x91, 0x03],
+            idempotency_key: b"idem-1".to_vec(),
+            encoding: ENCODING_MSGPACK,
+            compression: 0,
+            writer_id: None,
+            writer_seq: 0,
+            ttl: None,
+            links: Vec::new(),
+            delta_base: None,
+        };
+        assert_eq!(decode_hex(&fixture.payload_hex), build_append_payload(&req));
+    }
+
+    #[test]
+    fn get_last_payloads_match_fixtures() {
+        let fixture = load_fixture("get_last_default");
+        assert_eq!(fixture.msg_type, MSG_GET_LAST);
+        let mut payload = Vec::new();
+        payload.write_u64::<LittleEndian>(1).unwrap();
+        payload.write_u32::<LittleEndian>(10).unwrap();
+        payload.write_u32::<LittleEndian>(0).unwrap();
+        assert_eq!(decode_hex(&fixture.payload_hex), payload);
+
+        let fixture = load_fixture("get_last_payload");
+        assert_eq!(fixture.msg_type, MSG_GET_LAST);
+        let mut payload = Vec::new();
+        payload.write_u64::<LittleEndian>(1).unwrap();
         payload.write_u32::<LittleEndian>(5).unwrap();
         payload.write_u32::<LittleEndian>(1).unwrap();
         assert_eq!(decode_hex(&fixture.payload_hex), payload);
     }
+
+    #[test]
+    fn append_errors_map_to_typed_variants() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{error_payload, not_found_payload, spawn_scripted_server};
+
+        type Check = fn(&Error) -> bool;
+        let cases: Vec<(Vec<u8>, Check)> = vec![
+            (
+                not_found_payload("context", 5),
+                |e| matches!(e, Error::ContextNotFound { context_id } if context_id.get() == 5),
+            ),
+            (
+                not_found_payload("turn", 9),
+                |e| matches!(e, Error::TurnNotFound { turn_id } if turn_id.get() == 9),
+            ),
+            (error_payload(404, "context"), |e| {
+                crate::error::is_server_error(e, 404)
+            }),
+            (error_payload(404, "blob"), |e| {
+                crate::error::is_server_error(e, 404)
+            }),
+            (error_payload(400, "bad frame"), |e| {
+                crate::error::is_server_error(e, 400)
+            }),
+            (error_payload(409, "conflict"), |e| {
+                crate::error::is_server_error(e, 409)
+            }),
+            (error_payload(422, "invalid type_id"), |e| {
+                crate::error::is_server_error(e, 422)
+            }),
+            (error_payload(500, "storage"), |e| {
+                crate::error::is_server_error(e, 500)
+            }),
+        ];
+        let replies = cases
+            .iter()
+            .map(|(payload, _)| (MSG_ERROR, payload.clone()))
+            .collect();
+        let (addr, handle) = spawn_scripted_server(replies);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let mut req = AppendRequest::new(ContextId::new(5), "cxdb.ConversationItem", 3, vec![0x90]);
+        req.parent_turn_id = TurnId::new(9);
+        for (i, (_, check)) in cases.iter().enumerate() {
+            let err = client.append_turn(&ctx, &req).unwrap_err();
+            assert!(check(&err), "case {i}: got {err:?}");
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn append_result_reads_optional_sequence() {
+        let mut ack = Vec::new();
+        ack.write_u64::<LittleEndian>(1).unwrap();
+        ack.write_u64::<LittleEndian>(7).unwrap();
+        ack.write_u32::<LittleEndian>(3).unwrap();
+        ack.extend_from_slice(&[0xCC; 32]);
+        let result = parse_append_result(&ack).unwrap();
+        assert!(result.consistency_token.is_none());
+
+        ack.write_u64::<LittleEndian>(42).unwrap();
+        let result = parse_append_result(&ack).unwrap();
+        assert_eq!(result.turn_id, TurnId::new(7));
+        assert_eq!(result.consistency_token.sequence(), 42);
+    }
+
+    #[test]
+    fn append_dedup_checks_history_without_server_support() {
+        use crate::test_util::{spawn_scripted_server, turn_records_payload_omitting};
+
+        let history = turn_records_payload_omitting(&[b"a", b"b"], 0);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, history.clone()),
+            (MSG_GET_LAST, history),
+            (MSG_APPEND_TURN, vec![0u8; 52]),
+        ]);
+        let client = crate::dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let req = AppendRequest::new(ContextId::new(9), "test", 1, b"a".to_vec());
+        let (existing, appended) = client.append_dedup(&ctx, &req).unwrap();
+        assert!(!appended);
+        assert_eq!((existing.context_id.get(), existing.turn_id.get()), (9, 1));
+        assert_eq!(existing.payload_hash, *blake3::hash(b"a").as_bytes());
+        assert_eq!(existing.head, None);
+
+        let req = AppendRequest::new(ContextId::new(9), "test", 1, b"c".to_vec());
+        let (_, appended) = client.append_dedup(&ctx, &req).unwrap();
+        assert!(appended);
+
+        let requests = handle.join().unwrap();
+        let types: Vec<_> = requests.iter().map(|r| r.header.msg_type).collect();
+        assert_eq!(types, [MSG_GET_LAST, MSG_GET_LAST, MSG_APPEND_TURN]);
+        assert_eq!(requests[2].header.flags & APPEND_FLAG_DEDUP, 0);
+    }
+
+    #[test]
+    fn append_dedup_is_one_round_trip_when_negotiated() {
+        use crate::protocol::{read_frame, write_frame, FLAG_DEDUP, MSG_HELLO};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            assert_ne!(hello.header.flags & FLAG_DEDUP, 0);
+            let mut resp = 1u64.to_le_bytes().to_vec();
+            resp.extend_from_slice(&1u16.to_le_bytes());
+            write_frame(
+                &mut stream,
+                MSG_HELLO,
+                FLAG_DEDUP,
+                hello.header.req_id,
+                &resp,
+            )
+            .unwrap();
+
+            let req = read_frame(&mut stream).unwrap();
+            let mut ack = 9u64.to_le_bytes().to_vec();
+            ack.extend_from_slice(&4u64.to_le_bytes());
+            ack.extend_from_slice(&3u32.to_le_bytes());
+            ack.extend_from_slice(blake3::hash(b"a").as_bytes());
+            ack.push(0);
+            write_frame(&mut stream, MSG_APPEND_TURN, 0, req.header.req_id, &ack).unwrap();
+            req
+        });
+
+        let client = crate::dial(&addr, []).unwrap();
+        let req = AppendRequest::new(ContextId::new(9), "test", 1, b"a".to_vec());
+        let (existing, appended) = client
+            .append_dedup(&RequestContext::background(), &req)
+            .unwrap();
+        assert!(!appended);
+        assert_eq!((existing.turn_id.get(), existing.depth), (4, 3));
+
+        let req = handle.join().unwrap();
+        assert_eq!(req.header.msg_type, MSG_APPEND_TURN);
+        assert_ne!(req.header.flags & APPEND_FLAG_DEDUP, 0);
+    }
+
+    #[test]
+    fn append_result_reads_optional_metadata() {
+        let mut ack = Vec::new();
+        ack.write_u64::<LittleEndian>(1).unwrap();
+        ack.write_u64::<LittleEndian>(7).unwrap();
+        ack.write_u32::<LittleEndian>(3).unwrap();
+        ack.extend_from_slice(&[0xCC; 32]);
+        ack.write_u64::<LittleEndian>(0).unwrap();
+        let result = parse_append_result(&ack).unwrap();
+        assert_eq!(result.created_at_unix_ms, None);
+        assert_eq!(result.stored_len, None);
+        assert_eq!(result.head, None);
+
+        ack.write_u64::<LittleEndian>(1_700_000_000_000).unwrap();
+        ack.write_u32::<LittleEndian>(11).unwrap();
+        ack.write_u64::<LittleEndian>(7).unwrap();
+        ack.write_u32::<LittleEndian>(3).unwrap();
+        let result = parse_append_result(&ack).unwrap();
+        assert!(result.consistency_token.is_none());
+        assert_eq!(result.created_at_unix_ms, Some(1_700_000_000_000));
+        assert_eq!(result.stored_len, Some(11));
+        assert_eq!(
+            result.head,
+            Some(ContextHead {
+                context_id: ContextId::new(1),
+                head_turn_id: TurnId::new(7),
+                head_depth: 3,
+            })
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn shared_payloads_slice_one_recycled_buffer() {
+        use crate::test_util::spawn_scripted_server;
+
+        let response = turn_records_payload(&[b"\x91\x01", b"\x91\x02\x03"]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, response.clone()),
+            (MSG_GET_LAST, response.clone()),
+            (MSG_GET_LAST, response),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let owned = client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        let shared = client
+            .get_last_shared(&ctx, ContextId::new(1), opts.clone().reuse_buffer(true))
+            .unwrap();
+        assert_eq!(shared.len(), owned.len());
+        for (shared, owned) in shared.iter().zip(&owned) {
+            assert_eq!(shared.payload, owned.payload);
+            assert_eq!(shared.turn_id, owned.turn_id);
+        }
+        // Both payloads point into the same response allocation.
+        let gap = shared[1].payload.as_ptr() as usize - shared[0].payload.as_ptr() as usize;
+        assert!(gap < 128, "payloads not sliced from one buffer");
+
+        let first = shared[0].payload.as_ptr();
+        drop(shared);
+        let again = client
+            .get_last_shared(&ctx, ContextId::new(1), opts.reuse_buffer(true))
+            .unwrap();
+        assert_eq!(again[0].payload.as_ptr(), first, "read buffer not recycled");
+        handle.join().unwrap();
+    }
+
+    /// Accepts one connection, answers HELLO, then reads `count` requests
+    /// before writing any response, so a client that waits for each reply
+    /// would stall. `reply` sees the requests and returns the frames to send.
+    fn spawn_batching_server(
+        count: usize,
+        reply: impl FnOnce(&[crate::protocol::Frame]) -> Vec<(u16, u64, Vec<u8>)> + Send + 'static,
+    ) -> (String, std::thread::JoinHandle<()>) {
+        use crate::protocol::{read_frame, write_frame, MSG_HELLO};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello = read_frame(&mut stream).unwrap();
+            assert_eq!(hello.header.msg_type, MSG_HELLO);
+            let resp = [1u64.to_le_bytes().as_slice(), &1u16.to_le_bytes()].concat();
+            write_frame(&mut stream, MSG_HELLO, 0, hello.header.req_id, &resp).unwrap();
+
+            let requests: Vec<_> = (0..count)
+                .map(|_| read_frame(&mut stream).unwrap())
+                .collect();
+            for (msg_type, req_id, payload) in reply(&requests) {
+                write_frame(&mut stream, msg_type, 0, req_id, &payload).unwrap();
+            }
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn get_last_many_demultiplexes_out_of_order_responses() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::not_found_payload;
+
+        let (addr, handle) = spawn_batching_server(3, |requests| {
+            // Answer newest first; context 2 does not exist.
+            requests
+                .iter()
+                .rev()
+                .map(|req| {
+                    let context_id = u64::from_le_bytes(req.payload[..8].try_into().unwrap());
+                    if context_id == 2 {
+                        (
+                            MSG_ERROR,
+                            req.header.req_id,
+                            not_found_payload("context", 2),
+                        )
+                    } else {
+                        let payloads = vec![&b"\x90"[..]; context_id as usize];
+                        (
+                            MSG_GET_LAST,
+                            req.header.req_id,
+                            crate::test_util::turn_listing_payload(&payloads),
+                        )
+                    }
+                })
+                .collect()
+        });
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions::default();
+
+        let results = client
+            .get_last_many(
+                &ctx,
+                &[
+                    (ContextId::new(1), opts.clone()),
+                    (ContextId::new(2), opts.clone()),
+                    (ContextId::new(3), opts),
+                ],
+            )
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().len(), 1);
+        assert!(matches!(
+            results[1],
+            Err(Error::ContextNotFound { context_id }) if context_id.get() == 2
+        ));
+        assert_eq!(results[2].as_ref().unwrap().len(), 3);
+        assert!(!client.is_poisoned());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn get_last_many_fails_outstanding_requests_when_connection_drops() {
+        let (addr, handle) = spawn_batching_server(3, |requests| {
+            // Answer the second request only, then hang up.
+            vec![(
+                MSG_GET_LAST,
+                requests[1].header.req_id,
+                turn_records_payload(&[]),
+            )]
+        });
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions::default();
+
+        let results = client
+            .get_last_many(
+                &ctx,
+                &[
+                    (ContextId::new(1), opts.clone()),
+                    (ContextId::new(2), opts.clone()),
+                    (ContextId::new(3), opts.clone()),
+                ],
+            )
+            .unwrap();
+        handle.join().unwrap();
+        assert!(results[0].is_err());
+        assert!(results[1].as_ref().unwrap().is_empty());
+        assert!(matches!(results[2], Err(Error::ConnectionClosed)));
+        assert!(client.is_poisoned());
+        assert!(matches!(
+            client.get_last_many(&ctx, &[(ContextId::new(1), opts)]),
+            Err(Error::ConnectionClosed)
+        ));
+    }
+
+    #[test]
+    fn max_payload_bytes_is_sent_as_hint_and_omitted_turns_parse() {
+        use crate::test_util::{spawn_scripted_server, turn_records_payload_omitting};
+
+        let big = vec![0xC4; 300];
+        let response = turn_records_payload_omitting(&[b"\x91\x01", &big], 64);
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_LAST, response)]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let turns = client
+            .get_last(&ctx, ContextId::new(1), opts.max_payload_bytes(64))
+            .unwrap();
+        assert!(!turns[0].payload_omitted);
+        assert_eq!(turns[0].payload, b"\x91\x01");
+        assert!(turns[1].payload_omitted);
+        assert!(turns[1].payload.is_empty());
+        assert_eq!(turns[1].payload_size, 300);
+        assert_eq!(turns[1].payload_hash, *blake3::hash(&big).as_bytes());
+
+        let requests = handle.join().unwrap();
+        let payload = &requests[0].payload;
+        assert_eq!(payload.len(), 32);
+        // No consistency token: min_sequence and wait_ms are zero placeholders.
+        assert_eq!(&payload[16..28], &[0; 12]);
+        assert_eq!(&payload[28..], &64u32.to_le_bytes());
+    }
+
+    #[test]
+    fn get_last_orders_turns_and_pages_by_turn_id() {
+        use crate::test_util::{spawn_multi_server, turn_page_listing};
+
+        // History is turns 1..=5; the server answers oldest first, like
+        // cxdb-server, honoring limit and before_turn_id.
+        let addr = spawn_multi_server(|req| {
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let before = match req.payload.get(36..44) {
+                Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+                None => 6,
+            };
+            let first = before.saturating_sub(limit).max(1);
+            let payloads = vec![&b"\x90"[..]; (before - first) as usize];
+            (MSG_GET_LAST, turn_page_listing(first, &payloads))
+        });
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let ids =
+            |records: Vec<TurnRecord>| records.iter().map(|r| r.turn_id.get()).collect::<Vec<_>>();
+
+        for (order, expected) in [
+            (Order::OldestFirst, [vec![4, 5], vec![2, 3], vec![1]]),
+            (Order::NewestFirst, [vec![5, 4], vec![3, 2], vec![1]]),
+        ] {
+            let opts = GetLastOptions {
+                limit: 2,
+                ..Default::default()
+            }
+            .order(order);
+            let mut pages = Vec::new();
+            let mut cursor = None;
+            loop {
+                let opts = match cursor {
+                    Some(before) => opts.clone().before(before),
+                    None => opts.clone(),
+                };
+                let page = ids(client.get_last(&ctx, ContextId::new(1), opts).unwrap());
+                let Some(&oldest) = page.iter().min() else {
+                    break;
+                };
+                cursor = Some(TurnId::new(oldest));
+                pages.push(page);
+            }
+            assert_eq!(pages, expected, "{order:?}");
+        }
+        assert_eq!(GetLastOptions::default().order, Order::OldestFirst);
+    }
+
+    /// A server whose history is turns `1..=n`, turn `i` with an
+    /// `i * 10`-byte payload, honoring limit, include_payload and
+    /// before_turn_id like cxdb-server.
+    fn sized_history(n: u64) -> String {
+        use crate::test_util::{spawn_multi_server, turn_page_listing, turn_page_payload};
+
+        spawn_multi_server(move |req| {
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let include_payload = req.payload[12] != 0;
+            let before = match req.payload.get(36..44) {
+                Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()).min(n + 1),
+                None => n + 1,
+            };
+            let first = before.saturating_sub(limit).max(1);
+            let payloads: Vec<Vec<u8>> = (first..before)
+                .map(|id| vec![0x90; id as usize * 10])
+                .collect();
+            let payloads: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+            let page = if include_payload {
+                turn_page_payload(first, &payloads)
+            } else {
+                turn_page_listing(first, &payloads)
+            };
+            (MSG_GET_LAST, page)
+        })
+    }
+
+    #[test]
+    fn byte_budget_trims_the_oldest_turns_in_either_order() {
+        let client = crate::client::dial(&sized_history(6), Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let read = |opts: GetLastOptions| {
+            let page = client.get_last_page(&ctx, ContextId::new(1), opts).unwrap();
+            let ids: Vec<u64> = page.turns.iter().map(|t| t.turn_id.get()).collect();
+            (
+                ids,
+                page.truncated,
+                page.next_before_turn_id.map(TurnId::get),
+            )
+        };
+        let opts = GetLastOptions::default()
+            .include_payload(true)
+            .byte_budget(110);
+
+        // Turns 6 and 5 (60 + 50 bytes) fit; 4 would not.
+        assert_eq!(read(opts.clone()), (vec![5, 6], true, Some(5)));
+        assert_eq!(
+            read(opts.clone().order(Order::NewestFirst)),
+            (vec![6, 5], true, Some(5))
+        );
+        // Continuing from the cursor, the rest (100 bytes) fits.
+        assert_eq!(
+            read(opts.clone().before(TurnId::new(5))),
+            (vec![1, 2, 3, 4], false, None)
+        );
+        // A turn over budget on its own still comes back.
+        assert_eq!(read(opts.clone().byte_budget(10)), (vec![6], true, Some(6)));
+        // Payloads left out by max_payload_bytes count nothing.
+        assert_eq!(
+            read(opts.clone().max_payload_bytes(45).byte_budget(60)),
+            (vec![4, 5, 6], true, Some(4))
+        );
+        let turns = client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        assert_eq!(turns.iter().map(|t| t.payload.len()).sum::<usize>(), 110);
+    }
+
+    #[test]
+    fn byte_budget_and_limit_whichever_binds_first() {
+        let client = crate::client::dial(&sized_history(6), Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let read = |opts: GetLastOptions| {
+            let page = client.get_last_page(&ctx, ContextId::new(1), opts).unwrap();
+            let ids: Vec<u64> = page.turns.iter().map(|t| t.turn_id.get()).collect();
+            (ids, page.truncated)
+        };
+        let opts = GetLastOptions::default().include_payload(true);
+
+        // The limit binds: not truncated, even though more turns exist.
+        assert_eq!(
+            read(opts.clone().limit(2).byte_budget(1_000)),
+            (vec![5, 6], false)
+        );
+        // The budget binds before the limit.
+        assert_eq!(
+            read(opts.clone().limit(4).byte_budget(120)),
+            (vec![5, 6], true)
+        );
+        // Both allow exactly the same turns.
+        assert_eq!(
+            read(opts.clone().limit(2).byte_budget(110)),
+            (vec![5, 6], false)
+        );
+        // No budget: a plain get_last.
+        assert_eq!(read(opts.clone().limit(3)), (vec![4, 5, 6], false));
+
+        let empty = crate::client::dial(&sized_history(0), Vec::new()).unwrap();
+        let page = empty
+            .get_last_page(&ctx, ContextId::new(1), opts.byte_budget(100))
+            .unwrap();
+        assert!(page.turns.is_empty() && !page.truncated);
+    }
+
+    #[test]
+    fn zero_limit_returns_nothing_without_a_request() {
+        use crate::test_util::{spawn_scripted_server, turn_page_payload};
+
+        // One reply, for the one read with a non-zero limit.
+        let (addr, handle) =
+            spawn_scripted_server(vec![(MSG_GET_LAST, turn_page_payload(1, &[&b"\x90"[..]]))]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let zero = GetLastOptions::default().include_payload(true).limit(0);
+
+        assert!(client
+            .get_last(&ctx, ContextId::new(1), zero.clone())
+            .unwrap()
+            .is_empty());
+        assert!(client
+            .get_last_meta(&ctx, ContextId::new(1), zero.clone())
+            .unwrap()
+            .is_empty());
+        let filtered = zero.clone().type_filter("test").max_depth(3);
+        assert!(client
+            .get_last(&ctx, ContextId::new(1), filtered)
+            .unwrap()
+            .is_empty());
+        let page = client
+            .get_last_page(&ctx, ContextId::new(1), zero.clone().byte_budget(1 << 20))
+            .unwrap();
+        assert!(page.turns.is_empty() && !page.truncated);
+
+        let mut records = vec![lazy_record(b"\x90".to_vec())];
+        client
+            .get_last_into(&ctx, ContextId::new(1), zero.clone(), &mut records)
+            .unwrap();
+        assert!(records.is_empty());
+
+        let mut visited = 0;
+        client
+            .for_each_turn(&ctx, ContextId::new(1), zero.clone(), |_| {
+                visited += 1;
+                std::ops::ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(visited, 0);
+
+        let results = client
+            .get_last_many(
+                &ctx,
+                &[
+                    (ContextId::new(1), zero.clone()),
+                    (ContextId::new(2), zero.limit(5)),
+                ],
+            )
+            .unwrap();
+        assert!(results[0].as_ref().unwrap().is_empty());
+        assert_eq!(results[1].as_ref().unwrap().len(), 1);
+
+        client.close().unwrap();
+        let received = handle.join().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(&received[0].payload[..8], &2u64.to_le_bytes());
+        assert_eq!(&received[0].payload[8..12], &5u32.to_le_bytes());
+    }
+
+    #[test]
+    fn turn_meta_and_turn_convert_from_records() {
+        use crate::test_util::turn_page_payload;
+
+        let mut record = parse_turn_records(&turn_page_payload(7, &[&b"\x91\x01"[..]]))
+            .unwrap()
+            .remove(0);
+        record.created_at_unix_ms = Some(1_700_000_000_000);
+        let meta = TurnMeta::from(&record);
+        assert_eq!(
+            (meta.turn_id.get(), meta.type_id.as_str(), meta.payload_len),
+            (7, "test", 2)
+        );
+        assert_eq!(meta.content_hash, record.payload_hash);
+        assert_eq!(meta.created_at, Some(1_700_000_000_000));
+
+        let turn = Turn::from(record);
+        assert_eq!(turn.meta, meta);
+        let (meta, payload): (TurnMeta, Vec<u8>) = turn.into();
+        assert_eq!((meta.depth, payload), (7, b"\x91\x01".to_vec()));
+    }
+
+    #[test]
+    fn get_last_meta_lists_without_payloads() {
+        use crate::test_util::{spawn_scripted_server, turn_listing_payload};
+
+        let page = turn_listing_payload(&[b"\x90", b"\x91\x01"]);
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_LAST, page)]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        }
+        .order(Order::NewestFirst);
+        let metas = client
+            .get_last_meta(&RequestContext::background(), ContextId::new(1), opts)
+            .unwrap();
+        assert_eq!(
+            metas.iter().map(|m| m.turn_id.get()).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(
+            (metas[0].type_id.as_str(), metas[0].payload_len),
+            ("test", 2)
+        );
+
+        // Sent as a listing whatever the options said.
+        let payload = &handle.join().unwrap()[0].payload;
+        assert_eq!(&payload[12..16], &0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn get_last_rejects_pages_from_servers_ignoring_before() {
+        use crate::test_util::{spawn_scripted_server, turn_listing_payload};
+
+        let page = turn_listing_payload(&[b"\x90", b"\x90", b"\x90"]);
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_LAST, page)]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let opts = GetLastOptions::default().before(TurnId::new(3));
+        let err = client
+            .get_last(&RequestContext::background(), ContextId::new(1), opts)
+            .unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)), "got {err:?}");
+
+        let payload = &handle.join().unwrap()[0].payload;
+        assert_eq!(&payload[32..36], &GET_LAST_BEFORE.to_le_bytes());
+        assert_eq!(&payload[36..], &3u64.to_le_bytes());
+    }
+
+    #[test]
+    fn projections_are_sent_and_cleared_on_servers_without_them() {
+        use crate::test_util::{spawn_scripted_server, turn_listing_payload};
+
+        let fields = TurnFields::CONTENT_HASH | TurnFields::DEPTH;
+        let opts = GetLastOptions::default().projection(fields);
+        let payload = encode_get_last_request(ContextId::new(1), &opts, Duration::ZERO).unwrap();
+        assert_eq!(&payload[32..36], &GET_LAST_PROJECTION.to_le_bytes());
+        assert_eq!(&payload[36..], &fields.bits().to_le_bytes());
+        // Payloads cannot be read without their encoding.
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..opts
+        };
+        let payload = encode_get_last_request(ContextId::new(1), &opts, Duration::ZERO).unwrap();
+        assert_eq!(
+            &payload[36..],
+            &(fields | TurnFields::ENCODING).bits().to_le_bytes()
+        );
+        let payload = encode_get_last_request(
+            ContextId::new(1),
+            &GetLastOptions::default(),
+            Duration::ZERO,
+        );
+        assert_eq!(payload.unwrap().len(), 16);
+
+        // The scripted server did not echo FLAG_PROJECTION, so it is asked
+        // for whole records and the client clears the rest.
+        let page = turn_listing_payload(&[b"\x90", b"\x91\x01"]);
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_LAST, page)]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let opts = GetLastOptions::default().projection(TurnFields::CONTENT_HASH);
+        let turns = client
+            .get_last(&RequestContext::background(), ContextId::new(1), opts)
+            .unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[1].turn_id, TurnId::new(2));
+        assert_eq!(turns[1].payload_hash, *blake3::hash(b"\x91\x01").as_bytes());
+        assert_eq!(
+            (
+                turns[1].parent_id,
+                turns[1].depth,
+                turns[1].type_id.as_str()
+            ),
+            (TurnId::new(0), 0, "")
+        );
+        assert_eq!((turns[1].encoding, turns[1].payload_size), (0, 0));
+        assert_eq!(handle.join().unwrap()[0].payload.len(), 16);
+    }
+
+    #[test]
+    fn projected_records_parse_with_only_their_fields() {
+        // turn_id, then depth and the hash, then a trailer timestamp.
+        let mut payload = 2u32.to_le_bytes().to_vec();
+        for turn_id in [7u64, 8] {
+            payload.extend_from_slice(&turn_id.to_le_bytes());
+            payload.extend_from_slice(&(turn_id as u32 * 10).to_le_bytes());
+            payload.extend_from_slice(&[turn_id as u8; 32]);
+        }
+        // Empty writer and expiry trailers.
+        payload.extend_from_slice(&[0u8; 8]);
+        payload.extend_from_slice(&2u32.to_le_bytes());
+        payload.extend_from_slice(&5u64.to_le_bytes());
+        payload.extend_from_slice(&6u64.to_le_bytes());
+
+        let wire =
+            GetLastOptions::default().projection(TurnFields::DEPTH | TurnFields::CONTENT_HASH);
+        let turns = parse_get_last(&payload, &wire).unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!((turns[0].turn_id.get(), turns[0].depth), (7, 70));
+        assert_eq!(turns[1].payload_hash, [8; 32]);
+        assert_eq!(turns[1].type_id, "");
+        assert_eq!(turns[1].created_at_unix_ms, Some(6));
+
+        // The same bytes are too short for whole records.
+        assert!(parse_get_last(&payload, &GetLastOptions::default()).is_err());
+    }
+
+    #[test]
+    fn depth_filters_page_back_on_servers_without_them() {
+        use crate::test_util::{spawn_multi_server, turn_page_listing};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // History is turns 1..=100, each at depth == turn_id; the server
+        // ignores depth ranges.
+        let reads = Arc::new(AtomicUsize::new(0));
+        let addr = spawn_multi_server({
+            let reads = reads.clone();
+            move |req| {
+                reads.fetch_add(1, Ordering::SeqCst);
+                assert!(req.payload.len() < 36 || req.payload[32] & 8 == 0);
+                let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+                let before = if req.payload.len() >= 44 {
+                    u64::from_le_bytes(req.payload[36..44].try_into().unwrap())
+                } else {
+                    101
+                };
+                let first = before.saturating_sub(limit).max(1);
+                let payloads = vec![&b"\x90"[..]; (before - first) as usize];
+                (MSG_GET_LAST, turn_page_listing(first, &payloads))
+            }
+        });
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let depths = |turns: Vec<TurnRecord>| turns.iter().map(|t| t.depth).collect::<Vec<_>>();
+
+        let opts = GetLastOptions {
+            limit: 3,
+            ..Default::default()
+        };
+        let top = client
+            .get_last(&ctx, ContextId::new(1), opts.max_depth(40))
+            .unwrap();
+        assert_eq!(depths(top), [38, 39, 40]);
+        assert_eq!(reads.swap(0, Ordering::SeqCst), 21);
+
+        // The walk ends at the first turn below min_depth.
+        let opts = GetLastOptions::default().min_depth(98);
+        let recent = client.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        assert_eq!(depths(recent), [98, 99, 100]);
+        assert_eq!(reads.swap(0, Ordering::SeqCst), 1);
+
+        let opts = GetLastOptions::default()
+            .min_depth(3)
+            .max_depth(5)
+            .order(Order::NewestFirst);
+        let mut into = Vec::new();
+        client
+            .get_last_into(&ctx, ContextId::new(1), opts.clone(), &mut into)
+            .unwrap();
+        assert_eq!(depths(into), [5, 4, 3]);
+        let many = client
+            .get_last_many(
+                &ctx,
+                &[
+                    (ContextId::new(1), opts),
+                    (ContextId::new(1), GetLastOptions::default()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(many[0].as_ref().unwrap().len(), 3);
+        assert_eq!(many[1].as_ref().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn type_filters_page_back_until_limit_turns_match() {
+        use crate::test_util::{spawn_multi_server, typed_turn_page_listing};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // History is turns 1..=100; every tenth is a tool call.
+        let reads = Arc::new(AtomicUsize::new(0));
+        let addr = spawn_multi_server({
+            let reads = reads.clone();
+            move |req| {
+                reads.fetch_add(1, Ordering::SeqCst);
+                let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+                let before = if req.payload.len() >= 44 {
+                    u64::from_le_bytes(req.payload[36..44].try_into().unwrap())
+                } else {
+                    101
+                };
+                let first = before.saturating_sub(limit).max(1);
+                let turns: Vec<_> = (first..before)
+                    .map(|id| {
+                        let type_id = if id % 10 == 0 { "app.Tool" } else { "app.Msg" };
+                        (type_id, &b"\x90"[..])
+                    })
+                    .collect();
+                (MSG_GET_LAST, typed_turn_page_listing(first, &turns))
+            }
+        });
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let opts = GetLastOptions::default().limit(3).type_filter("app.Tool");
+        let tools = client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        let ids: Vec<u64> = tools.iter().map(|t| t.turn_id.get()).collect();
+        assert_eq!(ids, [80, 90, 100]);
+        assert!(tools.iter().all(|t| t.type_id == "app.Tool"));
+        assert_eq!(reads.swap(0, Ordering::SeqCst), 7);
+
+        // The type is read to filter on, then cleared with the projection.
+        let hashes = client
+            .get_last_meta(
+                &ctx,
+                ContextId::new(1),
+                opts.projection(TurnFields::CONTENT_HASH),
+            )
+            .unwrap();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes[0].type_id, "");
+
+        let opts = GetLastOptions::default().type_filter("app.Missing");
+        assert!(client
+            .get_last(&ctx, ContextId::new(1), opts)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn turn_at_depth_resolves_on_the_head_line() {
+        use crate::protocol::MSG_GET_HEAD;
+        use crate::test_util::{spawn_multi_server, turn_page_payload};
+
+        // History is turns 1..=100, each at depth == turn_id.
+        let addr = spawn_multi_server(|req| {
+            if req.header.msg_type == MSG_GET_HEAD {
+                let mut head = 1u64.to_le_bytes().to_vec();
+                head.extend_from_slice(&100u64.to_le_bytes());
+                head.extend_from_slice(&100u32.to_le_bytes());
+                return (MSG_GET_HEAD, head);
+            }
+            // Every read asks for compacted and expired turns too.
+            assert_eq!(req.payload[32] & 3, 3);
+            let limit = u32::from_le_bytes(req.payload[8..12].try_into().unwrap()) as u64;
+            let before = if req.payload.len() >= 44 {
+                u64::from_le_bytes(req.payload[36..44].try_into().unwrap())
+            } else {
+                101
+            };
+            let first = before.saturating_sub(limit).max(1);
+            let payloads = vec![&b"\x90"[..]; (before - first) as usize];
+            (MSG_GET_LAST, turn_page_payload(first, &payloads))
+        });
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let turn = client
+            .get_turn_at_depth(&ctx, ContextId::new(1), 97, GetTurnOptions::default())
+            .unwrap();
+        assert_eq!((turn.turn_id.get(), turn.depth), (97, 97));
+        assert_eq!(turn.payload, b"\x90");
+        let turn = client
+            .get_turn_at_depth(&ctx, ContextId::new(1), 100, GetTurnOptions::default())
+            .unwrap();
+        assert_eq!(turn.turn_id, TurnId::new(100));
+
+        for depth in [101, u64::from(u32::MAX) + 1] {
+            let err = client
+                .get_turn_at_depth(&ctx, ContextId::new(1), depth, GetTurnOptions::default())
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::DepthOutOfRange {
+                        context_id,
+                        depth: d,
+                        head_depth: 100
+                    } if context_id.get() == 1 && d == depth
+                ),
+                "{err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn depth_ranges_are_sent_and_checked() {
+        let opts = GetLastOptions::default().max_depth(4);
+        let payload = encode_get_last_request(ContextId::new(1), &opts, Duration::ZERO).unwrap();
+        assert_eq!(&payload[32..36], &GET_LAST_DEPTH_RANGE.to_le_bytes());
+        assert_eq!(&payload[36..40], &0u32.to_le_bytes());
+        assert_eq!(&payload[40..], &4u32.to_le_bytes());
+
+        // A server that claims to filter must not return turns out of range.
+        let mut records = parse_turn_records(&turn_records_payload(&[&b"\x90"[..]; 5])).unwrap();
+        let err = finish_records(&mut records, &opts).unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)), "{err:?}");
+        assert!(finish_records(&mut records, &opts.max_depth(5)).is_ok());
+    }
+
+    #[test]
+    fn compaction_requests_round_trip() {
+        use crate::protocol::{MSG_CTX_COMPACT, MSG_GET_TURN};
+        use crate::test_util::{spawn_scripted_server, turn_listing_payload, turn_records_payload};
+
+        let mut ack = 1u64.to_le_bytes().to_vec();
+        ack.extend_from_slice(&9u64.to_le_bytes());
+        ack.extend_from_slice(&6u32.to_le_bytes());
+        ack.extend_from_slice(&[0u8; 32]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_CTX_COMPACT, ack),
+            (MSG_GET_LAST, turn_listing_payload(&[b"\x91\x01"])),
+            (MSG_GET_TURN, turn_records_payload(&[b"\x91\x02"])),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let summary = CompactRequest::new(
+            TurnId::new(4),
+            "com.example.Summary",
+            1,
+            b"\x91\x03".to_vec(),
+        );
+        let result = client
+            .compact_context(&ctx, ContextId::new(1), summary.clone())
+            .unwrap();
+        assert_eq!(result.turn_id, TurnId::new(9));
+        assert_eq!(result.depth, 6);
+
+        let opts = GetLastOptions::default().include_compacted(true);
+        client.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        let turn = client.get_turn(&ctx, TurnId::new(2)).unwrap();
+        assert_eq!(turn.payload, b"\x91\x02");
+
+        let requests = handle.join().unwrap();
+        // CTX_COMPACT is up_to_turn_id followed by an APPEND_TURN body.
+        let mut expected = 4u64.to_le_bytes().to_vec();
+        let append = AppendRequest::new(
+            ContextId::new(1),
+            summary.summary_type_id,
+            summary.summary_type_version,
+            summary.summary_payload,
+        );
+        encode_append_request(&mut expected, &append, None).unwrap();
+        assert_eq!(requests[0].payload, expected);
+
+        // include_compacted rides after the positional trailer fields.
+        let payload = &requests[1].payload;
+        assert_eq!(payload.len(), 36);
+        assert_eq!(&payload[28..32], &u32::MAX.to_le_bytes());
+        assert_eq!(&payload[32..], &GET_LAST_INCLUDE_COMPACTED.to_le_bytes());
+
+        let mut expected = 2u64.to_le_bytes().to_vec();
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        assert_eq!(requests[2].payload, expected);
+    }
+
+    #[test]
+    fn writer_stamps_round_trip() {
+        use crate::protocol::MSG_ERROR;
+        use crate::test_util::{error_payload, spawn_scripted_server};
+
+        let mut ack = 1u64.to_le_bytes().to_vec();
+        ack.extend_from_slice(&9u64.to_le_bytes());
+        ack.extend_from_slice(&2u32.to_le_bytes());
+        ack.extend_from_slice(&[0u8; 32]);
+
+        let mut conflict = error_payload(409, "writer sequence");
+        conflict.extend_from_slice(&0u32.to_le_bytes());
+        conflict.extend_from_slice(&3u32.to_le_bytes());
+        for part in ["writer_id", "agent-a", "last_seq", "7", "writer_seq", "7"] {
+            conflict.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            conflict.extend_from_slice(part.as_bytes());
+        }
+
+        // Second item is stamped; the trailer names it by index.
+        let mut records = turn_records_payload(&[b"\x91\x01", b"\x91\x02"]);
+        records.extend_from_slice(&1u32.to_le_bytes());
+        records.extend_from_slice(&1u32.to_le_bytes());
+        records.extend_from_slice(&7u32.to_le_bytes());
+        records.extend_from_slice(b"agent-a");
+        records.extend_from_slice(&7u64.to_le_bytes());
+
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_APPEND_TURN, ack),
+            (MSG_ERROR, conflict),
+            (MSG_GET_LAST, records.clone()),
+            (MSG_GET_LAST, records),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let req = AppendRequest::new(
+            ContextId::new(1),
+            "com.example.Message",
+            1,
+            b"\x91\x03".to_vec(),
+        )
+        .writer_id("agent-a")
+        .writer_seq(7);
+        client.append_turn(&ctx, &req).unwrap();
+        match client.append_turn(&ctx, &req).unwrap_err() {
+            Error::WriterSequenceConflict {
+                writer_id,
+                last_seq,
+                writer_seq,
+            } => {
+                assert_eq!(writer_id, "agent-a");
+                assert_eq!((last_seq, writer_seq), (7, 7));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+        let turns = client
+            .get_last(&ctx, ContextId::new(1), opts.clone())
+            .unwrap();
+        assert_eq!(turns[0].writer_id, None);
+        assert_eq!(turns[1].writer_id.as_deref(), Some("agent-a"));
+        assert_eq!(turns[1].writer_seq, 7);
+
+        // Reused records drop stale stamps and pick up new ones.
+        let mut reused = vec![turns[1].clone(), turns[1].clone()];
+        client
+            .get_last_into(&ctx, ContextId::new(1), opts, &mut reused)
+            .unwrap();
+        assert_eq!(reused, turns);
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.flags, APPEND_FLAG_WRITER);
+        let mut expected = Vec::new();
+        let flags = encode_append_request(&mut expected, &req, None).unwrap();
+        assert_eq!(flags, APPEND_FLAG_WRITER);
+        assert_eq!(requests[0].payload, expected);
+        let mut tail = 7u32.to_le_bytes().to_vec();
+        tail.extend_from_slice(b"agent-a");
+        tail.extend_from_slice(&7u64.to_le_bytes());
+        assert!(expected.ends_with(&tail));
+    }
+
+    #[test]
+    fn builder_encodes_like_new_with_setters() {
+        let built = AppendRequest::builder(ContextId::new(7), "chat.Message")
+            .version(2)
+            .payload(vec![0x81, 0xa1, b'a', 0x01])
+            .parent_turn_id(TurnId::new(5))
+            .idempotency_key("msg-42")
+            .writer_id("agent-a")
+            .writer_seq(3)
+            .ttl(Duration::from_secs(60))
+            .link_to(TurnId::new(4), LinkKind::RepliesTo)
+            .build();
+        let mut set = AppendRequest::new(
+            ContextId::new(7),
+            "chat.Message",
+            2,
+            vec![0x81, 0xa1, b'a', 0x01],
+        )
+        .writer_id("agent-a")
+        .writer_seq(3)
+        .ttl(Duration::from_secs(60))
+        .link_to(TurnId::new(4), LinkKind::RepliesTo);
+        set.parent_turn_id = TurnId::new(5);
+        set.idempotency_key = b"msg-42".to_vec();
+
+        let (mut built_bytes, mut set_bytes) = (Vec::new(), Vec::new());
+        let built_flags = encode_append_request(&mut built_bytes, &built, None).unwrap();
+        let set_flags = encode_append_request(&mut set_bytes, &set, None).unwrap();
+        assert_eq!(built_flags, set_flags);
+        assert_eq!(built_bytes, set_bytes);
+
+        let minimal = AppendRequest::builder(ContextId::new(7), "chat.Message").build();
+        assert_eq!(minimal.type_version, 1);
+        assert!(minimal.payload.is_empty() && minimal.idempotency_key.is_empty());
+        assert_eq!(minimal.encoding, ENCODING_MSGPACK);
+    }
+
+    #[test]
+    fn ttls_and_expiry_markers_round_trip() {
+        use crate::test_util::spawn_scripted_server;
+
+        let mut ack = 1u64.to_le_bytes().to_vec();
+        ack.extend_from_slice(&9u64.to_le_bytes());
+        ack.extend_from_slice(&2u32.to_le_bytes());
+        ack.extend_from_slice(&[0u8; 32]);
+
+        // No writer stamps (count 0), then the second item's expiry. Turn ids
+        // and depths have gaps where expired turns were skipped.
+        let mut records = crate::test_util::turn_listing_payload(&[b"\x91\x01", b"\x91\x02"]);
+        records.extend_from_slice(&0u32.to_le_bytes());
+        records.extend_from_slice(&1u32.to_le_bytes());
+        records.extend_from_slice(&1u32.to_le_bytes());
+        records.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        records.push(1);
+
+        let (addr, handle) =
+            spawn_scripted_server(vec![(MSG_APPEND_TURN, ack), (MSG_GET_LAST, records)]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let req = AppendRequest::new(
+            ContextId::new(1),
+            "com.example.Message",
+            1,
+            b"\x91\x03".to_vec(),
+        )
+        .ttl(Duration::from_micros(1500));
+        client.append_turn(&ctx, &req).unwrap();
+        let opts = GetLastOptions::default().include_expired(true);
+        let turns = client.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        assert_eq!(turns[0].expires_at_unix_ms, None);
+        assert!(!turns[0].expired);
+        assert_eq!(turns[1].expires_at_unix_ms, Some(1_700_000_000_000));
+        assert!(turns[1].expired);
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].header.flags, APPEND_FLAG_TTL);
+        // Rounded up to whole milliseconds.
+        assert!(requests[0].payload.ends_with(&2u64.to_le_bytes()));
+        let flags = u32::from_le_bytes(requests[1].payload[32..36].try_into().unwrap());
+        assert_eq!(flags, GET_LAST_INCLUDE_EXPIRED);
+    }
+
+    #[test]
+    fn timestamp_trailer_sets_created_at() {
+        // Empty writer and expiry trailers, then one timestamp per record.
+        let mut payload = turn_records_payload(&[b"\x91\x01", b"\x91\x02"]);
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&2u32.to_le_bytes());
+        payload.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        payload.extend_from_slice(&1_700_000_000_250u64.to_le_bytes());
+
+        let turns = parse_turn_records(&payload).unwrap();
+        assert_eq!(turns[0].created_at_unix_ms, Some(1_700_000_000_000));
+        assert_eq!(turns[1].created_at_unix_ms, Some(1_700_000_000_250));
+        assert!(turns[0].writer_id.is_none() && turns[1].expires_at_unix_ms.is_none());
+
+        let mut short = turn_records_payload(&[b"\x91\x01", b"\x91\x02"]);
+        short.extend_from_slice(&[0; 8]);
+        short.extend_from_slice(&1u32.to_le_bytes());
+        short.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        assert!(parse_turn_records(&short).is_err());
+    }
+
+    #[test]
+    fn redaction_trailer_marks_turns_redacted() {
+        // Empty writer and expiry trailers, timestamps, then the first
+        // record's redaction.
+        let mut payload = turn_records_payload(&[b"", b"\x91\x02"]);
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&2u32.to_le_bytes());
+        payload.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        payload.extend_from_slice(&1_700_000_000_250u64.to_le_bytes());
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&1_700_000_009_000u64.to_le_bytes());
+        payload.extend_from_slice(&3u32.to_le_bytes());
+        payload.extend_from_slice(b"pii");
+
+        let turns = parse_turn_records(&payload).unwrap();
+        assert!(turns[0].redacted);
+        assert_eq!(turns[0].redacted_at_unix_ms, Some(1_700_000_009_000));
+        assert_eq!(turns[0].redaction_reason.as_deref(), Some("pii"));
+        let err = turns[0].decode::<serde_json::Value>().unwrap_err();
+        assert!(
+            matches!(err, Error::Redacted { turn_id } if turn_id.get() == 1),
+            "{err:?}"
+        );
+        assert!(!turns[1].redacted && turns[1].redaction_reason.is_none());
+        assert!(turns[1].decode::<serde_json::Value>().is_ok());
+    }
+
+    #[test]
+    fn max_payload_bytes_is_enforced_when_server_ignores_hint() {
+        use crate::test_util::spawn_scripted_server;
+
+        let big = vec![0xC4; 300];
+        let response = turn_records_payload(&[b"\x91\x01", &big]);
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_GET_LAST, response)]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let turns = client
+            .get_last(&ctx, ContextId::new(1), opts.max_payload_bytes(64))
+            .unwrap();
+        assert_eq!(turns[0].payload, b"\x91\x01");
+        assert!(turns[1].payload_omitted);
+        assert!(turns[1].payload.is_empty());
+        assert_eq!(turns[1].payload_size, 300);
+
+        let lazy = turns[1].clone().into_lazy();
+        let err = lazy.get::<rmpv::Value>().unwrap_err();
+        assert!(matches!(err, Error::Decode { message: msg, .. } if msg.contains("omitted")));
+        handle.join().unwrap();
+    }
+
+    fn lazy_record(payload: Vec<u8>) -> TurnRecord {
+        TurnRecord {
+            turn_id: TurnId::new(1),
+            parent_id: TurnId::new(0),
+            depth: 1,
+            type_id: "test".into(),
+            type_version: 1,
+            encoding: ENCODING_MSGPACK,
+            compression: COMPRESSION_NONE,
+            payload_hash: *blake3::hash(&payload).as_bytes(),
+            payload_size: payload.len() as u32,
+            payload_omitted: false,
+            payload,
+            writer_id: None,
+            writer_seq: 0,
+            expires_at_unix_ms: None,
+            expired: false,
+            created_at_unix_ms: None,
+            redacted: false,
+            redacted_at_unix_ms: None,
+            redaction_reason: None,
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn get_last_into_reuses_record_allocations() {
+        use crate::test_util::spawn_scripted_server;
+
+        let big = vec![0xAAu8; 256];
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, turn_records_payload(&[&big, &big])),
+            (
+                MSG_GET_LAST,
+                turn_records_payload(&[b"\x01", b"\x02", b"\x03"]),
+            ),
+            (MSG_GET_LAST, turn_records_payload(&[b"\x04"])),
+            (MSG_GET_LAST, vec![0, 0]),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+
+        let mut records = Vec::new();
+        client
+            .get_last_into(&ctx, ContextId::new(1), opts.clone(), &mut records)
+            .unwrap();
+        assert_eq!(records, client_records(&[&big, &big]));
+        let first_payload = records[0].payload.as_ptr();
+
+        // Existing records are overwritten in place and the Vec grows.
+        client
+            .get_last_into(&ctx, ContextId::new(1), opts.clone(), &mut records)
+            .unwrap();
+        assert_eq!(records, client_records(&[b"\x01", b"\x02", b"\x03"]));
+        assert_eq!(records[0].payload.as_ptr(), first_payload);
+        assert!(records[0].payload.capacity() >= big.len());
+
+        // Surplus records are dropped.
+        client
+            .get_last_into(&ctx, ContextId::new(1), opts.clone(), &mut records)
+            .unwrap();
+        assert_eq!(records, client_records(&[b"\x04"]));
+
+        let err = client
+            .get_last_into(&ctx, ContextId::new(1), opts, &mut records)
+            .unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)), "got {err:?}");
+        handle.join().unwrap();
+    }
+
+    /// What [`Client::get_last`] returns for [`turn_records_payload`].
+    fn client_records(payloads: &[&[u8]]) -> Vec<TurnRecord> {
+        parse_turn_records(&turn_records_payload(payloads)).unwrap()
+    }
+
+    #[test]
+    fn lazy_turn_decodes_once_and_caches() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Note {
+            #[serde(rename = "1")]
+            text: String,
+        }
+
+        let payload = crate::encoding::encode_msgpack(&Note { text: "hi".into() }).unwrap();
+        let turn = lazy_record(payload).into_lazy();
+        assert_eq!(turn.turn_id, TurnId::new(1));
+        assert!(!turn.is_decoded::<Note>());
+
+        let first = turn.get::<Note>().unwrap();
+        assert_eq!(first.text, "hi");
+        assert!(turn.is_decoded::<Note>());
+        assert!(Arc::ptr_eq(&first, &turn.get::<Note>().unwrap()));
+
+        // Another target type decodes independently of the cached one.
+        let map = turn
+            .get::<std::collections::BTreeMap<String, String>>()
+            .unwrap();
+        assert_eq!(map.get("1").map(String::as_str), Some("hi"));
+    }
+
+    #[test]
+    fn lazy_turn_surfaces_decode_errors_at_get() {
+        let turn = LazyTurn::from(lazy_record(vec![0xc1]));
+        assert_eq!(turn.raw(), &[0xc1]);
+        assert!(matches!(turn.get::<u64>(), Err(Error::Decode { .. })));
+        assert!(!turn.is_decoded::<u64>());
+
+        let mut compressed = lazy_record(vec![0x01]);
+        compressed.compression = crate::protocol::COMPRESSION_ZSTD;
+        let err = compressed.into_lazy().get::<u64>().unwrap_err();
+        assert!(matches!(err, Error::Decode { message: msg, .. } if msg.contains("compression")));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_turns_round_trip_through_append_and_get_last() {
+        use crate::encoding::encode_cbor;
+        use crate::protocol::{ENCODING_CBOR, MSG_APPEND_TURN};
+        use crate::test_util::spawn_scripted_server;
+
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Reading {
+            sensor: String,
+            values: Vec<f64>,
+            raw: Option<serde_bytes::ByteBuf>,
+        }
+
+        let reading = Reading {
+            sensor: "t1".into(),
+            values: vec![20.5, -3.0],
+            raw: Some(serde_bytes::ByteBuf::from(vec![0, 1, 2])),
+        };
+        let payload = encode_cbor(&reading).unwrap();
+        // The stored turn comes back with the encoding it was appended with;
+        // offset 36 is the first record's `encoding` field.
+        let mut stored = turn_records_payload(&[&payload]);
+        stored[36..40].copy_from_slice(&ENCODING_CBOR.to_le_bytes());
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_APPEND_TURN, vec![0u8; 52]),
+            (MSG_GET_LAST, stored),
+        ]);
+        let client = crate::dial(&addr, []).unwrap();
+        let ctx = RequestContext::background();
+
+        let req = AppendRequest::new(ContextId::new(1), "test.Reading", 1, payload)
+            .encoding(ENCODING_CBOR);
+        client.append_turn(&ctx, &req).unwrap();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+        let turns = client.get_last(&ctx, ContextId::new(1), opts).unwrap();
+        assert_eq!(turns[0].encoding, ENCODING_CBOR);
+        assert_eq!(turns[0].decode::<Reading>().unwrap(), reading);
+
+        let requests = handle.join().unwrap();
+        let type_id_end = 20 + "test.Reading".len();
+        let encoding = &requests[0].payload[type_id_end + 4..type_id_end + 8];
+        assert_eq!(encoding, ENCODING_CBOR.to_le_bytes());
+    }
+
+    #[test]
+    fn turns_display_on_one_line() {
+        let mut record = lazy_record(Vec::new());
+        record.turn_id = TurnId::new(42);
+        record.depth = 7;
+        record.type_id = "chat.Message".into();
+        record.type_version = 2;
+        let hash = record.hash_hex();
+        assert_eq!(hash.len(), 64);
+        let expected = format!("turn 42 depth 7 chat.Message v2 {}", &hash[..8]);
+        assert_eq!(record.to_string(), expected);
+        assert_eq!(record.summary(), expected);
+        assert_eq!(format!("{record:#}"), format!("{expected} (0 bytes)"));
+
+        record.redacted = true;
+        record.expired = true;
+        let (header, _) = record.split_payload();
+        assert_eq!(
+            format!("{header:#}"),
+            format!("{expected} (0 bytes, redacted, expired)")
+        );
+    }
+
+    #[test]
+    fn unknown_encodings_keep_raw_bytes() {
+        let mut record = lazy_record(vec![0xf6]);
+        record.encoding = 9;
+        assert!(matches!(
+            record.decode::<rmpv::Value>(),
+            Err(Error::UnsupportedEncoding(9))
+        ));
+        assert_eq!(record.payload, [0xf6]);
+
+        // CBOR is unknown unless the `cbor` feature is on.
+        record.encoding = crate::protocol::ENCODING_CBOR;
+        let decoded = record.decode::<Option<u8>>();
+        if cfg!(feature = "cbor") {
+            assert_eq!(decoded.unwrap(), None);
+        } else {
+            assert!(matches!(decoded, Err(Error::UnsupportedEncoding(2))));
+        }
+    }
+
+    #[test]
+    fn lazy_turn_enforces_decode_depth() {
+        let mut deep = vec![0x91; 64];
+        deep.push(0x00);
+        let turn = lazy_record(deep.clone()).into_lazy();
+        assert!(turn.get::<rmpv::Value>().is_ok());
+
+        let turn = LazyTurn::new(lazy_record(deep)).with_max_decode_depth(16);
+        assert!(matches!(
+            turn.get::<rmpv::Value>(),
+            Err(Error::Decode { .. })
+        ));
+    }
 }
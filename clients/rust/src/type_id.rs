@@ -0,0 +1,168 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Shared `type_id` strings.
+//!
+//! A context usually holds a handful of turn types, so a page of 10k turns
+//! repeats the same few `type_id`s thousands of times. Records read from
+//! the server hold a [`TypeId`], a refcounted string, and the client
+//! interns them: each distinct `type_id` is allocated once per thread and
+//! shared by every turn that carries it, across responses.
+//!
+//! [`TypeId`] derefs to `str` and compares with `str` and `String`, so most
+//! code reads it as it would a `String`.
+
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Distinct `type_id`s kept per thread; past this the cache starts over,
+/// so a reader of many ad hoc types does not grow it without bound.
+const MAX_INTERNED: usize = 1024;
+
+/// Type ids longer than this are not interned.
+const MAX_INTERNED_LEN: usize = 256;
+
+thread_local! {
+    static INTERNED: RefCell<HashSet<Arc<str>>> = RefCell::new(HashSet::new());
+}
+
+/// A turn's declared type, e.g. `"cxdb.ConversationItem"`. Cheap to clone:
+/// turns of the same type share one allocation.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TypeId(Arc<str>);
+
+impl TypeId {
+    /// The interned `TypeId` for `type_id`, allocating only the first time
+    /// this thread sees it.
+    pub fn intern(type_id: &str) -> Self {
+        if type_id.is_empty() || type_id.len() > MAX_INTERNED_LEN {
+            return Self(Arc::from(type_id));
+        }
+        INTERNED.with(|interned| {
+            let mut interned = interned.borrow_mut();
+            if let Some(shared) = interned.get(type_id) {
+                return Self(shared.clone());
+            }
+            if interned.len() >= MAX_INTERNED {
+                interned.clear();
+            }
+            let shared: Arc<str> = Arc::from(type_id);
+            interned.insert(shared.clone());
+            Self(shared)
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for TypeId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for TypeId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for TypeId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for TypeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for TypeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for TypeId {
+    fn from(type_id: &str) -> Self {
+        Self::intern(type_id)
+    }
+}
+
+impl From<String> for TypeId {
+    fn from(type_id: String) -> Self {
+        Self::intern(&type_id)
+    }
+}
+
+impl From<TypeId> for String {
+    fn from(type_id: TypeId) -> Self {
+        type_id.0.to_string()
+    }
+}
+
+impl PartialEq<str> for TypeId {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for TypeId {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for TypeId {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<TypeId> for str {
+    fn eq(&self, other: &TypeId) -> bool {
+        self == &*other.0
+    }
+}
+
+impl PartialEq<TypeId> for &str {
+    fn eq(&self, other: &TypeId) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<TypeId> for String {
+    fn eq(&self, other: &TypeId) -> bool {
+        **self == *other.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_type_ids_share_one_allocation() {
+        let a = TypeId::intern("cxdb.Msg");
+        let b = TypeId::from("cxdb.Msg".to_string());
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert!(!Arc::ptr_eq(&a.0, &TypeId::intern("cxdb.Tool").0));
+
+        assert_eq!(a, "cxdb.Msg");
+        assert_eq!("cxdb.Msg".to_string(), a);
+        assert_eq!(a.as_str(), "cxdb.Msg");
+        assert_eq!(format!("{a} {a:?}"), "cxdb.Msg \"cxdb.Msg\"");
+        assert!(a.starts_with("cxdb."));
+        assert!(TypeId::default().is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed append and read helpers.
+//!
+//! Implementing [`CxdbType`] ties a Rust type to its registry type id and
+//! version, so [`Client::append_typed`], [`Client::get_last_typed`] and
+//! [`TurnRecord::decode_as`] keep the declared type in sync with the value
+//! being stored. A [`TypeRegistry`] collects the types a program stores and,
+//! installed as its [validator](crate::validate), refuses appends under any
+//! other type id.
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, RequestContext};
+use crate::encoding::encode_msgpack;
+use crate::error::{Error, Result};
+use crate::ids::ContextId;
+use crate::turn::{AppendRequest, AppendResult, GetLastOptions, TurnHeader, TurnRecord};
+use crate::validate::{decodes_as, DecodeCheck, TurnValidator, ValidationError};
+
+/// A Rust type stored in CXDB under a fixed type id and version.
+///
+/// ```
+/// use cxdb::CxdbType;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Message {
+///     #[serde(rename = "1")]
+///     text: String,
+/// }
+///
+/// impl CxdbType for Message {
+///     const TYPE_ID: &'static str = "com.example.Message";
+///     const TYPE_VERSION: u32 = 1;
+/// }
+/// ```
+pub trait CxdbType {
+    /// Declared type id, e.g. `"com.example.Message"`.
+    const TYPE_ID: &'static str;
+    /// Declared type version written with each append.
+    const TYPE_VERSION: u32;
+    /// Older type ids whose payloads also decode as this type.
+    const TYPE_ID_ALIASES: &'static [&'static str] = &[];
+
+    /// Whether a turn declared as `type_id` holds this type.
+    fn matches(type_id: &str) -> bool {
+        type_id == Self::TYPE_ID || Self::TYPE_ID_ALIASES.contains(&type_id)
+    }
+}
+
+/// Encodes `value` into an append request for `context_id`.
+pub(crate) fn typed_append_request<T: CxdbType + Serialize>(
+    context_id: ContextId,
+    value: &T,
+) -> Result<AppendRequest> {
+    Ok(AppendRequest::new(
+        context_id,
+        T::TYPE_ID,
+        T::TYPE_VERSION,
+        encode_msgpack(value)?,
+    ))
+}
+
+/// Keeps the records declared as `T` and decodes their payloads.
+pub(crate) fn decode_typed<T: CxdbType + DeserializeOwned>(
+    records: Vec<TurnRecord>,
+    max_decode_depth: usize,
+) -> Result<Vec<(TurnHeader, T)>> {
+    records
+        .into_iter()
+        .filter(|record| T::matches(&record.type_id))
+        .map(|record| {
+            let value = record.decode_with_max_depth(max_decode_depth)?;
+            Ok((record.split_payload().0, value))
+        })
+        .collect()
+}
+
+impl<P: AsRef<[u8]>> TurnRecord<P> {
+    /// Decodes the payload as `T`, like [`TurnRecord::decode`], after
+    /// checking the turn is declared as `T` (or one of its aliases).
+    ///
+    /// A turn of another type returns [`Error::Decode`].
+    pub fn decode_as<T: CxdbType + DeserializeOwned>(&self) -> Result<T> {
+        if !T::matches(&self.type_id) {
+            return Err(Error::decode(format!(
+                "turn {} is {}, not {}",
+                self.turn_id,
+                self.type_id,
+                T::TYPE_ID
+            )));
+        }
+        self.decode()
+    }
+}
+
+/// A [`CxdbType`] as recorded in a [`TypeRegistry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisteredType {
+    pub type_id: &'static str,
+    pub type_version: u32,
+    /// The Rust type's name, from [`std::any::type_name`].
+    pub rust_type: &'static str,
+}
+
+/// The [`CxdbType`]s a program stores, by type id and alias.
+///
+/// As a [`TurnValidator`] it refuses payloads declared under any type id it
+/// does not know, so a mistyped id fails at append rather than mis-tagging
+/// the turn, and checks that payloads declared at a registered type's
+/// version decode as that type (like [`TypeValidator`]).
+///
+/// ```
+/// use cxdb::{CxdbType, TypeRegistry};
+/// # #[derive(serde::Deserialize)]
+/// # struct Message { text: String }
+/// # impl CxdbType for Message {
+/// #     const TYPE_ID: &'static str = "com.example.Message";
+/// #     const TYPE_VERSION: u32 = 1;
+/// # }
+///
+/// let registry = TypeRegistry::new().register::<Message>();
+/// assert_eq!(registry.get("com.example.Message").unwrap().type_version, 1);
+/// ```
+///
+/// [`TypeValidator`]: crate::validate::TypeValidator
+#[derive(Debug, Clone, Default)]
+pub struct TypeRegistry {
+    /// Keyed by type id and by each alias.
+    types: BTreeMap<&'static str, (RegisteredType, DecodeCheck)>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `T` under its type id and aliases. Registering a type again is
+    /// a no-op.
+    ///
+    /// # Panics
+    ///
+    /// If another Rust type already claims one of `T`'s ids.
+    pub fn register<T: CxdbType + DeserializeOwned>(mut self) -> Self {
+        let registered = RegisteredType {
+            type_id: T::TYPE_ID,
+            type_version: T::TYPE_VERSION,
+            rust_type: std::any::type_name::<T>(),
+        };
+        for id in std::iter::once(T::TYPE_ID).chain(T::TYPE_ID_ALIASES.iter().copied()) {
+            if let Some((existing, _)) = self.types.get(id) {
+                assert!(
+                    *existing == registered,
+                    "cxdb: type id {id} registered for both {} and {}",
+                    existing.rust_type,
+                    registered.rust_type
+                );
+            }
+            self.types.insert(id, (registered, decodes_as::<T>));
+        }
+        self
+    }
+
+    /// The type registered under `type_id` or under it as an alias.
+    pub fn get(&self, type_id: &str) -> Option<&RegisteredType> {
+        self.types.get(type_id).map(|(registered, _)| registered)
+    }
+
+    pub fn contains(&self, type_id: &str) -> bool {
+        self.types.contains_key(type_id)
+    }
+
+    /// Every registered type once, by type id.
+    pub fn types(&self) -> impl Iterator<Item = &RegisteredType> {
+        self.types
+            .iter()
+            .filter(|(id, (registered, _))| **id == registered.type_id)
+            .map(|(_, (registered, _))| registered)
+    }
+}
+
+impl TurnValidator for TypeRegistry {
+    fn validate(
+        &self,
+        type_id: &str,
+        type_version: u32,
+        payload: &[u8],
+    ) -> std::result::Result<(), ValidationError> {
+        match self.types.get(type_id) {
+            None => Err(ValidationError::new(
+                type_id,
+                type_version,
+                "type id is not registered",
+            )),
+            Some((registered, check)) if registered.type_version == type_version => {
+                check(payload).map_err(|msg| ValidationError::new(type_id, type_version, msg))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Encodes `value` and appends it to `context_id` under `T`'s type id and
+    /// version.
+    ///
+    /// Encoding failures return [`Error::Encode`](crate::Error::Encode)
+    /// before anything is sent; every other error comes from the append.
+    pub fn append_typed<T: CxdbType + Serialize>(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        value: &T,
+    ) -> Result<AppendResult> {
+        self.append_turn(ctx, &typed_append_request(context_id, value)?)
+    }
+
+    /// Fetches the last turns of `context_id` and decodes those declared as
+    /// `T`, oldest first. Payloads are always requested.
+    ///
+    /// `opts.limit` bounds the turns fetched before filtering, so fewer than
+    /// `limit` values may come back. A matching turn whose payload fails to
+    /// decode returns [`Error::Decode`](crate::Error::Decode).
+    pub fn get_last_typed<T: CxdbType + DeserializeOwned>(
+        &self,
+        ctx: &RequestContext,
+        context_id: ContextId,
+        opts: GetLastOptions,
+    ) -> Result<Vec<(TurnHeader, T)>> {
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..opts
+        };
+        let records = self.get_last(ctx, context_id, opts)?;
+        decode_typed(records, self.max_decode_depth())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::TurnId;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::error::Error;
+    use crate::protocol::{MSG_APPEND_TURN, MSG_GET_LAST};
+    use crate::test_util::{spawn_scripted_server, typed_turn_records_payload};
+    use crate::types::{new_user_input, ConversationItem, TypeIDConversationItemLegacy};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        #[serde(rename = "1")]
+        text: String,
+    }
+
+    impl CxdbType for Message {
+        const TYPE_ID: &'static str = "com.example.Message";
+        const TYPE_VERSION: u32 = 2;
+    }
+
+    struct Unencodable;
+
+    impl Serialize for Unencodable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> std::result::Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("refusing to encode"))
+        }
+    }
+
+    impl CxdbType for Unencodable {
+        const TYPE_ID: &'static str = "test.Unencodable";
+        const TYPE_VERSION: u32 = 1;
+    }
+
+    fn append_ack() -> Vec<u8> {
+        let mut ack = 1u64.to_le_bytes().to_vec();
+        ack.extend_from_slice(&7u64.to_le_bytes());
+        ack.extend_from_slice(&1u32.to_le_bytes());
+        ack.extend_from_slice(&[0u8; 32]);
+        ack
+    }
+
+    #[test]
+    fn append_typed_declares_type_from_trait() {
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_APPEND_TURN, append_ack())]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let value = Message { text: "hi".into() };
+        let result = client
+            .append_typed(&ctx, ContextId::new(1), &value)
+            .unwrap();
+        assert_eq!(result.turn_id, TurnId::new(7));
+
+        let requests = handle.join().unwrap();
+        let expected = typed_append_request(ContextId::new(1), &value).unwrap();
+        assert_eq!(expected.type_id, "com.example.Message");
+        assert_eq!(expected.type_version, 2);
+        assert_eq!(expected.payload, encode_msgpack(&value).unwrap());
+        let type_id = &requests[0].payload[20..20 + expected.type_id.len()];
+        assert_eq!(type_id, expected.type_id.as_bytes());
+    }
+
+    #[test]
+    fn append_typed_reports_encode_errors_without_sending() {
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_APPEND_TURN, append_ack())]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let err = client
+            .append_typed(&ctx, ContextId::new(1), &Unencodable)
+            .unwrap_err();
+        assert!(matches!(err, Error::Encode { .. }), "got {err:?}");
+        client.close().unwrap();
+        assert!(handle.join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn get_last_typed_filters_and_decodes() {
+        let hello = encode_msgpack(&Message {
+            text: "hello".into(),
+        })
+        .unwrap();
+        let other = encode_msgpack(&"unrelated").unwrap();
+        let bye = encode_msgpack(&Message { text: "bye".into() }).unwrap();
+        let response = typed_turn_records_payload(&[
+            ("com.example.Message", &hello),
+            ("com.example.Other", &other),
+            ("com.example.Message", &bye),
+        ]);
+        let (addr, handle) = spawn_scripted_server(vec![
+            (MSG_GET_LAST, response),
+            (
+                MSG_GET_LAST,
+                typed_turn_records_payload(&[("com.example.Message", &other)]),
+            ),
+        ]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let ctx = RequestContext::background();
+
+        let messages = client
+            .get_last_typed::<Message>(&ctx, ContextId::new(1), GetLastOptions::default())
+            .unwrap();
+        let texts: Vec<_> = messages.iter().map(|(_, m)| m.text.as_str()).collect();
+        assert_eq!(texts, ["hello", "bye"]);
+        assert_eq!(messages[0].0.turn_id, TurnId::new(1));
+        assert_eq!(messages[1].0.turn_id, TurnId::new(3));
+
+        let err = client
+            .get_last_typed::<Message>(&ctx, ContextId::new(1), GetLastOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, Error::Decode { .. }), "got {err:?}");
+
+        let requests = handle.join().unwrap();
+        // include_payload is forced on.
+        assert_eq!(&requests[0].payload[12..16], &1u32.to_le_bytes());
+    }
+
+    #[test]
+    fn decode_as_checks_the_declared_type() {
+        let hello = encode_msgpack(&Message {
+            text: "hello".into(),
+        })
+        .unwrap();
+        let (addr, _handle) = spawn_scripted_server(vec![(
+            MSG_GET_LAST,
+            typed_turn_records_payload(&[
+                ("com.example.Message", &hello),
+                ("com.example.Other", &hello),
+            ]),
+        )]);
+        let client = crate::client::dial(&addr, Vec::new()).unwrap();
+        let opts = GetLastOptions {
+            include_payload: true,
+            ..Default::default()
+        };
+        let turns = client
+            .get_last(&RequestContext::background(), ContextId::new(1), opts)
+            .unwrap();
+
+        assert_eq!(turns[0].decode_as::<Message>().unwrap().text, "hello");
+        let err = turns[1].decode_as::<Message>().unwrap_err();
+        assert!(
+            matches!(&err, Error::Decode { message: msg, .. } if msg == "turn 2 is com.example.Other, not com.example.Message"),
+            "got {err:?}"
+        );
+    }
+
+    #[test]
+    fn type_registry_refuses_unregistered_type_ids() {
+        let registry = TypeRegistry::new()
+            .register::<Message>()
+            .register::<ConversationItem>()
+            .register::<Message>();
+        let ids: Vec<_> = registry.types().map(|t| t.type_id).collect();
+        assert_eq!(ids, ["com.example.Message", ConversationItem::TYPE_ID]);
+        assert_eq!(
+            registry.get(TypeIDConversationItemLegacy).unwrap().type_id,
+            ConversationItem::TYPE_ID
+        );
+
+        let payload = encode_msgpack(&Message { text: "hi".into() }).unwrap();
+        assert!(registry
+            .validate("com.example.Message", 2, &payload)
+            .is_ok());
+        // Other versions are known but not decoded.
+        assert!(registry.validate("com.example.Message", 1, b"\xc1").is_ok());
+        let err = registry
+            .validate("com.example.Mesage", 2, &payload)
+            .unwrap_err();
+        assert_eq!(err.message, "type id is not registered");
+        assert!(registry
+            .validate("com.example.Message", 2, b"\xc1")
+            .is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "registered for both")]
+    fn type_registry_rejects_conflicting_types() {
+        struct Impostor;
+        impl<'de> Deserialize<'de> for Impostor {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                _: D,
+            ) -> std::result::Result<Self, D::Error> {
+                Ok(Impostor)
+            }
+        }
+        impl CxdbType for Impostor {
+            const TYPE_ID: &'static str = "com.example.Message";
+            const TYPE_VERSION: u32 = 2;
+        }
+        let _ = TypeRegistry::new()
+            .register::<Message>()
+            .register::<Impostor>();
+    }
+
+    #[test]
+    fn conversation_item_accepts_legacy_type_id() {
+        assert!(ConversationItem::matches(ConversationItem::TYPE_ID));
+        assert!(ConversationItem::matches(TypeIDConversationItemLegacy));
+        assert!(!ConversationItem::matches("com.example.Message"));
+
+        let item = new_user_input("hi", Vec::new());
+        let req = typed_append_request(ContextId::new(3), &item).unwrap();
+        assert_eq!(req.type_id, crate::types::TypeIDConversationItem);
+        assert_eq!(req.type_version, crate::types::TypeVersionConversationItem);
+    }
+}
@@ -5,6 +5,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::typed::CxdbType;
+
 pub const TypeIDConversationItem: &str = "cxdb.ConversationItem";
 pub const TypeVersionConversationItem: u32 = 3;
 pub const TypeIDConversationItemLegacy: &str = "cxdb.v3:ConversationItem";
@@ -48,11 +50,11 @@ pub const SystemKindRewind: &str = "rewind";
 pub struct ConversationItem {
     #[serde(rename = "1")]
     pub item_type: ItemType,
-    #[serde(rename = "2", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "2", default, skip_serializing_if = "String::is_empty")]
     pub status: ItemStatus,
-    #[serde(rename = "3", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "3", default, skip_serializing_if = "is_zero_i64")]
     pub timestamp: i64,
-    #[serde(rename = "4", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "4", default, skip_serializing_if = "String::is_empty")]
     pub id: String,
 
     #[serde(rename = "10")]
@@ -75,11 +77,17 @@ pub struct ConversationItem {
     pub context_metadata: Option<ContextMetadata>,
 }
 
+impl CxdbType for ConversationItem {
+    const TYPE_ID: &'static str = TypeIDConversationItem;
+    const TYPE_VERSION: u32 = TypeVersionConversationItem;
+    const TYPE_ID_ALIASES: &'static [&'static str] = &[TypeIDConversationItemLegacy];
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct UserInput {
     #[serde(rename = "1")]
     pub text: String,
-    #[serde(rename = "2", skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "2", default, skip_serializing_if = "Vec::is_empty")]
     pub files: Vec<String>,
 }
 
@@ -87,19 +95,19 @@ pub struct UserInput {
 pub struct AssistantTurn {
     #[serde(rename = "1")]
     pub text: String,
-    #[serde(rename = "2", skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "2", default, skip_serializing_if = "Vec::is_empty")]
     pub tool_calls: Vec<ToolCallItem>,
-    #[serde(rename = "3", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "3", default, skip_serializing_if = "String::is_empty")]
     pub reasoning: String,
     #[serde(rename = "4")]
     pub metrics: Option<TurnMetrics>,
-    #[serde(rename = "5", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "5", default, skip_serializing_if = "String::is_empty")]
     pub agent: String,
-    #[serde(rename = "6", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "6", default, skip_serializing_if = "is_zero_i64")]
     pub turn_number: i64,
-    #[serde(rename = "7", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "7", default, skip_serializing_if = "is_zero_i64")]
     pub max_turns: i64,
-    #[serde(rename = "8", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "8", default, skip_serializing_if = "String::is_empty")]
     pub finish_reason: String,
 }
 
@@ -113,17 +121,17 @@ pub struct ToolCallItem {
     pub args: String,
     #[serde(rename = "4")]
     pub status: ToolCallStatus,
-    #[serde(rename = "5", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "5", default, skip_serializing_if = "String::is_empty")]
     pub description: String,
-    #[serde(rename = "6", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "6", default, skip_serializing_if = "String::is_empty")]
     pub streaming_output: String,
-    #[serde(rename = "7", skip_serializing_if = "is_false")]
+    #[serde(rename = "7", default, skip_serializing_if = "is_false")]
     pub streaming_output_truncated: bool,
     #[serde(rename = "8")]
     pub result: Option<ToolCallResult>,
     #[serde(rename = "9")]
     pub error: Option<ToolCallError>,
-    #[serde(rename = "10", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "10", default, skip_serializing_if = "is_zero_i64")]
     pub duration_ms: i64,
 }
 
@@ -131,7 +139,7 @@ pub struct ToolCallItem {
 pub struct ToolCallResult {
     #[serde(rename = "1")]
     pub content: String,
-    #[serde(rename = "2", skip_serializing_if = "is_false")]
+    #[serde(rename = "2", default, skip_serializing_if = "is_false")]
     pub content_truncated: bool,
     #[serde(rename = "3")]
     pub success: bool,
@@ -141,7 +149,7 @@ pub struct ToolCallResult {
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ToolCallError {
-    #[serde(rename = "1", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "1", default, skip_serializing_if = "String::is_empty")]
     pub code: String,
     #[serde(rename = "2")]
     pub message: String,
@@ -163,7 +171,7 @@ pub struct TurnMetrics {
     pub reasoning_tokens: Option<i64>,
     #[serde(rename = "6")]
     pub duration_ms: Option<i64>,
-    #[serde(rename = "7", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "7", default, skip_serializing_if = "String::is_empty")]
     pub model: String,
 }
 
@@ -171,7 +179,7 @@ pub struct TurnMetrics {
 pub struct SystemMessage {
     #[serde(rename = "1")]
     pub kind: SystemKind,
-    #[serde(rename = "2", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "2", default, skip_serializing_if = "String::is_empty")]
     pub title: String,
     #[serde(rename = "3")]
     pub content: String,
@@ -183,11 +191,11 @@ pub struct HandoffInfo {
     pub from_agent: String,
     #[serde(rename = "2")]
     pub to_agent: String,
-    #[serde(rename = "3", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "3", default, skip_serializing_if = "String::is_empty")]
     pub tool_name: String,
-    #[serde(rename = "4", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "4", default, skip_serializing_if = "String::is_empty")]
     pub input: String,
-    #[serde(rename = "5", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "5", default, skip_serializing_if = "String::is_empty")]
     pub reason: String,
 }
 
@@ -195,15 +203,15 @@ pub struct HandoffInfo {
 pub struct Assistant {
     #[serde(rename = "1")]
     pub text: String,
-    #[serde(rename = "2", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "2", default, skip_serializing_if = "String::is_empty")]
     pub reasoning: String,
-    #[serde(rename = "3", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "3", default, skip_serializing_if = "String::is_empty")]
     pub model: String,
-    #[serde(rename = "4", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "4", default, skip_serializing_if = "is_zero_i64")]
     pub input_tokens: i64,
-    #[serde(rename = "5", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "5", default, skip_serializing_if = "is_zero_i64")]
     pub output_tokens: i64,
-    #[serde(rename = "6", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "6", default, skip_serializing_if = "String::is_empty")]
     pub stop_reason: String,
 }
 
@@ -215,7 +223,7 @@ pub struct ToolCall {
     pub name: String,
     #[serde(rename = "3")]
     pub args: String,
-    #[serde(rename = "4", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "4", default, skip_serializing_if = "String::is_empty")]
     pub description: String,
 }
 
@@ -229,23 +237,23 @@ pub struct ToolResult {
     pub is_error: bool,
     #[serde(rename = "4")]
     pub exit_code: Option<i64>,
-    #[serde(rename = "5", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "5", default, skip_serializing_if = "String::is_empty")]
     pub streaming_output: String,
-    #[serde(rename = "6", skip_serializing_if = "is_false")]
+    #[serde(rename = "6", default, skip_serializing_if = "is_false")]
     pub output_truncated: bool,
-    #[serde(rename = "7", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "7", default, skip_serializing_if = "is_zero_i64")]
     pub duration_ms: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ContextMetadata {
-    #[serde(rename = "1", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "1", default, skip_serializing_if = "String::is_empty")]
     pub client_tag: String,
-    #[serde(rename = "2", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "2", default, skip_serializing_if = "String::is_empty")]
     pub title: String,
-    #[serde(rename = "3", skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "3", default, skip_serializing_if = "Vec::is_empty")]
     pub labels: Vec<String>,
-    #[serde(rename = "4", skip_serializing_if = "map_is_empty")]
+    #[serde(rename = "4", default, skip_serializing_if = "map_is_empty")]
     pub custom: std::collections::HashMap<String, String>,
     #[serde(rename = "10")]
     pub provenance: Option<super::provenance::Provenance>,
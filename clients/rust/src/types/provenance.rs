@@ -7,65 +7,67 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::ids::ContextId;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Provenance {
     #[serde(rename = "1")]
-    pub parent_context_id: Option<u64>,
-    #[serde(rename = "2", skip_serializing_if = "String::is_empty")]
+    pub parent_context_id: Option<ContextId>,
+    #[serde(rename = "2", default, skip_serializing_if = "String::is_empty")]
     pub spawn_reason: String,
     #[serde(rename = "3")]
-    pub root_context_id: Option<u64>,
+    pub root_context_id: Option<ContextId>,
 
-    #[serde(rename = "10", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "10", default, skip_serializing_if = "String::is_empty")]
     pub trace_id: String,
-    #[serde(rename = "11", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "11", default, skip_serializing_if = "String::is_empty")]
     pub span_id: String,
-    #[serde(rename = "12", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "12", default, skip_serializing_if = "String::is_empty")]
     pub correlation_id: String,
 
-    #[serde(rename = "20", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "20", default, skip_serializing_if = "String::is_empty")]
     pub on_behalf_of: String,
-    #[serde(rename = "21", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "21", default, skip_serializing_if = "String::is_empty")]
     pub on_behalf_of_source: String,
-    #[serde(rename = "22", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "22", default, skip_serializing_if = "String::is_empty")]
     pub on_behalf_of_email: String,
 
-    #[serde(rename = "30", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "30", default, skip_serializing_if = "String::is_empty")]
     pub writer_method: String,
-    #[serde(rename = "31", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "31", default, skip_serializing_if = "String::is_empty")]
     pub writer_subject: String,
-    #[serde(rename = "32", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "32", default, skip_serializing_if = "String::is_empty")]
     pub writer_issuer: String,
 
-    #[serde(rename = "40", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "40", default, skip_serializing_if = "String::is_empty")]
     pub service_name: String,
-    #[serde(rename = "41", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "41", default, skip_serializing_if = "String::is_empty")]
     pub service_version: String,
-    #[serde(rename = "42", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "42", default, skip_serializing_if = "String::is_empty")]
     pub service_instance_id: String,
-    #[serde(rename = "43", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "43", default, skip_serializing_if = "is_zero_i64")]
     pub process_pid: i64,
-    #[serde(rename = "44", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "44", default, skip_serializing_if = "String::is_empty")]
     pub process_owner: String,
-    #[serde(rename = "45", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "45", default, skip_serializing_if = "String::is_empty")]
     pub host_name: String,
-    #[serde(rename = "46", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "46", default, skip_serializing_if = "String::is_empty")]
     pub host_arch: String,
 
-    #[serde(rename = "50", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "50", default, skip_serializing_if = "String::is_empty")]
     pub client_address: String,
-    #[serde(rename = "51", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "51", default, skip_serializing_if = "is_zero_i64")]
     pub client_port: i64,
 
     #[serde(rename = "60")]
     pub env_vars: Option<HashMap<String, String>>,
 
-    #[serde(rename = "70", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "70", default, skip_serializing_if = "String::is_empty")]
     pub sdk_name: String,
-    #[serde(rename = "71", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "71", default, skip_serializing_if = "String::is_empty")]
     pub sdk_version: String,
 
-    #[serde(rename = "80", skip_serializing_if = "is_zero_i64")]
+    #[serde(rename = "80", default, skip_serializing_if = "is_zero_i64")]
     pub captured_at: i64,
 }
 
@@ -142,10 +144,14 @@ pub fn new_provenance(
     p
 }
 
-pub fn with_parent_context(parent_id: u64, root_id: u64) -> ProvenanceOption {
+pub fn with_parent_context(parent_id: ContextId, root_id: ContextId) -> ProvenanceOption {
     Arc::new(move |p| {
         p.parent_context_id = Some(parent_id);
-        p.root_context_id = Some(if root_id == 0 { parent_id } else { root_id });
+        p.root_context_id = Some(if root_id.get() == 0 {
+            parent_id
+        } else {
+            root_id
+        });
     })
 }
 
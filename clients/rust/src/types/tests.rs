@@ -2,7 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
-use crate::encoding::{decode_msgpack, decode_msgpack_into, encode_msgpack};
+use crate::encoding::{
+    decode_msgpack, decode_msgpack_into, decode_msgpack_into_with_max_depth,
+    decode_msgpack_with_max_depth, encode_msgpack,
+};
+use crate::error::Error;
+use crate::ids::ContextId;
 use crate::test_util::decode_hex;
 use rmpv::Value;
 use serde::Deserialize;
@@ -111,6 +116,74 @@ fn decode_msgpack_into_conversation_item() {
     );
 }
 
+#[test]
+fn items_with_omitted_empty_fields_decode() {
+    let mut tool_call = build_tool_call_item("call-1", "ls", "{}");
+    tool_call.with_result("a.txt", None);
+    let mut turn = build_assistant_turn("");
+    turn.with_tool_call(tool_call.build());
+    for mut item in [
+        new_assistant("hi"),
+        turn.build(),
+        new_tool_result("c", "", false),
+    ] {
+        item.with_context_metadata(ContextMetadata {
+            client_tag: String::new(),
+            title: String::new(),
+            labels: Vec::new(),
+            custom: std::collections::HashMap::new(),
+            provenance: Some(Provenance::default()),
+        });
+        let decoded: ConversationItem =
+            decode_msgpack_into(&encode_msgpack(&item).unwrap()).unwrap();
+        assert_eq!(decoded, item);
+    }
+}
+
+/// `{1: [[...[0]...]]}` with `depth` levels of single-element arrays.
+fn nested_msgpack(depth: usize) -> Vec<u8> {
+    let mut data = vec![0x81, 0x01];
+    data.extend(std::iter::repeat_n(0x91, depth));
+    data.push(0x00);
+    data
+}
+
+#[test]
+fn decode_msgpack_rejects_excessive_nesting() {
+    let deep = nested_msgpack(200);
+    assert!(matches!(decode_msgpack(&deep), Err(Error::Decode { .. })));
+    assert!(matches!(
+        decode_msgpack_into::<BTreeMap<u64, Value>>(&deep),
+        Err(Error::Decode { .. })
+    ));
+    assert!(matches!(
+        decode_msgpack_into::<ConversationItem>(&deep),
+        Err(Error::Decode { .. })
+    ));
+
+    assert!(decode_msgpack_with_max_depth(&deep, 256).is_ok());
+    assert!(decode_msgpack_into_with_max_depth::<BTreeMap<u64, Value>>(&deep, 256).is_ok());
+
+    // Far past any sane limit: must fail cleanly rather than overflow the stack.
+    let hostile = nested_msgpack(1_000_000);
+    assert!(decode_msgpack(&hostile).is_err());
+    assert!(decode_msgpack_into::<ConversationItem>(&hostile).is_err());
+}
+
+#[test]
+fn decode_msgpack_rejects_oversized_length_prefixes() {
+    // Each declares ~4 GiB of content backed by a handful of bytes.
+    for data in [
+        vec![0x81, 0x01, 0xc6, 0xff, 0xff, 0xff, 0xff, 0x00],
+        vec![0x81, 0x01, 0xdb, 0xff, 0xff, 0xff, 0xff, 0x61],
+        vec![0x81, 0x01, 0xdd, 0xff, 0xff, 0xff, 0xff, 0x00],
+        vec![0xdf, 0xff, 0xff, 0xff, 0xff, 0x01, 0x02],
+    ] {
+        assert!(decode_msgpack(&data).is_err());
+        assert!(decode_msgpack_into::<ConversationItem>(&data).is_err());
+    }
+}
+
 #[test]
 fn capture_process_provenance_populates_fields() {
     let p = capture_process_provenance("test-service", "1.0.0", Vec::<ProvenanceOption>::new());
@@ -143,12 +216,18 @@ fn new_provenance_inherits_and_overrides() {
 
 #[test]
 fn with_parent_context_sets_root() {
-    let p = new_provenance(None, vec![with_parent_context(100, 50)]);
-    assert_eq!(p.parent_context_id, Some(100));
-    assert_eq!(p.root_context_id, Some(50));
+    let p = new_provenance(
+        None,
+        vec![with_parent_context(ContextId::new(100), ContextId::new(50))],
+    );
+    assert_eq!(p.parent_context_id, Some(ContextId::new(100)));
+    assert_eq!(p.root_context_id, Some(ContextId::new(50)));
 
-    let p2 = new_provenance(None, vec![with_parent_context(200, 0)]);
-    assert_eq!(p2.root_context_id, Some(200));
+    let p2 = new_provenance(
+        None,
+        vec![with_parent_context(ContextId::new(200), ContextId::new(0))],
+    );
+    assert_eq!(p2.root_context_id, Some(ContextId::new(200)));
 }
 
 #[test]
@@ -0,0 +1,296 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client-side payload validation before appends.
+//!
+//! A malformed turn is accepted by the server as opaque bytes and only fails
+//! when some reader decodes it. Install a [`TurnValidator`] with
+//! [`with_validator`] and every append the client makes checks the payload
+//! first: [`Client::append_turn`], [`Client::append_dedup`],
+//! [`Client::append_turn_with_fs`], compaction summaries, and the appends
+//! made on the client's behalf by [`ClientPool::append_many`], the outbox
+//! and its replay, and JSON Lines import. A rejected payload returns
+//! [`Error::Validation`] and nothing is sent.
+//!
+//! Two validators are built in. [`MsgpackWellFormed`] accepts any payload
+//! that is exactly one msgpack value. [`TypeValidator`] decodes the payloads
+//! of registered [`CxdbType`]s as those types.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use cxdb::validate::{with_validator, TypeValidator};
+//! use cxdb::{dial, CxdbType};
+//! # #[derive(serde::Deserialize)]
+//! # struct Message { text: String }
+//! # impl CxdbType for Message {
+//! #     const TYPE_ID: &'static str = "com.example.Message";
+//! #     const TYPE_VERSION: u32 = 1;
+//! # }
+//!
+//! let validator = TypeValidator::new().register::<Message>();
+//! let client = dial("127.0.0.1:9009", [with_validator(Arc::new(validator))])?;
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+//!
+//! [`Client::append_turn`]: crate::Client::append_turn
+//! [`Client::append_dedup`]: crate::Client::append_dedup
+//! [`Client::append_turn_with_fs`]: crate::Client::append_turn_with_fs
+//! [`ClientPool::append_many`]: crate::ClientPool::append_many
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{Client, ClientOption};
+use crate::encoding::{decode_msgpack_into, rmpv_depth};
+use crate::error::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::Result;
+use crate::protocol::MAX_DECODE_DEPTH;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::protocol::MSG_APPEND_TURN;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::turn::AppendRequest;
+use crate::typed::CxdbType;
+
+/// Checks a turn's payload before it is appended.
+///
+/// Runs on the appending thread, before any I/O.
+pub trait TurnValidator: Send + Sync {
+    fn validate(
+        &self,
+        type_id: &str,
+        type_version: u32,
+        payload: &[u8],
+    ) -> std::result::Result<(), ValidationError>;
+}
+
+impl fmt::Debug for dyn TurnValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TurnValidator")
+    }
+}
+
+/// Why a [`TurnValidator`] rejected a payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub type_id: String,
+    pub type_version: u32,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(type_id: &str, type_version: u32, message: impl Into<String>) -> Self {
+        Self {
+            type_id: type_id.to_string(),
+            type_version,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} v{} payload: {}",
+            self.type_id, self.type_version, self.message
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Validates every payload of the client and every connection redialed from
+/// it with `validator`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn with_validator(validator: Arc<dyn TurnValidator>) -> ClientOption {
+    Arc::new(move |opts| opts.validator = Some(validator.clone()))
+}
+
+/// Accepts payloads that are exactly one well-formed msgpack value, nested
+/// no deeper than [`MAX_DECODE_DEPTH`]. Every payload is checked, so do not
+/// install it on a client that also appends CBOR.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgpackWellFormed;
+
+impl TurnValidator for MsgpackWellFormed {
+    fn validate(
+        &self,
+        type_id: &str,
+        type_version: u32,
+        payload: &[u8],
+    ) -> std::result::Result<(), ValidationError> {
+        let mut rest = payload;
+        rmpv::decode::read_value_with_max_depth(&mut rest, rmpv_depth(MAX_DECODE_DEPTH))
+            .map_err(|err| ValidationError::new(type_id, type_version, err.to_string()))?;
+        if !rest.is_empty() {
+            return Err(ValidationError::new(
+                type_id,
+                type_version,
+                format!("{} trailing bytes after the value", rest.len()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) type DecodeCheck = fn(&[u8]) -> std::result::Result<(), String>;
+
+/// Checks that payloads declared as a registered [`CxdbType`] (by type id
+/// and version) decode as that type. Payloads of other types pass.
+#[derive(Debug, Clone, Default)]
+pub struct TypeValidator {
+    types: HashMap<(String, u32), DecodeCheck>,
+}
+
+impl TypeValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks payloads declared as `T::TYPE_ID` at `T::TYPE_VERSION`.
+    pub fn register<T: CxdbType + DeserializeOwned>(mut self) -> Self {
+        self.types
+            .insert((T::TYPE_ID.to_string(), T::TYPE_VERSION), decodes_as::<T>);
+        self
+    }
+}
+
+pub(crate) fn decodes_as<T: DeserializeOwned>(payload: &[u8]) -> std::result::Result<(), String> {
+    match decode_msgpack_into::<T>(payload) {
+        Ok(_) => Ok(()),
+        Err(Error::Decode { message: msg, .. }) => Err(msg),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+impl TurnValidator for TypeValidator {
+    fn validate(
+        &self,
+        type_id: &str,
+        type_version: u32,
+        payload: &[u8],
+    ) -> std::result::Result<(), ValidationError> {
+        match self.types.get(&(type_id.to_string(), type_version)) {
+            Some(check) => {
+                check(payload).map_err(|msg| ValidationError::new(type_id, type_version, msg))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Runs the installed validator, if any, over `req`'s payload, after
+    /// checking the client is not read-only and the server takes its links.
+    pub(crate) fn validate_append(&self, req: &AppendRequest) -> Result<()> {
+        self.require_writable(MSG_APPEND_TURN)?;
+        self.check_links_supported(req)?;
+        validate_with(self.validator(), req)
+    }
+}
+
+/// Runs `validator`, if any, over `req`'s payload.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn validate_with(
+    validator: std::option::Option<&Arc<dyn TurnValidator>>,
+    req: &AppendRequest,
+) -> Result<()> {
+    match validator {
+        Some(validator) => validator
+            .validate(&req.type_id, req.type_version, &req.payload)
+            .map_err(Error::Validation),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::ContextId;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::encoding::encode_msgpack;
+    use crate::protocol::MSG_APPEND_TURN;
+    use crate::test_util::spawn_scripted_server;
+    use crate::RequestContext;
+
+    #[derive(Serialize, Deserialize)]
+    struct Note {
+        #[serde(rename = "1")]
+        role: String,
+        #[serde(rename = "2")]
+        text: String,
+    }
+
+    impl CxdbType for Note {
+        const TYPE_ID: &'static str = "test.Note";
+        const TYPE_VERSION: u32 = 1;
+    }
+
+    #[test]
+    fn msgpack_well_formed_rejects_truncated_and_trailing_bytes() {
+        let payload = encode_msgpack(&("user", "hi")).unwrap();
+        assert!(MsgpackWellFormed.validate("t", 1, &payload).is_ok());
+
+        let err = MsgpackWellFormed
+            .validate("t", 1, &payload[..payload.len() - 1])
+            .unwrap_err();
+        assert_eq!((err.type_id.as_str(), err.type_version), ("t", 1));
+
+        let mut trailing = payload.clone();
+        trailing.push(0xc0);
+        let err = MsgpackWellFormed.validate("t", 1, &trailing).unwrap_err();
+        assert_eq!(err.message, "1 trailing bytes after the value");
+    }
+
+    #[test]
+    fn type_validator_checks_registered_types_only() {
+        let validator = TypeValidator::new().register::<Note>();
+        let note = encode_msgpack(&Note {
+            role: "user".into(),
+            text: "hi".into(),
+        })
+        .unwrap();
+        let not_a_note = encode_msgpack(&42).unwrap();
+
+        assert!(validator.validate("test.Note", 1, &note).is_ok());
+        assert!(validator.validate("test.Note", 1, &not_a_note).is_err());
+        assert!(validator.validate("test.Note", 2, &not_a_note).is_ok());
+        assert!(validator.validate("test.Other", 1, &not_a_note).is_ok());
+    }
+
+    #[test]
+    fn rejected_appends_never_reach_the_server() {
+        let (addr, handle) = spawn_scripted_server(vec![(MSG_APPEND_TURN, vec![0u8; 52])]);
+        let validator = TypeValidator::new().register::<Note>();
+        let client = crate::dial(&addr, [with_validator(Arc::new(validator))]).unwrap();
+        let ctx = RequestContext::background();
+
+        let bad = AppendRequest::new(
+            ContextId::new(1),
+            "test.Note",
+            1,
+            encode_msgpack(&"hi").unwrap(),
+        );
+        let err = client.append_turn(&ctx, &bad).unwrap_err();
+        assert!(matches!(&err, Error::Validation(e) if e.type_id == "test.Note"));
+        assert!(matches!(
+            client.append_dedup(&ctx, &bad),
+            Err(Error::Validation(_))
+        ));
+
+        let good = AppendRequest::new(ContextId::new(1), "test.Other", 1, vec![0xc0]);
+        client.append_turn(&ctx, &good).unwrap();
+        client.close().unwrap();
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].header.msg_type, MSG_APPEND_TURN);
+    }
+}
@@ -0,0 +1,497 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Watching many contexts for changes.
+//!
+//! [`Client::watch`] polls the heads of a set of contexts on a dedicated
+//! connection, one pipelined batch of GET_HEADs per interval, and yields a
+//! [`ContextChanged`] whenever a head moves. Events carry no payloads: a
+//! dashboard or scheduler learns *which* contexts changed and reads what it
+//! needs. The watched set can grow and shrink with [`WatchStream::add`] and
+//! [`WatchStream::remove`] without reconnecting.
+//!
+//! Changes are coalesced: while an event for a context waits for the
+//! consumer, later moves of the same head update it in place rather than
+//! queueing another, so a burst of appends surfaces as one event naming the
+//! latest head. Memory is therefore bounded by the number of watched
+//! contexts, and a head that moves and moves back between polls is not
+//! seen at all.
+//!
+//! ```no_run
+//! use cxdb::watch::WatchOptions;
+//! use cxdb::{dial, ContextId, RequestContext};
+//!
+//! let client = dial("127.0.0.1:9009", [])?;
+//! let ctx = RequestContext::background();
+//! let watch = client.watch(&ctx, &[1, 2, 3].map(ContextId::new), WatchOptions::default())?;
+//! watch.add(&[ContextId::new(4)]);
+//! for event in &watch {
+//!     let event = event?;
+//!     println!("context {} is at turn {}", event.context_id, event.new_head_turn_id);
+//! }
+//! # Ok::<(), cxdb::Error>(())
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::client::{Client, RequestContext};
+use crate::context::{parse_context_head, ContextHead};
+use crate::error::{Error, Result};
+use crate::ids::{ContextId, TurnId};
+use crate::protocol::MSG_GET_HEAD;
+use crate::reconnect::{is_connection_error, DialFunc};
+
+/// Default [`WatchOptions::poll_interval`].
+pub const DEFAULT_WATCH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How [`Client::watch`] polls.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchOptions {
+    /// How often every watched head is checked.
+    pub poll_interval: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: DEFAULT_WATCH_POLL_INTERVAL,
+        }
+    }
+}
+
+impl WatchOptions {
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+}
+
+/// A watched context's head moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextChanged {
+    pub context_id: ContextId,
+    pub new_head_turn_id: TurnId,
+    pub new_depth: u32,
+}
+
+impl Client {
+    /// Watches `context_ids`, yielding a [`ContextChanged`] each time one of
+    /// their heads moves (see the [module docs](self)). The first poll only
+    /// records where each head is; changes are reported from then on. The
+    /// watch reads on its own connection and stops when dropped.
+    ///
+    /// Every poll carries `ctx`'s values, and cancelling `ctx` ends the
+    /// watch; its deadline is not applied, since a watch has no natural end.
+    /// Fails only if the client is closed.
+    pub fn watch(
+        &self,
+        ctx: &RequestContext,
+        context_ids: &[ContextId],
+        opts: WatchOptions,
+    ) -> Result<WatchStream> {
+        if self.is_closed() {
+            return Err(Error::ClientClosed);
+        }
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+        shared.add(context_ids);
+        let values: BTreeMap<String, String> = ctx
+            .values()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let poller = Poller {
+            shared: shared.clone(),
+            dial: self.dialer(),
+            ctx: RequestContext::background().with_values(&values),
+            parent: ctx.clone(),
+            opts,
+        };
+        thread::Builder::new()
+            .name("cxdb-watch".into())
+            .spawn(move || poller.run())?;
+        Ok(WatchStream { shared })
+    }
+}
+
+/// Changes to a set of contexts, returned by [`Client::watch`]. Iterate it
+/// (by value or by reference) to receive them; iteration blocks until the
+/// next change.
+///
+/// A watched context that does not exist is dropped from the set and
+/// reported as [`Error::ContextNotFound`], after which iteration goes on.
+/// Any other error ends it.
+pub struct WatchStream {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when `pending`, `missing` or `closed` changes.
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// Each watched context and its head as of the last poll; `None` until
+    /// the first poll after it was added.
+    watched: BTreeMap<ContextId, Option<ContextHead>>,
+    /// Contexts with an event waiting, in the order they first changed.
+    order: VecDeque<ContextId>,
+    /// The waiting event per context, updated in place as its head moves.
+    pending: BTreeMap<ContextId, ContextChanged>,
+    /// Watched contexts found missing, yielded in turn.
+    missing: VecDeque<Error>,
+    /// Error to yield once the events are drained, ending the watch.
+    error: Option<Error>,
+    /// The poller stopped; nothing more will be queued.
+    finished: bool,
+    /// The stream was dropped or closed.
+    closed: bool,
+}
+
+impl WatchStream {
+    /// Starts watching `context_ids` as well. Their heads are recorded at
+    /// the next poll and changes reported after that; ids already watched
+    /// are left as they are.
+    pub fn add(&self, context_ids: &[ContextId]) {
+        self.shared.add(context_ids);
+    }
+
+    /// Stops watching `context_ids`, discarding any events for them that
+    /// have not been yielded yet.
+    pub fn remove(&self, context_ids: &[ContextId]) {
+        let mut state = self.shared.lock();
+        for context_id in context_ids {
+            state.watched.remove(context_id);
+            state.pending.remove(context_id);
+        }
+        let State { order, pending, .. } = &mut *state;
+        order.retain(|context_id| pending.contains_key(context_id));
+    }
+
+    /// The contexts currently watched, ascending.
+    pub fn watched(&self) -> Vec<ContextId> {
+        self.shared.lock().watched.keys().copied().collect()
+    }
+
+    /// Events waiting for the consumer.
+    pub fn pending(&self) -> usize {
+        self.shared.lock().order.len()
+    }
+
+    /// Stops polling. Events already waiting are still yielded.
+    pub fn close(&self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        self.shared.changed.notify_all();
+    }
+
+    fn recv(&self) -> Option<Result<ContextChanged>> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(err) = state.missing.pop_front() {
+                return Some(Err(err));
+            }
+            if let Some(context_id) = state.order.pop_front() {
+                let event = state.pending.remove(&context_id).expect("pending event");
+                return Some(Ok(event));
+            }
+            if let Some(err) = state.error.take() {
+                return Some(Err(err));
+            }
+            if state.finished || state.closed {
+                return None;
+            }
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Drop for WatchStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Iterator for WatchStream {
+    type Item = Result<ContextChanged>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Iterator for &WatchStream {
+    type Item = Result<ContextChanged>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn add(&self, context_ids: &[ContextId]) {
+        let mut state = self.lock();
+        for &context_id in context_ids {
+            state.watched.entry(context_id).or_insert(None);
+        }
+    }
+}
+
+/// The background half of a [`WatchStream`].
+struct Poller {
+    shared: Arc<Shared>,
+    dial: DialFunc,
+    /// The caller's values without its deadline.
+    ctx: RequestContext,
+    /// The caller's context, checked for cancellation.
+    parent: RequestContext,
+    opts: WatchOptions,
+}
+
+impl Poller {
+    fn run(self) {
+        let result = self.poll_loop();
+        let mut state = self.shared.lock();
+        if let Err(err) = result {
+            state.error.get_or_insert(err);
+        }
+        state.finished = true;
+        self.shared.changed.notify_all();
+    }
+
+    fn poll_loop(&self) -> Result<()> {
+        let mut conn: Option<Client> = None;
+        loop {
+            if self.shared.lock().closed || self.parent.is_cancelled() {
+                return Ok(());
+            }
+            let client = match conn.as_ref().filter(|client| !client.is_poisoned()) {
+                Some(client) => client,
+                None => match (self.dial)() {
+                    Ok(client) => conn.insert(client),
+                    Err(err) if is_connection_error(&err) => {
+                        self.pause();
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                },
+            };
+            match self.poll(client) {
+                Ok(()) => self.pause(),
+                Err(err) if is_connection_error(&err) => {
+                    conn = None;
+                    self.pause();
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reads every watched head in one pipelined batch and queues an event
+    /// for each that moved.
+    fn poll(&self, client: &Client) -> Result<()> {
+        let context_ids: Vec<ContextId> = self.shared.lock().watched.keys().copied().collect();
+        if context_ids.is_empty() {
+            return Ok(());
+        }
+        let mut requests = Vec::with_capacity(context_ids.len());
+        for &context_id in &context_ids {
+            let mut payload = Vec::with_capacity(8);
+            payload.write_u64::<LittleEndian>(context_id.get())?;
+            requests.push((MSG_GET_HEAD, payload));
+        }
+        let heads = client
+            .pipeline(&self.ctx, &requests)?
+            .into_iter()
+            .map(|frame| {
+                frame
+                    .map_err(|err| err.resolve_not_found())
+                    .and_then(|frame| parse_context_head(&frame.payload))
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = self.shared.lock();
+        for (head, context_id) in heads.into_iter().zip(context_ids) {
+            // Removed while the batch was in flight.
+            let Some(last) = state.watched.get_mut(&context_id) else {
+                continue;
+            };
+            let head = match head {
+                Ok(head) => head,
+                Err(err @ Error::ContextNotFound { .. }) => {
+                    state.watched.remove(&context_id);
+                    state.missing.push_back(err);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let moved = last
+                .as_ref()
+                .is_some_and(|last| last.head_turn_id != head.head_turn_id);
+            let event = ContextChanged {
+                context_id,
+                new_head_turn_id: head.head_turn_id,
+                new_depth: head.head_depth,
+            };
+            *last = Some(head);
+            if !moved {
+                continue;
+            }
+            if state.pending.insert(context_id, event).is_none() {
+                state.order.push_back(context_id);
+            }
+        }
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Waits out the poll interval, returning early if the stream is
+    /// closed.
+    fn pause(&self) {
+        let state = self.shared.lock();
+        let _ = self
+            .shared
+            .changed
+            .wait_timeout_while(state, self.opts.poll_interval, |state| !state.closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::dial;
+    use crate::protocol::MSG_ERROR;
+    use crate::test_util::{not_found_payload, spawn_multi_server};
+
+    /// Contexts whose head turn ids are in `heads`, at depth == turn id.
+    /// Contexts missing from the map answer 404.
+    fn contexts(heads: Arc<Mutex<HashMap<u64, u64>>>) -> String {
+        spawn_multi_server(move |req| {
+            assert_eq!(req.header.msg_type, MSG_GET_HEAD);
+            let context_id = u64::from_le_bytes(req.payload[..8].try_into().unwrap());
+            match heads.lock().unwrap().get(&context_id) {
+                Some(&head) => {
+                    let mut out = context_id.to_le_bytes().to_vec();
+                    out.extend_from_slice(&head.to_le_bytes());
+                    out.extend_from_slice(&(head as u32).to_le_bytes());
+                    (MSG_GET_HEAD, out)
+                }
+                None => (MSG_ERROR, not_found_payload("context", context_id)),
+            }
+        })
+    }
+
+    fn opts() -> WatchOptions {
+        WatchOptions::default().poll_interval(Duration::from_millis(10))
+    }
+
+    /// Waits until the poller has recorded a head for every watched context.
+    fn wait_polled(watch: &WatchStream) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while watch.shared.lock().watched.values().any(Option::is_none) {
+            assert!(Instant::now() < deadline, "never polled");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn changed(context_id: u64, head: u64) -> ContextChanged {
+        ContextChanged {
+            context_id: ContextId::new(context_id),
+            new_head_turn_id: TurnId::new(head),
+            new_depth: head as u32,
+        }
+    }
+
+    #[test]
+    fn reports_and_coalesces_head_moves() {
+        let heads = Arc::new(Mutex::new(HashMap::from([(1, 5), (2, 7), (3, 9)])));
+        let client = dial(&contexts(heads.clone()), []).unwrap();
+        let mut watch = client
+            .watch(
+                &RequestContext::background(),
+                &[ContextId::new(1), ContextId::new(2), ContextId::new(3)],
+                opts(),
+            )
+            .unwrap();
+        wait_polled(&watch);
+        assert_eq!(watch.pending(), 0);
+
+        heads.lock().unwrap().insert(2, 8);
+        assert_eq!(watch.next().unwrap().unwrap(), changed(2, 8));
+
+        // Both moves of context 1 land before the consumer looks.
+        heads.lock().unwrap().insert(1, 6);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while watch.pending() == 0 {
+            assert!(Instant::now() < deadline, "change never seen");
+            thread::sleep(Duration::from_millis(5));
+        }
+        heads.lock().unwrap().insert(1, 7);
+        heads.lock().unwrap().insert(3, 10);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(watch.pending(), 2);
+        assert_eq!(watch.next().unwrap().unwrap(), changed(1, 7));
+        assert_eq!(watch.next().unwrap().unwrap(), changed(3, 10));
+    }
+
+    #[test]
+    fn add_and_remove_adjust_the_watched_set() {
+        let heads = Arc::new(Mutex::new(HashMap::from([(1, 1), (2, 2)])));
+        let client = dial(&contexts(heads.clone()), []).unwrap();
+        let mut watch = client
+            .watch(&RequestContext::background(), &[ContextId::new(1)], opts())
+            .unwrap();
+        watch.add(&[ContextId::new(2)]);
+        wait_polled(&watch);
+        assert_eq!(watch.watched(), [1, 2].map(ContextId::new));
+
+        watch.remove(&[ContextId::new(1)]);
+        heads.lock().unwrap().insert(1, 3);
+        heads.lock().unwrap().insert(2, 4);
+        assert_eq!(watch.next().unwrap().unwrap(), changed(2, 4));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(watch.pending(), 0);
+        assert_eq!(watch.watched(), [ContextId::new(2)]);
+    }
+
+    #[test]
+    fn missing_context_is_reported_and_dropped() {
+        let heads = Arc::new(Mutex::new(HashMap::from([(1, 1)])));
+        let client = dial(&contexts(heads.clone()), []).unwrap();
+        let (ctx, cancel) = RequestContext::cancellable();
+        let mut watch = client
+            .watch(&ctx, &[ContextId::new(1), ContextId::new(404)], opts())
+            .unwrap();
+
+        match watch.next() {
+            Some(Err(Error::ContextNotFound { context_id })) if context_id.get() == 404 => {}
+            other => panic!("expected context not found, got {other:?}"),
+        }
+        assert_eq!(watch.watched(), [ContextId::new(1)]);
+        heads.lock().unwrap().insert(1, 2);
+        assert_eq!(watch.next().unwrap().unwrap(), changed(1, 2));
+
+        cancel.cancel();
+        assert!(watch.next().is_none());
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! [`WebSocketTransport`]: the frame stream over a browser `WebSocket`.
+//!
+//! Binary messages carry the same bytes a TCP connection would, split
+//! however the far end likes; frames are reassembled from them, so a plain
+//! WebSocket-to-TCP bridge in front of the server is enough. Each outgoing
+//! frame is sent as one message.
+
+use std::cell::RefCell;
+use std::future::poll_fn;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+use js_sys::Uint8Array;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{BinaryType, Event, MessageEvent, WebSocket};
+
+use crate::error::{Error, Result};
+use crate::protocol::{Frame, FrameHeader, FRAME_HEADER_LEN};
+use crate::transport::Transport;
+
+/// What the socket's event handlers have seen so far.
+#[derive(Default)]
+struct SocketState {
+    open: bool,
+    closed: bool,
+    /// Bytes received but not yet returned as frames.
+    received: Vec<u8>,
+    /// The task awaiting the next event, if any.
+    waker: Option<Waker>,
+}
+
+impl SocketState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct WebSocketTransport {
+    socket: WebSocket,
+    state: Rc<RefCell<SocketState>>,
+    // Owned here so they live as long as the socket can call them.
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(Event)>,
+}
+
+impl Transport for WebSocketTransport {
+    /// Opens a WebSocket to the `ws://` or `wss://` URL `addr` and waits for
+    /// it to open.
+    async fn connect(addr: &str) -> Result<Self> {
+        let connect_error = |source| Error::Connect {
+            addr: addr.to_string(),
+            source,
+        };
+        let socket = WebSocket::new(addr).map_err(|err| connect_error(js_io_error(err)))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let state = Rc::new(RefCell::new(SocketState::default()));
+        let on_open = Closure::<dyn FnMut(Event)>::new({
+            let state = state.clone();
+            move |_| {
+                let mut state = state.borrow_mut();
+                state.open = true;
+                state.wake();
+            }
+        });
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new({
+            let state = state.clone();
+            move |event: MessageEvent| {
+                let data = Uint8Array::new(&event.data()).to_vec();
+                let mut state = state.borrow_mut();
+                state.received.extend_from_slice(&data);
+                state.wake();
+            }
+        });
+        // An error event is always followed by a close event; either one
+        // ends the connection.
+        let on_close = Closure::<dyn FnMut(Event)>::new({
+            let state = state.clone();
+            move |_| {
+                let mut state = state.borrow_mut();
+                state.closed = true;
+                state.wake();
+            }
+        });
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_close.as_ref().unchecked_ref()));
+
+        let transport = Self {
+            socket,
+            state,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        };
+        poll_fn(|cx| {
+            let mut state = transport.state.borrow_mut();
+            if state.open {
+                Poll::Ready(Ok(()))
+            } else if state.closed {
+                Poll::Ready(Err(connect_error(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "websocket closed before opening",
+                ))))
+            } else {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await?;
+        Ok(transport)
+    }
+
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        if self.state.borrow().closed {
+            return Err(Error::ConnectionClosed);
+        }
+        self.socket
+            .send_with_u8_array(frame)
+            .map_err(|err| Error::Io(js_io_error(err)))
+    }
+
+    async fn read_frame(&mut self, max_frame_size: u32) -> Result<Frame> {
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            let header = state
+                .received
+                .first_chunk::<FRAME_HEADER_LEN>()
+                .map(FrameHeader::decode);
+            if let Some(header) = header {
+                if header.len > max_frame_size {
+                    return Poll::Ready(Err(Error::FrameTooLarge {
+                        len: header.len,
+                        max: max_frame_size,
+                    }));
+                }
+                let end = FRAME_HEADER_LEN + header.len as usize;
+                if state.received.len() >= end {
+                    let payload = state.received[FRAME_HEADER_LEN..end].to_vec();
+                    state.received.drain(..end);
+                    return Poll::Ready(Ok(Frame { header, payload }));
+                }
+            }
+            if state.closed {
+                return Poll::Ready(Err(Error::ConnectionClosed));
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl Drop for WebSocketTransport {
+    fn drop(&mut self) {
+        // Detach the handlers before their closures are freed.
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        self.socket.set_onerror(None);
+        let _ = self.socket.close();
+    }
+}
+
+fn js_io_error(err: JsValue) -> std::io::Error {
+    std::io::Error::other(format!("{err:?}"))
+}
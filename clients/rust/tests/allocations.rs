@@ -0,0 +1,92 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Heap allocations per request on a warmed-up connection.
+//!
+//! Counts allocations made by the calling thread only, so the mock server's
+//! own allocations are excluded. The budgets are the allocations a request
+//! genuinely needs; anything extra on the frame read or write path (a Vec
+//! per frame, a buffered reader per read) fails the test.
+
+#[path = "../benches/support/mod.rs"]
+mod support;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use cxdb::{dial, AppendRequest, ContextId, GetLastOptions, RequestContext};
+
+use support::MockServer;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Allocations made by `f` on this thread.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> u64 {
+    let start = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    let count = ALLOCATIONS.with(Cell::get) - start;
+    drop(value);
+    count
+}
+
+#[test]
+fn steady_state_requests_stay_within_allocation_budget() {
+    let server = MockServer::start();
+    let client = dial(server.addr(), Vec::new()).unwrap();
+    let ctx = RequestContext::background();
+    let context_id = ContextId::new(1);
+    let turns = 8;
+    let req = AppendRequest::new(context_id, "bench.Turn", 1, vec![0x5Au8; 4 * 1024]);
+    let opts = GetLastOptions {
+        limit: turns,
+        include_payload: true,
+        ..Default::default()
+    };
+    for _ in 0..turns {
+        client.append_turn(&ctx, &req).unwrap();
+        client.get_last(&ctx, context_id, opts.clone()).unwrap();
+    }
+
+    // Request body and response payload.
+    let append = count_allocations(|| client.append_turn(&ctx, &req).unwrap());
+    assert_eq!(append, 2, "append_turn allocations");
+
+    // Request body, response payload and result Vec, plus a payload per
+    // turn. The type id was interned by the warm-up reads.
+    let get_last = count_allocations(|| client.get_last(&ctx, context_id, opts.clone()).unwrap());
+    assert_eq!(get_last, 3 + u64::from(turns), "get_last allocations");
+
+    // Only the request body: the response buffer and the records' payloads
+    // are reused, and their type ids are interned.
+    let mut records = Vec::new();
+    client
+        .get_last_into(&ctx, context_id, opts.clone(), &mut records)
+        .unwrap();
+    let get_last_into =
+        count_allocations(|| client.get_last_into(&ctx, context_id, opts.clone(), &mut records));
+    assert_eq!(get_last_into, 1, "get_last_into allocations");
+    assert_eq!(records.len(), turns as usize);
+}
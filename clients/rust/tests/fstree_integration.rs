@@ -3,7 +3,7 @@
 
 use cxdb::fstree;
 use cxdb::types::{new_user_input, TypeIDConversationItem, TypeVersionConversationItem};
-use cxdb::{dial, encode_msgpack, AppendRequest, RequestContext};
+use cxdb::{dial, encode_msgpack, AppendRequest, CreateContextOptions, RequestContext};
 use serde_json::Value;
 
 #[test]
@@ -20,7 +20,7 @@ fn integration_fstree_snapshot_http() {
     let client = dial(&addr, Vec::new()).expect("dial failed");
     let ctx = RequestContext::background();
     let head = client
-        .create_context(&ctx, 0)
+        .create_context(&ctx, CreateContextOptions::default())
         .expect("create context failed");
 
     let temp_dir = tempfile::TempDir::new().expect("temp dir");
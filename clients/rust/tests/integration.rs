@@ -1,7 +1,21 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use cxdb::{dial, RequestContext};
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
+
+use cxdb::metrics::{with_metrics, InMemoryMetrics};
+use cxdb::{
+    dial, encode_msgpack, is_server_error, with_compression, with_turn_cache, AppendRequest,
+    ArchiveFilter, CacheConfig, Codec, CompactRequest, ContextId, ContextStats,
+    CreateContextOptions, DeleteFilter, Error, GetLastOptions, GetPathOptions, GetTurnOptions,
+    ImportOptions, IterOptions, LinkDirection, LinkKind, ListContextsOptions, Order, RedactOptions,
+    RequestContext, Snapshot, SubscribeOptions, SubscriptionItem, TextQuery, TurnFields, TurnId,
+    TurnLink, TypeHistogramOptions, WatchOptions,
+};
+use cxdb::{with_payload_encryption, PayloadKey};
 
 #[test]
 fn integration_create_context_smoke() {
@@ -14,7 +28,2145 @@ fn integration_create_context_smoke() {
     let client = dial(&addr, Vec::new()).expect("dial failed");
     let ctx = RequestContext::background();
     let head = client
-        .create_context(&ctx, 0)
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    assert!(!head.context_id.is_zero());
+
+    // Context values travel as frame metadata and must not disturb the request.
+    let traced = ctx.with_value("request_id", "integration-smoke");
+    let head = client
+        .get_head(&traced, head.context_id)
+        .expect("get head with metadata failed");
+    assert!(!head.context_id.is_zero());
+}
+
+#[test]
+fn integration_get_last_omits_large_payloads() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+
+    let small = encode_msgpack(&"preview").unwrap();
+    let large = encode_msgpack(&"x".repeat(4096)).unwrap();
+    for payload in [&small, &large] {
+        client
+            .append_turn(
+                &ctx,
+                &AppendRequest::new(head.context_id, "test.Blob", 1, payload.clone()),
+            )
+            .expect("append failed");
+    }
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let turns = client
+        .get_last(&ctx, head.context_id, opts.max_payload_bytes(1024))
+        .expect("get_last failed");
+    assert_eq!(turns.len(), 2);
+    assert_eq!(turns[0].payload, small);
+    assert!(turns[1].payload_omitted);
+    assert_eq!(turns[1].payload_size as usize, large.len());
+
+    let fetched = client
+        .get_blob(&ctx, &turns[1].payload_hash)
+        .expect("get_blob failed");
+    assert_eq!(fetched, large);
+}
+
+#[test]
+fn integration_append_returns_metadata() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+
+    let payload = encode_msgpack(&"hello").unwrap();
+    let result = client
+        .append_turn(
+            &ctx,
+            &AppendRequest::new(head.context_id, "test.Note", 1, payload.clone()),
+        )
+        .expect("append failed");
+    assert!(result.created_at_unix_ms.is_some_and(|ms| ms > 0));
+    assert_eq!(result.stored_len, Some(payload.len() as u32));
+    let new_head = result.head.expect("no head in append result");
+    assert_eq!(new_head.head_turn_id, result.turn_id);
+    assert_eq!(new_head.head_depth, 0);
+}
+
+#[test]
+fn integration_append_dedup() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+
+    let append = |text: &str| {
+        let req = AppendRequest::new(
+            head.context_id,
+            "test.Note",
+            1,
+            encode_msgpack(&text).unwrap(),
+        );
+        client
+            .append_dedup(&ctx, &req)
+            .expect("append_dedup failed")
+    };
+    let (first, appended) = append("same");
+    assert!(appended);
+    let (second, appended) = append("other");
+    assert!(appended);
+    let (repeat, appended) = append("same");
+    assert!(!appended);
+    assert_eq!(repeat.turn_id, first.turn_id);
+    assert_eq!(
+        repeat.head.map(|head| head.head_turn_id),
+        Some(second.turn_id)
+    );
+    let head = client
+        .get_head(&ctx, head.context_id)
+        .expect("get head failed");
+    assert_eq!(head.head_turn_id, second.turn_id);
+}
+
+#[test]
+fn integration_context_aliases() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let ctx = RequestContext::background();
+    let alias = format!("session:{}", uuid::Uuid::new_v4());
+
+    // Racing creators on separate connections all land on one context.
+    let infos: Vec<_> = (0..4)
+        .map(|_| {
+            let (addr, alias, ctx) = (addr.clone(), alias.clone(), ctx.clone());
+            std::thread::spawn(move || {
+                let client = dial(&addr, Vec::new()).expect("dial failed");
+                client
+                    .create_or_get_context_by_alias(&ctx, &alias, CreateContextOptions::default())
+                    .expect("create by alias failed")
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(infos.iter().filter(|info| info.created).count(), 1);
+    assert!(infos
+        .iter()
+        .all(|info| info.context_id == infos[0].context_id));
+
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    assert_eq!(
+        client.resolve_alias(&ctx, &alias).expect("resolve failed"),
+        Some(infos[0].context_id)
+    );
+    assert_eq!(
+        client
+            .resolve_alias(&ctx, "session:never-created")
+            .expect("resolve failed"),
+        None
+    );
+}
+
+#[test]
+fn integration_compact_context() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+
+    let mut turn_ids = Vec::new();
+    for i in 0..4u32 {
+        let payload = encode_msgpack(&i).unwrap();
+        let appended = client
+            .append_turn(
+                &ctx,
+                &AppendRequest::new(head.context_id, "test.Step", 1, payload),
+            )
+            .expect("append failed");
+        turn_ids.push(appended.turn_id);
+    }
+
+    let summary = encode_msgpack(&"steps 0-3").unwrap();
+    let compacted = client
+        .compact_context(
+            &ctx,
+            head.context_id,
+            CompactRequest::new(turn_ids[3], "test.Summary", 1, summary),
+        )
+        .expect("compact failed");
+
+    let opts = GetLastOptions {
+        limit: 100,
+        include_payload: true,
+        ..Default::default()
+    };
+    let turns = client
+        .get_last(&ctx, head.context_id, opts.clone())
+        .expect("get_last failed");
+    assert_eq!(turns.len(), 1);
+    assert_eq!(turns[0].turn_id, compacted.turn_id);
+
+    let turns = client
+        .get_last(&ctx, head.context_id, opts.include_compacted(true))
+        .expect("get_last with compacted failed");
+    assert_eq!(turns.len(), 5);
+
+    let first = client.get_turn(&ctx, turn_ids[0]).expect("get_turn failed");
+    assert_eq!(first.payload, encode_msgpack(&0u32).unwrap());
+}
+
+#[test]
+fn integration_writer_sequences() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+
+    let append = |seq: u64| {
+        let payload = encode_msgpack(&seq).unwrap();
+        let req = AppendRequest::new(head.context_id, "test.Step", 1, payload)
+            .writer_id("agent-a")
+            .writer_seq(seq);
+        client.append_turn(&ctx, &req)
+    };
+    append(1).expect("append 1 failed");
+    append(3).expect("append 3 failed");
+    let err = append(2).expect_err("out-of-order append succeeded");
+    assert!(matches!(
+        err,
+        Error::WriterSequenceConflict {
+            last_seq: 3,
+            writer_seq: 2,
+            ..
+        }
+    ));
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let turns = client
+        .get_last(&ctx, head.context_id, opts)
+        .expect("get_last failed");
+    let stamps: Vec<_> = turns
+        .iter()
+        .map(|t| (t.writer_id.as_deref(), t.writer_seq))
+        .collect();
+    assert_eq!(stamps, [(Some("agent-a"), 1), (Some("agent-a"), 3)]);
+}
+
+#[test]
+fn integration_turn_ttl() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+
+    let append = |step: u64, ttl: Option<Duration>| {
+        let payload = encode_msgpack(&step).unwrap();
+        let mut req = AppendRequest::new(head.context_id, "test.Step", 1, payload);
+        req.ttl = ttl;
+        client.append_turn(&ctx, &req).expect("append failed")
+    };
+    let first = append(1, None);
+    let scratch = append(2, Some(Duration::from_millis(1)));
+    let kept = append(3, Some(Duration::from_secs(3600)));
+    std::thread::sleep(Duration::from_millis(20));
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let live = client
+        .get_last(&ctx, head.context_id, opts.clone())
+        .expect("get_last failed");
+    let ids: Vec<_> = live.iter().map(|t| t.turn_id).collect();
+    assert_eq!(ids, [first.turn_id, kept.turn_id]);
+    assert_eq!(live[1].depth, kept.depth);
+    assert!(live[1].expires_at_unix_ms.is_some() && !live[1].expired);
+
+    let all = client
+        .get_last(&ctx, head.context_id, opts.include_expired(true))
+        .expect("get_last failed");
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[1].turn_id, scratch.turn_id);
+    assert!(all[1].expired);
+}
+
+#[test]
+fn integration_bearer_token() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+    // Needs a server started with CXDB_AUTH_TOKEN set to the same value.
+    let Ok(token) = std::env::var("CXDB_TEST_AUTH_TOKEN") else {
+        eprintln!("CXDB_TEST_AUTH_TOKEN not set; skipping integration test");
+        return;
+    };
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, [cxdb::with_bearer_token(token.clone())]).expect("dial failed");
+    client
+        .create_context(
+            &RequestContext::background(),
+            CreateContextOptions::default(),
+        )
+        .expect("create context failed");
+
+    // A per-request token replaces the connection's for that call only.
+    let wrong = RequestContext::background().with_auth(format!("{token}x"));
+    match client.create_context(&wrong, CreateContextOptions::default()) {
+        Err(Error::Unauthenticated { .. }) => {}
+        other => panic!("expected unauthenticated, got {other:?}"),
+    }
+    let right = RequestContext::background().with_auth(token.clone());
+    client
+        .create_context(&right, CreateContextOptions::default())
+        .expect("create context with request token failed");
+    client
+        .create_context(
+            &RequestContext::background(),
+            CreateContextOptions::default(),
+        )
+        .expect("create context after override failed");
+
+    for options in [
+        vec![cxdb::with_bearer_token(format!("{token}x"))],
+        Vec::new(),
+    ] {
+        match dial(&addr, options) {
+            Err(Error::Unauthenticated { .. }) => {}
+            Err(other) => panic!("expected unauthenticated, got {other:?}"),
+            Ok(_) => panic!("dial without a valid token succeeded"),
+        }
+    }
+}
+
+#[test]
+fn integration_turn_cache() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let metrics = std::sync::Arc::new(InMemoryMetrics::default());
+    let cached = dial(
+        &addr,
+        [
+            with_turn_cache(CacheConfig::default()),
+            with_metrics(metrics.clone()),
+        ],
+    )
+    .expect("dial failed");
+    let plain = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = plain
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    for step in 0..3u64 {
+        let payload = encode_msgpack(&step).unwrap();
+        let req = AppendRequest::new(head.context_id, "test.Step", 1, payload);
+        plain.append_turn(&ctx, &req).expect("append failed");
+    }
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let expected = plain
+        .get_last(&ctx, head.context_id, opts.clone())
+        .expect("get_last failed");
+    cached
+        .get_turn(&ctx, expected[0].turn_id)
+        .expect("get_turn failed");
+    for _ in 0..2 {
+        let turns = cached
+            .get_last(&ctx, head.context_id, opts.clone())
+            .expect("cached get_last failed");
+        assert_eq!(turns, expected);
+    }
+    assert_eq!((metrics.cache_hits(), metrics.cache_misses()), (4, 3));
+}
+
+#[test]
+fn integration_get_last_pages_in_order() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    let turn_ids: Vec<TurnId> = (0..5u64)
+        .map(|step| {
+            let payload = encode_msgpack(&step).unwrap();
+            let req = AppendRequest::new(head.context_id, "test.Step", 1, payload);
+            client
+                .append_turn(&ctx, &req)
+                .expect("append failed")
+                .turn_id
+        })
+        .collect();
+
+    for order in [Order::OldestFirst, Order::NewestFirst] {
+        let mut opts = GetLastOptions {
+            limit: 2,
+            include_payload: true,
+            ..Default::default()
+        }
+        .order(order);
+        let mut seen = Vec::new();
+        loop {
+            let page = client
+                .get_last(&ctx, head.context_id, opts.clone())
+                .expect("get_last failed");
+            let Some(oldest) = page.iter().map(|t| t.turn_id).min() else {
+                break;
+            };
+            seen.extend(page.iter().map(|t| t.turn_id));
+            opts = opts.before(oldest);
+        }
+        // Pages run newest to oldest; turns within a page follow `order`.
+        let expected = match order {
+            Order::OldestFirst => [3, 4, 1, 2, 0],
+            Order::NewestFirst => [4, 3, 2, 1, 0],
+        }
+        .map(|i| turn_ids[i]);
+        assert_eq!(seen, expected, "{order:?}");
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn integration_cbor_payloads_round_trip() {
+    use cxdb::protocol::{ENCODING_CBOR, ENCODING_MSGPACK};
+
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Reading {
+        sensor: String,
+        values: Vec<f64>,
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    let reading = Reading {
+        sensor: "t1".into(),
+        values: vec![20.5, -3.0],
+    };
+    let payload = cxdb::encode_cbor(&reading).unwrap();
+    let req =
+        AppendRequest::new(head.context_id, "test.Reading", 1, payload).encoding(ENCODING_CBOR);
+    client.append_turn(&ctx, &req).expect("append failed");
+    // A msgpack turn after it: one context may mix encodings.
+    let payload = cxdb::encode_msgpack(&reading).unwrap();
+    let req = AppendRequest::new(head.context_id, "test.Reading", 1, payload);
+    client.append_turn(&ctx, &req).expect("append failed");
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let turns = client
+        .get_last(&ctx, head.context_id, opts.clone())
+        .expect("get_last failed");
+    assert_eq!(turns[0].encoding, ENCODING_CBOR);
+    assert_eq!(turns[1].encoding, ENCODING_MSGPACK);
+    for turn in &turns {
+        assert_eq!(turn.decode::<Reading>().unwrap(), reading);
+    }
+
+    let mut exported = Vec::new();
+    client
+        .export_jsonl(&ctx, head.context_id, &mut exported)
+        .expect("export failed");
+    let imported = client
+        .import_jsonl(&ctx, exported.as_slice(), ImportOptions::default())
+        .expect("import failed");
+    let replayed = client
+        .get_last(&ctx, imported.context_id, opts)
+        .expect("get_last failed");
+    assert_eq!(replayed.len(), 2);
+    for (turn, original) in replayed.iter().zip(&turns) {
+        assert_eq!(turn.encoding, original.encoding);
+        assert_eq!(turn.payload_hash, original.payload_hash);
+        assert_eq!(turn.decode::<Reading>().unwrap(), reading);
+    }
+}
+
+#[test]
+fn integration_get_quotas_without_quota_support() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    // The reference server enforces no quotas and does not implement
+    // GET_QUOTAS; the client must say so rather than invent limits.
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let err = client.get_quotas(&ctx).unwrap_err();
+    assert!(matches!(err, Error::Unsupported(_)), "got {err:?}");
+    // The connection stays usable.
+    client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+}
+
+#[test]
+fn integration_search_turns() {
+    use cxdb::types::{new_assistant, new_user_input, ConversationItem};
+    use cxdb::{CxdbType, SearchQuery};
+
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    let items = [
+        new_user_input("where is my refund?", Vec::new()),
+        new_assistant("checking the refund now"),
+        new_user_input("thanks", Vec::new()),
+        new_user_input("refund arrived", Vec::new()),
+    ];
+    let mut turn_ids = Vec::new();
+    for item in &items {
+        let result = client
+            .append_typed(&ctx, head.context_id, item)
+            .expect("append failed");
+        turn_ids.push(result.turn_id);
+    }
+    client
+        .append_turn(
+            &ctx,
+            &AppendRequest::new(head.context_id, "test.Other", 1, vec![0xc0]),
+        )
+        .expect("append failed");
+
+    // User input text lives at {"10": {"1": text}}.
+    let query = SearchQuery::contains(["10", "1"], "refund").type_id(ConversationItem::TYPE_ID);
+    let hits = client
+        .search_turns(&ctx, head.context_id, &query)
+        .expect("search failed");
+    let ids: Vec<TurnId> = hits.iter().map(|hit| hit.turn.turn_id).collect();
+    assert_eq!(ids, [turn_ids[0], turn_ids[3]]);
+    assert_eq!(hits[1].value.as_str(), Some("refund arrived"));
+    assert_eq!(hits[1].turn.type_id, ConversationItem::TYPE_ID);
+
+    let query = SearchQuery::equals(["1"], "user_input").limit(2);
+    let hits = client
+        .search_turns(&ctx, head.context_id, &query)
+        .expect("search failed");
+    let ids: Vec<TurnId> = hits.iter().map(|hit| hit.turn.turn_id).collect();
+    assert_eq!(ids, [turn_ids[2], turn_ids[3]]);
+}
+
+#[test]
+fn integration_jsonl_export_import_round_trip() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    for step in 0..5u64 {
+        let payload = encode_msgpack(&vec![step; step as usize]).unwrap();
+        let req = AppendRequest::new(head.context_id, "test.Step", 1, payload);
+        client.append_turn(&ctx, &req).expect("append failed");
+    }
+
+    let mut exported = Vec::new();
+    let written = client
+        .export_jsonl(&ctx, head.context_id, &mut exported)
+        .expect("export failed");
+    assert_eq!(written, 5);
+
+    let imported = client
+        .import_jsonl(&ctx, exported.as_slice(), ImportOptions::default())
+        .expect("import failed");
+    assert_ne!(imported.context_id, head.context_id);
+    assert_eq!(imported.head_depth, 4);
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let original = client
+        .get_last(&ctx, head.context_id, opts.clone())
+        .expect("get_last failed");
+    let copy = client
+        .get_last(&ctx, imported.context_id, opts)
+        .expect("get_last failed");
+    let content = |turns: &[cxdb::TurnRecord]| {
+        turns
+            .iter()
+            .map(|t| {
+                (
+                    t.depth,
+                    t.type_id.clone(),
+                    t.payload_hash,
+                    t.payload.clone(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(content(&copy), content(&original));
+}
+
+#[test]
+fn integration_snapshot_restore_round_trip() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
         .expect("create context failed");
-    assert!(head.context_id > 0);
+    for step in 1..=4u64 {
+        let payload = encode_msgpack(&vec![step; step as usize]).unwrap();
+        let req = AppendRequest::new(head.context_id, "test.Step", 1, payload)
+            .writer_id("snapshotter")
+            .writer_seq(step);
+        client.append_turn(&ctx, &req).expect("append failed");
+    }
+
+    let snapshot = client
+        .snapshot_context(&ctx, head.context_id)
+        .expect("snapshot failed");
+    assert_eq!(snapshot.turns.len(), 4);
+    let blob = snapshot.to_bytes().expect("encode failed");
+    let restored = Snapshot::from_bytes(&blob).expect("decode failed");
+    assert_eq!(restored, snapshot);
+
+    let copy = client
+        .restore_context(&ctx, &restored)
+        .expect("restore failed");
+    assert_ne!(copy.context_id, head.context_id);
+    assert_eq!(copy.head_depth, 3);
+
+    let again = client
+        .snapshot_context(&ctx, copy.context_id)
+        .expect("snapshot failed");
+    let content = |snapshot: &Snapshot| {
+        snapshot
+            .turns
+            .iter()
+            .map(|t| {
+                (
+                    t.depth,
+                    t.type_id.clone(),
+                    t.payload.clone(),
+                    t.writer_id.clone(),
+                    t.writer_seq,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(content(&again), content(&snapshot));
+}
+
+#[test]
+fn integration_iter_turns_with_concurrent_appends() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let reader = dial(&addr, Vec::new()).expect("dial failed");
+    let writer = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = reader
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+
+    let append = |i: usize| {
+        let req = AppendRequest::new(context_id, "test.Note", 1, encode_msgpack(&i).unwrap());
+        writer
+            .append_turn(&ctx, &req)
+            .expect("append failed")
+            .turn_id
+    };
+    let mut written: Vec<TurnId> = (0..25).map(append).collect();
+
+    // Snapshot: the second client appends after every turn read, yet the
+    // iteration returns exactly the 25 turns present when it started.
+    let opts = IterOptions::default().page_size(10).snapshot(true);
+    let mut seen = Vec::new();
+    for turn in reader.iter_turns(&ctx, context_id, opts) {
+        seen.push(turn.expect("iterate failed").turn_id);
+        written.push(append(written.len()));
+    }
+    assert_eq!(seen, written[..25]);
+
+    // Default: appends made during the first 10 reads are returned too.
+    let opts = IterOptions::default().page_size(10);
+    let mut seen = Vec::new();
+    for turn in reader.iter_turns(&ctx, context_id, opts) {
+        seen.push(turn.expect("iterate failed").turn_id);
+        if seen.len() <= 10 {
+            written.push(append(written.len()));
+        }
+    }
+    assert_eq!(seen, written);
+}
+
+#[test]
+fn integration_turn_timestamps() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    let req = AppendRequest::new(
+        head.context_id,
+        "test.Note",
+        1,
+        encode_msgpack(&"hi").unwrap(),
+    );
+    let appended = client.append_turn(&ctx, &req).expect("append failed");
+    let created_at = appended.created_at_unix_ms;
+    assert!(created_at.is_some_and(|at| at > 0));
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let last = client
+        .get_last(&ctx, head.context_id, opts)
+        .expect("get_last failed");
+    assert_eq!(last[0].created_at_unix_ms, created_at);
+    let turn = client
+        .get_turn(&ctx, appended.turn_id)
+        .expect("get_turn failed");
+    assert_eq!(turn.created_at_unix_ms, created_at);
+
+    let mut out = Vec::new();
+    client
+        .export_jsonl(&ctx, head.context_id, &mut out)
+        .expect("export failed");
+    let line: serde_json::Value = serde_json::from_slice(out.trim_ascii_end()).unwrap();
+    assert_eq!(line["created_at_unix_ms"].as_u64(), created_at);
+}
+
+#[test]
+fn integration_get_path_across_a_fork() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let append = |context_id: ContextId, text: &str| {
+        let req = AppendRequest::new(context_id, "test.Note", 1, encode_msgpack(&text).unwrap());
+        client
+            .append_turn(&ctx, &req)
+            .expect("append failed")
+            .turn_id
+    };
+
+    let main = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let a = append(main, "a");
+    let b = append(main, "b");
+    let c = append(main, "c");
+    let fork = client
+        .create_context(&ctx, CreateContextOptions::default().base_turn_id(b))
+        .expect("fork failed")
+        .context_id;
+    let d = append(fork, "d");
+
+    let ids = |path: Vec<cxdb::TurnRecord>| path.iter().map(|t| t.turn_id).collect::<Vec<_>>();
+    let opts = GetPathOptions::default().page_size(1);
+    let path = client
+        .get_path(&ctx, fork, None, d, opts)
+        .expect("get_path failed");
+    assert_eq!(ids(path), [a, b, d]);
+    let path = client
+        .get_path(&ctx, main, Some(b), d, opts.include_payload(true))
+        .expect("get_path failed");
+    assert_eq!(path[1].decode::<String>().unwrap(), "d");
+    assert_eq!(ids(path), [b, d]);
+
+    let err = client.get_path(&ctx, fork, Some(c), d, opts).unwrap_err();
+    assert!(
+        matches!(err, Error::NotAnAncestor { ancestor, turn_id } if ancestor == c && turn_id == d)
+    );
+
+    let children = GetTurnOptions::default().include_payload(false);
+    let forked = client
+        .get_children(&ctx, main, b, children)
+        .expect("get_children failed");
+    assert_eq!(ids(forked), [c, d]);
+    let linear = client
+        .get_children(&ctx, main, a, children.include_payload(true))
+        .expect("get_children failed");
+    assert_eq!(linear[0].decode::<String>().unwrap(), "b");
+    assert!(client
+        .get_children(&ctx, fork, d, children)
+        .expect("get_children failed")
+        .is_empty());
+    let err = client
+        .get_children(&ctx, main, TurnId::new(d.get() + 1_000_000), children)
+        .unwrap_err();
+    assert!(matches!(err, Error::TurnNotFound { .. }), "{err:?}");
+}
+
+#[test]
+fn integration_prune_context() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let turns: Vec<TurnId> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|text| {
+            let req = AppendRequest::new(context_id, "test.Note", 1, encode_msgpack(text).unwrap());
+            client
+                .append_turn(&ctx, &req)
+                .expect("append failed")
+                .turn_id
+        })
+        .collect();
+
+    let result = client
+        .prune_context(&ctx, context_id, 2)
+        .expect("prune failed");
+    assert_eq!(result.turns_pruned, 2);
+    assert!(result.bytes_reclaimed > 0);
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let history = client
+        .get_last(&ctx, context_id, opts)
+        .expect("get_last failed");
+    let ids: Vec<TurnId> = history.iter().map(|t| t.turn_id).collect();
+    assert_eq!(ids, turns[2..]);
+    assert_eq!(history[0].parent_id, turns[1]);
+
+    let err = client
+        .get_path(&ctx, context_id, None, turns[3], GetPathOptions::default())
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::Pruned { turn_id } if turn_id == turns[1]),
+        "{err:?}"
+    );
+    let path = client
+        .get_path(
+            &ctx,
+            context_id,
+            Some(turns[2]),
+            turns[3],
+            GetPathOptions::default(),
+        )
+        .expect("get_path failed");
+    assert_eq!(path.len(), 2);
+
+    let err = client.prune_context(&ctx, context_id, 9).unwrap_err();
+    assert!(is_server_error(&err, 422), "{err:?}");
+}
+
+#[test]
+fn integration_archive_contexts() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let req = AppendRequest::new(context_id, "test.Note", 1, encode_msgpack(&"a").unwrap());
+    let first = client.append_turn(&ctx, &req).expect("append failed");
+
+    let head = client
+        .archive_context(&ctx, context_id)
+        .expect("archive failed");
+    assert_eq!(head.head_turn_id, first.turn_id);
+    let archived = client
+        .list_contexts(
+            &ctx,
+            ListContextsOptions::default()
+                .limit(u32::MAX)
+                .filter(ArchiveFilter::Archived),
+        )
+        .expect("list_contexts failed");
+    assert!(archived
+        .iter()
+        .any(|c| c.context_id == context_id && c.archived));
+
+    let is_archived =
+        |err: Error| matches!(err, Error::ContextArchived { context_id: id } if id == context_id);
+    let err = client
+        .get_last(&ctx, context_id, GetLastOptions::default())
+        .unwrap_err();
+    assert!(is_archived(err));
+    assert!(is_archived(client.append_turn(&ctx, &req).unwrap_err()));
+    assert_eq!(
+        client
+            .get_head(&ctx, context_id)
+            .expect("get_head failed")
+            .head_turn_id,
+        first.turn_id
+    );
+
+    let turns = client
+        .get_last(
+            &ctx,
+            context_id,
+            GetLastOptions::default().auto_restore(true),
+        )
+        .expect("auto_restore read failed");
+    assert_eq!(turns.len(), 1);
+    client
+        .append_turn(&ctx, &req)
+        .expect("append after restore failed");
+}
+
+#[test]
+fn integration_namespaces_need_server_support() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    // The reference server does not scope by namespace, so a namespaced
+    // client must not get through to its unnamespaced contexts.
+    let err = dial(&addr, [cxdb::with_namespace("prod-eu")])
+        .err()
+        .expect("namespaced dial succeeded");
+    assert!(
+        matches!(&err, cxdb::Error::Unsupported(what) if what == "namespaces"),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn integration_delete_contexts() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let create = || {
+        client
+            .create_context(&ctx, CreateContextOptions::default())
+            .expect("create context failed")
+            .context_id
+    };
+    let (a, b, c) = (create(), create(), create());
+    let tag = format!("delete-test-{a}");
+    // Context metadata lives at {30: {1: client_tag}}, with integer keys.
+    let metadata = BTreeMap::from([(30u64, BTreeMap::from([(1u64, tag.clone())]))]);
+    let payload = encode_msgpack(&metadata).unwrap();
+    for context_id in [a, b] {
+        let req = AppendRequest::new(context_id, "test.Tagged", 1, payload.clone());
+        client.append_turn(&ctx, &req).expect("append failed");
+    }
+
+    let err = client
+        .delete_contexts(&ctx, &DeleteFilter::new())
+        .unwrap_err();
+    assert!(matches!(err, Error::EmptyFilter), "{err:?}");
+    // Criteria are AND-ed: nothing was created before the epoch.
+    let none = DeleteFilter::new().ids([a, b, c]).created_before(0);
+    assert_eq!(
+        client.delete_contexts(&ctx, &none).expect("delete failed"),
+        0
+    );
+
+    let tagged = DeleteFilter::new().ids([a, b, c]).metadata("tag", tag);
+    assert_eq!(
+        client
+            .delete_contexts(&ctx, &tagged)
+            .expect("delete failed"),
+        2
+    );
+    for context_id in [a, b] {
+        let err = client.get_head(&ctx, context_id).unwrap_err();
+        assert!(
+            matches!(err, Error::ContextNotFound { context_id: id } if id == context_id),
+            "{err:?}"
+        );
+    }
+    client.get_head(&ctx, c).expect("untagged context survives");
+    assert!(!client
+        .context_exists(&ctx, a)
+        .expect("context_exists failed"));
+    assert!(client
+        .context_exists(&ctx, c)
+        .expect("context_exists failed"));
+}
+
+#[test]
+fn integration_shared_client_across_threads() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = Arc::new(dial(&addr, Vec::new()).expect("dial failed"));
+    let writers: Vec<_> = (0..8)
+        .map(|_| {
+            let client = client.clone();
+            std::thread::spawn(move || {
+                let ctx = RequestContext::background();
+                let context_id = client
+                    .create_context(&ctx, CreateContextOptions::default())
+                    .expect("create context failed")
+                    .context_id;
+                for n in 0..25u32 {
+                    let req =
+                        AppendRequest::new(context_id, "test.Note", 1, encode_msgpack(&n).unwrap());
+                    client.append_turn(&ctx, &req).expect("append failed");
+                }
+                context_id
+            })
+        })
+        .collect();
+
+    let ctx = RequestContext::background();
+    for writer in writers {
+        let context_id = writer.join().unwrap();
+        let turns = client
+            .get_last(
+                &ctx,
+                context_id,
+                GetLastOptions::default().limit(100).include_payload(true),
+            )
+            .expect("get_last failed");
+        let values: Vec<u32> = turns.iter().map(|t| t.decode().unwrap()).collect();
+        assert_eq!(values, (0..25).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn integration_redact_turn() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let secret = encode_msgpack(&"ssn 078-05-1120").unwrap();
+    let turns: Vec<TurnId> = [secret.clone(), encode_msgpack(&"ok").unwrap()]
+        .into_iter()
+        .map(|payload| {
+            let req = AppendRequest::new(context_id, "test.Note", 1, payload);
+            client
+                .append_turn(&ctx, &req)
+                .expect("append failed")
+                .turn_id
+        })
+        .collect();
+
+    let opts = RedactOptions::default().reason("pii");
+    let redaction = client
+        .redact_turn(&ctx, context_id, turns[0], opts.clone())
+        .expect("redact failed");
+    assert_eq!(redaction.reason, "pii");
+    let again = client
+        .redact_turn(&ctx, context_id, turns[0], RedactOptions::default())
+        .expect("redact failed");
+    assert_eq!(again, redaction);
+
+    let history = client
+        .get_last(
+            &ctx,
+            context_id,
+            GetLastOptions {
+                include_payload: true,
+                ..Default::default()
+            },
+        )
+        .expect("get_last failed");
+    assert_eq!(history.len(), 2);
+    assert!(history[0].redacted && history[0].payload.is_empty());
+    assert_eq!(history[0].payload_hash, *blake3::hash(&secret).as_bytes());
+    assert_eq!(
+        history[0].redacted_at_unix_ms,
+        Some(redaction.redacted_at_unix_ms)
+    );
+    assert!(matches!(
+        history[0].decode::<String>(),
+        Err(Error::Redacted { .. })
+    ));
+    assert!(!history[1].redacted);
+
+    let mut out = Vec::new();
+    client
+        .export_jsonl(&ctx, context_id, &mut out)
+        .expect("export failed");
+    assert!(std::str::from_utf8(&out)
+        .unwrap()
+        .contains(r#""redaction_reason":"pii""#));
+
+    let err = client
+        .redact_turn(&ctx, context_id, TurnId::new(turns[1].get() + 1000), opts)
+        .unwrap_err();
+    assert!(matches!(err, Error::TurnNotFound { .. }), "{err:?}");
+}
+
+#[test]
+fn integration_get_last_depth_filters() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let append = |context_id: ContextId, step: u64| {
+        let req = AppendRequest::new(context_id, "test.Step", 1, encode_msgpack(&step).unwrap());
+        client.append_turn(&ctx, &req).expect("append failed")
+    };
+    // Depths 0..=5; a fork branches off depth 2 and continues at depth 3.
+    let turns: Vec<_> = (0..6).map(|step| append(context_id, step)).collect();
+    let fork = client
+        .fork_context(&ctx, turns[2].turn_id)
+        .expect("fork failed");
+    let branch = append(fork.context_id, 100);
+    assert_eq!(branch.depth, 3);
+
+    let depths = |context_id: ContextId, opts: GetLastOptions| {
+        client
+            .get_last(&ctx, context_id, opts)
+            .expect("get_last failed")
+            .iter()
+            .map(|t| t.depth)
+            .collect::<Vec<_>>()
+    };
+    let opts = GetLastOptions {
+        limit: 2,
+        include_payload: true,
+        ..Default::default()
+    };
+    assert_eq!(depths(context_id, opts.clone().max_depth(3)), [2, 3]);
+    assert_eq!(
+        depths(context_id, opts.clone().min_depth(1).max_depth(1)),
+        [1]
+    );
+    assert_eq!(depths(fork.context_id, opts.clone().min_depth(2)), [2, 3]);
+    assert_eq!(depths(fork.context_id, opts.clone().max_depth(1)), [0, 1]);
+    let head = client.get_head(&ctx, context_id).expect("get_head failed");
+    assert!(depths(context_id, opts.min_depth(head.head_depth + 1)).is_empty());
+}
+
+#[test]
+fn integration_text_search() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    // Other tests share the server, so the searched word is unique to this run.
+    let marker = format!("marker{context_id}");
+    let turns: Vec<TurnId> = [
+        format!("Flights to San Francisco {marker}"),
+        "nothing to see".to_string(),
+        format!("{marker} francisco hotels"),
+    ]
+    .into_iter()
+    .map(|text| {
+        let req = AppendRequest::new(context_id, "test.Note", 1, encode_msgpack(&text).unwrap());
+        client
+            .append_turn(&ctx, &req)
+            .expect("append failed")
+            .turn_id
+    })
+    .collect();
+
+    let query = TextQuery::new(format!("Francisco {marker}")).limit(1);
+    let hits = client.search(&ctx, &query).expect("search failed");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].turn.turn_id, turns[2]);
+    assert_eq!(hits[0].context_id, context_id);
+    assert_eq!(hits[0].snippet, format!("{marker} francisco hotels"));
+
+    let next = client
+        .search(&ctx, &query.clone().before(turns[2]))
+        .expect("search failed");
+    assert_eq!(next.len(), 1);
+    assert_eq!(next[0].turn.turn_id, turns[0]);
+
+    let scoped = TextQuery::new("hotels").context(context_id);
+    let hits = client.search(&ctx, &scoped).expect("search failed");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].turn.turn_id, turns[2]);
+}
+
+#[test]
+fn integration_type_histogram() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    for type_id in [
+        "test.Msg",
+        "test.Tool",
+        "test.Msg",
+        "test.Tool",
+        "test.Tool",
+    ] {
+        let req = AppendRequest::new(context_id, type_id, 1, encode_msgpack(&"x").unwrap());
+        client.append_turn(&ctx, &req).expect("append failed");
+    }
+
+    let counts = client
+        .type_histogram(&ctx, context_id)
+        .expect("histogram failed");
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["test.Msg"], 2);
+    assert_eq!(counts["test.Tool"], 3);
+
+    let opts = TypeHistogramOptions::default().min_depth(1).max_depth(2);
+    let counts = client
+        .type_histogram_scoped(&ctx, context_id, &opts)
+        .expect("histogram failed");
+    assert_eq!(counts["test.Msg"], 1);
+    assert_eq!(counts["test.Tool"], 1);
+}
+
+#[test]
+fn integration_server_capabilities() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let caps = client.server_capabilities();
+    assert_eq!(caps.protocol_version, 1);
+    assert!(caps.message_types.is_some());
+    assert!(caps.supports(cxdb::protocol::MSG_CONTEXT_STATS));
+    assert!(caps.has_flag(cxdb::protocol::FLAG_PROJECTION));
+    // No quotas: get_quotas fails without a round trip.
+    assert!(!caps.supports(cxdb::protocol::MSG_GET_QUOTAS));
+}
+
+#[test]
+fn integration_frame_compression() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, [with_compression(Codec::Zstd)]).expect("dial failed");
+    assert_eq!(client.negotiated_compression(), Some(Codec::Zstd));
+    let ctx = RequestContext::background();
+
+    // Large enough that both the append and the read are compressed.
+    let payload = encode_msgpack(&"compressible ".repeat(4096)).unwrap();
+    let head = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .unwrap();
+    for _ in 0..4 {
+        let req = AppendRequest::new(head.context_id, "test.Big", 1, payload.clone());
+        client.append_turn(&ctx, &req).unwrap();
+    }
+    let turns = client
+        .get_last(
+            &ctx,
+            head.context_id,
+            GetLastOptions {
+                limit: 4,
+                include_payload: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(turns.len(), 4);
+    assert!(turns.iter().all(|turn| turn.payload == payload));
+
+    let plain = dial(&addr, Vec::new()).expect("dial failed");
+    assert_eq!(plain.negotiated_compression(), None);
+}
+
+#[test]
+fn integration_get_last_meta() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    for i in 0..3u32 {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&i).unwrap());
+        client.append_turn(&ctx, &req).expect("append failed");
+    }
+
+    let opts = GetLastOptions::default();
+    let metas = client
+        .get_last_meta(&ctx, context_id, opts.clone())
+        .expect("get_last_meta failed");
+    let turns = client
+        .get_last_full(&ctx, context_id, opts)
+        .expect("get_last_full failed");
+    assert_eq!(metas.len(), 3);
+    for (i, (meta, turn)) in metas.into_iter().zip(turns).enumerate() {
+        assert_eq!(meta, turn.meta);
+        assert_eq!(turn.payload, encode_msgpack(&(i as u32)).unwrap());
+    }
+}
+
+#[test]
+fn integration_for_each_turn() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let mut appended = Vec::new();
+    for i in 0..5u32 {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&i).unwrap());
+        appended.push(
+            client
+                .append_turn(&ctx, &req)
+                .expect("append failed")
+                .turn_id,
+        );
+    }
+
+    let opts = GetLastOptions {
+        limit: u32::MAX,
+        include_payload: true,
+        ..Default::default()
+    };
+    let mut seen = Vec::new();
+    client
+        .for_each_turn(&ctx, context_id, opts, |turn| {
+            seen.push(turn.turn_id);
+            if seen.len() == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .expect("for_each_turn failed");
+    assert_eq!(seen, appended[..3]);
+}
+
+#[test]
+fn integration_context_stats() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let mut turns = Vec::new();
+    for (type_id, text) in [
+        ("test.Msg", "hi"),
+        ("test.Tool", "lookup"),
+        ("test.Msg", "ok"),
+    ] {
+        let req = AppendRequest::new(context_id, type_id, 1, encode_msgpack(&text).unwrap());
+        turns.push(
+            client
+                .append_turn(&ctx, &req)
+                .expect("append failed")
+                .turn_id,
+        );
+    }
+    client
+        .redact_turn(&ctx, context_id, turns[1], RedactOptions::default())
+        .expect("redact failed");
+    let fork = client
+        .fork_context(&ctx, turns[2])
+        .expect("fork failed")
+        .context_id;
+    let req = AppendRequest::new(fork, "test.Msg", 1, encode_msgpack(&"more").unwrap());
+    client.append_turn(&ctx, &req).expect("append failed");
+
+    let stats = client
+        .context_stats(&ctx, fork)
+        .expect("context stats failed");
+    assert_eq!(stats.turns, 4);
+    assert_eq!(stats.bytes_by_type.len(), 2);
+    assert_eq!(stats.bytes_by_type["test.Tool"], 0);
+    assert_eq!(stats.payload_bytes, stats.bytes_by_type["test.Msg"]);
+    assert!(stats.first_created_at_unix_ms <= stats.last_created_at_unix_ms);
+    assert_eq!(
+        stats,
+        ContextStats::compute(&client, &ctx, fork).expect("compute failed")
+    );
+
+    let stats = client
+        .context_stats(&ctx, context_id)
+        .expect("context stats failed");
+    assert_eq!(stats.turns, 3);
+}
+
+#[test]
+fn integration_payload_encryption() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let key = PayloadKey::new("it-key", [42; 32]);
+    let client = dial(&addr, [with_payload_encryption(Arc::new(key))]).expect("dial failed");
+    let plain = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let payload = encode_msgpack(&"top secret").unwrap();
+    let req = AppendRequest::new(context_id, "test.Note", 1, payload.clone());
+    let appended = client.append_turn(&ctx, &req).expect("append failed");
+    assert_ne!(appended.payload_hash, *blake3::hash(&payload).as_bytes());
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let turns = client
+        .get_last(&ctx, context_id, opts.clone())
+        .expect("get_last failed");
+    assert_eq!(turns[0].decode::<String>().unwrap(), "top secret");
+    let turn = client
+        .get_turn(&ctx, appended.turn_id)
+        .expect("get_turn failed");
+    assert_eq!(turn.payload, payload);
+
+    // The server and keyless clients only see the envelope.
+    let sealed = plain
+        .get_last(&ctx, context_id, opts.clone())
+        .expect("get_last failed");
+    assert_eq!(
+        *blake3::hash(&sealed[0].payload).as_bytes(),
+        appended.payload_hash
+    );
+    let err = sealed[0].decode::<String>().unwrap_err();
+    assert!(
+        matches!(&err, Error::NoDecryptionKey { key_id } if key_id == "it-key"),
+        "{err:?}"
+    );
+
+    // Exports keep the envelope, and the imported copy opens with the key.
+    let mut exported = Vec::new();
+    client
+        .export_jsonl(&ctx, context_id, &mut exported)
+        .expect("export failed");
+    let copy = client
+        .import_jsonl(&ctx, exported.as_slice(), ImportOptions::default())
+        .expect("import failed");
+    let copied = client
+        .get_last(&ctx, copy.context_id, opts)
+        .expect("get_last failed");
+    assert_eq!(copied[0].payload_hash, appended.payload_hash);
+    assert_eq!(copied[0].decode::<String>().unwrap(), "top secret");
+}
+
+#[test]
+fn integration_append_multi() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let chat = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    let audit = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed");
+    let entry = |context_id: ContextId, text: &str, writer_seq: u64| {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&text).unwrap())
+            .writer_id("fanout")
+            .writer_seq(writer_seq);
+        (context_id, req)
+    };
+
+    let results = client
+        .append_multi(
+            &ctx,
+            &[
+                entry(chat.context_id, "hello", 1),
+                entry(audit.context_id, "hello", 1),
+                entry(chat.context_id, "again", 2),
+            ],
+        )
+        .expect("append_multi failed");
+    let depths: Vec<_> = results.iter().map(|r| (r.context_id, r.depth)).collect();
+    assert_eq!(
+        depths,
+        [
+            (chat.context_id, 0),
+            (audit.context_id, 0),
+            (chat.context_id, 1)
+        ]
+    );
+
+    // The audit entry's stale sequence aborts the chat entry too.
+    let err = client
+        .append_multi(
+            &ctx,
+            &[
+                entry(chat.context_id, "third", 3),
+                entry(audit.context_id, "stale", 1),
+            ],
+        )
+        .unwrap_err();
+    match err {
+        Error::TransactionAborted { entry, source } => {
+            assert_eq!(entry, 1);
+            assert!(
+                matches!(*source, Error::WriterSequenceConflict { last_seq: 1, .. }),
+                "{source:?}"
+            );
+        }
+        other => panic!("expected abort, got {other:?}"),
+    }
+    let head = client
+        .get_head(&ctx, chat.context_id)
+        .expect("get_head failed");
+    assert_eq!(head.head_turn_id, results[2].turn_id);
+}
+
+#[test]
+fn integration_turn_at_depth() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let mut turns = Vec::new();
+    for i in 0..5u32 {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&i).unwrap());
+        turns.push(client.append_turn(&ctx, &req).expect("append failed"));
+    }
+    // A fork shares depths 0..=2 and then goes its own way.
+    let fork = client
+        .fork_context(&ctx, turns[2].turn_id)
+        .expect("fork failed");
+    let req = AppendRequest::new(
+        fork.context_id,
+        "test.Msg",
+        1,
+        encode_msgpack(&9u32).unwrap(),
+    );
+    let branch = client.append_turn(&ctx, &req).expect("append failed");
+
+    let turn = client
+        .get_turn_at_depth(&ctx, context_id, 3, GetTurnOptions::default())
+        .expect("get_turn_at_depth failed");
+    assert_eq!(turn.turn_id, turns[3].turn_id);
+    assert_eq!(turn.decode::<u32>().unwrap(), 3);
+    let shared = client
+        .get_turn_at_depth(&ctx, fork.context_id, 1, GetTurnOptions::default())
+        .expect("get_turn_at_depth failed");
+    assert_eq!(shared.turn_id, turns[1].turn_id);
+    let own = client
+        .get_turn_at_depth(&ctx, fork.context_id, 3, GetTurnOptions::default())
+        .expect("get_turn_at_depth failed");
+    assert_eq!(own.turn_id, branch.turn_id);
+
+    let err = client
+        .get_turn_at_depth(&ctx, fork.context_id, 4, GetTurnOptions::default())
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::DepthOutOfRange {
+                depth: 4,
+                head_depth: 3,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn integration_subscribe_and_range() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let append = |i: u32| {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&i).unwrap());
+        client.append_turn(&ctx, &req).expect("append failed")
+    };
+    for i in 0..3 {
+        append(i);
+    }
+
+    let opts = SubscribeOptions::default()
+        .from_depth(0)
+        .poll_interval(Duration::from_millis(20));
+    let mut subscription = client
+        .subscribe(context_id, opts)
+        .expect("subscribe failed");
+    let mut next = || match subscription.next() {
+        Some(Ok(SubscriptionItem::Turn(turn))) => turn.decode::<u32>().unwrap(),
+        other => panic!("expected a turn, got {other:?}"),
+    };
+    assert_eq!([next(), next(), next()], [0, 1, 2]);
+    append(3);
+    append(4);
+    assert_eq!([next(), next()], [3, 4]);
+
+    let range = client
+        .get_range(&ctx, context_id, 1, 3, GetTurnOptions::default())
+        .expect("get_range failed");
+    let values: Vec<u32> = range.iter().map(|t| t.decode().unwrap()).collect();
+    assert_eq!(values, [1, 2, 3]);
+}
+
+#[test]
+fn integration_watch_contexts() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let create = || {
+        client
+            .create_context(&ctx, CreateContextOptions::default())
+            .expect("create context failed")
+            .context_id
+    };
+    let (a, b) = (create(), create());
+    let append = |context_id: ContextId| {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&1u32).unwrap());
+        client.append_turn(&ctx, &req).expect("append failed")
+    };
+
+    let opts = WatchOptions::default().poll_interval(Duration::from_millis(20));
+    let mut watch = client.watch(&ctx, &[a], opts).expect("watch failed");
+    watch.add(&[b]);
+    std::thread::sleep(Duration::from_millis(200));
+    let appended = append(b);
+    let event = watch.next().expect("watch ended").expect("watch failed");
+    assert_eq!(event.context_id, b);
+    assert_eq!(event.new_head_turn_id, appended.turn_id);
+    assert_eq!(event.new_depth, appended.depth);
+
+    watch.remove(&[b]);
+    append(b);
+    let appended = append(a);
+    let event = watch.next().expect("watch ended").expect("watch failed");
+    assert_eq!(
+        (event.context_id, event.new_head_turn_id),
+        (a, appended.turn_id)
+    );
+}
+
+#[test]
+fn integration_get_last_projection() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let mut appended = Vec::new();
+    for i in 0..3u32 {
+        let req = AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&i).unwrap());
+        appended.push(client.append_turn(&ctx, &req).expect("append failed"));
+    }
+
+    let full = client
+        .get_last(&ctx, context_id, GetLastOptions::default())
+        .expect("get_last failed");
+    assert_eq!(full.len(), 3);
+    assert_eq!(full[2].type_id, "test.Msg");
+
+    let opts = GetLastOptions::default().projection(TurnFields::CONTENT_HASH);
+    let hashes = client
+        .get_last(&ctx, context_id, opts)
+        .expect("projected get_last failed");
+    for (turn, ack) in hashes.iter().zip(&appended) {
+        assert_eq!(turn.turn_id, ack.turn_id);
+        assert_eq!(turn.payload_hash, ack.payload_hash);
+        assert_eq!(
+            (turn.depth, turn.parent_id, turn.type_id.as_str()),
+            (0, TurnId::default(), "")
+        );
+    }
+
+    let opts = GetTurnOptions::default().projection(TurnFields::DEPTH);
+    let turns = client
+        .get_range(&ctx, context_id, 1, 2, opts)
+        .expect("projected get_range failed");
+    let depths: Vec<u32> = turns.iter().map(|t| t.depth).collect();
+    assert_eq!(depths, [1, 2]);
+    assert_eq!(turns[1].decode::<u32>().unwrap(), 2);
+    assert_eq!(turns[1].payload_hash, [0; 32]);
+}
+
+#[test]
+fn integration_turn_links() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+    let msg = |context_id: ContextId, i: u32| {
+        AppendRequest::new(context_id, "test.Msg", 1, encode_msgpack(&i).unwrap())
+    };
+    let call = client
+        .append_turn(&ctx, &msg(context_id, 0))
+        .expect("append failed");
+    let result = client
+        .append_turn(
+            &ctx,
+            &msg(context_id, 1).link_to(call.turn_id, LinkKind::Causes),
+        )
+        .expect("linked append failed");
+
+    // A fork shares the call but not turns appended to its base later.
+    let fork = client
+        .fork_context(&ctx, result.turn_id)
+        .expect("fork failed");
+    let later = client
+        .append_turn(&ctx, &msg(context_id, 2))
+        .expect("append failed");
+    let err = client
+        .append_turn(
+            &ctx,
+            &msg(fork.context_id, 3).link_to(later.turn_id, LinkKind::RepliesTo),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::TurnNotFound { turn_id } if turn_id == later.turn_id),
+        "{err:?}"
+    );
+    let reply = client
+        .append_turn(
+            &ctx,
+            &msg(fork.context_id, 3).link_to(call.turn_id, LinkKind::Custom("cites".into())),
+        )
+        .expect("cross-fork link failed");
+
+    let outgoing = client
+        .get_linked_turns(&ctx, context_id, result.turn_id, LinkDirection::Outgoing)
+        .expect("get_linked_turns failed");
+    assert_eq!(outgoing.len(), 1);
+    assert_eq!(outgoing[0].turn_id, call.turn_id);
+    assert_eq!(outgoing[0].decode::<u32>().unwrap(), 0);
+
+    let incoming = client
+        .get_linked_turns(&ctx, context_id, call.turn_id, LinkDirection::Incoming)
+        .expect("get_linked_turns failed");
+    assert_eq!(incoming.len(), 1);
+    assert_eq!(incoming[0].turn_id, result.turn_id);
+    assert_eq!(
+        incoming[0].links,
+        [TurnLink {
+            target_turn_id: call.turn_id,
+            kind: LinkKind::Causes,
+        }]
+    );
+    let incoming = client
+        .get_linked_turns(&ctx, fork.context_id, call.turn_id, LinkDirection::Incoming)
+        .expect("get_linked_turns failed");
+    let ids: Vec<TurnId> = incoming.iter().map(|t| t.turn_id).collect();
+    assert_eq!(ids, [result.turn_id, reply.turn_id]);
+}
+
+#[test]
+fn integration_delta_payloads() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let context_id = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create context failed")
+        .context_id;
+
+    let mut state: Vec<(u32, String)> = (0..2000)
+        .map(|i| (i, format!("fact {i} about the workspace")))
+        .collect();
+    let mut snapshots = Vec::new();
+    let mut base = None;
+    for step in 0..3 {
+        state[step * 500].1 = format!("fact revised at step {step}");
+        let payload = encode_msgpack(&state).unwrap();
+        let mut req = AppendRequest::new(context_id, "com.example.AgentState", 1, payload.clone());
+        if let Some(turn_id) = base {
+            req = req.delta_against(turn_id);
+        }
+        base = Some(
+            client
+                .append_turn(&ctx, &req)
+                .expect("append failed")
+                .turn_id,
+        );
+        snapshots.push(payload);
+    }
+
+    let opts = GetLastOptions {
+        include_payload: true,
+        ..Default::default()
+    };
+    let history = client
+        .get_last(&ctx, context_id, opts.clone())
+        .expect("get_last failed");
+    let payloads: Vec<_> = history.iter().map(|t| t.payload.clone()).collect();
+    assert_eq!(payloads, snapshots);
+    let head = client
+        .get_turn(&ctx, history[2].turn_id)
+        .expect("get_turn failed");
+    assert_eq!(head.payload, snapshots[2]);
+
+    let raw = client
+        .get_last(&ctx, context_id, opts.clone().include_raw_delta(true))
+        .expect("get_last failed");
+    assert_eq!(raw[0].encoding, cxdb::protocol::ENCODING_MSGPACK);
+    for turn in &raw[1..] {
+        assert_eq!(turn.encoding, cxdb::delta::ENCODING_DELTA);
+        assert!(turn.payload.len() * 20 < snapshots[0].len(), "{turn}");
+    }
+
+    client
+        .prune_context(&ctx, context_id, 1)
+        .expect("prune failed");
+    let err = client.get_last(&ctx, context_id, opts).unwrap_err();
+    assert!(
+        matches!(err, Error::DeltaBaseUnavailable { base_turn_id, .. } if base_turn_id == history[0].turn_id),
+        "{err:?}"
+    );
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn integration_fixtures() {
+    use cxdb::testing::fixtures::FixtureBuilder;
+
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+    let manifest = FixtureBuilder::new(7)
+        .contexts(2)
+        .turns_per_context(5..10)
+        .type_mix(&[("test.Message", 0.7), ("test.ToolCall", 0.3)])
+        .build(&client)
+        .expect("fixtures failed");
+    assert_eq!(manifest.contexts.len(), 2);
+
+    for context in &manifest.contexts {
+        let turns = client
+            .get_last(&ctx, context.context_id, GetLastOptions::default())
+            .expect("get_last failed");
+        let stored: Vec<(TurnId, &str, [u8; 32])> = turns
+            .iter()
+            .map(|turn| (turn.turn_id, &*turn.type_id, turn.payload_hash))
+            .collect();
+        let expected: Vec<(TurnId, &str, [u8; 32])> = context
+            .turns
+            .iter()
+            .map(|turn| (turn.turn_id, turn.type_id.as_str(), turn.payload_hash))
+            .collect();
+        assert_eq!(stored, expected);
+    }
+}
+
+#[test]
+fn integration_create_context_with_turn() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+
+    let system = AppendRequest::new(ContextId::default(), "test.System", 1, vec![0x80]);
+    let (head, first) = client
+        .create_context_with_turn(&ctx, CreateContextOptions::default(), &system)
+        .expect("create with turn failed");
+    assert_eq!(head.head_turn_id, first.turn_id);
+    assert_eq!(first.depth, 0);
+    let turns = client
+        .get_last(&ctx, head.context_id, GetLastOptions::default())
+        .expect("get_last failed");
+    assert_eq!(turns.len(), 1);
+    assert_eq!(turns[0].type_id, "test.System");
+
+    // A turn the server rejects leaves no context behind.
+    let all = ListContextsOptions {
+        limit: 100_000,
+        ..Default::default()
+    };
+    let before = client.list_contexts(&ctx, all).expect("list failed").len();
+    let mut orphan = system.clone();
+    orphan.parent_turn_id = TurnId::new(u64::MAX);
+    let err = client
+        .create_context_with_turn(&ctx, CreateContextOptions::default(), &orphan)
+        .unwrap_err();
+    assert!(matches!(err, Error::TurnNotFound { .. }), "{err:?}");
+    let after = client.list_contexts(&ctx, all).expect("list failed").len();
+    assert_eq!(after, before);
+}
+
+#[test]
+fn integration_parent_contexts() {
+    if std::env::var("CXDB_INTEGRATION").is_err() {
+        eprintln!("CXDB_INTEGRATION not set; skipping integration test");
+        return;
+    }
+
+    let addr = std::env::var("CXDB_TEST_ADDR").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
+    let client = dial(&addr, Vec::new()).expect("dial failed");
+    let ctx = RequestContext::background();
+
+    let root = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create root failed")
+        .context_id;
+    let child = client
+        .create_context(&ctx, CreateContextOptions::default().parent(root))
+        .expect("create child failed")
+        .context_id;
+    assert_eq!(
+        client.get_context(&ctx, root).expect("get root").parent,
+        None
+    );
+    assert_eq!(
+        client.get_context(&ctx, child).expect("get child").parent,
+        Some(root)
+    );
+
+    // A first turn naming the parent in its provenance counts too.
+    let spawned = client
+        .create_context(&ctx, CreateContextOptions::default())
+        .expect("create failed")
+        .context_id;
+    let provenance = std::collections::BTreeMap::from([(
+        30u64,
+        std::collections::BTreeMap::from([(
+            10u64,
+            std::collections::BTreeMap::from([(1u64, root)]),
+        )]),
+    )]);
+    let payload = encode_msgpack(&provenance).expect("encode failed");
+    client
+        .append_turn(&ctx, &AppendRequest::new(spawned, "test.Spawn", 1, payload))
+        .expect("append failed");
+
+    let children: Vec<ContextId> = client
+        .list_children(&ctx, root)
+        .expect("list children failed")
+        .iter()
+        .map(|c| c.context_id)
+        .collect();
+    assert_eq!(children, [child, spawned]);
+
+    let err = client
+        .create_context(
+            &ctx,
+            CreateContextOptions::default().parent(ContextId::new(u64::MAX)),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::ContextNotFound { context_id } if context_id.get() == u64::MAX),
+        "{err:?}"
+    );
 }
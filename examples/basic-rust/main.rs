@@ -40,7 +40,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Step 2: Create a context
     println!("\nCreating new context...");
-    let context = client.create_context(&ctx, 0)?;
+    let context = client.create_context(&ctx, cxdb::CreateContextOptions::default())?;
     println!(
         "Created context ID: {} (head_turn_id={}, depth={})",
         context.context_id, context.head_turn_id, context.head_depth
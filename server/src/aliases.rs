@@ -0,0 +1,211 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Context aliases: stable string names mapped to context ids.
+//!
+//! # Storage Format
+//!
+//! The alias index (`turns/aliases.idx`) is an append-only file of
+//! variable-size records:
+//! - alias_len: u32
+//! - alias: [alias_len]u8 (UTF-8)
+//! - context_id: u64 (0 = alias released)
+//! - crc32: u32 over the preceding fields
+//!
+//! Last write wins per alias. The file is a [`RecordLog`], so a torn or
+//! corrupt tail is truncated on load.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Result, StoreError};
+use crate::record_log::{Record, RecordLog};
+
+/// Longest alias accepted, in bytes.
+pub const MAX_ALIAS_LEN: usize = 1024;
+
+/// One binding in the log; `context_id` 0 releases the alias.
+struct AliasRecord {
+    alias: String,
+    context_id: u64,
+}
+
+impl Record for AliasRecord {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.write_u32::<LittleEndian>(self.alias.len() as u32)?;
+        buf.extend_from_slice(self.alias.as_bytes());
+        buf.write_u64::<LittleEndian>(self.context_id)?;
+        Ok(())
+    }
+
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self> {
+        let alias_len = reader.read_u32::<LittleEndian>()? as usize;
+        if alias_len > MAX_ALIAS_LEN {
+            return Err(StoreError::Corrupt("alias record too long".into()));
+        }
+        let mut alias = vec![0u8; alias_len];
+        reader.read_exact(&mut alias)?;
+        let context_id = reader.read_u64::<LittleEndian>()?;
+        let alias =
+            String::from_utf8(alias).map_err(|_| StoreError::Corrupt("alias not utf8".into()))?;
+        Ok(Self { alias, context_id })
+    }
+}
+
+pub struct AliasIndex {
+    log: RecordLog<AliasRecord>,
+    aliases: HashMap<String, u64>,
+}
+
+impl AliasIndex {
+    /// Open or create the alias index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<AliasRecord>::open(dir, "aliases.idx")?;
+        let mut aliases = HashMap::new();
+        for record in records {
+            if record.context_id == 0 {
+                aliases.remove(&record.alias);
+            } else {
+                aliases.insert(record.alias, record.context_id);
+            }
+        }
+        Ok(Self { log, aliases })
+    }
+
+    fn write_record(&mut self, alias: &str, context_id: u64) -> Result<()> {
+        self.log.append(&AliasRecord {
+            alias: alias.to_string(),
+            context_id,
+        })
+    }
+
+    /// Bind `alias` to `context_id`. Fails if the alias is already bound.
+    pub fn insert(&mut self, alias: &str, context_id: u64) -> Result<()> {
+        validate_alias(alias)?;
+        if self.aliases.contains_key(alias) {
+            return Err(StoreError::InvalidInput(format!(
+                "alias {alias:?} already in use"
+            )));
+        }
+        self.write_record(alias, context_id)?;
+        self.aliases.insert(alias.to_string(), context_id);
+        Ok(())
+    }
+
+    /// Free every alias bound to `context_id`, e.g. when the context is
+    /// deleted. Returns the aliases released.
+    pub fn release_context(&mut self, context_id: u64) -> Result<Vec<String>> {
+        let released: Vec<String> = self
+            .aliases
+            .iter()
+            .filter(|(_, id)| **id == context_id)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        for alias in &released {
+            self.write_record(alias, 0)?;
+            self.aliases.remove(alias);
+        }
+        Ok(released)
+    }
+
+    /// Free aliases whose context `exists` no longer reports, so a reused
+    /// context id can never inherit a stale alias.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .aliases
+            .values()
+            .copied()
+            .filter(|id| !exists(*id))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        for context_id in missing {
+            self.release_context(context_id)?;
+        }
+        Ok(())
+    }
+
+    /// Context currently bound to `alias`.
+    pub fn get(&self, alias: &str) -> Option<u64> {
+        self.aliases.get(alias).copied()
+    }
+}
+
+/// Aliases are non-empty UTF-8 of at most [`MAX_ALIAS_LEN`] bytes.
+pub fn validate_alias(alias: &str) -> Result<()> {
+    if alias.is_empty() {
+        return Err(StoreError::InvalidInput("alias is empty".into()));
+    }
+    if alias.len() > MAX_ALIAS_LEN {
+        return Err(StoreError::InvalidInput(format!(
+            "alias longer than {MAX_ALIAS_LEN} bytes"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    #[test]
+    fn aliases_persist_and_release() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = AliasIndex::open(tmpdir.path()).unwrap();
+
+        index.insert("session:abc", 7).unwrap();
+        index.insert("session:def", 7).unwrap();
+        index.insert("session:xyz", 9).unwrap();
+        assert!(matches!(
+            index.insert("session:abc", 8),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            index.insert("", 8),
+            Err(StoreError::InvalidInput(_))
+        ));
+
+        let mut released = index.release_context(7).unwrap();
+        released.sort();
+        assert_eq!(released, ["session:abc", "session:def"]);
+        assert_eq!(index.get("session:abc"), None);
+        index.insert("session:abc", 10).unwrap();
+
+        drop(index);
+        let mut index = AliasIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get("session:abc"), Some(10));
+        assert_eq!(index.get("session:def"), None);
+        assert_eq!(index.get("session:xyz"), Some(9));
+
+        index.release_missing(|id| id == 10).unwrap();
+        assert_eq!(index.get("session:abc"), Some(10));
+        assert_eq!(index.get("session:xyz"), None);
+    }
+
+    #[test]
+    fn torn_tail_is_truncated() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = AliasIndex::open(tmpdir.path()).unwrap();
+        index.insert("a", 1).unwrap();
+        index.insert("b", 2).unwrap();
+        drop(index);
+
+        let path = tmpdir.path().join("aliases.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        let mut index = AliasIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get("a"), Some(1));
+        assert_eq!(index.get("b"), None);
+        // New records land after the last intact one.
+        index.insert("b", 3).unwrap();
+        drop(index);
+        assert_eq!(AliasIndex::open(tmpdir.path()).unwrap().get("b"), Some(3));
+    }
+}
@@ -14,6 +14,13 @@ use crate::error::{Result, StoreError};
 const BLOB_MAGIC: u32 = 0x42534C42; // 'B''S''L''B'
 const BLOB_VERSION: u16 = 1;
 
+/// Index entry flag (in the reserved field): the blob was removed.
+const IDX_FLAG_REMOVED: u16 = 1 << 0;
+
+/// Bytes before a blob's stored bytes in the pack: magic(4) + version(2) +
+/// codec(2) + raw_len(4) + stored_len(4) + hash(32).
+const PACK_HEADER_LEN: u64 = 4 + 2 + 2 + 4 + 4 + 32;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlobCodec {
     None = 0,
@@ -73,7 +80,7 @@ impl BlobStore {
         let mut buf = Vec::new();
         self.idx_file.read_to_end(&mut buf)?;
 
-        // Each index entry is 52 bytes: hash(32) + offset(8) + raw_len(4) + stored_len(4) + codec(2) + reserved(2)
+        // Each index entry is 52 bytes: hash(32) + offset(8) + raw_len(4) + stored_len(4) + codec(2) + flags(2)
         const ENTRY_SIZE: usize = 32 + 8 + 4 + 4 + 2 + 2;
 
         let mut cursor = std::io::Cursor::new(&buf);
@@ -111,10 +118,15 @@ impl BlobStore {
                 Ok(v) => v,
                 Err(_) => break,
             };
-            let _reserved = match cursor.read_u16::<LittleEndian>() {
+            let flags = match cursor.read_u16::<LittleEndian>() {
                 Ok(v) => v,
                 Err(_) => break,
             };
+            if flags & IDX_FLAG_REMOVED != 0 {
+                self.index.remove(&hash);
+                valid_len = cursor.position();
+                continue;
+            }
 
             let codec = match codec_raw {
                 0 => BlobCodec::None,
@@ -272,6 +284,34 @@ impl BlobStore {
         Ok(raw_bytes)
     }
 
+    /// Remove a blob: its stored bytes are overwritten with zeros in the
+    /// pack and a removal entry is appended to the index. Returns whether
+    /// the blob was present. A later `put_if_absent` of the same hash
+    /// stores it afresh.
+    pub fn remove(&mut self, hash: &[u8; 32]) -> Result<bool> {
+        let Some(entry) = self.index.remove(hash) else {
+            return Ok(false);
+        };
+
+        self.pack_file
+            .seek(SeekFrom::Start(entry.offset + PACK_HEADER_LEN))?;
+        self.pack_file
+            .write_all(&vec![0u8; entry.stored_len as usize])?;
+        self.pack_file.flush()?;
+
+        let mut idx_entry = Vec::with_capacity(32 + 8 + 4 + 4 + 2 + 2);
+        idx_entry.extend_from_slice(hash);
+        idx_entry.write_u64::<LittleEndian>(entry.offset)?;
+        idx_entry.write_u32::<LittleEndian>(entry.raw_len)?;
+        idx_entry.write_u32::<LittleEndian>(entry.stored_len)?;
+        idx_entry.write_u16::<LittleEndian>(entry.codec as u16)?;
+        idx_entry.write_u16::<LittleEndian>(IDX_FLAG_REMOVED)?;
+        self.idx_file.seek(SeekFrom::End(0))?;
+        self.idx_file.write_all(&idx_entry)?;
+        self.idx_file.flush()?;
+        Ok(true)
+    }
+
     pub fn stats(&self) -> BlobStoreStats {
         BlobStoreStats {
             blobs_total: self.index.len(),
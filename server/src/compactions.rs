@@ -0,0 +1,122 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Context compaction: summary turns that stand in for older history.
+//!
+//! Compacting a context appends a summary turn and records the newest turn
+//! it covers (`up_to_turn_id`). Default reads walk back from the head and
+//! stop at that boundary, so they end at the summary; the compacted turns
+//! stay in the turn store and remain readable by id.
+//!
+//! # Storage Format
+//!
+//! The compaction index (`turns/compactions.idx`) is an append-only file of
+//! fixed-size records:
+//! - summary_turn_id: u64
+//! - up_to_turn_id: u64 (0 = record dropped)
+//! - crc32: u32 over the preceding fields
+//!
+//! Records are keyed by summary turn rather than context, so forks that
+//! share the summary see the same boundary. The file is a [`RecordLog`],
+//! so a torn or corrupt tail is truncated on load.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{Result, StoreError};
+use crate::record_log::RecordLog;
+
+pub struct CompactionIndex {
+    log: RecordLog<(u64, u64)>,
+    /// summary_turn_id -> up_to_turn_id
+    boundaries: HashMap<u64, u64>,
+}
+
+impl CompactionIndex {
+    /// Open or create the compaction index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<(u64, u64)>::open(dir, "compactions.idx")?;
+        let mut boundaries = HashMap::new();
+        for (summary_turn_id, up_to_turn_id) in records {
+            if up_to_turn_id == 0 {
+                boundaries.remove(&summary_turn_id);
+            } else {
+                boundaries.insert(summary_turn_id, up_to_turn_id);
+            }
+        }
+        Ok(Self { log, boundaries })
+    }
+
+    /// Record that `summary_turn_id` covers history up to and including
+    /// `up_to_turn_id`.
+    pub fn insert(&mut self, summary_turn_id: u64, up_to_turn_id: u64) -> Result<()> {
+        if up_to_turn_id == 0 {
+            return Err(StoreError::InvalidInput("up_to_turn_id is required".into()));
+        }
+        self.log.append(&(summary_turn_id, up_to_turn_id))?;
+        self.boundaries.insert(summary_turn_id, up_to_turn_id);
+        Ok(())
+    }
+
+    /// Drop records whose summary turn `exists` no longer reports, so a
+    /// reused turn id can never inherit a stale boundary.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .boundaries
+            .keys()
+            .copied()
+            .filter(|id| !exists(*id))
+            .collect();
+        missing.sort_unstable();
+        for summary_turn_id in missing {
+            self.log.append(&(summary_turn_id, 0))?;
+            self.boundaries.remove(&summary_turn_id);
+        }
+        Ok(())
+    }
+
+    /// Newest turn compacted by `summary_turn_id`, if it is a summary turn.
+    pub fn boundary(&self, summary_turn_id: u64) -> Option<u64> {
+        self.boundaries.get(&summary_turn_id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    #[test]
+    fn compactions_persist_and_truncate_torn_tail() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = CompactionIndex::open(tmpdir.path()).unwrap();
+        index.insert(10, 7).unwrap();
+        index.insert(20, 15).unwrap();
+        index.insert(30, 25).unwrap();
+        assert!(matches!(
+            index.insert(40, 0),
+            Err(StoreError::InvalidInput(_))
+        ));
+        index.release_missing(|id| id != 20).unwrap();
+        drop(index);
+
+        let path = tmpdir.path().join("compactions.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        // The torn tombstone for 20 is lost, so its record is live again.
+        let mut index = CompactionIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.boundary(10), Some(7));
+        assert_eq!(index.boundary(20), Some(15));
+        assert_eq!(index.boundary(30), Some(25));
+        assert_eq!(index.boundary(7), None);
+
+        index.release_missing(|id| id != 30).unwrap();
+        drop(index);
+        let index = CompactionIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.boundary(10), Some(7));
+        assert_eq!(index.boundary(30), None);
+    }
+}
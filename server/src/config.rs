@@ -2,13 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::env;
+use std::fmt;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Config {
     pub data_dir: PathBuf,
     pub bind_addr: String,
     pub http_bind_addr: String,
+    /// Bearer token binary-protocol clients must present on HELLO
+    /// (`CXDB_AUTH_TOKEN`). Unset or empty disables authentication.
+    pub auth_token: Option<String>,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("data_dir", &self.data_dir)
+            .field("bind_addr", &self.bind_addr)
+            .field("http_bind_addr", &self.http_bind_addr)
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
 }
 
 impl Config {
@@ -17,10 +35,14 @@ impl Config {
         let bind_addr = env::var("CXDB_BIND").unwrap_or_else(|_| "127.0.0.1:9009".to_string());
         let http_bind_addr =
             env::var("CXDB_HTTP_BIND").unwrap_or_else(|_| "127.0.0.1:9010".to_string());
+        let auth_token = env::var("CXDB_AUTH_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
         Self {
             data_dir: PathBuf::from(data_dir),
             bind_addr,
             http_bind_addr,
+            auth_token,
         }
     }
 }
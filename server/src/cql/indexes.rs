@@ -249,6 +249,51 @@ impl SecondaryIndexes {
             .insert(context_id);
     }
 
+    /// Drop a deleted context from every index.
+    pub fn remove_context(&mut self, context_id: u64) {
+        if !self.all_context_ids.remove(&context_id) {
+            return;
+        }
+        for exact in [
+            &mut self.tag_exact,
+            &mut self.tag_lower_exact,
+            &mut self.title_exact,
+            &mut self.title_lower_exact,
+            &mut self.label_exact,
+            &mut self.user_exact,
+            &mut self.user_lower_exact,
+            &mut self.service_exact,
+            &mut self.service_lower_exact,
+            &mut self.host_exact,
+            &mut self.trace_id_exact,
+        ] {
+            remove_from_sets(exact, context_id);
+        }
+        for sorted in [
+            &mut self.tag_sorted,
+            &mut self.tag_lower_sorted,
+            &mut self.title_sorted,
+            &mut self.title_lower_sorted,
+            &mut self.user_sorted,
+            &mut self.user_lower_sorted,
+            &mut self.service_sorted,
+            &mut self.service_lower_sorted,
+            &mut self.host_sorted,
+        ] {
+            sorted.retain(|(_, id)| *id != context_id);
+        }
+        remove_from_sets(&mut self.parent_exact, context_id);
+        remove_from_sets(&mut self.root_exact, context_id);
+        self.created_btree.retain(|_, ids| {
+            ids.remove(&context_id);
+            !ids.is_empty()
+        });
+        self.depth_btree.retain(|_, ids| {
+            ids.remove(&context_id);
+            !ids.is_empty()
+        });
+    }
+
     /// Get all context IDs (for NOT operations).
     pub fn all_contexts(&self) -> &HashSet<u64> {
         &self.all_context_ids
@@ -479,3 +524,13 @@ pub struct IndexStats {
     pub host_entries: usize,
     pub created_entries: usize,
 }
+
+fn remove_from_sets<K: Eq + std::hash::Hash>(
+    index: &mut HashMap<K, HashSet<u64>>,
+    context_id: u64,
+) {
+    index.retain(|_, ids| {
+        ids.remove(&context_id);
+        !ids.is_empty()
+    });
+}
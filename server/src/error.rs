@@ -11,8 +11,33 @@ pub enum StoreError {
     Corrupt(String),
     #[error("not found: {0}")]
     NotFound(String),
+    #[error("context {context_id} not found")]
+    ContextNotFound { context_id: u64 },
+    /// `what` names the turn's role in the request ("parent turn", ...).
+    #[error("{what} {turn_id} not found")]
+    TurnNotFound { what: &'static str, turn_id: u64 },
     #[error("invalid input: {0}")]
     InvalidInput(String),
+    #[error("unauthenticated: {0}")]
+    Unauthenticated(String),
+    #[error("turn {turn_id} was pruned")]
+    Pruned { turn_id: u64 },
+    #[error("context {context_id} is archived")]
+    ContextArchived { context_id: u64 },
+    #[error("link target turn {turn_id} is not in the history of context {context_id}")]
+    LinkTargetNotFound { turn_id: u64, context_id: u64 },
+    #[error("writer {writer_id:?} sequence {writer_seq} is not after {last_seq}")]
+    WriterSequenceConflict {
+        writer_id: String,
+        last_seq: u64,
+        writer_seq: u64,
+    },
+    /// Entry `entry` (0-based) of a batch failed, so none of it was applied.
+    #[error("transaction entry {entry}: {source}")]
+    TransactionAborted {
+        entry: usize,
+        source: Box<StoreError>,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, StoreError>;
@@ -0,0 +1,130 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-turn time to live.
+//!
+//! An append can carry a TTL; the turn then expires at
+//! `appended_at + ttl`. Expired turns stay in the turn store, keep their
+//! depth and remain the parents of their children, so turn ids stay
+//! monotonic and history reads see gaps rather than renumbered turns.
+//! Default reads skip them; `include_expired` returns them marked
+//! `expired`. Reads by id always return the turn.
+//!
+//! # Storage Format
+//!
+//! The expiry index (`turns/expiry.idx`) is an append-only file of
+//! fixed-size records:
+//! - turn_id: u64
+//! - expires_at_unix_ms: u64 (0 = record dropped)
+//! - crc32: u32 over the preceding fields
+//!
+//! The file is a [`RecordLog`], so a torn or corrupt tail is truncated on
+//! load.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{Result, StoreError};
+use crate::record_log::RecordLog;
+
+pub struct ExpiryIndex {
+    log: RecordLog<(u64, u64)>,
+    /// turn_id -> expires_at_unix_ms
+    expiries: HashMap<u64, u64>,
+}
+
+impl ExpiryIndex {
+    /// Open or create the expiry index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<(u64, u64)>::open(dir, "expiry.idx")?;
+        let mut expiries = HashMap::new();
+        for (turn_id, expires_at) in records {
+            if expires_at == 0 {
+                expiries.remove(&turn_id);
+            } else {
+                expiries.insert(turn_id, expires_at);
+            }
+        }
+        Ok(Self { log, expiries })
+    }
+
+    /// Record that `turn_id` expires at `expires_at_unix_ms`.
+    pub fn insert(&mut self, turn_id: u64, expires_at_unix_ms: u64) -> Result<()> {
+        if expires_at_unix_ms == 0 {
+            return Err(StoreError::InvalidInput("expires_at is required".into()));
+        }
+        self.log.append(&(turn_id, expires_at_unix_ms))?;
+        self.expiries.insert(turn_id, expires_at_unix_ms);
+        Ok(())
+    }
+
+    /// Drop records of turns `exists` no longer reports, so a reused turn
+    /// id can never inherit a stale expiry.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .expiries
+            .keys()
+            .copied()
+            .filter(|id| !exists(*id))
+            .collect();
+        missing.sort_unstable();
+        for turn_id in missing {
+            self.log.append(&(turn_id, 0))?;
+            self.expiries.remove(&turn_id);
+        }
+        Ok(())
+    }
+
+    /// When `turn_id` expires, if it was appended with a TTL.
+    pub fn expires_at(&self, turn_id: u64) -> Option<u64> {
+        self.expiries.get(&turn_id).copied()
+    }
+
+    /// Whether `turn_id` has expired as of `now_ms`.
+    pub fn is_expired(&self, turn_id: u64, now_ms: u64) -> bool {
+        self.expires_at(turn_id)
+            .is_some_and(|expires_at| expires_at <= now_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    #[test]
+    fn expiries_persist_and_truncate_torn_tail() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = ExpiryIndex::open(tmpdir.path()).unwrap();
+        index.insert(1, 1_000).unwrap();
+        index.insert(2, 2_000).unwrap();
+        index.insert(3, 3_000).unwrap();
+        assert!(matches!(
+            index.insert(4, 0),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(index.is_expired(1, 1_000));
+        assert!(!index.is_expired(2, 1_000));
+        assert!(!index.is_expired(4, u64::MAX));
+        index.release_missing(|id| id != 2).unwrap();
+        drop(index);
+
+        let path = tmpdir.path().join("expiry.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        // The torn tombstone for 2 is lost, so its record is live again.
+        let mut index = ExpiryIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.expires_at(1), Some(1_000));
+        assert_eq!(index.expires_at(2), Some(2_000));
+        assert_eq!(index.expires_at(3), Some(3_000));
+
+        index.release_missing(|id| id != 3).unwrap();
+        drop(index);
+        let index = ExpiryIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.expires_at(1), Some(1_000));
+        assert_eq!(index.expires_at(3), None);
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Full-text search over turn payloads (TEXT_SEARCH).
+//!
+//! The index maps each word found in a turn's msgpack string values to the
+//! turns holding it. A word is a run of alphanumeric characters, lowercased;
+//! words longer than [`MAX_WORD_LEN`] characters are not indexed. A query
+//! matches the turns holding every one of its words.
+//!
+//! The index lives in memory only. [`Store`](crate::store::Store) builds it
+//! from the stored payloads on the first search and keeps it up to date
+//! from then on.
+
+use std::collections::{BTreeSet, HashMap};
+
+use rmpv::Value;
+
+use crate::search::ENCODING_MSGPACK;
+
+/// Longest word indexed, in characters.
+pub const MAX_WORD_LEN: usize = 64;
+
+/// Deepest payload nesting read for text.
+const MAX_TEXT_DEPTH: usize = 128;
+
+/// Characters of context kept on each side of the match in a snippet.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// A TEXT_SEARCH query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSearch {
+    pub text: String,
+    /// Search only this context's history; `None` searches every context.
+    pub context_id: Option<u64>,
+    /// Only turns declared with this type; `None` searches every type.
+    pub type_id: Option<String>,
+    /// Return only turns older than this one (0 = from the newest).
+    pub before_turn_id: u64,
+    pub limit: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct TextIndex {
+    postings: HashMap<String, BTreeSet<u64>>,
+    /// Per indexed turn: the context it was first appended to and its words.
+    turns: HashMap<u64, (u64, Vec<String>)>,
+}
+
+impl TextIndex {
+    /// Index the payload of `turn_id`, appended to `context_id`. Payloads
+    /// that are not msgpack hold no words.
+    pub fn insert(&mut self, turn_id: u64, context_id: u64, encoding: u32, payload: &[u8]) {
+        if self.turns.contains_key(&turn_id) {
+            return;
+        }
+        let mut words: Vec<String> = Vec::new();
+        if encoding == ENCODING_MSGPACK {
+            for text in payload_strings(payload) {
+                words.extend(words_of(&text).map(|(_, word)| word));
+            }
+        }
+        words.sort_unstable();
+        words.dedup();
+        for word in &words {
+            self.postings
+                .entry(word.clone())
+                .or_default()
+                .insert(turn_id);
+        }
+        self.turns.insert(turn_id, (context_id, words));
+    }
+
+    /// Drop `turn_id` from the index.
+    pub fn remove(&mut self, turn_id: u64) {
+        let Some((_, words)) = self.turns.remove(&turn_id) else {
+            return;
+        };
+        for word in words {
+            if let Some(turns) = self.postings.get_mut(&word) {
+                turns.remove(&turn_id);
+                if turns.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// The context `turn_id` was first appended to, if it is indexed.
+    pub fn home_context(&self, turn_id: u64) -> Option<u64> {
+        self.turns.get(&turn_id).map(|(context_id, _)| *context_id)
+    }
+
+    /// Turns holding every word of `query`, newest first. Empty when the
+    /// query has no words.
+    pub fn matches(&self, query: &str) -> Vec<u64> {
+        let mut words: Vec<String> = words_of(query).map(|(_, word)| word).collect();
+        words.sort_unstable();
+        words.dedup();
+        let mut sets = Vec::with_capacity(words.len());
+        for word in &words {
+            match self.postings.get(word) {
+                Some(turns) => sets.push(turns),
+                None => return Vec::new(),
+            }
+        }
+        sets.sort_by_key(|turns| turns.len());
+        let Some((smallest, rest)) = sets.split_first() else {
+            return Vec::new();
+        };
+        smallest
+            .iter()
+            .rev()
+            .copied()
+            .filter(|turn_id| rest.iter().all(|turns| turns.contains(turn_id)))
+            .collect()
+    }
+}
+
+/// The words of `text`, lowercased, each with its character range.
+pub fn words_of(text: &str) -> impl Iterator<Item = ((usize, usize), String)> + '_ {
+    let mut chars = text.chars().enumerate().peekable();
+    std::iter::from_fn(move || loop {
+        let (start, first) = chars.next()?;
+        if !first.is_alphanumeric() {
+            continue;
+        }
+        let mut word: String = first.to_lowercase().collect();
+        let mut end = start + 1;
+        while let Some(&(index, c)) = chars.peek() {
+            if !c.is_alphanumeric() {
+                break;
+            }
+            word.extend(c.to_lowercase());
+            end = index + 1;
+            chars.next();
+        }
+        if end - start <= MAX_WORD_LEN {
+            return Some(((start, end), word));
+        }
+    })
+}
+
+/// Every string value in a msgpack `payload`, in document order. Map keys
+/// are not included.
+pub fn payload_strings(payload: &[u8]) -> Vec<String> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let Ok(value) = rmpv::decode::read_value_with_max_depth(&mut cursor, MAX_TEXT_DEPTH) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    collect_strings(&value, &mut out);
+    out
+}
+
+fn collect_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(text) => {
+            if let Some(text) = text.as_str() {
+                out.push(text.to_string());
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, out)),
+        Value::Map(entries) => entries
+            .iter()
+            .for_each(|(_, value)| collect_strings(value, out)),
+        _ => {}
+    }
+}
+
+/// An excerpt of the first string in `payload` holding a word of `query`,
+/// with up to [`SNIPPET_CONTEXT`] characters on each side and `…` where
+/// the string was cut. Empty if no string holds one.
+pub fn snippet(payload: &[u8], query: &str) -> String {
+    let words: Vec<String> = words_of(query).map(|(_, word)| word).collect();
+    for text in payload_strings(payload) {
+        let Some(((start, end), _)) = words_of(&text).find(|(_, word)| words.contains(word)) else {
+            continue;
+        };
+        let chars: Vec<char> = text.chars().collect();
+        let from = start.saturating_sub(SNIPPET_CONTEXT);
+        let to = (end + SNIPPET_CONTEXT).min(chars.len());
+        let mut out = String::new();
+        if from > 0 {
+            out.push('…');
+        }
+        out.extend(&chars[from..to]);
+        if to < chars.len() {
+            out.push('…');
+        }
+        return out;
+    }
+    String::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msgpack(value: &Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        rmpv::encode::write_value(&mut out, value).unwrap();
+        out
+    }
+
+    #[test]
+    fn matches_need_every_word_and_ignore_case() {
+        let sf = msgpack(&Value::Map(vec![(
+            Value::from("text"),
+            Value::from("Flights to San Francisco, please."),
+        )]));
+        let sd = msgpack(&Value::Array(vec![
+            Value::from("San Diego"),
+            Value::from(3),
+        ]));
+        let mut index = TextIndex::default();
+        index.insert(1, 10, ENCODING_MSGPACK, &sf);
+        index.insert(2, 10, ENCODING_MSGPACK, &sd);
+        index.insert(3, 11, 2, b"san francisco");
+
+        assert_eq!(index.matches("san"), [2, 1]);
+        assert_eq!(index.matches("SAN francisco!"), [1]);
+        assert!(index.matches("san jose").is_empty());
+        assert!(index.matches("  ").is_empty());
+        assert_eq!(index.home_context(2), Some(10));
+
+        index.remove(1);
+        assert_eq!(index.matches("san"), [2]);
+        assert!(index.matches("francisco").is_empty());
+    }
+
+    #[test]
+    fn snippets_cut_long_strings_around_the_match() {
+        let text = format!("{} San Francisco {}", "a".repeat(60), "b".repeat(60));
+        let payload = msgpack(&Value::Array(vec![
+            Value::from("nothing here"),
+            Value::from(text),
+        ]));
+        let snippet = snippet(&payload, "francisco");
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("San Francisco"));
+        assert_eq!(snippet.chars().count(), 2 + 40 + "francisco".len() + 40);
+
+        let short = msgpack(&Value::from("Visit San Francisco"));
+        assert_eq!(super::snippet(&short, "visit"), "Visit San Francisco");
+    }
+}
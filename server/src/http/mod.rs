@@ -3,6 +3,7 @@
 
 use std::collections::HashMap;
 use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -15,15 +16,24 @@ use url::Url;
 use crate::error::{Result, StoreError};
 use crate::events::EventBus;
 use crate::fs_store::EntryKind;
+use crate::links::LinkKind;
 use crate::metrics::{Metrics, SessionTracker};
 use crate::projection::{BytesRender, EnumRender, RenderOptions, TimeRender, U64Format};
+use crate::protocol::{read_frame, write_frame};
 use crate::registry::{PutOutcome, Registry, RegistryBundle, RendererSpec, TypeVersionSpec};
 use crate::store::Store;
+use crate::turn_store::CONTEXT_FLAG_ARCHIVED;
 
 type HttpResponse = (u16, Response<std::io::Cursor<Vec<u8>>>);
 
+/// How long `POST /v1/rpc` waits on the binary protocol listener.
+const RPC_RELAY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Serves the HTTP API on `bind_addr`. `rpc_addr` is the binary protocol
+/// listener that `POST /v1/rpc` relays frames to.
 pub fn start_http(
     bind_addr: String,
+    rpc_addr: String,
     store: Arc<Mutex<Store>>,
     registry: Arc<Mutex<Registry>>,
     metrics: Arc<Metrics>,
@@ -36,6 +46,7 @@ pub fn start_http(
         for request in server.incoming_requests() {
             if let Err(err) = handle_request(
                 request,
+                &rpc_addr,
                 &store,
                 &registry,
                 &metrics,
@@ -51,6 +62,7 @@ pub fn start_http(
 
 fn handle_request(
     mut request: tiny_http::Request,
+    rpc_addr: &str,
     store: &Arc<Mutex<Store>>,
     registry: &Arc<Mutex<Registry>>,
     metrics: &Arc<Metrics>,
@@ -244,6 +256,7 @@ fn handle_request(
                             "head_depth": c.head_depth,
                             "created_at_unix_ms": c.created_at_unix_ms,
                             "is_live": is_live,
+                            "archived": c.flags & CONTEXT_FLAG_ARCHIVED != 0,
                         });
 
                         if let Some(tag) = client_tag {
@@ -523,6 +536,11 @@ fn handle_request(
                     .map(|v| v == "1")
                     .unwrap_or(false);
 
+                let include_expired = params
+                    .get("include_expired")
+                    .map(|v| v == "1")
+                    .unwrap_or(false);
+
                 let as_type_id = params.get("as_type_id").cloned();
                 let as_type_version = params
                     .get("as_type_version")
@@ -540,9 +558,9 @@ fn handle_request(
                 let head = store.get_head(context_id)?;
                 let t0 = Instant::now();
                 let turns = if before_turn_id == 0 {
-                    store.get_last(context_id, limit, true)?
+                    store.get_last(context_id, limit, true, include_expired)?
                 } else {
-                    store.get_before(context_id, before_turn_id, limit, true)?
+                    store.get_before(context_id, before_turn_id, limit, true, include_expired)?
                 };
                 metrics.record_get_last(t0.elapsed());
 
@@ -588,8 +606,53 @@ fn handle_request(
                             "type_version": declared_type_version,
                         }),
                     );
+                    if let Some(writer) = &item.writer {
+                        turn_obj.insert(
+                            "writer".into(),
+                            json!({
+                                "writer_id": writer.writer_id,
+                                "writer_seq": writer.writer_seq.to_string(),
+                            }),
+                        );
+                    }
+                    if let Some(expires_at) = item.expires_at_unix_ms {
+                        turn_obj.insert("expires_at".into(), JsonValue::Number(expires_at.into()));
+                        turn_obj.insert("expired".into(), JsonValue::Bool(item.expired));
+                    }
+                    if let Some(redaction) = &item.redaction {
+                        turn_obj.insert(
+                            "redaction".into(),
+                            json!({
+                                "redacted_at": redaction.redacted_at_unix_ms,
+                                "reason": redaction.reason,
+                            }),
+                        );
+                    }
 
-                    if view == "typed" || view == "both" {
+                    if !item.links.is_empty() {
+                        let links: Vec<JsonValue> = item
+                            .links
+                            .iter()
+                            .map(|link| {
+                                let mut obj = json!({
+                                    "target_turn_id": link.target_turn_id.to_string(),
+                                    "kind": match link.kind {
+                                        LinkKind::RepliesTo => "replies_to",
+                                        LinkKind::Causes => "causes",
+                                        LinkKind::Custom(_) => "custom",
+                                    },
+                                });
+                                if let LinkKind::Custom(name) = &link.kind {
+                                    obj["name"] = JsonValue::String(name.clone());
+                                }
+                                obj
+                            })
+                            .collect();
+                        turn_obj.insert("links".into(), JsonValue::Array(links));
+                    }
+
+                    // A redacted turn has no payload to project.
+                    if (view == "typed" || view == "both") && item.redaction.is_none() {
                         let desc = registry
                             .get_type_version(&decoded_type_id, decoded_type_version)
                             .ok_or_else(|| StoreError::NotFound("type descriptor".into()))?;
@@ -597,8 +660,13 @@ fn handle_request(
                             .payload
                             .as_ref()
                             .ok_or_else(|| StoreError::InvalidInput("payload not loaded".into()))?;
-                        let projected =
-                            crate::projection::project_msgpack(payload, desc, &registry, &options)?;
+                        let projected = crate::projection::project_payload(
+                            payload,
+                            item.meta.encoding,
+                            desc,
+                            &registry,
+                            &options,
+                        )?;
                         turn_obj.insert(
                             "decoded_as".into(),
                             json!({
@@ -892,6 +960,45 @@ fn handle_request(
                     Err(e) => Err(e),
                 }
             }
+            // Binary protocol frames tunnelled over HTTP, for clients that
+            // can only reach the server through the gateway.
+            (Method::Post, ["v1", "rpc"]) => {
+                let mut body = Vec::new();
+                request.as_reader().read_to_end(&mut body)?;
+                let frames = count_frames(&body)?;
+                match relay_frames(rpc_addr, &body, frames) {
+                    Ok(responses) => Ok((
+                        200,
+                        Response::from_data(responses)
+                            .with_status_code(StatusCode(200))
+                            .with_header(
+                                Header::from_bytes(
+                                    &b"Content-Type"[..],
+                                    &b"application/octet-stream"[..],
+                                )
+                                .unwrap(),
+                            ),
+                    )),
+                    Err(err) => {
+                        let bytes = serde_json::to_vec(
+                            &json!({"error": {"code": 502, "message": err.to_string()}}),
+                        )
+                        .map_err(|e| StoreError::InvalidInput(format!("json encode error: {e}")))?;
+                        Ok((
+                            502,
+                            Response::from_data(bytes)
+                                .with_status_code(StatusCode(502))
+                                .with_header(
+                                    Header::from_bytes(
+                                        &b"Content-Type"[..],
+                                        &b"application/json"[..],
+                                    )
+                                    .unwrap(),
+                                ),
+                        ))
+                    }
+                }
+            }
             _ => Err(StoreError::NotFound("route".into())),
         }
     })();
@@ -1023,6 +1130,65 @@ fn parse_query(query: &str) -> HashMap<String, String> {
         .collect()
 }
 
+/// Counts the binary protocol frames in a `/v1/rpc` body, rejecting a body
+/// that does not end on a frame boundary.
+fn count_frames(body: &[u8]) -> Result<usize> {
+    let mut offset = 0;
+    let mut frames = 0;
+    while offset < body.len() {
+        let header = body
+            .get(offset..offset + 16)
+            .ok_or_else(|| StoreError::InvalidInput("truncated frame header".into()))?;
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        offset += 16 + len;
+        if offset > body.len() {
+            return Err(StoreError::InvalidInput("truncated frame payload".into()));
+        }
+        frames += 1;
+    }
+    if frames == 0 {
+        return Err(StoreError::InvalidInput("no frames".into()));
+    }
+    Ok(frames)
+}
+
+/// Sends `body` over a fresh connection to the binary protocol listener at
+/// `rpc_addr` and returns the first `frames` response frames.
+fn relay_frames(rpc_addr: &str, body: &[u8], frames: usize) -> Result<Vec<u8>> {
+    let addr = rpc_addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| StoreError::InvalidInput(format!("cannot resolve {rpc_addr}")))?;
+    // A listener bound to every interface is reached over loopback.
+    let addr = match addr.ip() {
+        std::net::IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(std::net::Ipv4Addr::LOCALHOST.into(), addr.port())
+        }
+        std::net::IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(std::net::Ipv6Addr::LOCALHOST.into(), addr.port())
+        }
+        _ => addr,
+    };
+    let mut stream = TcpStream::connect_timeout(&addr, RPC_RELAY_TIMEOUT)?;
+    stream.set_read_timeout(Some(RPC_RELAY_TIMEOUT))?;
+    stream.set_write_timeout(Some(RPC_RELAY_TIMEOUT))?;
+    stream.write_all(body)?;
+
+    let mut responses = Vec::new();
+    for _ in 0..frames {
+        let (header, payload) = read_frame(&mut stream)?;
+        write_frame(
+            &mut responses,
+            header.msg_type,
+            header.flags,
+            header.req_id,
+            &payload,
+        )?;
+    }
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    Ok(responses)
+}
+
 fn map_error(err: &StoreError) -> (u16, String) {
     match err {
         StoreError::NotFound(msg) => {
@@ -1035,6 +1201,14 @@ fn map_error(err: &StoreError) -> (u16, String) {
         StoreError::InvalidInput(msg) => (422, msg.clone()),
         StoreError::Corrupt(msg) => (500, msg.clone()),
         StoreError::Io(msg) => (500, msg.to_string()),
+        StoreError::WriterSequenceConflict { .. } => (409, err.to_string()),
+        StoreError::Unauthenticated(msg) => (401, msg.clone()),
+        StoreError::Pruned { .. } => (410, err.to_string()),
+        StoreError::ContextArchived { .. } => (423, err.to_string()),
+        StoreError::ContextNotFound { .. }
+        | StoreError::TurnNotFound { .. }
+        | StoreError::LinkTargetNotFound { .. } => (404, err.to_string()),
+        StoreError::TransactionAborted { source, .. } => (map_error(source).0, err.to_string()),
     }
 }
 
@@ -3,17 +3,28 @@
 
 //! Library crate for the AI Context Store service.
 
+pub mod aliases;
 pub mod blob_store;
+pub mod compactions;
 pub mod config;
 pub mod cql;
 pub mod error;
 pub mod events;
+pub mod expiry;
 pub mod fs_store;
+pub mod fulltext;
 pub mod http;
+pub mod links;
 pub mod metrics;
+pub mod parents;
 pub mod projection;
 pub mod protocol;
+pub mod prunes;
+pub mod record_log;
+pub mod redactions;
 pub mod registry;
 pub mod s3_sync;
+pub mod search;
 pub mod store;
 pub mod turn_store;
+pub mod writers;
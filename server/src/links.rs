@@ -0,0 +1,336 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structured links from a turn to earlier turns in its history.
+//!
+//! A turn can be appended with links (a reply to an earlier turn, the turn
+//! that caused it, or an application-defined kind). Links point back along
+//! the turn's own history, so they are checked once at append time and
+//! never dangle afterwards; this index also keeps the reverse direction so
+//! the turns linking to a turn can be found without a scan.
+//!
+//! # Storage Format
+//!
+//! The link index (`turns/links.idx`) is an append-only file of
+//! variable-size records:
+//! - turn_id: u64
+//! - link_count: u32 (0 = links dropped)
+//! - per link:
+//!   - target_turn_id: u64
+//!   - kind: u8 (1 = replies_to, 2 = causes, 3 = custom)
+//!   - name_len: u32 (0 unless custom)
+//!   - name: [name_len]u8 (UTF-8)
+//! - crc32: u32 over the preceding fields
+//!
+//! The file is a [`RecordLog`], so a torn or corrupt tail is truncated on
+//! load.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Result, StoreError};
+use crate::record_log::{Record, RecordLog};
+
+/// Most links one turn can carry.
+pub const MAX_LINKS: usize = 64;
+
+/// Longest custom link kind accepted, in bytes.
+pub const MAX_LINK_KIND_LEN: usize = 256;
+
+const KIND_REPLIES_TO: u8 = 1;
+const KIND_CAUSES: u8 = 2;
+const KIND_CUSTOM: u8 = 3;
+
+/// How a turn relates to the turn it links to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// The turn answers the target.
+    RepliesTo,
+    /// The target caused the turn (a tool result for its tool call).
+    Causes,
+    /// An application-defined relation.
+    Custom(String),
+}
+
+impl LinkKind {
+    /// The kind's tag on the wire and on disk.
+    pub fn tag(&self) -> u8 {
+        match self {
+            LinkKind::RepliesTo => KIND_REPLIES_TO,
+            LinkKind::Causes => KIND_CAUSES,
+            LinkKind::Custom(_) => KIND_CUSTOM,
+        }
+    }
+
+    /// The custom kind's name; empty for the built-in kinds.
+    pub fn name(&self) -> &str {
+        match self {
+            LinkKind::Custom(name) => name,
+            _ => "",
+        }
+    }
+
+    /// The kind for `tag` and `name`, as read back from [`tag`](Self::tag)
+    /// and [`name`](Self::name).
+    pub fn from_parts(tag: u8, name: String) -> Result<Self> {
+        let kind = match tag {
+            KIND_REPLIES_TO if name.is_empty() => LinkKind::RepliesTo,
+            KIND_CAUSES if name.is_empty() => LinkKind::Causes,
+            KIND_CUSTOM => LinkKind::Custom(name),
+            _ => return Err(StoreError::InvalidInput(format!("unknown link kind {tag}"))),
+        };
+        validate_kind(&kind)?;
+        Ok(kind)
+    }
+}
+
+/// A link from one turn to an earlier turn in its history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurnLink {
+    pub target_turn_id: u64,
+    pub kind: LinkKind,
+}
+
+/// Which way [`Store::get_linked`] follows links from a turn.
+///
+/// [`Store::get_linked`]: crate::store::Store::get_linked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkDirection {
+    /// The turns it links to.
+    Outgoing,
+    /// The turns linking to it.
+    Incoming,
+}
+
+/// The links of one turn in the log; no links drops the turn's entry.
+struct LinkRecord {
+    turn_id: u64,
+    links: Vec<TurnLink>,
+}
+
+impl Record for LinkRecord {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.write_u64::<LittleEndian>(self.turn_id)?;
+        buf.write_u32::<LittleEndian>(self.links.len() as u32)?;
+        for link in &self.links {
+            buf.write_u64::<LittleEndian>(link.target_turn_id)?;
+            buf.write_u8(link.kind.tag())?;
+            buf.write_u32::<LittleEndian>(link.kind.name().len() as u32)?;
+            buf.extend_from_slice(link.kind.name().as_bytes());
+        }
+        Ok(())
+    }
+
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self> {
+        let turn_id = reader.read_u64::<LittleEndian>()?;
+        let link_count = reader.read_u32::<LittleEndian>()? as usize;
+        if link_count > MAX_LINKS {
+            return Err(StoreError::Corrupt("link record too long".into()));
+        }
+        let mut links = Vec::with_capacity(link_count);
+        for _ in 0..link_count {
+            let target_turn_id = reader.read_u64::<LittleEndian>()?;
+            let tag = reader.read_u8()?;
+            let name_len = reader.read_u32::<LittleEndian>()? as usize;
+            if name_len > MAX_LINK_KIND_LEN {
+                return Err(StoreError::Corrupt("link kind too long".into()));
+            }
+            let mut name = vec![0u8; name_len];
+            reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| StoreError::Corrupt("link kind not utf8".into()))?;
+            let kind = LinkKind::from_parts(tag, name)
+                .map_err(|_| StoreError::Corrupt("invalid link kind".into()))?;
+            links.push(TurnLink {
+                target_turn_id,
+                kind,
+            });
+        }
+        Ok(Self { turn_id, links })
+    }
+}
+
+pub struct LinkIndex {
+    log: RecordLog<LinkRecord>,
+    /// source turn_id -> its links
+    links: HashMap<u64, Vec<TurnLink>>,
+    /// target turn_id -> turns linking to it
+    incoming: HashMap<u64, BTreeSet<u64>>,
+}
+
+impl LinkIndex {
+    /// Open or create the link index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<LinkRecord>::open(dir, "links.idx")?;
+        let mut links = HashMap::new();
+        for record in records {
+            if record.links.is_empty() {
+                links.remove(&record.turn_id);
+            } else {
+                links.insert(record.turn_id, record.links);
+            }
+        }
+
+        let mut index = Self {
+            log,
+            links,
+            incoming: HashMap::new(),
+        };
+        index.rebuild_incoming();
+        Ok(index)
+    }
+
+    fn rebuild_incoming(&mut self) {
+        self.incoming.clear();
+        for (&source, links) in &self.links {
+            for link in links {
+                self.incoming
+                    .entry(link.target_turn_id)
+                    .or_default()
+                    .insert(source);
+            }
+        }
+    }
+
+    /// Record the links of `turn_id`. The caller checks that the targets
+    /// are in its history (see [`Store::check_links`]).
+    ///
+    /// [`Store::check_links`]: crate::store::Store::check_links
+    pub fn insert(&mut self, turn_id: u64, links: Vec<TurnLink>) -> Result<()> {
+        validate_links(&links)?;
+        if links.is_empty() {
+            return Ok(());
+        }
+        let record = LinkRecord { turn_id, links };
+        self.log.append(&record)?;
+        for link in &record.links {
+            self.incoming
+                .entry(link.target_turn_id)
+                .or_default()
+                .insert(turn_id);
+        }
+        self.links.insert(turn_id, record.links);
+        Ok(())
+    }
+
+    /// Drop links from and to turns `exists` no longer reports, so a reused
+    /// turn id can never inherit or receive a stale link.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .links
+            .iter()
+            .filter(|(source, links)| {
+                !exists(**source) || links.iter().any(|link| !exists(link.target_turn_id))
+            })
+            .map(|(source, _)| *source)
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        missing.sort_unstable();
+        for turn_id in missing {
+            self.log.append(&LinkRecord {
+                turn_id,
+                links: Vec::new(),
+            })?;
+            self.links.remove(&turn_id);
+        }
+        self.rebuild_incoming();
+        Ok(())
+    }
+
+    /// The links of `turn_id`, in the order they were appended.
+    pub fn get(&self, turn_id: u64) -> &[TurnLink] {
+        self.links
+            .get(&turn_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Turns linking to `turn_id`, oldest first.
+    pub fn incoming(&self, turn_id: u64) -> impl Iterator<Item = u64> + '_ {
+        self.incoming.get(&turn_id).into_iter().flatten().copied()
+    }
+}
+
+/// At most [`MAX_LINKS`] links, each of a valid kind.
+pub fn validate_links(links: &[TurnLink]) -> Result<()> {
+    if links.len() > MAX_LINKS {
+        return Err(StoreError::InvalidInput(format!(
+            "more than {MAX_LINKS} links on one turn"
+        )));
+    }
+    links.iter().try_for_each(|link| validate_kind(&link.kind))
+}
+
+/// Custom link kinds are non-empty UTF-8 of at most [`MAX_LINK_KIND_LEN`]
+/// bytes.
+fn validate_kind(kind: &LinkKind) -> Result<()> {
+    match kind {
+        LinkKind::Custom(name) if name.is_empty() => {
+            Err(StoreError::InvalidInput("custom link kind is empty".into()))
+        }
+        LinkKind::Custom(name) if name.len() > MAX_LINK_KIND_LEN => Err(StoreError::InvalidInput(
+            format!("custom link kind longer than {MAX_LINK_KIND_LEN} bytes"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    fn link(target_turn_id: u64, kind: LinkKind) -> TurnLink {
+        TurnLink {
+            target_turn_id,
+            kind,
+        }
+    }
+
+    #[test]
+    fn links_persist_with_reverse_index_and_truncate_torn_tail() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = LinkIndex::open(tmpdir.path()).unwrap();
+        index
+            .insert(
+                3,
+                vec![
+                    link(1, LinkKind::RepliesTo),
+                    link(2, LinkKind::Custom("cites".into())),
+                ],
+            )
+            .unwrap();
+        index.insert(4, vec![link(1, LinkKind::Causes)]).unwrap();
+        assert!(matches!(
+            index.insert(5, vec![link(1, LinkKind::Custom(String::new()))]),
+            Err(StoreError::InvalidInput(_))
+        ));
+        drop(index);
+
+        let mut index = LinkIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(3)[1], link(2, LinkKind::Custom("cites".into())));
+        assert_eq!(index.incoming(1).collect::<Vec<_>>(), [3, 4]);
+        assert!(index.get(5).is_empty());
+
+        // Dropping turn 2 drops the links of turn 3, which points at it.
+        index.release_missing(|turn_id| turn_id != 2).unwrap();
+        assert!(index.get(3).is_empty());
+        assert_eq!(index.incoming(1).collect::<Vec<_>>(), [4]);
+        drop(index);
+
+        // The drop for 3 is the last record; tearing it brings 3 back.
+        let path = tmpdir.path().join("links.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+        let index = LinkIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(3).len(), 2);
+        assert_eq!(index.incoming(1).collect::<Vec<_>>(), [3, 4]);
+    }
+}
@@ -16,14 +16,28 @@ use cxdb_server::http::start_http;
 use cxdb_server::metrics::Metrics;
 use cxdb_server::metrics::SessionTracker;
 use cxdb_server::protocol::{
-    encode_append_ack, encode_attach_fs_resp, encode_ctx_create_resp, encode_error,
-    encode_hello_resp, encode_put_blob_resp, parse_append_turn, parse_attach_fs, parse_ctx_create,
-    parse_ctx_fork, parse_get_blob, parse_get_head, parse_get_last, parse_hello, parse_put_blob,
-    read_frame, write_frame, MsgType,
+    decompress_frame, encode_append_ack, encode_append_ack_meta, encode_append_multi_resp,
+    encode_attach_fs_resp, encode_context_stats, encode_ctx_archive_resp,
+    encode_ctx_create_alias_resp, encode_ctx_create_resp, encode_ctx_delete_many_resp,
+    encode_error, encode_error_with_details, encode_get_context_resp, encode_hello_resp,
+    encode_list_contexts_resp, encode_prune_result, encode_put_blob_resp, encode_redact_resp,
+    encode_resolve_alias_resp, encode_type_histogram, metadata_auth, parse_append_multi,
+    parse_append_turn, parse_attach_fs, parse_ctx_compact, parse_ctx_create,
+    parse_ctx_create_alias, parse_ctx_create_with_turn, parse_ctx_delete_many, parse_ctx_fork,
+    parse_ctx_prune, parse_get_blob, parse_get_children, parse_get_head, parse_get_last,
+    parse_get_linked, parse_get_turn, parse_hello, parse_list_contexts, parse_put_blob,
+    parse_resolve_alias, parse_search_turns, parse_text_search, parse_turn_redact,
+    parse_type_histogram, read_frame, split_frame_metadata, verify_frame_checksum,
+    write_frame_negotiated, MsgType, FLAG_APPEND_META, FLAG_COMPRESSED, FLAG_CRC32C, FLAG_DEDUP,
+    FLAG_DEPTH_FILTER, FLAG_LINKS, FLAG_METADATA, FLAG_PROJECTION, FLAG_REDACTIONS, FLAG_SEARCH,
+    FLAG_TIMESTAMPS, METADATA_AUTH_KEY, PAYLOAD_OMITTED, SERVED_CODECS, SERVED_MESSAGE_TYPES,
+    TURN_FIELDS_ALL, TURN_FIELD_CONTENT_HASH, TURN_FIELD_DEPTH, TURN_FIELD_ENCODING,
+    TURN_FIELD_PARENT, TURN_FIELD_SIZE, TURN_FIELD_TYPE,
 };
 use cxdb_server::registry::Registry;
 use cxdb_server::s3_sync::{S3Sync, S3SyncConfig, S3SyncHandle};
-use cxdb_server::store::Store;
+use cxdb_server::store::{BatchAppend, GetLastScope, Store, TurnWithMeta};
+use cxdb_server::turn_store::TurnRecord;
 
 fn main() -> Result<()> {
     // Create tokio runtime for async S3 operations
@@ -77,6 +91,7 @@ fn main() -> Result<()> {
 
     let _http = start_http(
         config.http_bind_addr.clone(),
+        config.bind_addr.clone(),
         Arc::clone(&store),
         Arc::clone(&registry),
         Arc::clone(&metrics),
@@ -113,6 +128,7 @@ fn main() -> Result<()> {
                 let session_tracker = Arc::clone(&session_tracker);
                 let event_bus = Arc::clone(&event_bus);
                 let peer_addr_str = peer_addr.to_string();
+                let auth_token = config.auth_token.clone();
                 thread::spawn(move || {
                     if let Err(err) = handle_client(
                         stream,
@@ -121,6 +137,7 @@ fn main() -> Result<()> {
                         session_tracker,
                         event_bus,
                         peer_addr_str,
+                        auth_token,
                     ) {
                         eprintln!("connection error: {err}");
                     }
@@ -156,19 +173,54 @@ fn handle_client(
     session_tracker: Arc<SessionTracker>,
     event_bus: Arc<EventBus>,
     peer_addr: String,
+    auth_token: Option<String>,
 ) -> Result<()> {
     let session = metrics.register_session();
     let session_id = session.session_id();
     // Client tag will be set when HELLO is received
     let mut client_tag_received = false;
     let mut client_tag = String::new();
+    // Set once HELLO negotiates CRC32C frame checksums.
+    let mut checksums = false;
+    // Set once HELLO negotiates request metadata blocks.
+    let mut metadata = false;
+    // Set once HELLO negotiates append metadata in acks.
+    let mut append_meta = false;
+    // Set once HELLO negotiates turn timestamps in read responses.
+    let mut timestamps = false;
+    // Set once HELLO negotiates redaction markers in read responses.
+    let mut redactions = false;
+    // Set once HELLO negotiates turn links in read responses.
+    let mut links = false;
+    // Set once HELLO negotiates frame compression.
+    let mut compression: Option<u8> = None;
+    // With an auth token configured, nothing but HELLO is served until a
+    // HELLO presents the token.
+    let mut authenticated = auth_token.is_none();
 
     loop {
         let (header, payload) = match read_frame(&mut stream) {
+            Ok(v) if checksums => verify_frame_checksum(v.0, v.1)?,
             Ok(v) => v,
             Err(StoreError::Io(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
             Err(e) => return Err(e),
         };
+        // The checksum covers the compressed bytes, as sent.
+        let (header, payload) = match compression {
+            Some(codec) => decompress_frame(header, payload, codec)?,
+            None => (header, payload),
+        };
+        // Request metadata (request ids, tenant hints) correlates
+        // server-side logs; an `authorization` entry authenticates the
+        // request alone.
+        let (header, request_metadata, payload) = if metadata
+            && header.flags & FLAG_METADATA != 0
+            && header.msg_type != MsgType::Hello as u16
+        {
+            split_frame_metadata(header, payload)?
+        } else {
+            (header, Vec::new(), payload)
+        };
 
         metrics.record_session_activity(session_id);
         session_tracker.record_activity(session_id);
@@ -176,216 +228,753 @@ fn handle_client(
         let req_id = header.req_id;
 
         let op_start = std::time::Instant::now();
-        let response = match msg_type {
-            x if x == MsgType::Hello as u16 => {
-                let hello = parse_hello(&payload)?;
-                // Register session with client tag and peer address
-                if !client_tag_received {
-                    client_tag = hello.client_tag.clone();
-                    session_tracker.register(
-                        session_id,
-                        hello.client_tag.clone(),
-                        Some(peer_addr.clone()),
-                    );
-                    client_tag_received = true;
+        let mut resp_flags = 0;
+        let mut hello_codec = None;
+        // Handler errors become ERROR frames; only transport errors end the
+        // connection.
+        let response = (|| -> Result<(u16, Vec<u8>)> {
+            // A per-request token stands in for the connection's, so a
+            // gateway can act for several principals over one connection.
+            match (&auth_token, metadata_auth(&request_metadata)) {
+                (Some(expected), Some(presented)) if !token_matches(expected, presented) => {
+                    return Err(StoreError::Unauthenticated(
+                        "invalid request bearer token".into(),
+                    ));
+                }
+                (Some(_), Some(_)) => {}
+                _ if !authenticated && msg_type != MsgType::Hello as u16 => {
+                    return Err(StoreError::Unauthenticated(
+                        "HELLO with a bearer token required".into(),
+                    ));
+                }
+                _ => {}
+            }
+            match msg_type {
+                x if x == MsgType::Hello as u16 => {
+                    let hello = parse_hello(&payload)?;
+                    if let Some(expected) = &auth_token {
+                        let presented = hello.bearer_token.as_deref().unwrap_or_default();
+                        if !token_matches(expected, presented) {
+                            return Err(StoreError::Unauthenticated("invalid bearer token".into()));
+                        }
+                        authenticated = true;
+                    }
+                    // Register session with client tag and peer address
+                    if !client_tag_received {
+                        client_tag = hello.client_tag.clone();
+                        session_tracker.register(
+                            session_id,
+                            hello.client_tag.clone(),
+                            Some(peer_addr.clone()),
+                        );
+                        client_tag_received = true;
+
+                        // Publish ClientConnected event
+                        event_bus.publish(StoreEvent::ClientConnected {
+                            session_id: session_id.to_string(),
+                            client_tag: hello.client_tag.clone(),
+                        });
+                    }
+                    // The first codec the client lists that this server
+                    // has; none leaves frames plain.
+                    if header.flags & FLAG_COMPRESSED != 0 {
+                        hello_codec = hello
+                            .codecs
+                            .iter()
+                            .copied()
+                            .find(|codec| SERVED_CODECS.contains(codec));
+                    }
+                    if hello_codec.is_some() {
+                        resp_flags |= FLAG_COMPRESSED;
+                    }
+                    // protocol version 1
+                    let resp = encode_hello_resp(session_id, 1, SERVED_MESSAGE_TYPES, hello_codec)?;
 
-                    // Publish ClientConnected event
-                    event_bus.publish(StoreEvent::ClientConnected {
+                    // Echo a checksum request; the HELLO response itself is
+                    // plain and checksums start with the next frame.
+                    if header.flags & FLAG_CRC32C != 0 {
+                        resp_flags |= FLAG_CRC32C;
+                    }
+                    resp_flags |= header.flags
+                        & (FLAG_METADATA
+                            | FLAG_SEARCH
+                            | FLAG_APPEND_META
+                            | FLAG_DEDUP
+                            | FLAG_TIMESTAMPS
+                            | FLAG_REDACTIONS
+                            | FLAG_LINKS
+                            | FLAG_DEPTH_FILTER
+                            | FLAG_PROJECTION);
+                    Ok((MsgType::Hello as u16, resp))
+                }
+                x if x == MsgType::CtxCreate as u16 => {
+                    // If no HELLO was sent, register with empty tag
+                    if !client_tag_received {
+                        session_tracker.register(
+                            session_id,
+                            String::new(),
+                            Some(peer_addr.clone()),
+                        );
+                        client_tag_received = true;
+                    }
+                    let req = parse_ctx_create(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let head = match req.parent_context_id {
+                        Some(parent) => store.create_child_context(req.base_turn_id, parent)?,
+                        None => store.create_context(req.base_turn_id)?,
+                    };
+                    // Associate context with this session
+                    session_tracker.add_context(session_id, head.context_id);
+
+                    // Publish ContextCreated event
+                    event_bus.publish(StoreEvent::ContextCreated {
+                        context_id: head.context_id.to_string(),
                         session_id: session_id.to_string(),
-                        client_tag: hello.client_tag.clone(),
+                        client_tag: client_tag.clone(),
+                        created_at: unix_ms(),
                     });
+
+                    let resp = encode_ctx_create_resp(
+                        head.context_id,
+                        head.head_turn_id,
+                        head.head_depth,
+                    )?;
+                    Ok((MsgType::CtxCreate as u16, resp))
                 }
-                let resp = encode_hello_resp(session_id, 1)?; // protocol version 1
-                Ok((MsgType::Hello as u16, resp))
-            }
-            x if x == MsgType::CtxCreate as u16 => {
-                // If no HELLO was sent, register with empty tag
-                if !client_tag_received {
-                    session_tracker.register(session_id, String::new(), Some(peer_addr.clone()));
-                    client_tag_received = true;
-                }
-                let base_turn_id = parse_ctx_create(&payload)?;
-                let mut store = store.lock().unwrap();
-                let head = store.create_context(base_turn_id)?;
-                // Associate context with this session
-                session_tracker.add_context(session_id, head.context_id);
-
-                // Publish ContextCreated event
-                event_bus.publish(StoreEvent::ContextCreated {
-                    context_id: head.context_id.to_string(),
-                    session_id: session_id.to_string(),
-                    client_tag: client_tag.clone(),
-                    created_at: unix_ms(),
-                });
+                x if x == MsgType::CtxFork as u16 => {
+                    // If no HELLO was sent, register with empty tag
+                    if !client_tag_received {
+                        session_tracker.register(
+                            session_id,
+                            String::new(),
+                            Some(peer_addr.clone()),
+                        );
+                        client_tag_received = true;
+                    }
+                    let base_turn_id = parse_ctx_fork(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let head = store.fork_context(base_turn_id)?;
+                    // Associate forked context with this session
+                    session_tracker.add_context(session_id, head.context_id);
 
-                let resp =
-                    encode_ctx_create_resp(head.context_id, head.head_turn_id, head.head_depth)?;
-                Ok((MsgType::CtxCreate as u16, resp))
-            }
-            x if x == MsgType::CtxFork as u16 => {
-                // If no HELLO was sent, register with empty tag
-                if !client_tag_received {
-                    session_tracker.register(session_id, String::new(), Some(peer_addr.clone()));
-                    client_tag_received = true;
-                }
-                let base_turn_id = parse_ctx_fork(&payload)?;
-                let mut store = store.lock().unwrap();
-                let head = store.fork_context(base_turn_id)?;
-                // Associate forked context with this session
-                session_tracker.add_context(session_id, head.context_id);
-
-                // Publish ContextCreated event for forked context
-                event_bus.publish(StoreEvent::ContextCreated {
-                    context_id: head.context_id.to_string(),
-                    session_id: session_id.to_string(),
-                    client_tag: client_tag.clone(),
-                    created_at: unix_ms(),
-                });
+                    // Publish ContextCreated event for forked context
+                    event_bus.publish(StoreEvent::ContextCreated {
+                        context_id: head.context_id.to_string(),
+                        session_id: session_id.to_string(),
+                        client_tag: client_tag.clone(),
+                        created_at: unix_ms(),
+                    });
 
-                let resp =
-                    encode_ctx_create_resp(head.context_id, head.head_turn_id, head.head_depth)?;
-                Ok((MsgType::CtxFork as u16, resp))
-            }
-            x if x == MsgType::GetHead as u16 => {
-                let context_id = parse_get_head(&payload)?;
-                let store = store.lock().unwrap();
-                let head = store.get_head(context_id)?;
-                let resp =
-                    encode_ctx_create_resp(head.context_id, head.head_turn_id, head.head_depth)?;
-                Ok((MsgType::GetHead as u16, resp))
-            }
-            x if x == MsgType::AppendTurn as u16 => {
-                let req = parse_append_turn(&payload, header.flags)?;
-                let declared_type_id_clone = req.declared_type_id.clone();
-                let declared_type_version = req.declared_type_version;
-                let mut store = store.lock().unwrap();
-                let (record, metadata) = store.append_turn(
-                    req.context_id,
-                    req.parent_turn_id,
-                    req.declared_type_id,
-                    req.declared_type_version,
-                    req.encoding,
-                    req.compression,
-                    req.uncompressed_len,
-                    req.content_hash,
-                    &req.payload_bytes,
-                )?;
-                // If fs_root_hash was provided, attach it to this turn
-                if let Some(fs_root_hash) = req.fs_root_hash {
-                    store.attach_fs(record.turn_id, fs_root_hash)?;
-                }
-                metrics.record_append(op_start.elapsed());
-
-                // Publish TurnAppended event
-                event_bus.publish(StoreEvent::TurnAppended {
-                    context_id: req.context_id.to_string(),
-                    turn_id: record.turn_id.to_string(),
-                    parent_turn_id: record.parent_turn_id.to_string(),
-                    depth: record.depth,
-                    declared_type_id: Some(declared_type_id_clone),
-                    declared_type_version: Some(declared_type_version),
-                });
+                    let resp = encode_ctx_create_resp(
+                        head.context_id,
+                        head.head_turn_id,
+                        head.head_depth,
+                    )?;
+                    Ok((MsgType::CtxFork as u16, resp))
+                }
+                x if x == MsgType::CtxCreateWithTurn as u16 => {
+                    // If no HELLO was sent, register with empty tag
+                    if !client_tag_received {
+                        session_tracker.register(
+                            session_id,
+                            String::new(),
+                            Some(peer_addr.clone()),
+                        );
+                        client_tag_received = true;
+                    }
+                    let req = parse_ctx_create_with_turn(&payload, header.flags)?;
+                    let first = req.first;
+                    if first.dedup {
+                        return Err(StoreError::InvalidInput(
+                            "the first turn of a context cannot be deduplicated".into(),
+                        ));
+                    }
+                    let declared_type_id = first.declared_type_id.clone();
+                    let declared_type_version = first.declared_type_version;
+                    let mut store = store.lock().unwrap();
+                    let (head, record, metadata) = store.create_context_with_turn(
+                        req.base_turn_id,
+                        BatchAppend {
+                            context_id: 0,
+                            parent_turn_id: first.parent_turn_id,
+                            declared_type_id: first.declared_type_id,
+                            declared_type_version: first.declared_type_version,
+                            encoding: first.encoding,
+                            compression: first.compression,
+                            uncompressed_len: first.uncompressed_len,
+                            content_hash: first.content_hash,
+                            payload_bytes: first.payload_bytes,
+                            writer: first.writer,
+                            fs_root_hash: first.fs_root_hash,
+                            ttl_ms: first.ttl_ms,
+                            links: first.links,
+                        },
+                    )?;
+                    session_tracker.add_context(session_id, head.context_id);
+                    metrics.record_append(op_start.elapsed());
+
+                    event_bus.publish(StoreEvent::ContextCreated {
+                        context_id: head.context_id.to_string(),
+                        session_id: session_id.to_string(),
+                        client_tag: client_tag.clone(),
+                        created_at: unix_ms(),
+                    });
+                    event_bus.publish(StoreEvent::TurnAppended {
+                        context_id: head.context_id.to_string(),
+                        turn_id: record.turn_id.to_string(),
+                        parent_turn_id: record.parent_turn_id.to_string(),
+                        depth: record.depth,
+                        declared_type_id: Some(declared_type_id),
+                        declared_type_version: Some(declared_type_version),
+                    });
+                    if let Some(meta) = metadata {
+                        event_bus.publish(StoreEvent::ContextMetadataUpdated {
+                            context_id: head.context_id.to_string(),
+                            client_tag: meta.client_tag,
+                            title: meta.title,
+                            labels: meta.labels,
+                            has_provenance: meta.provenance.is_some(),
+                        });
+                    }
 
-                // If metadata was extracted (first turn), publish ContextMetadataUpdated
-                if let Some(meta) = metadata {
-                    event_bus.publish(StoreEvent::ContextMetadataUpdated {
+                    let resp = encode_ack(&store, head.context_id, &record, append_meta)?;
+                    Ok((MsgType::CtxCreateWithTurn as u16, resp))
+                }
+                x if x == MsgType::CtxCreateAlias as u16 => {
+                    // If no HELLO was sent, register with empty tag
+                    if !client_tag_received {
+                        session_tracker.register(
+                            session_id,
+                            String::new(),
+                            Some(peer_addr.clone()),
+                        );
+                        client_tag_received = true;
+                    }
+                    let req = parse_ctx_create_alias(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let (head, created) =
+                        store.create_or_get_context_by_alias(&req.alias, req.base_turn_id)?;
+                    if created {
+                        session_tracker.add_context(session_id, head.context_id);
+                        event_bus.publish(StoreEvent::ContextCreated {
+                            context_id: head.context_id.to_string(),
+                            session_id: session_id.to_string(),
+                            client_tag: client_tag.clone(),
+                            created_at: unix_ms(),
+                        });
+                    }
+
+                    let resp = encode_ctx_create_alias_resp(
+                        head.context_id,
+                        head.head_turn_id,
+                        head.head_depth,
+                        created,
+                    )?;
+                    Ok((MsgType::CtxCreateAlias as u16, resp))
+                }
+                x if x == MsgType::ResolveAlias as u16 => {
+                    let alias = parse_resolve_alias(&payload)?;
+                    let store = store.lock().unwrap();
+                    let resp = encode_resolve_alias_resp(store.resolve_alias(&alias))?;
+                    Ok((MsgType::ResolveAlias as u16, resp))
+                }
+                x if x == MsgType::GetHead as u16 => {
+                    let context_id = parse_get_head(&payload)?;
+                    let store = store.lock().unwrap();
+                    let head = store.get_head(context_id)?;
+                    let resp = encode_ctx_create_resp(
+                        head.context_id,
+                        head.head_turn_id,
+                        head.head_depth,
+                    )?;
+                    Ok((MsgType::GetHead as u16, resp))
+                }
+                x if x == MsgType::GetContext as u16 => {
+                    let context_id = parse_get_head(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let head = store.get_head(context_id)?;
+                    let parent = store.context_parent(context_id)?;
+                    Ok((
+                        MsgType::GetContext as u16,
+                        encode_get_context_resp(&head, parent)?,
+                    ))
+                }
+                x if x == MsgType::ListChildren as u16 => {
+                    let parent_context_id = parse_get_head(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let children = store.list_children(parent_context_id)?;
+                    Ok((
+                        MsgType::ListChildren as u16,
+                        encode_list_contexts_resp(&children)?,
+                    ))
+                }
+                x if x == MsgType::AppendTurn as u16 => {
+                    let req = parse_append_turn(&payload, header.flags)?;
+                    let declared_type_id_clone = req.declared_type_id.clone();
+                    let declared_type_version = req.declared_type_version;
+                    let mut store = store.lock().unwrap();
+                    store.check_links(req.context_id, req.parent_turn_id, &req.links)?;
+                    // A dedup hit appends nothing: no head move, stamp,
+                    // expiry or links, and no events.
+                    if req.dedup {
+                        if let Some(existing) =
+                            store.find_turn_by_hash(req.context_id, &req.content_hash)?
+                        {
+                            let mut resp =
+                                encode_ack(&store, req.context_id, &existing, append_meta)?;
+                            resp.push(0);
+                            return Ok((MsgType::AppendTurn as u16, resp));
+                        }
+                    }
+                    let (record, metadata) = match req.writer {
+                        Some(writer) => store.append_turn_as_writer(
+                            writer,
+                            req.context_id,
+                            req.parent_turn_id,
+                            req.declared_type_id,
+                            req.declared_type_version,
+                            req.encoding,
+                            req.compression,
+                            req.uncompressed_len,
+                            req.content_hash,
+                            &req.payload_bytes,
+                        )?,
+                        None => store.append_turn(
+                            req.context_id,
+                            req.parent_turn_id,
+                            req.declared_type_id,
+                            req.declared_type_version,
+                            req.encoding,
+                            req.compression,
+                            req.uncompressed_len,
+                            req.content_hash,
+                            &req.payload_bytes,
+                        )?,
+                    };
+                    // If fs_root_hash was provided, attach it to this turn
+                    if let Some(fs_root_hash) = req.fs_root_hash {
+                        store.attach_fs(record.turn_id, fs_root_hash)?;
+                    }
+                    if let Some(ttl_ms) = req.ttl_ms {
+                        store.set_turn_ttl(record.turn_id, ttl_ms)?;
+                    }
+                    store.links.insert(record.turn_id, req.links)?;
+                    metrics.record_append(op_start.elapsed());
+
+                    // Publish TurnAppended event
+                    event_bus.publish(StoreEvent::TurnAppended {
                         context_id: req.context_id.to_string(),
-                        client_tag: meta.client_tag,
-                        title: meta.title,
-                        labels: meta.labels,
-                        has_provenance: meta.provenance.is_some(),
+                        turn_id: record.turn_id.to_string(),
+                        parent_turn_id: record.parent_turn_id.to_string(),
+                        depth: record.depth,
+                        declared_type_id: Some(declared_type_id_clone),
+                        declared_type_version: Some(declared_type_version),
                     });
+
+                    // If metadata was extracted (first turn), publish ContextMetadataUpdated
+                    if let Some(meta) = metadata {
+                        event_bus.publish(StoreEvent::ContextMetadataUpdated {
+                            context_id: req.context_id.to_string(),
+                            client_tag: meta.client_tag,
+                            title: meta.title,
+                            labels: meta.labels,
+                            has_provenance: meta.provenance.is_some(),
+                        });
+                    }
+
+                    let mut resp = encode_ack(&store, req.context_id, &record, append_meta)?;
+                    if req.dedup {
+                        resp.push(1);
+                    }
+                    Ok((MsgType::AppendTurn as u16, resp))
+                }
+                x if x == MsgType::AppendMulti as u16 => {
+                    let entries: Vec<BatchAppend> = parse_append_multi(&payload)?
+                        .into_iter()
+                        .map(|req| BatchAppend {
+                            context_id: req.context_id,
+                            parent_turn_id: req.parent_turn_id,
+                            declared_type_id: req.declared_type_id,
+                            declared_type_version: req.declared_type_version,
+                            encoding: req.encoding,
+                            compression: req.compression,
+                            uncompressed_len: req.uncompressed_len,
+                            content_hash: req.content_hash,
+                            payload_bytes: req.payload_bytes,
+                            writer: req.writer,
+                            fs_root_hash: req.fs_root_hash,
+                            ttl_ms: req.ttl_ms,
+                            links: req.links,
+                        })
+                        .collect();
+                    let targets: Vec<(u64, String, u32)> = entries
+                        .iter()
+                        .map(|e| {
+                            (
+                                e.context_id,
+                                e.declared_type_id.clone(),
+                                e.declared_type_version,
+                            )
+                        })
+                        .collect();
+                    let mut store = store.lock().unwrap();
+                    let appended = store.append_batch(entries)?;
+                    let mut acks = Vec::with_capacity(appended.len());
+                    for ((record, metadata), (context_id, type_id, type_version)) in
+                        appended.into_iter().zip(targets)
+                    {
+                        metrics.record_append(op_start.elapsed());
+                        event_bus.publish(StoreEvent::TurnAppended {
+                            context_id: context_id.to_string(),
+                            turn_id: record.turn_id.to_string(),
+                            parent_turn_id: record.parent_turn_id.to_string(),
+                            depth: record.depth,
+                            declared_type_id: Some(type_id),
+                            declared_type_version: Some(type_version),
+                        });
+                        if let Some(meta) = metadata {
+                            event_bus.publish(StoreEvent::ContextMetadataUpdated {
+                                context_id: context_id.to_string(),
+                                client_tag: meta.client_tag,
+                                title: meta.title,
+                                labels: meta.labels,
+                                has_provenance: meta.provenance.is_some(),
+                            });
+                        }
+                        acks.push(encode_ack(&store, context_id, &record, append_meta)?);
+                    }
+                    Ok((
+                        MsgType::AppendMulti as u16,
+                        encode_append_multi_resp(&acks)?,
+                    ))
                 }
+                x if x == MsgType::CtxCompact as u16 => {
+                    let req = parse_ctx_compact(&payload, header.flags)?;
+                    let summary = req.summary;
+                    if summary.writer.is_some() {
+                        return Err(StoreError::InvalidInput(
+                            "summary turns cannot carry a writer stamp".into(),
+                        ));
+                    }
+                    if summary.ttl_ms.is_some() {
+                        return Err(StoreError::InvalidInput(
+                            "summary turns cannot expire".into(),
+                        ));
+                    }
+                    if summary.dedup {
+                        return Err(StoreError::InvalidInput(
+                            "summary turns cannot be deduplicated".into(),
+                        ));
+                    }
+                    if !summary.links.is_empty() {
+                        return Err(StoreError::InvalidInput(
+                            "summary turns cannot carry links".into(),
+                        ));
+                    }
+                    let mut store = store.lock().unwrap();
+                    let (record, _) = store.compact_context(
+                        summary.context_id,
+                        req.up_to_turn_id,
+                        summary.parent_turn_id,
+                        summary.declared_type_id.clone(),
+                        summary.declared_type_version,
+                        summary.encoding,
+                        summary.compression,
+                        summary.uncompressed_len,
+                        summary.content_hash,
+                        &summary.payload_bytes,
+                    )?;
+                    if let Some(fs_root_hash) = summary.fs_root_hash {
+                        store.attach_fs(record.turn_id, fs_root_hash)?;
+                    }
+                    metrics.record_append(op_start.elapsed());
 
-                let resp = encode_append_ack(
-                    req.context_id,
-                    record.turn_id,
-                    record.depth,
-                    &record.payload_hash,
-                )?;
-                Ok((MsgType::AppendTurn as u16, resp))
-            }
-            x if x == MsgType::AttachFs as u16 => {
-                let req = parse_attach_fs(&payload)?;
-                let mut store = store.lock().unwrap();
-                store.attach_fs(req.turn_id, req.fs_root_hash)?;
-                let resp = encode_attach_fs_resp(req.turn_id, &req.fs_root_hash)?;
-                Ok((MsgType::AttachFs as u16, resp))
-            }
-            x if x == MsgType::PutBlob as u16 => {
-                let req = parse_put_blob(&payload)?;
-                let mut store = store.lock().unwrap();
-                // Verify hash matches
-                let actual_hash = blake3::hash(&req.data);
-                if actual_hash.as_bytes() != &req.hash {
-                    return Err(StoreError::InvalidInput("blob hash mismatch".into()));
-                }
-                let was_new = !store.blob_store.contains(&req.hash);
-                store.blob_store.put_if_absent(req.hash, &req.data)?;
-                let resp = encode_put_blob_resp(&req.hash, was_new)?;
-                Ok((MsgType::PutBlob as u16, resp))
-            }
-            x if x == MsgType::GetLast as u16 => {
-                let req = parse_get_last(&payload)?;
-                let mut store = store.lock().unwrap();
-                let items = store.get_last(req.context_id, req.limit, req.include_payload != 0)?;
-                metrics.record_get_last(op_start.elapsed());
-                let mut resp = Vec::new();
-                resp.write_u32::<byteorder::LittleEndian>(items.len() as u32)?;
-                for item in items {
-                    resp.write_u64::<byteorder::LittleEndian>(item.record.turn_id)?;
-                    resp.write_u64::<byteorder::LittleEndian>(item.record.parent_turn_id)?;
-                    resp.write_u32::<byteorder::LittleEndian>(item.record.depth)?;
-                    resp.write_u32::<byteorder::LittleEndian>(
-                        item.meta.declared_type_id.len() as u32
+                    event_bus.publish(StoreEvent::TurnAppended {
+                        context_id: summary.context_id.to_string(),
+                        turn_id: record.turn_id.to_string(),
+                        parent_turn_id: record.parent_turn_id.to_string(),
+                        depth: record.depth,
+                        declared_type_id: Some(summary.declared_type_id),
+                        declared_type_version: Some(summary.declared_type_version),
+                    });
+
+                    let resp = encode_ack(&store, summary.context_id, &record, append_meta)?;
+                    Ok((MsgType::CtxCompact as u16, resp))
+                }
+                x if x == MsgType::GetTurn as u16 => {
+                    let req = parse_get_turn(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let item = store.get_turn(req.turn_id, req.include_payload != 0)?;
+                    let resp = encode_turns(
+                        vec![item],
+                        None,
+                        TURN_FIELDS_ALL,
+                        timestamps,
+                        redactions,
+                        links,
+                    )?;
+                    Ok((MsgType::GetTurn as u16, resp))
+                }
+                x if x == MsgType::GetChildren as u16 => {
+                    let req = parse_get_children(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let items = store.get_children(
+                        req.context_id,
+                        req.turn_id,
+                        req.include_payload != 0,
+                    )?;
+                    let resp =
+                        encode_turns(items, None, TURN_FIELDS_ALL, timestamps, redactions, links)?;
+                    Ok((MsgType::GetChildren as u16, resp))
+                }
+                x if x == MsgType::GetLinked as u16 => {
+                    let req = parse_get_linked(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let items = store.get_linked(
+                        req.context_id,
+                        req.turn_id,
+                        req.direction,
+                        req.include_payload,
+                    )?;
+                    let resp =
+                        encode_turns(items, None, TURN_FIELDS_ALL, timestamps, redactions, links)?;
+                    Ok((MsgType::GetLinked as u16, resp))
+                }
+                x if x == MsgType::CtxPrune as u16 => {
+                    let req = parse_ctx_prune(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let result = store.prune_context(req.context_id, req.keep_from_depth)?;
+                    let resp = encode_prune_result(result.turns_pruned, result.bytes_reclaimed)?;
+                    Ok((MsgType::CtxPrune as u16, resp))
+                }
+                x if x == MsgType::CtxArchive as u16 => {
+                    let context_id = parse_get_head(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let head = store.archive_context(context_id)?;
+                    Ok((MsgType::CtxArchive as u16, encode_ctx_archive_resp(&head)?))
+                }
+                x if x == MsgType::CtxRestore as u16 => {
+                    let context_id = parse_get_head(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let head = store.restore_context(context_id)?;
+                    Ok((MsgType::CtxRestore as u16, encode_ctx_archive_resp(&head)?))
+                }
+                x if x == MsgType::CtxDeleteMany as u16 => {
+                    let req = parse_ctx_delete_many(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let deleted = store.delete_contexts(&req.filter)?;
+                    Ok((
+                        MsgType::CtxDeleteMany as u16,
+                        encode_ctx_delete_many_resp(deleted.len())?,
+                    ))
+                }
+                x if x == MsgType::ListContexts as u16 => {
+                    let req = parse_list_contexts(&payload)?;
+                    let store = store.lock().unwrap();
+                    let heads = store.list_contexts(req.limit, req.archived);
+                    Ok((
+                        MsgType::ListContexts as u16,
+                        encode_list_contexts_resp(&heads)?,
+                    ))
+                }
+                x if x == MsgType::TurnRedact as u16 => {
+                    let req = parse_turn_redact(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let redaction = store.redact_turn(req.context_id, req.turn_id, &req.reason)?;
+                    let resp = encode_redact_resp(
+                        req.turn_id,
+                        redaction.redacted_at_unix_ms,
+                        &redaction.reason,
                     )?;
-                    resp.extend_from_slice(item.meta.declared_type_id.as_bytes());
-                    resp.write_u32::<byteorder::LittleEndian>(item.meta.declared_type_version)?;
-                    resp.write_u32::<byteorder::LittleEndian>(item.meta.encoding)?;
-                    // always return raw payload when included
-                    let compression = if item.payload.is_some() {
-                        0
-                    } else {
-                        item.meta.compression
+                    Ok((MsgType::TurnRedact as u16, resp))
+                }
+                x if x == MsgType::SearchTurns as u16 => {
+                    let req = parse_search_turns(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let hits = store.search_turns(req.context_id, &req.search, req.limit)?;
+                    // The matched values, then the hits' metadata as a
+                    // GET_LAST response without payloads.
+                    let mut resp = Vec::new();
+                    resp.write_u32::<byteorder::LittleEndian>(hits.len() as u32)?;
+                    let mut items = Vec::with_capacity(hits.len());
+                    for (item, value) in hits {
+                        let mut encoded = Vec::new();
+                        rmpv::encode::write_value(&mut encoded, &value)
+                            .map_err(std::io::Error::from)?;
+                        resp.write_u32::<byteorder::LittleEndian>(encoded.len() as u32)?;
+                        resp.extend_from_slice(&encoded);
+                        items.push(item);
+                    }
+                    resp.extend_from_slice(&encode_turns(
+                        items,
+                        None,
+                        TURN_FIELDS_ALL,
+                        timestamps,
+                        redactions,
+                        links,
+                    )?);
+                    Ok((MsgType::SearchTurns as u16, resp))
+                }
+                x if x == MsgType::TypeHistogram as u16 => {
+                    let req = parse_type_histogram(&payload)?;
+                    let scope = GetLastScope {
+                        before_turn_id: req.before_turn_id,
+                        include_compacted: req.include_compacted,
+                        include_expired: req.include_expired,
+                        depths: req.depths,
                     };
-                    resp.write_u32::<byteorder::LittleEndian>(compression)?;
-                    let uncompressed_len = item
-                        .payload
-                        .as_ref()
-                        .map(|p| p.len() as u32)
-                        .unwrap_or(item.meta.uncompressed_len);
-                    resp.write_u32::<byteorder::LittleEndian>(uncompressed_len)?;
-                    resp.extend_from_slice(&item.record.payload_hash);
-                    if let Some(payload) = item.payload {
-                        resp.write_u32::<byteorder::LittleEndian>(payload.len() as u32)?;
-                        resp.extend_from_slice(&payload);
+                    let mut store = store.lock().unwrap();
+                    let counts = store.type_histogram(req.context_id, &scope)?;
+                    Ok((
+                        MsgType::TypeHistogram as u16,
+                        encode_type_histogram(&counts)?,
+                    ))
+                }
+                x if x == MsgType::ContextStats as u16 => {
+                    let context_id = parse_get_head(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let stats = store.context_stats(context_id)?;
+                    Ok((MsgType::ContextStats as u16, encode_context_stats(&stats)?))
+                }
+                x if x == MsgType::TextSearch as u16 => {
+                    let req = parse_text_search(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let hits = store.search_text(&req.search)?;
+                    // Each hit's context and snippet, then the hits'
+                    // metadata as a GET_LAST response without payloads.
+                    let mut resp = Vec::new();
+                    resp.write_u32::<byteorder::LittleEndian>(hits.len() as u32)?;
+                    let mut items = Vec::with_capacity(hits.len());
+                    for hit in hits {
+                        resp.write_u64::<byteorder::LittleEndian>(hit.context_id)?;
+                        resp.write_u32::<byteorder::LittleEndian>(hit.snippet.len() as u32)?;
+                        resp.extend_from_slice(hit.snippet.as_bytes());
+                        items.push(hit.turn);
                     }
+                    resp.extend_from_slice(&encode_turns(
+                        items,
+                        None,
+                        TURN_FIELDS_ALL,
+                        timestamps,
+                        redactions,
+                        links,
+                    )?);
+                    Ok((MsgType::TextSearch as u16, resp))
                 }
-                Ok((MsgType::GetLast as u16, resp))
-            }
-            x if x == MsgType::GetBlob as u16 => {
-                let hash = parse_get_blob(&payload)?;
-                let mut store = store.lock().unwrap();
-                let bytes = store.get_blob(&hash)?;
-                metrics.record_get_blob(op_start.elapsed());
-                let mut resp = Vec::new();
-                resp.write_u32::<byteorder::LittleEndian>(bytes.len() as u32)?;
-                resp.extend_from_slice(&bytes);
-                Ok((MsgType::GetBlob as u16, resp))
+                x if x == MsgType::AttachFs as u16 => {
+                    let req = parse_attach_fs(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    store.attach_fs(req.turn_id, req.fs_root_hash)?;
+                    let resp = encode_attach_fs_resp(req.turn_id, &req.fs_root_hash)?;
+                    Ok((MsgType::AttachFs as u16, resp))
+                }
+                x if x == MsgType::PutBlob as u16 => {
+                    let req = parse_put_blob(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    // Verify hash matches
+                    let actual_hash = blake3::hash(&req.data);
+                    if actual_hash.as_bytes() != &req.hash {
+                        return Err(StoreError::InvalidInput("blob hash mismatch".into()));
+                    }
+                    let was_new = !store.blob_store.contains(&req.hash);
+                    store.blob_store.put_if_absent(req.hash, &req.data)?;
+                    let resp = encode_put_blob_resp(&req.hash, was_new)?;
+                    Ok((MsgType::PutBlob as u16, resp))
+                }
+                x if x == MsgType::GetLast as u16 => {
+                    let req = parse_get_last(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let scope = GetLastScope {
+                        before_turn_id: req.before_turn_id,
+                        include_compacted: req.include_compacted,
+                        include_expired: req.include_expired,
+                        depths: req.depths,
+                    };
+                    let items = store.get_last_scoped(
+                        req.context_id,
+                        req.limit,
+                        req.include_payload != 0,
+                        &scope,
+                    )?;
+                    metrics.record_get_last(op_start.elapsed());
+                    let resp = encode_turns(
+                        items,
+                        req.max_payload_bytes,
+                        req.fields,
+                        timestamps,
+                        redactions,
+                        links,
+                    )?;
+                    Ok((MsgType::GetLast as u16, resp))
+                }
+                x if x == MsgType::GetBlob as u16 => {
+                    let hash = parse_get_blob(&payload)?;
+                    let mut store = store.lock().unwrap();
+                    let bytes = store.get_blob(&hash)?;
+                    metrics.record_get_blob(op_start.elapsed());
+                    let mut resp = Vec::new();
+                    resp.write_u32::<byteorder::LittleEndian>(bytes.len() as u32)?;
+                    resp.extend_from_slice(&bytes);
+                    Ok((MsgType::GetBlob as u16, resp))
+                }
+                _ => Err(StoreError::InvalidInput("unknown msg_type".into())),
             }
-            _ => Err(StoreError::InvalidInput("unknown msg_type".into())),
-        };
+        })();
 
         match response {
             Ok((resp_type, resp_payload)) => {
-                write_frame(&mut stream, resp_type, 0, req_id, &resp_payload)?;
+                // HELLO flags are echoed on plain connections only.
+                let flags = if checksums { 0 } else { resp_flags };
+                write_frame_negotiated(
+                    &mut stream,
+                    resp_type,
+                    flags,
+                    req_id,
+                    &resp_payload,
+                    checksums,
+                    compression,
+                )?;
                 stream.flush()?;
+                checksums |= resp_flags & FLAG_CRC32C != 0;
+                if resp_flags & FLAG_COMPRESSED != 0 {
+                    compression = hello_codec;
+                }
+                metadata |= resp_flags & FLAG_METADATA != 0;
+                append_meta |= resp_flags & FLAG_APPEND_META != 0;
+                timestamps |= resp_flags & FLAG_TIMESTAMPS != 0;
+                redactions |= resp_flags & FLAG_REDACTIONS != 0;
+                links |= resp_flags & FLAG_LINKS != 0;
             }
             Err(err) => {
                 metrics.record_error("binary");
-                let (code, detail) = map_error(&err);
-                let payload = encode_error(code, &detail)?;
-                write_frame(&mut stream, MsgType::Error as u16, 0, req_id, &payload)?;
+                let (code, detail, details) = map_error(&err);
+                if !request_metadata.is_empty() {
+                    let pairs: Vec<String> = request_metadata
+                        .iter()
+                        .map(|(key, value)| match key.as_str() {
+                            METADATA_AUTH_KEY => format!("{key}=<redacted>"),
+                            _ => format!("{key}={value:?}"),
+                        })
+                        .collect();
+                    eprintln!(
+                        "request error (msg_type {msg_type}, req_id {req_id}, {}): {detail}",
+                        pairs.join(" ")
+                    );
+                }
+                let payload = if details.is_empty() {
+                    encode_error(code, &detail)?
+                } else {
+                    encode_error_with_details(code, &detail, 0, &details)?
+                };
+                write_frame_negotiated(
+                    &mut stream,
+                    MsgType::Error as u16,
+                    0,
+                    req_id,
+                    &payload,
+                    checksums,
+                    compression,
+                )?;
                 stream.flush()?;
             }
         }
@@ -402,6 +991,168 @@ fn handle_client(
     Ok(())
 }
 
+/// Encode GET_LAST / GET_TURN response items, withholding payloads larger
+/// than `max_payload_bytes` and item fields not in `fields` (`turn_id` is
+/// always written). Writer stamps follow the items as a trailer,
+/// then expiries, each only when some item has one, then, with
+/// `timestamps` or `redactions`, every item's creation time, then, with
+/// `redactions` or `links`, the redacted items, then, with `links`, the
+/// items' links. A trailer's count is written (possibly 0) whenever a
+/// later trailer follows.
+fn encode_turns(
+    items: Vec<TurnWithMeta>,
+    max_payload_bytes: Option<u32>,
+    fields: u32,
+    timestamps: bool,
+    redactions: bool,
+    links: bool,
+) -> Result<Vec<u8>> {
+    let redactions = redactions || links;
+    let timestamps = timestamps || redactions;
+    let mut resp = Vec::new();
+    resp.write_u32::<byteorder::LittleEndian>(items.len() as u32)?;
+    let mut writers = Vec::new();
+    let mut expiries = Vec::new();
+    let mut created_at = Vec::new();
+    let mut redacted = Vec::new();
+    let mut linked = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        created_at.push(item.record.created_at_unix_ms);
+        if !item.links.is_empty() {
+            linked.push((index as u32, item.links));
+        }
+        if let Some(redaction) = item.redaction {
+            redacted.push((index as u32, redaction));
+        }
+        if let Some(writer) = item.writer {
+            writers.push((index as u32, writer));
+        }
+        if let Some(expires_at) = item.expires_at_unix_ms {
+            expiries.push((index as u32, expires_at, item.expired));
+        }
+        resp.write_u64::<byteorder::LittleEndian>(item.record.turn_id)?;
+        if fields & TURN_FIELD_PARENT != 0 {
+            resp.write_u64::<byteorder::LittleEndian>(item.record.parent_turn_id)?;
+        }
+        if fields & TURN_FIELD_DEPTH != 0 {
+            resp.write_u32::<byteorder::LittleEndian>(item.record.depth)?;
+        }
+        if fields & TURN_FIELD_TYPE != 0 {
+            resp.write_u32::<byteorder::LittleEndian>(item.meta.declared_type_id.len() as u32)?;
+            resp.extend_from_slice(item.meta.declared_type_id.as_bytes());
+            resp.write_u32::<byteorder::LittleEndian>(item.meta.declared_type_version)?;
+        }
+        if fields & TURN_FIELD_ENCODING != 0 {
+            resp.write_u32::<byteorder::LittleEndian>(item.meta.encoding)?;
+            // always return raw payload when included
+            let compression = if item.payload.is_some() {
+                0
+            } else {
+                item.meta.compression
+            };
+            resp.write_u32::<byteorder::LittleEndian>(compression)?;
+        }
+        if fields & TURN_FIELD_SIZE != 0 {
+            let uncompressed_len = item
+                .payload
+                .as_ref()
+                .map(|p| p.len() as u32)
+                .unwrap_or(item.meta.uncompressed_len);
+            resp.write_u32::<byteorder::LittleEndian>(uncompressed_len)?;
+        }
+        if fields & TURN_FIELD_CONTENT_HASH != 0 {
+            resp.extend_from_slice(&item.record.payload_hash);
+        }
+        match item.payload {
+            Some(payload) if max_payload_bytes.is_some_and(|max| payload.len() > max as usize) => {
+                resp.write_u32::<byteorder::LittleEndian>(PAYLOAD_OMITTED)?;
+            }
+            Some(payload) => {
+                resp.write_u32::<byteorder::LittleEndian>(payload.len() as u32)?;
+                resp.extend_from_slice(&payload);
+            }
+            None => {}
+        }
+    }
+    if !writers.is_empty() || !expiries.is_empty() || timestamps {
+        resp.write_u32::<byteorder::LittleEndian>(writers.len() as u32)?;
+        for (index, writer) in writers {
+            resp.write_u32::<byteorder::LittleEndian>(index)?;
+            resp.write_u32::<byteorder::LittleEndian>(writer.writer_id.len() as u32)?;
+            resp.extend_from_slice(writer.writer_id.as_bytes());
+            resp.write_u64::<byteorder::LittleEndian>(writer.writer_seq)?;
+        }
+    }
+    if !expiries.is_empty() || timestamps {
+        resp.write_u32::<byteorder::LittleEndian>(expiries.len() as u32)?;
+        for (index, expires_at, expired) in expiries {
+            resp.write_u32::<byteorder::LittleEndian>(index)?;
+            resp.write_u64::<byteorder::LittleEndian>(expires_at)?;
+            resp.push(expired as u8);
+        }
+    }
+    if timestamps {
+        resp.write_u32::<byteorder::LittleEndian>(created_at.len() as u32)?;
+        for created_at in created_at {
+            resp.write_u64::<byteorder::LittleEndian>(created_at)?;
+        }
+    }
+    if redactions {
+        resp.write_u32::<byteorder::LittleEndian>(redacted.len() as u32)?;
+        for (index, redaction) in redacted {
+            resp.write_u32::<byteorder::LittleEndian>(index)?;
+            resp.write_u64::<byteorder::LittleEndian>(redaction.redacted_at_unix_ms)?;
+            resp.write_u32::<byteorder::LittleEndian>(redaction.reason.len() as u32)?;
+            resp.extend_from_slice(redaction.reason.as_bytes());
+        }
+    }
+    if links {
+        resp.write_u32::<byteorder::LittleEndian>(linked.len() as u32)?;
+        for (index, item_links) in linked {
+            resp.write_u32::<byteorder::LittleEndian>(index)?;
+            resp.write_u32::<byteorder::LittleEndian>(item_links.len() as u32)?;
+            for link in item_links {
+                resp.write_u64::<byteorder::LittleEndian>(link.target_turn_id)?;
+                resp.push(link.kind.tag());
+                resp.write_u32::<byteorder::LittleEndian>(link.kind.name().len() as u32)?;
+                resp.extend_from_slice(link.kind.name().as_bytes());
+            }
+        }
+    }
+    Ok(resp)
+}
+
+/// Encode the APPEND_TURN / CTX_COMPACT ack for `record`, with the append
+/// metadata when the connection negotiated it.
+fn encode_ack(
+    store: &Store,
+    context_id: u64,
+    record: &TurnRecord,
+    append_meta: bool,
+) -> Result<Vec<u8>> {
+    let ack = encode_append_ack(
+        context_id,
+        record.turn_id,
+        record.depth,
+        &record.payload_hash,
+    )?;
+    if !append_meta {
+        return Ok(ack);
+    }
+    let stored_len = store
+        .blob_store
+        .stored_len(&record.payload_hash)
+        .unwrap_or_default();
+    let head = store.get_head(context_id)?;
+    encode_append_ack_meta(
+        ack,
+        record.created_at_unix_ms,
+        stored_len,
+        head.head_turn_id,
+        head.head_depth,
+    )
+}
+
 /// Get current time in milliseconds since Unix epoch.
 fn unix_ms() -> u64 {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -411,11 +1162,74 @@ fn unix_ms() -> u64 {
         .unwrap_or(0)
 }
 
-fn map_error(err: &StoreError) -> (u32, String) {
+/// Compares bearer tokens in time independent of where they differ.
+fn token_matches(expected: &str, presented: &str) -> bool {
+    let (expected, presented) = (expected.as_bytes(), presented.as_bytes());
+    expected.len() == presented.len()
+        && expected
+            .iter()
+            .zip(presented)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Error code, detail text and structured ERROR trailer details.
+fn map_error(err: &StoreError) -> (u32, String, Vec<(&'static str, String)>) {
     match err {
-        StoreError::NotFound(msg) => (404, msg.clone()),
-        StoreError::InvalidInput(msg) => (422, msg.clone()),
-        StoreError::Corrupt(msg) => (500, msg.clone()),
-        StoreError::Io(msg) => (500, msg.to_string()),
+        StoreError::Unauthenticated(msg) => (401, msg.clone(), Vec::new()),
+        StoreError::NotFound(msg) => (404, msg.clone(), Vec::new()),
+        StoreError::ContextNotFound { context_id } => (
+            404,
+            err.to_string(),
+            vec![
+                ("resource", "context".into()),
+                ("context_id", context_id.to_string()),
+            ],
+        ),
+        StoreError::TurnNotFound { turn_id, .. } => (
+            404,
+            err.to_string(),
+            vec![
+                ("resource", "turn".into()),
+                ("turn_id", turn_id.to_string()),
+            ],
+        ),
+        StoreError::InvalidInput(msg) => (422, msg.clone(), Vec::new()),
+        StoreError::Corrupt(msg) => (500, msg.clone(), Vec::new()),
+        StoreError::Io(msg) => (500, msg.to_string(), Vec::new()),
+        StoreError::Pruned { turn_id } => {
+            (410, err.to_string(), vec![("turn_id", turn_id.to_string())])
+        }
+        StoreError::ContextArchived { context_id } => (
+            423,
+            err.to_string(),
+            vec![("context_id", context_id.to_string())],
+        ),
+        StoreError::LinkTargetNotFound { turn_id, .. } => (
+            404,
+            err.to_string(),
+            vec![
+                ("resource", "turn".into()),
+                ("turn_id", turn_id.to_string()),
+            ],
+        ),
+        StoreError::WriterSequenceConflict {
+            writer_id,
+            last_seq,
+            writer_seq,
+        } => (
+            409,
+            err.to_string(),
+            vec![
+                ("writer_id", writer_id.clone()),
+                ("last_seq", last_seq.to_string()),
+                ("writer_seq", writer_seq.to_string()),
+            ],
+        ),
+        StoreError::TransactionAborted { entry, source } => {
+            let (code, detail, mut details) = map_error(source);
+            details.push(("entry", entry.to_string()));
+            (code, detail, details)
+        }
     }
 }
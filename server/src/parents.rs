@@ -0,0 +1,143 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Context parents: which context another was derived from.
+//!
+//! A parent given at CTX_CREATE is recorded here. Contexts created without
+//! one may still name a parent in their first turn's provenance
+//! (`parent_context_id`); [`Store`](crate::store::Store) falls back to that,
+//! so both kinds show up in GET_CONTEXT and LIST_CHILDREN.
+//!
+//! # Storage Format
+//!
+//! The parent index (`turns/parents.idx`) is an append-only file of
+//! fixed-size records:
+//! - context_id: u64
+//! - parent_context_id: u64 (0 = record dropped)
+//! - crc32: u32 over the preceding fields
+//!
+//! Last write wins per context. The file is a [`RecordLog`], so a torn or
+//! corrupt tail is truncated on load.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{Result, StoreError};
+use crate::record_log::RecordLog;
+
+pub struct ParentIndex {
+    log: RecordLog<(u64, u64)>,
+    /// context_id -> parent_context_id
+    parents: HashMap<u64, u64>,
+}
+
+impl ParentIndex {
+    /// Open or create the parent index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<(u64, u64)>::open(dir, "parents.idx")?;
+        let mut parents = HashMap::new();
+        for (context_id, parent_context_id) in records {
+            if parent_context_id == 0 {
+                parents.remove(&context_id);
+            } else {
+                parents.insert(context_id, parent_context_id);
+            }
+        }
+        Ok(Self { log, parents })
+    }
+
+    /// Record that `context_id` derives from `parent_context_id`.
+    pub fn insert(&mut self, context_id: u64, parent_context_id: u64) -> Result<()> {
+        if parent_context_id == 0 {
+            return Err(StoreError::InvalidInput(
+                "parent_context_id is required".into(),
+            ));
+        }
+        self.log.append(&(context_id, parent_context_id))?;
+        self.parents.insert(context_id, parent_context_id);
+        Ok(())
+    }
+
+    /// Drop the record for `context_id`, e.g. when the context is deleted.
+    /// Its children keep naming it as their parent.
+    pub fn release_context(&mut self, context_id: u64) -> Result<()> {
+        if self.parents.contains_key(&context_id) {
+            self.log.append(&(context_id, 0))?;
+            self.parents.remove(&context_id);
+        }
+        Ok(())
+    }
+
+    /// Drop records whose context `exists` no longer reports, so a reused
+    /// context id can never inherit a stale parent.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .parents
+            .keys()
+            .copied()
+            .filter(|id| !exists(*id))
+            .collect();
+        missing.sort_unstable();
+        for context_id in missing {
+            self.release_context(context_id)?;
+        }
+        Ok(())
+    }
+
+    /// Parent recorded for `context_id` at creation.
+    pub fn parent(&self, context_id: u64) -> Option<u64> {
+        self.parents.get(&context_id).copied()
+    }
+
+    /// Contexts recorded with `parent_context_id` as their parent, in
+    /// ascending id order.
+    pub fn children(&self, parent_context_id: u64) -> Vec<u64> {
+        let mut children: Vec<u64> = self
+            .parents
+            .iter()
+            .filter(|(_, parent)| **parent == parent_context_id)
+            .map(|(context_id, _)| *context_id)
+            .collect();
+        children.sort_unstable();
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parents_persist_and_truncate_torn_tail() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = ParentIndex::open(tmpdir.path()).unwrap();
+        index.insert(2, 1).unwrap();
+        index.insert(3, 1).unwrap();
+        index.insert(4, 2).unwrap();
+        assert!(matches!(
+            index.insert(5, 0),
+            Err(StoreError::InvalidInput(_))
+        ));
+        index.release_context(3).unwrap();
+        drop(index);
+
+        let path = tmpdir.path().join("parents.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        // The torn tombstone for 3 is lost, so its record is live again.
+        let mut index = ParentIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.children(1), [2, 3]);
+        assert_eq!(index.parent(4), Some(2));
+        assert_eq!(index.parent(1), None);
+
+        index.release_missing(|id| id != 2).unwrap();
+        drop(index);
+        let index = ParentIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.children(1), [3]);
+        assert_eq!(index.children(2), [4]);
+    }
+}
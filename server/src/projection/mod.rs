@@ -10,6 +10,7 @@ use serde_json::{Map, Number, Value as JsonValue};
 
 use crate::error::{Result, StoreError};
 use crate::registry::{ItemsSpec, Registry, TypeVersionSpec};
+use crate::search::ENCODING_MSGPACK;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BytesRender {
@@ -51,15 +52,44 @@ pub struct ProjectionResult {
     pub unknown: Option<JsonValue>,
 }
 
+/// Turn encoding of a CBOR (RFC 8949) payload.
+pub const ENCODING_CBOR: u32 = 2;
+
 pub fn project_msgpack(
     payload: &[u8],
     descriptor: &TypeVersionSpec,
     registry: &Registry,
     options: &RenderOptions,
 ) -> Result<ProjectionResult> {
-    let mut cursor = std::io::Cursor::new(payload);
-    let value = rmpv::decode::read_value(&mut cursor)
-        .map_err(|e| StoreError::InvalidInput(format!("msgpack decode error: {e}")))?;
+    project_payload(payload, ENCODING_MSGPACK, descriptor, registry, options)
+}
+
+/// Projects a payload stored with `encoding`. CBOR payloads are converted to
+/// the msgpack value model first, so both render the same way.
+pub fn project_payload(
+    payload: &[u8],
+    encoding: u32,
+    descriptor: &TypeVersionSpec,
+    registry: &Registry,
+    options: &RenderOptions,
+) -> Result<ProjectionResult> {
+    let value = match encoding {
+        ENCODING_MSGPACK => {
+            let mut cursor = std::io::Cursor::new(payload);
+            rmpv::decode::read_value(&mut cursor)
+                .map_err(|e| StoreError::InvalidInput(format!("msgpack decode error: {e}")))?
+        }
+        ENCODING_CBOR => {
+            let value: ciborium::Value = ciborium::de::from_reader(payload)
+                .map_err(|e| StoreError::InvalidInput(format!("cbor decode error: {e}")))?;
+            cbor_to_msgpack(value)?
+        }
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unsupported payload encoding {other}"
+            )))
+        }
+    };
 
     let map = normalize_tags(&value)?;
     let mut data = Map::new();
@@ -91,6 +121,49 @@ pub fn project_msgpack(
     })
 }
 
+/// Converts a CBOR value to msgpack's model. Semantic tags are dropped and
+/// their content kept; integers outside the u64/i64 range are rejected.
+fn cbor_to_msgpack(value: ciborium::Value) -> Result<Value> {
+    use ciborium::Value as Cbor;
+    Ok(match value {
+        Cbor::Integer(int) => {
+            let int = i128::from(int);
+            if let Ok(u) = u64::try_from(int) {
+                Value::from(u)
+            } else if let Ok(i) = i64::try_from(int) {
+                Value::from(i)
+            } else {
+                return Err(StoreError::InvalidInput(format!(
+                    "cbor integer {int} out of range"
+                )));
+            }
+        }
+        Cbor::Bytes(bytes) => Value::Binary(bytes),
+        Cbor::Float(f) => Value::F64(f),
+        Cbor::Text(text) => Value::String(text.into()),
+        Cbor::Bool(b) => Value::Boolean(b),
+        Cbor::Null => Value::Nil,
+        Cbor::Tag(_, inner) => cbor_to_msgpack(*inner)?,
+        Cbor::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(cbor_to_msgpack)
+                .collect::<Result<_>>()?,
+        ),
+        Cbor::Map(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| Ok((cbor_to_msgpack(k)?, cbor_to_msgpack(v)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unsupported cbor value {other:?}"
+            )))
+        }
+    })
+}
+
 fn normalize_tags(value: &Value) -> Result<HashMap<u64, Value>> {
     let mut out = HashMap::new();
     let map = match value {
@@ -3,16 +3,161 @@
 
 //! Binary protocol framing and message helpers.
 
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
+use std::ops::RangeInclusive;
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::error::{Result, StoreError};
+use crate::fulltext::TextSearch;
+use crate::links::{LinkDirection, LinkKind, TurnLink, MAX_LINKS, MAX_LINK_KIND_LEN};
+use crate::search::{SearchMatch, TurnSearch};
+use crate::store::{ContextStats, DeleteFilter};
+use crate::turn_store::ContextHead;
+use crate::writers::{TurnWriter, MAX_WRITER_ID_LEN};
 
 /// Maximum frame payload size (64 MB). Frames larger than this are rejected
 /// to prevent memory exhaustion from malicious or corrupted clients.
 const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
 
+/// Frame flag: the payload is followed by a 4-byte CRC32C trailer. A client
+/// requests checksums by setting it on HELLO; echoing it on the HELLO
+/// response enables the trailer on every later frame in both directions.
+pub const FLAG_CRC32C: u16 = 1 << 15;
+
+/// Frame flag: the request payload starts with a metadata block of string
+/// key/value pairs (request ids, tenant hints). Negotiated on HELLO like
+/// [`FLAG_CRC32C`]; see [`split_frame_metadata`].
+pub const FLAG_METADATA: u16 = 1 << 14;
+
+/// Frame flag, HELLO only: the server answers SEARCH_TURNS. Requested by
+/// the client and echoed like [`FLAG_CRC32C`].
+pub const FLAG_SEARCH: u16 = 1 << 13;
+
+/// Frame flag, HELLO only: append acks on this connection carry the append
+/// metadata (see [`encode_append_ack_meta`]). Requested by the client and
+/// echoed like [`FLAG_CRC32C`].
+pub const FLAG_APPEND_META: u16 = 1 << 12;
+
+/// Frame flag, HELLO only: the server honours [`APPEND_FLAG_DEDUP`].
+/// Requested by the client and echoed like [`FLAG_CRC32C`].
+pub const FLAG_DEDUP: u16 = 1 << 11;
+
+/// Frame flag, HELLO only: GET_LAST, GET_TURN and SEARCH_TURNS responses on
+/// this connection end with every item's creation time (see the
+/// `timestamps` trailer in the protocol README). Requested by the client and
+/// echoed like [`FLAG_CRC32C`].
+pub const FLAG_TIMESTAMPS: u16 = 1 << 10;
+
+/// Frame flag, HELLO only: GET_LAST, GET_TURN, GET_CHILDREN and
+/// SEARCH_TURNS responses on this connection end with the redactions of
+/// their items (see the `redactions` trailer in the protocol README), after
+/// a timestamps trailer that is then always present. Requested by the
+/// client and echoed like [`FLAG_CRC32C`].
+pub const FLAG_REDACTIONS: u16 = 1 << 9;
+
+/// Frame flag, HELLO only: the server honours [`GET_LAST_DEPTH_RANGE`].
+/// Requested by the client and echoed like [`FLAG_CRC32C`].
+pub const FLAG_DEPTH_FILTER: u16 = 1 << 8;
+
+/// Frame flag, HELLO only: the server honours [`GET_LAST_PROJECTION`].
+/// Requested by the client and echoed like [`FLAG_CRC32C`].
+pub const FLAG_PROJECTION: u16 = 1 << 7;
+
+/// Frame flag: the payload is compressed with the codec negotiated on
+/// HELLO. A client requests compression by setting it on HELLO and listing
+/// its codecs (see [`parse_hello`]); echoing it on the HELLO response, with
+/// the chosen codec, lets either side compress any later frame. See
+/// [`write_frame_negotiated`] and [`decompress_frame`].
+pub const FLAG_COMPRESSED: u16 = 1 << 6;
+
+/// Frame flag, HELLO only: GET_LAST, GET_TURN, GET_CHILDREN, GET_LINKED
+/// and SEARCH_TURNS responses on this connection end with the links of
+/// their items (see the `links` trailer in the protocol README), after a
+/// redactions trailer that is then always present. Requested by the client
+/// and echoed like [`FLAG_CRC32C`].
+pub const FLAG_LINKS: u16 = 1 << 5;
+
+/// Frame compression codec: zstd. Ids match the turn payload
+/// `compression` field.
+pub const CODEC_ZSTD: u8 = 1;
+
+/// Codecs this server compresses frames with.
+pub const SERVED_CODECS: &[u8] = &[CODEC_ZSTD];
+
+/// Payloads shorter than this go out plain even on compressed connections;
+/// the codec's framing would eat most of the saving.
+const COMPRESS_MIN_BYTES: usize = 512;
+
+/// APPEND_TURN flag: the request carries a 32-byte fs_root_hash.
+pub const APPEND_FLAG_FS_ROOT: u16 = 1 << 0;
+
+/// APPEND_TURN flag: the request carries a writer stamp
+/// (`writer_id_len u32`, `writer_id`, `writer_seq u64`) after the optional
+/// fs_root_hash.
+pub const APPEND_FLAG_WRITER: u16 = 1 << 1;
+
+/// APPEND_TURN flag: the request carries a time to live (`ttl_ms u64`,
+/// non-zero) after the optional writer stamp.
+pub const APPEND_FLAG_TTL: u16 = 1 << 2;
+
+/// APPEND_TURN flag: append only if no live turn in the context's history
+/// already has `content_hash`. The ack describes the turn appended or the
+/// newest match and ends with `appended u8`. Carries no request bytes.
+pub const APPEND_FLAG_DEDUP: u16 = 1 << 3;
+
+/// APPEND_TURN flag: the request carries links to earlier turns in the
+/// new turn's history after the optional ttl: `link_count u32`, then per
+/// link `target_turn_id u64`, `kind u8` (1 = replies_to, 2 = causes,
+/// 3 = custom), `name_len u32` and the custom kind's `name` (UTF-8, empty
+/// unless custom).
+pub const APPEND_FLAG_LINKS: u16 = 1 << 4;
+
+/// GET_LAST request flag: include turns hidden by a compaction.
+pub const GET_LAST_INCLUDE_COMPACTED: u32 = 1 << 0;
+
+/// GET_LAST request flag: include expired turns, marked in the expiry
+/// trailer, instead of skipping them.
+pub const GET_LAST_INCLUDE_EXPIRED: u32 = 1 << 1;
+
+/// GET_LAST request flag: the request carries `before_turn_id u64` after
+/// the flags and pages back from there instead of from the head.
+pub const GET_LAST_BEFORE: u32 = 1 << 2;
+
+/// GET_LAST request flag: the request carries `min_depth u32` and
+/// `max_depth u32` after the optional `before_turn_id`; only turns at those
+/// depths are returned, and `limit` counts only them.
+pub const GET_LAST_DEPTH_RANGE: u32 = 1 << 3;
+
+/// GET_LAST request flag: the request carries `fields u32` (a set of
+/// `TURN_FIELD_*` bits) after the optional depth range; response items
+/// carry `turn_id` and only those fields.
+pub const GET_LAST_PROJECTION: u32 = 1 << 4;
+
+/// Projected GET_LAST field: `parent_turn_id`.
+pub const TURN_FIELD_PARENT: u32 = 1 << 0;
+/// Projected GET_LAST field: `depth`.
+pub const TURN_FIELD_DEPTH: u32 = 1 << 1;
+/// Projected GET_LAST fields: `declared_type_id` and `declared_type_version`.
+pub const TURN_FIELD_TYPE: u32 = 1 << 2;
+/// Projected GET_LAST fields: `encoding` and `compression`.
+pub const TURN_FIELD_ENCODING: u32 = 1 << 3;
+/// Projected GET_LAST field: `uncompressed_len`.
+pub const TURN_FIELD_SIZE: u32 = 1 << 4;
+/// Projected GET_LAST field: `content_hash_b3_256`.
+pub const TURN_FIELD_CONTENT_HASH: u32 = 1 << 5;
+/// Every GET_LAST item field; the layout of unprojected responses.
+pub const TURN_FIELDS_ALL: u32 = TURN_FIELD_PARENT
+    | TURN_FIELD_DEPTH
+    | TURN_FIELD_TYPE
+    | TURN_FIELD_ENCODING
+    | TURN_FIELD_SIZE
+    | TURN_FIELD_CONTENT_HASH;
+
+/// GET_LAST `payload_len` sentinel for a payload withheld by `max_payload_bytes`.
+pub const PAYLOAD_OMITTED: u32 = u32::MAX;
+
 #[repr(u16)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MsgType {
@@ -27,9 +172,63 @@ pub enum MsgType {
     GetBlob = 9,
     AttachFs = 10,
     PutBlob = 11,
+    CtxCreateAlias = 12,
+    ResolveAlias = 13,
+    CtxCompact = 14,
+    GetTurn = 15,
+    SearchTurns = 17,
+    GetChildren = 18,
+    CtxPrune = 19,
+    TurnRedact = 20,
+    TextSearch = 21,
+    TypeHistogram = 22,
+    AppendMulti = 23,
+    ContextStats = 24,
+    GetLinked = 25,
+    CtxArchive = 26,
+    CtxRestore = 27,
+    ListContexts = 28,
+    CtxDeleteMany = 29,
+    CtxCreateWithTurn = 30,
+    GetContext = 31,
+    ListChildren = 32,
     Error = 255,
 }
 
+/// Message types this server handles, advertised in the HELLO response so
+/// clients can fail fast instead of sending a request it would reject.
+pub const SERVED_MESSAGE_TYPES: &[MsgType] = &[
+    MsgType::Hello,
+    MsgType::CtxCreate,
+    MsgType::CtxFork,
+    MsgType::GetHead,
+    MsgType::AppendTurn,
+    MsgType::GetLast,
+    MsgType::GetBlob,
+    MsgType::AttachFs,
+    MsgType::PutBlob,
+    MsgType::CtxCreateAlias,
+    MsgType::ResolveAlias,
+    MsgType::CtxCompact,
+    MsgType::GetTurn,
+    MsgType::SearchTurns,
+    MsgType::GetChildren,
+    MsgType::CtxPrune,
+    MsgType::TurnRedact,
+    MsgType::TextSearch,
+    MsgType::TypeHistogram,
+    MsgType::AppendMulti,
+    MsgType::ContextStats,
+    MsgType::GetLinked,
+    MsgType::CtxArchive,
+    MsgType::CtxRestore,
+    MsgType::ListContexts,
+    MsgType::CtxDeleteMany,
+    MsgType::CtxCreateWithTurn,
+    MsgType::GetContext,
+    MsgType::ListChildren,
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FrameHeader {
     pub len: u32,
@@ -53,6 +252,15 @@ pub struct AppendTurnRequest {
     /// Optional filesystem snapshot root hash to attach to this turn.
     /// Present if flags bit 0 is set.
     pub fs_root_hash: Option<[u8; 32]>,
+    /// Optional writer stamp. Present if flags bit 1 is set.
+    pub writer: Option<TurnWriter>,
+    /// Optional time to live in milliseconds. Present if flags bit 2 is set.
+    pub ttl_ms: Option<u64>,
+    /// Skip the append when the content is already in the context (flags
+    /// bit 3).
+    pub dedup: bool,
+    /// Links to earlier turns. Present if flags bit 4 is set.
+    pub links: Vec<TurnLink>,
 }
 
 /// Request to attach a filesystem snapshot to an existing turn.
@@ -69,11 +277,130 @@ pub struct PutBlobRequest {
     pub data: Vec<u8>,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Request to create the context named by `alias`, or return the existing one.
+#[derive(Debug, Clone)]
+pub struct CtxCreateAliasRequest {
+    pub base_turn_id: u64,
+    pub alias: String,
+}
+
+#[derive(Debug, Clone)]
 pub struct GetLastRequest {
     pub context_id: u64,
     pub limit: u32,
     pub include_payload: u32,
+    /// Payloads larger than this are withheld and sent as `PAYLOAD_OMITTED`.
+    pub max_payload_bytes: Option<u32>,
+    /// Walk past compaction boundaries into the raw history.
+    pub include_compacted: bool,
+    /// Return expired turns instead of skipping them.
+    pub include_expired: bool,
+    /// Return only turns older than this one (0 = from the head).
+    pub before_turn_id: u64,
+    /// Return only turns at these depths.
+    pub depths: RangeInclusive<u32>,
+    /// `TURN_FIELD_*` bits of the item fields to return.
+    pub fields: u32,
+}
+
+/// Request to append a summary turn that compacts history up to
+/// `up_to_turn_id`.
+#[derive(Debug, Clone)]
+pub struct CtxCompactRequest {
+    pub up_to_turn_id: u64,
+    pub summary: AppendTurnRequest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CtxCreateRequest {
+    pub base_turn_id: u64,
+    /// Context the new one derives from, if the client named one.
+    pub parent_context_id: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CtxCreateWithTurnRequest {
+    pub base_turn_id: u64,
+    /// The first turn; its `context_id` is ignored.
+    pub first: AppendTurnRequest,
+}
+
+/// SEARCH_TURNS `match` values.
+pub const SEARCH_MATCH_EQUALS: u32 = 0;
+pub const SEARCH_MATCH_CONTAINS: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct SearchTurnsRequest {
+    pub context_id: u64,
+    pub limit: u32,
+    pub search: TurnSearch,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextSearchRequest {
+    pub search: TextSearch,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GetChildrenRequest {
+    pub context_id: u64,
+    pub turn_id: u64,
+    pub include_payload: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GetLinkedRequest {
+    pub context_id: u64,
+    pub turn_id: u64,
+    pub direction: LinkDirection,
+    pub include_payload: bool,
+}
+
+/// LIST_CONTEXTS `filter`: every context, only active ones, or only
+/// archived ones.
+pub const LIST_CONTEXTS_ALL: u32 = 0;
+pub const LIST_CONTEXTS_ACTIVE: u32 = 1;
+pub const LIST_CONTEXTS_ARCHIVED: u32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ListContextsRequest {
+    pub limit: u32,
+    /// `None` lists every context, `Some(archived)` only those in that state.
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CtxDeleteManyRequest {
+    pub filter: DeleteFilter,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CtxPruneRequest {
+    pub context_id: u64,
+    pub keep_from_depth: u64,
+}
+
+/// A TYPE_HISTOGRAM request; the scope fields read like [`GetLastRequest`]'s.
+#[derive(Debug, Clone)]
+pub struct TypeHistogramRequest {
+    pub context_id: u64,
+    pub include_compacted: bool,
+    pub include_expired: bool,
+    pub before_turn_id: u64,
+    pub depths: RangeInclusive<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnRedactRequest {
+    pub context_id: u64,
+    pub turn_id: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GetTurnRequest {
+    pub turn_id: u64,
+    pub include_payload: u32,
 }
 
 pub fn read_frame<R: Read>(reader: &mut R) -> Result<(FrameHeader, Vec<u8>)> {
@@ -121,25 +448,564 @@ pub fn write_frame<W: Write>(
     Ok(())
 }
 
-pub fn parse_ctx_create(payload: &[u8]) -> Result<u64> {
+/// Writes a frame with a CRC32C trailer (negotiated connections only).
+pub fn write_frame_with_checksum<W: Write>(
+    writer: &mut W,
+    msg_type: u16,
+    flags: u16,
+    req_id: u64,
+    payload: &[u8],
+) -> Result<()> {
+    let header = FrameHeader {
+        len: payload.len() as u32 + 4,
+        msg_type,
+        flags: flags | FLAG_CRC32C,
+        req_id,
+    };
+    let checksum = frame_checksum(&header, payload);
+    write_frame(
+        writer,
+        msg_type,
+        header.flags,
+        req_id,
+        &[payload, &checksum.to_le_bytes()].concat(),
+    )
+}
+
+/// Writes a frame on a connection with negotiated options: compressed with
+/// `codec` when the payload is large enough to gain from it, then
+/// checksummed when `checksums` is set.
+pub fn write_frame_negotiated<W: Write>(
+    writer: &mut W,
+    msg_type: u16,
+    flags: u16,
+    req_id: u64,
+    payload: &[u8],
+    checksums: bool,
+    codec: Option<u8>,
+) -> Result<()> {
+    let compressed = match codec {
+        Some(CODEC_ZSTD) if payload.len() >= COMPRESS_MIN_BYTES => zstd::bulk::compress(payload, 1)
+            .ok()
+            .filter(|compressed| compressed.len() < payload.len()),
+        _ => None,
+    };
+    let (flags, payload) = match &compressed {
+        Some(compressed) => (flags | FLAG_COMPRESSED, &compressed[..]),
+        None => (flags, payload),
+    };
+    if checksums {
+        write_frame_with_checksum(writer, msg_type, flags, req_id, payload)
+    } else {
+        write_frame(writer, msg_type, flags, req_id, payload)
+    }
+}
+
+/// Decompresses a frame flagged with [`FLAG_COMPRESSED`] on a connection
+/// that negotiated `codec`. The decompressed payload is held to the same
+/// limit as a frame read off the wire.
+pub fn decompress_frame(
+    header: FrameHeader,
+    payload: Vec<u8>,
+    codec: u8,
+) -> Result<(FrameHeader, Vec<u8>)> {
+    if header.flags & FLAG_COMPRESSED == 0 {
+        return Ok((header, payload));
+    }
+    let corrupt = |detail: String| {
+        StoreError::Corrupt(format!(
+            "frame decompression failed (msg_type {}, req_id {}): {detail}",
+            header.msg_type, header.req_id
+        ))
+    };
+    let payload = match codec {
+        CODEC_ZSTD => {
+            let len = zstd::zstd_safe::get_frame_content_size(&payload)
+                .ok()
+                .flatten()
+                .ok_or_else(|| corrupt("no content size".into()))?;
+            if len > MAX_FRAME_SIZE as u64 {
+                return Err(StoreError::InvalidInput(format!(
+                    "frame size {len} exceeds maximum {MAX_FRAME_SIZE}"
+                )));
+            }
+            zstd::bulk::decompress(&payload, len as usize)
+                .map_err(|err| corrupt(err.to_string()))?
+        }
+        other => return Err(corrupt(format!("unknown codec {other}"))),
+    };
+    Ok((
+        FrameHeader {
+            len: payload.len() as u32,
+            flags: header.flags & !FLAG_COMPRESSED,
+            ..header
+        },
+        payload,
+    ))
+}
+
+/// Verifies and strips the CRC32C trailer of a frame read on a negotiated
+/// connection. Callers must drop the connection on error rather than try to
+/// resynchronize the stream.
+pub fn verify_frame_checksum(
+    header: FrameHeader,
+    mut payload: Vec<u8>,
+) -> Result<(FrameHeader, Vec<u8>)> {
+    let corrupt = || {
+        StoreError::Corrupt(format!(
+            "frame checksum mismatch (msg_type {}, req_id {})",
+            header.msg_type, header.req_id
+        ))
+    };
+    if header.flags & FLAG_CRC32C == 0 || payload.len() < 4 {
+        return Err(corrupt());
+    }
+    let body_len = payload.len() - 4;
+    let mut trailer = [0u8; 4];
+    trailer.copy_from_slice(&payload[body_len..]);
+    if frame_checksum(&header, &payload[..body_len]) != u32::from_le_bytes(trailer) {
+        return Err(corrupt());
+    }
+    payload.truncate(body_len);
+    Ok((
+        FrameHeader {
+            len: body_len as u32,
+            flags: header.flags & !FLAG_CRC32C,
+            ..header
+        },
+        payload,
+    ))
+}
+
+/// Request metadata key/value pairs, in wire order.
+pub type FrameMetadata = Vec<(String, String)>;
+
+/// Metadata key of a per-request bearer token, which replaces the
+/// connection's HELLO token for that request only.
+pub const METADATA_AUTH_KEY: &str = "authorization";
+
+/// The per-request bearer token in `metadata`, if any.
+pub fn metadata_auth(metadata: &FrameMetadata) -> Option<&str> {
+    metadata
+        .iter()
+        .find(|(key, _)| key == METADATA_AUTH_KEY)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Strips the metadata block from a request flagged with [`FLAG_METADATA`],
+/// returning the pairs and the header/payload of the request proper.
+///
+/// Block format: count (u16), then per entry key_len (u16), key, value_len
+/// (u16), value, all UTF-8.
+pub fn split_frame_metadata(
+    header: FrameHeader,
+    payload: Vec<u8>,
+) -> Result<(FrameHeader, FrameMetadata, Vec<u8>)> {
+    fn read_field(cursor: &mut std::io::Cursor<&[u8]>) -> Result<String> {
+        let len = cursor.read_u16::<LittleEndian>()? as usize;
+        let mut field = vec![0u8; len];
+        cursor.read_exact(&mut field)?;
+        String::from_utf8(field).map_err(|_| StoreError::InvalidInput("metadata not utf8".into()))
+    }
+
+    let mut cursor = std::io::Cursor::new(payload.as_slice());
+    let count = cursor.read_u16::<LittleEndian>()?;
+    let mut metadata = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let key = read_field(&mut cursor)?;
+        let value = read_field(&mut cursor)?;
+        metadata.push((key, value));
+    }
+    let body = payload[cursor.position() as usize..].to_vec();
+    Ok((
+        FrameHeader {
+            len: body.len() as u32,
+            flags: header.flags & !FLAG_METADATA,
+            ..header
+        },
+        metadata,
+        body,
+    ))
+}
+
+/// CRC32C over the 16-byte header (with `len` counting the trailer) followed
+/// by the payload.
+fn frame_checksum(header: &FrameHeader, payload: &[u8]) -> u32 {
+    let mut encoded = [0u8; 16];
+    encoded[0..4].copy_from_slice(&header.len.to_le_bytes());
+    encoded[4..6].copy_from_slice(&header.msg_type.to_le_bytes());
+    encoded[6..8].copy_from_slice(&header.flags.to_le_bytes());
+    encoded[8..16].copy_from_slice(&header.req_id.to_le_bytes());
+    crc32c::crc32c_append(crc32c::crc32c(&encoded), payload)
+}
+
+/// Parse CTX_CREATE request: base_turn_id (u64), optionally followed by
+/// parent_context_id (u64, 0 = none)
+pub fn parse_ctx_create(payload: &[u8]) -> Result<CtxCreateRequest> {
     let mut cursor = std::io::Cursor::new(payload);
-    Ok(cursor.read_u64::<LittleEndian>()?)
+    let base_turn_id = cursor.read_u64::<LittleEndian>()?;
+    let parent_context_id = if payload.len() > 8 {
+        Some(cursor.read_u64::<LittleEndian>()?).filter(|id| *id != 0)
+    } else {
+        None
+    };
+    Ok(CtxCreateRequest {
+        base_turn_id,
+        parent_context_id,
+    })
 }
 
 pub fn parse_ctx_fork(payload: &[u8]) -> Result<u64> {
-    parse_ctx_create(payload)
+    parse_get_head(payload)
 }
 
 pub fn parse_get_head(payload: &[u8]) -> Result<u64> {
-    parse_ctx_create(payload)
+    let mut cursor = std::io::Cursor::new(payload);
+    Ok(cursor.read_u64::<LittleEndian>()?)
+}
+
+/// Parse CTX_CREATE_ALIAS request: base_turn_id (u64) + alias_len (u32) + alias
+pub fn parse_ctx_create_alias(payload: &[u8]) -> Result<CtxCreateAliasRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let base_turn_id = cursor.read_u64::<LittleEndian>()?;
+    let alias = read_alias(&mut cursor)?;
+    Ok(CtxCreateAliasRequest {
+        base_turn_id,
+        alias,
+    })
+}
+
+/// Parse RESOLVE_ALIAS request: alias_len (u32) + alias
+pub fn parse_resolve_alias(payload: &[u8]) -> Result<String> {
+    read_alias(&mut std::io::Cursor::new(payload))
+}
+
+fn read_alias(cursor: &mut std::io::Cursor<&[u8]>) -> Result<String> {
+    let alias_len = cursor.read_u32::<LittleEndian>()? as usize;
+    if alias_len > crate::aliases::MAX_ALIAS_LEN {
+        return Err(StoreError::InvalidInput(format!(
+            "alias longer than {} bytes",
+            crate::aliases::MAX_ALIAS_LEN
+        )));
+    }
+    let mut alias = vec![0u8; alias_len];
+    cursor.read_exact(&mut alias)?;
+    String::from_utf8(alias).map_err(|_| StoreError::InvalidInput("alias not utf8".into()))
 }
 
 pub fn parse_get_last(payload: &[u8]) -> Result<GetLastRequest> {
     let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let limit = cursor.read_u32::<LittleEndian>()?;
+    let include_payload = cursor.read_u32::<LittleEndian>()?;
+    // Optional trailer: min_sequence (u64) and wait_ms (u32), then
+    // max_payload_bytes (u32), then flags (u32), then before_turn_id (u64)
+    // with GET_LAST_BEFORE, then min_depth and max_depth (u32 each) with
+    // GET_LAST_DEPTH_RANGE, then fields (u32) with GET_LAST_PROJECTION. A
+    // single node has applied every write it acknowledged, so the
+    // read-your-writes fields need no waiting here.
+    let max_payload_bytes = if payload.len() >= 32 {
+        cursor.set_position(28);
+        Some(cursor.read_u32::<LittleEndian>()?)
+    } else {
+        None
+    };
+    let flags = if payload.len() >= 36 {
+        cursor.read_u32::<LittleEndian>()?
+    } else {
+        0
+    };
+    let (before_turn_id, depths) = read_scope_trailer(&mut cursor, flags)?;
+    let fields = if flags & GET_LAST_PROJECTION != 0 {
+        cursor.read_u32::<LittleEndian>()? & TURN_FIELDS_ALL
+    } else {
+        TURN_FIELDS_ALL
+    };
     Ok(GetLastRequest {
-        context_id: cursor.read_u64::<LittleEndian>()?,
-        limit: cursor.read_u32::<LittleEndian>()?,
-        include_payload: cursor.read_u32::<LittleEndian>()?,
+        context_id,
+        limit,
+        include_payload,
+        max_payload_bytes,
+        include_compacted: flags & GET_LAST_INCLUDE_COMPACTED != 0,
+        include_expired: flags & GET_LAST_INCLUDE_EXPIRED != 0,
+        before_turn_id,
+        depths,
+        fields,
+    })
+}
+
+/// Parse TYPE_HISTOGRAM request: context_id (u64) and flags (u32), both
+/// bits and trailer as in GET_LAST.
+pub fn parse_type_histogram(payload: &[u8]) -> Result<TypeHistogramRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let flags = cursor.read_u32::<LittleEndian>()?;
+    let (before_turn_id, depths) = read_scope_trailer(&mut cursor, flags)?;
+    Ok(TypeHistogramRequest {
+        context_id,
+        include_compacted: flags & GET_LAST_INCLUDE_COMPACTED != 0,
+        include_expired: flags & GET_LAST_INCLUDE_EXPIRED != 0,
+        before_turn_id,
+        depths,
+    })
+}
+
+/// Reads the before_turn_id (with [`GET_LAST_BEFORE`]) and depth range
+/// (with [`GET_LAST_DEPTH_RANGE`]) that follow GET_LAST-style flags.
+fn read_scope_trailer(
+    cursor: &mut std::io::Cursor<&[u8]>,
+    flags: u32,
+) -> Result<(u64, RangeInclusive<u32>)> {
+    let before_turn_id = if flags & GET_LAST_BEFORE != 0 {
+        cursor.read_u64::<LittleEndian>()?
+    } else {
+        0
+    };
+    let depths = if flags & GET_LAST_DEPTH_RANGE != 0 {
+        let min_depth = cursor.read_u32::<LittleEndian>()?;
+        let max_depth = cursor.read_u32::<LittleEndian>()?;
+        min_depth..=max_depth
+    } else {
+        0..=u32::MAX
+    };
+    Ok((before_turn_id, depths))
+}
+
+/// Parse SEARCH_TURNS request: context_id (u64), limit (u32), match (u32),
+/// then length-prefixed (u32) type_id (empty = any type), a path (segment
+/// count u32, each segment length-prefixed) and the operand: a msgpack
+/// value for equals, UTF-8 text for contains.
+pub fn parse_search_turns(payload: &[u8]) -> Result<SearchTurnsRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let limit = cursor.read_u32::<LittleEndian>()?;
+    let mode = cursor.read_u32::<LittleEndian>()?;
+    let utf8 = |bytes: &[u8], field: &str| {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| StoreError::InvalidInput(format!("{field} not utf8")))
+    };
+    let type_id = utf8(read_len_prefixed(&mut cursor)?, "type_id")?;
+    let segments = cursor.read_u32::<LittleEndian>()?;
+    let mut path = Vec::new();
+    for _ in 0..segments {
+        path.push(utf8(read_len_prefixed(&mut cursor)?, "path segment")?);
+    }
+    let operand = read_len_prefixed(&mut cursor)?;
+    let matcher = match mode {
+        SEARCH_MATCH_EQUALS => {
+            let value = rmpv::decode::read_value(&mut &operand[..])
+                .map_err(|_| StoreError::InvalidInput("search value not msgpack".into()))?;
+            SearchMatch::Equals(value)
+        }
+        SEARCH_MATCH_CONTAINS => SearchMatch::Contains(utf8(operand, "search text")?),
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unknown search match {other}"
+            )))
+        }
+    };
+    Ok(SearchTurnsRequest {
+        context_id,
+        limit,
+        search: TurnSearch {
+            type_id: (!type_id.is_empty()).then_some(type_id),
+            path,
+            matcher,
+        },
+    })
+}
+
+/// Parse TEXT_SEARCH request: context_id (u64, 0 = every context), limit
+/// (u32), before_turn_id (u64, 0 = from the newest), then length-prefixed
+/// (u32) type_id (empty = any type) and UTF-8 search text.
+pub fn parse_text_search(payload: &[u8]) -> Result<TextSearchRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let limit = cursor.read_u32::<LittleEndian>()?;
+    let before_turn_id = cursor.read_u64::<LittleEndian>()?;
+    let utf8 = |bytes: &[u8], field: &str| {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| StoreError::InvalidInput(format!("{field} not utf8")))
+    };
+    let type_id = utf8(read_len_prefixed(&mut cursor)?, "type_id")?;
+    let text = utf8(read_len_prefixed(&mut cursor)?, "search text")?;
+    Ok(TextSearchRequest {
+        search: TextSearch {
+            text,
+            context_id: (context_id != 0).then_some(context_id),
+            type_id: (!type_id.is_empty()).then_some(type_id),
+            before_turn_id,
+            limit,
+        },
+    })
+}
+
+/// Reads a u32 length and that many bytes, borrowed from the payload.
+fn read_len_prefixed<'a>(cursor: &mut std::io::Cursor<&'a [u8]>) -> Result<&'a [u8]> {
+    let len = cursor.read_u32::<LittleEndian>()? as usize;
+    let start = cursor.position() as usize;
+    let bytes = cursor
+        .get_ref()
+        .get(start..start.saturating_add(len))
+        .ok_or_else(|| StoreError::InvalidInput("field overruns payload".into()))?;
+    cursor.set_position((start + len) as u64);
+    Ok(bytes)
+}
+
+/// Parse CTX_COMPACT request: up_to_turn_id (u64) followed by an APPEND_TURN
+/// request body for the summary turn.
+pub fn parse_ctx_compact(payload: &[u8], flags: u16) -> Result<CtxCompactRequest> {
+    if payload.len() < 8 {
+        return Err(StoreError::InvalidInput(
+            "ctx_compact payload too short".into(),
+        ));
+    }
+    let up_to_turn_id = u64::from_le_bytes(payload[..8].try_into().unwrap());
+    let summary = parse_append_turn(&payload[8..], flags)?;
+    Ok(CtxCompactRequest {
+        up_to_turn_id,
+        summary,
+    })
+}
+
+/// Parse CTX_CREATE_WITH_TURN request: base_turn_id (u64) followed by an
+/// APPEND_TURN request body, whose flags are the frame's
+pub fn parse_ctx_create_with_turn(payload: &[u8], flags: u16) -> Result<CtxCreateWithTurnRequest> {
+    if payload.len() < 8 {
+        return Err(StoreError::InvalidInput(
+            "ctx_create_with_turn payload too short".into(),
+        ));
+    }
+    let base_turn_id = u64::from_le_bytes(payload[..8].try_into().unwrap());
+    let first = parse_append_turn(&payload[8..], flags)?;
+    Ok(CtxCreateWithTurnRequest {
+        base_turn_id,
+        first,
+    })
+}
+
+/// Parse GET_TURN request: turn_id (u64) + include_payload (u32)
+pub fn parse_get_turn(payload: &[u8]) -> Result<GetTurnRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let turn_id = cursor.read_u64::<LittleEndian>()?;
+    let include_payload = cursor.read_u32::<LittleEndian>()?;
+    Ok(GetTurnRequest {
+        turn_id,
+        include_payload,
+    })
+}
+
+/// Parse GET_CHILDREN request: context_id (u64) + turn_id (u64) +
+/// include_payload (u32)
+pub fn parse_get_children(payload: &[u8]) -> Result<GetChildrenRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let turn_id = cursor.read_u64::<LittleEndian>()?;
+    let include_payload = cursor.read_u32::<LittleEndian>()?;
+    Ok(GetChildrenRequest {
+        context_id,
+        turn_id,
+        include_payload,
+    })
+}
+
+/// Parse GET_LINKED request: context_id (u64) + turn_id (u64) +
+/// direction (u32: 0 = outgoing, 1 = incoming) + include_payload (u32)
+pub fn parse_get_linked(payload: &[u8]) -> Result<GetLinkedRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let turn_id = cursor.read_u64::<LittleEndian>()?;
+    let direction = match cursor.read_u32::<LittleEndian>()? {
+        0 => LinkDirection::Outgoing,
+        1 => LinkDirection::Incoming,
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unknown link direction {other}"
+            )))
+        }
+    };
+    let include_payload = cursor.read_u32::<LittleEndian>()? != 0;
+    Ok(GetLinkedRequest {
+        context_id,
+        turn_id,
+        direction,
+        include_payload,
+    })
+}
+
+/// Parse CTX_PRUNE request: context_id (u64) + keep_from_depth (u64)
+pub fn parse_ctx_prune(payload: &[u8]) -> Result<CtxPruneRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let keep_from_depth = cursor.read_u64::<LittleEndian>()?;
+    Ok(CtxPruneRequest {
+        context_id,
+        keep_from_depth,
+    })
+}
+
+/// Parse LIST_CONTEXTS request: limit (u32) + filter (u32, see
+/// [`LIST_CONTEXTS_ALL`])
+pub fn parse_list_contexts(payload: &[u8]) -> Result<ListContextsRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let limit = cursor.read_u32::<LittleEndian>()?;
+    let archived = match cursor.read_u32::<LittleEndian>()? {
+        LIST_CONTEXTS_ALL => None,
+        LIST_CONTEXTS_ACTIVE => Some(false),
+        LIST_CONTEXTS_ARCHIVED => Some(true),
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unknown list contexts filter {other}"
+            )))
+        }
+    };
+    Ok(ListContextsRequest { limit, archived })
+}
+
+/// Parse CTX_DELETE_MANY request: id count (u32) + context ids (u64 each),
+/// pair count (u32) + length-prefixed (u32) UTF-8 field and value per
+/// pair, then has_before (u8) + created_before_unix_ms (u64)
+pub fn parse_ctx_delete_many(payload: &[u8]) -> Result<CtxDeleteManyRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let utf8 = |bytes: &[u8], field: &str| {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| StoreError::InvalidInput(format!("{field} not utf8")))
+    };
+    let id_count = cursor.read_u32::<LittleEndian>()?;
+    let mut context_ids = Vec::new();
+    for _ in 0..id_count {
+        context_ids.push(cursor.read_u64::<LittleEndian>()?);
+    }
+    let pair_count = cursor.read_u32::<LittleEndian>()?;
+    let mut metadata = Vec::new();
+    for _ in 0..pair_count {
+        let field = utf8(read_len_prefixed(&mut cursor)?, "metadata field")?;
+        let value = utf8(read_len_prefixed(&mut cursor)?, "metadata value")?;
+        metadata.push((field, value));
+    }
+    let has_before = cursor.read_u8()?;
+    let before = cursor.read_u64::<LittleEndian>()?;
+    Ok(CtxDeleteManyRequest {
+        filter: DeleteFilter {
+            context_ids,
+            metadata,
+            created_before_unix_ms: (has_before != 0).then_some(before),
+        },
+    })
+}
+
+/// Parse TURN_REDACT request: context_id (u64) + turn_id (u64) +
+/// length-prefixed (u32) UTF-8 reason
+pub fn parse_turn_redact(payload: &[u8]) -> Result<TurnRedactRequest> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let context_id = cursor.read_u64::<LittleEndian>()?;
+    let turn_id = cursor.read_u64::<LittleEndian>()?;
+    let reason = String::from_utf8(read_len_prefixed(&mut cursor)?.to_vec())
+        .map_err(|_| StoreError::InvalidInput("reason not utf8".into()))?;
+    Ok(TurnRedactRequest {
+        context_id,
+        turn_id,
+        reason,
     })
 }
 
@@ -181,7 +1047,7 @@ pub fn parse_append_turn(payload: &[u8], flags: u16) -> Result<AppendTurnRequest
     }
 
     // Check for optional fs_root_hash (flags bit 0)
-    let fs_root_hash = if flags & 1 != 0 {
+    let fs_root_hash = if flags & APPEND_FLAG_FS_ROOT != 0 {
         let mut hash = [0u8; 32];
         cursor.read_exact(&mut hash)?;
         Some(hash)
@@ -189,6 +1055,67 @@ pub fn parse_append_turn(payload: &[u8], flags: u16) -> Result<AppendTurnRequest
         None
     };
 
+    let writer = if flags & APPEND_FLAG_WRITER != 0 {
+        let writer_id_len = cursor.read_u32::<LittleEndian>()? as usize;
+        if writer_id_len > MAX_WRITER_ID_LEN {
+            return Err(StoreError::InvalidInput(format!(
+                "writer_id longer than {MAX_WRITER_ID_LEN} bytes"
+            )));
+        }
+        let mut writer_id = vec![0u8; writer_id_len];
+        cursor.read_exact(&mut writer_id)?;
+        let writer_id = String::from_utf8(writer_id)
+            .map_err(|_| StoreError::InvalidInput("writer_id not utf8".into()))?;
+        let writer_seq = cursor.read_u64::<LittleEndian>()?;
+        Some(TurnWriter {
+            writer_id,
+            writer_seq,
+        })
+    } else {
+        None
+    };
+
+    let ttl_ms = if flags & APPEND_FLAG_TTL != 0 {
+        let ttl_ms = cursor.read_u64::<LittleEndian>()?;
+        if ttl_ms == 0 {
+            return Err(StoreError::InvalidInput("ttl must be positive".into()));
+        }
+        Some(ttl_ms)
+    } else {
+        None
+    };
+
+    let links = if flags & APPEND_FLAG_LINKS != 0 {
+        let link_count = cursor.read_u32::<LittleEndian>()? as usize;
+        if link_count > MAX_LINKS {
+            return Err(StoreError::InvalidInput(format!(
+                "more than {MAX_LINKS} links on one turn"
+            )));
+        }
+        let mut links = Vec::with_capacity(link_count);
+        for _ in 0..link_count {
+            let target_turn_id = cursor.read_u64::<LittleEndian>()?;
+            let tag = cursor.read_u8()?;
+            let name_len = cursor.read_u32::<LittleEndian>()? as usize;
+            if name_len > MAX_LINK_KIND_LEN {
+                return Err(StoreError::InvalidInput(format!(
+                    "custom link kind longer than {MAX_LINK_KIND_LEN} bytes"
+                )));
+            }
+            let mut name = vec![0u8; name_len];
+            cursor.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| StoreError::InvalidInput("link kind not utf8".into()))?;
+            links.push(TurnLink {
+                target_turn_id,
+                kind: LinkKind::from_parts(tag, name)?,
+            });
+        }
+        links
+    } else {
+        Vec::new()
+    };
+
     Ok(AppendTurnRequest {
         context_id,
         parent_turn_id,
@@ -201,9 +1128,42 @@ pub fn parse_append_turn(payload: &[u8], flags: u16) -> Result<AppendTurnRequest
         payload_bytes,
         idempotency_key,
         fs_root_hash,
+        writer,
+        ttl_ms,
+        dedup: flags & APPEND_FLAG_DEDUP != 0,
+        links,
     })
 }
 
+/// Parse APPEND_MULTI request: count (u32), then per entry the APPEND_TURN
+/// frame flags (u32), body_len (u32) and an APPEND_TURN body.
+pub fn parse_append_multi(payload: &[u8]) -> Result<Vec<AppendTurnRequest>> {
+    let mut cursor = std::io::Cursor::new(payload);
+    let count = cursor.read_u32::<LittleEndian>()? as usize;
+    if count == 0 {
+        return Err(StoreError::InvalidInput(
+            "append_multi has no entries".into(),
+        ));
+    }
+    let mut entries = Vec::with_capacity(count.min(1024));
+    for entry in 0..count {
+        let flags = cursor.read_u32::<LittleEndian>()?;
+        let body_len = cursor.read_u32::<LittleEndian>()? as usize;
+        let mut body = vec![0u8; body_len];
+        cursor.read_exact(&mut body)?;
+        let flags = u16::try_from(flags).map_err(|_| {
+            StoreError::InvalidInput(format!("append_multi entry {entry}: unknown flags"))
+        })?;
+        if flags & APPEND_FLAG_DEDUP != 0 {
+            return Err(StoreError::InvalidInput(format!(
+                "append_multi entry {entry}: dedup is not supported"
+            )));
+        }
+        entries.push(parse_append_turn(&body, flags)?);
+    }
+    Ok(entries)
+}
+
 /// Parse ATTACH_FS request: turn_id (u64) + fs_root_hash (32 bytes)
 pub fn parse_attach_fs(payload: &[u8]) -> Result<AttachFsRequest> {
     if payload.len() < 40 {
@@ -265,6 +1225,64 @@ pub fn encode_ctx_create_resp(
     Ok(buf)
 }
 
+/// Encode CTX_CREATE_ALIAS response: the CTX_CREATE fields + created (u32)
+pub fn encode_ctx_create_alias_resp(
+    context_id: u64,
+    head_turn_id: u64,
+    head_depth: u32,
+    created: bool,
+) -> Result<Vec<u8>> {
+    let mut buf = encode_ctx_create_resp(context_id, head_turn_id, head_depth)?;
+    buf.write_u32::<LittleEndian>(created as u32)?;
+    Ok(buf)
+}
+
+/// Encode CTX_ARCHIVE and CTX_RESTORE responses: the CTX_CREATE fields +
+/// the context's flags (u32)
+pub fn encode_ctx_archive_resp(head: &ContextHead) -> Result<Vec<u8>> {
+    let mut buf = encode_ctx_create_resp(head.context_id, head.head_turn_id, head.head_depth)?;
+    buf.write_u32::<LittleEndian>(head.flags)?;
+    Ok(buf)
+}
+
+/// Encode LIST_CONTEXTS response: count (u32), then per context the
+/// CTX_ARCHIVE fields and the head's created_at_unix_ms (u64)
+pub fn encode_list_contexts_resp(heads: &[ContextHead]) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(4 + heads.len() * 32);
+    buf.write_u32::<LittleEndian>(heads.len() as u32)?;
+    for head in heads {
+        buf.extend_from_slice(&encode_ctx_archive_resp(head)?);
+        buf.write_u64::<LittleEndian>(head.created_at_unix_ms)?;
+    }
+    Ok(buf)
+}
+
+/// Encode GET_CONTEXT response: a LIST_CONTEXTS entry +
+/// parent_context_id (u64, 0 = none)
+pub fn encode_get_context_resp(
+    head: &ContextHead,
+    parent_context_id: Option<u64>,
+) -> Result<Vec<u8>> {
+    let mut buf = encode_ctx_archive_resp(head)?;
+    buf.write_u64::<LittleEndian>(head.created_at_unix_ms)?;
+    buf.write_u64::<LittleEndian>(parent_context_id.unwrap_or(0))?;
+    Ok(buf)
+}
+
+/// Encode CTX_DELETE_MANY response: number of contexts deleted (u64)
+pub fn encode_ctx_delete_many_resp(deleted: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(8);
+    buf.write_u64::<LittleEndian>(deleted as u64)?;
+    Ok(buf)
+}
+
+/// Encode RESOLVE_ALIAS response: context_id (u64), 0 if the alias is unbound
+pub fn encode_resolve_alias_resp(context_id: Option<u64>) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(8);
+    buf.write_u64::<LittleEndian>(context_id.unwrap_or(0))?;
+    Ok(buf)
+}
+
 pub fn encode_append_ack(
     context_id: u64,
     new_turn_id: u64,
@@ -279,6 +1297,87 @@ pub fn encode_append_ack(
     Ok(buf)
 }
 
+/// Encode the CTX_PRUNE response: turns_pruned (u64) + bytes_reclaimed (u64).
+pub fn encode_prune_result(turns_pruned: u64, bytes_reclaimed: u64) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(16);
+    buf.write_u64::<LittleEndian>(turns_pruned)?;
+    buf.write_u64::<LittleEndian>(bytes_reclaimed)?;
+    Ok(buf)
+}
+
+pub fn encode_redact_resp(turn_id: u64, redacted_at_unix_ms: u64, reason: &str) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(8 + 8 + 4 + reason.len());
+    buf.write_u64::<LittleEndian>(turn_id)?;
+    buf.write_u64::<LittleEndian>(redacted_at_unix_ms)?;
+    buf.write_u32::<LittleEndian>(reason.len() as u32)?;
+    buf.extend_from_slice(reason.as_bytes());
+    Ok(buf)
+}
+
+/// Encodes TYPE_HISTOGRAM counts: count (u32), then per type a
+/// length-prefixed type_id and its turn count (u64), by type_id.
+pub fn encode_type_histogram(counts: &BTreeMap<String, u64>) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    buf.write_u32::<LittleEndian>(counts.len() as u32)?;
+    for (type_id, count) in counts {
+        buf.write_u32::<LittleEndian>(type_id.len() as u32)?;
+        buf.extend_from_slice(type_id.as_bytes());
+        buf.write_u64::<LittleEndian>(*count)?;
+    }
+    Ok(buf)
+}
+
+/// Encodes CONTEXT_STATS: turns (u64), payload_bytes (u64),
+/// first_created_at_unix_ms and last_created_at_unix_ms (u64 each, 0 when
+/// empty), then count (u32) and per type a length-prefixed type_id and its
+/// payload bytes (u64), by type_id.
+pub fn encode_context_stats(stats: &ContextStats) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    buf.write_u64::<LittleEndian>(stats.turns)?;
+    buf.write_u64::<LittleEndian>(stats.payload_bytes)?;
+    buf.write_u64::<LittleEndian>(stats.first_created_at_unix_ms)?;
+    buf.write_u64::<LittleEndian>(stats.last_created_at_unix_ms)?;
+    buf.write_u32::<LittleEndian>(stats.bytes_by_type.len() as u32)?;
+    for (type_id, bytes) in &stats.bytes_by_type {
+        buf.write_u32::<LittleEndian>(type_id.len() as u32)?;
+        buf.extend_from_slice(type_id.as_bytes());
+        buf.write_u64::<LittleEndian>(*bytes)?;
+    }
+    Ok(buf)
+}
+
+/// Encode APPEND_MULTI response: count (u32), then per entry ack_len (u32)
+/// and the entry's APPEND_TURN ack.
+pub fn encode_append_multi_resp(acks: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    buf.write_u32::<LittleEndian>(acks.len() as u32)?;
+    for ack in acks {
+        buf.write_u32::<LittleEndian>(ack.len() as u32)?;
+        buf.extend_from_slice(ack);
+    }
+    Ok(buf)
+}
+
+/// Extends an APPEND_TURN ack with the append metadata negotiated by
+/// [`FLAG_APPEND_META`]: a zero commit sequence (this server does not
+/// replicate), the turn's timestamp, its stored payload size and the
+/// context head after the append.
+pub fn encode_append_ack_meta(
+    mut ack: Vec<u8>,
+    created_at_unix_ms: u64,
+    stored_len: u32,
+    head_turn_id: u64,
+    head_depth: u32,
+) -> Result<Vec<u8>> {
+    ack.reserve(8 + 8 + 4 + 8 + 4);
+    ack.write_u64::<LittleEndian>(0)?;
+    ack.write_u64::<LittleEndian>(created_at_unix_ms)?;
+    ack.write_u32::<LittleEndian>(stored_len)?;
+    ack.write_u64::<LittleEndian>(head_turn_id)?;
+    ack.write_u32::<LittleEndian>(head_depth)?;
+    Ok(ack)
+}
+
 pub fn encode_error(code: u32, detail: &str) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     buf.write_u32::<LittleEndian>(code)?;
@@ -287,12 +1386,50 @@ pub fn encode_error(code: u32, detail: &str) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// Encode an ERROR with the optional trailer: `flags` (bit 0 = retryable)
+/// and string key/value details a client can act on.
+pub fn encode_error_with_details(
+    code: u32,
+    detail: &str,
+    flags: u32,
+    details: &[(&str, String)],
+) -> Result<Vec<u8>> {
+    let mut buf = encode_error(code, detail)?;
+    buf.write_u32::<LittleEndian>(flags)?;
+    buf.write_u32::<LittleEndian>(details.len() as u32)?;
+    for (key, value) in details {
+        buf.write_u32::<LittleEndian>(key.len() as u32)?;
+        buf.extend_from_slice(key.as_bytes());
+        buf.write_u32::<LittleEndian>(value.len() as u32)?;
+        buf.extend_from_slice(value.as_bytes());
+    }
+    Ok(buf)
+}
+
 /// Parsed HELLO request with optional client metadata.
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub struct HelloRequest {
     pub protocol_version: u16,
     pub client_tag: String,
     pub client_meta_json: Option<String>,
+    pub bearer_token: Option<String>,
+    /// Frame compression codecs the client accepts, most preferred first.
+    pub codecs: Vec<u8>,
+}
+
+impl std::fmt::Debug for HelloRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HelloRequest")
+            .field("protocol_version", &self.protocol_version)
+            .field("client_tag", &self.client_tag)
+            .field("client_meta_json", &self.client_meta_json)
+            .field(
+                "bearer_token",
+                &self.bearer_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("codecs", &self.codecs)
+            .finish()
+    }
 }
 
 /// Parse HELLO payload. Supports both old (empty) and new (with metadata) formats.
@@ -303,6 +1440,7 @@ pub fn parse_hello(payload: &[u8]) -> Result<HelloRequest> {
     }
 
     // New format: protocol_version(u16) + client_tag_len(u16) + client_tag + meta_json_len(u32) + meta_json
+    // [+ bearer_token_len(u32) + bearer_token [+ codec_count(u8) + codecs]]
     if payload.len() < 4 {
         return Err(StoreError::InvalidInput("hello payload too short".into()));
     }
@@ -334,17 +1472,193 @@ pub fn parse_hello(payload: &[u8]) -> Result<HelloRequest> {
         None
     };
 
+    let bearer_token = if cursor.position() < payload.len() as u64 {
+        let token_len = cursor.read_u32::<LittleEndian>()? as usize;
+        if token_len > payload.len() - cursor.position() as usize {
+            return Err(StoreError::InvalidInput("hello payload truncated".into()));
+        }
+        let mut token_bytes = vec![0u8; token_len];
+        cursor.read_exact(&mut token_bytes)?;
+        // Clients listing codecs without a token send an empty one.
+        Some(
+            String::from_utf8(token_bytes)
+                .map_err(|_| StoreError::InvalidInput("bearer_token not utf8".into()))?,
+        )
+        .filter(|token| !token.is_empty())
+    } else {
+        None
+    };
+
+    let codecs = if cursor.position() < payload.len() as u64 {
+        let count = cursor.read_u8()? as usize;
+        let mut codecs = vec![0u8; count];
+        cursor
+            .read_exact(&mut codecs)
+            .map_err(|_| StoreError::InvalidInput("hello payload truncated".into()))?;
+        codecs
+    } else {
+        Vec::new()
+    };
+
     Ok(HelloRequest {
         protocol_version,
         client_tag,
         client_meta_json,
+        bearer_token,
+        codecs,
     })
 }
 
-/// Encode HELLO response with session_id and protocol_version.
-pub fn encode_hello_resp(session_id: u64, protocol_version: u16) -> Result<Vec<u8>> {
-    let mut buf = Vec::with_capacity(10);
+/// Encode HELLO response with session_id, protocol_version, the
+/// message_types bitset (bit n set: msg_type n is handled) and, when frame
+/// compression was negotiated, the chosen codec.
+pub fn encode_hello_resp(
+    session_id: u64,
+    protocol_version: u16,
+    message_types: &[MsgType],
+    codec: Option<u8>,
+) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(19);
     buf.write_u64::<LittleEndian>(session_id)?;
     buf.write_u16::<LittleEndian>(protocol_version)?;
+    let bits = message_types
+        .iter()
+        .filter(|msg_type| (**msg_type as u16) < 64)
+        .fold(0u64, |bits, msg_type| bits | 1 << (*msg_type as u16));
+    buf.write_u64::<LittleEndian>(bits)?;
+    if let Some(codec) = codec {
+        buf.write_u8(codec)?;
+    }
     Ok(buf)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hello_resp_advertises_message_types() {
+        let resp = encode_hello_resp(7, 1, &[MsgType::Hello, MsgType::ContextStats], None).unwrap();
+        assert_eq!(&resp[..8], &7u64.to_le_bytes());
+        assert_eq!(&resp[8..10], &1u16.to_le_bytes());
+        assert_eq!(&resp[10..], &((1u64 << 1) | (1 << 24)).to_le_bytes());
+
+        let resp = encode_hello_resp(7, 1, &[], Some(CODEC_ZSTD)).unwrap();
+        assert_eq!(resp.len(), 19);
+        assert_eq!(resp[18], CODEC_ZSTD);
+    }
+
+    #[test]
+    fn hello_lists_codecs_after_the_token() {
+        let mut payload = Vec::new();
+        payload.write_u16::<LittleEndian>(1).unwrap();
+        payload.write_u16::<LittleEndian>(0).unwrap();
+        payload.write_u32::<LittleEndian>(0).unwrap();
+        payload.write_u32::<LittleEndian>(0).unwrap();
+        payload.extend_from_slice(&[2, 9, CODEC_ZSTD]);
+        let hello = parse_hello(&payload).unwrap();
+        assert_eq!(hello.bearer_token, None);
+        assert_eq!(hello.codecs, [9, CODEC_ZSTD]);
+
+        payload.pop();
+        assert!(parse_hello(&payload).is_err());
+    }
+
+    #[test]
+    fn negotiated_frames_compress_large_payloads_only() {
+        let large = b"cxdb.ConversationItem ".repeat(100);
+        for (payload, checksums) in [
+            (&large[..], true),
+            (&large[..], false),
+            (&b"small"[..], true),
+        ] {
+            let mut wire = Vec::new();
+            write_frame_negotiated(&mut wire, 6, 0, 1, payload, checksums, Some(CODEC_ZSTD))
+                .unwrap();
+            let (header, body) = read_frame(&mut &wire[..]).unwrap();
+            let compressed = header.flags & FLAG_COMPRESSED != 0;
+            assert_eq!(compressed, payload.len() == large.len());
+            assert_eq!(compressed, body.len() < payload.len());
+            let (header, body) = if checksums {
+                verify_frame_checksum(header, body).unwrap()
+            } else {
+                (header, body)
+            };
+            let (header, body) = decompress_frame(header, body, CODEC_ZSTD).unwrap();
+            assert_eq!(header.flags, 0);
+            assert_eq!(header.len as usize, payload.len());
+            assert_eq!(body, payload);
+        }
+
+        let header = FrameHeader {
+            len: 3,
+            msg_type: 6,
+            flags: FLAG_COMPRESSED,
+            req_id: 1,
+        };
+        assert!(decompress_frame(header, vec![1, 2, 3], CODEC_ZSTD).is_err());
+    }
+
+    #[test]
+    fn split_frame_metadata_strips_block() {
+        let mut payload = Vec::new();
+        payload.write_u16::<LittleEndian>(2).unwrap();
+        for field in ["request_id", "req-1", "tenant", "acme"] {
+            payload
+                .write_u16::<LittleEndian>(field.len() as u16)
+                .unwrap();
+            payload.extend_from_slice(field.as_bytes());
+        }
+        payload.extend_from_slice(&7u64.to_le_bytes());
+        let header = FrameHeader {
+            len: payload.len() as u32,
+            msg_type: MsgType::GetHead as u16,
+            flags: FLAG_METADATA | 1,
+            req_id: 3,
+        };
+
+        let (header, metadata, body) = split_frame_metadata(header, payload).unwrap();
+        assert_eq!(
+            metadata,
+            [
+                ("request_id".to_string(), "req-1".to_string()),
+                ("tenant".to_string(), "acme".to_string()),
+            ]
+        );
+        assert_eq!(header.flags, 1);
+        assert_eq!(header.len, 8);
+        assert_eq!(parse_get_head(&body).unwrap(), 7);
+        assert_eq!(metadata_auth(&metadata), None);
+        let with_auth = vec![(METADATA_AUTH_KEY.to_string(), "t0k".to_string())];
+        assert_eq!(metadata_auth(&with_auth), Some("t0k"));
+
+        // A block that overruns the payload is rejected.
+        let header = FrameHeader {
+            len: 4,
+            msg_type: MsgType::GetHead as u16,
+            flags: FLAG_METADATA,
+            req_id: 4,
+        };
+        assert!(split_frame_metadata(header, vec![1, 0, 9, 0]).is_err());
+    }
+
+    #[test]
+    fn hello_carries_optional_bearer_token() {
+        let mut payload = Vec::new();
+        payload.write_u16::<LittleEndian>(1).unwrap();
+        payload.write_u16::<LittleEndian>(3).unwrap();
+        payload.extend_from_slice(b"app");
+        payload.write_u32::<LittleEndian>(0).unwrap();
+        assert_eq!(parse_hello(&payload).unwrap().bearer_token, None);
+
+        payload.write_u32::<LittleEndian>(6).unwrap();
+        payload.extend_from_slice(b"s3cr3t");
+        let hello = parse_hello(&payload).unwrap();
+        assert_eq!(hello.client_tag, "app");
+        assert_eq!(hello.bearer_token.as_deref(), Some("s3cr3t"));
+        assert!(!format!("{hello:?}").contains("s3cr3t"));
+
+        payload.truncate(payload.len() - 1);
+        assert!(parse_hello(&payload).is_err());
+    }
+}
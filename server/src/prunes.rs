@@ -0,0 +1,119 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tombstones for pruned turns.
+//!
+//! Pruning a context deletes the turns below a depth from the turn store
+//! (see [`Store::prune_context`](crate::store::Store::prune_context)). The
+//! ids are recorded here so reads that reach them report the turn as pruned
+//! rather than unknown.
+//!
+//! # Storage Format
+//!
+//! The prune index (`turns/pruned.idx`) is an append-only file of
+//! fixed-size records:
+//! - turn_id: u64
+//! - pruned: u32 (1 = pruned, 0 = record dropped)
+//! - crc32: u32 over the preceding fields
+//!
+//! The file is a [`RecordLog`], so a torn or corrupt tail is truncated on
+//! load.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::record_log::RecordLog;
+
+pub struct PruneIndex {
+    log: RecordLog<(u64, u32)>,
+    pruned: HashSet<u64>,
+}
+
+impl PruneIndex {
+    /// Open or create the prune index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<(u64, u32)>::open(dir, "pruned.idx")?;
+        let mut pruned = HashSet::new();
+        for (turn_id, flag) in records {
+            if flag == 0 {
+                pruned.remove(&turn_id);
+            } else {
+                pruned.insert(turn_id);
+            }
+        }
+        Ok(Self { log, pruned })
+    }
+
+    fn write_records(&mut self, turn_ids: &[u64], pruned: u32) -> Result<()> {
+        let records: Vec<(u64, u32)> = turn_ids.iter().map(|id| (*id, pruned)).collect();
+        self.log.append_all(&records)
+    }
+
+    /// Record that `turn_ids` were pruned.
+    pub fn insert(&mut self, turn_ids: &[u64]) -> Result<()> {
+        self.write_records(turn_ids, 1)?;
+        self.pruned.extend(turn_ids);
+        Ok(())
+    }
+
+    /// Drop tombstones for ids the turn store has not issued (at or after
+    /// `next_turn_id`), so a reused turn id is never reported as pruned.
+    pub fn release_unissued(&mut self, next_turn_id: u64) -> Result<()> {
+        let mut unissued: Vec<u64> = self
+            .pruned
+            .iter()
+            .copied()
+            .filter(|id| *id >= next_turn_id)
+            .collect();
+        if unissued.is_empty() {
+            return Ok(());
+        }
+        unissued.sort_unstable();
+        self.write_records(&unissued, 0)?;
+        for turn_id in unissued {
+            self.pruned.remove(&turn_id);
+        }
+        Ok(())
+    }
+
+    /// Whether `turn_id` was pruned.
+    pub fn is_pruned(&self, turn_id: u64) -> bool {
+        self.pruned.contains(&turn_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    #[test]
+    fn tombstones_persist_and_truncate_torn_tail() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = PruneIndex::open(tmpdir.path()).unwrap();
+        index.insert(&[1, 2, 3]).unwrap();
+        index.insert(&[7]).unwrap();
+        assert!(index.is_pruned(2));
+        assert!(!index.is_pruned(4));
+        index.release_unissued(3).unwrap();
+        drop(index);
+
+        let path = tmpdir.path().join("pruned.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        // The torn tombstone drop for 7 is lost, so 7 is pruned again.
+        let mut index = PruneIndex::open(tmpdir.path()).unwrap();
+        assert!(index.is_pruned(1) && index.is_pruned(2) && index.is_pruned(7));
+        assert!(!index.is_pruned(3));
+
+        index.release_unissued(2).unwrap();
+        drop(index);
+        let index = PruneIndex::open(tmpdir.path()).unwrap();
+        assert!(index.is_pruned(1));
+        assert!(!index.is_pruned(2) && !index.is_pruned(7));
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Append-only, checksummed record logs behind the small `turns/*.idx`
+//! indexes (aliases, compactions, expiry, links, parents, prunes,
+//! redactions and writers).
+//!
+//! # Storage Format
+//!
+//! A log is a file of records, each:
+//! - body: encoded by the index's [`Record`] type
+//! - crc32: u32 over the body
+//!
+//! Records are only ever appended; an index replays them in order on open,
+//! later records overriding earlier ones. A torn or corrupt tail is
+//! truncated on open, like `fs/roots.idx`, so new records land after the
+//! last intact one.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crc32fast::Hasher;
+
+use crate::error::Result;
+
+/// The body of one record in a [`RecordLog`].
+pub trait Record: Sized {
+    /// Appends the body to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()>;
+
+    /// Reads a body written by [`encode`](Record::encode). Errors, including
+    /// running out of input, mark the record as torn or corrupt.
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self>;
+}
+
+/// `(key, value)` pairs, e.g. turn id to expiry time.
+impl Record for (u64, u64) {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.write_u64::<LittleEndian>(self.0)?;
+        buf.write_u64::<LittleEndian>(self.1)?;
+        Ok(())
+    }
+
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self> {
+        Ok((
+            reader.read_u64::<LittleEndian>()?,
+            reader.read_u64::<LittleEndian>()?,
+        ))
+    }
+}
+
+/// `(key, flag)` pairs, e.g. turn id to a pruned marker.
+impl Record for (u64, u32) {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.write_u64::<LittleEndian>(self.0)?;
+        buf.write_u32::<LittleEndian>(self.1)?;
+        Ok(())
+    }
+
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self> {
+        Ok((
+            reader.read_u64::<LittleEndian>()?,
+            reader.read_u32::<LittleEndian>()?,
+        ))
+    }
+}
+
+pub struct RecordLog<R> {
+    file: File,
+    _record: PhantomData<fn() -> R>,
+}
+
+impl<R: Record> RecordLog<R> {
+    /// Open or create the log `name` in `dir`. Returns it with its intact
+    /// records, oldest first, after truncating any torn or corrupt tail.
+    pub fn open(dir: &Path, name: &str) -> Result<(Self, Vec<R>)> {
+        std::fs::create_dir_all(dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(dir.join(name))?;
+
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        let mut records = Vec::new();
+        let mut valid_len = 0;
+        while valid_len < buf.len() {
+            match Self::read_record(&buf[valid_len..]) {
+                Some((record, len)) => {
+                    records.push(record);
+                    valid_len += len;
+                }
+                None => break,
+            }
+        }
+        if valid_len < buf.len() {
+            file.set_len(valid_len as u64)?;
+        }
+
+        let log = Self {
+            file,
+            _record: PhantomData,
+        };
+        Ok((log, records))
+    }
+
+    /// Decodes the record at the start of `buf` and its length on disk;
+    /// `None` if it is torn or corrupt.
+    fn read_record(buf: &[u8]) -> Option<(R, usize)> {
+        let mut cursor = Cursor::new(buf);
+        let record = R::decode(&mut cursor).ok()?;
+        let body_len = cursor.position() as usize;
+        let crc = cursor.read_u32::<LittleEndian>().ok()?;
+        if crc != compute_crc(&buf[..body_len]) {
+            return None;
+        }
+        Some((record, body_len + 4))
+    }
+
+    /// Append `record`.
+    pub fn append(&mut self, record: &R) -> Result<()> {
+        self.append_all(std::slice::from_ref(record))
+    }
+
+    /// Append `records` in one write.
+    pub fn append_all(&mut self, records: &[R]) -> Result<()> {
+        let mut buf = Vec::new();
+        for record in records {
+            let start = buf.len();
+            record.encode(&mut buf)?;
+            let crc = compute_crc(&buf[start..]);
+            buf.write_u32::<LittleEndian>(crc)?;
+        }
+
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&buf)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Compute CRC32 for a record body.
+fn compute_crc(body: &[u8]) -> u32 {
+    let mut hasher = Hasher::new();
+    hasher.update(body);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn records_replay_in_order_after_reopen() {
+        let tmpdir = TempDir::new().unwrap();
+        let (mut log, records) = RecordLog::<(u64, u64)>::open(tmpdir.path(), "t.idx").unwrap();
+        assert!(records.is_empty());
+        log.append(&(1, 10)).unwrap();
+        log.append_all(&[(2, 20), (1, 0)]).unwrap();
+        drop(log);
+
+        let (_, records) = RecordLog::<(u64, u64)>::open(tmpdir.path(), "t.idx").unwrap();
+        assert_eq!(records, [(1, 10), (2, 20), (1, 0)]);
+    }
+
+    #[test]
+    fn corrupt_records_truncate_the_log() {
+        let tmpdir = TempDir::new().unwrap();
+        let (mut log, _) = RecordLog::<(u64, u32)>::open(tmpdir.path(), "t.idx").unwrap();
+        log.append_all(&[(1, 1), (2, 1), (3, 1)]).unwrap();
+        drop(log);
+
+        // Flip a byte in the second record's body.
+        let path = tmpdir.path().join("t.idx");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[16] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+
+        let (mut log, records) = RecordLog::<(u64, u32)>::open(tmpdir.path(), "t.idx").unwrap();
+        assert_eq!(records, [(1, 1)]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 16);
+        log.append(&(4, 1)).unwrap();
+        drop(log);
+
+        let (_, records) = RecordLog::<(u64, u32)>::open(tmpdir.path(), "t.idx").unwrap();
+        assert_eq!(records, [(1, 1), (4, 1)]);
+    }
+}
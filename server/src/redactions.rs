@@ -0,0 +1,201 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Redaction markers on turns.
+//!
+//! Redacting a turn (see
+//! [`Store::redact_turn`](crate::store::Store::redact_turn)) removes its
+//! payload but keeps the turn, its place in history and its content hash.
+//! The marker recorded here is what reads report in place of the payload.
+//!
+//! # Storage Format
+//!
+//! The redaction index (`turns/redactions.idx`) is an append-only file of
+//! variable-size records:
+//! - turn_id: u64
+//! - redacted_at_unix_ms: u64 (0 = marker dropped)
+//! - reason_len: u32
+//! - reason: [reason_len]u8 (UTF-8)
+//! - crc32: u32 over the preceding fields
+//!
+//! The file is a [`RecordLog`], so a torn or corrupt tail is truncated on
+//! load.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Result, StoreError};
+use crate::record_log::{Record, RecordLog};
+
+/// Longest redaction reason accepted, in bytes.
+pub const MAX_REASON_LEN: usize = 1024;
+
+/// Why and when a turn's payload was removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    pub redacted_at_unix_ms: u64,
+    pub reason: String,
+}
+
+/// One marker in the log; `None` drops the turn's marker.
+struct RedactionRecord {
+    turn_id: u64,
+    redaction: Option<Redaction>,
+}
+
+impl Record for RedactionRecord {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let (redacted_at_unix_ms, reason) = match &self.redaction {
+            Some(redaction) => (redaction.redacted_at_unix_ms, redaction.reason.as_str()),
+            None => (0, ""),
+        };
+        buf.write_u64::<LittleEndian>(self.turn_id)?;
+        buf.write_u64::<LittleEndian>(redacted_at_unix_ms)?;
+        buf.write_u32::<LittleEndian>(reason.len() as u32)?;
+        buf.extend_from_slice(reason.as_bytes());
+        Ok(())
+    }
+
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self> {
+        let turn_id = reader.read_u64::<LittleEndian>()?;
+        let redacted_at_unix_ms = reader.read_u64::<LittleEndian>()?;
+        let reason_len = reader.read_u32::<LittleEndian>()? as usize;
+        if reason_len > MAX_REASON_LEN {
+            return Err(StoreError::Corrupt("redaction record too long".into()));
+        }
+        let mut reason = vec![0u8; reason_len];
+        reader.read_exact(&mut reason)?;
+        if redacted_at_unix_ms == 0 {
+            return Ok(Self {
+                turn_id,
+                redaction: None,
+            });
+        }
+        let reason = String::from_utf8(reason)
+            .map_err(|_| StoreError::Corrupt("redaction reason not utf8".into()))?;
+        Ok(Self {
+            turn_id,
+            redaction: Some(Redaction {
+                redacted_at_unix_ms,
+                reason,
+            }),
+        })
+    }
+}
+
+pub struct RedactionIndex {
+    log: RecordLog<RedactionRecord>,
+    redactions: HashMap<u64, Redaction>,
+}
+
+impl RedactionIndex {
+    /// Open or create the redaction index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<RedactionRecord>::open(dir, "redactions.idx")?;
+        let mut redactions = HashMap::new();
+        for record in records {
+            match record.redaction {
+                Some(redaction) => redactions.insert(record.turn_id, redaction),
+                None => redactions.remove(&record.turn_id),
+            };
+        }
+        Ok(Self { log, redactions })
+    }
+
+    /// Mark `turn_id` as redacted.
+    pub fn insert(&mut self, turn_id: u64, redaction: Redaction) -> Result<()> {
+        validate_reason(&redaction.reason)?;
+        self.log.append(&RedactionRecord {
+            turn_id,
+            redaction: Some(redaction.clone()),
+        })?;
+        self.redactions.insert(turn_id, redaction);
+        Ok(())
+    }
+
+    /// Drop markers of turns `exists` no longer reports, so a reused turn id
+    /// can never inherit a stale marker.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .redactions
+            .keys()
+            .copied()
+            .filter(|id| !exists(*id))
+            .collect();
+        missing.sort_unstable();
+        for turn_id in missing {
+            self.log.append(&RedactionRecord {
+                turn_id,
+                redaction: None,
+            })?;
+            self.redactions.remove(&turn_id);
+        }
+        Ok(())
+    }
+
+    /// The redaction of `turn_id`, if it was redacted.
+    pub fn get(&self, turn_id: u64) -> Option<&Redaction> {
+        self.redactions.get(&turn_id)
+    }
+
+    /// Whether `turn_id` was redacted.
+    pub fn is_redacted(&self, turn_id: u64) -> bool {
+        self.redactions.contains_key(&turn_id)
+    }
+}
+
+/// Redaction reasons are UTF-8 of at most [`MAX_REASON_LEN`] bytes.
+pub fn validate_reason(reason: &str) -> Result<()> {
+    if reason.len() > MAX_REASON_LEN {
+        return Err(StoreError::InvalidInput(format!(
+            "redaction reason longer than {MAX_REASON_LEN} bytes"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    fn redaction(at: u64, reason: &str) -> Redaction {
+        Redaction {
+            redacted_at_unix_ms: at,
+            reason: reason.into(),
+        }
+    }
+
+    #[test]
+    fn markers_persist_and_truncate_torn_tail() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = RedactionIndex::open(tmpdir.path()).unwrap();
+        index.insert(1, redaction(100, "pii")).unwrap();
+        index.insert(2, redaction(200, "")).unwrap();
+        index.insert(3, redaction(300, "legal hold")).unwrap();
+        assert!(matches!(
+            index.insert(4, redaction(400, &"x".repeat(MAX_REASON_LEN + 1))),
+            Err(StoreError::InvalidInput(_))
+        ));
+        index.release_missing(|turn_id| turn_id != 2).unwrap();
+        drop(index);
+
+        let index = RedactionIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(1), Some(&redaction(100, "pii")));
+        assert!(!index.is_redacted(2) && !index.is_redacted(4));
+        drop(index);
+
+        // The drop for 2 is the last record; tearing it brings 2 back.
+        let path = tmpdir.path().join("redactions.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+        let index = RedactionIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(2), Some(&redaction(200, "")));
+        assert_eq!(index.get(3), Some(&redaction(300, "legal hold")));
+    }
+}
@@ -25,6 +25,7 @@
 //!   turns/turns.idx
 //!   turns/turns.meta
 //!   turns/heads.tbl
+//!   turns/aliases.idx
 //!   registry/{bundle_id}.json
 //!   sync_manifest.json    # metadata about last sync
 //! ```
@@ -131,6 +132,10 @@ const SYNC_FILES: &[&str] = &[
     "turns/turns.idx",
     "turns/turns.meta",
     "turns/heads.tbl",
+    "turns/aliases.idx",
+    "turns/compactions.idx",
+    "turns/writers.idx",
+    "turns/expiry.idx",
 ];
 
 /// S3 sync manager
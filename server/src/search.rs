@@ -0,0 +1,71 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Field search over a context's turn payloads (SEARCH_TURNS).
+//!
+//! A query names a path into the msgpack payload. Each segment selects a
+//! map entry whose key is that string, or that integer when the segment is
+//! decimal, or an array element by index. The value found there is compared
+//! against the query.
+
+use rmpv::Value;
+
+/// Turn encoding of the payloads a search can read.
+pub const ENCODING_MSGPACK: u32 = 1;
+
+/// Deepest payload nesting a search will decode.
+const MAX_SEARCH_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchMatch {
+    /// The field equals this value.
+    Equals(Value),
+    /// The field is a string containing this one.
+    Contains(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnSearch {
+    /// Only turns declared with this type; `None` searches every type.
+    pub type_id: Option<String>,
+    pub path: Vec<String>,
+    pub matcher: SearchMatch,
+}
+
+impl TurnSearch {
+    /// The value at the query's path in a msgpack `payload`, if it matches.
+    /// Payloads that fail to decode never match.
+    pub fn find(&self, payload: &[u8]) -> Option<Value> {
+        let mut cursor = std::io::Cursor::new(payload);
+        let value = rmpv::decode::read_value_with_max_depth(&mut cursor, MAX_SEARCH_DEPTH).ok()?;
+        let field = self
+            .path
+            .iter()
+            .try_fold(value, |value, segment| match value {
+                Value::Map(entries) => entries
+                    .into_iter()
+                    .find(|(key, _)| key_matches(key, segment))
+                    .map(|(_, value)| value),
+                Value::Array(items) => {
+                    let index = segment.parse::<usize>().ok()?;
+                    items.into_iter().nth(index)
+                }
+                _ => None,
+            })?;
+        let matched = match &self.matcher {
+            SearchMatch::Equals(expected) => &field == expected,
+            SearchMatch::Contains(needle) => field
+                .as_str()
+                .is_some_and(|text| text.contains(needle.as_str())),
+        };
+        matched.then_some(field)
+    }
+}
+
+fn key_matches(key: &Value, segment: &str) -> bool {
+    match key {
+        Value::String(key) => key.as_str() == Some(segment),
+        Value::Integer(key) => key.as_u64().is_some_and(|key| segment.parse() == Ok(key)),
+        _ => false,
+    }
+}
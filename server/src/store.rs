@@ -1,23 +1,122 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::path::Path;
 
 use blake3::Hasher;
 use rmpv::Value;
 
+use crate::aliases::{validate_alias, AliasIndex};
 use crate::blob_store::BlobStore;
+use crate::compactions::CompactionIndex;
 use crate::cql::{self, CqlError, CqlQuery, IndexStats, SecondaryIndexes};
 use crate::error::{Result, StoreError};
+use crate::expiry::ExpiryIndex;
 use crate::fs_store::{FsRootsIndex, TreeEntry};
-use crate::turn_store::{ContextHead, TurnMeta, TurnRecord, TurnStore};
+use crate::fulltext::{self, TextIndex, TextSearch};
+use crate::links::{validate_links, LinkDirection, LinkIndex, TurnLink};
+use crate::parents::ParentIndex;
+use crate::prunes::PruneIndex;
+use crate::redactions::{validate_reason, Redaction, RedactionIndex};
+use crate::search::{TurnSearch, ENCODING_MSGPACK};
+use crate::turn_store::{
+    ContextHead, TurnMeta, TurnRecord, TurnStore, Walk, CONTEXT_FLAG_ARCHIVED,
+};
+use crate::writers::{TurnWriter, WriterIndex};
 
 #[derive(Debug, Clone)]
 pub struct TurnWithMeta {
     pub record: TurnRecord,
     pub meta: TurnMeta,
     pub payload: Option<Vec<u8>>,
+    /// Writer stamp, if the turn was appended with one.
+    pub writer: Option<TurnWriter>,
+    /// When the turn expires, if it was appended with a TTL.
+    pub expires_at_unix_ms: Option<u64>,
+    /// Whether the turn had expired when it was read.
+    pub expired: bool,
+    /// Set if the turn was redacted; its payload is then empty.
+    pub redaction: Option<Redaction>,
+    /// Links to earlier turns, in the order they were appended.
+    pub links: Vec<TurnLink>,
+}
+
+/// Which turns [`Store::get_last_scoped`] reads.
+#[derive(Debug, Clone)]
+pub struct GetLastScope {
+    /// Return only turns older than this one (0 = from the head).
+    pub before_turn_id: u64,
+    /// Walk past compaction boundaries into the raw history.
+    pub include_compacted: bool,
+    /// Return expired turns instead of skipping them.
+    pub include_expired: bool,
+    /// Return only turns at these depths.
+    pub depths: RangeInclusive<u32>,
+}
+
+impl Default for GetLastScope {
+    fn default() -> Self {
+        Self {
+            before_turn_id: 0,
+            include_compacted: false,
+            include_expired: false,
+            depths: 0..=u32::MAX,
+        }
+    }
+}
+
+/// Size figures for one context, from [`Store::context_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContextStats {
+    pub turns: u64,
+    /// Uncompressed payload bytes; redacted turns count as empty.
+    pub payload_bytes: u64,
+    /// `payload_bytes` per declared type.
+    pub bytes_by_type: BTreeMap<String, u64>,
+    /// Creation time of the oldest and newest turn; 0 for an empty context.
+    pub first_created_at_unix_ms: u64,
+    pub last_created_at_unix_ms: u64,
+}
+
+/// A turn found by [`Store::search_text`].
+#[derive(Debug, Clone)]
+pub struct TextHit {
+    /// The searched context, or for an unscoped search the context the
+    /// turn was first appended to.
+    pub context_id: u64,
+    /// The turn, without its payload.
+    pub turn: TurnWithMeta,
+    /// An excerpt of the payload around a matching word.
+    pub snippet: String,
+}
+
+/// What [`Store::prune_context`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneResult {
+    pub turns_pruned: u64,
+    /// Bytes freed from the turn log and turn metadata.
+    pub bytes_reclaimed: u64,
+}
+
+/// One entry of [`Store::append_batch`]: the arguments of
+/// [`Store::append_turn`] plus what APPEND_TURN can attach to the turn.
+#[derive(Debug, Clone)]
+pub struct BatchAppend {
+    pub context_id: u64,
+    pub parent_turn_id: u64,
+    pub declared_type_id: String,
+    pub declared_type_version: u32,
+    pub encoding: u32,
+    pub compression: u32,
+    pub uncompressed_len: u32,
+    pub content_hash: [u8; 32],
+    pub payload_bytes: Vec<u8>,
+    pub writer: Option<TurnWriter>,
+    pub fs_root_hash: Option<[u8; 32]>,
+    pub ttl_ms: Option<u64>,
+    pub links: Vec<TurnLink>,
 }
 
 /// Provenance captures the origin story of a context.
@@ -77,6 +176,51 @@ pub struct ContextMetadata {
     pub provenance: Option<Provenance>,
 }
 
+/// Which contexts [`Store::delete_contexts`] deletes. Criteria are
+/// AND-ed; at least one must be given.
+#[derive(Debug, Clone, Default)]
+pub struct DeleteFilter {
+    /// Only these contexts.
+    pub context_ids: Vec<u64>,
+    /// Only contexts whose metadata holds every `(field, value)`, matched
+    /// exactly. Fields are named as in CQL: `tag`, `title`, `label`,
+    /// `user`, `service`, `host` and `trace_id`.
+    pub metadata: Vec<(String, String)>,
+    /// Only contexts created before this time.
+    pub created_before_unix_ms: Option<u64>,
+}
+
+impl DeleteFilter {
+    pub fn is_empty(&self) -> bool {
+        self.context_ids.is_empty()
+            && self.metadata.is_empty()
+            && self.created_before_unix_ms.is_none()
+    }
+}
+
+/// Whether `metadata` has `value` in `field`, named as in CQL.
+fn metadata_matches(metadata: &ContextMetadata, field: &str, value: &str) -> Result<bool> {
+    let provenance = metadata.provenance.as_ref();
+    let actual = match field {
+        "tag" => metadata.client_tag.as_deref(),
+        "title" => metadata.title.as_deref(),
+        "label" => {
+            let labels = metadata.labels.as_deref().unwrap_or_default();
+            return Ok(labels.iter().any(|label| label == value));
+        }
+        "user" => provenance.and_then(|p| p.on_behalf_of.as_deref()),
+        "service" => provenance.and_then(|p| p.service_name.as_deref()),
+        "host" => provenance.and_then(|p| p.host_name.as_deref()),
+        "trace_id" => provenance.and_then(|p| p.trace_id.as_deref()),
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unknown metadata field {other}"
+            )))
+        }
+    };
+    Ok(actual == Some(value))
+}
+
 /// Result of a CQL search query.
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct SearchResult {
@@ -88,6 +232,14 @@ pub struct SearchResult {
 
 pub struct Store {
     pub blob_store: BlobStore,
+    pub aliases: AliasIndex,
+    pub compactions: CompactionIndex,
+    pub writers: WriterIndex,
+    pub expiry: ExpiryIndex,
+    pub prunes: PruneIndex,
+    pub redactions: RedactionIndex,
+    pub links: LinkIndex,
+    pub parents: ParentIndex,
     pub turn_store: TurnStore,
     pub fs_roots: FsRootsIndex,
     /// Cache of context metadata, populated lazily from first turn.
@@ -95,18 +247,54 @@ pub struct Store {
     pub context_metadata_cache: HashMap<u64, Option<ContextMetadata>>,
     /// Secondary indexes for CQL queries.
     secondary_indexes: SecondaryIndexes,
+    /// Full-text index for TEXT_SEARCH, built on the first search.
+    text_index: Option<TextIndex>,
 }
 
 impl Store {
     pub fn open(dir: &Path) -> Result<Self> {
         let mut store = Self {
             blob_store: BlobStore::open(&dir.join("blobs"))?,
+            aliases: AliasIndex::open(&dir.join("turns"))?,
+            compactions: CompactionIndex::open(&dir.join("turns"))?,
+            writers: WriterIndex::open(&dir.join("turns"))?,
+            expiry: ExpiryIndex::open(&dir.join("turns"))?,
+            prunes: PruneIndex::open(&dir.join("turns"))?,
+            redactions: RedactionIndex::open(&dir.join("turns"))?,
+            links: LinkIndex::open(&dir.join("turns"))?,
+            parents: ParentIndex::open(&dir.join("turns"))?,
             turn_store: TurnStore::open(&dir.join("turns"))?,
             fs_roots: FsRootsIndex::open(&dir.join("fs"))?,
             context_metadata_cache: HashMap::new(),
             secondary_indexes: SecondaryIndexes::new(),
+            text_index: None,
         };
 
+        // Aliases must never outlive (or be inherited by a reuse of) their context.
+        let turn_store = &store.turn_store;
+        store
+            .aliases
+            .release_missing(|context_id| turn_store.get_head(context_id).is_ok())?;
+        store
+            .compactions
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        store
+            .writers
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        store
+            .expiry
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        store
+            .redactions
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        store
+            .links
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        store
+            .parents
+            .release_missing(|context_id| turn_store.get_head(context_id).is_ok())?;
+        store.prunes.release_unissued(turn_store.next_turn_id())?;
+
         // Pre-populate metadata cache and build secondary indexes
         store.build_indexes();
 
@@ -146,6 +334,9 @@ impl Store {
     fn load_context_metadata(&mut self, context_id: u64) -> Option<ContextMetadata> {
         // Get the first turn (depth=0) for this context
         let first_turn = self.turn_store.get_first_turn(context_id).ok()?;
+        if self.redactions.is_redacted(first_turn.turn_id) {
+            return None;
+        }
         let payload = self.blob_store.get(&first_turn.payload_hash).ok()?;
         extract_context_metadata(&payload)
     }
@@ -172,14 +363,208 @@ impl Store {
         self.turn_store.create_context(base_turn_id)
     }
 
+    /// Create a context from `base_turn_id` that derives from
+    /// `parent_context_id`, which must exist.
+    pub fn create_child_context(
+        &mut self,
+        base_turn_id: u64,
+        parent_context_id: u64,
+    ) -> Result<ContextHead> {
+        self.turn_store.get_head(parent_context_id)?;
+        let head = self.create_context(base_turn_id)?;
+        self.parents.insert(head.context_id, parent_context_id)?;
+        Ok(head)
+    }
+
+    /// The context `context_id` derives from: the parent given when it was
+    /// created, else the `parent_context_id` in its first turn's
+    /// provenance. The parent need not exist any more.
+    pub fn context_parent(&mut self, context_id: u64) -> Result<Option<u64>> {
+        self.turn_store.get_head(context_id)?;
+        if let Some(parent) = self.parents.parent(context_id) {
+            return Ok(Some(parent));
+        }
+        Ok(self
+            .get_context_metadata(context_id)
+            .and_then(|metadata| metadata.provenance)
+            .and_then(|provenance| provenance.parent_context_id))
+    }
+
+    /// Contexts whose [`Store::context_parent`] is `parent_context_id`, in
+    /// ascending id order. The parent need not exist any more.
+    pub fn list_children(&mut self, parent_context_id: u64) -> Result<Vec<ContextHead>> {
+        let mut candidates = self.parents.children(parent_context_id);
+        candidates.extend(
+            self.secondary_indexes
+                .lookup_parent_exact(parent_context_id),
+        );
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut children = Vec::with_capacity(candidates.len());
+        for context_id in candidates {
+            let Ok(head) = self.turn_store.get_head(context_id) else {
+                continue;
+            };
+            // A parent given at creation overrides the provenance one.
+            if self.context_parent(context_id)? == Some(parent_context_id) {
+                children.push(head);
+            }
+        }
+        Ok(children)
+    }
+
     pub fn fork_context(&mut self, base_turn_id: u64) -> Result<ContextHead> {
         self.turn_store.fork_context(base_turn_id)
     }
 
+    /// Create a context from `base_turn_id` and append `first` to it, all or
+    /// none. `first.context_id` is ignored. If the append is rejected the new
+    /// context is deleted again before the error is returned, so callers
+    /// holding the store lock never see it empty; its id is not reused.
+    pub fn create_context_with_turn(
+        &mut self,
+        base_turn_id: u64,
+        mut first: BatchAppend,
+    ) -> Result<(ContextHead, TurnRecord, Option<ContextMetadata>)> {
+        let head = self.create_context(base_turn_id)?;
+        first.context_id = head.context_id;
+        match self.append_batch(vec![first]) {
+            Ok(mut appended) => {
+                let (record, metadata) = appended.remove(0);
+                Ok((self.get_head(head.context_id)?, record, metadata))
+            }
+            Err(err) => {
+                self.delete_contexts(&DeleteFilter {
+                    context_ids: vec![head.context_id],
+                    ..DeleteFilter::default()
+                })?;
+                Err(match err {
+                    StoreError::TransactionAborted { source, .. } => *source,
+                    other => other,
+                })
+            }
+        }
+    }
+
+    /// Returns the context bound to `alias`, creating it from `base_turn_id`
+    /// and binding the alias on first use. The flag reports whether the
+    /// context was created. Callers hold the store lock, so racing creators
+    /// all get the same context.
+    pub fn create_or_get_context_by_alias(
+        &mut self,
+        alias: &str,
+        base_turn_id: u64,
+    ) -> Result<(ContextHead, bool)> {
+        validate_alias(alias)?;
+        if let Some(context_id) = self.aliases.get(alias) {
+            return Ok((self.get_head(context_id)?, false));
+        }
+        let head = self.create_context(base_turn_id)?;
+        self.aliases.insert(alias, head.context_id)?;
+        Ok((head, true))
+    }
+
+    pub fn resolve_alias(&self, alias: &str) -> Option<u64> {
+        self.aliases.get(alias)
+    }
+
     pub fn get_head(&self, context_id: u64) -> Result<ContextHead> {
         self.turn_store.get_head(context_id)
     }
 
+    /// Archive `context_id`: until it is restored, reads of its history and
+    /// appends to it fail with [`StoreError::ContextArchived`]. Its head
+    /// stays readable and listed. Archiving an archived context is a no-op.
+    pub fn archive_context(&mut self, context_id: u64) -> Result<ContextHead> {
+        let flags = self.turn_store.get_head(context_id)?.flags | CONTEXT_FLAG_ARCHIVED;
+        self.turn_store.set_context_flags(context_id, flags)
+    }
+
+    /// Undo [`Store::archive_context`]. Restoring an active context is a
+    /// no-op.
+    pub fn restore_context(&mut self, context_id: u64) -> Result<ContextHead> {
+        let flags = self.turn_store.get_head(context_id)?.flags & !CONTEXT_FLAG_ARCHIVED;
+        self.turn_store.set_context_flags(context_id, flags)
+    }
+
+    /// Delete every context `filter` matches and return their ids. An empty
+    /// filter fails rather than deleting everything. Aliases of a deleted
+    /// context are released; its turns stay, as forks may share them.
+    pub fn delete_contexts(&mut self, filter: &DeleteFilter) -> Result<Vec<u64>> {
+        if filter.is_empty() {
+            return Err(StoreError::InvalidInput(
+                "delete filter matches every context".into(),
+            ));
+        }
+        // Reject unknown fields before anything is deleted.
+        for (field, value) in &filter.metadata {
+            metadata_matches(&ContextMetadata::default(), field, value)?;
+        }
+
+        let mut candidates: Vec<u64> = if filter.context_ids.is_empty() {
+            self.turn_store
+                .list_recent_contexts(u32::MAX)
+                .iter()
+                .map(|head| head.context_id)
+                .collect()
+        } else {
+            filter
+                .context_ids
+                .iter()
+                .copied()
+                .filter(|id| self.turn_store.get_head(*id).is_ok())
+                .collect()
+        };
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut deleted = Vec::new();
+        for context_id in candidates {
+            if let Some(before) = filter.created_before_unix_ms {
+                let created = self.turn_store.context_created_at(context_id);
+                if created.is_none_or(|created| created >= before) {
+                    continue;
+                }
+            }
+            if !filter.metadata.is_empty() {
+                let Some(metadata) = self.get_context_metadata(context_id) else {
+                    continue;
+                };
+                let mut matches = true;
+                for (field, value) in &filter.metadata {
+                    matches &= metadata_matches(&metadata, field, value)?;
+                }
+                if !matches {
+                    continue;
+                }
+            }
+            self.turn_store.delete_context(context_id)?;
+            self.aliases.release_context(context_id)?;
+            self.parents.release_context(context_id)?;
+            self.context_metadata_cache.remove(&context_id);
+            self.secondary_indexes.remove_context(context_id);
+            deleted.push(context_id);
+        }
+        if !deleted.is_empty() {
+            // Turns indexed under a deleted context are re-homed on the
+            // next search.
+            self.text_index = None;
+        }
+        Ok(deleted)
+    }
+
+    /// Fails with [`StoreError::ContextArchived`] if `context_id` is
+    /// archived. Unknown contexts pass, for the caller to report.
+    fn check_active(&self, context_id: u64) -> Result<()> {
+        match self.turn_store.get_head(context_id) {
+            Ok(head) if head.flags & CONTEXT_FLAG_ARCHIVED != 0 => {
+                Err(StoreError::ContextArchived { context_id })
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Append a turn to a context.
     ///
     /// Returns the turn record and, if this is the first turn (depth=0), the extracted metadata.
@@ -196,29 +581,9 @@ impl Store {
         content_hash: [u8; 32],
         payload_bytes: &[u8],
     ) -> Result<(TurnRecord, Option<ContextMetadata>)> {
-        let raw_bytes = match compression {
-            0 => payload_bytes.to_vec(),
-            1 => zstd::decode_all(payload_bytes)
-                .map_err(|e| StoreError::InvalidInput(format!("zstd decode failed: {e}")))?,
-            other => {
-                return Err(StoreError::InvalidInput(format!(
-                    "unsupported compression: {other}"
-                )))
-            }
-        };
-
-        if raw_bytes.len() as u32 != uncompressed_len {
-            return Err(StoreError::InvalidInput(
-                "uncompressed length mismatch".into(),
-            ));
-        }
-
-        let mut hasher = Hasher::new();
-        hasher.update(&raw_bytes);
-        let hash = hasher.finalize();
-        if hash.as_bytes() != &content_hash {
-            return Err(StoreError::InvalidInput("content hash mismatch".into()));
-        }
+        self.check_active(context_id)?;
+        let raw_bytes =
+            verified_payload(compression, uncompressed_len, &content_hash, payload_bytes)?;
 
         self.blob_store.put_if_absent(content_hash, &raw_bytes)?;
 
@@ -233,6 +598,10 @@ impl Store {
             uncompressed_len,
         )?;
 
+        if let Some(index) = &mut self.text_index {
+            index.insert(record.turn_id, context_id, encoding, &raw_bytes);
+        }
+
         // Cache metadata if this is the first turn, and return it for event publishing
         let metadata = self.maybe_cache_metadata(context_id, record.depth, &raw_bytes);
 
@@ -250,28 +619,852 @@ impl Store {
         Ok((record, metadata))
     }
 
+    /// Like [`Store::append_turn`], stamping the turn with `writer`.
+    ///
+    /// Fails with [`StoreError::WriterSequenceConflict`], without appending,
+    /// unless `writer.writer_seq` is above the last sequence this writer
+    /// appended to the context.
+    #[allow(clippy::too_many_arguments)]
+    pub fn append_turn_as_writer(
+        &mut self,
+        writer: TurnWriter,
+        context_id: u64,
+        parent_turn_id: u64,
+        declared_type_id: String,
+        declared_type_version: u32,
+        encoding: u32,
+        compression: u32,
+        uncompressed_len: u32,
+        content_hash: [u8; 32],
+        payload_bytes: &[u8],
+    ) -> Result<(TurnRecord, Option<ContextMetadata>)> {
+        self.writers.check(context_id, &writer)?;
+        let (record, metadata) = self.append_turn(
+            context_id,
+            parent_turn_id,
+            declared_type_id,
+            declared_type_version,
+            encoding,
+            compression,
+            uncompressed_len,
+            content_hash,
+            payload_bytes,
+        )?;
+        self.writers.insert(record.turn_id, context_id, writer)?;
+        Ok((record, metadata))
+    }
+
+    /// Append `entries` in order, all or none.
+    ///
+    /// Every entry is checked before the first is written: its payload, its
+    /// context and parent turn, its fs root tree and its writer sequence
+    /// (counting the sequences of earlier entries). The first entry that
+    /// fails aborts the batch with [`StoreError::TransactionAborted`] and
+    /// nothing is appended. An I/O error while writing can still leave the
+    /// entries before it appended.
+    pub fn append_batch(
+        &mut self,
+        entries: Vec<BatchAppend>,
+    ) -> Result<Vec<(TurnRecord, Option<ContextMetadata>)>> {
+        let abort = |entry: usize| {
+            move |err: StoreError| StoreError::TransactionAborted {
+                entry,
+                source: Box::new(err),
+            }
+        };
+        let mut writer_seqs: HashMap<(u64, &str), u64> = HashMap::new();
+        for (entry, append) in entries.iter().enumerate() {
+            self.check_batch_append(append, &mut writer_seqs)
+                .map_err(abort(entry))?;
+        }
+
+        let mut appended = Vec::with_capacity(entries.len());
+        for (entry, append) in entries.into_iter().enumerate() {
+            let (record, metadata) = match append.writer {
+                Some(writer) => self.append_turn_as_writer(
+                    writer,
+                    append.context_id,
+                    append.parent_turn_id,
+                    append.declared_type_id,
+                    append.declared_type_version,
+                    append.encoding,
+                    append.compression,
+                    append.uncompressed_len,
+                    append.content_hash,
+                    &append.payload_bytes,
+                ),
+                None => self.append_turn(
+                    append.context_id,
+                    append.parent_turn_id,
+                    append.declared_type_id,
+                    append.declared_type_version,
+                    append.encoding,
+                    append.compression,
+                    append.uncompressed_len,
+                    append.content_hash,
+                    &append.payload_bytes,
+                ),
+            }
+            .map_err(abort(entry))?;
+            if let Some(fs_root_hash) = append.fs_root_hash {
+                self.attach_fs(record.turn_id, fs_root_hash)
+                    .map_err(abort(entry))?;
+            }
+            if let Some(ttl_ms) = append.ttl_ms {
+                self.set_turn_ttl(record.turn_id, ttl_ms)
+                    .map_err(abort(entry))?;
+            }
+            self.links
+                .insert(record.turn_id, append.links)
+                .map_err(abort(entry))?;
+            appended.push((record, metadata));
+        }
+        Ok(appended)
+    }
+
+    /// Everything [`Store::append_batch`] checks for one entry, without
+    /// writing. `writer_seqs` holds the sequences earlier entries claimed.
+    fn check_batch_append<'a>(
+        &self,
+        append: &'a BatchAppend,
+        writer_seqs: &mut HashMap<(u64, &'a str), u64>,
+    ) -> Result<()> {
+        verified_payload(
+            append.compression,
+            append.uncompressed_len,
+            &append.content_hash,
+            &append.payload_bytes,
+        )?;
+        self.check_active(append.context_id)?;
+        self.turn_store.get_head(append.context_id)?;
+        if append.parent_turn_id != 0 {
+            self.turn_store
+                .get_turn(append.parent_turn_id)
+                .map_err(|_| StoreError::TurnNotFound {
+                    what: "parent turn",
+                    turn_id: append.parent_turn_id,
+                })?;
+        }
+        if let Some(fs_root_hash) = &append.fs_root_hash {
+            if !self.blob_store.contains(fs_root_hash) {
+                return Err(StoreError::NotFound("fs root tree blob".into()));
+            }
+        }
+        if append.ttl_ms == Some(0) {
+            return Err(StoreError::InvalidInput("ttl must be positive".into()));
+        }
+        self.check_links(append.context_id, append.parent_turn_id, &append.links)?;
+        if let Some(writer) = &append.writer {
+            self.writers.check(append.context_id, writer)?;
+            let key = (append.context_id, writer.writer_id.as_str());
+            if let Some(&last_seq) = writer_seqs.get(&key) {
+                if writer.writer_seq <= last_seq {
+                    return Err(StoreError::WriterSequenceConflict {
+                        writer_id: writer.writer_id.clone(),
+                        last_seq,
+                        writer_seq: writer.writer_seq,
+                    });
+                }
+            }
+            writer_seqs.insert(key, writer.writer_seq);
+        }
+        Ok(())
+    }
+
+    /// Record a summary turn that replaces the history of `context_id` up to
+    /// and including `up_to_turn_id` in default reads.
+    ///
+    /// `up_to_turn_id` must be on the context's current history. The summary
+    /// is appended like any other turn; compacted turns stay stored and
+    /// addressable by id.
+    #[allow(clippy::too_many_arguments)]
+    pub fn compact_context(
+        &mut self,
+        context_id: u64,
+        up_to_turn_id: u64,
+        parent_turn_id: u64,
+        declared_type_id: String,
+        declared_type_version: u32,
+        encoding: u32,
+        compression: u32,
+        uncompressed_len: u32,
+        content_hash: [u8; 32],
+        payload_bytes: &[u8],
+    ) -> Result<(TurnRecord, Option<ContextMetadata>)> {
+        self.check_active(context_id)?;
+        let head = self.turn_store.get_head(context_id)?;
+        let mut current = head.head_turn_id;
+        while current != 0 && current != up_to_turn_id {
+            current = self.turn_store.get_turn(current)?.stored_parent();
+        }
+        if current == 0 {
+            return Err(StoreError::InvalidInput(format!(
+                "turn {up_to_turn_id} is not in the history of context {context_id}"
+            )));
+        }
+
+        let (record, metadata) = self.append_turn(
+            context_id,
+            parent_turn_id,
+            declared_type_id,
+            declared_type_version,
+            encoding,
+            compression,
+            uncompressed_len,
+            content_hash,
+            payload_bytes,
+        )?;
+        self.compactions.insert(record.turn_id, up_to_turn_id)?;
+        Ok((record, metadata))
+    }
+
+    /// Set `turn_id` to expire `ttl_ms` milliseconds from now.
+    pub fn set_turn_ttl(&mut self, turn_id: u64, ttl_ms: u64) -> Result<()> {
+        if ttl_ms == 0 {
+            return Err(StoreError::InvalidInput("ttl must be positive".into()));
+        }
+        self.turn_store.get_turn(turn_id)?;
+        let expires_at = TurnStore::now_unix_ms().saturating_add(ttl_ms);
+        self.expiry.insert(turn_id, expires_at)
+    }
+
+    /// Newest `limit` turns of a context, including compacted history.
+    /// Expired turns are skipped unless `include_expired` is set.
     pub fn get_last(
         &mut self,
         context_id: u64,
         limit: u32,
         include_payload: bool,
+        include_expired: bool,
     ) -> Result<Vec<TurnWithMeta>> {
-        let turns = self.turn_store.get_last(context_id, limit)?;
-        let mut out = Vec::with_capacity(turns.len());
-        for record in turns {
-            let meta = self.turn_store.get_turn_meta(record.turn_id)?;
-            let payload = if include_payload {
-                Some(self.blob_store.get(&record.payload_hash)?)
-            } else {
-                None
+        let scope = GetLastScope {
+            include_compacted: true,
+            include_expired,
+            ..GetLastScope::default()
+        };
+        self.get_last_scoped(context_id, limit, include_payload, &scope)
+    }
+
+    /// Like [`Store::get_last`], but ends at the newest compaction: turns
+    /// covered by a summary turn on the way back from the head are left out.
+    pub fn get_last_compacted(
+        &mut self,
+        context_id: u64,
+        limit: u32,
+        include_payload: bool,
+        include_expired: bool,
+    ) -> Result<Vec<TurnWithMeta>> {
+        let scope = GetLastScope {
+            include_expired,
+            ..GetLastScope::default()
+        };
+        self.get_last_scoped(context_id, limit, include_payload, &scope)
+    }
+
+    /// A page of up to `limit` turns older than `before_turn_id`, read like
+    /// [`Store::get_last`] (or [`Store::get_last_compacted`] without
+    /// `include_compacted`) from the head. Turns from the head down to the
+    /// cursor are walked but not returned, so a compaction summary above
+    /// the cursor still ends the page at its boundary.
+    pub fn get_last_before(
+        &mut self,
+        context_id: u64,
+        before_turn_id: u64,
+        limit: u32,
+        include_payload: bool,
+        include_compacted: bool,
+        include_expired: bool,
+    ) -> Result<Vec<TurnWithMeta>> {
+        let scope = GetLastScope {
+            before_turn_id,
+            include_compacted,
+            include_expired,
+            ..GetLastScope::default()
+        };
+        self.get_last_scoped(context_id, limit, include_payload, &scope)
+    }
+
+    /// Newest `limit` turns of a context within `scope`, oldest first. The
+    /// limit counts only turns in scope.
+    pub fn get_last_scoped(
+        &mut self,
+        context_id: u64,
+        limit: u32,
+        include_payload: bool,
+        scope: &GetLastScope,
+    ) -> Result<Vec<TurnWithMeta>> {
+        self.check_active(context_id)?;
+        let now_ms = TurnStore::now_unix_ms();
+        let compactions = &self.compactions;
+        let expiry = &self.expiry;
+        let mut boundaries = Vec::new();
+        let turns = self
+            .turn_store
+            .get_last_filtered(context_id, limit, |record| {
+                if !scope.include_compacted {
+                    if boundaries.contains(&record.turn_id) {
+                        return Walk::Stop;
+                    }
+                    boundaries.extend(compactions.boundary(record.turn_id));
+                }
+                // Depths fall by one per parent, so nothing older is in
+                // range either.
+                if record.depth < *scope.depths.start() {
+                    return Walk::Stop;
+                }
+                // Turn ids grow along a chain, so this skips the turns
+                // from the head down to the cursor.
+                if scope.before_turn_id != 0 && record.turn_id >= scope.before_turn_id {
+                    return Walk::Skip;
+                }
+                if record.depth > *scope.depths.end() {
+                    return Walk::Skip;
+                }
+                if !scope.include_expired && expiry.is_expired(record.turn_id, now_ms) {
+                    return Walk::Skip;
+                }
+                Walk::Keep
+            })?;
+        self.with_meta(turns, include_payload, now_ms)
+    }
+
+    /// Turns per declared type along the history of `context_id`, read with
+    /// the same scope as [`Store::get_last_scoped`]. Payloads are not read.
+    pub fn type_histogram(
+        &mut self,
+        context_id: u64,
+        scope: &GetLastScope,
+    ) -> Result<BTreeMap<String, u64>> {
+        let turns = self.get_last_scoped(context_id, u32::MAX, false, scope)?;
+        let mut counts = BTreeMap::new();
+        for turn in turns {
+            *counts.entry(turn.meta.declared_type_id).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Turn count, payload bytes and creation time range of everything
+    /// stored along the history of `context_id`, compacted and expired
+    /// turns included. Payloads are not read.
+    pub fn context_stats(&mut self, context_id: u64) -> Result<ContextStats> {
+        let scope = GetLastScope {
+            include_compacted: true,
+            include_expired: true,
+            ..GetLastScope::default()
+        };
+        let turns = self.get_last_scoped(context_id, u32::MAX, false, &scope)?;
+        let mut stats = ContextStats::default();
+        for turn in &turns {
+            let bytes = u64::from(turn.meta.uncompressed_len);
+            stats.turns += 1;
+            stats.payload_bytes += bytes;
+            *stats
+                .bytes_by_type
+                .entry(turn.meta.declared_type_id.clone())
+                .or_insert(0) += bytes;
+        }
+        if let (Some(first), Some(last)) = (turns.first(), turns.last()) {
+            stats.first_created_at_unix_ms = first.record.created_at_unix_ms;
+            stats.last_created_at_unix_ms = last.record.created_at_unix_ms;
+        }
+        Ok(stats)
+    }
+
+    /// The newest `limit` turns of a context matching `search`, oldest first,
+    /// each with the matched field value. Searches the full history,
+    /// compacted turns included, and skips expired and redacted turns. Only msgpack
+    /// payloads are searched.
+    pub fn search_turns(
+        &mut self,
+        context_id: u64,
+        search: &TurnSearch,
+        limit: u32,
+    ) -> Result<Vec<(TurnWithMeta, Value)>> {
+        self.check_active(context_id)?;
+        let now_ms = TurnStore::now_unix_ms();
+        let turn_store = &self.turn_store;
+        let expiry = &self.expiry;
+        let redactions = &self.redactions;
+        let candidates = turn_store.get_last_filtered(context_id, u32::MAX, |record| {
+            if expiry.is_expired(record.turn_id, now_ms) || redactions.is_redacted(record.turn_id) {
+                return Walk::Skip;
+            }
+            let Ok(meta) = turn_store.get_turn_meta(record.turn_id) else {
+                return Walk::Skip;
             };
-            out.push(TurnWithMeta {
-                record,
-                meta,
-                payload,
+            let type_matches = search
+                .type_id
+                .as_ref()
+                .is_none_or(|type_id| *type_id == meta.declared_type_id);
+            if meta.encoding != ENCODING_MSGPACK || !type_matches {
+                return Walk::Skip;
+            }
+            Walk::Keep
+        })?;
+
+        let mut hits = Vec::new();
+        for record in candidates.into_iter().rev() {
+            if hits.len() >= limit as usize {
+                break;
+            }
+            let payload = self.blob_store.get(&record.payload_hash)?;
+            if let Some(value) = search.find(&payload) {
+                hits.push((record, value));
+            }
+        }
+        hits.reverse();
+        let (records, values): (Vec<_>, Vec<_>) = hits.into_iter().unzip();
+        let turns = self.with_meta(records, false, now_ms)?;
+        Ok(turns.into_iter().zip(values).collect())
+    }
+
+    /// The newest `search.limit` turns holding every word of `search.text`,
+    /// newest first, each with a snippet of its payload (see
+    /// [`crate::fulltext`]). Compacted turns are searched; expired and
+    /// redacted turns never match.
+    pub fn search_text(&mut self, search: &TextSearch) -> Result<Vec<TextHit>> {
+        if fulltext::words_of(&search.text).next().is_none() {
+            return Err(StoreError::InvalidInput("search text has no words".into()));
+        }
+        let scope = match search.context_id {
+            Some(context_id) => {
+                self.check_active(context_id)?;
+                let chain = self.turn_store.get_last(context_id, u32::MAX)?;
+                Some((
+                    context_id,
+                    chain.iter().map(|r| r.turn_id).collect::<HashSet<_>>(),
+                ))
+            }
+            None => None,
+        };
+        self.build_text_index()?;
+        let index = self.text_index.as_ref().expect("text index built");
+
+        let now_ms = TurnStore::now_unix_ms();
+        let mut found = Vec::new();
+        for turn_id in index.matches(&search.text) {
+            if found.len() >= search.limit as usize {
+                break;
+            }
+            if search.before_turn_id != 0 && turn_id >= search.before_turn_id {
+                continue;
+            }
+            let context_id = match &scope {
+                Some((context_id, chain)) if chain.contains(&turn_id) => *context_id,
+                Some(_) => continue,
+                None => match index.home_context(turn_id) {
+                    // Archived contexts are not searched.
+                    Some(context_id) if self.check_active(context_id).is_ok() => context_id,
+                    _ => continue,
+                },
+            };
+            if self.expiry.is_expired(turn_id, now_ms) || self.redactions.is_redacted(turn_id) {
+                continue;
+            }
+            let (Ok(record), Ok(meta)) = (
+                self.turn_store.get_turn(turn_id),
+                self.turn_store.get_turn_meta(turn_id),
+            ) else {
+                continue;
+            };
+            let type_matches = search
+                .type_id
+                .as_ref()
+                .is_none_or(|type_id| *type_id == meta.declared_type_id);
+            if type_matches {
+                found.push((context_id, record));
+            }
+        }
+
+        let mut hits = Vec::with_capacity(found.len());
+        for (context_id, record) in found {
+            let payload = self.blob_store.get(&record.payload_hash)?;
+            let snippet = fulltext::snippet(&payload, &search.text);
+            let turn = self.with_meta(vec![record], false, now_ms)?.remove(0);
+            hits.push(TextHit {
+                context_id,
+                turn,
+                snippet,
             });
         }
-        Ok(out)
+        Ok(hits)
+    }
+
+    /// Index every stored payload, unless that was done already. Each turn
+    /// is attributed to the oldest context whose history reaches it.
+    fn build_text_index(&mut self) -> Result<()> {
+        if self.text_index.is_some() {
+            return Ok(());
+        }
+        let mut index = TextIndex::default();
+        let mut contexts = self.turn_store.list_recent_contexts(u32::MAX);
+        contexts.sort_by_key(|head| head.context_id);
+        for head in contexts {
+            for record in self.turn_store.get_last(head.context_id, u32::MAX)? {
+                if index.home_context(record.turn_id).is_some()
+                    || self.redactions.is_redacted(record.turn_id)
+                {
+                    continue;
+                }
+                let meta = self.turn_store.get_turn_meta(record.turn_id)?;
+                let payload = self.blob_store.get(&record.payload_hash)?;
+                index.insert(record.turn_id, head.context_id, meta.encoding, &payload);
+            }
+        }
+        self.text_index = Some(index);
+        Ok(())
+    }
+
+    /// The newest turn in a context's history, compacted turns included,
+    /// whose payload hash is `hash`. Expired and redacted turns never match.
+    pub fn find_turn_by_hash(
+        &self,
+        context_id: u64,
+        hash: &[u8; 32],
+    ) -> Result<Option<TurnRecord>> {
+        let now_ms = TurnStore::now_unix_ms();
+        let expiry = &self.expiry;
+        let redactions = &self.redactions;
+        let found = self.turn_store.get_last_filtered(context_id, 1, |record| {
+            if record.payload_hash != *hash
+                || expiry.is_expired(record.turn_id, now_ms)
+                || redactions.is_redacted(record.turn_id)
+            {
+                return Walk::Skip;
+            }
+            Walk::Keep
+        })?;
+        Ok(found.into_iter().next())
+    }
+
+    /// Delete the turns of `context_id` below `keep_from_depth`. The oldest
+    /// turn kept becomes a root marked as having a pruned parent, and reads
+    /// that reach a pruned turn fail with [`StoreError::Pruned`].
+    ///
+    /// Turns another context still reaches (a fork point and everything
+    /// below it) are kept, so pruning one context never rewrites another's
+    /// history. Payload blobs are content addressed and may be shared, so
+    /// they stay in the blob store.
+    pub fn prune_context(&mut self, context_id: u64, keep_from_depth: u64) -> Result<PruneResult> {
+        self.check_active(context_id)?;
+        let head = self.turn_store.get_head(context_id)?;
+        if keep_from_depth == 0 {
+            return Ok(PruneResult::default());
+        }
+        if head.head_turn_id == 0 || keep_from_depth > u64::from(head.head_depth) {
+            return Err(StoreError::InvalidInput(format!(
+                "keep_from_depth {keep_from_depth} is past the head of context {context_id}"
+            )));
+        }
+        let chain = self.turn_store.get_last(context_id, u32::MAX)?;
+        let oldest_depth = u64::from(chain[0].depth);
+        if keep_from_depth <= oldest_depth {
+            return Ok(PruneResult::default());
+        }
+        let root = (keep_from_depth - oldest_depth) as usize;
+
+        let other_heads: HashSet<u64> = self
+            .turn_store
+            .list_recent_contexts(u32::MAX)
+            .into_iter()
+            .filter(|other| other.context_id != context_id)
+            .map(|other| other.head_turn_id)
+            .collect();
+        let mut first_pruned = root;
+        while first_pruned > 0 {
+            let candidate = &chain[first_pruned - 1];
+            let next = chain[first_pruned].turn_id;
+            let shared = other_heads.contains(&candidate.turn_id)
+                || self
+                    .turn_store
+                    .get_children(candidate.turn_id)?
+                    .iter()
+                    .any(|child| child.turn_id != next);
+            if shared {
+                break;
+            }
+            first_pruned -= 1;
+        }
+        let pruned: Vec<u64> = chain[first_pruned..root]
+            .iter()
+            .map(|record| record.turn_id)
+            .collect();
+        if pruned.is_empty() {
+            return Ok(PruneResult::default());
+        }
+
+        // The new root keeps the snapshot it inherited from pruned history.
+        let root_id = chain[root].turn_id;
+        if self.fs_roots.get(root_id).is_none() {
+            if let Some(fs_root) = self.fs_roots.get_inherited(root_id, &self.turn_store) {
+                self.fs_roots.attach(root_id, fs_root)?;
+            }
+        }
+        let bytes_reclaimed = self.turn_store.prune(root_id, &pruned)?;
+        // Tombstones only once the turns are durably gone, so a failed
+        // rewrite never reports live turns as pruned.
+        self.prunes.insert(&pruned)?;
+        if let Some(index) = &mut self.text_index {
+            pruned.iter().for_each(|turn_id| index.remove(*turn_id));
+        }
+        let turn_store = &self.turn_store;
+        self.compactions
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        self.writers
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        self.expiry
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        self.redactions
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        self.links
+            .release_missing(|turn_id| turn_store.get_turn(turn_id).is_ok())?;
+        Ok(PruneResult {
+            turns_pruned: pruned.len() as u64,
+            bytes_reclaimed,
+        })
+    }
+
+    /// Maps a missing turn that was pruned onto [`StoreError::Pruned`].
+    fn check_pruned<T>(&self, turn_id: u64, result: Result<T>) -> Result<T> {
+        match result {
+            Err(StoreError::NotFound(_) | StoreError::TurnNotFound { .. })
+                if self.prunes.is_pruned(turn_id) =>
+            {
+                Err(StoreError::Pruned { turn_id })
+            }
+            other => other,
+        }
+    }
+
+    /// A single turn by id, whether or not it has been compacted or has
+    /// expired.
+    pub fn get_turn(&mut self, turn_id: u64, include_payload: bool) -> Result<TurnWithMeta> {
+        let record = self.check_pruned(turn_id, self.turn_store.get_turn(turn_id))?;
+        let now_ms = TurnStore::now_unix_ms();
+        let mut turns = self.with_meta(vec![record], include_payload, now_ms)?;
+        Ok(turns.remove(0))
+    }
+
+    /// Remove the payload of `turn_id`, which must be on the history of
+    /// `context_id`. The turn keeps its place, type and content hash; reads
+    /// return it with an empty payload and the redaction. Redacting a turn
+    /// again returns the first redaction unchanged.
+    ///
+    /// The payload blob is erased from the blob store unless another
+    /// unredacted turn or a filesystem snapshot still holds the same
+    /// content. A redacted first turn no longer supplies context metadata.
+    pub fn redact_turn(
+        &mut self,
+        context_id: u64,
+        turn_id: u64,
+        reason: &str,
+    ) -> Result<Redaction> {
+        validate_reason(reason)?;
+        self.check_active(context_id)?;
+        let head = self.turn_store.get_head(context_id)?;
+        let record = self.check_pruned(turn_id, self.turn_store.get_turn(turn_id))?;
+        let mut current = head.head_turn_id;
+        while current != 0 && current != turn_id {
+            current = self.turn_store.get_turn(current)?.stored_parent();
+        }
+        if current == 0 {
+            return Err(StoreError::InvalidInput(format!(
+                "turn {turn_id} is not in the history of context {context_id}"
+            )));
+        }
+        if let Some(existing) = self.redactions.get(turn_id) {
+            return Ok(existing.clone());
+        }
+
+        let redaction = Redaction {
+            redacted_at_unix_ms: TurnStore::now_unix_ms(),
+            reason: reason.to_string(),
+        };
+        self.redactions.insert(turn_id, redaction.clone())?;
+        if let Some(index) = &mut self.text_index {
+            index.remove(turn_id);
+        }
+        if !self.blob_in_use(&record.payload_hash) {
+            self.blob_store.remove(&record.payload_hash)?;
+        }
+
+        if record.depth == 0 {
+            let heads = self.turn_store.list_recent_contexts(u32::MAX);
+            for head in &heads {
+                let first = self.turn_store.get_first_turn(head.context_id);
+                if first.is_ok_and(|first| first.turn_id == turn_id) {
+                    self.context_metadata_cache.insert(head.context_id, None);
+                }
+            }
+            self.secondary_indexes = SecondaryIndexes::new();
+            self.secondary_indexes
+                .build_from_cache(&self.context_metadata_cache, &heads);
+        }
+        Ok(redaction)
+    }
+
+    /// Whether an unredacted turn or a filesystem snapshot holds the blob
+    /// `hash`.
+    fn blob_in_use(&mut self, hash: &[u8; 32]) -> bool {
+        let held_by_turn = self
+            .turn_store
+            .turns_with_hash(hash)
+            .into_iter()
+            .any(|turn_id| !self.redactions.is_redacted(turn_id));
+        if held_by_turn {
+            return true;
+        }
+        let mut visited = HashSet::new();
+        self.fs_roots
+            .unique_roots()
+            .iter()
+            .any(|root| self.tree_holds(root, hash, &mut visited))
+    }
+
+    /// Whether the tree `tree_hash` is `hash` or reaches it.
+    fn tree_holds(
+        &mut self,
+        tree_hash: &[u8; 32],
+        hash: &[u8; 32],
+        visited: &mut HashSet<[u8; 32]>,
+    ) -> bool {
+        if tree_hash == hash {
+            return true;
+        }
+        if !visited.insert(*tree_hash) {
+            return false;
+        }
+        let Ok(entries) = crate::fs_store::load_tree_entries(&mut self.blob_store, tree_hash)
+        else {
+            return false;
+        };
+        entries.iter().any(|entry| match entry.hash_array() {
+            Ok(entry_hash) if entry.kind == 1 => self.tree_holds(&entry_hash, hash, visited),
+            Ok(entry_hash) => entry_hash == *hash,
+            Err(_) => false,
+        })
+    }
+
+    /// The children of `turn_id` across every context, whether or not they
+    /// have been compacted or have expired. Fails if `context_id` does not
+    /// exist.
+    pub fn get_children(
+        &mut self,
+        context_id: u64,
+        turn_id: u64,
+        include_payload: bool,
+    ) -> Result<Vec<TurnWithMeta>> {
+        self.check_active(context_id)?;
+        self.turn_store.get_head(context_id)?;
+        let children = self.check_pruned(turn_id, self.turn_store.get_children(turn_id))?;
+        let now_ms = TurnStore::now_unix_ms();
+        self.with_meta(children, include_payload, now_ms)
+    }
+
+    /// Fails unless every target of `links` is in the history a turn
+    /// appended to `context_id` under `parent_turn_id` (0 = the head) would
+    /// have. History runs through fork points, so a fork can link to turns
+    /// it shares with its base context but not to turns appended there
+    /// since.
+    pub fn check_links(
+        &self,
+        context_id: u64,
+        parent_turn_id: u64,
+        links: &[TurnLink],
+    ) -> Result<()> {
+        validate_links(links)?;
+        if links.is_empty() {
+            return Ok(());
+        }
+        let parent_turn_id = match parent_turn_id {
+            0 => self.turn_store.get_head(context_id)?.head_turn_id,
+            parent_turn_id => parent_turn_id,
+        };
+        for link in links {
+            if !self.reaches(parent_turn_id, link.target_turn_id)? {
+                return Err(StoreError::LinkTargetNotFound {
+                    turn_id: link.target_turn_id,
+                    context_id,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `turn_id` is `from` or one of its ancestors.
+    fn reaches(&self, from: u64, turn_id: u64) -> Result<bool> {
+        let Ok(target) = self.turn_store.get_turn(turn_id) else {
+            return Ok(false);
+        };
+        let mut current = from;
+        while current != 0 {
+            let record = self.turn_store.get_turn(current)?;
+            if record.turn_id == turn_id {
+                return Ok(true);
+            }
+            // Depths fall by one per parent.
+            if record.depth <= target.depth {
+                return Ok(false);
+            }
+            current = record.stored_parent();
+        }
+        Ok(false)
+    }
+
+    /// The turns `turn_id` links to ([`LinkDirection::Outgoing`]) or the
+    /// turns in the history of `context_id` that link to it
+    /// ([`LinkDirection::Incoming`]), oldest first, whether or not they have
+    /// been compacted or have expired. `turn_id` must be in the history of
+    /// `context_id`.
+    pub fn get_linked(
+        &mut self,
+        context_id: u64,
+        turn_id: u64,
+        direction: LinkDirection,
+        include_payload: bool,
+    ) -> Result<Vec<TurnWithMeta>> {
+        self.check_active(context_id)?;
+        let head = self.turn_store.get_head(context_id)?;
+        let record = self.check_pruned(turn_id, self.turn_store.get_turn(turn_id))?;
+        if !self.reaches(head.head_turn_id, turn_id)? {
+            return Err(StoreError::InvalidInput(format!(
+                "turn {turn_id} is not in the history of context {context_id}"
+            )));
+        }
+        let mut linked: Vec<u64> = match direction {
+            LinkDirection::Outgoing => self
+                .links
+                .get(turn_id)
+                .iter()
+                .map(|link| link.target_turn_id)
+                .collect(),
+            LinkDirection::Incoming => {
+                // Linking turns descend from `turn_id`; keep those on this
+                // context's history rather than a fork's.
+                let mut history = HashSet::new();
+                let mut current = head.head_turn_id;
+                while current != 0 {
+                    let ancestor = self.turn_store.get_turn(current)?;
+                    if ancestor.depth <= record.depth {
+                        break;
+                    }
+                    history.insert(current);
+                    current = ancestor.stored_parent();
+                }
+                self.links
+                    .incoming(turn_id)
+                    .filter(|source| history.contains(source))
+                    .collect()
+            }
+        };
+        linked.sort_unstable();
+        linked.dedup();
+        let records = linked
+            .into_iter()
+            .map(|id| self.turn_store.get_turn(id))
+            .collect::<Result<Vec<_>>>()?;
+        let now_ms = TurnStore::now_unix_ms();
+        self.with_meta(records, include_payload, now_ms)
     }
 
     pub fn get_before(
@@ -280,22 +1473,54 @@ impl Store {
         before_turn_id: u64,
         limit: u32,
         include_payload: bool,
+        include_expired: bool,
+    ) -> Result<Vec<TurnWithMeta>> {
+        self.check_active(context_id)?;
+        let now_ms = TurnStore::now_unix_ms();
+        let expiry = &self.expiry;
+        let turns =
+            self.turn_store
+                .get_before_filtered(context_id, before_turn_id, limit, |record| {
+                    if !include_expired && expiry.is_expired(record.turn_id, now_ms) {
+                        return Walk::Skip;
+                    }
+                    Walk::Keep
+                })?;
+        self.with_meta(turns, include_payload, now_ms)
+    }
+
+    fn with_meta(
+        &mut self,
+        turns: Vec<TurnRecord>,
+        include_payload: bool,
+        now_ms: u64,
     ) -> Result<Vec<TurnWithMeta>> {
-        let turns = self
-            .turn_store
-            .get_before(context_id, before_turn_id, limit)?;
         let mut out = Vec::with_capacity(turns.len());
         for record in turns {
-            let meta = self.turn_store.get_turn_meta(record.turn_id)?;
-            let payload = if include_payload {
-                Some(self.blob_store.get(&record.payload_hash)?)
-            } else {
+            let mut meta = self.turn_store.get_turn_meta(record.turn_id)?;
+            let redaction = self.redactions.get(record.turn_id).cloned();
+            let payload = if !include_payload {
                 None
+            } else if redaction.is_some() {
+                Some(Vec::new())
+            } else {
+                Some(self.blob_store.get(&record.payload_hash)?)
             };
+            if redaction.is_some() {
+                meta.uncompressed_len = 0;
+            }
+            let writer = self.writers.get(record.turn_id).cloned();
+            let expires_at_unix_ms = self.expiry.expires_at(record.turn_id);
+            let links = self.links.get(record.turn_id).to_vec();
             out.push(TurnWithMeta {
+                expired: expires_at_unix_ms.is_some_and(|at| at <= now_ms),
                 record,
                 meta,
                 payload,
+                writer,
+                expires_at_unix_ms,
+                redaction,
+                links,
             });
         }
         Ok(out)
@@ -309,6 +1534,17 @@ impl Store {
         self.turn_store.list_recent_contexts(limit)
     }
 
+    /// Up to `limit` contexts, most recently active first. `archived`
+    /// keeps only the contexts in that state.
+    pub fn list_contexts(&self, limit: u32, archived: Option<bool>) -> Vec<ContextHead> {
+        let mut contexts = self.turn_store.list_recent_contexts(u32::MAX);
+        if let Some(archived) = archived {
+            contexts.retain(|head| (head.flags & CONTEXT_FLAG_ARCHIVED != 0) == archived);
+        }
+        contexts.truncate(limit as usize);
+        contexts
+    }
+
     // =========================================================================
     // CQL Search Methods
     // =========================================================================
@@ -539,6 +1775,39 @@ pub struct StoreStats {
     pub fs_content_bytes: u64,
 }
 
+/// Decompresses an appended payload and checks its length and hash.
+fn verified_payload(
+    compression: u32,
+    uncompressed_len: u32,
+    content_hash: &[u8; 32],
+    payload_bytes: &[u8],
+) -> Result<Vec<u8>> {
+    let raw_bytes = match compression {
+        0 => payload_bytes.to_vec(),
+        1 => zstd::decode_all(payload_bytes)
+            .map_err(|e| StoreError::InvalidInput(format!("zstd decode failed: {e}")))?,
+        other => {
+            return Err(StoreError::InvalidInput(format!(
+                "unsupported compression: {other}"
+            )))
+        }
+    };
+
+    if raw_bytes.len() as u32 != uncompressed_len {
+        return Err(StoreError::InvalidInput(
+            "uncompressed length mismatch".into(),
+        ));
+    }
+
+    let mut hasher = Hasher::new();
+    hasher.update(&raw_bytes);
+    let hash = hasher.finalize();
+    if hash.as_bytes() != content_hash {
+        return Err(StoreError::InvalidInput("content hash mismatch".into()));
+    }
+    Ok(raw_bytes)
+}
+
 /// Extract context metadata from a msgpack-encoded ConversationItem payload.
 ///
 /// The payload is expected to be a msgpack map with numeric keys.
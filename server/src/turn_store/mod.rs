@@ -3,7 +3,7 @@
 
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -12,6 +12,18 @@ use crc32fast::Hasher;
 
 use crate::error::{Result, StoreError};
 
+/// [`TurnRecord::flags`] bit: the turn's parent was pruned, so the turn is
+/// the oldest left on its chain. `parent_turn_id` still names the parent.
+pub const TURN_FLAG_PARENT_PRUNED: u32 = 1 << 0;
+
+/// [`ContextHead::flags`] bit: the context is archived, so its history is
+/// neither read nor appended to until it is restored.
+pub const CONTEXT_FLAG_ARCHIVED: u32 = 1 << 0;
+
+/// [`ContextHead::flags`] bit written once, as a tombstone: the context was
+/// deleted. Heads carrying it are dropped on load.
+pub const CONTEXT_FLAG_DELETED: u32 = 1 << 1;
+
 #[derive(Debug, Clone)]
 pub struct TurnRecord {
     pub turn_id: u64,
@@ -24,6 +36,18 @@ pub struct TurnRecord {
     pub created_at_unix_ms: u64,
 }
 
+impl TurnRecord {
+    /// The parent a history walk continues to: 0 at a root, including a
+    /// turn whose parent was pruned.
+    pub fn stored_parent(&self) -> u64 {
+        if self.flags & TURN_FLAG_PARENT_PRUNED != 0 {
+            0
+        } else {
+            self.parent_turn_id
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TurnMeta {
     pub declared_type_id: String,
@@ -42,6 +66,17 @@ pub struct ContextHead {
     pub flags: u32,
 }
 
+/// What a history walk does with the turn it is visiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Walk {
+    /// Return the turn.
+    Keep,
+    /// Leave the turn out and keep walking.
+    Skip,
+    /// Leave the turn out and end the walk.
+    Stop,
+}
+
 pub struct TurnStore {
     turns_log_path: std::path::PathBuf,
     turns_idx_path: std::path::PathBuf,
@@ -54,9 +89,14 @@ pub struct TurnStore {
     heads_tbl: File,
 
     turns: HashMap<u64, TurnRecord>,
+    /// Child turn ids of each turn, in append order.
+    children: HashMap<u64, Vec<u64>>,
     turn_index: HashMap<u64, u64>,
     turn_meta: HashMap<u64, TurnMeta>,
     heads: HashMap<u64, ContextHead>,
+    /// When each context was created: the time on its first head record.
+    /// `ContextHead::created_at_unix_ms` moves with every append.
+    created_at: HashMap<u64, u64>,
 
     next_turn_id: u64,
     next_context_id: u64,
@@ -105,9 +145,11 @@ impl TurnStore {
             turns_meta,
             heads_tbl,
             turns: HashMap::new(),
+            children: HashMap::new(),
             turn_index: HashMap::new(),
             turn_meta: HashMap::new(),
             heads: HashMap::new(),
+            created_at: HashMap::new(),
             next_turn_id: 1,
             next_context_id: 1,
         };
@@ -135,6 +177,7 @@ impl TurnStore {
 
     fn load_turns(&mut self) -> Result<()> {
         self.turns.clear();
+        self.children.clear();
         self.turn_index.clear();
 
         self.turns_log.seek(SeekFrom::Start(0))?;
@@ -156,6 +199,7 @@ impl TurnStore {
                 Err(e) => return Err(e),
             };
 
+            self.add_child(&record);
             self.turns.insert(record.turn_id, record.clone());
             self.turn_index.insert(record.turn_id, offset);
             offset = self.turns_log.stream_position()?;
@@ -234,6 +278,7 @@ impl TurnStore {
 
     fn load_heads(&mut self) -> Result<()> {
         self.heads.clear();
+        self.created_at.clear();
         self.heads_tbl.seek(SeekFrom::Start(0))?;
         loop {
             let start = self.heads_tbl.stream_position()?;
@@ -292,6 +337,17 @@ impl TurnStore {
                 break;
             }
 
+            // Deleted ids stay taken, so a tombstone of the newest context
+            // still advances the id counter.
+            self.next_context_id = self.next_context_id.max(context_id + 1);
+            if flags & CONTEXT_FLAG_DELETED != 0 {
+                self.heads.remove(&context_id);
+                self.created_at.remove(&context_id);
+                continue;
+            }
+            self.created_at
+                .entry(context_id)
+                .or_insert(created_at_unix_ms);
             self.heads.insert(
                 context_id,
                 ContextHead {
@@ -322,11 +378,11 @@ impl TurnStore {
             self.next_turn_id = max_id + 1;
         }
         if let Some(max_ctx) = self.heads.keys().max().cloned() {
-            self.next_context_id = max_ctx + 1;
+            self.next_context_id = self.next_context_id.max(max_ctx + 1);
         }
     }
 
-    fn now_unix_ms() -> u64 {
+    pub(crate) fn now_unix_ms() -> u64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
@@ -340,7 +396,10 @@ impl TurnStore {
             let turn = self
                 .turns
                 .get(&base_turn_id)
-                .ok_or_else(|| StoreError::NotFound("base turn".into()))?;
+                .ok_or(StoreError::TurnNotFound {
+                    what: "base turn",
+                    turn_id: base_turn_id,
+                })?;
             (turn.turn_id, turn.depth)
         };
 
@@ -356,6 +415,7 @@ impl TurnStore {
         };
 
         self.write_head(&head)?;
+        self.created_at.insert(context_id, head.created_at_unix_ms);
         self.heads.insert(context_id, head.clone());
         Ok(head)
     }
@@ -368,7 +428,7 @@ impl TurnStore {
         self.heads
             .get(&context_id)
             .cloned()
-            .ok_or_else(|| StoreError::NotFound("context".into()))
+            .ok_or(StoreError::ContextNotFound { context_id })
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -387,20 +447,26 @@ impl TurnStore {
             let parent = self
                 .turns
                 .get(&parent_turn_id)
-                .ok_or_else(|| StoreError::NotFound("parent turn".into()))?;
+                .ok_or(StoreError::TurnNotFound {
+                    what: "parent turn",
+                    turn_id: parent_turn_id,
+                })?;
             (parent.turn_id, parent.depth + 1)
         } else {
             let head = self
                 .heads
                 .get(&context_id)
-                .ok_or_else(|| StoreError::NotFound("context".into()))?;
+                .ok_or(StoreError::ContextNotFound { context_id })?;
             if head.head_turn_id == 0 {
                 (0, 0)
             } else {
-                let parent = self
-                    .turns
-                    .get(&head.head_turn_id)
-                    .ok_or_else(|| StoreError::NotFound("head turn".into()))?;
+                let parent =
+                    self.turns
+                        .get(&head.head_turn_id)
+                        .ok_or(StoreError::TurnNotFound {
+                            what: "head turn",
+                            turn_id: head.head_turn_id,
+                        })?;
                 (parent.turn_id, parent.depth + 1)
             }
         };
@@ -430,28 +496,20 @@ impl TurnStore {
         self.turns_idx.flush()?;
 
         // store meta
-        let mut meta_bytes = Vec::new();
-        meta_bytes.write_u64::<LittleEndian>(turn_id)?;
-        meta_bytes.write_u32::<LittleEndian>(declared_type_id.len() as u32)?;
-        meta_bytes.extend_from_slice(declared_type_id.as_bytes());
-        meta_bytes.write_u32::<LittleEndian>(declared_type_version)?;
-        meta_bytes.write_u32::<LittleEndian>(encoding)?;
-        meta_bytes.write_u32::<LittleEndian>(compression)?;
-        meta_bytes.write_u32::<LittleEndian>(uncompressed_len)?;
+        let meta = TurnMeta {
+            declared_type_id,
+            declared_type_version,
+            encoding,
+            compression,
+            uncompressed_len,
+        };
+        let meta_bytes = encode_turn_meta(turn_id, &meta)?;
         self.turns_meta.seek(SeekFrom::End(0))?;
         self.turns_meta.write_all(&meta_bytes)?;
         self.turns_meta.flush()?;
 
-        self.turn_meta.insert(
-            turn_id,
-            TurnMeta {
-                declared_type_id,
-                declared_type_version,
-                encoding,
-                compression,
-                uncompressed_len,
-            },
-        );
+        self.turn_meta.insert(turn_id, meta);
+        self.add_child(&record);
         self.turns.insert(turn_id, record.clone());
         self.turn_index.insert(turn_id, offset);
 
@@ -461,7 +519,7 @@ impl TurnStore {
             head_turn_id: turn_id,
             head_depth: depth,
             created_at_unix_ms: record.created_at_unix_ms,
-            flags: 0,
+            flags: self.heads.get(&context_id).map_or(0, |head| head.flags),
         };
         self.write_head(&head)?;
         self.heads.insert(context_id, head);
@@ -469,6 +527,33 @@ impl TurnStore {
         Ok(record)
     }
 
+    /// Replace the flags of `context_id`'s head, keeping the head itself.
+    pub fn set_context_flags(&mut self, context_id: u64, flags: u32) -> Result<ContextHead> {
+        let mut head = self.get_head(context_id)?;
+        if head.flags != flags {
+            head.flags = flags;
+            self.write_head(&head)?;
+            self.heads.insert(context_id, head.clone());
+        }
+        Ok(head)
+    }
+
+    /// Delete `context_id` by writing a tombstone head. Its turns stay, as
+    /// forks may share them.
+    pub fn delete_context(&mut self, context_id: u64) -> Result<()> {
+        let mut head = self.get_head(context_id)?;
+        head.flags |= CONTEXT_FLAG_DELETED;
+        self.write_head(&head)?;
+        self.heads.remove(&context_id);
+        self.created_at.remove(&context_id);
+        Ok(())
+    }
+
+    /// When `context_id` was created, as opposed to its head's time.
+    pub fn context_created_at(&self, context_id: u64) -> Option<u64> {
+        self.created_at.get(&context_id).copied()
+    }
+
     fn write_head(&mut self, head: &ContextHead) -> Result<()> {
         let mut buf = Vec::with_capacity(8 + 8 + 4 + 4 + 8 + 4);
         buf.write_u64::<LittleEndian>(head.context_id)?;
@@ -490,35 +575,157 @@ impl TurnStore {
         self.turns
             .get(&turn_id)
             .cloned()
-            .ok_or_else(|| StoreError::NotFound("turn".into()))
+            .ok_or(StoreError::TurnNotFound {
+                what: "turn",
+                turn_id,
+            })
+    }
+
+    /// Ids of every turn whose payload hash is `hash`, in any context.
+    pub fn turns_with_hash(&self, hash: &[u8; 32]) -> Vec<u64> {
+        self.turns
+            .values()
+            .filter(|record| record.payload_hash == *hash)
+            .map(|record| record.turn_id)
+            .collect()
+    }
+
+    /// The turns whose parent is `turn_id`, in append order, whichever
+    /// contexts they were appended to.
+    pub fn get_children(&self, turn_id: u64) -> Result<Vec<TurnRecord>> {
+        if !self.turns.contains_key(&turn_id) {
+            return Err(StoreError::TurnNotFound {
+                what: "turn",
+                turn_id,
+            });
+        }
+        let children = self.children.get(&turn_id).map(Vec::as_slice);
+        Ok(children
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|child| self.turns.get(child).cloned())
+            .collect())
+    }
+
+    fn add_child(&mut self, record: &TurnRecord) {
+        if record.parent_turn_id != 0 {
+            self.children
+                .entry(record.parent_turn_id)
+                .or_default()
+                .push(record.turn_id);
+        }
+    }
+
+    /// Deletes `pruned` (turns below `new_root` on its chain) and marks
+    /// `new_root` as having a pruned parent, then rewrites the turn log,
+    /// index and meta without them. Returns the bytes freed.
+    pub fn prune(&mut self, new_root: u64, pruned: &[u64]) -> Result<u64> {
+        let before = file_len(&self.turns_log_path) + file_len(&self.turns_meta_path);
+        for turn_id in pruned {
+            let Some(record) = self.turns.remove(turn_id) else {
+                continue;
+            };
+            self.turn_meta.remove(turn_id);
+            self.turn_index.remove(turn_id);
+            self.children.remove(turn_id);
+            if let Some(siblings) = self.children.get_mut(&record.parent_turn_id) {
+                siblings.retain(|child| child != turn_id);
+            }
+        }
+        let root = self
+            .turns
+            .get_mut(&new_root)
+            .ok_or(StoreError::TurnNotFound {
+                what: "turn",
+                turn_id: new_root,
+            })?;
+        root.flags |= TURN_FLAG_PARENT_PRUNED;
+
+        let mut turn_ids: Vec<u64> = self.turns.keys().copied().collect();
+        turn_ids.sort_unstable();
+
+        // Both files are written aside and renamed into place, so a crash
+        // leaves each with either its old or its new contents.
+        let mut turn_index = HashMap::with_capacity(turn_ids.len());
+        replace_file(&self.turns_log_path, |out| {
+            let mut offset = 0u64;
+            for turn_id in &turn_ids {
+                let bytes = encode_turn_record(&self.turns[turn_id])?;
+                out.write_all(&bytes)?;
+                turn_index.insert(*turn_id, offset);
+                offset += bytes.len() as u64;
+            }
+            Ok(())
+        })?;
+        replace_file(&self.turns_meta_path, |out| {
+            for turn_id in &turn_ids {
+                if let Some(meta) = self.turn_meta.get(turn_id) {
+                    out.write_all(&encode_turn_meta(*turn_id, meta)?)?;
+                }
+            }
+            Ok(())
+        })?;
+        if let Some(dir) = self.turns_log_path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        self.turns_log = open_rw(&self.turns_log_path)?;
+        self.turns_meta = open_rw(&self.turns_meta_path)?;
+        self.turn_index = turn_index;
+        self.rebuild_index()?;
+
+        let after = file_len(&self.turns_log_path) + file_len(&self.turns_meta_path);
+        Ok(before.saturating_sub(after))
+    }
+
+    /// The next turn id [`TurnStore::append_turn`] will issue.
+    pub fn next_turn_id(&self) -> u64 {
+        self.next_turn_id
     }
 
     pub fn get_turn_meta(&self, turn_id: u64) -> Result<TurnMeta> {
         self.turn_meta
             .get(&turn_id)
             .cloned()
-            .ok_or_else(|| StoreError::NotFound("turn meta".into()))
+            .ok_or(StoreError::TurnNotFound {
+                what: "turn",
+                turn_id,
+            })
     }
 
     pub fn get_last(&self, context_id: u64, limit: u32) -> Result<Vec<TurnRecord>> {
+        self.get_last_while(context_id, limit, |_| true)
+    }
+
+    /// Like [`TurnStore::get_last`], but stops walking back at the first
+    /// turn for which `keep` returns false (that turn is excluded).
+    pub fn get_last_while(
+        &self,
+        context_id: u64,
+        limit: u32,
+        mut keep: impl FnMut(&TurnRecord) -> bool,
+    ) -> Result<Vec<TurnRecord>> {
+        self.get_last_filtered(context_id, limit, |rec| {
+            if keep(rec) {
+                Walk::Keep
+            } else {
+                Walk::Stop
+            }
+        })
+    }
+
+    /// Like [`TurnStore::get_last`], letting `visit` skip turns (which do not
+    /// count towards `limit`) or end the walk.
+    pub fn get_last_filtered(
+        &self,
+        context_id: u64,
+        limit: u32,
+        visit: impl FnMut(&TurnRecord) -> Walk,
+    ) -> Result<Vec<TurnRecord>> {
         let head = self
             .heads
             .get(&context_id)
-            .ok_or_else(|| StoreError::NotFound("context".into()))?;
-
-        let mut results = Vec::new();
-        let mut current = head.head_turn_id;
-        while current != 0 && results.len() < limit as usize {
-            let rec = self
-                .turns
-                .get(&current)
-                .ok_or_else(|| StoreError::NotFound("turn".into()))?
-                .clone();
-            results.push(rec.clone());
-            current = rec.parent_turn_id;
-        }
-        results.reverse();
-        Ok(results)
+            .ok_or(StoreError::ContextNotFound { context_id })?;
+        self.walk_back(head.head_turn_id, limit, visit)
     }
 
     pub fn get_before(
@@ -526,50 +733,79 @@ impl TurnStore {
         context_id: u64,
         before_turn_id: u64,
         limit: u32,
+    ) -> Result<Vec<TurnRecord>> {
+        self.get_before_filtered(context_id, before_turn_id, limit, |_| Walk::Keep)
+    }
+
+    /// Like [`TurnStore::get_before`], filtered like
+    /// [`TurnStore::get_last_filtered`].
+    pub fn get_before_filtered(
+        &self,
+        context_id: u64,
+        before_turn_id: u64,
+        limit: u32,
+        visit: impl FnMut(&TurnRecord) -> Walk,
     ) -> Result<Vec<TurnRecord>> {
         let head = self
             .heads
             .get(&context_id)
-            .ok_or_else(|| StoreError::NotFound("context".into()))?;
+            .ok_or(StoreError::ContextNotFound { context_id })?;
 
         if before_turn_id == 0 || head.head_turn_id == 0 {
-            return self.get_last(context_id, limit);
+            return self.walk_back(head.head_turn_id, limit, visit);
         }
 
         let before = self
             .turns
             .get(&before_turn_id)
-            .ok_or_else(|| StoreError::NotFound("before turn".into()))?;
-        let mut current = before.parent_turn_id;
+            .ok_or(StoreError::TurnNotFound {
+                what: "before turn",
+                turn_id: before_turn_id,
+            })?;
+        self.walk_back(before.parent_turn_id, limit, visit)
+    }
+
+    /// Collects up to `limit` turns walking back from `start`, oldest first.
+    fn walk_back(
+        &self,
+        start: u64,
+        limit: u32,
+        mut visit: impl FnMut(&TurnRecord) -> Walk,
+    ) -> Result<Vec<TurnRecord>> {
         let mut results = Vec::new();
+        let mut current = start;
         while current != 0 && results.len() < limit as usize {
-            let rec = self
-                .turns
-                .get(&current)
-                .ok_or_else(|| StoreError::NotFound("turn".into()))?
-                .clone();
-            results.push(rec.clone());
-            current = rec.parent_turn_id;
+            let rec = self.turns.get(&current).ok_or(StoreError::TurnNotFound {
+                what: "turn",
+                turn_id: current,
+            })?;
+            match visit(rec) {
+                Walk::Keep => results.push(rec.clone()),
+                Walk::Skip => {}
+                Walk::Stop => break,
+            }
+            current = rec.stored_parent();
         }
         results.reverse();
         Ok(results)
     }
 
-    /// Get the first turn (depth=0) of a context, if it exists.
+    /// Get the first turn of a context (depth 0, or the oldest left after
+    /// pruning), if it exists.
     pub fn get_first_turn(&self, context_id: u64) -> Result<TurnRecord> {
         let head = self
             .heads
             .get(&context_id)
-            .ok_or_else(|| StoreError::NotFound("context".into()))?;
+            .ok_or(StoreError::ContextNotFound { context_id })?;
 
         // Walk back from head to find the turn with depth=0
         let mut current = head.head_turn_id;
         while current != 0 {
-            let rec = self
-                .turns
-                .get(&current)
-                .ok_or_else(|| StoreError::NotFound("turn".into()))?;
-            if rec.depth == 0 {
+            let rec = self.turns.get(&current).ok_or(StoreError::TurnNotFound {
+                what: "turn",
+                turn_id: current,
+            })?;
+            if rec.stored_parent() == 0 {
                 return Ok(rec.clone());
             }
             current = rec.parent_turn_id;
@@ -581,7 +817,7 @@ impl TurnStore {
     pub fn list_recent_contexts(&self, limit: u32) -> Vec<ContextHead> {
         let mut contexts: Vec<ContextHead> = self.heads.values().cloned().collect();
         // Sort by created_at descending (most recent first)
-        contexts.sort_by(|a, b| b.created_at_unix_ms.cmp(&a.created_at_unix_ms));
+        contexts.sort_by_key(|c| std::cmp::Reverse(c.created_at_unix_ms));
         contexts.truncate(limit as usize);
         contexts
     }
@@ -602,6 +838,23 @@ fn file_len(path: &std::path::PathBuf) -> u64 {
     std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
 }
 
+/// Replaces `path` with what `write` produces: written to `path.tmp`,
+/// synced and renamed over it. The caller syncs the directory.
+fn replace_file(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    write(&mut out)?;
+    let file = out.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn open_rw(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().read(true).write(true).open(path)?)
+}
+
 fn encode_turn_record(record: &TurnRecord) -> Result<Vec<u8>> {
     let mut buf = Vec::with_capacity(80);
     buf.write_u64::<LittleEndian>(record.turn_id)?;
@@ -619,6 +872,18 @@ fn encode_turn_record(record: &TurnRecord) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+fn encode_turn_meta(turn_id: u64, meta: &TurnMeta) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(28 + meta.declared_type_id.len());
+    buf.write_u64::<LittleEndian>(turn_id)?;
+    buf.write_u32::<LittleEndian>(meta.declared_type_id.len() as u32)?;
+    buf.extend_from_slice(meta.declared_type_id.as_bytes());
+    buf.write_u32::<LittleEndian>(meta.declared_type_version)?;
+    buf.write_u32::<LittleEndian>(meta.encoding)?;
+    buf.write_u32::<LittleEndian>(meta.compression)?;
+    buf.write_u32::<LittleEndian>(meta.uncompressed_len)?;
+    Ok(buf)
+}
+
 fn read_turn_record(reader: &mut File) -> Result<TurnRecord> {
     let turn_id = reader.read_u64::<LittleEndian>()?;
     let parent_turn_id = reader.read_u64::<LittleEndian>()?;
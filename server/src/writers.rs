@@ -0,0 +1,291 @@
+// Copyright 2025 StrongDM Inc
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-writer sequence stamps on turns.
+//!
+//! A producer appending to a shared context can stamp each turn with its
+//! `writer_id` and a `writer_seq`. Sequences must strictly increase per
+//! (context, writer), so a producer that retries an append it already made
+//! gets a conflict instead of a duplicate turn, and readers can spot gaps.
+//!
+//! # Storage Format
+//!
+//! The writer index (`turns/writers.idx`) is an append-only file of
+//! variable-size records:
+//! - turn_id: u64
+//! - context_id: u64
+//! - writer_id_len: u32 (0 = stamp dropped)
+//! - writer_id: [writer_id_len]u8 (UTF-8)
+//! - writer_seq: u64
+//! - crc32: u32 over the preceding fields
+//!
+//! The file is a [`RecordLog`], so a torn or corrupt tail is truncated on
+//! load.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Result, StoreError};
+use crate::record_log::{Record, RecordLog};
+
+/// Longest writer id accepted, in bytes.
+pub const MAX_WRITER_ID_LEN: usize = 256;
+
+/// The producer of a turn and its position in that producer's sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurnWriter {
+    pub writer_id: String,
+    pub writer_seq: u64,
+}
+
+struct Stamp {
+    context_id: u64,
+    writer: TurnWriter,
+}
+
+/// One stamp in the log; `None` drops the turn's stamp.
+struct WriterRecord {
+    turn_id: u64,
+    context_id: u64,
+    writer: Option<TurnWriter>,
+}
+
+impl Record for WriterRecord {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let (writer_id, writer_seq) = match &self.writer {
+            Some(writer) => (writer.writer_id.as_str(), writer.writer_seq),
+            None => ("", 0),
+        };
+        buf.write_u64::<LittleEndian>(self.turn_id)?;
+        buf.write_u64::<LittleEndian>(self.context_id)?;
+        buf.write_u32::<LittleEndian>(writer_id.len() as u32)?;
+        buf.extend_from_slice(writer_id.as_bytes());
+        buf.write_u64::<LittleEndian>(writer_seq)?;
+        Ok(())
+    }
+
+    fn decode(reader: &mut Cursor<&[u8]>) -> Result<Self> {
+        let turn_id = reader.read_u64::<LittleEndian>()?;
+        let context_id = reader.read_u64::<LittleEndian>()?;
+        let writer_id_len = reader.read_u32::<LittleEndian>()? as usize;
+        if writer_id_len > MAX_WRITER_ID_LEN {
+            return Err(StoreError::Corrupt("writer record too long".into()));
+        }
+        let mut writer_id = vec![0u8; writer_id_len];
+        reader.read_exact(&mut writer_id)?;
+        let writer_seq = reader.read_u64::<LittleEndian>()?;
+        if writer_id.is_empty() {
+            return Ok(Self {
+                turn_id,
+                context_id,
+                writer: None,
+            });
+        }
+        let writer_id = String::from_utf8(writer_id)
+            .map_err(|_| StoreError::Corrupt("writer id not utf8".into()))?;
+        Ok(Self {
+            turn_id,
+            context_id,
+            writer: Some(TurnWriter {
+                writer_id,
+                writer_seq,
+            }),
+        })
+    }
+}
+
+pub struct WriterIndex {
+    log: RecordLog<WriterRecord>,
+    stamps: HashMap<u64, Stamp>,
+    /// (context_id, writer_id) -> highest writer_seq
+    last_seq: HashMap<(u64, String), u64>,
+}
+
+impl WriterIndex {
+    /// Open or create the writer index in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let (log, records) = RecordLog::<WriterRecord>::open(dir, "writers.idx")?;
+        let mut stamps = HashMap::new();
+        for record in records {
+            match record.writer {
+                Some(writer) => stamps.insert(
+                    record.turn_id,
+                    Stamp {
+                        context_id: record.context_id,
+                        writer,
+                    },
+                ),
+                None => stamps.remove(&record.turn_id),
+            };
+        }
+
+        let mut index = Self {
+            log,
+            stamps,
+            last_seq: HashMap::new(),
+        };
+        index.rebuild_last_seq();
+        Ok(index)
+    }
+
+    fn rebuild_last_seq(&mut self) {
+        self.last_seq.clear();
+        for stamp in self.stamps.values() {
+            let last = self
+                .last_seq
+                .entry((stamp.context_id, stamp.writer.writer_id.clone()))
+                .or_insert(stamp.writer.writer_seq);
+            *last = (*last).max(stamp.writer.writer_seq);
+        }
+    }
+
+    /// Fails with [`StoreError::WriterSequenceConflict`] unless
+    /// `writer.writer_seq` is above the writer's last sequence in `context_id`.
+    pub fn check(&self, context_id: u64, writer: &TurnWriter) -> Result<()> {
+        validate_writer_id(&writer.writer_id)?;
+        let key = (context_id, writer.writer_id.clone());
+        match self.last_seq.get(&key) {
+            Some(&last_seq) if writer.writer_seq <= last_seq => {
+                Err(StoreError::WriterSequenceConflict {
+                    writer_id: writer.writer_id.clone(),
+                    last_seq,
+                    writer_seq: writer.writer_seq,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Stamp `turn_id`, appended to `context_id`, with `writer`. Fails like
+    /// [`check`](Self::check) if the sequence does not advance.
+    pub fn insert(&mut self, turn_id: u64, context_id: u64, writer: TurnWriter) -> Result<()> {
+        self.check(context_id, &writer)?;
+        self.log.append(&WriterRecord {
+            turn_id,
+            context_id,
+            writer: Some(writer.clone()),
+        })?;
+        self.last_seq
+            .insert((context_id, writer.writer_id.clone()), writer.writer_seq);
+        self.stamps.insert(turn_id, Stamp { context_id, writer });
+        Ok(())
+    }
+
+    /// Drop stamps of turns `exists` no longer reports, so a reused turn id
+    /// can never inherit a stale stamp.
+    pub fn release_missing(&mut self, exists: impl Fn(u64) -> bool) -> Result<()> {
+        let mut missing: Vec<u64> = self
+            .stamps
+            .keys()
+            .copied()
+            .filter(|id| !exists(*id))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        missing.sort_unstable();
+        for turn_id in missing {
+            let stamp = self.stamps.remove(&turn_id).expect("stamp listed above");
+            self.log.append(&WriterRecord {
+                turn_id,
+                context_id: stamp.context_id,
+                writer: None,
+            })?;
+        }
+        self.rebuild_last_seq();
+        Ok(())
+    }
+
+    /// Writer stamp of `turn_id`, if it has one.
+    pub fn get(&self, turn_id: u64) -> Option<&TurnWriter> {
+        self.stamps.get(&turn_id).map(|stamp| &stamp.writer)
+    }
+}
+
+/// Writer ids are non-empty UTF-8 of at most [`MAX_WRITER_ID_LEN`] bytes.
+pub fn validate_writer_id(writer_id: &str) -> Result<()> {
+    if writer_id.is_empty() {
+        return Err(StoreError::InvalidInput("writer_id is empty".into()));
+    }
+    if writer_id.len() > MAX_WRITER_ID_LEN {
+        return Err(StoreError::InvalidInput(format!(
+            "writer_id longer than {MAX_WRITER_ID_LEN} bytes"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    fn writer(writer_id: &str, writer_seq: u64) -> TurnWriter {
+        TurnWriter {
+            writer_id: writer_id.into(),
+            writer_seq,
+        }
+    }
+
+    #[test]
+    fn sequences_are_enforced_per_context_and_writer() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = WriterIndex::open(tmpdir.path()).unwrap();
+
+        index.insert(1, 10, writer("agent-a", 1)).unwrap();
+        index.insert(2, 10, writer("agent-b", 1)).unwrap();
+        index.insert(3, 10, writer("agent-a", 5)).unwrap();
+        // Same writer in another context has its own sequence.
+        index.insert(4, 11, writer("agent-a", 1)).unwrap();
+
+        for seq in [1, 5] {
+            assert!(matches!(
+                index.check(10, &writer("agent-a", seq)),
+                Err(StoreError::WriterSequenceConflict { last_seq: 5, .. })
+            ));
+        }
+        assert!(matches!(
+            index.check(10, &writer("", 9)),
+            Err(StoreError::InvalidInput(_))
+        ));
+        index.check(10, &writer("agent-a", 6)).unwrap();
+
+        drop(index);
+        let mut index = WriterIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(3), Some(&writer("agent-a", 5)));
+        assert!(index.check(10, &writer("agent-a", 5)).is_err());
+
+        // Dropping turn 3 rolls agent-a back to its previous high-water mark.
+        index.release_missing(|turn_id| turn_id != 3).unwrap();
+        drop(index);
+        let index = WriterIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(3), None);
+        index.check(10, &writer("agent-a", 2)).unwrap();
+    }
+
+    #[test]
+    fn torn_tail_is_truncated() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut index = WriterIndex::open(tmpdir.path()).unwrap();
+        index.insert(1, 10, writer("agent-a", 1)).unwrap();
+        index.insert(2, 10, writer("agent-a", 2)).unwrap();
+        drop(index);
+
+        let path = tmpdir.path().join("writers.idx");
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        let mut index = WriterIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(1), Some(&writer("agent-a", 1)));
+        assert_eq!(index.get(2), None);
+        index.insert(3, 10, writer("agent-a", 2)).unwrap();
+        drop(index);
+        let index = WriterIndex::open(tmpdir.path()).unwrap();
+        assert_eq!(index.get(3), Some(&writer("agent-a", 2)));
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright 2025 StrongDM Inc
 // SPDX-License-Identifier: Apache-2.0
 
-use cxdb_server::projection::project_msgpack;
+use cxdb_server::projection::{project_msgpack, project_payload, ENCODING_CBOR};
 use cxdb_server::projection::{BytesRender, EnumRender, RenderOptions, TimeRender, U64Format};
 use cxdb_server::registry::Registry;
 use rmpv::Value;
@@ -80,6 +80,72 @@ fn registry_ingest_and_project() {
     assert!(unknown_obj.contains_key("9"));
 }
 
+#[test]
+fn cbor_payloads_project_like_msgpack() {
+    let dir = tempdir().expect("tempdir");
+    let mut registry = Registry::open(dir.path()).expect("open registry");
+    let bundle = r#"
+    {
+      "registry_version": 1,
+      "bundle_id": "cbor-test",
+      "types": {
+        "test:Reading": {
+          "versions": {
+            "1": {
+              "fields": {
+                "1": { "name": "sensor", "type": "string" },
+                "2": { "name": "values", "type": "array", "items": "int64" },
+                "3": { "name": "raw", "type": "bytes" }
+              }
+            }
+          }
+        }
+      },
+      "enums": {}
+    }
+    "#;
+    registry
+        .put_bundle("cbor-test", bundle.as_bytes())
+        .expect("put bundle");
+    let desc = registry
+        .get_type_version("test:Reading", 1)
+        .expect("descriptor");
+
+    let msgpack = Value::Map(vec![
+        (Value::Integer(1.into()), Value::String("t1".into())),
+        (
+            Value::Integer(2.into()),
+            Value::Array(vec![Value::Integer(20.into()), Value::Integer((-3).into())]),
+        ),
+        (Value::Integer(3.into()), Value::Binary(vec![0, 1, 2])),
+    ]);
+    let mut msgpack_buf = Vec::new();
+    rmpv::encode::write_value(&mut msgpack_buf, &msgpack).expect("encode msgpack");
+
+    use ciborium::Value as Cbor;
+    let cbor = Cbor::Map(vec![
+        (Cbor::Integer(1.into()), Cbor::Text("t1".into())),
+        (
+            Cbor::Integer(2.into()),
+            Cbor::Array(vec![Cbor::Integer(20.into()), Cbor::Integer((-3).into())]),
+        ),
+        (Cbor::Integer(3.into()), Cbor::Bytes(vec![0, 1, 2])),
+    ]);
+    let mut cbor_buf = Vec::new();
+    ciborium::ser::into_writer(&cbor, &mut cbor_buf).expect("encode cbor");
+
+    let options = default_options();
+    let from_msgpack = project_msgpack(&msgpack_buf, desc, &registry, &options).expect("project");
+    let from_cbor =
+        project_payload(&cbor_buf, ENCODING_CBOR, desc, &registry, &options).expect("project");
+    assert_eq!(from_cbor.data, from_msgpack.data);
+    assert_eq!(from_cbor.data["sensor"], "t1");
+
+    // CBOR bytes are not msgpack, and unknown encodings are refused.
+    assert!(project_msgpack(&cbor_buf, desc, &registry, &options).is_err());
+    assert!(project_payload(&cbor_buf, 9, desc, &registry, &options).is_err());
+}
+
 #[test]
 fn nested_type_references() {
     let dir = tempdir().expect("tempdir");
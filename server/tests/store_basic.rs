@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use blake3::Hasher;
-use cxdb_server::store::Store;
+use cxdb_server::error::StoreError;
+use cxdb_server::links::{LinkDirection, LinkKind, TurnLink};
+use cxdb_server::store::{BatchAppend, ContextStats, DeleteFilter, Store};
+use cxdb_server::writers::TurnWriter;
 use tempfile::tempdir;
 
 #[test]
@@ -55,7 +58,1379 @@ fn append_and_fork() {
 
     assert!(store.blob_store.contains(hash.as_bytes()));
 
-    let last = store.get_last(fork.context_id, 10, true).expect("get last");
+    let last = store
+        .get_last(fork.context_id, 10, true, false)
+        .expect("get last");
     assert_eq!(last.len(), 2);
     assert_eq!(last[0].record.turn_id, first.turn_id);
 }
+
+#[test]
+fn aliases_resolve_to_one_context_across_restarts() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+
+    let (head, created) = store
+        .create_or_get_context_by_alias("session:abc123", 0)
+        .expect("create by alias");
+    assert!(created);
+    let (again, created) = store
+        .create_or_get_context_by_alias("session:abc123", 0)
+        .expect("get by alias");
+    assert!(!created);
+    assert_eq!(again.context_id, head.context_id);
+
+    let (other, _) = store
+        .create_or_get_context_by_alias("session:def456", 0)
+        .expect("second alias");
+    assert_ne!(other.context_id, head.context_id);
+    assert!(store.create_or_get_context_by_alias("", 0).is_err());
+    assert_eq!(store.resolve_alias("session:missing"), None);
+
+    // Whatever survives a restart, an alias never points at a missing context.
+    drop(store);
+    let mut store = Store::open(dir.path()).expect("reopen store");
+    let (reopened, _) = store
+        .create_or_get_context_by_alias("session:abc123", 0)
+        .expect("get after reopen");
+    assert_eq!(
+        store.resolve_alias("session:abc123"),
+        Some(reopened.context_id)
+    );
+    store
+        .get_head(reopened.context_id)
+        .expect("aliased context exists");
+}
+
+#[test]
+fn racing_alias_creators_share_a_context() {
+    use std::sync::{Arc, Mutex};
+
+    let dir = tempdir().expect("tempdir");
+    let store = Arc::new(Mutex::new(Store::open(dir.path()).expect("open store")));
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                store
+                    .lock()
+                    .unwrap()
+                    .create_or_get_context_by_alias("session:race", 0)
+                    .expect("create by alias")
+            })
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(results.iter().filter(|(_, created)| *created).count(), 1);
+    assert!(results
+        .iter()
+        .all(|(head, _)| head.context_id == results[0].0.context_id));
+}
+
+#[test]
+fn compaction_hides_history_from_default_reads() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                ctx.context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    let turns: Vec<_> = (0..5u8).map(|i| append(&mut store, &[i])).collect();
+
+    let summary_payload = b"summary of turns 0-3".to_vec();
+    let hash = blake3::hash(&summary_payload);
+    let (summary, _) = store
+        .compact_context(
+            ctx.context_id,
+            turns[3].turn_id,
+            0,
+            "com.example.Summary".to_string(),
+            1,
+            1,
+            0,
+            summary_payload.len() as u32,
+            *hash.as_bytes(),
+            &summary_payload,
+        )
+        .expect("compact");
+    let after = append(&mut store, b"after");
+
+    // Turns after the boundary that precede the summary stay visible.
+    let ids = |items: Vec<cxdb_server::store::TurnWithMeta>| {
+        items.iter().map(|t| t.record.turn_id).collect::<Vec<_>>()
+    };
+    let compacted = store
+        .get_last_compacted(ctx.context_id, 100, false, false)
+        .expect("compacted read");
+    assert_eq!(
+        ids(compacted),
+        [turns[4].turn_id, summary.turn_id, after.turn_id]
+    );
+    let full = store
+        .get_last(ctx.context_id, 100, false, false)
+        .expect("full read");
+    assert_eq!(full.len(), 7);
+
+    // Pages before a cursor still end at the boundary above them.
+    let page = store
+        .get_last_before(ctx.context_id, after.turn_id, 1, false, false, false)
+        .expect("page");
+    assert_eq!(ids(page), [summary.turn_id]);
+    let page = store
+        .get_last_before(ctx.context_id, summary.turn_id, 10, false, false, false)
+        .expect("page");
+    assert_eq!(ids(page), [turns[4].turn_id]);
+    let page = store
+        .get_last_before(ctx.context_id, turns[4].turn_id, 2, false, true, false)
+        .expect("raw page");
+    assert_eq!(ids(page), [turns[2].turn_id, turns[3].turn_id]);
+
+    let old = store.get_turn(turns[0].turn_id, true).expect("get turn");
+    assert_eq!(old.payload.as_deref(), Some(&[0u8][..]));
+    assert_eq!(old.record.payload_hash, *blake3::hash(&[0]).as_bytes());
+
+    let other = store.create_context(0).expect("other context");
+    let err = store
+        .compact_context(
+            other.context_id,
+            turns[1].turn_id,
+            0,
+            "com.example.Summary".to_string(),
+            1,
+            1,
+            0,
+            summary_payload.len() as u32,
+            *hash.as_bytes(),
+            &summary_payload,
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        cxdb_server::error::StoreError::InvalidInput(_)
+    ));
+}
+
+#[test]
+fn writer_sequences_must_increase() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, writer_id: &str, writer_seq: u64| {
+        let payload = writer_seq.to_le_bytes();
+        let hash = blake3::hash(&payload);
+        store.append_turn_as_writer(
+            TurnWriter {
+                writer_id: writer_id.to_string(),
+                writer_seq,
+            },
+            ctx.context_id,
+            0,
+            "com.example.Test".to_string(),
+            1,
+            1,
+            0,
+            payload.len() as u32,
+            *hash.as_bytes(),
+            &payload,
+        )
+    };
+
+    let first = append(&mut store, "agent-a", 1).expect("append a1").0;
+    append(&mut store, "agent-b", 1).expect("append b1");
+    append(&mut store, "agent-a", 3).expect("append a3");
+
+    match append(&mut store, "agent-a", 3) {
+        Err(StoreError::WriterSequenceConflict {
+            writer_id,
+            last_seq,
+            writer_seq,
+        }) => {
+            assert_eq!(writer_id, "agent-a");
+            assert_eq!(last_seq, 3);
+            assert_eq!(writer_seq, 3);
+        }
+        other => panic!("expected conflict, got {other:?}"),
+    }
+    // A rejected append leaves the context untouched.
+    let items = store
+        .get_last(ctx.context_id, 10, false, false)
+        .expect("get last");
+    assert_eq!(items.len(), 3);
+
+    let writers: Vec<_> = items
+        .iter()
+        .map(|item| {
+            item.writer
+                .as_ref()
+                .map(|w| (w.writer_id.as_str(), w.writer_seq))
+        })
+        .collect();
+    assert_eq!(
+        writers,
+        [
+            Some(("agent-a", 1)),
+            Some(("agent-b", 1)),
+            Some(("agent-a", 3))
+        ]
+    );
+
+    let turn = store.get_turn(first.turn_id, false).expect("get turn");
+    assert_eq!(turn.writer.map(|w| w.writer_seq), Some(1));
+    assert!(matches!(
+        store.writers.check(
+            ctx.context_id,
+            &TurnWriter {
+                writer_id: "agent-a".into(),
+                writer_seq: 2,
+            }
+        ),
+        Err(StoreError::WriterSequenceConflict { last_seq: 3, .. })
+    ));
+}
+
+#[test]
+fn expired_turns_are_skipped_unless_requested() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                ctx.context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    let turns: Vec<_> = (0..4u8).map(|i| append(&mut store, &[i])).collect();
+    store.set_turn_ttl(turns[1].turn_id, 1).expect("ttl");
+    store.set_turn_ttl(turns[2].turn_id, 1).expect("ttl");
+    store
+        .set_turn_ttl(turns[3].turn_id, 3_600_000)
+        .expect("ttl");
+    assert!(matches!(
+        store.set_turn_ttl(turns[0].turn_id, 0),
+        Err(StoreError::InvalidInput(_))
+    ));
+    std::thread::sleep(std::time::Duration::from_millis(5));
+
+    let ids = |items: &[cxdb_server::store::TurnWithMeta]| {
+        items.iter().map(|t| t.record.turn_id).collect::<Vec<_>>()
+    };
+    // Skipped turns do not count towards the limit; ids and depths keep gaps.
+    let live = store
+        .get_last(ctx.context_id, 2, false, false)
+        .expect("get last");
+    assert_eq!(ids(&live), [turns[0].turn_id, turns[3].turn_id]);
+    assert_eq!(live[1].record.depth, 3);
+    assert!(!live[1].expired);
+    assert!(live[1].expires_at_unix_ms.is_some());
+    assert_eq!(live[0].expires_at_unix_ms, None);
+
+    let all = store
+        .get_last_compacted(ctx.context_id, 10, false, true)
+        .expect("get last");
+    let expired: Vec<_> = all.iter().map(|t| t.expired).collect();
+    assert_eq!(expired, [false, true, true, false]);
+
+    let before = store
+        .get_before(ctx.context_id, turns[3].turn_id, 10, false, false)
+        .expect("get before");
+    assert_eq!(ids(&before), [turns[0].turn_id]);
+
+    // Reads by id always return the turn.
+    let turn = store.get_turn(turns[1].turn_id, false).expect("get turn");
+    assert!(turn.expired);
+}
+
+#[test]
+fn search_turns_matches_payload_fields() {
+    use cxdb_server::search::{SearchMatch, TurnSearch};
+    use rmpv::Value;
+
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, type_id: &str, value: Value| {
+        let mut payload = Vec::new();
+        rmpv::encode::write_value(&mut payload, &value).unwrap();
+        let hash = blake3::hash(&payload);
+        store
+            .append_turn(
+                ctx.context_id,
+                0,
+                type_id.to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                &payload,
+            )
+            .expect("append")
+            .0
+    };
+    let item = |kind: &str, text: &str| {
+        Value::Map(vec![
+            (Value::from("1"), Value::from(kind)),
+            (
+                Value::from(3),
+                Value::Array(vec![Value::from(text), Value::Nil]),
+            ),
+        ])
+    };
+    let first = append(&mut store, "com.example.Item", item("user", "hello world"));
+    append(&mut store, "com.example.Item", item("assistant", "hi"));
+    let third = append(&mut store, "com.example.Item", item("user", "world peace"));
+    append(&mut store, "com.example.Other", item("user", "world"));
+
+    let search = |type_id: Option<&str>, path: &[&str], matcher| TurnSearch {
+        type_id: type_id.map(str::to_string),
+        path: path.iter().map(|s| s.to_string()).collect(),
+        matcher,
+    };
+    let ids = |hits: &[(cxdb_server::store::TurnWithMeta, Value)]| {
+        hits.iter()
+            .map(|(t, _)| t.record.turn_id)
+            .collect::<Vec<_>>()
+    };
+
+    let users = search(
+        Some("com.example.Item"),
+        &["1"],
+        SearchMatch::Equals(Value::from("user")),
+    );
+    let hits = store
+        .search_turns(ctx.context_id, &users, 10)
+        .expect("search");
+    assert_eq!(ids(&hits), [first.turn_id, third.turn_id]);
+    assert_eq!(hits[0].0.meta.declared_type_id, "com.example.Item");
+    assert_eq!(hits[0].1, Value::from("user"));
+
+    // The newest matches win the limit; integer keys and array indexes.
+    let world = search(None, &["3", "0"], SearchMatch::Contains("world".into()));
+    let hits = store
+        .search_turns(ctx.context_id, &world, 2)
+        .expect("search");
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].0.record.turn_id, third.turn_id);
+    assert_eq!(hits[1].0.meta.declared_type_id, "com.example.Other");
+    assert_eq!(hits[0].1, Value::from("world peace"));
+
+    let missing = search(None, &["3", "5"], SearchMatch::Contains("world".into()));
+    assert!(store
+        .search_turns(ctx.context_id, &missing, 10)
+        .expect("search")
+        .is_empty());
+}
+
+#[test]
+fn children_span_forks() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, context_id: u64, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    let root = append(&mut store, ctx.context_id, b"root");
+    let main = append(&mut store, ctx.context_id, b"main");
+    let fork = store.fork_context(root.turn_id).expect("fork context");
+    let branch = append(&mut store, fork.context_id, b"branch");
+
+    let children = store
+        .get_children(ctx.context_id, root.turn_id, true)
+        .expect("children");
+    let ids: Vec<u64> = children.iter().map(|c| c.record.turn_id).collect();
+    assert_eq!(ids, [main.turn_id, branch.turn_id]);
+    assert_eq!(children[1].payload.as_deref(), Some(&b"branch"[..]));
+
+    let leaf = store
+        .get_children(ctx.context_id, main.turn_id, false)
+        .expect("leaf");
+    assert!(leaf.is_empty());
+
+    assert!(matches!(
+        store.get_children(ctx.context_id, 999, false),
+        Err(StoreError::TurnNotFound { turn_id: 999, .. })
+    ));
+    assert!(matches!(
+        store.get_children(999, root.turn_id, false),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+}
+
+#[test]
+fn prune_keeps_forks_and_marks_the_new_root() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, context_id: u64, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    // Depths 0..=5; a fork branches off depth 1.
+    let turns: Vec<_> = (0..6u8)
+        .map(|i| append(&mut store, ctx.context_id, &[b'a' + i]))
+        .collect();
+    let fork = store.fork_context(turns[1].turn_id).expect("fork context");
+    let branch = append(&mut store, fork.context_id, b"branch");
+    let log_bytes = store.turn_store.stats().turns_log_bytes;
+
+    assert!(matches!(
+        store.prune_context(ctx.context_id, 6),
+        Err(StoreError::InvalidInput(_))
+    ));
+    // Depth 1 is the fork point, so only depths 2 and 3 go.
+    let result = store.prune_context(ctx.context_id, 4).expect("prune");
+    assert_eq!(result.turns_pruned, 2);
+    assert!(result.bytes_reclaimed > 0);
+    assert!(store.turn_store.stats().turns_log_bytes < log_bytes);
+    let turns_dir = dir.path().join("turns");
+    assert!(turns_dir.join("turns.log").exists() && !turns_dir.join("turns.log.tmp").exists());
+    assert!(!turns_dir.join("turns.meta.tmp").exists());
+
+    let history = store
+        .get_last(ctx.context_id, 10, false, false)
+        .expect("history");
+    let ids: Vec<u64> = history.iter().map(|t| t.record.turn_id).collect();
+    assert_eq!(ids, [turns[4].turn_id, turns[5].turn_id]);
+    assert_eq!(history[0].record.parent_turn_id, turns[3].turn_id);
+    assert_eq!(history[0].record.stored_parent(), 0);
+    let first = store
+        .turn_store
+        .get_first_turn(ctx.context_id)
+        .expect("first");
+    assert_eq!(first.turn_id, turns[4].turn_id);
+
+    assert!(matches!(
+        store.get_turn(turns[3].turn_id, false),
+        Err(StoreError::Pruned { turn_id }) if turn_id == turns[3].turn_id
+    ));
+    assert!(matches!(
+        store.get_turn(999, false),
+        Err(StoreError::TurnNotFound { turn_id: 999, .. })
+    ));
+    let fork_history = store
+        .get_last(fork.context_id, 10, false, false)
+        .expect("fork");
+    let ids: Vec<u64> = fork_history.iter().map(|t| t.record.turn_id).collect();
+    assert_eq!(ids, [turns[0].turn_id, turns[1].turn_id, branch.turn_id]);
+    let children = store
+        .get_children(ctx.context_id, turns[1].turn_id, false)
+        .expect("children");
+    assert_eq!(children.len(), 1);
+
+    // Pruning again at or below the new root is a no-op; appends continue.
+    let again = store.prune_context(ctx.context_id, 3).expect("prune again");
+    assert_eq!(again.turns_pruned, 0);
+    let next = append(&mut store, ctx.context_id, b"next");
+    assert_eq!(next.depth, 6);
+}
+
+#[test]
+fn redaction_erases_payloads_but_keeps_history() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+    let other = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, context_id: u64, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    let first = append(&mut store, ctx.context_id, b"hello");
+    let secret = append(&mut store, ctx.context_id, b"ssn 078-05-1120");
+    let shared = append(&mut store, ctx.context_id, b"shared");
+    let copy = append(&mut store, other.context_id, b"shared");
+
+    assert!(matches!(
+        store.redact_turn(other.context_id, secret.turn_id, "pii"),
+        Err(StoreError::InvalidInput(_))
+    ));
+    let redaction = store
+        .redact_turn(ctx.context_id, secret.turn_id, "pii")
+        .expect("redact");
+    assert_eq!(redaction.reason, "pii");
+    let again = store
+        .redact_turn(ctx.context_id, secret.turn_id, "other reason")
+        .expect("redact again");
+    assert_eq!(again, redaction);
+
+    let history = store
+        .get_last(ctx.context_id, 10, true, false)
+        .expect("history");
+    let ids: Vec<u64> = history.iter().map(|t| t.record.turn_id).collect();
+    assert_eq!(ids, [first.turn_id, secret.turn_id, shared.turn_id]);
+    assert_eq!(history[1].record.payload_hash, secret.payload_hash);
+    assert_eq!(history[1].payload.as_deref(), Some(&b""[..]));
+    assert_eq!(history[1].meta.uncompressed_len, 0);
+    assert_eq!(history[1].redaction.as_ref(), Some(&redaction));
+    assert!(history[0].redaction.is_none());
+    // The content is gone from the blob store, not just hidden.
+    assert!(matches!(
+        store.get_blob(&secret.payload_hash),
+        Err(StoreError::NotFound(_))
+    ));
+    assert!(store
+        .find_turn_by_hash(ctx.context_id, &secret.payload_hash)
+        .expect("find")
+        .is_none());
+
+    // A blob another context still holds stays readable there.
+    store
+        .redact_turn(ctx.context_id, shared.turn_id, "")
+        .expect("redact shared");
+    let copy_read = store.get_turn(copy.turn_id, true).expect("copy");
+    assert_eq!(copy_read.payload.as_deref(), Some(&b"shared"[..]));
+    assert!(copy_read.redaction.is_none());
+}
+
+#[test]
+fn depth_scoped_reads_count_only_turns_in_range() {
+    use cxdb_server::store::GetLastScope;
+
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, context_id: u64, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    // Depths 0..=5; a fork branches off depth 2 and continues at depth 3.
+    let turns: Vec<_> = (0..6u8)
+        .map(|i| append(&mut store, ctx.context_id, &[b'a' + i]))
+        .collect();
+    let fork = store.fork_context(turns[2].turn_id).expect("fork context");
+    let branch = append(&mut store, fork.context_id, b"branch");
+    assert_eq!(branch.depth, 3);
+
+    let depths = |items: Vec<cxdb_server::store::TurnWithMeta>| {
+        items.iter().map(|t| t.record.depth).collect::<Vec<_>>()
+    };
+    let scoped = |depths: std::ops::RangeInclusive<u32>| GetLastScope {
+        depths,
+        ..GetLastScope::default()
+    };
+
+    // The limit applies after filtering: two turns at depth <= 3, not the
+    // two newest turns.
+    let top = store
+        .get_last_scoped(ctx.context_id, 2, false, &scoped(0..=3))
+        .expect("top");
+    assert_eq!(depths(top), [2, 3]);
+    let middle = store
+        .get_last_scoped(ctx.context_id, 10, false, &scoped(1..=4))
+        .expect("middle");
+    assert_eq!(depths(middle), [1, 2, 3, 4]);
+    let forked = store
+        .get_last_scoped(fork.context_id, 10, false, &scoped(2..=3))
+        .expect("forked");
+    assert_eq!(
+        forked.iter().map(|t| t.record.turn_id).collect::<Vec<_>>(),
+        [turns[2].turn_id, branch.turn_id]
+    );
+
+    let page = store
+        .get_last_scoped(
+            ctx.context_id,
+            10,
+            false,
+            &GetLastScope {
+                before_turn_id: turns[3].turn_id,
+                ..scoped(1..=4)
+            },
+        )
+        .expect("page");
+    assert_eq!(depths(page), [1, 2]);
+    let empty = store
+        .get_last_scoped(ctx.context_id, 10, false, &scoped(7..=9))
+        .expect("empty");
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn text_search_finds_words_across_contexts() {
+    use cxdb_server::fulltext::TextSearch;
+    use rmpv::Value;
+
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, context_id: u64, type_id: &str, text: &str| {
+        let mut payload = Vec::new();
+        rmpv::encode::write_value(
+            &mut payload,
+            &Value::Map(vec![(Value::from("text"), Value::from(text))]),
+        )
+        .unwrap();
+        let hash = blake3::hash(&payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                type_id.to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                &payload,
+            )
+            .expect("append")
+            .0
+    };
+    let search = |text: &str, context_id: Option<u64>, before_turn_id: u64| TextSearch {
+        text: text.to_string(),
+        context_id,
+        type_id: None,
+        before_turn_id,
+        limit: 10,
+    };
+    let ids = |hits: &[cxdb_server::store::TextHit]| {
+        hits.iter()
+            .map(|h| h.turn.record.turn_id)
+            .collect::<Vec<_>>()
+    };
+
+    let first = append(
+        &mut store,
+        ctx.context_id,
+        "com.example.Msg",
+        "Flights to San Francisco",
+    );
+    let second = append(
+        &mut store,
+        ctx.context_id,
+        "com.example.Note",
+        "san francisco hotels",
+    );
+    let fork = store.fork_context(first.turn_id).expect("fork context");
+
+    // The first search builds the index; later appends are indexed as they land.
+    let hits = store
+        .search_text(&search("francisco", None, 0))
+        .expect("search");
+    assert_eq!(ids(&hits), [second.turn_id, first.turn_id]);
+    assert_eq!(hits[1].context_id, ctx.context_id);
+    assert_eq!(hits[1].snippet, "Flights to San Francisco");
+    assert_eq!(hits[0].turn.meta.declared_type_id, "com.example.Note");
+
+    let branch = append(
+        &mut store,
+        fork.context_id,
+        "com.example.Msg",
+        "Francisco again",
+    );
+    let hits = store
+        .search_text(&search("FRANCISCO", None, 0))
+        .expect("search");
+    assert_eq!(ids(&hits), [branch.turn_id, second.turn_id, first.turn_id]);
+    assert_eq!(hits[0].context_id, fork.context_id);
+
+    // A scoped search sees the context's history, shared turns included.
+    let hits = store
+        .search_text(&search("francisco", Some(fork.context_id), 0))
+        .expect("search");
+    assert_eq!(ids(&hits), [branch.turn_id, first.turn_id]);
+    assert!(hits.iter().all(|h| h.context_id == fork.context_id));
+
+    // Every word must match; the cursor pages back; types filter.
+    let hits = store
+        .search_text(&search("san hotels", None, 0))
+        .expect("search");
+    assert_eq!(ids(&hits), [second.turn_id]);
+    let hits = store
+        .search_text(&search("francisco", None, second.turn_id))
+        .expect("search");
+    assert_eq!(ids(&hits), [first.turn_id]);
+    let mut typed = search("francisco", None, 0);
+    typed.type_id = Some("com.example.Msg".into());
+    typed.limit = 1;
+    let hits = store.search_text(&typed).expect("search");
+    assert_eq!(ids(&hits), [branch.turn_id]);
+
+    store
+        .redact_turn(ctx.context_id, second.turn_id, "")
+        .expect("redact");
+    let hits = store
+        .search_text(&search("hotels", None, 0))
+        .expect("search");
+    assert!(hits.is_empty());
+
+    assert!(matches!(
+        store.search_text(&search(" !? ", None, 0)),
+        Err(StoreError::InvalidInput(_))
+    ));
+    assert!(matches!(
+        store.search_text(&search("francisco", Some(999), 0)),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+}
+
+#[test]
+fn type_histogram_counts_the_scoped_history() {
+    use cxdb_server::store::GetLastScope;
+
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+
+    let append = |store: &mut Store, context_id: u64, type_id: &str, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                type_id.to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append")
+            .0
+    };
+    let types = ["msg", "tool", "msg", "tool", "tool"];
+    let turns: Vec<_> = types
+        .iter()
+        .enumerate()
+        .map(|(i, type_id)| append(&mut store, ctx.context_id, type_id, &[i as u8]))
+        .collect();
+    let fork = store.fork_context(turns[1].turn_id).expect("fork context");
+    append(&mut store, fork.context_id, "note", b"branch");
+
+    let histogram = |store: &mut Store, context_id: u64, scope: GetLastScope| {
+        store
+            .type_histogram(context_id, &scope)
+            .expect("histogram")
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    let counts = |pairs: &[(&str, u64)]| {
+        pairs
+            .iter()
+            .map(|(t, n)| (t.to_string(), *n))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        histogram(&mut store, ctx.context_id, GetLastScope::default()),
+        counts(&[("msg", 2), ("tool", 3)])
+    );
+    assert_eq!(
+        histogram(&mut store, fork.context_id, GetLastScope::default()),
+        counts(&[("msg", 1), ("note", 1), ("tool", 1)])
+    );
+    let middle = GetLastScope {
+        depths: 1..=3,
+        ..GetLastScope::default()
+    };
+    assert_eq!(
+        histogram(&mut store, ctx.context_id, middle),
+        counts(&[("msg", 1), ("tool", 2)])
+    );
+    let older = GetLastScope {
+        before_turn_id: turns[2].turn_id,
+        ..GetLastScope::default()
+    };
+    assert_eq!(
+        histogram(&mut store, ctx.context_id, older),
+        counts(&[("msg", 1), ("tool", 1)])
+    );
+
+    assert!(matches!(
+        store.type_histogram(999, &GetLastScope::default()),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+}
+
+#[test]
+fn context_stats_sum_the_stored_history() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context");
+    assert_eq!(
+        store.context_stats(ctx.context_id).expect("stats"),
+        ContextStats::default()
+    );
+
+    let mut turns = Vec::new();
+    for (type_id, payload) in [("msg", &b"hello"[..]), ("tool", b"{}"), ("msg", b"bye")] {
+        let hash = blake3::hash(payload);
+        let (turn, _) = store
+            .append_turn(
+                ctx.context_id,
+                0,
+                type_id.to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                payload,
+            )
+            .expect("append");
+        turns.push(turn);
+    }
+    store
+        .redact_turn(ctx.context_id, turns[1].turn_id, "secret")
+        .expect("redact");
+
+    let stats = store.context_stats(ctx.context_id).expect("stats");
+    assert_eq!(stats.turns, 3);
+    assert_eq!(stats.payload_bytes, 8);
+    assert_eq!(
+        stats.bytes_by_type.into_iter().collect::<Vec<_>>(),
+        [("msg".to_string(), 8), ("tool".to_string(), 0)]
+    );
+    assert_eq!(stats.first_created_at_unix_ms, turns[0].created_at_unix_ms);
+    assert_eq!(stats.last_created_at_unix_ms, turns[2].created_at_unix_ms);
+
+    assert!(matches!(
+        store.context_stats(999),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+}
+
+#[test]
+fn append_batch_is_all_or_nothing() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let main = store.create_context(0).expect("create main").context_id;
+    let audit = store.create_context(0).expect("create audit").context_id;
+
+    let entry = |context_id: u64, payload: &[u8], writer: Option<(&str, u64)>| BatchAppend {
+        context_id,
+        parent_turn_id: 0,
+        declared_type_id: "com.example.Test".to_string(),
+        declared_type_version: 1,
+        encoding: 1,
+        compression: 0,
+        uncompressed_len: payload.len() as u32,
+        content_hash: *blake3::hash(payload).as_bytes(),
+        payload_bytes: payload.to_vec(),
+        writer: writer.map(|(writer_id, writer_seq)| TurnWriter {
+            writer_id: writer_id.to_string(),
+            writer_seq,
+        }),
+        fs_root_hash: None,
+        ttl_ms: None,
+        links: Vec::new(),
+    };
+
+    let appended = store
+        .append_batch(vec![
+            entry(main, b"hello", Some(("agent", 1))),
+            entry(audit, b"audit hello", None),
+            entry(main, b"again", Some(("agent", 2))),
+        ])
+        .expect("append batch");
+    let depths: Vec<u32> = appended.iter().map(|(record, _)| record.depth).collect();
+    assert_eq!(depths, [0, 0, 1]);
+
+    // A stale writer sequence in the last entry aborts the whole batch.
+    match store.append_batch(vec![
+        entry(audit, b"audit again", None),
+        entry(main, b"stale", Some(("agent", 2))),
+    ]) {
+        Err(StoreError::TransactionAborted { entry, source }) => {
+            assert_eq!(entry, 1);
+            assert!(
+                matches!(
+                    *source,
+                    StoreError::WriterSequenceConflict { last_seq: 2, .. }
+                ),
+                "{source:?}"
+            );
+        }
+        other => panic!("expected abort, got {other:?}"),
+    }
+    // So does a sequence an earlier entry of the same batch claimed.
+    match store.append_batch(vec![
+        entry(main, b"third", Some(("agent", 3))),
+        entry(main, b"fourth", Some(("agent", 3))),
+    ]) {
+        Err(StoreError::TransactionAborted { entry: 1, .. }) => {}
+        other => panic!("expected abort, got {other:?}"),
+    }
+    match store.append_batch(vec![entry(main, b"x", None), entry(999, b"y", None)]) {
+        Err(StoreError::TransactionAborted { entry: 1, source }) => {
+            assert!(
+                matches!(*source, StoreError::ContextNotFound { context_id: 999 }),
+                "{source:?}"
+            );
+        }
+        other => panic!("expected abort, got {other:?}"),
+    }
+
+    let main_turns = store.get_last(main, 10, false, false).expect("get main");
+    let audit_turns = store.get_last(audit, 10, false, false).expect("get audit");
+    assert_eq!(main_turns.len(), 2);
+    assert_eq!(audit_turns.len(), 1);
+}
+
+#[test]
+fn links_stay_within_history_across_forks() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let ctx = store.create_context(0).expect("create context").context_id;
+
+    let entry = |context_id: u64, payload: &[u8], links: Vec<TurnLink>| BatchAppend {
+        context_id,
+        parent_turn_id: 0,
+        declared_type_id: "com.example.Test".to_string(),
+        declared_type_version: 1,
+        encoding: 1,
+        compression: 0,
+        uncompressed_len: payload.len() as u32,
+        content_hash: *blake3::hash(payload).as_bytes(),
+        payload_bytes: payload.to_vec(),
+        writer: None,
+        fs_root_hash: None,
+        ttl_ms: None,
+        links,
+    };
+    let link = |target_turn_id: u64, kind: LinkKind| TurnLink {
+        target_turn_id,
+        kind,
+    };
+    let append = |store: &mut Store, context_id: u64, payload: &[u8], links| {
+        store
+            .append_batch(vec![entry(context_id, payload, links)])
+            .expect("append")
+            .remove(0)
+            .0
+    };
+
+    let call = append(&mut store, ctx, b"tool call", vec![]);
+    let result = append(
+        &mut store,
+        ctx,
+        b"tool result",
+        vec![link(call.turn_id, LinkKind::Causes)],
+    );
+    let fork = store.fork_context(result.turn_id).expect("fork").context_id;
+    let main_only = append(&mut store, ctx, b"main only", vec![]);
+
+    // A fork links to history it shares with its base context...
+    let reply = append(
+        &mut store,
+        fork,
+        b"reply",
+        vec![
+            link(call.turn_id, LinkKind::RepliesTo),
+            link(result.turn_id, LinkKind::Custom("summarizes".into())),
+        ],
+    );
+    // ...but not to turns appended there after the fork, nor to turns
+    // that do not exist.
+    for target in [main_only.turn_id, 999] {
+        match store.append_batch(vec![entry(
+            fork,
+            b"bad",
+            vec![link(target, LinkKind::RepliesTo)],
+        )]) {
+            Err(StoreError::TransactionAborted { source, .. }) => {
+                assert!(
+                    matches!(*source, StoreError::LinkTargetNotFound { turn_id, .. } if turn_id == target),
+                    "{source:?}"
+                );
+            }
+            other => panic!("expected abort, got {other:?}"),
+        }
+    }
+    assert!(store
+        .check_links(ctx, 0, &[link(reply.turn_id, LinkKind::Causes)])
+        .is_err());
+
+    let item = store.get_turn(reply.turn_id, false).expect("get turn");
+    assert_eq!(
+        item.links[1],
+        link(result.turn_id, LinkKind::Custom("summarizes".into()))
+    );
+
+    let ids = |items: Vec<cxdb_server::store::TurnWithMeta>| -> Vec<u64> {
+        items.iter().map(|item| item.record.turn_id).collect()
+    };
+    let outgoing = store
+        .get_linked(fork, reply.turn_id, LinkDirection::Outgoing, true)
+        .expect("outgoing");
+    assert_eq!(outgoing[0].payload.as_deref(), Some(&b"tool call"[..]));
+    assert_eq!(ids(outgoing), [call.turn_id, result.turn_id]);
+    // Incoming links are scoped to the context read: the fork's reply is
+    // not on the base context's history.
+    let incoming = store
+        .get_linked(fork, call.turn_id, LinkDirection::Incoming, false)
+        .expect("incoming in fork");
+    assert_eq!(ids(incoming), [result.turn_id, reply.turn_id]);
+    let incoming = store
+        .get_linked(ctx, call.turn_id, LinkDirection::Incoming, false)
+        .expect("incoming in base");
+    assert_eq!(ids(incoming), [result.turn_id]);
+    assert!(matches!(
+        store.get_linked(ctx, reply.turn_id, LinkDirection::Outgoing, false),
+        Err(StoreError::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn archived_contexts_refuse_reads_and_appends_until_restored() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let append = |store: &mut Store, context_id: u64, payload: &[u8]| {
+        let hash = blake3::hash(payload);
+        store.append_turn(
+            context_id,
+            0,
+            "com.example.Test".to_string(),
+            1,
+            1,
+            0,
+            payload.len() as u32,
+            *hash.as_bytes(),
+            payload,
+        )
+    };
+    let ctx = store.create_context(0).expect("create context").context_id;
+    let other = store.create_context(0).expect("create context").context_id;
+    append(&mut store, ctx, b"first").expect("append");
+    append(&mut store, other, b"other").expect("append");
+
+    let head = store.archive_context(ctx).expect("archive");
+    assert_eq!((head.head_depth, head.flags), (0, 1));
+    // Archiving twice is a no-op.
+    store.archive_context(ctx).expect("archive again");
+    let archived =
+        |err| matches!(err, StoreError::ContextArchived { context_id } if context_id == ctx);
+    assert!(archived(store.get_last(ctx, 10, true, false).unwrap_err()));
+    assert!(archived(append(&mut store, ctx, b"second").unwrap_err()));
+    assert!(archived(store.context_stats(ctx).unwrap_err()));
+    // The head stays readable, and other contexts are unaffected.
+    assert_eq!(store.get_head(ctx).expect("head").head_depth, 0);
+    append(&mut store, other, b"more").expect("append to other");
+
+    let listed = |store: &Store, archived| -> Vec<u64> {
+        store
+            .list_contexts(10, archived)
+            .iter()
+            .map(|head| head.context_id)
+            .collect()
+    };
+    assert_eq!(listed(&store, Some(true)), [ctx]);
+    assert_eq!(listed(&store, Some(false)), [other]);
+    assert_eq!(listed(&store, None).len(), 2);
+    assert!(matches!(
+        store.archive_context(999),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+
+    let head = store.restore_context(ctx).expect("restore");
+    assert_eq!(head.flags, 0);
+    append(&mut store, ctx, b"second").expect("append after restore");
+    let turns = store.get_last(ctx, 10, true, false).expect("get last");
+    assert_eq!(turns.len(), 2);
+    assert_eq!(listed(&store, Some(true)), Vec::<u64>::new());
+}
+
+#[test]
+fn delete_contexts_matches_every_criterion() {
+    use rmpv::Value;
+
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let append_tagged = |store: &mut Store, context_id: u64, tag: &str| {
+        let value = Value::Map(vec![(
+            Value::from(30),
+            Value::Map(vec![(Value::from(1), Value::from(tag))]),
+        )]);
+        let mut payload = Vec::new();
+        rmpv::encode::write_value(&mut payload, &value).unwrap();
+        let hash = blake3::hash(&payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                &payload,
+            )
+            .expect("append");
+    };
+    let live = |store: &Store| -> Vec<u64> {
+        let mut ids: Vec<u64> = store
+            .list_contexts(10, None)
+            .iter()
+            .map(|head| head.context_id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    };
+
+    let a = store.create_context(0).expect("create").context_id;
+    let b = store.create_context(0).expect("create").context_id;
+    let c = store.create_context(0).expect("create").context_id;
+    append_tagged(&mut store, a, "batch");
+    append_tagged(&mut store, b, "batch");
+    append_tagged(&mut store, c, "keep");
+    store
+        .aliases
+        .insert("nightly", a)
+        .expect("alias the first context");
+
+    // An empty filter never deletes everything.
+    assert!(matches!(
+        store.delete_contexts(&DeleteFilter::default()),
+        Err(StoreError::InvalidInput(_))
+    ));
+    let unknown_field = DeleteFilter {
+        metadata: vec![("colour".into(), "red".into())],
+        ..Default::default()
+    };
+    assert!(matches!(
+        store.delete_contexts(&unknown_field),
+        Err(StoreError::InvalidInput(_))
+    ));
+    assert_eq!(live(&store), [a, b, c]);
+
+    // Criteria are AND-ed: of the listed ids only the tagged one goes.
+    let by_ids_and_tag = DeleteFilter {
+        context_ids: vec![a, c, 999],
+        metadata: vec![("tag".into(), "batch".into())],
+        ..Default::default()
+    };
+    assert_eq!(store.delete_contexts(&by_ids_and_tag).expect("delete"), [a]);
+    assert_eq!(live(&store), [b, c]);
+    assert!(
+        matches!(store.get_head(a), Err(StoreError::ContextNotFound { context_id }) if context_id == a)
+    );
+    assert_eq!(store.resolve_alias("nightly"), None);
+
+    // Appending to a context moves its head time, not its creation time.
+    let cutoff = store.get_head(c).expect("head").created_at_unix_ms + 1;
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    append_tagged(&mut store, b, "later");
+    let d = store.create_context(0).expect("create").context_id;
+    let before = DeleteFilter {
+        created_before_unix_ms: Some(cutoff),
+        ..Default::default()
+    };
+    let mut deleted = store.delete_contexts(&before).expect("delete");
+    deleted.sort_unstable();
+    assert_eq!(deleted, [b, c]);
+    assert_eq!(live(&store), [d]);
+    // Deleted ids are never handed out again.
+    assert!(store.create_context(0).expect("create").context_id > d);
+}
+
+#[test]
+fn create_context_with_turn_is_all_or_none() {
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let entry = |payload: &[u8], content_hash: [u8; 32]| BatchAppend {
+        context_id: 0,
+        parent_turn_id: 0,
+        declared_type_id: "com.example.Test".to_string(),
+        declared_type_version: 1,
+        encoding: 1,
+        compression: 0,
+        uncompressed_len: payload.len() as u32,
+        content_hash,
+        payload_bytes: payload.to_vec(),
+        writer: None,
+        fs_root_hash: None,
+        ttl_ms: None,
+        links: Vec::new(),
+    };
+
+    let (head, record, _) = store
+        .create_context_with_turn(0, entry(b"system", *blake3::hash(b"system").as_bytes()))
+        .expect("create with turn");
+    assert_eq!(head.head_turn_id, record.turn_id);
+    assert_eq!(record.depth, 0);
+    let stored = store.get_head(head.context_id).expect("head");
+    assert_eq!(stored.head_turn_id, record.turn_id);
+
+    // The base turn is kept: the first turn of the new context follows it.
+    let (forked, child, _) = store
+        .create_context_with_turn(
+            record.turn_id,
+            entry(b"user", *blake3::hash(b"user").as_bytes()),
+        )
+        .expect("create from base");
+    assert_ne!(forked.context_id, head.context_id);
+    assert_eq!(child.parent_turn_id, record.turn_id);
+    assert_eq!(child.depth, 1);
+
+    // A turn that fails verification leaves no context behind.
+    let before = store.list_contexts(10, None).len();
+    assert!(matches!(
+        store.create_context_with_turn(0, entry(b"bad", [0; 32])),
+        Err(StoreError::InvalidInput(_))
+    ));
+    assert_eq!(store.list_contexts(10, None).len(), before);
+}
+
+#[test]
+fn parents_come_from_creation_or_first_turn_provenance() {
+    use rmpv::Value;
+
+    let dir = tempdir().expect("tempdir");
+    let mut store = Store::open(dir.path()).expect("open store");
+    let append_provenance_parent = |store: &mut Store, context_id: u64, parent: u64| {
+        let value = Value::Map(vec![(
+            Value::from(30),
+            Value::Map(vec![(
+                Value::from(10),
+                Value::Map(vec![(Value::from(1), Value::from(parent))]),
+            )]),
+        )]);
+        let mut payload = Vec::new();
+        rmpv::encode::write_value(&mut payload, &value).unwrap();
+        let hash = blake3::hash(&payload);
+        store
+            .append_turn(
+                context_id,
+                0,
+                "com.example.Test".to_string(),
+                1,
+                1,
+                0,
+                payload.len() as u32,
+                *hash.as_bytes(),
+                &payload,
+            )
+            .expect("append");
+    };
+    let ids = |heads: Vec<cxdb_server::turn_store::ContextHead>| -> Vec<u64> {
+        heads.iter().map(|head| head.context_id).collect()
+    };
+
+    let root = store.create_context(0).expect("create").context_id;
+    let a = store
+        .create_child_context(0, root)
+        .expect("create child")
+        .context_id;
+    let b = store.create_context(0).expect("create").context_id;
+    append_provenance_parent(&mut store, b, root);
+    // A parent given at creation wins over the provenance one.
+    let c = store
+        .create_child_context(0, a)
+        .expect("create grandchild")
+        .context_id;
+    append_provenance_parent(&mut store, c, root);
+
+    assert_eq!(store.context_parent(root).expect("parent"), None);
+    assert_eq!(store.context_parent(a).expect("parent"), Some(root));
+    assert_eq!(store.context_parent(b).expect("parent"), Some(root));
+    assert_eq!(store.context_parent(c).expect("parent"), Some(a));
+    assert_eq!(ids(store.list_children(root).expect("children")), [a, b]);
+    assert_eq!(ids(store.list_children(a).expect("children")), [c]);
+    assert!(store.list_children(c).expect("children").is_empty());
+    assert!(matches!(
+        store.create_child_context(0, 999),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+    assert!(matches!(
+        store.context_parent(999),
+        Err(StoreError::ContextNotFound { context_id: 999 })
+    ));
+
+    // Deleting a parent orphans nothing.
+    store
+        .delete_contexts(&DeleteFilter {
+            context_ids: vec![root],
+            ..Default::default()
+        })
+        .expect("delete");
+    assert_eq!(store.context_parent(a).expect("parent"), Some(root));
+    assert_eq!(ids(store.list_children(root).expect("children")), [a, b]);
+}